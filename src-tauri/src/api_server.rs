@@ -0,0 +1,440 @@
+use crate::metadata_store::ItemRecord;
+use crate::protocol;
+use crate::search_engine::SearchQuery;
+use crate::secrets;
+use crate::upload_server::{json_response, lan_address, query_param, sanitize_file_name};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub const DEFAULT_PORT: u16 = 47615;
+const DEFAULT_LIMIT: usize = 50;
+const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+const MAX_UPLOAD_BYTES: u64 = 50 * 1024 * 1024;
+const MAX_JSON_BYTES: u64 = 1024 * 1024;
+const KEYCHAIN_TOKEN: &str = "api-token";
+// 共有リンクの既定の有効期間と上限
+pub const DEFAULT_SHARE_TTL_SECS: u64 = 24 * 60 * 60;
+const MAX_SHARE_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+const SHARE_PAGE: &str = include_str!("share_page.html");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiServerSettings {
+    // 起動時に自動で開始する
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    // リクエストの Authorization: Bearer に指定するトークン（OS のキーチェーンに置く）
+    #[serde(default)]
+    pub token: String,
+    // LAN 内の端末から共有リンクを開けるようにする（0.0.0.0 で待ち受けるが、/v1 は引き続きこの PC からのみ）
+    #[serde(default)]
+    pub share_links: bool,
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+impl Default for ApiServerSettings {
+    fn default() -> Self {
+        ApiServerSettings {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: String::new(),
+            share_links: false,
+        }
+    }
+}
+
+impl ApiServerSettings {
+    pub fn load(path: &Path) -> Self {
+        let mut settings: Self = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        secrets::fill(path, KEYCHAIN_TOKEN, &mut settings.token);
+        settings
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut settings = self.clone();
+        secrets::stash(path, KEYCHAIN_TOKEN, &mut settings.token);
+        fs::write(path, serde_json::to_string_pretty(&settings)?)?;
+        Ok(())
+    }
+
+    // トークンがまだなければ作る
+    pub fn ensure_token(&mut self) {
+        if self.token.is_empty() {
+            self.regenerate_token();
+        }
+    }
+
+    pub fn regenerate_token(&mut self) {
+        self.token = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiServerInfo {
+    pub url: String,
+    pub port: u16,
+    // 共有リンクの起点（共有リンクを無効にしているときは None）
+    pub share_url: Option<String>,
+}
+
+// 1件のアイテムだけを期限付きで見せるリンク
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLink {
+    pub item_id: String,
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiServerStatus {
+    // 停止中は None
+    pub server: Option<ApiServerInfo>,
+    pub settings: ApiServerSettings,
+}
+
+// PATCH で変更できる項目（省略した項目はそのまま）
+#[derive(Debug, Default, Deserialize)]
+pub struct ItemPatch {
+    pub memo: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub location_name: Option<String>,
+}
+
+impl ItemPatch {
+    pub fn apply(self, item: &mut ItemRecord) {
+        if let Some(memo) = self.memo {
+            item.memo = memo;
+        }
+        if let Some(tags) = self.tags {
+            item.tags = tags;
+        }
+        if let Some(name) = self.location_name {
+            item.location_name = Some(name).filter(|n| !n.trim().is_empty());
+        }
+    }
+}
+
+// API から呼ぶアプリ側の処理（非公開アイテムは返さない・変更しない）
+pub trait ApiBackend: Send + Sync {
+    fn is_locked(&self) -> bool;
+    fn search(&self, query: SearchQuery) -> Result<Vec<ItemRecord>>;
+    fn get_item(&self, id: &str) -> Result<Option<ItemRecord>>;
+    fn import(&self, path: &Path) -> Result<ItemRecord>;
+    fn update_item(&self, id: &str, patch: ItemPatch) -> Result<Option<ItemRecord>>;
+    fn delete_item(&self, id: &str) -> Result<bool>;
+    fn thumbnail(&self, source: &Path, size: u32) -> Result<Vec<u8>>;
+}
+
+// スクリプトやランチャーから使うための REST API（127.0.0.1 のみで待ち受け、明示的に有効にしたときだけ動く）
+pub struct ApiServer {
+    server: Arc<Server>,
+    info: ApiServerInfo,
+    token: String,
+}
+
+impl ApiServer {
+    pub fn start(
+        port: u16,
+        token: String,
+        share_links: bool,
+        staging_dir: PathBuf,
+        backend: Arc<dyn ApiBackend>,
+    ) -> Result<Self> {
+        fs::create_dir_all(&staging_dir)?;
+        let host = if share_links { "0.0.0.0" } else { "127.0.0.1" };
+        let server =
+            Arc::new(Server::http((host, port)).map_err(|e| anyhow::anyhow!("Failed to start API server: {}", e))?);
+        let port = server
+            .server_addr()
+            .to_ip()
+            .context("API server is not bound to a TCP address")?
+            .port();
+
+        let share_url = if share_links {
+            Some(format!("http://{}:{}", lan_address()?, port))
+        } else {
+            None
+        };
+
+        let worker = server.clone();
+        let worker_token = token.clone();
+        std::thread::spawn(move || {
+            for request in worker.incoming_requests() {
+                let staging_dir = staging_dir.clone();
+                let backend = backend.clone();
+                let token = worker_token.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_request(request, &token, &staging_dir, backend.as_ref()) {
+                        log::warn!("API request failed: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(ApiServer {
+            server,
+            info: ApiServerInfo {
+                url: format!("http://127.0.0.1:{}/v1", port),
+                port,
+                share_url,
+            },
+            token,
+        })
+    }
+
+    pub fn info(&self) -> &ApiServerInfo {
+        &self.info
+    }
+
+    // トークンで署名した期限付きのリンクを作る（トークンを作り直すとそれまでのリンクはすべて使えなくなる）
+    pub fn share_link(&self, item_id: &str, ttl_secs: u64) -> Result<ShareLink> {
+        let Some(share_url) = &self.info.share_url else {
+            bail!("Share links are not enabled");
+        };
+        if ttl_secs == 0 || ttl_secs > MAX_SHARE_TTL_SECS {
+            bail!(
+                "Share link lifetime must be between 1 second and {} days",
+                MAX_SHARE_TTL_SECS / 86400
+            );
+        }
+        let expires_at = Utc::now() + Duration::seconds(ttl_secs as i64);
+        let expires = expires_at.timestamp();
+        Ok(ShareLink {
+            item_id: item_id.to_string(),
+            url: format!(
+                "{}/share/{}?e={}&s={}",
+                share_url,
+                item_id,
+                expires,
+                hex::encode(share_signature(&self.token, item_id, expires).finalize().into_bytes())
+            ),
+            expires_at,
+        })
+    }
+
+    pub fn stop(&self) {
+        self.server.unblock();
+    }
+}
+
+fn error_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    json_response(status, serde_json::json!({ "error": message }))
+}
+
+// トークンは一致するまでの時間が変わらないように比べる
+fn is_authorized(request: &Request, token: &str) -> bool {
+    let expected = format!("Bearer {}", token);
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .is_some_and(|h| bool::from(h.value.as_str().as_bytes().ct_eq(expected.as_bytes())))
+}
+
+fn is_local(request: &Request) -> bool {
+    request.remote_addr().is_some_and(|addr| addr.ip().is_loopback())
+}
+
+fn share_signature(token: &str, item_id: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(item_id.as_bytes());
+    mac.update(b".");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+// 署名が正しく期限内か（署名は定数時間で比べる）
+fn verify_share(token: &str, item_id: &str, url: &str) -> std::result::Result<(), (u16, &'static str)> {
+    let expires = query_param(url, "e").and_then(|e| e.parse::<i64>().ok());
+    let signature = query_param(url, "s").and_then(|s| hex::decode(s).ok());
+    let (Some(expires), Some(signature)) = (expires, signature) else {
+        return Err((403, "Invalid share link"));
+    };
+    if share_signature(token, item_id, expires)
+        .verify_slice(&signature)
+        .is_err()
+    {
+        return Err((403, "Invalid share link"));
+    }
+    if Utc::now().timestamp() >= expires {
+        return Err((410, "Share link has expired"));
+    }
+    Ok(())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn read_body(request: &mut Request, max_bytes: u64) -> Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    request.as_reader().take(max_bytes + 1).read_to_end(&mut data)?;
+    Ok((data.len() as u64 <= max_bytes).then_some(data))
+}
+
+fn search_query(url: &str) -> SearchQuery {
+    let tags: Vec<String> = query_param(url, "tags")
+        .map(|tags| tags.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect())
+        .unwrap_or_default();
+    SearchQuery {
+        query: query_param(url, "q").unwrap_or_default(),
+        tags: (!tags.is_empty()).then_some(tags),
+        limit: Some(
+            query_param(url, "limit")
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(DEFAULT_LIMIT),
+        ),
+        ..Default::default()
+    }
+}
+
+fn handle_request(mut request: Request, token: &str, staging_dir: &Path, backend: &dyn ApiBackend) -> Result<()> {
+    let response = if request.url().starts_with("/share/") {
+        share_route(&request, token, backend).unwrap_or_else(|e| error_response(500, &e.to_string()))
+    } else if !is_local(&request) {
+        // 共有リンク以外は LAN から使わせない
+        error_response(403, "Forbidden")
+    } else if !is_authorized(&request, token) {
+        error_response(401, "Unauthorized")
+    } else if backend.is_locked() {
+        // ロック中は何も返さない
+        error_response(423, "App is locked")
+    } else {
+        route(&mut request, staging_dir, backend).unwrap_or_else(|e| error_response(500, &e.to_string()))
+    };
+    Ok(request.respond(response)?)
+}
+
+// 共有リンク: GET /share/{id} で書類を表示するページ、GET /share/{id}/image で画像そのもの（トークンは不要）
+fn share_route(request: &Request, token: &str, backend: &dyn ApiBackend) -> Result<Response<Cursor<Vec<u8>>>> {
+    if request.method() != &Method::Get {
+        return Ok(error_response(405, "Method Not Allowed"));
+    }
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or("/").trim_end_matches('/');
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    let (id, image) = match segments.as_slice() {
+        ["share", id] => (*id, false),
+        ["share", id, "image"] => (*id, true),
+        _ => return Ok(error_response(404, "Not Found")),
+    };
+    if let Err((status, message)) = verify_share(token, id, &url) {
+        return Ok(error_response(status, message));
+    }
+    if backend.is_locked() {
+        return Ok(error_response(423, "App is locked"));
+    }
+    // 非公開にした・ゴミ箱に移したアイテムはリンクの期限内でも見せない
+    let Some(item) = backend.get_item(id)? else {
+        return Ok(error_response(404, "Item not found"));
+    };
+    let Some(source) = item.image_path.as_deref().map(PathBuf::from) else {
+        return Ok(error_response(404, "Image not found"));
+    };
+
+    let response = if image {
+        Response::from_data(fs::read(&source)?)
+            .with_header(Header::from_bytes("Content-Type", protocol::content_type(&source)).unwrap())
+    } else {
+        let query = url.split_once('?').map_or("", |(_, query)| query);
+        let page = SHARE_PAGE
+            .replace(
+                "{{TITLE}}",
+                &escape_html(item.title.as_deref().unwrap_or("Snap Organizer")),
+            )
+            .replace("{{IMAGE_URL}}", &escape_html(&format!("/share/{}/image?{}", id, query)));
+        Response::from_string(page).with_header(Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap())
+    };
+    // 期限が切れたあとに端末のキャッシュから見えないようにする
+    Ok(response.with_header(Header::from_bytes("Cache-Control", "no-store").unwrap()))
+}
+
+fn route(request: &mut Request, staging_dir: &Path, backend: &dyn ApiBackend) -> Result<Response<Cursor<Vec<u8>>>> {
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or("/").trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    let method = request.method().clone();
+
+    let response = match (&method, segments.as_slice()) {
+        (Method::Get, ["v1", "items"]) => json_response(200, serde_json::to_value(backend.search(search_query(&url))?)?),
+        (Method::Post, ["v1", "items"]) => {
+            // 本文に画像そのものを送る（?name= に元のファイル名）
+            let Some(data) = read_body(request, MAX_UPLOAD_BYTES)? else {
+                return Ok(error_response(413, "File too large"));
+            };
+            let name = sanitize_file_name(&query_param(&url, "name").unwrap_or_default());
+            let staged = staging_dir.join(format!("{}-{}", uuid::Uuid::new_v4().simple(), name));
+            fs::write(&staged, &data)?;
+            let result = backend.import(&staged);
+            let _ = fs::remove_file(&staged);
+            match result {
+                Ok(item) => json_response(201, serde_json::to_value(item)?),
+                Err(e) => error_response(422, &e.to_string()),
+            }
+        }
+        (Method::Get, ["v1", "items", id]) => match backend.get_item(id)? {
+            Some(item) => json_response(200, serde_json::to_value(item)?),
+            None => error_response(404, "Item not found"),
+        },
+        (Method::Patch, ["v1", "items", id]) => {
+            let Some(data) = read_body(request, MAX_JSON_BYTES)? else {
+                return Ok(error_response(413, "Request too large"));
+            };
+            match serde_json::from_slice::<ItemPatch>(&data) {
+                Ok(patch) => match backend.update_item(id, patch)? {
+                    Some(item) => json_response(200, serde_json::to_value(item)?),
+                    None => error_response(404, "Item not found"),
+                },
+                Err(e) => error_response(400, &e.to_string()),
+            }
+        }
+        (Method::Delete, ["v1", "items", id]) => match backend.delete_item(id)? {
+            true => json_response(200, serde_json::json!({ "deleted": true })),
+            false => error_response(404, "Item not found"),
+        },
+        (Method::Get, ["v1", "items", id, kind @ ("image" | "thumbnail")]) => {
+            let source = backend.get_item(id)?.and_then(|item| item.image_path).map(PathBuf::from);
+            let Some(source) = source else {
+                return Ok(error_response(404, "Image not found"));
+            };
+            let (data, mime) = if *kind == "image" {
+                (fs::read(&source)?, protocol::content_type(&source))
+            } else {
+                let size = query_param(&url, "size")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+                (backend.thumbnail(&source, size)?, "image/jpeg")
+            };
+            Response::from_data(data).with_header(Header::from_bytes("Content-Type", mime).unwrap())
+        }
+        _ => error_response(404, "Not Found"),
+    };
+    Ok(response)
+}
@@ -0,0 +1,104 @@
+use age::secrecy::SecretString;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// パスコード自体は保存せず、パスコードで暗号化した値を復号できるかで確かめる
+#[derive(Default, Serialize, Deserialize)]
+struct AppLockFile {
+    verifier: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppLockStatus {
+    pub enabled: bool,
+    pub locked: bool,
+}
+
+fn secret(passcode: &str) -> Result<SecretString> {
+    if passcode.is_empty() {
+        bail!("Passcode must not be empty");
+    }
+    Ok(SecretString::from(passcode.to_string()))
+}
+
+// アプリのロック（ロック中は画像を返すプロトコルハンドラが応答しない）
+pub struct AppLock {
+    path: PathBuf,
+    locked: AtomicBool,
+}
+
+impl AppLock {
+    // パスコードが設定されていれば起動時はロックされた状態
+    pub fn new(path: PathBuf) -> Self {
+        let locked = Self::load_file(&path).verifier.is_some();
+        AppLock {
+            path,
+            locked: AtomicBool::new(locked),
+        }
+    }
+
+    fn load_file(path: &PathBuf) -> AppLockFile {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn verify(&self, passcode: &str) -> Result<()> {
+        let verifier = Self::load_file(&self.path).verifier.context("App lock is not enabled")?;
+        let encrypted = STANDARD.decode(verifier).context("Invalid app lock file")?;
+        let identity = age::scrypt::Identity::new(secret(passcode)?);
+        age::decrypt(&identity, &encrypted).map_err(|_| anyhow!("Incorrect passcode"))?;
+        Ok(())
+    }
+
+    pub fn status(&self) -> AppLockStatus {
+        AppLockStatus {
+            enabled: Self::load_file(&self.path).verifier.is_some(),
+            locked: self.is_locked(),
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    pub fn unlock(&self, passcode: &str) -> Result<()> {
+        self.verify(passcode)?;
+        self.locked.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn lock(&self) -> Result<()> {
+        if Self::load_file(&self.path).verifier.is_none() {
+            bail!("App lock is not enabled");
+        }
+        self.locked.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    // パスコードを設定・変更する（None で無効にする）。設定済みなら現在のパスコードが必要
+    pub fn set_passcode(&self, current: Option<&str>, passcode: Option<&str>) -> Result<()> {
+        if Self::load_file(&self.path).verifier.is_some() {
+            self.verify(current.context("The current passcode is required")?)?;
+        }
+        let verifier = match passcode {
+            Some(passcode) => {
+                let recipient = age::scrypt::Recipient::new(secret(passcode)?);
+                let token = uuid::Uuid::new_v4().to_string();
+                Some(STANDARD.encode(age::encrypt(&recipient, token.as_bytes())?))
+            }
+            None => None,
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&AppLockFile { verifier })?)?;
+        self.locked.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+}
@@ -0,0 +1,575 @@
+use crate::jobs::ProgressReporter;
+use crate::metadata_store::{GroupRecord, ItemFilter, ItemRecord, MetadataStore};
+use crate::paths::LibraryPaths;
+use crate::private_items;
+use age::secrecy::SecretString;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+pub const MANIFEST_NAME: &str = "manifest.json";
+// age 形式のヘッダ（暗号化されたバックアップの判定に使う）
+const AGE_MAGIC: &[u8] = b"age-encryption.org/v1";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub path: String,
+    pub size: u64,
+    pub blake3: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: DateTime<Utc>,
+    pub item_count: usize,
+    pub group_count: usize,
+    pub entries: Vec<BackupEntry>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct BackupOptions {
+    pub include_originals: bool,
+    pub include_thumbnails: bool,
+    // 指定するとアーカイブ全体をパスフレーズで暗号化する
+    pub passphrase: Option<String>,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        BackupOptions {
+            include_originals: true,
+            include_thumbnails: false,
+            passphrase: None,
+        }
+    }
+}
+
+fn secret(passphrase: &str) -> Result<SecretString> {
+    if passphrase.is_empty() {
+        bail!("Passphrase must not be empty");
+    }
+    Ok(SecretString::from(passphrase.to_string()))
+}
+
+pub fn is_encrypted(path: &Path) -> Result<bool> {
+    let mut header = vec![0u8; AGE_MAGIC.len()];
+    let mut file = File::open(path).with_context(|| format!("Failed to open backup: {}", path.display()))?;
+    let read = file.read(&mut header)?;
+    Ok(read == header.len() && header == AGE_MAGIC)
+}
+
+pub struct BackupSource<'a> {
+    pub paths: &'a LibraryPaths,
+    pub store: &'a Mutex<Option<MetadataStore>>,
+}
+
+// アーカイブ内のパスとディスク上のファイル
+struct PendingFile {
+    archive_path: String,
+    source: PathBuf,
+    compress: bool,
+}
+
+fn list_dir(dir: &Path, prefix: &str, compress: bool) -> Result<Vec<PendingFile>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(PendingFile {
+                archive_path: format!("{}/{}", prefix, entry.file_name().to_string_lossy()),
+                source: entry.path(),
+                compress,
+            });
+        }
+    }
+    Ok(files)
+}
+
+// 書き込みながら blake3 チェックサムを計算する
+fn copy_with_hash<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> Result<(u64, String)> {
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        size += n as u64;
+    }
+    Ok((size, hasher.finalize().to_hex().to_string()))
+}
+
+// 元画像・サムネイル・メタデータ（JSON と SQLite）・設定を1つの zip にまとめる
+pub fn export_backup(
+    source: &BackupSource,
+    dest: &Path,
+    options: &BackupOptions,
+    reporter: &dyn ProgressReporter,
+) -> Result<BackupManifest> {
+    let work_dir = create_work_dir(source.paths, "backup")?;
+    let result = write_archive(source, dest, options, reporter, &work_dir);
+    let _ = fs::remove_dir_all(&work_dir);
+    if result.is_err() {
+        let _ = fs::remove_file(dest.with_extension("partial"));
+    }
+    result
+}
+
+fn write_archive(
+    source: &BackupSource,
+    dest: &Path,
+    options: &BackupOptions,
+    reporter: &dyn ProgressReporter,
+    work_dir: &Path,
+) -> Result<BackupManifest> {
+    // メタデータはロック中にまとめてスナップショットを取る
+    let (item_count, group_count) = {
+        let store = source.store.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+
+        let items = store.list_items(&ItemFilter::default())?;
+        let groups = store.list_groups()?;
+        fs::write(work_dir.join("items.json"), serde_json::to_vec_pretty(&items)?)?;
+        fs::write(work_dir.join("groups.json"), serde_json::to_vec_pretty(&groups)?)?;
+        store.snapshot_to(&work_dir.join("library.db"))?;
+        (items.len(), groups.len())
+    };
+
+    let mut files = vec![
+        PendingFile { archive_path: "metadata/items.json".into(), source: work_dir.join("items.json"), compress: true },
+        PendingFile { archive_path: "metadata/groups.json".into(), source: work_dir.join("groups.json"), compress: true },
+        PendingFile { archive_path: "metadata/library.db".into(), source: work_dir.join("library.db"), compress: true },
+    ];
+    for settings_file in source.paths.settings_files() {
+        if settings_file.is_file() {
+            files.push(PendingFile {
+                archive_path: format!("settings/{}", settings_file.file_name().unwrap().to_string_lossy()),
+                source: settings_file,
+                compress: true,
+            });
+        }
+    }
+    // 画像は圧縮済みなので無圧縮で格納する
+    if options.include_originals {
+        files.extend(list_dir(&source.paths.images_dir(), "images", false)?);
+    }
+    if options.include_thumbnails {
+        files.extend(list_dir(&source.paths.thumbnails_dir(), "thumbnails", false)?);
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_dest = dest.with_extension("partial");
+    let output = BufWriter::new(
+        File::create(&tmp_dest).with_context(|| format!("Failed to create backup: {}", dest.display()))?,
+    );
+
+    let manifest = match &options.passphrase {
+        Some(passphrase) => {
+            // 平文の zip をディスクに残さないよう、暗号化ストリームへ直接書き込む
+            let encryptor = age::Encryptor::with_user_passphrase(secret(passphrase)?);
+            let zip = ZipWriter::new_stream(encryptor.wrap_output(output)?);
+            let (manifest, writer) = write_entries(zip, &files, item_count, group_count, reporter)?;
+            writer.into_inner().finish()?.flush()?;
+            manifest
+        }
+        None => {
+            let (manifest, mut writer) =
+                write_entries(ZipWriter::new(output), &files, item_count, group_count, reporter)?;
+            writer.flush()?;
+            manifest
+        }
+    };
+
+    // 書き込み完了後に置き換え、途中で失敗しても壊れたバックアップを残さない
+    fs::rename(&tmp_dest, dest)?;
+    Ok(manifest)
+}
+
+fn write_entries<W: Write + Seek>(
+    mut zip: ZipWriter<W>,
+    files: &[PendingFile],
+    item_count: usize,
+    group_count: usize,
+    reporter: &dyn ProgressReporter,
+) -> Result<(BackupManifest, W)> {
+    reporter.set_total(files.len() as u64);
+    let mut entries = Vec::with_capacity(files.len());
+    for (i, file) in files.iter().enumerate() {
+        reporter.checkpoint()?;
+
+        let method = if file.compress { CompressionMethod::Deflated } else { CompressionMethod::Stored };
+        let file_options = SimpleFileOptions::default()
+            .compression_method(method)
+            .large_file(true);
+        zip.start_file(file.archive_path.as_str(), file_options)?;
+
+        let mut reader = File::open(&file.source)
+            .with_context(|| format!("Failed to read {}", file.source.display()))?;
+        let (size, blake3) = copy_with_hash(&mut reader, &mut zip)?;
+        entries.push(BackupEntry {
+            path: file.archive_path.clone(),
+            size,
+            blake3,
+        });
+
+        reporter.progress(i as u64 + 1, &file.archive_path);
+    }
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        item_count,
+        group_count,
+        entries,
+    };
+    zip.start_file(MANIFEST_NAME, SimpleFileOptions::default())?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    let writer = zip.finish()?;
+    Ok((manifest, writer))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    // 現在のライブラリをバックアップの内容で置き換える
+    Replace,
+    // 現在のライブラリに統合する（id・コンテンツハッシュで重複排除し、新しい方を残す）
+    Merge,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreReport {
+    pub items_added: usize,
+    pub items_updated: usize,
+    pub items_skipped: usize,
+    pub groups_added: usize,
+    pub groups_updated: usize,
+    pub files_restored: usize,
+    // インデックスの再登録が必要なアイテム（Replace の場合は全件再構築）
+    pub changed_item_ids: Vec<String>,
+}
+
+pub fn read_manifest<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<BackupManifest> {
+    let mut json = String::new();
+    archive
+        .by_name(MANIFEST_NAME)
+        .context("Not a Snap Organizer backup: manifest.json missing")?
+        .read_to_string(&mut json)?;
+    let manifest: BackupManifest = serde_json::from_str(&json)?;
+    if manifest.format_version > BACKUP_FORMAT_VERSION {
+        bail!("Unsupported backup format version: {}", manifest.format_version);
+    }
+    Ok(manifest)
+}
+
+// アーカイブ内のパスを検証（ディレクトリトラバーサル対策）
+fn staging_path(staging: &Path, archive_path: &str) -> Result<PathBuf> {
+    let (dir, name) = archive_path
+        .split_once('/')
+        .with_context(|| format!("Unexpected entry in backup: {}", archive_path))?;
+    if !matches!(dir, "metadata" | "settings" | "images" | "thumbnails")
+        || name.is_empty()
+        || name.contains('/')
+        || name.contains('\\')
+        || name == ".."
+    {
+        bail!("Unexpected entry in backup: {}", archive_path);
+    }
+    Ok(staging.join(dir).join(name))
+}
+
+// 暗号化されたバックアップは復号しながら読む（平文の zip をディスクに書き出さない）
+fn open_encrypted(archive_path: &Path, passphrase: Option<&str>) -> Result<ZipArchive<impl Read + Seek>> {
+    let passphrase = passphrase.context("This backup is encrypted; a passphrase is required")?;
+    let decryptor = age::Decryptor::new(BufReader::new(File::open(archive_path)?))
+        .context("Failed to read encrypted backup")?;
+    let identity = age::scrypt::Identity::new(secret(passphrase)?);
+    let reader = decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .context("Incorrect passphrase or corrupted backup")?;
+    ZipArchive::new(reader).context("Incorrect passphrase or corrupted backup")
+}
+
+// チェックサムを検証しながら全ファイルを作業ディレクトリに展開する
+fn extract_verified(
+    archive_path: &Path,
+    passphrase: Option<&str>,
+    staging: &Path,
+    reporter: &dyn ProgressReporter,
+) -> Result<BackupManifest> {
+    if is_encrypted(archive_path)? {
+        extract_entries(open_encrypted(archive_path, passphrase)?, staging, reporter)
+    } else {
+        let archive = ZipArchive::new(File::open(archive_path)
+            .with_context(|| format!("Failed to open backup: {}", archive_path.display()))?)?;
+        extract_entries(archive, staging, reporter)
+    }
+}
+
+fn extract_entries<R: Read + Seek>(
+    mut archive: ZipArchive<R>,
+    staging: &Path,
+    reporter: &dyn ProgressReporter,
+) -> Result<BackupManifest> {
+    let manifest = read_manifest(&mut archive)?;
+
+    reporter.set_total(manifest.entries.len() as u64);
+    for (i, entry) in manifest.entries.iter().enumerate() {
+        reporter.checkpoint()?;
+
+        let dest = staging_path(staging, &entry.path)?;
+        fs::create_dir_all(dest.parent().unwrap())?;
+        let mut reader = archive.by_name(&entry.path)?;
+        let mut writer = BufWriter::new(File::create(&dest)?);
+        let (_, blake3) = copy_with_hash(&mut reader, &mut writer)?;
+        writer.flush()?;
+        if blake3 != entry.blake3 {
+            bail!("Checksum mismatch in backup: {}", entry.path);
+        }
+
+        reporter.progress(i as u64 + 1, &entry.path);
+    }
+    Ok(manifest)
+}
+
+// 作業ディレクトリはライブラリの staging 内に本人だけが読めるように作る（共有の一時ディレクトリに平文を置かない）
+fn create_work_dir(paths: &LibraryPaths, prefix: &str) -> Result<PathBuf> {
+    let parent = paths.staging_dir();
+    fs::create_dir_all(&parent)?;
+    let dir = parent.join(format!("{}-{}", prefix, uuid::Uuid::new_v4()));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+        .create(&dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+// 作業ディレクトリのファイルをライブラリ側へコピー（既存ファイルは上書きしない）
+fn copy_missing(from: &Path, to: &Path) -> Result<usize> {
+    if !from.is_dir() {
+        return Ok(0);
+    }
+    fs::create_dir_all(to)?;
+    let mut copied = 0;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if !dest.exists() {
+            fs::copy(entry.path(), &dest)?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+pub fn import_backup(
+    target: &BackupSource,
+    archive_path: &Path,
+    mode: RestoreMode,
+    passphrase: Option<&str>,
+    reporter: &dyn ProgressReporter,
+) -> Result<RestoreReport> {
+    let staging = create_work_dir(target.paths, "restore")?;
+    let result = extract_verified(archive_path, passphrase, &staging, reporter).and_then(|_| match mode {
+        RestoreMode::Replace => restore_replace(target, &staging),
+        RestoreMode::Merge => restore_merge(target, &staging),
+    });
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+// DB ファイルとその -wal / -shm
+const DB_SUFFIXES: [&str; 3] = ["", "-wal", "-shm"];
+
+fn with_suffix(file: &Path, suffix: &str) -> PathBuf {
+    let mut name = file.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// DB ファイルを -wal / -shm ごと移す（ないファイルは飛ばす）
+fn move_db(from: &Path, to: &Path) -> Result<()> {
+    for suffix in DB_SUFFIXES {
+        let file = with_suffix(from, suffix);
+        if file.exists() {
+            fs::rename(&file, with_suffix(to, suffix))?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_db(file: &Path) {
+    for suffix in DB_SUFFIXES {
+        let _ = fs::remove_file(with_suffix(file, suffix));
+    }
+}
+
+fn open_store(db_file: &Path) -> Result<MetadataStore> {
+    let mut store = MetadataStore::open(db_file)?;
+    store.set_relative_paths(crate::paths::is_portable())?;
+    Ok(store)
+}
+
+fn restore_replace(target: &BackupSource, staging: &Path) -> Result<RestoreReport> {
+    let paths = target.paths;
+    let db_file = paths.metadata_db_file();
+    let restoring = with_suffix(&db_file, ".restore");
+    let previous = with_suffix(&db_file, ".bak");
+    let mut report = RestoreReport::default();
+
+    // 復元する DB は先に同じディレクトリへコピーしておく（コピーに失敗しても今の DB には触れない）
+    remove_db(&restoring);
+    if let Err(e) = fs::copy(staging.join("metadata").join("library.db"), &restoring) {
+        let _ = fs::remove_file(&restoring);
+        return Err(e).context("Failed to copy the restored database");
+    }
+
+    let mut store = target.store.lock().unwrap();
+    // 接続を閉じてから DB ファイルを差し替える（今の DB は復元した DB を開けるまで .bak として残す）
+    *store = None;
+    remove_db(&previous);
+    let mut replaced = false;
+    let opened = move_db(&db_file, &previous).and_then(|_| {
+        fs::rename(&restoring, &db_file)?;
+        replaced = true;
+        let mut restored_store = open_store(&db_file)?;
+        restored_store.relocate_images(&paths.images_dir())?;
+        Ok(restored_store)
+    });
+    let restored_store = match opened {
+        Ok(restored_store) => restored_store,
+        Err(e) => {
+            // 元の DB に戻して開き直す（差し替えたあとなら復元した DB を消す）
+            if replaced {
+                remove_db(&db_file);
+            }
+            let _ = fs::remove_file(&restoring);
+            match move_db(&previous, &db_file).and_then(|_| open_store(&db_file)) {
+                Ok(reopened) => *store = Some(reopened),
+                Err(reopen) => log::error!("Failed to reopen the library after a failed restore: {}", reopen),
+            }
+            return Err(e.context("Failed to restore the library database"));
+        }
+    };
+    remove_db(&previous);
+
+    report.files_restored += copy_missing(&staging.join("images"), &paths.images_dir())?;
+    report.files_restored += copy_missing(&staging.join("thumbnails"), &paths.thumbnails_dir())?;
+    for settings_file in paths.settings_files() {
+        let restored = staging.join("settings").join(settings_file.file_name().unwrap());
+        if restored.is_file() {
+            fs::copy(&restored, &settings_file)?;
+            report.files_restored += 1;
+        }
+    }
+
+    report.items_added = restored_store.count_items()?;
+    *store = Some(restored_store);
+    Ok(report)
+}
+
+fn restore_merge(target: &BackupSource, staging: &Path) -> Result<RestoreReport> {
+    let paths = target.paths;
+    let mut report = RestoreReport::default();
+
+    let items: Vec<ItemRecord> = serde_json::from_slice(&fs::read(staging.join("metadata").join("items.json"))?)?;
+    let groups: Vec<GroupRecord> = serde_json::from_slice(&fs::read(staging.join("metadata").join("groups.json"))?)?;
+
+    report.files_restored += copy_missing(&staging.join("images"), &paths.images_dir())?;
+
+    let mut store = target.store.lock().unwrap();
+    let store = store.as_mut().context("Metadata store not initialized")?;
+
+    // アイテムが参照するため先にグループを統合する
+    for group in &groups {
+        match store.get_group(&group.id)? {
+            None => {
+                store.save_group(group)?;
+                report.groups_added += 1;
+            }
+            Some(existing) if group.updated_at > existing.updated_at => {
+                store.save_group(group)?;
+                report.groups_updated += 1;
+            }
+            Some(_) => {}
+        }
+    }
+
+    // 非公開アイテムは同じ鍵で暗号化したバックアップからだけ取り込む（暗号化したテキストは DB から読む）
+    let key_file = paths.private_key_file();
+    let staged_key_file = staging.join("settings").join(key_file.file_name().unwrap());
+    let has_private = items.iter().any(|item| item.private);
+    let sealed_source = if has_private && private_items::same_key(&staged_key_file, &key_file)? {
+        Some(MetadataStore::open(&staging.join("metadata").join("library.db"))?)
+    } else {
+        None
+    };
+
+    let images_dir = paths.images_dir();
+    for mut item in items {
+        // 画像パスは復元先のライブラリに合わせる
+        if let Some(name) = item.image_path.as_deref().and_then(|p| Path::new(p).file_name()) {
+            item.image_path = Some(images_dir.join(name).to_string_lossy().to_string());
+        }
+
+        let sealed_text = match &sealed_source {
+            Some(source) if item.private => source.sealed_text(&item.id)?,
+            _ => None,
+        };
+        if item.private && sealed_text.is_none() {
+            log::warn!("Skipping private item {}: it was sealed with a different key", item.id);
+            report.items_skipped += 1;
+            continue;
+        }
+
+        let existing = match store.get_item(&item.id)? {
+            Some(existing) => Some(existing),
+            None => match &item.content_hash {
+                Some(hash) => store.find_by_hash(hash)?,
+                None => None,
+            },
+        };
+
+        match existing {
+            None => {
+                store.put_item(&item, sealed_text.as_deref())?;
+                report.items_added += 1;
+                report.changed_item_ids.push(item.id);
+            }
+            Some(existing) if item.updated_at > existing.updated_at => {
+                // 同じ内容の別IDのアイテムは既存のIDを維持して更新する
+                item.id = existing.id;
+                store.put_item(&item, sealed_text.as_deref())?;
+                report.items_updated += 1;
+                report.changed_item_ids.push(item.id);
+            }
+            Some(_) => report.items_skipped += 1,
+        }
+    }
+
+    Ok(report)
+}
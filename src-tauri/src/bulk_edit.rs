@@ -0,0 +1,105 @@
+use crate::metadata_store::ItemRecord;
+use crate::search_engine::SearchQuery;
+use serde::{Deserialize, Serialize};
+
+// 一括編集の対象（ID の一覧か、検索条件に一致するアイテムすべて）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkTarget {
+    Ids(Vec<String>),
+    Query(SearchQuery),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BulkLocation {
+    pub name: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+// 指定した項目だけを変える（省略した項目はそのまま）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BulkChanges {
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+    // 位置情報を置き換える（clear_location なら消す）
+    #[serde(default)]
+    pub location: Option<BulkLocation>,
+    #[serde(default)]
+    pub clear_location: bool,
+    // グループに入れる（clear_group ならグループから外す）
+    #[serde(default)]
+    pub group_id: Option<String>,
+    #[serde(default)]
+    pub clear_group: bool,
+    #[serde(default)]
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BulkUpdateResult {
+    // 対象になったアイテムの数と、実際に変わったアイテム
+    pub matched: usize,
+    pub updated: Vec<String>,
+    // 見つからなかった（削除された）アイテム
+    pub missing: Vec<String>,
+}
+
+impl BulkChanges {
+    pub fn is_empty(&self) -> bool {
+        self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+            && self.location.is_none()
+            && !self.clear_location
+            && self.group_id.is_none()
+            && !self.clear_group
+            && self.archived.is_none()
+    }
+
+    // アイテムに変更を当てる（何か変わったら true）
+    pub fn apply(&self, item: &mut ItemRecord) -> bool {
+        let mut changed = false;
+        let before = item.tags.len();
+        item.tags.retain(|tag| !self.remove_tags.contains(tag));
+        changed |= item.tags.len() != before;
+        for tag in &self.add_tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !item.tags.iter().any(|t| t == tag) {
+                item.tags.push(tag.to_string());
+                changed = true;
+            }
+        }
+
+        let location = match (&self.location, self.clear_location) {
+            (_, true) => Some((None, None, None)),
+            (Some(location), false) => Some((location.name.clone(), location.latitude, location.longitude)),
+            (None, false) => None,
+        };
+        if let Some((name, latitude, longitude)) = location {
+            if item.location_name != name || item.latitude != latitude || item.longitude != longitude {
+                item.location_name = name;
+                item.latitude = latitude;
+                item.longitude = longitude;
+                changed = true;
+            }
+        }
+
+        let group_id = if self.clear_group {
+            Some(None)
+        } else {
+            self.group_id.clone().map(Some)
+        };
+        if let Some(group_id) = group_id.filter(|group_id| *group_id != item.group_id) {
+            item.group_id = group_id;
+            changed = true;
+        }
+
+        if let Some(archived) = self.archived.filter(|archived| *archived != item.archived) {
+            item.archived = archived;
+            changed = true;
+        }
+        changed
+    }
+}
@@ -0,0 +1,105 @@
+use crate::inference::{InferenceInput, InferenceService};
+use crate::models;
+use anyhow::{anyhow, Context, Result};
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::sync::{Arc, Mutex};
+use tokenizers::Tokenizer;
+
+pub const FEATURE: &str = "captions";
+const ENCODER_MODEL: &str = "vit-gpt2-caption-encoder";
+const DECODER_MODEL: &str = "vit-gpt2-caption-decoder";
+const TOKENIZER: &str = "vit-gpt2-caption-tokenizer";
+
+// ViT の前処理（224x224 に縮小し、平均・標準偏差 0.5 で正規化）
+const IMAGE_SIZE: u32 = 224;
+const MEAN: f32 = 0.5;
+const STD: f32 = 0.5;
+// GPT-2 の文の始まりと終わりは同じトークン
+const BOS_TOKEN: i64 = 50256;
+const EOS_TOKEN: i64 = 50256;
+const MAX_TOKENS: usize = 24;
+
+fn pixel_values(image: &DynamicImage) -> Result<InferenceInput> {
+    // 切り出さずに縦横を合わせる（学習時の前処理と同じ）
+    let image = image.resize_exact(IMAGE_SIZE, IMAGE_SIZE, FilterType::CatmullRom).to_rgb8();
+    let plane = (IMAGE_SIZE * IMAGE_SIZE) as usize;
+    let mut data = vec![0f32; plane * 3];
+    for (i, pixel) in image.pixels().enumerate() {
+        for c in 0..3 {
+            data[c * plane + i] = (pixel[c] as f32 / 255.0 - MEAN) / STD;
+        }
+    }
+    InferenceInput::f32(&[1, 3, IMAGE_SIZE as usize, IMAGE_SIZE as usize], data)
+}
+
+// 先頭を大文字にして句点を付け、1文にする
+fn to_sentence(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.trim_end_matches('.').trim();
+    let mut chars = text.chars();
+    let first = chars.next()?;
+    Some(format!("{}{}.", first.to_uppercase(), chars.as_str()))
+}
+
+// 画像の内容を説明する1文の生成（ViT + GPT-2、貪欲法でデコード）
+pub struct CaptionService {
+    inference: Arc<InferenceService>,
+    tokenizer: Mutex<Option<Arc<Tokenizer>>>,
+}
+
+impl CaptionService {
+    pub fn new(inference: Arc<InferenceService>) -> Self {
+        CaptionService {
+            inference,
+            tokenizer: Mutex::new(None),
+        }
+    }
+
+    // モデルがダウンロード済みか（なければ取り込み時に説明文を生成しない）
+    pub fn is_available(&self) -> bool {
+        self.inference.models().feature_installed(FEATURE)
+    }
+
+    fn tokenizer(&self) -> Result<Arc<Tokenizer>> {
+        let mut tokenizer = self.tokenizer.lock().unwrap();
+        if let Some(tokenizer) = tokenizer.as_ref() {
+            return Ok(tokenizer.clone());
+        }
+        let path = self.inference.models().path(TOKENIZER);
+        let loaded = Arc::new(Tokenizer::from_file(&path).map_err(|e| anyhow!("Failed to load tokenizer: {}", e))?);
+        *tokenizer = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    // 説明文を生成する（何も生成できなければ None）
+    pub fn caption(&self, image: &DynamicImage) -> Result<Option<String>> {
+        let tokenizer = self.tokenizer()?;
+        let hidden = self
+            .inference
+            .run(ENCODER_MODEL, vec![("pixel_values", pixel_values(image)?)])?
+            .remove("last_hidden_state")
+            .context("Model has no last_hidden_state output")?;
+
+        let ids = self.inference.greedy_decode(DECODER_MODEL, BOS_TOKEN, EOS_TOKEN, MAX_TOKENS, &[], || {
+            Ok(vec![("encoder_hidden_states", InferenceInput::F32(hidden.clone()))])
+        })?;
+        let tokens: Vec<u32> = ids.iter().map(|&id| id as u32).collect();
+        let text = tokenizer
+            .decode(&tokens, true)
+            .map_err(|e| anyhow!("Failed to decode caption: {}", e))?;
+        Ok(to_sentence(&text))
+    }
+
+    // モデルを消したときは読み込み済みのトークナイザーも捨てる
+    pub fn unload(&self) {
+        *self.tokenizer.lock().unwrap() = None;
+    }
+}
+
+// 説明文の生成に使うモデルか（ダウンロード・削除したときに読み込み済みのものを捨てる）
+pub fn is_model(model_id: &str) -> bool {
+    models::CATALOG
+        .iter()
+        .any(|spec| spec.id == model_id && spec.feature == FEATURE)
+}
@@ -0,0 +1,303 @@
+use crate::backup::{self, BackupOptions, BackupSource};
+use crate::captions::CaptionService;
+use crate::embeddings::EmbeddingService;
+use crate::folder_import::{self, FolderImportOptions};
+use crate::geocoding::GeocodingService;
+use crate::import_pipeline::{DuplicatePolicy, ImportContext};
+use crate::inference::InferenceService;
+use crate::jobs::ProgressReporter;
+use crate::libraries::LibraryRegistry;
+use crate::metadata_store::MetadataStore;
+use crate::ocr::{OcrService, TesseractEngine};
+use crate::ocr_corrections::CorrectionDictionary;
+use crate::paths::{self, LibraryPaths};
+use crate::plugins::PluginHost;
+use crate::rules::RulesService;
+use crate::search_engine::{SearchEngine, SearchQuery};
+use crate::settings::SettingsStore;
+use crate::translation::TranslationService;
+use crate::SearchEngineState;
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// tauri.conf.json の identifier（GUI と同じデータディレクトリを使う）
+const APP_IDENTIFIER: &str = "com.tauri.dev";
+const SUBCOMMANDS: &[&str] = &["import", "search", "export-backup", "reindex", "help", "--help", "-h", "--version", "-V"];
+
+#[derive(Parser)]
+#[command(name = "snap-organizer", version, about = "Snap Organizer のライブラリを GUI なしで操作する")]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        env = "SNAP_ORGANIZER_LIBRARY",
+        help = "対象のライブラリのフォルダ（省略時は GUI で選ばれているライブラリ）"
+    )]
+    library: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "フォルダ内の画像を取り込む")]
+    Import {
+        folder: PathBuf,
+        #[arg(long, help = "サブフォルダをたどらない")]
+        no_recursive: bool,
+        #[arg(long, value_enum, help = "内容が同じアイテムが既にあるときの扱い（省略時は設定の値）")]
+        duplicates: Option<DuplicateArg>,
+        #[arg(long, help = "ホワイトボードの写真として台形補正し、背景を白くして取り込む")]
+        whiteboard: bool,
+    },
+    #[command(about = "検索して ID・スコア・1行目を表示する")]
+    Search {
+        query: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long, help = "結果を JSON で出力する")]
+        json: bool,
+    },
+    #[command(about = "ライブラリ全体をバックアップする")]
+    ExportBackup {
+        path: PathBuf,
+        #[arg(long)]
+        include_thumbnails: bool,
+        #[arg(long, env = "SNAP_ORGANIZER_BACKUP_PASSPHRASE", help = "指定するとパスフレーズで暗号化する")]
+        passphrase: Option<String>,
+    },
+    #[command(about = "メタデータストアから検索インデックスを作り直す")]
+    Reindex,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DuplicateArg {
+    Skip,
+    Link,
+    ImportAnyway,
+}
+
+impl From<DuplicateArg> for DuplicatePolicy {
+    fn from(arg: DuplicateArg) -> Self {
+        match arg {
+            DuplicateArg::Skip => DuplicatePolicy::Skip,
+            DuplicateArg::Link => DuplicatePolicy::Link,
+            DuplicateArg::ImportAnyway => DuplicatePolicy::ImportAnyway,
+        }
+    }
+}
+
+// 進捗を標準エラーに1行ずつ出す
+struct ConsoleProgress {
+    total: Mutex<u64>,
+}
+
+impl ProgressReporter for ConsoleProgress {
+    fn set_total(&self, total: u64) {
+        *self.total.lock().unwrap() = total;
+    }
+
+    fn progress(&self, done: u64, message: &str) {
+        eprintln!("[{}/{}] {}", done, self.total.lock().unwrap(), message);
+    }
+
+    fn checkpoint(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// GUI と同じ規則でライブラリのフォルダを決める
+fn library_root(explicit: Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(root) = explicit {
+        return Ok(root);
+    }
+    let base = match paths::portable_data_dir() {
+        Some(dir) => dir,
+        None => dirs::data_dir()
+            .context("App data directory not found")?
+            .join(APP_IDENTIFIER),
+    };
+    Ok(PathBuf::from(&LibraryRegistry::load_from(&base).active().path))
+}
+
+fn open_store(paths: &LibraryPaths) -> Result<MetadataStore> {
+    let mut store = MetadataStore::open(&paths.metadata_db_file())?;
+    store.set_relative_paths(paths::is_portable())?;
+    Ok(store)
+}
+
+// GUI が起動中だと検索インデックスの書き込みロックが取れない
+// インデックスを作り直した場合（新しいフィールドの追加など）はメタデータストアから入れ直す
+fn open_search_engine(paths: &LibraryPaths, store: &MetadataStore) -> Result<SearchEngine> {
+    let index_dir = paths.index_dir();
+    std::fs::create_dir_all(&index_dir)?;
+    let mut engine =
+        SearchEngine::new(&index_dir).context("Failed to open search index (is the app running?)")?;
+    if engine.was_recreated() {
+        crate::rebuild_index(store, &mut engine)?;
+    }
+    Ok(engine)
+}
+
+fn import(paths: &LibraryPaths, folder: &Path, options: FolderImportOptions) -> Result<()> {
+    let store = open_store(paths)?;
+    let search = Mutex::new(Some(open_search_engine(paths, &store)?));
+    let store = Mutex::new(Some(store));
+    let ocr_engine = TesseractEngine::locate(paths.tessdata_dir())
+        .map_err(|e| eprintln!("OCR disabled: {}", e))
+        .ok();
+    let ocr = OcrService::new(ocr_engine, paths.ocr_settings_file());
+    let corrections = CorrectionDictionary::new(paths.ocr_corrections_file());
+    let inference = Arc::new(InferenceService::new(paths.models_dir()));
+    let embeddings = EmbeddingService::new(inference.clone(), paths.vector_index_file());
+    let captions = CaptionService::new(inference.clone());
+    let translator = TranslationService::new(inference, paths.translation_settings_file());
+    let geocoder = GeocodingService::new(vec![paths.geonames_dir()], paths.geocoding_settings_file());
+    let rules = RulesService::new(paths.rules_file());
+    let plugins = PluginHost::new(paths.plugins_dir(), paths.plugin_settings_file());
+    let ctx = ImportContext {
+        paths,
+        store: &store,
+        search: &search,
+        ocr: Some(&ocr),
+        corrections: Some(&corrections),
+        embeddings: Some(&embeddings),
+        captions: Some(&captions),
+        translator: Some(&translator),
+        geocoder: Some(&geocoder),
+        rules: Some(&rules),
+        duplicates: SettingsStore::new(paths.app_settings_file()).get().duplicate_policy,
+        whiteboard: false,
+        webhooks: None,
+        plugins: Some(&plugins),
+    };
+    let reporter = ConsoleProgress { total: Mutex::new(0) };
+    let report = folder_import::import_folder(&ctx, folder, &options, &reporter, &|_| {})?;
+    embeddings.flush()?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+fn search(paths: &LibraryPaths, query: String, limit: usize, json: bool) -> Result<()> {
+    let store = open_store(paths)?;
+    let state = SearchEngineState(Mutex::new(Some(open_search_engine(paths, &store)?)));
+    let query = SearchQuery {
+        query,
+        limit: Some(limit),
+        ..Default::default()
+    };
+    // 非公開アイテムは CLI では表示しない
+    let private_ids = store.private_item_ids()?;
+    let results: Vec<_> = crate::search_index(&store, &state, query)
+        .map_err(|e| anyhow!(e))?
+        .into_iter()
+        .filter(|r| !private_ids.contains(&r.id))
+        .collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+    let mut out = std::io::stdout().lock();
+    for result in results {
+        let title = store
+            .get_item(&result.id)?
+            .and_then(|item| {
+                [item.memo, item.ocr_text]
+                    .iter()
+                    .flat_map(|t| t.lines().map(str::trim).find(|l| !l.is_empty()).map(str::to_string))
+                    .next()
+            })
+            .unwrap_or_default();
+        writeln!(out, "{}\t{:.2}\t{}", result.id, result.score, title)?;
+    }
+    Ok(())
+}
+
+fn export_backup(paths: &LibraryPaths, dest: &Path, options: BackupOptions) -> Result<()> {
+    let store = Mutex::new(Some(open_store(paths)?));
+    let source = BackupSource { paths, store: &store };
+    let reporter = ConsoleProgress { total: Mutex::new(0) };
+    let manifest = backup::export_backup(&source, dest, &options, &reporter)?;
+    println!("Backed up {} items to {}", manifest.item_count, dest.display());
+    Ok(())
+}
+
+fn reindex(paths: &LibraryPaths) -> Result<()> {
+    let store = open_store(paths)?;
+    let mut engine = open_search_engine(paths, &store)?;
+    let count = crate::rebuild_index(&store, &mut engine)?;
+    println!("Reindexed {} items", count);
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let paths = LibraryPaths::new(library_root(cli.library)?);
+    match cli.command {
+        Command::Import {
+            folder,
+            no_recursive,
+            duplicates,
+            whiteboard,
+        } => {
+            let options = FolderImportOptions {
+                recursive: !no_recursive,
+                duplicates: duplicates.map(DuplicatePolicy::from),
+                whiteboard,
+                ..Default::default()
+            };
+            import(&paths, &folder, options)
+        }
+        Command::Search { query, limit, json } => search(&paths, query, limit, json),
+        Command::ExportBackup {
+            path,
+            include_thumbnails,
+            passphrase,
+        } => {
+            let options = BackupOptions {
+                include_thumbnails,
+                passphrase,
+                ..Default::default()
+            };
+            export_backup(&paths, &path, options)
+        }
+        Command::Reindex => reindex(&paths),
+    }
+}
+
+// Windows のリリースビルドはコンソールを持たないため、起動元のコンソールに出力をつなぐ
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+// 最初の引数がサブコマンドなら CLI として実行して終了コードを返す（それ以外は GUI を起動する）
+pub fn run_from_args() -> Option<i32> {
+    let first = std::env::args().nth(1)?;
+    if !SUBCOMMANDS.contains(&first.as_str()) {
+        return None;
+    }
+    attach_console();
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            return Some(e.exit_code());
+        }
+    };
+    match run(cli) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            Some(1)
+        }
+    }
+}
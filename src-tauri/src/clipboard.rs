@@ -0,0 +1,37 @@
+use anyhow::{bail, Context, Result};
+use image::{ImageFormat, RgbaImage};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+// クリップボードの画像を読み出す（画像がなければ None）
+pub fn clipboard_image() -> Result<Option<RgbaImage>> {
+    let mut clipboard = arboard::Clipboard::new().context("Failed to open clipboard")?;
+    let image = match clipboard.get_image() {
+        Ok(image) => image,
+        Err(arboard::Error::ContentNotAvailable) => return Ok(None),
+        Err(e) => return Err(e).context("Failed to read clipboard"),
+    };
+    let buffer = RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+        .context("Invalid clipboard image")?;
+    Ok(Some(buffer))
+}
+
+// 画像を PNG として staging ディレクトリに書き出す
+pub fn save_png(image: RgbaImage, staging_dir: &Path) -> Result<PathBuf> {
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgba8(image).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+
+    fs::create_dir_all(staging_dir)?;
+    let path = staging_dir.join(format!("clipboard-{}.png", uuid::Uuid::new_v4().simple()));
+    fs::write(&path, png)?;
+    Ok(path)
+}
+
+// クリップボードの画像を PNG として staging ディレクトリに書き出す
+pub fn save_clipboard_image(staging_dir: &Path) -> Result<PathBuf> {
+    match clipboard_image()? {
+        Some(image) => save_png(image, staging_dir),
+        None => bail!("No image on the clipboard"),
+    }
+}
@@ -0,0 +1,47 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use tauri::Url;
+
+pub const SCHEME: &str = "snaporganizer";
+// ID（UUID）の - などはそのまま読めるようにエンコードしない
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+// snaporganizer://item/<id> / snaporganizer://search?q=<検索文字列>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeepLink {
+    Item(String),
+    Search(String),
+}
+
+impl DeepLink {
+    pub fn parse(url: &Url) -> Option<Self> {
+        if url.scheme() != SCHEME {
+            return None;
+        }
+        let segments: Vec<String> = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .map(|s| percent_encoding::percent_decode_str(s).decode_utf8_lossy().to_string())
+            .collect();
+        match url.host_str()? {
+            "item" => segments.into_iter().next().map(DeepLink::Item),
+            "search" => {
+                let query = url
+                    .query_pairs()
+                    .find(|(key, _)| key == "q")
+                    .map(|(_, value)| value.to_string())
+                    .or_else(|| segments.first().cloned())?;
+                Some(DeepLink::Search(query))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_url(&self) -> String {
+        match self {
+            DeepLink::Item(id) => format!("{}://item/{}", SCHEME, utf8_percent_encode(id, COMPONENT)),
+            DeepLink::Search(query) => format!("{}://search?q={}", SCHEME, utf8_percent_encode(query, COMPONENT)),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use chrono::{Datelike, Local, NaiveDate};
+use regex::Regex;
+use std::sync::OnceLock;
+
+// 書類の日付であることを示す項目名（この行か次の行の日付を優先する）
+const DATE_LABELS: &[&str] = &[
+    "発行日", "作成日", "請求日", "発行年月日", "日付", "年月日", "受付日", "取引日", "date", "issued", "dated",
+];
+// 書類の日付ではないもの（期限・生年月日など）は他に日付がないときだけ使う
+const OTHER_DATE_LABELS: &[&str] = &[
+    "期限", "期日", "有効", "生年月日", "誕生日", "満了", "due", "expir", "valid", "birth",
+];
+// 和暦の元年の前年（令和1年 = 2019年）
+const ERAS: &[(&str, &str, i32)] = &[("令和", "R", 2018), ("平成", "H", 1988), ("昭和", "S", 1925)];
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+// 2024年4月1日 / 2024/4/1 / 2024-04-01 / 2024.4.1
+fn ymd_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"((?:19|20)[0-9]{2})\s*[年/.\-]\s*([0-9]{1,2})\s*[月/.\-]\s*([0-9]{1,2})").unwrap()
+    })
+}
+
+// 令和6年4月1日 / 平成元年 / R6.4.1 / H31/4/30（小文字にした行に当てる）
+fn era_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"(?:(令和|平成|昭和)|\b([rhs]))\s*(元|[0-9]{1,2})\s*[年/.\-]\s*([0-9]{1,2})\s*[月/.\-]\s*([0-9]{1,2})",
+        )
+        .unwrap()
+    })
+}
+
+// April 1, 2024 / Apr. 1st 2024
+fn month_day_year_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"\b(jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]*\.?\s+([0-9]{1,2})(?:st|nd|rd|th)?,?\s+((?:19|20)[0-9]{2})\b")
+            .unwrap()
+    })
+}
+
+// 1 April 2024 / 1st Apr, 2024
+fn day_month_year_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"\b([0-9]{1,2})(?:st|nd|rd|th)?\s+(jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]*\.?,?\s+((?:19|20)[0-9]{2})\b")
+            .unwrap()
+    })
+}
+
+// 4/1/2024（月/日/年、最初の数が12より大きければ日/月/年）
+fn numeric_dmy_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\b([0-9]{1,2})[/.\-]([0-9]{1,2})[/.\-]((?:19|20)[0-9]{2})\b").unwrap())
+}
+
+// 全角の数字と記号を半角にする（OCR は全角で読むことがある）
+fn normalize(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '０'..='９' | '／' | '．' | '－' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    MONTHS.iter().position(|m| name.starts_with(m)).map(|i| i as u32 + 1)
+}
+
+fn era_year(era: &str, year: &str) -> Option<i32> {
+    let base = ERAS
+        .iter()
+        .find(|(name, short, _)| *name == era || short.eq_ignore_ascii_case(era))?
+        .2;
+    let year: i32 = if year == "元" { 1 } else { year.parse().ok()? };
+    (year >= 1).then_some(base + year)
+}
+
+// 1行から読み取れる日付（行の中の位置と一緒に返す）
+fn dates_in_line(line: &str) -> Vec<(usize, NaiveDate)> {
+    let mut dates = Vec::new();
+    let mut push = |start: usize, year: Option<i32>, month: Option<u32>, day: Option<u32>| {
+        if let Some(date) = year.zip(month).zip(day).and_then(|((y, m), d)| NaiveDate::from_ymd_opt(y, m, d)) {
+            dates.push((start, date));
+        }
+    };
+    for caps in ymd_pattern().captures_iter(line) {
+        push(caps.get(0).unwrap().start(), caps[1].parse().ok(), caps[2].parse().ok(), caps[3].parse().ok());
+    }
+    for caps in era_pattern().captures_iter(line) {
+        let era = caps.get(1).or(caps.get(2)).unwrap().as_str();
+        push(caps.get(0).unwrap().start(), era_year(era, &caps[3]), caps[4].parse().ok(), caps[5].parse().ok());
+    }
+    for caps in month_day_year_pattern().captures_iter(line) {
+        push(caps.get(0).unwrap().start(), caps[3].parse().ok(), month_number(&caps[1]), caps[2].parse().ok());
+    }
+    for caps in day_month_year_pattern().captures_iter(line) {
+        push(caps.get(0).unwrap().start(), caps[3].parse().ok(), month_number(&caps[2]), caps[1].parse().ok());
+    }
+    for caps in numeric_dmy_pattern().captures_iter(line) {
+        let first: Option<u32> = caps[1].parse().ok();
+        let second: Option<u32> = caps[2].parse().ok();
+        let (month, day) = if first.is_some_and(|n| n > 12) { (second, first) } else { (first, second) };
+        push(caps.get(0).unwrap().start(), caps[3].parse().ok(), month, day);
+    }
+    dates.sort_by_key(|(start, _)| *start);
+    dates
+}
+
+fn contains_any(line: &str, labels: &[&str]) -> bool {
+    labels.iter().any(|label| line.contains(label))
+}
+
+// OCR のテキストから書類の日付を読み取る
+// 発行日などの項目名が付いた日付、項目名のない日付、期限などの日付の順に、先に出てくるものを選ぶ
+// 来年より先の日付は読み間違いとみなす
+pub fn parse(text: &str) -> Option<NaiveDate> {
+    let latest = Local::now().year() + 1;
+    let lines: Vec<String> = text.lines().map(|line| normalize(line).to_lowercase()).collect();
+    let mut unlabelled = None;
+    let mut other = None;
+    for (index, line) in lines.iter().enumerate() {
+        let Some(date) = dates_in_line(line)
+            .into_iter()
+            .map(|(_, date)| date)
+            .find(|date| date.year() <= latest)
+        else {
+            continue;
+        };
+        let previous = index.checked_sub(1).map(|i| lines[i].as_str()).unwrap_or("");
+        // 項目名だけの行の次の行に日付があることもある
+        let labelled_previous = dates_in_line(previous).is_empty() && contains_any(previous, DATE_LABELS);
+        if contains_any(line, OTHER_DATE_LABELS) {
+            other = other.or(Some(date));
+        } else if contains_any(line, DATE_LABELS) || labelled_previous {
+            return Some(date);
+        } else {
+            unlabelled = unlabelled.or(Some(date));
+        }
+    }
+    unlabelled.or(other)
+}
@@ -0,0 +1,133 @@
+use crate::entities::{self, EntityKind};
+use crate::ocr::OcrWord;
+use crate::receipts;
+use serde::{Deserialize, Serialize};
+
+// これ未満の点数ならどの種類ともみなさない
+const MIN_SCORE: f32 = 2.0;
+// 手書きの文字は OCR の確からしさが低くなる（Tesseract の 0〜100）
+const HANDWRITING_MAX_CONFIDENCE: f32 = 55.0;
+// ホワイトボードは画像の高さに対して文字が大きく、語数が少ない
+const WHITEBOARD_MIN_WORD_HEIGHT_RATIO: f32 = 0.035;
+const WHITEBOARD_MAX_WORDS: usize = 120;
+// 名刺の縦横比（91mm x 55mm）の許容範囲
+const BUSINESS_CARD_ASPECT: (f32, f32) = (1.45, 1.9);
+const BUSINESS_CARD_MAX_CHARS: usize = 400;
+
+const RECEIPT_KEYWORDS: &[&str] = &["領収", "レシート", "receipt", "お預", "お釣", "釣銭", "小計", "合計", "点数", "レジ"];
+const INVOICE_KEYWORDS: &[&str] = &["請求書", "invoice", "請求金額", "ご請求", "支払期限", "支払期日", "お振込", "振込先", "bill to"];
+const BUSINESS_CARD_KEYWORDS: &[&str] = &[
+    "代表取締役", "取締役", "部長", "課長", "主任", "担当", "mobile", "fax", "tel", "e-mail", "株式会社", "事務所",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    Receipt,
+    Invoice,
+    BusinessCard,
+    Whiteboard,
+    HandwrittenNote,
+    #[default]
+    Other,
+}
+
+impl DocumentType {
+    pub const ALL: [DocumentType; 6] = [
+        DocumentType::Receipt,
+        DocumentType::Invoice,
+        DocumentType::BusinessCard,
+        DocumentType::Whiteboard,
+        DocumentType::HandwrittenNote,
+        DocumentType::Other,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DocumentType::Receipt => "receipt",
+            DocumentType::Invoice => "invoice",
+            DocumentType::BusinessCard => "business_card",
+            DocumentType::Whiteboard => "whiteboard",
+            DocumentType::HandwrittenNote => "handwritten_note",
+            DocumentType::Other => "other",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        DocumentType::ALL
+            .into_iter()
+            .find(|t| t.as_str() == value)
+            .unwrap_or_default()
+    }
+
+    // 合計・消費税などのレシートの項目を読み取る種類
+    pub fn has_receipt_fields(self) -> bool {
+        matches!(self, DocumentType::Receipt | DocumentType::Invoice)
+    }
+}
+
+fn keyword_hits(text: &str, keywords: &[&str]) -> f32 {
+    keywords.iter().filter(|k| text.contains(*k)).count() as f32
+}
+
+// 画像の大きさが分からない（0）ときは縦横比・文字の大きさでは判定しない
+fn aspect_ratio(width: u32, height: u32) -> Option<f32> {
+    (width > 0 && height > 0).then(|| width.max(height) as f32 / width.min(height) as f32)
+}
+
+fn mean_confidence(words: &[OcrWord]) -> Option<f32> {
+    (!words.is_empty()).then(|| words.iter().map(|w| w.confidence).sum::<f32>() / words.len() as f32)
+}
+
+fn mean_word_height_ratio(words: &[OcrWord], height: u32) -> Option<f32> {
+    (!words.is_empty() && height > 0)
+        .then(|| words.iter().map(|w| w.height as f32).sum::<f32>() / words.len() as f32 / height as f32)
+}
+
+// OCR の結果と画像の大きさから書類の種類を推定する（キーワードと文字の配置による判定）
+pub fn classify(text: &str, words: &[OcrWord], width: u32, height: u32) -> DocumentType {
+    let lower = text.to_lowercase();
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+    let mut scores = Vec::new();
+
+    let receipt = receipts::parse(text);
+    let mut receipt_score = keyword_hits(&lower, RECEIPT_KEYWORDS);
+    if receipt.is_receipt() {
+        receipt_score += 1.0 + receipt.tax.map_or(0.0, |_| 1.0) + receipt.purchased_on.map_or(0.0, |_| 0.5);
+    }
+    let invoice_score = keyword_hits(&lower, INVOICE_KEYWORDS) * 2.0 + if receipt.is_receipt() { 0.5 } else { 0.0 };
+    scores.push((DocumentType::Receipt, receipt_score));
+    scores.push((DocumentType::Invoice, invoice_score));
+
+    // 名刺は短い文字の中に連絡先がまとまっている
+    if chars <= BUSINESS_CARD_MAX_CHARS {
+        let found = entities::extract(text);
+        let has = |kind: EntityKind| found.iter().any(|e| e.kind == kind);
+        let mut score = keyword_hits(&lower, BUSINESS_CARD_KEYWORDS) * 0.5;
+        score += [EntityKind::Phone, EntityKind::Email, EntityKind::Url, EntityKind::PostalCode]
+            .into_iter()
+            .filter(|kind| has(*kind))
+            .count() as f32;
+        if aspect_ratio(width, height).is_some_and(|r| (BUSINESS_CARD_ASPECT.0..=BUSINESS_CARD_ASPECT.1).contains(&r)) {
+            score += 1.0;
+        }
+        scores.push((DocumentType::BusinessCard, score));
+    }
+
+    let confidence = mean_confidence(words);
+    let large_text = mean_word_height_ratio(words, height).is_some_and(|r| r >= WHITEBOARD_MIN_WORD_HEIGHT_RATIO);
+    if large_text && words.len() <= WHITEBOARD_MAX_WORDS {
+        let landscape = width > height;
+        scores.push((DocumentType::Whiteboard, 2.0 + if landscape { 0.5 } else { 0.0 }));
+    }
+    if words.len() >= 3 && confidence.is_some_and(|c| c < HANDWRITING_MAX_CONFIDENCE) {
+        scores.push((DocumentType::HandwrittenNote, 2.0 + if large_text { 0.0 } else { 0.5 }));
+    }
+
+    scores
+        .into_iter()
+        .filter(|(_, score)| *score >= MIN_SCORE)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(doc_type, _)| doc_type)
+        .unwrap_or_default()
+}
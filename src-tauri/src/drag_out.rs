@@ -0,0 +1,187 @@
+use crate::markdown_export::sanitize;
+use crate::metadata_store::ItemRecord;
+use crate::private_items;
+use crate::thumbnail_cache::ThumbnailCache;
+use anyhow::Result;
+use chrono::Local;
+use image::codecs::jpeg::JpegEncoder;
+use image::GenericImageView;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const MAX_TITLE_CHARS: usize = 40;
+const JPEG_QUALITY: u8 = 90;
+const ICON_SIZE: u32 = 128;
+// ドロップ先がコピーし終わるまで残しておく時間
+const KEEP_FOR: Duration = Duration::from_secs(60 * 60);
+
+// ドラッグで渡すファイルの形式
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DragFormat {
+    // どのアプリでも開ける JPEG に変換する
+    #[default]
+    Jpeg,
+    // 取り込んだときのファイルそのまま
+    Original,
+    // 1アイテム1ページの PDF
+    Pdf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DragFiles {
+    pub files: Vec<String>,
+    // ドラッグ中にカーソルの横に出す画像
+    pub icon: Option<String>,
+}
+
+// ドロップ先で分かりやすいよう、メモ（なければ文字）の1行目と日時をファイル名にする
+fn file_stem(item: &ItemRecord) -> String {
+    let title = [&item.memo, &item.ocr_text]
+        .iter()
+        .find_map(|text| text.lines().map(str::trim).find(|l| !l.is_empty()))
+        .map(|line| sanitize(&line.chars().take(MAX_TITLE_CHARS).collect::<String>()))
+        .unwrap_or_default();
+    let date = item.created_at.with_timezone(&Local).format("%Y-%m-%d %H%M%S");
+    if title.is_empty() {
+        format!("Snap {}", date)
+    } else {
+        format!("{} {}", title, date)
+    }
+}
+
+fn unique_path(dir: &Path, stem: &str, ext: &str, used: &mut HashSet<PathBuf>) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", stem, ext));
+    let mut n = 2;
+    while !used.insert(path.clone()) {
+        path = dir.join(format!("{} ({}).{}", stem, n, ext));
+        n += 1;
+    }
+    path
+}
+
+fn to_jpeg(data: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let image = image::load_from_memory(data)?;
+    let (width, height) = image.dimensions();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY).encode_image(&image.to_rgb8())?;
+    Ok((jpeg, width, height))
+}
+
+// JPEG を1枚だけ貼った1ページの PDF（用紙は画像と同じ大きさ、72dpi 換算）
+fn jpeg_to_pdf(jpeg: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let content = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", width, height);
+    let objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Im0 4 0 R >> >> /Contents 5 0 R >>",
+            width, height
+        )
+        .into_bytes(),
+        [
+            format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+                width,
+                height,
+                jpeg.len()
+            )
+            .as_bytes(),
+            jpeg,
+            b"\nendstream",
+        ]
+        .concat(),
+        format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content).into_bytes(),
+    ];
+
+    let mut pdf = Vec::new();
+    pdf.write_all(b"%PDF-1.4\n")?;
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        writeln!(pdf, "{} 0 obj", i + 1)?;
+        pdf.write_all(object)?;
+        pdf.write_all(b"\nendobj\n")?;
+    }
+    let xref = pdf.len();
+    writeln!(pdf, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1)?;
+    for offset in offsets {
+        writeln!(pdf, "{:010} 00000 n ", offset)?;
+    }
+    writeln!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF",
+        objects.len() + 1,
+        xref
+    )?;
+    Ok(pdf)
+}
+
+// ドラッグを始めるときに、ドロップ先へ渡す一時ファイルを作る
+// items は (アイテム, 元画像のデータ) の組（非公開アイテムは復号済みのデータを渡す）
+pub fn materialize(items: &[(ItemRecord, Vec<u8>)], dir: &Path, format: DragFormat) -> Result<DragFiles> {
+    fs::create_dir_all(dir)?;
+    let mut used = HashSet::new();
+    let mut files = Vec::new();
+    for (item, data) in items {
+        let stem = file_stem(item);
+        let path = match format {
+            DragFormat::Original => {
+                let source = item.image_path.as_deref().map(Path::new).unwrap_or(Path::new(""));
+                let source = if private_items::is_sealed_path(source) {
+                    private_items::inner_path(source)
+                } else {
+                    source.to_path_buf()
+                };
+                let ext = source.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_lowercase();
+                let path = unique_path(dir, &stem, &ext, &mut used);
+                fs::write(&path, data)?;
+                path
+            }
+            DragFormat::Jpeg => {
+                let path = unique_path(dir, &stem, "jpg", &mut used);
+                fs::write(&path, to_jpeg(data)?.0)?;
+                path
+            }
+            DragFormat::Pdf => {
+                let (jpeg, width, height) = to_jpeg(data)?;
+                let path = unique_path(dir, &stem, "pdf", &mut used);
+                fs::write(&path, jpeg_to_pdf(&jpeg, width, height)?)?;
+                path
+            }
+        };
+        files.push(path.to_string_lossy().to_string());
+    }
+
+    // アイコンはドロップするファイルと名前が重ならないよう隠しファイルにする
+    let icon = match items.first().map(|(_, data)| ThumbnailCache::render(data, ICON_SIZE)) {
+        Some(Ok(thumbnail)) => {
+            let path = dir.join(".drag-icon.jpg");
+            fs::write(&path, thumbnail)?;
+            Some(path.to_string_lossy().to_string())
+        }
+        _ => None,
+    };
+    Ok(DragFiles { files, icon })
+}
+
+// 前回までのドラッグで作った一時フォルダのうち、古いものを削除する
+pub fn purge_old(root: &Path) {
+    for entry in fs::read_dir(root).into_iter().flatten().flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age >= KEEP_FOR);
+        if expired {
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                log::warn!("Failed to remove {}: {}", entry.path().display(), e);
+            }
+        }
+    }
+}
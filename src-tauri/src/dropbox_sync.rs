@@ -0,0 +1,240 @@
+use crate::oauth::OAuthClient;
+use crate::sync::{RemoteObject, SyncBackend};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const API_URL: &str = "https://api.dropboxapi.com/2";
+const CONTENT_URL: &str = "https://content.dropboxapi.com/2";
+// これより大きいファイルはアップロードセッションで分割して送る
+const UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DropboxConfig {
+    // アプリフォルダ内の保存先
+    #[serde(default = "default_folder")]
+    pub folder: String,
+}
+
+fn default_folder() -> String {
+    "SnapOrganizer".to_string()
+}
+
+#[derive(Deserialize)]
+struct ListFolderResult {
+    entries: Vec<Value>,
+    cursor: String,
+    has_more: bool,
+}
+
+// Dropbox-API-Arg ヘッダは ASCII のみ許されるため、非 ASCII 文字を \uXXXX に変換する
+fn api_arg(value: &Value) -> String {
+    let mut escaped = String::new();
+    for c in value.to_string().chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else {
+            let mut buf = [0u16; 2];
+            for unit in c.encode_utf16(&mut buf) {
+                escaped.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    escaped
+}
+
+pub struct DropboxBackend {
+    auth: OAuthClient,
+    client: Client,
+    // 例: /SnapOrganizer
+    root: String,
+}
+
+impl DropboxBackend {
+    pub fn new(config: DropboxConfig, auth: OAuthClient) -> Result<Self> {
+        let folder = config.folder.trim_matches('/');
+        Ok(DropboxBackend {
+            auth,
+            client: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+            root: if folder.is_empty() { String::new() } else { format!("/{}", folder) },
+        })
+    }
+
+    fn path(&self, key: &str) -> String {
+        let key = key.trim_end_matches('/');
+        if key.is_empty() {
+            self.root.clone()
+        } else {
+            format!("{}/{}", self.root, key)
+        }
+    }
+
+    // Dropbox のパスは大文字小文字を区別しないため、比較は小文字で行う
+    fn key_of(&self, path_display: &str) -> Option<String> {
+        let root_len = self.root.len() + 1;
+        if path_display.len() <= root_len
+            || !path_display.is_char_boundary(root_len)
+            || !path_display[..root_len].eq_ignore_ascii_case(&format!("{}/", self.root))
+        {
+            return None;
+        }
+        Some(path_display[root_len..].to_string())
+    }
+
+    fn send(&self, request: RequestBuilder, what: &str) -> Result<Response> {
+        let response = request
+            .bearer_auth(self.auth.access_token()?)
+            .send()
+            .with_context(|| format!("Dropbox request failed: {}", what))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().unwrap_or_default();
+            bail!("Dropbox {} returned {}: {}", what, status, text.chars().take(500).collect::<String>());
+        }
+        Ok(response)
+    }
+
+    fn rpc(&self, endpoint: &str, body: Value) -> Result<Response> {
+        self.send(self.client.post(format!("{}/{}", API_URL, endpoint)).json(&body), endpoint)
+    }
+
+    fn content(&self, endpoint: &str, arg: Value, data: Vec<u8>) -> Result<Response> {
+        self.send(
+            self.client
+                .post(format!("{}/{}", CONTENT_URL, endpoint))
+                .header("Dropbox-API-Arg", api_arg(&arg))
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .body(data),
+            endpoint,
+        )
+    }
+
+    fn rev_of(response: Response) -> Result<String> {
+        let metadata: Value = response.json()?;
+        metadata["rev"]
+            .as_str()
+            .map(|rev| rev.to_string())
+            .context("Dropbox did not return a revision")
+    }
+}
+
+impl SyncBackend for DropboxBackend {
+    fn remote_id(&self) -> String {
+        format!("dropbox:{}", self.root)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        let response = self
+            .client
+            .post(format!("{}/files/list_folder", API_URL))
+            .bearer_auth(self.auth.access_token()?)
+            .json(&json!({ "path": self.path(prefix), "recursive": true, "limit": 2000 }))
+            .send()
+            .context("Dropbox request failed: files/list_folder")?;
+        // フォルダがまだない場合は 409 (path/not_found)
+        if response.status() == StatusCode::CONFLICT {
+            let text = response.text().unwrap_or_default();
+            if text.contains("not_found") {
+                return Ok(Vec::new());
+            }
+            bail!("Dropbox files/list_folder failed: {}", text);
+        }
+        if !response.status().is_success() {
+            bail!("Dropbox files/list_folder returned {}", response.status());
+        }
+
+        let mut objects = Vec::new();
+        let mut page: ListFolderResult = response.json()?;
+        loop {
+            for entry in &page.entries {
+                if entry[".tag"] != "file" {
+                    continue;
+                }
+                let key = match entry["path_display"].as_str().and_then(|p| self.key_of(p)) {
+                    Some(key) => key,
+                    None => continue,
+                };
+                objects.push(RemoteObject {
+                    key,
+                    etag: entry["rev"].as_str().unwrap_or_default().to_string(),
+                    size: entry["size"].as_u64().unwrap_or(0),
+                    modified: entry["server_modified"]
+                        .as_str()
+                        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                        .map(|d| d.with_timezone(&Utc)),
+                });
+            }
+            if !page.has_more {
+                break;
+            }
+            page = self
+                .rpc("files/list_folder/continue", json!({ "cursor": page.cursor }))?
+                .json()?;
+        }
+        Ok(objects)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let response = self.content("files/download", json!({ "path": self.path(key) }), Vec::new())?;
+        Ok(response.bytes()?.to_vec())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        let commit = json!({ "path": self.path(key), "mode": "overwrite", "mute": true });
+        if data.len() <= UPLOAD_CHUNK_SIZE {
+            return Self::rev_of(self.content("files/upload", commit, data.to_vec())?);
+        }
+
+        let mut chunks = data.chunks(UPLOAD_CHUNK_SIZE);
+        let first = chunks.next().unwrap_or_default();
+        let started: Value = self
+            .content("files/upload_session/start", json!({ "close": false }), first.to_vec())?
+            .json()?;
+        let session_id = started["session_id"]
+            .as_str()
+            .context("Dropbox did not return an upload session")?
+            .to_string();
+
+        let mut offset = first.len();
+        for chunk in chunks {
+            self.content(
+                "files/upload_session/append_v2",
+                json!({ "cursor": { "session_id": session_id, "offset": offset }, "close": false }),
+                chunk.to_vec(),
+            )?;
+            offset += chunk.len();
+        }
+        let finished = self.content(
+            "files/upload_session/finish",
+            json!({ "cursor": { "session_id": session_id, "offset": offset }, "commit": commit }),
+            Vec::new(),
+        )?;
+        Self::rev_of(finished)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("{}/files/delete_v2", API_URL))
+            .bearer_auth(self.auth.access_token()?)
+            .json(&json!({ "path": self.path(key) }))
+            .send()
+            .context("Dropbox request failed: files/delete_v2")?;
+        if response.status() == StatusCode::CONFLICT {
+            let text = response.text().unwrap_or_default();
+            if text.contains("not_found") {
+                return Ok(());
+            }
+            bail!("Dropbox files/delete_v2 failed: {}", text);
+        }
+        if !response.status().is_success() {
+            bail!("Dropbox files/delete_v2 returned {}", response.status());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+use crate::embeddings::EmbeddingService;
+use crate::hashing;
+use crate::jobs::ProgressReporter;
+use crate::metadata_store::{ImageFingerprint, ItemFilter, ItemRecord, MetadataStore};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+
+// 知覚ハッシュ（64bit）のハミング距離がこれ以下なら似た画像とみなす
+pub const DEFAULT_SIMILARITY_THRESHOLD: u32 = 6;
+// 画像の埋め込みのコサイン類似度がこれ以上なら同じ書類の撮り直しとみなす
+pub const DEFAULT_EMBEDDING_THRESHOLD: f32 = 0.93;
+// 埋め込みの近傍索引から1件ごとに取り出す候補の数
+const EMBEDDING_NEIGHBORS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateKind {
+    // 内容が完全に一致
+    Exact,
+    // 見た目が似ている（リサイズ・再圧縮・スクリーンショットの撮り直しなど）
+    Similar,
+    // 同じ書類を別の角度・明るさで撮ったもの（知覚ハッシュは違うが画像の埋め込みが近い）
+    Near,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCandidate {
+    pub item_id: String,
+    pub image_path: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub file_size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    // 残すことを勧めるアイテム（解像度が最も高く、同じなら最も古いもの）
+    pub keeper: String,
+    pub items: Vec<DuplicateCandidate>,
+}
+
+// dHash: 9x8 のグレースケールに縮小し、横に隣り合う画素の明暗を 64bit に並べる
+fn perceptual_hash(img: &image::DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, image::imageops::FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+fn compute_fingerprint(path: &Path, content_hash: Option<&str>) -> Result<ImageFingerprint> {
+    let content_hash = match content_hash {
+        Some(hash) => hash.to_string(),
+        None => hashing::file_hash(path)?,
+    };
+    let img = image::open(path).with_context(|| format!("Failed to decode {}", path.display()))?;
+    Ok(ImageFingerprint {
+        content_hash,
+        perceptual_hash: perceptual_hash(&img),
+        width: img.width(),
+        height: img.height(),
+    })
+}
+
+#[derive(Clone)]
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        UnionFind {
+            parent: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+fn to_group(kind: DuplicateKind, mut items: Vec<DuplicateCandidate>) -> DuplicateGroup {
+    items.sort_by(|a, b| {
+        let pixels = |c: &DuplicateCandidate| c.width as u64 * c.height as u64;
+        pixels(b).cmp(&pixels(a)).then(a.created_at.cmp(&b.created_at))
+    });
+    DuplicateGroup {
+        kind,
+        keeper: items[0].item_id.clone(),
+        items,
+    }
+}
+
+// ライブラリ全体の重複を探す（特徴は計算済みのものを再利用し、未計算・内容が変わったものだけ計算する）
+// embeddings を渡すと、埋め込みの類似度が embedding_threshold 以上の撮り直しもまとめる
+pub fn scan_duplicates(
+    store: &Mutex<Option<MetadataStore>>,
+    threshold: u32,
+    embeddings: Option<&EmbeddingService>,
+    embedding_threshold: f32,
+    reporter: &dyn ProgressReporter,
+) -> Result<Vec<DuplicateGroup>> {
+    let (items, fingerprints): (Vec<ItemRecord>, HashMap<String, ImageFingerprint>) = {
+        let store = store.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        (store.list_items(&ItemFilter::default())?, store.fingerprints()?)
+    };
+    let items: Vec<ItemRecord> = items.into_iter().filter(|item| item.image_path.is_some()).collect();
+    reporter.set_total(items.len() as u64);
+
+    // 画像のデコードは重いため、ロックを持たずに計算してから1件ずつ保存する
+    let mut candidates = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        reporter.checkpoint()?;
+        let path = Path::new(item.image_path.as_deref().unwrap_or_default());
+        let cached = fingerprints
+            .get(&item.id)
+            .filter(|f| item.content_hash.is_none() || item.content_hash.as_ref() == Some(&f.content_hash));
+        let fingerprint = match cached {
+            Some(fingerprint) => fingerprint.clone(),
+            None => match compute_fingerprint(path, item.content_hash.as_deref()) {
+                Ok(fingerprint) => {
+                    let mut store = store.lock().unwrap();
+                    if let Some(store) = store.as_mut() {
+                        store.set_fingerprint(&item.id, &fingerprint)?;
+                    }
+                    fingerprint
+                }
+                Err(e) => {
+                    log::warn!("Skipping {} in duplicate scan: {}", path.display(), e);
+                    continue;
+                }
+            },
+        };
+        let candidate = DuplicateCandidate {
+            item_id: item.id.clone(),
+            image_path: item.image_path.clone(),
+            width: fingerprint.width,
+            height: fingerprint.height,
+            file_size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            created_at: item.created_at,
+        };
+        candidates.push((candidate, fingerprint));
+        reporter.progress(i as u64 + 1, &item.id);
+    }
+
+    // 完全一致: 同じ内容ハッシュをまとめる
+    let mut by_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (_, fingerprint)) in candidates.iter().enumerate() {
+        by_hash.entry(fingerprint.content_hash.as_str()).or_default().push(i);
+    }
+
+    // 似た画像: 完全一致のまとまりごとに代表1件を選び、知覚ハッシュの距離で連結する
+    // （件数が数万程度なら総当たりでも 64bit の比較なので十分速い）
+    let representatives: Vec<usize> = by_hash.values().map(|indices| indices[0]).collect();
+    let mut union_find = UnionFind::new(representatives.len());
+    for a in 0..representatives.len() {
+        for b in (a + 1)..representatives.len() {
+            let hash_a = candidates[representatives[a]].1.perceptual_hash;
+            let hash_b = candidates[representatives[b]].1.perceptual_hash;
+            if (hash_a ^ hash_b).count_ones() <= threshold {
+                union_find.union(a, b);
+            }
+        }
+    }
+
+    // 撮り直し: 埋め込みの近傍で類似度の高いものを、知覚ハッシュのまとまりどうしで連結する
+    // （近傍索引に埋め込みのないアイテムは知覚ハッシュだけで判定する）
+    let mut near_find = union_find.clone();
+    if let Some(embeddings) = embeddings {
+        let mut representative_of = HashMap::new();
+        for (r, &index) in representatives.iter().enumerate() {
+            for &i in &by_hash[candidates[index].1.content_hash.as_str()] {
+                representative_of.insert(candidates[i].0.item_id.as_str(), r);
+            }
+        }
+        for (r, &index) in representatives.iter().enumerate() {
+            reporter.checkpoint()?;
+            let Some(vector) = embeddings.vector(&candidates[index].0.item_id) else {
+                continue;
+            };
+            // 近傍は類似度の高い順
+            for (id, score) in embeddings.search(&vector, EMBEDDING_NEIGHBORS) {
+                if score < embedding_threshold {
+                    break;
+                }
+                if let Some(&other) = representative_of.get(id.as_str()) {
+                    near_find.union(r, other);
+                }
+            }
+        }
+    }
+
+    let mut groups = Vec::new();
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for r in 0..representatives.len() {
+        clusters.entry(near_find.find(r)).or_default().push(r);
+    }
+    for members in clusters.values() {
+        let hash_of = |r: usize| candidates[representatives[r]].1.content_hash.as_str();
+        if members.len() > 1 {
+            // 知覚ハッシュのまとまりを埋め込みでつないだものは撮り直しとする
+            let similar_roots: HashSet<usize> = members.iter().map(|&r| union_find.find(r)).collect();
+            let kind = if similar_roots.len() > 1 {
+                DuplicateKind::Near
+            } else {
+                DuplicateKind::Similar
+            };
+            // 似た画像のまとまりには、各代表と完全一致するものもすべて含める
+            let items = members
+                .iter()
+                .flat_map(|&r| by_hash[hash_of(r)].iter())
+                .map(|&i| candidates[i].0.clone())
+                .collect();
+            groups.push(to_group(kind, items));
+        } else {
+            let exact = &by_hash[hash_of(members[0])];
+            if exact.len() > 1 {
+                let items = exact.iter().map(|&i| candidates[i].0.clone()).collect();
+                groups.push(to_group(DuplicateKind::Exact, items));
+            }
+        }
+    }
+
+    // 削除候補の多いまとまりから確認できるようにする
+    groups.sort_by(|a, b| b.items.len().cmp(&a.items.len()));
+    Ok(groups)
+}
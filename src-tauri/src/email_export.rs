@@ -0,0 +1,200 @@
+use crate::metadata_store::ItemRecord;
+use crate::secrets;
+use crate::table_export::extract_amounts;
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use image::codecs::jpeg::JpegEncoder;
+use image::GenericImageView;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// 添付画像の長辺（メールサーバーのサイズ制限に収まるよう縮小する）
+const ATTACHMENT_MAX_DIMENSION: u32 = 1600;
+const ATTACHMENT_QUALITY: u8 = 80;
+// 添付の合計がこれを超えたら送らない（多くのメールサービスの上限は 20〜25MB）
+const MAX_TOTAL_ATTACHMENT_BYTES: usize = 20 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    // 587 番ポートなど（STARTTLS）
+    #[default]
+    StartTls,
+    // 465 番ポートなど（最初から TLS）
+    Tls,
+    // 暗号化なし（ローカルのリレー用）
+    None,
+}
+
+// SMTP の設定（パスワードを含むためバックアップには含めない）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    // 差出人（例: "山田 太郎 <taro@example.com>"）
+    pub from: String,
+}
+
+impl SmtpSettings {
+    // パスワードは OS のキーチェーンに置く
+    pub fn load(path: &Path) -> Self {
+        let mut settings: Self = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        secrets::fill(path, "smtp-password", &mut settings.password);
+        settings
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut settings = self.clone();
+        secrets::stash(path, "smtp-password", &mut settings.password);
+        fs::write(path, serde_json::to_string_pretty(&settings)?)?;
+        Ok(())
+    }
+
+    fn transport(&self) -> Result<SmtpTransport> {
+        if self.host.trim().is_empty() {
+            bail!("SMTP server is not configured");
+        }
+        let host = self.host.trim();
+        let mut builder = match self.security {
+            SmtpSecurity::StartTls => SmtpTransport::starttls_relay(host)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(host)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(host),
+        };
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if !self.username.is_empty() {
+            builder = builder.credentials(Credentials::new(self.username.clone(), self.password.clone()));
+        }
+        Ok(builder.build())
+    }
+
+    // 設定画面の「接続テスト」用
+    pub fn test_connection(&self) -> Result<()> {
+        if !self.transport()?.test_connection()? {
+            bail!("Could not connect to {}", self.host);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct EmailReport {
+    pub recipients: usize,
+    pub attachments: usize,
+    pub attachment_bytes: usize,
+    // 画像がない・読み込めなかったアイテム
+    pub skipped_item_ids: Vec<String>,
+}
+
+fn resize_for_mail(path: &Path) -> Result<Vec<u8>> {
+    let img = image::open(path).with_context(|| format!("Failed to decode {}", path.display()))?;
+    let img = if img.width().max(img.height()) > ATTACHMENT_MAX_DIMENSION {
+        img.resize(ATTACHMENT_MAX_DIMENSION, ATTACHMENT_MAX_DIMENSION, image::imageops::FilterType::Lanczos3)
+    } else {
+        img
+    };
+    let rgb = img.to_rgb8();
+    let (width, height) = img.dimensions();
+    let mut encoded = Vec::new();
+    JpegEncoder::new_with_quality(&mut encoded, ATTACHMENT_QUALITY).encode(
+        rgb.as_raw(),
+        width,
+        height,
+        image::ColorType::Rgb8,
+    )?;
+    Ok(encoded)
+}
+
+// 本文: アイテムごとの日付・タグ・メモと、OCR から読み取った金額の合計
+fn summary(items: &[(String, &ItemRecord)]) -> String {
+    let mut body = String::new();
+    let mut total = 0u64;
+    for (i, (file_name, item)) in items.iter().enumerate() {
+        body.push_str(&format!("{}. {}", i + 1, item.created_at.with_timezone(&Local).format("%Y-%m-%d")));
+        if !item.tags.is_empty() {
+            body.push_str(&format!(" [{}]", item.tags.join(", ")));
+        }
+        body.push_str(&format!(" ({})\n", file_name));
+        if let Some(amount) = extract_amounts(&item.ocr_text).into_iter().max() {
+            body.push_str(&format!("   金額: ¥{}\n", amount));
+            total += amount;
+        }
+        for line in item.memo.lines().filter(|l| !l.trim().is_empty()) {
+            body.push_str(&format!("   {}\n", line.trim()));
+        }
+        body.push('\n');
+    }
+    if total > 0 {
+        body.push_str(&format!("合計: ¥{}\n", total));
+    }
+    body
+}
+
+// アイテムの画像を縮小して添付し、メモの一覧を本文にしたメールを送る
+pub fn email_items(settings: &SmtpSettings, items: &[ItemRecord], to: &[String], subject: &str) -> Result<EmailReport> {
+    if to.is_empty() {
+        bail!("No recipients");
+    }
+    let mut report = EmailReport::default();
+    let mut attached = Vec::new();
+    let mut attachments = Vec::new();
+    for item in items {
+        let data = match item.image_path.as_deref().map(|p| resize_for_mail(Path::new(p))) {
+            Some(Ok(data)) => data,
+            Some(Err(e)) => {
+                log::warn!("Skipping attachment for {}: {}", item.id, e);
+                report.skipped_item_ids.push(item.id.clone());
+                continue;
+            }
+            None => {
+                report.skipped_item_ids.push(item.id.clone());
+                continue;
+            }
+        };
+        report.attachment_bytes += data.len();
+        if report.attachment_bytes > MAX_TOTAL_ATTACHMENT_BYTES {
+            bail!("Attachments exceed {} MB; select fewer items", MAX_TOTAL_ATTACHMENT_BYTES / 1024 / 1024);
+        }
+        let date = item.created_at.with_timezone(&Local).format("%Y%m%d");
+        let short_id: String = item.id.chars().take(8).collect();
+        let file_name = format!("{}_{}.jpg", date, short_id);
+        attachments.push(Attachment::new(file_name.clone()).body(data, ContentType::parse("image/jpeg")?));
+        attached.push((file_name, item));
+    }
+
+    let mut multipart = MultiPart::mixed().singlepart(SinglePart::plain(summary(&attached)));
+    for attachment in attachments {
+        multipart = multipart.singlepart(attachment);
+    }
+    let mut message = Message::builder()
+        .from(settings.from.parse::<Mailbox>().context("Invalid sender address")?)
+        .subject(subject);
+    for address in to {
+        message = message.to(address.parse::<Mailbox>().with_context(|| format!("Invalid address: {}", address))?);
+    }
+    let message = message.multipart(multipart)?;
+
+    settings.transport()?.send(&message)?;
+    report.recipients = to.len();
+    report.attachments = attached.len();
+    Ok(report)
+}
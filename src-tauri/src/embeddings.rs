@@ -0,0 +1,151 @@
+use crate::inference::{InferenceInput, InferenceService};
+use crate::models;
+use crate::vector_index::{self, VectorIndex};
+use anyhow::{anyhow, Context, Result};
+use image::imageops::FilterType;
+use image::DynamicImage;
+use ndarray::ArrayD;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokenizers::Tokenizer;
+
+pub const FEATURE: &str = "semantic_search";
+const IMAGE_MODEL: &str = "clip-vit-b32-vision";
+const TEXT_MODEL: &str = "clip-vit-b32-text";
+const TOKENIZER: &str = "clip-vit-b32-tokenizer";
+const DIMENSIONS: usize = 512;
+
+// CLIP の前処理（224x224、ImageNet ではなく CLIP の学習時の平均・標準偏差で正規化）
+const IMAGE_SIZE: u32 = 224;
+const MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+const STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+// CLIP のテキストの最大トークン数（開始・終了のトークンを含む）
+const MAX_TOKENS: usize = 77;
+
+fn pixel_values(image: &DynamicImage) -> Result<InferenceInput> {
+    // 短い辺を合わせて中央を切り出す
+    let image = image.resize_to_fill(IMAGE_SIZE, IMAGE_SIZE, FilterType::CatmullRom).to_rgb8();
+    let plane = (IMAGE_SIZE * IMAGE_SIZE) as usize;
+    let mut data = vec![0f32; plane * 3];
+    for (i, pixel) in image.pixels().enumerate() {
+        for c in 0..3 {
+            data[c * plane + i] = (pixel[c] as f32 / 255.0 - MEAN[c]) / STD[c];
+        }
+    }
+    InferenceInput::f32(&[1, 3, IMAGE_SIZE as usize, IMAGE_SIZE as usize], data)
+}
+
+fn output_vector(mut outputs: HashMap<String, ArrayD<f32>>, name: &str) -> Result<Vec<f32>> {
+    let output = outputs.remove(name).with_context(|| format!("Model has no {} output", name))?;
+    let mut vector: Vec<f32> = output.iter().copied().collect();
+    vector_index::normalize(&mut vector);
+    Ok(vector)
+}
+
+// 画像と文章の埋め込み（CLIP）と、画像の埋め込みの近傍探索の索引
+// 索引は初めて使うときに読み込み、変更は flush で保存する
+pub struct EmbeddingService {
+    inference: Arc<InferenceService>,
+    index_path: PathBuf,
+    index: Mutex<Option<VectorIndex>>,
+    tokenizer: Mutex<Option<Arc<Tokenizer>>>,
+}
+
+impl EmbeddingService {
+    pub fn new(inference: Arc<InferenceService>, index_path: PathBuf) -> Self {
+        EmbeddingService {
+            inference,
+            index_path,
+            index: Mutex::new(None),
+            tokenizer: Mutex::new(None),
+        }
+    }
+
+    // モデルがダウンロード済みか（なければ取り込み時に埋め込みを計算しない）
+    pub fn is_available(&self) -> bool {
+        self.inference.models().feature_installed(FEATURE)
+    }
+
+    pub fn embed_image(&self, image: &DynamicImage) -> Result<Vec<f32>> {
+        let outputs = self.inference.run(IMAGE_MODEL, vec![("pixel_values", pixel_values(image)?)])?;
+        output_vector(outputs, "image_embeds")
+    }
+
+    fn tokenizer(&self) -> Result<Arc<Tokenizer>> {
+        let mut tokenizer = self.tokenizer.lock().unwrap();
+        if let Some(tokenizer) = tokenizer.as_ref() {
+            return Ok(tokenizer.clone());
+        }
+        let path = self.inference.models().path(TOKENIZER);
+        let loaded = Arc::new(Tokenizer::from_file(&path).map_err(|e| anyhow!("Failed to load tokenizer: {}", e))?);
+        *tokenizer = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    pub fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        let encoding = self
+            .tokenizer()?
+            .encode(text, true)
+            .map_err(|e| anyhow!("Failed to tokenize text: {}", e))?;
+        let mut ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
+        // 長すぎる文は終了のトークンを残して切り詰める
+        if ids.len() > MAX_TOKENS {
+            let end = *ids.last().unwrap();
+            ids.truncate(MAX_TOKENS - 1);
+            ids.push(end);
+        }
+        let len = ids.len();
+        let outputs = self.inference.run(
+            TEXT_MODEL,
+            vec![
+                ("input_ids", InferenceInput::i64(&[1, len], ids)?),
+                ("attention_mask", InferenceInput::i64(&[1, len], vec![1; len])?),
+            ],
+        )?;
+        output_vector(outputs, "text_embeds")
+    }
+
+    pub fn with_index<R>(&self, f: impl FnOnce(&mut VectorIndex) -> R) -> R {
+        let mut index = self.index.lock().unwrap();
+        let index = index.get_or_insert_with(|| VectorIndex::open(&self.index_path, IMAGE_MODEL, DIMENSIONS));
+        f(index)
+    }
+
+    pub fn add(&self, item_id: &str, vector: Vec<f32>) -> Result<()> {
+        self.with_index(|index| index.insert(item_id, vector))
+    }
+
+    pub fn remove(&self, item_id: &str) {
+        self.with_index(|index| index.remove(item_id));
+    }
+
+    pub fn vector(&self, item_id: &str) -> Option<Vec<f32>> {
+        self.with_index(|index| index.vector(item_id).map(<[f32]>::to_vec))
+    }
+
+    pub fn search(&self, vector: &[f32], limit: usize) -> Vec<(String, f32)> {
+        self.with_index(|index| index.search(vector, limit))
+    }
+
+    // 変更があれば索引をファイルへ保存する
+    pub fn flush(&self) -> Result<()> {
+        let mut index = self.index.lock().unwrap();
+        match index.as_mut() {
+            Some(index) if index.is_dirty() => index.save(&self.index_path),
+            _ => Ok(()),
+        }
+    }
+
+    // モデルを消したときは読み込み済みのトークナイザーも捨てる
+    pub fn unload(&self) {
+        *self.tokenizer.lock().unwrap() = None;
+    }
+}
+
+// 意味での検索に使うモデルか（ダウンロード・削除したときに読み込み済みのものを捨てる）
+pub fn is_model(model_id: &str) -> bool {
+    models::CATALOG
+        .iter()
+        .any(|spec| spec.id == model_id && spec.feature == FEATURE)
+}
@@ -0,0 +1,309 @@
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+// 伝票番号の前後にあることが多い語（数字だけの番号は、これがある行でだけ追跡番号とみなす）
+const TRACKING_LABELS: &[&str] = &["伝票番号", "追跡番号", "問い合わせ番号", "問合せ番号", "お問合せ番号", "送り状", "tracking"];
+const PREFECTURES: &[&str] = &[
+    "北海道", "青森県", "岩手県", "宮城県", "秋田県", "山形県", "福島県", "茨城県", "栃木県", "群馬県", "埼玉県", "千葉県",
+    "東京都", "神奈川県", "新潟県", "富山県", "石川県", "福井県", "山梨県", "長野県", "岐阜県", "静岡県", "愛知県", "三重県",
+    "滋賀県", "京都府", "大阪府", "兵庫県", "奈良県", "和歌山県", "鳥取県", "島根県", "岡山県", "広島県", "山口県", "徳島県",
+    "香川県", "愛媛県", "高知県", "福岡県", "佐賀県", "長崎県", "熊本県", "大分県", "宮崎県", "鹿児島県", "沖縄県",
+];
+// 住所の後ろに続くことが多いもの（ここで住所を切る）
+const ADDRESS_TERMINATORS: &[&str] = &["tel", "fax", "電話", "ＴＥＬ", "ＦＡＸ", "  ", "\t"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Phone,
+    Email,
+    Url,
+    PostalCode,
+    Tracking,
+    Address,
+}
+
+impl EntityKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntityKind::Phone => "phone",
+            EntityKind::Email => "email",
+            EntityKind::Url => "url",
+            EntityKind::PostalCode => "postal_code",
+            EntityKind::Tracking => "tracking",
+            EntityKind::Address => "address",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "phone" => Some(EntityKind::Phone),
+            "email" => Some(EntityKind::Email),
+            "url" => Some(EntityKind::Url),
+            "postal_code" => Some(EntityKind::PostalCode),
+            "tracking" => Some(EntityKind::Tracking),
+            "address" => Some(EntityKind::Address),
+            _ => None,
+        }
+    }
+}
+
+// OCR のテキストから見つけた電話番号・メールアドレスなど
+// value は検索用に正規化した値（電話番号は数字だけ、メールアドレスは小文字など）、text は見つけたままの文字
+// link はタップしたときに開く先（tel: / mailto: / 配送業者の追跡ページ / 地図）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub value: String,
+    pub text: String,
+    pub link: Option<String>,
+}
+
+fn email_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}").unwrap())
+}
+
+fn url_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)(?:https?://|www\.)[^\s<>「」『』（）()、。]+").unwrap())
+}
+
+fn phone_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?:\+81[\s\-]?|0)[0-9]{1,4}[\s\-‐－(（)）]{0,2}[0-9]{1,4}[\s\-‐－(（)）]{0,2}[0-9]{3,4}").unwrap()
+    })
+}
+
+fn postal_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(〒\s*)?([0-9]{3})[\-‐－]([0-9]{4})|〒\s*([0-9]{3})([0-9]{4})").unwrap())
+}
+
+// 国際郵便の番号（AB123456789JP の形式）
+fn s10_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"([A-Z]{2})([0-9]{8})([0-9])([A-Z]{2})").unwrap())
+}
+
+fn tracking_number_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"[0-9]{4}[\s\-]?[0-9]{4}[\s\-]?[0-9]{3,6}").unwrap())
+}
+
+// 前後が英数字に続いていない（長い番号の一部を拾わない）
+fn standalone(text: &str, m: &regex::Match) -> bool {
+    let before = text[..m.start()].chars().next_back();
+    let after = text[m.end()..].chars().next();
+    !before.is_some_and(|c| c.is_ascii_alphanumeric()) && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+}
+
+fn digits(text: &str) -> String {
+    text.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+fn phone_value(text: &str) -> Option<String> {
+    let digits = digits(text);
+    let value = match digits.strip_prefix("81") {
+        Some(rest) if text.trim_start().starts_with('+') => format!("0{}", rest),
+        _ => digits,
+    };
+    // 固定電話・携帯電話・フリーダイヤルの桁数
+    (value.starts_with('0') && (10..=11).contains(&value.len())).then_some(value)
+}
+
+// 万国郵便連合の S10 形式のチェックディジット
+fn s10_check_digit(serial: &str) -> Option<u32> {
+    const WEIGHTS: [u32; 8] = [8, 6, 4, 2, 3, 5, 9, 7];
+    let sum: u32 = serial
+        .chars()
+        .zip(WEIGHTS)
+        .map(|(c, w)| c.to_digit(10).map(|d| d * w))
+        .sum::<Option<u32>>()?;
+    Some(match 11 - sum % 11 {
+        10 => 0,
+        11 => 5,
+        d => d,
+    })
+}
+
+// ヤマト運輸の伝票番号（12桁、先頭11桁を7で割った余りが末尾の桁）
+fn is_yamato_number(number: &str) -> bool {
+    number.len() == 12
+        && number[..11].parse::<u64>().is_ok_and(|n| Some((n % 7) as u32) == number[11..].parse().ok())
+}
+
+// 番号の形から配送業者を推定して追跡ページを返す（日本郵便は国際郵便と11桁・13桁の番号）
+fn tracking_link(number: &str) -> Option<String> {
+    let japan_post = number.ends_with("JP") || number.len() == 11 || number.len() == 13;
+    if japan_post {
+        Some(format!("https://trackings.post.japanpost.jp/services/srv/search/direct?reqCodeNo1={}", number))
+    } else if is_yamato_number(number) {
+        Some(format!("https://toi.kuronekoyamato.co.jp/cgi-bin/tneko?number01={}", number))
+    } else if number.len() == 12 {
+        Some(format!("https://k2k.sagawa-exp.co.jp/p/web/okurijosearch.do?okurijoNo={}", number))
+    } else {
+        None
+    }
+}
+
+fn trim_url(url: &str) -> &str {
+    url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"'])
+}
+
+// 行の中の住所（都道府県か〒の後ろに市区町村が続くもの）
+// 都道府県のない住所は〒の後ろにあるものだけ拾う（市区町村の名前だけでは住所と見分けられない）
+fn line_address(line: &str) -> Option<String> {
+    let start = PREFECTURES
+        .iter()
+        .filter_map(|prefecture| line.find(prefecture))
+        .min()
+        .or_else(|| postal_pattern().find(line).filter(|m| m.as_str().contains('〒')).map(|m| m.end()))?;
+    let rest = &line[start..];
+    let lower = rest.to_lowercase();
+    let end = ADDRESS_TERMINATORS
+        .iter()
+        .filter_map(|t| lower.find(&t.to_lowercase()))
+        .min()
+        .unwrap_or(rest.len());
+    // 小文字にすると長さの変わる文字があれば、文字の境目でない位置で切らないよう全体を使う
+    let address = rest.get(..end).unwrap_or(rest).trim().trim_end_matches(['、', ',', '　']);
+    let has_locality = address.chars().skip(2).any(|c| matches!(c, '市' | '区' | '町' | '村' | '郡'));
+    (has_locality && address.chars().count() >= 5).then(|| address.to_string())
+}
+
+// OCR のテキストから見つけた住所（見つけた順）
+pub fn addresses(text: &str) -> Vec<String> {
+    let mut addresses: Vec<String> = Vec::new();
+    for address in text.lines().filter_map(line_address) {
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    addresses
+}
+
+fn address_value(address: &str) -> String {
+    address.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn push(entities: &mut Vec<Entity>, kind: EntityKind, value: String, text: &str, link: Option<String>) {
+    if !entities.iter().any(|e| e.kind == kind && e.value == value) {
+        entities.push(Entity {
+            kind,
+            value,
+            text: text.trim().to_string(),
+            link,
+        });
+    }
+}
+
+// 電話番号・メールアドレス・URL・郵便番号・追跡番号・住所を見つける（同じものは1つにまとめる）
+pub fn extract(text: &str) -> Vec<Entity> {
+    let mut entities = Vec::new();
+
+    for m in email_pattern().find_iter(text) {
+        let value = m.as_str().to_lowercase();
+        let link = format!("mailto:{}", value);
+        push(&mut entities, EntityKind::Email, value, m.as_str(), Some(link));
+    }
+
+    for m in url_pattern().find_iter(text) {
+        let url = trim_url(m.as_str());
+        let link = if url.to_lowercase().starts_with("www.") {
+            format!("https://{}", url)
+        } else {
+            url.to_string()
+        };
+        push(&mut entities, EntityKind::Url, url.to_string(), url, Some(link));
+    }
+
+    for line in text.lines() {
+        let lower = line.to_lowercase();
+        let labelled = TRACKING_LABELS.iter().any(|label| lower.contains(label));
+
+        // 伝票番号の行の数字は電話番号としては扱わない
+        if labelled {
+            for m in tracking_number_pattern().find_iter(line).filter(|m| standalone(line, m)) {
+                let number = digits(m.as_str());
+                let link = tracking_link(&number);
+                push(&mut entities, EntityKind::Tracking, number, m.as_str(), link);
+            }
+        } else {
+            for m in phone_pattern().find_iter(line).filter(|m| standalone(line, m)) {
+                if let Some(value) = phone_value(m.as_str()) {
+                    let link = format!("tel:{}", value);
+                    push(&mut entities, EntityKind::Phone, value, m.as_str(), Some(link));
+                }
+            }
+        }
+
+        for caps in s10_pattern().captures_iter(line) {
+            if standalone(line, &caps.get(0).unwrap()) && s10_check_digit(&caps[2]) == caps[3].parse().ok() {
+                let number = caps[0].to_string();
+                let link = tracking_link(&number);
+                push(&mut entities, EntityKind::Tracking, number.clone(), &number, link);
+            }
+        }
+
+        for caps in postal_pattern().captures_iter(line) {
+            let whole = caps.get(0).unwrap();
+            let marked = caps.get(1).is_some() || caps.get(4).is_some();
+            // 〒のない 123-4567 は、伝票番号の行や電話番号の一部でないものだけ
+            let in_number = entities.iter().any(|e| e.text.contains(whole.as_str()));
+            if !standalone(line, &whole) || (!marked && (labelled || in_number)) {
+                continue;
+            }
+            let (Some(head), Some(tail)) = (caps.get(2).or(caps.get(4)), caps.get(3).or(caps.get(5))) else {
+                continue;
+            };
+            let value = format!("{}-{}", head.as_str(), tail.as_str());
+            push(&mut entities, EntityKind::PostalCode, value, whole.as_str(), None);
+        }
+    }
+
+    for address in addresses(text) {
+        let link = format!(
+            "https://www.openstreetmap.org/search?query={}",
+            utf8_percent_encode(&address, NON_ALPHANUMERIC)
+        );
+        push(&mut entities, EntityKind::Address, address_value(&address), &address, Some(link));
+    }
+    entities
+}
+
+// 検索語が電話番号・メールアドレスなどの形なら、エンティティのフィールドで完全一致を探すための値にする
+pub fn search_key(query: &str) -> Option<String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return None;
+    }
+    if email_pattern().find(query).is_some_and(|m| m.as_str() == query) {
+        return Some(query.to_lowercase());
+    }
+    if url_pattern().find(query).is_some_and(|m| m.as_str() == query) {
+        return Some(trim_url(query).to_string());
+    }
+    if let Some(caps) = s10_pattern().captures(query).filter(|c| &c[0] == query) {
+        return Some(caps[0].to_string());
+    }
+    let stripped: String = query
+        .chars()
+        .filter(|c| !matches!(c, '-' | '‐' | '－' | ' ' | '(' | ')' | '（' | '）' | '〒'))
+        .collect();
+    if let Some(number) = stripped.strip_prefix('+') {
+        return phone_value(&format!("+{}", number));
+    }
+    if !stripped.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match stripped.len() {
+        // 郵便番号はハイフン付きで保存している
+        7 => Some(format!("{}-{}", &stripped[..3], &stripped[3..])),
+        10..=14 => Some(stripped),
+        _ => None,
+    }
+}
@@ -0,0 +1,219 @@
+use crate::i18n;
+use crate::image_decode::DecodeError;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+
+// ディスクがいっぱいのときの OS のエラーコード（ERROR_HANDLE_DISK_FULL / ERROR_DISK_FULL、ENOSPC）
+#[cfg(windows)]
+const DISK_FULL_OS_ERRORS: &[i32] = &[39, 112];
+#[cfg(not(windows))]
+const DISK_FULL_OS_ERRORS: &[i32] = &[28];
+
+// Tauri コマンドが返すエラー
+// フロントエンドには { code, message, detail, retryable } の JSON で届く
+// code はエラーの種類、message は表示言語に合わせた画面に出す文言、detail は原因の詳細
+#[derive(Debug, Clone)]
+pub enum AppError {
+    // 起動直後でメタデータストアや検索インデックスの準備ができていない
+    NotReady(String),
+    NotFound(String),
+    InvalidInput(String),
+    // 検索インデックスを別のプロセス（CLI など）が書き込み中
+    IndexLocked(String),
+    // データベースを別の処理が使用中
+    DatabaseBusy(String),
+    DiskFull(String),
+    PermissionDenied(String),
+    Network(String),
+    AppLocked,
+    Cancelled,
+    ImageRejected(DecodeError),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotReady(_) => "not_ready",
+            AppError::NotFound(_) => "not_found",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::IndexLocked(_) => "index_locked",
+            AppError::DatabaseBusy(_) => "database_busy",
+            AppError::DiskFull(_) => "disk_full",
+            AppError::PermissionDenied(_) => "permission_denied",
+            AppError::Network(_) => "network",
+            AppError::AppLocked => "app_locked",
+            AppError::Cancelled => "cancelled",
+            AppError::ImageRejected(_) => "image_rejected",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    // 少し待ってやり直せば成功する見込みがある
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            AppError::NotReady(_) | AppError::IndexLocked(_) | AppError::DatabaseBusy(_) | AppError::Network(_)
+        )
+    }
+
+    // 画面に出す文言（設定の表示言語で返す）
+    pub fn message(&self) -> String {
+        i18n::text(&format!("error.{}", self.code()))
+    }
+
+    pub fn detail(&self) -> String {
+        match self {
+            AppError::NotReady(detail)
+            | AppError::NotFound(detail)
+            | AppError::InvalidInput(detail)
+            | AppError::IndexLocked(detail)
+            | AppError::DatabaseBusy(detail)
+            | AppError::DiskFull(detail)
+            | AppError::PermissionDenied(detail)
+            | AppError::Network(detail)
+            | AppError::Internal(detail) => detail.clone(),
+            AppError::AppLocked => "App is locked".to_string(),
+            AppError::Cancelled => "Cancelled".to_string(),
+            AppError::ImageRejected(e) => e.to_string(),
+        }
+    }
+
+    // ok_or("...") などの文字列のエラーは文言から種類を決める
+    fn from_message(message: String) -> Self {
+        let lower = message.to_lowercase();
+        if lower.contains("not initialized") {
+            AppError::NotReady(message)
+        } else if lower.contains("not found") {
+            AppError::NotFound(message)
+        } else if lower.contains("cancelled") {
+            AppError::Cancelled
+        } else if lower.contains("is locked") {
+            AppError::AppLocked
+        } else {
+            AppError::Internal(message)
+        }
+    }
+
+    fn from_io(e: &io::Error, detail: String) -> Option<Self> {
+        if e.raw_os_error().is_some_and(|code| DISK_FULL_OS_ERRORS.contains(&code)) {
+            return Some(AppError::DiskFull(detail));
+        }
+        match e.kind() {
+            io::ErrorKind::NotFound => Some(AppError::NotFound(detail)),
+            io::ErrorKind::PermissionDenied => Some(AppError::PermissionDenied(detail)),
+            io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted => Some(AppError::Network(detail)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.detail())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 5)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.message())?;
+        state.serialize_field("detail", &self.detail())?;
+        state.serialize_field("retryable", &self.retryable())?;
+        // 画像が読めなかった理由（大きすぎる・形式が違うなど）
+        match self {
+            AppError::ImageRejected(e) => state.serialize_field("reason", e)?,
+            _ => state.skip_field("reason")?,
+        }
+        state.end()
+    }
+}
+
+// 原因をたどって種類を決める（分からなければ Internal）
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        let detail = e.to_string();
+        for cause in e.chain() {
+            if let Some(e) = cause.downcast_ref::<AppError>() {
+                return e.clone();
+            }
+            if let Some(e) = cause.downcast_ref::<DecodeError>() {
+                return AppError::ImageRejected(e.clone());
+            }
+            if let Some(error) = cause.downcast_ref::<io::Error>().and_then(|e| AppError::from_io(e, detail.clone())) {
+                return error;
+            }
+            if let Some(rusqlite::Error::SqliteFailure(e, _)) = cause.downcast_ref::<rusqlite::Error>() {
+                match e.code {
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked => {
+                        return AppError::DatabaseBusy(detail)
+                    }
+                    rusqlite::ErrorCode::DiskFull => return AppError::DiskFull(detail),
+                    rusqlite::ErrorCode::PermissionDenied | rusqlite::ErrorCode::ReadOnly => {
+                        return AppError::PermissionDenied(detail)
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(tantivy::TantivyError::LockFailure(..)) = cause.downcast_ref::<tantivy::TantivyError>() {
+                return AppError::IndexLocked(detail);
+            }
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                if e.is_connect() || e.is_timeout() {
+                    return AppError::Network(detail);
+                }
+            }
+        }
+        AppError::from_message(detail)
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(e: io::Error) -> Self {
+        anyhow::Error::from(e).into()
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::InvalidInput(e.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        anyhow::Error::from(e).into()
+    }
+}
+
+impl From<image::ImageError> for AppError {
+    fn from(e: image::ImageError) -> Self {
+        AppError::ImageRejected(e.into())
+    }
+}
+
+impl From<DecodeError> for AppError {
+    fn from(e: DecodeError) -> Self {
+        AppError::ImageRejected(e)
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::from_message(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::from_message(message.to_string())
+    }
+}
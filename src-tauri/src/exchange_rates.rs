@@ -0,0 +1,96 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// 為替レートの取得元（API キー不要、1日1回更新）
+const RATES_URL: &str = "https://open.er-api.com/v6/latest/USD";
+const MAX_AGE_HOURS: i64 = 24;
+
+// 基準通貨に対する各通貨のレート（1 base = rates[code] code）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatesTable {
+    pub base: String,
+    pub fetched_at: DateTime<Utc>,
+    pub rates: HashMap<String, f64>,
+}
+
+impl RatesTable {
+    // 通貨を換算する（どちらかのレートがなければ None）
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Option<f64> {
+        if from == to {
+            return Some(amount);
+        }
+        let from_rate = self.rates.get(from).filter(|r| **r > 0.0)?;
+        let to_rate = self.rates.get(to)?;
+        Some(amount / from_rate * to_rate)
+    }
+
+    fn is_stale(&self) -> bool {
+        Utc::now() - self.fetched_at > Duration::hours(MAX_AGE_HOURS)
+    }
+}
+
+#[derive(Deserialize)]
+struct RatesResponse {
+    result: String,
+    base_code: String,
+    rates: HashMap<String, f64>,
+}
+
+// 為替レートをファイルに保存して使い回す（古くなったら取り直し、取れなければ古いものを使う）
+pub struct ExchangeRates {
+    path: PathBuf,
+    table: Mutex<Option<RatesTable>>,
+}
+
+impl ExchangeRates {
+    pub fn new(path: PathBuf) -> Self {
+        ExchangeRates { path, table: Mutex::new(None) }
+    }
+
+    fn load(&self) -> Option<RatesTable> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    fn fetch(&self) -> Result<RatesTable> {
+        let response: RatesResponse = reqwest::blocking::Client::new()
+            .get(RATES_URL)
+            .header("User-Agent", concat!("SnapOrganizer/", env!("CARGO_PKG_VERSION")))
+            .send()?
+            .error_for_status()?
+            .json()?;
+        if response.result != "success" {
+            bail!("Exchange rate service returned {}", response.result);
+        }
+        let table = RatesTable {
+            base: response.base_code,
+            fetched_at: Utc::now(),
+            rates: response.rates,
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&table)?)?;
+        Ok(table)
+    }
+
+    pub fn rates(&self) -> Result<RatesTable> {
+        let mut table = self.table.lock().unwrap();
+        if table.is_none() {
+            *table = self.load();
+        }
+        if table.as_ref().map_or(true, RatesTable::is_stale) {
+            match self.fetch() {
+                Ok(fetched) => *table = Some(fetched),
+                Err(e) => log::warn!("Failed to update exchange rates: {}", e),
+            }
+        }
+        table.clone().context("Exchange rates are not available")
+    }
+}
@@ -0,0 +1,26 @@
+use exif::{In, Reader, Tag, Value};
+use std::io::Cursor;
+
+// Exif の GPS 情報（緯度・経度）を十進の度で返す
+pub fn read_gps(data: &[u8]) -> Option<(f64, f64)> {
+    let exif = Reader::new().read_from_container(&mut Cursor::new(data)).ok()?;
+    let coordinate = |tag: Tag, ref_tag: Tag| -> Option<f64> {
+        let field = exif.get_field(tag, In::PRIMARY)?;
+        let degrees = match &field.value {
+            Value::Rational(v) if v.len() >= 3 => v[0].to_f64() + v[1].to_f64() / 60.0 + v[2].to_f64() / 3600.0,
+            _ => return None,
+        };
+        let reference = exif
+            .get_field(ref_tag, In::PRIMARY)
+            .map(|f| f.display_value().to_string())
+            .unwrap_or_default();
+        Some(if reference.contains('S') || reference.contains('W') { -degrees } else { degrees })
+    };
+    let latitude = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef)?;
+    let longitude = coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef)?;
+    // 位置を取得できなかった端末は 0,0 を書くことがある
+    if !latitude.is_finite() || !longitude.is_finite() || (latitude == 0.0 && longitude == 0.0) {
+        return None;
+    }
+    Some((latitude, longitude))
+}
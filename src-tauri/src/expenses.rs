@@ -0,0 +1,117 @@
+use crate::exchange_rates::RatesTable;
+use crate::metadata_store::ItemRecord;
+use crate::receipts::ReceiptFields;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+// タグのないアイテムをまとめるキー
+pub const UNTAGGED: &str = "";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpenseTotal {
+    // タグ名、または年月（YYYY-MM）
+    pub key: String,
+    pub total: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpenseSummary {
+    pub currency: String,
+    pub total: f64,
+    pub count: usize,
+    // 複数のタグが付いたアイテムはそれぞれのタグに数える（金額の大きい順）
+    pub by_tag: Vec<ExpenseTotal>,
+    // 購入日（読み取れなければ取り込んだ日）の月ごと（古い順）
+    pub by_month: Vec<ExpenseTotal>,
+    // レートがなくて換算できなかったアイテム（合計には含めない）
+    pub unconverted: Vec<String>,
+    // 換算に使ったレートを取得した日時（換算しなかったときは None）
+    pub rates_fetched_at: Option<DateTime<Utc>>,
+}
+
+fn add(totals: &mut HashMap<String, (f64, usize)>, key: &str, amount: f64) {
+    let entry = totals.entry(key.to_string()).or_insert((0.0, 0));
+    entry.0 += amount;
+    entry.1 += 1;
+}
+
+// 通貨ごとの最小単位に丸める（円は整数、それ以外は小数2桁）
+fn round(amount: f64, currency: &str) -> f64 {
+    match currency {
+        "JPY" | "KRW" => amount.round(),
+        _ => (amount * 100.0).round() / 100.0,
+    }
+}
+
+// レシートの合計を currency に換算して集計する（合計が読み取れていないアイテムは含めない）
+pub fn summarize(
+    items: &[ItemRecord],
+    receipts: &HashMap<String, ReceiptFields>,
+    currency: &str,
+    rates: Option<&RatesTable>,
+) -> ExpenseSummary {
+    let mut total = 0.0;
+    let mut count = 0;
+    let mut by_tag = HashMap::new();
+    let mut by_month = HashMap::new();
+    let mut unconverted = Vec::new();
+    let mut converted = false;
+    for item in items {
+        let Some((receipt, amount)) = receipts.get(&item.id).and_then(|r| r.total.map(|t| (r, t))) else {
+            continue;
+        };
+        let amount = if receipt.currency == currency {
+            amount as f64
+        } else {
+            match rates.and_then(|rates| rates.convert(amount as f64, &receipt.currency, currency)) {
+                Some(amount) => {
+                    converted = true;
+                    amount
+                }
+                None => {
+                    unconverted.push(item.id.clone());
+                    continue;
+                }
+            }
+        };
+        total += amount;
+        count += 1;
+        if item.tags.is_empty() {
+            add(&mut by_tag, UNTAGGED, amount);
+        }
+        for tag in &item.tags {
+            add(&mut by_tag, tag, amount);
+        }
+        let month = match receipt.purchased_on {
+            Some(date) => date.format("%Y-%m").to_string(),
+            None => item.created_at.format("%Y-%m").to_string(),
+        };
+        add(&mut by_month, &month, amount);
+    }
+
+    let to_totals = |totals: HashMap<String, (f64, usize)>| -> Vec<ExpenseTotal> {
+        totals
+            .into_iter()
+            .map(|(key, (total, count))| ExpenseTotal {
+                key,
+                total: round(total, currency),
+                count,
+            })
+            .collect()
+    };
+    let mut by_tag = to_totals(by_tag);
+    by_tag.sort_by(|a, b| b.total.total_cmp(&a.total).then_with(|| a.key.cmp(&b.key)));
+    let by_month: BTreeMap<String, ExpenseTotal> = to_totals(by_month).into_iter().map(|t| (t.key.clone(), t)).collect();
+
+    ExpenseSummary {
+        currency: currency.to_string(),
+        total: round(total, currency),
+        count,
+        by_tag,
+        by_month: by_month.into_values().collect(),
+        unconverted,
+        rates_fetched_at: rates.filter(|_| converted).map(|rates| rates.fetched_at),
+    }
+}
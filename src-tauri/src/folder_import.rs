@@ -0,0 +1,149 @@
+use crate::import_pipeline::{self, DuplicatePolicy, ImportContext, ImportOutcome, ImportProgress};
+use crate::jobs::ProgressReporter;
+use crate::paths::is_image_path;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FolderImportOptions {
+    // サブフォルダもたどる
+    #[serde(default = "default_true")]
+    pub recursive: bool,
+    // 取り込む拡張子（省略時は対応しているすべての画像形式）
+    #[serde(default)]
+    pub extensions: Option<Vec<String>>,
+    // 内容が同じアイテムが既にあるファイルの扱い（省略時は設定の値）
+    #[serde(default)]
+    pub duplicates: Option<DuplicatePolicy>,
+    // 隠しファイル・隠しフォルダ（名前が . で始まるもの）も対象にする
+    #[serde(default)]
+    pub include_hidden: bool,
+    // ホワイトボードの写真として整えた画像を取り込む
+    #[serde(default)]
+    pub whiteboard: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for FolderImportOptions {
+    fn default() -> Self {
+        FolderImportOptions {
+            recursive: true,
+            extensions: None,
+            duplicates: None,
+            include_hidden: false,
+            whiteboard: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub existing_item_id: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FolderImportReport {
+    pub imported: Vec<String>,
+    // 重複として既存のアイテムと関係づけたもの（imported にも含む）
+    pub linked: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+    pub failed: Vec<serde_json::Value>,
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+fn matches_extension(path: &Path, options: &FolderImportOptions) -> bool {
+    if !is_image_path(path) {
+        return false;
+    }
+    match &options.extensions {
+        Some(extensions) => {
+            let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+        }
+        None => true,
+    }
+}
+
+// 取り込み対象のファイルを集める（シンボリックリンクのフォルダはたどらない）
+pub fn collect_files(root: &Path, options: &FolderImportOptions) -> Result<Vec<PathBuf>> {
+    if !root.is_dir() {
+        bail!("Not a folder: {}", root.display());
+    }
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir != root => {
+                log::warn!("Skipping {}: {}", dir.display(), e);
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !options.include_hidden && is_hidden(&path) {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => {
+                    if options.recursive {
+                        dirs.push(path);
+                    }
+                }
+                Ok(_) if matches_extension(&path, options) && path.is_file() => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// フォルダ内の画像を並列に取り込み、1ファイルごとに進捗を通知する
+pub fn import_folder(
+    ctx: &ImportContext,
+    root: &Path,
+    options: &FolderImportOptions,
+    reporter: &(dyn ProgressReporter + Sync),
+    on_progress: &(dyn Fn(ImportProgress) + Sync),
+) -> Result<FolderImportReport> {
+    let files = collect_files(root, options)?;
+    reporter.set_total(files.len() as u64);
+    let ctx = ImportContext {
+        duplicates: options.duplicates.unwrap_or(ctx.duplicates),
+        whiteboard: options.whiteboard,
+        ..*ctx
+    };
+
+    let mut report = FolderImportReport::default();
+    let results = import_pipeline::import_files(&ctx, &files, reporter, on_progress)?;
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(ImportOutcome::Imported(item)) => report.imported.push(item.id),
+            Ok(ImportOutcome::Linked { item, .. }) => {
+                report.imported.push(item.id.clone());
+                report.linked.push(item.id);
+            }
+            Ok(ImportOutcome::Skipped(existing)) => report.skipped.push(SkippedFile {
+                path: path.to_string_lossy().to_string(),
+                existing_item_id: existing.id,
+            }),
+            Err(e) => {
+                report.failed.push(serde_json::json!({ "path": path.to_string_lossy(), "error": e.to_string() }))
+            }
+        }
+    }
+    Ok(report)
+}
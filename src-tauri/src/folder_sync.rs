@@ -0,0 +1,102 @@
+use crate::sync::{RemoteObject, SyncBackend};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// ローカルのフォルダを同期先として扱う（LAN 同期で相手に公開するストアなど）
+pub struct FolderBackend {
+    root: PathBuf,
+}
+
+impl FolderBackend {
+    pub fn new(root: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&root)?;
+        Ok(FolderBackend { root })
+    }
+
+    // ネットワーク越しのキーも受け取るため、ルート外を指すパスは拒否する
+    fn resolve(&self, key: &str) -> Result<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in key.split('/') {
+            if segment.is_empty() || segment == "." || segment == ".." || segment.contains(['\\', ':']) {
+                bail!("Invalid key: {}", key);
+            }
+            path.push(segment);
+        }
+        Ok(path)
+    }
+
+    // 更新日時とサイズから etag を作る（内容のハッシュは大きな画像では重いため）
+    fn etag(path: &Path) -> Result<(String, u64, Option<DateTime<Utc>>)> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified().ok();
+        let nanos = modified
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        Ok((
+            format!("{:x}-{:x}", nanos, metadata.len()),
+            metadata.len(),
+            modified.map(DateTime::<Utc>::from),
+        ))
+    }
+
+    fn walk(&self, dir: &Path, prefix: &str, objects: &mut Vec<RemoteObject>) -> Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let key = format!("{}{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                self.walk(&entry.path(), &format!("{}/", key), objects)?;
+            } else if !name.ends_with(".partial") {
+                let (etag, size, modified) = Self::etag(&entry.path())?;
+                objects.push(RemoteObject { key, etag, size, modified });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SyncBackend for FolderBackend {
+    fn remote_id(&self) -> String {
+        format!("folder:{}", self.root.display())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        let mut objects = Vec::new();
+        let prefix = prefix.trim_end_matches('/');
+        if prefix.is_empty() {
+            self.walk(&self.root, "", &mut objects)?;
+        } else {
+            self.walk(&self.resolve(prefix)?, &format!("{}/", prefix), &mut objects)?;
+        }
+        Ok(objects)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.resolve(key)?)?)
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        let path = self.resolve(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("partial");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(Self::etag(&path)?.0)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.resolve(key)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::oauth::OAuthClient;
+use crate::sync::{RemoteObject, SyncBackend};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const API_URL: &str = "https://www.googleapis.com/drive/v3";
+const UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3";
+const FOLDER_MIME: &str = "application/vnd.google-apps.folder";
+const FILE_FIELDS: &str = "id,name,mimeType,version,size,modifiedTime";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoogleDriveConfig {
+    // マイドライブ直下に作るフォルダ名
+    #[serde(default = "default_folder")]
+    pub folder: String,
+}
+
+fn default_folder() -> String {
+    "SnapOrganizer".to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveFile {
+    id: String,
+    name: String,
+    mime_type: String,
+    // ファイルが変更されるたびに増える番号を etag として使う
+    version: Option<String>,
+    size: Option<String>,
+    modified_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileList {
+    #[serde(default)]
+    files: Vec<DriveFile>,
+    next_page_token: Option<String>,
+}
+
+fn escape_query(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+// "items/abc.json" → ("items/", "abc.json")
+fn split_key(key: &str) -> (String, &str) {
+    match key.rsplit_once('/') {
+        Some((dir, name)) => (format!("{}/", dir), name),
+        None => (String::new(), key),
+    }
+}
+
+// Google Drive はパスではなくIDでファイルを扱うため、フォルダとファイルのIDをキャッシュする
+pub struct GoogleDriveBackend {
+    config: GoogleDriveConfig,
+    auth: OAuthClient,
+    client: Client,
+    // "" がライブラリのルートフォルダ、"items/" などがサブフォルダ
+    folder_ids: Mutex<HashMap<String, String>>,
+    file_ids: Mutex<HashMap<String, String>>,
+}
+
+impl GoogleDriveBackend {
+    pub fn new(config: GoogleDriveConfig, auth: OAuthClient) -> Result<Self> {
+        Ok(GoogleDriveBackend {
+            config,
+            auth,
+            client: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+            folder_ids: Mutex::new(HashMap::new()),
+            file_ids: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn send(&self, request: RequestBuilder, what: &str) -> Result<Response> {
+        let response = request
+            .bearer_auth(self.auth.access_token()?)
+            .send()
+            .with_context(|| format!("Google Drive request failed: {}", what))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().unwrap_or_default();
+            bail!("Google Drive {} returned {}: {}", what, status, text.chars().take(500).collect::<String>());
+        }
+        Ok(response)
+    }
+
+    fn query(&self, q: &str) -> Result<Vec<DriveFile>> {
+        let mut files = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut params = vec![
+                ("q", q.to_string()),
+                ("fields", format!("nextPageToken,files({})", FILE_FIELDS)),
+                ("pageSize", "1000".to_string()),
+                ("spaces", "drive".to_string()),
+            ];
+            if let Some(token) = page_token.take() {
+                params.push(("pageToken", token));
+            }
+            let page: FileList = self
+                .send(self.client.get(format!("{}/files", API_URL)).query(&params), "list files")?
+                .json()?;
+            files.extend(page.files);
+            match page.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        Ok(files)
+    }
+
+    fn find_child(&self, parent_id: &str, name: &str, folder: bool) -> Result<Option<DriveFile>> {
+        let mime = if folder { format!(" and mimeType = '{}'", FOLDER_MIME) } else { String::new() };
+        let q = format!(
+            "'{}' in parents and name = '{}' and trashed = false{}",
+            escape_query(parent_id),
+            escape_query(name),
+            mime
+        );
+        Ok(self.query(&q)?.into_iter().next())
+    }
+
+    fn create_folder(&self, parent_id: &str, name: &str) -> Result<String> {
+        let body = json!({ "name": name, "mimeType": FOLDER_MIME, "parents": [parent_id] });
+        let created: DriveFile = self
+            .send(
+                self.client
+                    .post(format!("{}/files", API_URL))
+                    .query(&[("fields", FILE_FIELDS)])
+                    .json(&body),
+                "create folder",
+            )?
+            .json()?;
+        Ok(created.id)
+    }
+
+    // フォルダのIDを解決する（create が true なら途中のフォルダも作成する）
+    fn folder_id(&self, dir: &str, create: bool) -> Result<Option<String>> {
+        if let Some(id) = self.folder_ids.lock().unwrap().get(dir) {
+            return Ok(Some(id.clone()));
+        }
+
+        let (parent_id, name) = if dir.is_empty() {
+            ("root".to_string(), self.config.folder.as_str())
+        } else {
+            let trimmed = dir.trim_end_matches('/');
+            let (parent, name) = match trimmed.rsplit_once('/') {
+                Some((parent, name)) => (format!("{}/", parent), name),
+                None => (String::new(), trimmed),
+            };
+            match self.folder_id(&parent, create)? {
+                Some(parent_id) => (parent_id, name),
+                None => return Ok(None),
+            }
+        };
+
+        let id = match self.find_child(&parent_id, name, true)? {
+            Some(folder) => folder.id,
+            None if create => self.create_folder(&parent_id, name)?,
+            None => return Ok(None),
+        };
+        self.folder_ids.lock().unwrap().insert(dir.to_string(), id.clone());
+        Ok(Some(id))
+    }
+
+    fn file_id(&self, key: &str) -> Result<Option<String>> {
+        if let Some(id) = self.file_ids.lock().unwrap().get(key) {
+            return Ok(Some(id.clone()));
+        }
+        let (dir, name) = split_key(key);
+        let parent_id = match self.folder_id(&dir, false)? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let id = self.find_child(&parent_id, name, false)?.map(|file| file.id);
+        if let Some(id) = &id {
+            self.file_ids.lock().unwrap().insert(key.to_string(), id.clone());
+        }
+        Ok(id)
+    }
+}
+
+impl SyncBackend for GoogleDriveBackend {
+    fn remote_id(&self) -> String {
+        format!("gdrive:{}", self.config.folder)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        let mut objects = Vec::new();
+        let root_id = match self.folder_id(prefix, false)? {
+            Some(id) => id,
+            None => return Ok(objects),
+        };
+
+        let mut pending = vec![(prefix.to_string(), root_id)];
+        while let Some((dir, folder_id)) = pending.pop() {
+            let q = format!("'{}' in parents and trashed = false", escape_query(&folder_id));
+            for file in self.query(&q)? {
+                if file.mime_type == FOLDER_MIME {
+                    let child = format!("{}{}/", dir, file.name);
+                    self.folder_ids.lock().unwrap().insert(child.clone(), file.id.clone());
+                    pending.push((child, file.id));
+                } else {
+                    let key = format!("{}{}", dir, file.name);
+                    self.file_ids.lock().unwrap().insert(key.clone(), file.id);
+                    objects.push(RemoteObject {
+                        key,
+                        etag: file.version.unwrap_or_default(),
+                        size: file.size.and_then(|s| s.parse().ok()).unwrap_or(0),
+                        modified: file.modified_time,
+                    });
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let id = self.file_id(key)?.with_context(|| format!("Remote file not found: {}", key))?;
+        let response = self.send(
+            self.client
+                .get(format!("{}/files/{}", API_URL, id))
+                .query(&[("alt", "media")]),
+            "download",
+        )?;
+        Ok(response.bytes()?.to_vec())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        let updated: DriveFile = match self.file_id(key)? {
+            Some(id) => self
+                .send(
+                    self.client
+                        .patch(format!("{}/files/{}", UPLOAD_URL, id))
+                        .query(&[("uploadType", "media"), ("fields", FILE_FIELDS)])
+                        .header(header::CONTENT_TYPE, "application/octet-stream")
+                        .body(data.to_vec()),
+                    "update file",
+                )?
+                .json()?,
+            None => {
+                let (dir, name) = split_key(key);
+                let parent_id = self.folder_id(&dir, true)?.context("Failed to create remote folder")?;
+                // メタデータでアップロードセッションを作り、本体は1回の PUT で送る
+                let session = self.send(
+                    self.client
+                        .post(format!("{}/files", UPLOAD_URL))
+                        .query(&[("uploadType", "resumable"), ("fields", FILE_FIELDS)])
+                        .json(&json!({ "name": name, "parents": [parent_id] })),
+                    "start upload",
+                )?;
+                let location = session
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .context("Google Drive did not return an upload URL")?
+                    .to_string();
+                self.send(self.client.put(location).body(data.to_vec()), "upload file")?
+                    .json()?
+            }
+        };
+        self.file_ids.lock().unwrap().insert(key.to_string(), updated.id);
+        Ok(updated.version.unwrap_or_default())
+    }
+
+    // 誤操作に備えて完全には削除せずゴミ箱へ移動する
+    fn delete(&self, key: &str) -> Result<()> {
+        if let Some(id) = self.file_id(key)? {
+            self.send(
+                self.client
+                    .patch(format!("{}/files/{}", API_URL, id))
+                    .json(&json!({ "trashed": true })),
+                "trash file",
+            )?;
+            self.file_ids.lock().unwrap().remove(key);
+        }
+        Ok(())
+    }
+}
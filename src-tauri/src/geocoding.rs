@@ -0,0 +1,430 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// オフラインの地名データで、これより遠い地点しかなければ地名を付けない
+const MAX_OFFLINE_DISTANCE_KM: f64 = 30.0;
+// Nominatim の利用規約（1秒に1回まで）
+const NOMINATIM_INTERVAL: Duration = Duration::from_secs(1);
+const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org/reverse";
+const NOMINATIM_SEARCH_URL: &str = "https://nominatim.openstreetmap.org/search";
+// オフラインの地名データで住所に含まれるか調べる地名の最短の長さ（「北」などの1文字は誤って一致する）
+const MIN_PLACE_NAME_CHARS: usize = 2;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeocodingProvider {
+    // GeoNames の都市データ（cities*.txt）を使う
+    #[default]
+    Offline,
+    // OpenStreetMap の Nominatim に問い合わせる（座標が外部に送られる）
+    Nominatim,
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeocodingSettings {
+    #[serde(default)]
+    pub provider: GeocodingProvider,
+    // 取り込み時に位置情報から地名を入れる
+    #[serde(default = "default_auto_fill")]
+    pub auto_fill_on_import: bool,
+    // 位置情報のない画像は、OCR のテキストの住所から座標と地名を入れる
+    #[serde(default = "default_auto_fill")]
+    pub locate_addresses_on_import: bool,
+    // オンラインの問い合わせで使う言語
+    #[serde(default = "default_language")]
+    pub language: String,
+}
+
+fn default_auto_fill() -> bool {
+    true
+}
+
+fn default_language() -> String {
+    "ja".to_string()
+}
+
+impl Default for GeocodingSettings {
+    fn default() -> Self {
+        GeocodingSettings {
+            provider: GeocodingProvider::default(),
+            auto_fill_on_import: default_auto_fill(),
+            locate_addresses_on_import: default_auto_fill(),
+            language: default_language(),
+        }
+    }
+}
+
+impl GeocodingSettings {
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeocodedLocation {
+    // location_name に入れる表示用の地名
+    pub name: String,
+    pub provider: GeocodingProvider,
+    // オフラインの場合、一致した地点までの距離
+    pub distance_km: Option<f64>,
+}
+
+// 住所から求めた座標
+#[derive(Debug, Clone, Serialize)]
+pub struct GeocodedAddress {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub provider: GeocodingProvider,
+}
+
+// 地点から半径 radius_km 以内にあるものでの絞り込み
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GeoFilter {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_km: f64,
+}
+
+impl GeoFilter {
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        distance_km(self.latitude, self.longitude, latitude, longitude) <= self.radius_km
+    }
+
+    // 緯度・経度の範囲（SQL で大まかに絞り込んでから距離を求める）
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let d_lat = (self.radius_km / EARTH_RADIUS_KM).to_degrees();
+        let d_lon = d_lat / self.latitude.to_radians().cos().abs().max(0.01);
+        (self.latitude - d_lat, self.latitude + d_lat, self.longitude - d_lon, self.longitude + d_lon)
+    }
+}
+
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+struct City {
+    name: String,
+    // 日本語などの別名（英字の別名は name と同じく綴りが揺れるため持たない）
+    alternate_names: Vec<String>,
+    population: u64,
+    region: Option<String>,
+    country_code: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+// GeoNames の cities*.txt（タブ区切り）と、あれば admin1CodesASCII.txt（都道府県・州の名前）を読む
+struct OfflineGeocoder {
+    cities: Vec<City>,
+}
+
+impl OfflineGeocoder {
+    fn find_cities_file(dir: &Path) -> Option<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                let name = p.file_name().unwrap_or_default().to_string_lossy();
+                name.starts_with("cities") && name.ends_with(".txt")
+            })
+            .collect();
+        // cities500 のように数字が小さいほど地点が多い
+        files.sort();
+        files.into_iter().next()
+    }
+
+    fn load(dir: &Path) -> Result<Self> {
+        let cities_file = Self::find_cities_file(dir)
+            .with_context(|| format!("GeoNames cities file not found in {}", dir.display()))?;
+
+        let mut regions = HashMap::new();
+        if let Ok(file) = fs::File::open(dir.join("admin1CodesASCII.txt")) {
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                let mut columns = line.split('\t');
+                if let (Some(code), Some(name)) = (columns.next(), columns.next()) {
+                    regions.insert(code.to_string(), name.to_string());
+                }
+            }
+        }
+
+        let file = fs::File::open(&cities_file)?;
+        let mut cities = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let columns: Vec<&str> = line.split('\t').collect();
+            if columns.len() < 11 {
+                continue;
+            }
+            let (latitude, longitude) = match (columns[4].parse(), columns[5].parse()) {
+                (Ok(lat), Ok(lon)) => (lat, lon),
+                _ => continue,
+            };
+            let alternate_names = columns[3]
+                .split(',')
+                .filter(|name| !name.is_ascii() && name.chars().count() >= MIN_PLACE_NAME_CHARS)
+                .map(|name| name.to_string())
+                .collect();
+            cities.push(City {
+                name: columns[1].to_string(),
+                alternate_names,
+                population: columns.get(14).and_then(|p| p.parse().ok()).unwrap_or(0),
+                region: regions.get(&format!("{}.{}", columns[8], columns[10])).cloned(),
+                country_code: columns[8].to_string(),
+                latitude,
+                longitude,
+            });
+        }
+        log::info!("Loaded {} places from {}", cities.len(), cities_file.display());
+        Ok(OfflineGeocoder { cities })
+    }
+
+    fn location_name(city: &City) -> String {
+        let mut parts = vec![city.name.clone()];
+        parts.extend(city.region.clone().filter(|r| r != &city.name));
+        parts.push(city.country_code.clone());
+        parts.join(", ")
+    }
+
+    // 数万件程度なので総当たりで最も近い地点を探す
+    fn nearest(&self, latitude: f64, longitude: f64) -> Option<GeocodedLocation> {
+        let (city, distance) = self
+            .cities
+            .iter()
+            .map(|c| (c, distance_km(latitude, longitude, c.latitude, c.longitude)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        if distance > MAX_OFFLINE_DISTANCE_KM {
+            return None;
+        }
+        Some(GeocodedLocation {
+            name: Self::location_name(city),
+            provider: GeocodingProvider::Offline,
+            distance_km: Some(distance),
+        })
+    }
+}
+
+// 住所に含まれる地名のうち最も長いもの（同じ長さなら人口の多いもの）の地点
+fn find_in_address<'a>(cities: &'a [City], address: &str) -> Option<&'a City> {
+    let lower = address.to_lowercase();
+    cities
+        .iter()
+        .filter_map(|city| {
+            let ascii = (city.name.chars().count() >= MIN_PLACE_NAME_CHARS
+                && contains_word(&lower, &city.name.to_lowercase()))
+            .then(|| city.name.chars().count());
+            let alternate = city
+                .alternate_names
+                .iter()
+                .filter(|name| address.contains(name.as_str()))
+                .map(|name| name.chars().count())
+                .max();
+            ascii.max(alternate).map(|len| (city, len))
+        })
+        .max_by(|a, b| a.1.cmp(&b.1).then(a.0.population.cmp(&b.0.population)))
+        .map(|(city, _)| city)
+}
+
+// 英字の地名は単語として含まれるものだけ（Ome が Home に一致しないように）
+fn contains_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric()) && !after.is_some_and(|c| c.is_alphanumeric())
+    })
+}
+
+#[derive(Deserialize)]
+struct NominatimPlace {
+    lat: String,
+    lon: String,
+    #[serde(default)]
+    address: HashMap<String, String>,
+    display_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NominatimResponse {
+    #[serde(default)]
+    address: HashMap<String, String>,
+    display_name: Option<String>,
+}
+
+// 住所全体は長すぎるため、市区町村と都道府県（州）だけを使う
+fn nominatim_name(response: NominatimResponse) -> Option<String> {
+    let locality = ["city", "town", "village", "suburb", "county"]
+        .iter()
+        .find_map(|key| response.address.get(*key).cloned());
+    let region = ["state", "province"].iter().find_map(|key| response.address.get(*key).cloned());
+    let parts: Vec<String> = region.into_iter().chain(locality).collect();
+    if parts.is_empty() {
+        response.display_name
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+pub struct GeocodingService {
+    // 地名データを探すフォルダ（ライブラリ内、同梱リソースの順）
+    data_dirs: Vec<PathBuf>,
+    settings: Mutex<GeocodingSettings>,
+    settings_path: PathBuf,
+    // 地名データは初めて使うときに読み込む
+    offline: Mutex<Option<Arc<OfflineGeocoder>>>,
+    last_online_request: Mutex<Option<Instant>>,
+}
+
+impl GeocodingService {
+    pub fn new(data_dirs: Vec<PathBuf>, settings_path: PathBuf) -> Self {
+        GeocodingService {
+            data_dirs,
+            settings: Mutex::new(GeocodingSettings::load(&settings_path)),
+            settings_path,
+            offline: Mutex::new(None),
+            last_online_request: Mutex::new(None),
+        }
+    }
+
+    pub fn settings(&self) -> GeocodingSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set_settings(&self, settings: GeocodingSettings) -> Result<()> {
+        settings.save(&self.settings_path)?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    fn offline(&self) -> Result<Arc<OfflineGeocoder>> {
+        let mut offline = self.offline.lock().unwrap();
+        if let Some(geocoder) = offline.as_ref() {
+            return Ok(geocoder.clone());
+        }
+        let dir = self
+            .data_dirs
+            .iter()
+            .find(|dir| OfflineGeocoder::find_cities_file(dir).is_some())
+            .context("Offline place data (GeoNames cities*.txt) is not installed")?;
+        let geocoder = Arc::new(OfflineGeocoder::load(dir)?);
+        *offline = Some(geocoder.clone());
+        Ok(geocoder)
+    }
+
+    fn wait_for_nominatim(&self) {
+        let mut last = self.last_online_request.lock().unwrap();
+        if let Some(elapsed) = last.map(|t| t.elapsed()) {
+            if elapsed < NOMINATIM_INTERVAL {
+                std::thread::sleep(NOMINATIM_INTERVAL - elapsed);
+            }
+        }
+        *last = Some(Instant::now());
+    }
+
+    fn nominatim(&self, latitude: f64, longitude: f64, language: &str) -> Result<Option<GeocodedLocation>> {
+        self.wait_for_nominatim();
+        let response = reqwest::blocking::Client::new()
+            .get(NOMINATIM_URL)
+            .header("User-Agent", concat!("SnapOrganizer/", env!("CARGO_PKG_VERSION")))
+            .query(&[
+                ("format", "jsonv2"),
+                ("lat", &latitude.to_string()),
+                ("lon", &longitude.to_string()),
+                ("zoom", "14"),
+                ("accept-language", language),
+            ])
+            .send()?
+            .error_for_status()?;
+        let name = nominatim_name(response.json()?);
+        Ok(name.map(|name| GeocodedLocation {
+            name,
+            provider: GeocodingProvider::Nominatim,
+            distance_km: None,
+        }))
+    }
+
+    fn nominatim_search(&self, address: &str, language: &str) -> Result<Option<GeocodedAddress>> {
+        self.wait_for_nominatim();
+        let places: Vec<NominatimPlace> = reqwest::blocking::Client::new()
+            .get(NOMINATIM_SEARCH_URL)
+            .header("User-Agent", concat!("SnapOrganizer/", env!("CARGO_PKG_VERSION")))
+            .query(&[
+                ("format", "jsonv2"),
+                ("q", address),
+                ("limit", "1"),
+                ("addressdetails", "1"),
+                ("accept-language", language),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let Some(place) = places.into_iter().next() else {
+            return Ok(None);
+        };
+        let (Ok(latitude), Ok(longitude)) = (place.lat.parse(), place.lon.parse()) else {
+            return Ok(None);
+        };
+        let name = nominatim_name(NominatimResponse {
+            address: place.address,
+            display_name: place.display_name,
+        });
+        Ok(name.map(|name| GeocodedAddress {
+            name,
+            latitude,
+            longitude,
+            provider: GeocodingProvider::Nominatim,
+        }))
+    }
+
+    // 住所から座標と地名を求める（オフラインの地名データでは市区町村の中心の座標になる）
+    pub fn geocode(&self, address: &str) -> Result<Option<GeocodedAddress>> {
+        let settings = self.settings();
+        match settings.provider {
+            GeocodingProvider::Offline => {
+                let offline = self.offline()?;
+                Ok(find_in_address(&offline.cities, address).map(|city| GeocodedAddress {
+                    name: OfflineGeocoder::location_name(city),
+                    latitude: city.latitude,
+                    longitude: city.longitude,
+                    provider: GeocodingProvider::Offline,
+                }))
+            }
+            GeocodingProvider::Nominatim => self.nominatim_search(address, &settings.language),
+            GeocodingProvider::Disabled => Ok(None),
+        }
+    }
+
+    // 座標から地名を求める（見つからなければ None）
+    pub fn reverse_geocode(&self, latitude: f64, longitude: f64) -> Result<Option<GeocodedLocation>> {
+        let settings = self.settings();
+        match settings.provider {
+            GeocodingProvider::Offline => Ok(self.offline()?.nearest(latitude, longitude)),
+            GeocodingProvider::Nominatim => self.nominatim(latitude, longitude, &settings.language),
+            GeocodingProvider::Disabled => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+use std::fs::File;
+use std::path::Path;
+
+// 画像内容のハッシュ（キャッシュキーや重複判定に使用）
+pub fn content_hash(data: &[u8]) -> String {
+    blake3::hash(data).to_hex().to_string()
+}
+
+pub fn file_hash(path: &Path) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    let mut file = File::open(path)?;
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
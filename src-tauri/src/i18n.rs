@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+// 言語ごとの文言（キー → 文言、{name} の形で値を埋め込む）
+const CATALOGS: &[(&str, &str)] = &[
+    ("ja", include_str!("../locales/ja.json")),
+    ("en", include_str!("../locales/en.json")),
+];
+// 設定の言語に文言がなければこの言語の文言を使う
+const FALLBACK_LOCALE: &str = "ja";
+
+// 設定の表示言語（フロントエンドが settings.language に保存したもの）
+static LOCALE: RwLock<String> = RwLock::new(String::new());
+
+fn catalogs() -> &'static HashMap<&'static str, HashMap<String, String>> {
+    static PARSED: OnceLock<HashMap<&'static str, HashMap<String, String>>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|(locale, json)| (*locale, serde_json::from_str(json).expect("invalid message catalog")))
+            .collect()
+    })
+}
+
+// "en-US" のような地域付きの指定は言語の部分で探す
+pub fn set_locale(locale: &str) {
+    let language = locale.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    *LOCALE.write().unwrap() = language;
+}
+
+fn lookup(key: &str) -> Option<&'static str> {
+    let locale = LOCALE.read().unwrap();
+    [locale.as_str(), FALLBACK_LOCALE]
+        .iter()
+        .find_map(|l| catalogs().get(l)?.get(key))
+        .map(String::as_str)
+}
+
+// キーに対応する文言（どの言語にもなければキーをそのまま返す）
+pub fn text(key: &str) -> String {
+    lookup(key).unwrap_or(key).to_string()
+}
+
+// {name} を args の値で置き換えた文言
+pub fn format(key: &str, args: &[(&str, &str)]) -> String {
+    args.iter()
+        .fold(text(key), |message, (name, value)| message.replace(&format!("{{{}}}", name), value))
+}
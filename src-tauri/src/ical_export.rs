@@ -0,0 +1,168 @@
+use crate::metadata_store::{ItemRecord, Reminder};
+use chrono::{NaiveDate, Utc};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// 予定・期限として扱う行に含まれる語（レシートの日付などを拾わないように）
+const DATE_KEYWORDS: &[&str] = &[
+    "期限", "締切", "締め切り", "〆切", "まで", "有効", "開催", "日時", "予約", "予定", "due", "deadline", "expire",
+    "valid", "event",
+];
+const SUMMARY_CHARS: usize = 40;
+
+// OCR テキストから見つけた日付
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedDate {
+    pub date: NaiveDate,
+    // 日付が書かれていた行
+    pub line: String,
+}
+
+// 2024/5/31・2024-05-31・2024年5月31日 の形式で、予定や期限らしい行にある日付を拾う
+pub fn detect_dates(text: &str) -> Vec<DetectedDate> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r"((?:19|20)\d{2})\s*(?:[/\-.]|年)\s*(\d{1,2})\s*(?:[/\-.]|月)\s*(\d{1,2})").unwrap()
+    });
+    let mut dates = Vec::new();
+    for line in text.lines().map(str::trim) {
+        let lower = line.to_lowercase();
+        if !DATE_KEYWORDS.iter().any(|k| lower.contains(k)) {
+            continue;
+        }
+        for caps in pattern.captures_iter(line) {
+            let date = NaiveDate::from_ymd_opt(
+                caps[1].parse().unwrap_or(0),
+                caps[2].parse().unwrap_or(0),
+                caps[3].parse().unwrap_or(0),
+            );
+            if let Some(date) = date {
+                let detected = DetectedDate { date, line: line.to_string() };
+                if !dates.contains(&detected) {
+                    dates.push(detected);
+                }
+            }
+        }
+    }
+    dates
+}
+
+// テキスト値のエスケープ（RFC 5545 3.3.11）
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// 1行75オクテットを超えたら折り返す（UTF-8 の文字の途中では切らない）
+fn push_line(out: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            length = 1;
+        }
+        out.push(c);
+        length += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn item_summary(item: &ItemRecord) -> String {
+    let text = [item.memo.as_str(), item.ocr_text.as_str()]
+        .into_iter()
+        .flat_map(|t| t.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("Snap Organizer");
+    text.chars().take(SUMMARY_CHARS).collect()
+}
+
+fn item_description(item: &ItemRecord) -> String {
+    let mut description = item.memo.clone();
+    if !item.tags.is_empty() {
+        if !description.is_empty() {
+            description.push('\n');
+        }
+        description.push_str(&format!("タグ: {}", item.tags.join(", ")));
+    }
+    description
+}
+
+struct Event {
+    uid: String,
+    start: String,
+    summary: String,
+    description: String,
+    // 予定の何分前に通知するか
+    alarm_minutes: Option<i64>,
+}
+
+impl Event {
+    fn write(&self, out: &mut String, stamp: &str) {
+        push_line(out, "BEGIN:VEVENT");
+        push_line(out, &format!("UID:{}", self.uid));
+        push_line(out, &format!("DTSTAMP:{}", stamp));
+        push_line(out, &self.start);
+        push_line(out, &format!("SUMMARY:{}", escape(&self.summary)));
+        if !self.description.is_empty() {
+            push_line(out, &format!("DESCRIPTION:{}", escape(&self.description)));
+        }
+        if let Some(minutes) = self.alarm_minutes {
+            push_line(out, "BEGIN:VALARM");
+            push_line(out, "ACTION:DISPLAY");
+            push_line(out, &format!("DESCRIPTION:{}", escape(&self.summary)));
+            push_line(out, &format!("TRIGGER:-PT{}M", minutes));
+            push_line(out, "END:VALARM");
+        }
+        push_line(out, "END:VEVENT");
+    }
+}
+
+// アイテムのリマインダーと OCR から見つけた日付を予定として書き出す
+pub fn build_calendar(items: &[ItemRecord], reminders: &[Reminder], name: &str) -> (String, usize) {
+    let items_by_id: HashMap<&str, &ItemRecord> = items.iter().map(|i| (i.id.as_str(), i)).collect();
+    let mut events = Vec::new();
+
+    for reminder in reminders {
+        let Some(item) = items_by_id.get(reminder.item_id.as_str()) else {
+            continue;
+        };
+        let summary = if reminder.note.is_empty() { item_summary(item) } else { reminder.note.clone() };
+        events.push(Event {
+            uid: format!("reminder-{}@snap-organizer", reminder.id),
+            start: format!("DTSTART:{}", reminder.due_at.format("%Y%m%dT%H%M%SZ")),
+            summary,
+            description: item_description(item),
+            alarm_minutes: Some((reminder.due_at - reminder.remind_at).num_minutes().max(0)),
+        });
+    }
+
+    for item in items {
+        for detected in detect_dates(&item.ocr_text) {
+            events.push(Event {
+                uid: format!("{}-{}@snap-organizer", item.id, detected.date.format("%Y%m%d")),
+                start: format!("DTSTART;VALUE=DATE:{}", detected.date.format("%Y%m%d")),
+                summary: detected.line.chars().take(SUMMARY_CHARS).collect(),
+                description: item_description(item),
+                alarm_minutes: None,
+            });
+        }
+    }
+
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//Snap Organizer//JA");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in &events {
+        event.write(&mut out, &stamp);
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    (out, events.len())
+}
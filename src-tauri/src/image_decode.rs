@@ -0,0 +1,137 @@
+#[cfg(feature = "turbojpeg")]
+use crate::turbo_jpeg;
+use image::codecs::jpeg::JpegEncoder;
+use image::error::{ImageError, LimitErrorKind};
+use image::io::{Limits, Reader};
+#[cfg(feature = "turbojpeg")]
+use image::ImageFormat;
+use image::DynamicImage;
+use serde::Serialize;
+use std::fmt;
+use std::io::Cursor;
+
+// 受け付けるファイルの大きさ（カメラの RAW 相当の JPEG/PNG でも収まる）
+pub const MAX_FILE_BYTES: usize = 200 * 1024 * 1024;
+// 縦横それぞれの画素数
+pub const MAX_DIMENSION: u32 = 20_000;
+// デコードに使うメモリ（RGBA で約 1 億画素分）
+pub const MAX_ALLOC_BYTES: u64 = 512 * 1024 * 1024;
+
+// デコードできなかった理由（フロントエンドには kind で振り分けられる JSON で返す）
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DecodeError {
+    FileTooLarge { bytes: usize, limit: usize },
+    DimensionsTooLarge { width: u32, height: u32, limit: u32 },
+    OutOfMemory { limit: u64 },
+    UnsupportedFormat { message: String },
+    Invalid { message: String },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::FileTooLarge { bytes, limit } => {
+                write!(f, "Image file is too large ({} bytes, limit {} bytes)", bytes, limit)
+            }
+            DecodeError::DimensionsTooLarge { width, height, limit } => {
+                write!(f, "Image is too large ({}x{}, limit {}px per side)", width, height, limit)
+            }
+            DecodeError::OutOfMemory { limit } => {
+                write!(f, "Decoding the image would need more than {} bytes of memory", limit)
+            }
+            DecodeError::UnsupportedFormat { message } => write!(f, "Unsupported image format: {}", message),
+            DecodeError::Invalid { message } => write!(f, "Failed to decode image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<ImageError> for DecodeError {
+    fn from(e: ImageError) -> Self {
+        match e {
+            ImageError::Limits(limit) => match limit.kind() {
+                LimitErrorKind::InsufficientMemory => DecodeError::OutOfMemory { limit: MAX_ALLOC_BYTES },
+                _ => DecodeError::Invalid { message: limit.to_string() },
+            },
+            ImageError::Unsupported(e) => DecodeError::UnsupportedFormat { message: e.to_string() },
+            e => DecodeError::Invalid { message: e.to_string() },
+        }
+    }
+}
+
+fn limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC_BYTES);
+    limits
+}
+
+fn reader(data: &[u8]) -> Result<Reader<Cursor<&[u8]>>, DecodeError> {
+    if data.len() > MAX_FILE_BYTES {
+        return Err(DecodeError::FileTooLarge {
+            bytes: data.len(),
+            limit: MAX_FILE_BYTES,
+        });
+    }
+    let reader = Reader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| DecodeError::Invalid { message: e.to_string() })?;
+    if reader.format().is_none() {
+        return Err(DecodeError::UnsupportedFormat {
+            message: "unknown file signature".to_string(),
+        });
+    }
+    Ok(reader)
+}
+
+// ヘッダーだけを読んで大きさを確かめる（画素はデコードしない）
+pub fn check(data: &[u8]) -> Result<(u32, u32), DecodeError> {
+    let (width, height) = reader(data)?.into_dimensions()?;
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(DecodeError::DimensionsTooLarge {
+            width,
+            height,
+            limit: MAX_DIMENSION,
+        });
+    }
+    Ok((width, height))
+}
+
+// 外から渡された画像は image::load_from_memory ではなくこれでデコードする
+// 壊れた・極端に大きい画像で数 GB を確保してしまわないよう、大きさとメモリに上限を設ける
+pub fn decode(data: &[u8]) -> Result<DynamicImage, DecodeError> {
+    let (width, height) = check(data)?;
+    // RGB に展開しただけで上限を超えるものは読み始めない
+    if width as u64 * height as u64 * 3 > MAX_ALLOC_BYTES {
+        return Err(DecodeError::OutOfMemory { limit: MAX_ALLOC_BYTES });
+    }
+    let mut reader = reader(data)?;
+
+    // libjpeg-turbo が使えれば JPEG はそちらでデコードし、失敗したら image クレートで読み直す
+    #[cfg(feature = "turbojpeg")]
+    if reader.format() == Some(ImageFormat::Jpeg) {
+        match turbo_jpeg::decode(data) {
+            Ok(image) => return Ok(image),
+            Err(e) => log::debug!("turbojpeg decode failed, falling back: {}", e),
+        }
+    }
+
+    reader.limits(limits());
+    Ok(reader.decode()?)
+}
+
+// JPEG にエンコードする（libjpeg-turbo が使えればそちらを使う）
+pub fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, ImageError> {
+    let rgb = image.to_rgb8();
+    #[cfg(feature = "turbojpeg")]
+    match turbo_jpeg::encode(&rgb, quality) {
+        Ok(jpeg) => return Ok(jpeg),
+        Err(e) => log::debug!("turbojpeg encode failed, falling back: {}", e),
+    }
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, quality).encode_image(&rgb)?;
+    Ok(jpeg)
+}
@@ -0,0 +1,520 @@
+use crate::captions::CaptionService;
+use crate::document_dates;
+use crate::document_types::{self, DocumentType};
+use crate::embeddings::EmbeddingService;
+use crate::entities;
+use crate::exif_data;
+use crate::geocoding::GeocodingService;
+use crate::hashing;
+use crate::image_decode;
+use crate::jobs::ProgressReporter;
+use crate::metadata_store::{ItemRecord, MetadataStore, RelationType};
+use crate::ocr::{self, OcrMode, OcrService, OcrWord};
+use crate::ocr_corrections::CorrectionDictionary;
+use crate::ocr_preprocess;
+use crate::paths::{is_image_path, LibraryPaths};
+use crate::plugins::{PluginHook, PluginHost};
+use crate::qr_codes::{self, QrCode};
+use crate::rules::{self, RulesService};
+use crate::search_engine::SearchEngine;
+use crate::titles;
+use crate::translation::{Translation, TranslationService};
+use crate::webhooks::{WebhookEvent, WebhookService};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+const MAX_IMPORT_THREADS: usize = 8;
+const WHITEBOARD_JPEG_QUALITY: u8 = 90;
+
+// 取り込み処理の進捗（フロントエンドへのイベント用）
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportProgress {
+    pub path: String,
+    pub status: String,
+    pub item_id: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ImportProgress {
+    pub fn started(path: &Path) -> Self {
+        ImportProgress {
+            path: path.to_string_lossy().to_string(),
+            status: "importing".to_string(),
+            item_id: None,
+            error: None,
+        }
+    }
+
+    pub fn imported(path: &Path, item_id: &str) -> Self {
+        ImportProgress {
+            status: "imported".to_string(),
+            item_id: Some(item_id.to_string()),
+            ..Self::started(path)
+        }
+    }
+
+    // 同じ内容のアイテムが既にある
+    pub fn skipped(path: &Path, existing_item_id: &str) -> Self {
+        ImportProgress {
+            status: "skipped".to_string(),
+            item_id: Some(existing_item_id.to_string()),
+            ..Self::started(path)
+        }
+    }
+
+    pub fn failed(path: &Path, error: &anyhow::Error) -> Self {
+        ImportProgress {
+            status: "failed".to_string(),
+            error: Some(error.to_string()),
+            ..Self::started(path)
+        }
+    }
+}
+
+// 内容が同じアイテム（blake3 のハッシュが一致）が既にあるときの扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    // 取り込まない
+    #[default]
+    Skip,
+    // 画像ファイルと OCR の結果を共有した別のアイテムとして登録し、既存のアイテムと duplicate_of で関係づける
+    Link,
+    // 通常どおり取り込む
+    ImportAnyway,
+}
+
+#[derive(Debug, Clone)]
+pub enum ImportOutcome {
+    Imported(ItemRecord),
+    Linked { item: ItemRecord, duplicate_of: String },
+    // 既存のアイテム
+    Skipped(ItemRecord),
+}
+
+impl ImportOutcome {
+    pub fn item(&self) -> &ItemRecord {
+        match self {
+            ImportOutcome::Imported(item) | ImportOutcome::Linked { item, .. } | ImportOutcome::Skipped(item) => item,
+        }
+    }
+
+    pub fn into_item(self) -> ItemRecord {
+        match self {
+            ImportOutcome::Imported(item) | ImportOutcome::Linked { item, .. } | ImportOutcome::Skipped(item) => item,
+        }
+    }
+
+    pub fn progress(&self, path: &Path) -> ImportProgress {
+        match self {
+            ImportOutcome::Skipped(existing) => ImportProgress::skipped(path, &existing.id),
+            _ => ImportProgress::imported(path, &self.item().id),
+        }
+    }
+}
+
+// 取り込みに必要な共有状態（GUI以外からも同じ処理を使えるよう参照で受け取る）
+pub struct ImportContext<'a> {
+    pub paths: &'a LibraryPaths,
+    pub store: &'a Mutex<Option<MetadataStore>>,
+    pub search: &'a Mutex<Option<SearchEngine>>,
+    pub ocr: Option<&'a OcrService>,
+    // ユーザーが繰り返し直した OCR の誤りを取り込み時に直す
+    pub corrections: Option<&'a CorrectionDictionary>,
+    // 意味での検索用に画像の埋め込みを計算する（モデルがダウンロード済みのときだけ）
+    pub embeddings: Option<&'a EmbeddingService>,
+    // 画像の説明文を生成する（モデルがダウンロード済みのときだけ）
+    pub captions: Option<&'a CaptionService>,
+    // OCR のテキストを訳して検索できるようにする（設定で有効にしたときだけ）
+    pub translator: Option<&'a TranslationService>,
+    pub geocoder: Option<&'a GeocodingService>,
+    pub rules: Option<&'a RulesService>,
+    pub duplicates: DuplicatePolicy,
+    // ホワイトボードを撮った写真として、台形補正して背景を白くした画像を保存する（元の画像は保存しない）
+    pub whiteboard: bool,
+    // 取り込んだことを外部の自動化ツールへ知らせる（CLI では送らない）
+    pub webhooks: Option<&'a WebhookService>,
+    pub plugins: Option<&'a PluginHost>,
+}
+
+// 取り込みの前半（ロックをほとんど取らないので並列に実行できる）の結果
+// 登録の前までに求めたもの
+struct ReadyImport {
+    item: ItemRecord,
+    existing: Option<ItemRecord>,
+    add_albums: Vec<String>,
+    ocr_words: Vec<OcrWord>,
+    embedding: Option<Vec<f32>>,
+    translation: Option<Translation>,
+    qr_codes: Vec<QrCode>,
+}
+
+enum Prepared {
+    Ready(ReadyImport),
+    Skipped(ItemRecord),
+}
+
+// 画像ファイルをライブラリに取り込み、メタデータストアとインデックスに登録する
+// 内容が同じアイテムが既にあれば ctx.duplicates に従う
+pub fn import_file(ctx: &ImportContext, source: &Path) -> Result<ImportOutcome> {
+    match prepare(ctx, source)? {
+        Prepared::Ready(ready) => commit(ctx, ready),
+        Prepared::Skipped(existing) => Ok(ImportOutcome::Skipped(existing)),
+    }
+}
+
+// 同時に取り込むファイルの数（OCR のプロセスと画像のデコードでメモリを使うため上限を設ける）
+fn import_threads() -> usize {
+    std::thread::available_parallelism()
+        .map_or(2, |n| n.get())
+        .min(MAX_IMPORT_THREADS)
+}
+
+// 複数のファイルを並列に取り込む（デコード・ハッシュ・OCR は並列、登録はストアのロックで1件ずつ）
+// 結果は paths と同じ順で返す。キャンセルされたらまだ始めていないファイルは取り込まない
+pub fn import_files(
+    ctx: &ImportContext,
+    paths: &[PathBuf],
+    reporter: &(dyn ProgressReporter + Sync),
+    on_progress: &(dyn Fn(ImportProgress) + Sync),
+) -> Result<Vec<Result<ImportOutcome>>> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(import_threads()).build()?;
+    let done = AtomicU64::new(0);
+    pool.install(|| {
+        paths
+            .par_iter()
+            .map(|path| {
+                reporter.checkpoint()?;
+                on_progress(ImportProgress::started(path));
+                let result = import_file(ctx, path);
+                on_progress(match &result {
+                    Ok(outcome) => outcome.progress(path),
+                    Err(e) => ImportProgress::failed(path, e),
+                });
+                reporter.progress(done.fetch_add(1, Ordering::SeqCst) + 1, &path.to_string_lossy());
+                Ok(result)
+            })
+            .collect()
+    })
+}
+
+fn prepare(ctx: &ImportContext, source: &Path) -> Result<Prepared> {
+    if !is_image_path(source) {
+        bail!("Unsupported file type: {}", source.display());
+    }
+
+    let original = fs::read(source)
+        .with_context(|| format!("Failed to read file: {}", source.display()))?;
+    // 整えた画像には Exif が残らないので、位置情報は元の画像から読む
+    let coordinates = exif_data::read_gps(&original);
+    // ホワイトボードとして取り込むときは整えた画像を JPEG で保存する（同じ写真からは同じ画像になるので重複も見分けられる）
+    let (data, stored_name) = if ctx.whiteboard {
+        let image = image_decode::decode(&original).with_context(|| format!("Rejected image: {}", source.display()))?;
+        let enhanced = image_decode::encode_jpeg(&ocr_preprocess::whiteboard(&image), WHITEBOARD_JPEG_QUALITY)?;
+        (enhanced, source.with_extension("jpg"))
+    } else {
+        (original, source.to_path_buf())
+    };
+    let hash = hashing::content_hash(&data);
+
+    let existing = match ctx.duplicates {
+        DuplicatePolicy::ImportAnyway => None,
+        _ => {
+            let store = ctx.store.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store.find_by_hash(&hash)?
+        }
+    };
+    if let Some(existing) = &existing {
+        if ctx.duplicates == DuplicatePolicy::Skip {
+            return Ok(Prepared::Skipped(existing.clone()));
+        }
+    }
+
+    // 前処理: デコードできない画像や大きすぎる画像は取り込まない
+    // デコードした画像から意味での検索用の埋め込みと説明文、写っている QR コードも求める（失敗しても取り込みは続行する）
+    let (width, height, embedding, caption, qr_codes) = {
+        let image = image_decode::decode(&data).with_context(|| format!("Rejected image: {}", source.display()))?;
+        let embedding = ctx
+            .embeddings
+            .filter(|embeddings| embeddings.is_available())
+            .and_then(|embeddings| match embeddings.embed_image(&image) {
+                Ok(vector) => Some(vector),
+                Err(e) => {
+                    log::warn!("Failed to compute embedding for {}: {}", source.display(), e);
+                    None
+                }
+            });
+        // 重複として登録する場合は既存のアイテムの説明文を使う
+        let caption = match &existing {
+            Some(existing) if !existing.private => existing.caption.clone(),
+            _ => ctx
+                .captions
+                .filter(|captions| captions.is_available())
+                .and_then(|captions| match captions.caption(&image) {
+                    Ok(caption) => caption,
+                    Err(e) => {
+                        log::warn!("Failed to generate caption for {}: {}", source.display(), e);
+                        None
+                    }
+                }),
+        };
+        (image.width(), image.height(), embedding, caption, qr_codes::decode(&image))
+    };
+
+    let stored_path = store_original(ctx.paths, &stored_name, &hash, &data)?;
+
+    let created_at = fs::metadata(source)
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+
+    // OCRに失敗しても取り込み自体は続行する（重複として登録する場合は既存のアイテムの結果を使う）
+    // 印刷の文字としてほとんど読めなければ手書きとしても読む
+    let (ocr_text, ocr_words, ocr_mode) = match (ctx.ocr, &existing) {
+        (_, Some(existing)) if !existing.private => {
+            let store = ctx.store.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            (existing.ocr_text.clone(), store.ocr_words(&existing.id)?, OcrMode::Printed)
+        }
+        (Some(engine), _) => match engine.recognize_auto(&stored_path, &ocr::default_languages()) {
+            Ok(result) => match ctx.corrections {
+                Some(corrections) => (corrections.apply(&result.text), result.words, result.mode),
+                None => (result.text, result.words, result.mode),
+            },
+            Err(e) => {
+                log::warn!("OCR failed for {}: {}", source.display(), e);
+                (String::new(), Vec::new(), OcrMode::Printed)
+            }
+        },
+        (None, _) => (String::new(), Vec::new(), OcrMode::Printed),
+    };
+
+    // 書類の種類（重複として登録する場合は既存のアイテムに合わせる）
+    let doc_type = match &existing {
+        Some(existing) if !existing.private => existing.doc_type,
+        _ => match document_types::classify(&ocr_text, &ocr_words, width, height) {
+            DocumentType::Other if ocr_mode == OcrMode::Handwriting => DocumentType::HandwrittenNote,
+            doc_type => doc_type,
+        },
+    };
+
+    // 書類の日付（重複として登録する場合は既存のアイテムに合わせる）
+    let document_date = match &existing {
+        Some(existing) if !existing.private => existing.document_date,
+        _ => document_dates::parse(&ocr_text),
+    };
+
+    // 一覧に出す見出し（重複として登録する場合は既存のアイテムに合わせる）
+    let title = match &existing {
+        Some(existing) if !existing.private => existing.title.clone(),
+        _ => titles::generate(&ocr_text, doc_type, caption.as_deref()),
+    };
+
+    // 位置情報があれば地名を入れる（失敗しても取り込みは続行する）
+    let location_name = match (coordinates, ctx.geocoder) {
+        (Some((lat, lon)), Some(geocoder)) if geocoder.settings().auto_fill_on_import => {
+            match geocoder.reverse_geocode(lat, lon) {
+                Ok(location) => location.map(|l| l.name),
+                Err(e) => {
+                    log::warn!("Reverse geocoding failed for {}: {}", source.display(), e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // 位置情報がなければ OCR のテキストの住所から座標と地名を入れる（失敗しても取り込みは続行する）
+    let address_location = match (coordinates, ctx.geocoder) {
+        (None, Some(geocoder)) if geocoder.settings().locate_addresses_on_import => {
+            match entities::addresses(&ocr_text).first().map(|address| geocoder.geocode(address)) {
+                Some(Ok(location)) => location,
+                Some(Err(e)) => {
+                    log::warn!("Address geocoding failed for {}: {}", source.display(), e);
+                    None
+                }
+                None => None,
+            }
+        }
+        _ => None,
+    };
+    let (location_name, coordinates) = match address_location {
+        Some(location) => (Some(location.name), Some((location.latitude, location.longitude))),
+        None => (location_name, coordinates),
+    };
+
+    let now = Utc::now();
+    let mut item = ItemRecord {
+        id: Uuid::new_v4().to_string(),
+        group_id: None,
+        image_path: Some(stored_path.to_string_lossy().to_string()),
+        content_hash: Some(hash),
+        ocr_text,
+        memo: String::new(),
+        tags: Vec::new(),
+        location_name,
+        latitude: coordinates.map(|c| c.0),
+        longitude: coordinates.map(|c| c.1),
+        created_at,
+        updated_at: now,
+        private: false,
+        doc_type,
+        caption,
+        document_date,
+        title,
+        archived: false,
+    };
+
+    if let Some(plugins) = ctx.plugins {
+        plugins.run(PluginHook::PostOcr, &mut item);
+    }
+
+    // 自動タグ付けルール（アルバムへの追加は登録後に行う）
+    let outcome = ctx.rules.map(|r| r.evaluate(&item)).unwrap_or_default();
+    item.tags.extend(outcome.add_tags);
+
+    if let Some(plugins) = ctx.plugins {
+        plugins.run(PluginHook::PreIndex, &mut item);
+    }
+
+    // 検索用の訳（重複として登録する場合は既存のアイテムの訳を使う。失敗しても取り込みは続行する）
+    let translation = match (&existing, ctx.translator) {
+        (Some(existing), _) if !existing.private => {
+            let store = ctx.store.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store.translation(existing)?
+        }
+        (_, Some(translator)) if translator.is_enabled() && translator.settings().translate_on_import => {
+            match translator.translate(&item.ocr_text) {
+                Ok(translation) => translation,
+                Err(e) => {
+                    log::warn!("Failed to translate OCR text of {}: {}", source.display(), e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    Ok(Prepared::Ready(ReadyImport {
+        item,
+        existing,
+        add_albums: outcome.add_albums,
+        ocr_words,
+        embedding,
+        translation,
+        qr_codes,
+    }))
+}
+
+// 取り込みの後半: メタデータストアと検索インデックスへの登録（ストアのロックで1件ずつ行う）
+fn commit(ctx: &ImportContext, ready: ReadyImport) -> Result<ImportOutcome> {
+    let ReadyImport {
+        mut item,
+        mut existing,
+        add_albums,
+        ocr_words,
+        embedding,
+        translation,
+        qr_codes,
+    } = ready;
+    let duplicate_of = {
+        let mut store = ctx.store.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+
+        // 並列に取り込んだ同じ内容のファイルが先に登録されていないか、ロックを取ってから確かめ直す
+        if existing.is_none() && ctx.duplicates != DuplicatePolicy::ImportAnyway {
+            if let Some(hash) = &item.content_hash {
+                existing = store.find_by_hash(hash)?;
+            }
+            if let Some(existing) = &existing {
+                if ctx.duplicates == DuplicatePolicy::Skip {
+                    return Ok(ImportOutcome::Skipped(existing.clone()));
+                }
+            }
+        }
+
+        store.insert_item(&item)?;
+        store.set_ocr_words(&item.id, &ocr_words)?;
+        if let Some(translation) = &translation {
+            store.set_translation(&item.id, &item.ocr_text, translation)?;
+        }
+        if !qr_codes.is_empty() {
+            store.set_qr_codes(&item.id, &qr_codes)?;
+        }
+        if let (Some(embeddings), Some(vector)) = (ctx.embeddings, embedding) {
+            embeddings.add(&item.id, vector)?;
+        }
+        if let Err(e) = rules::add_to_albums(store, &item.id, &add_albums) {
+            log::warn!("Failed to add {} to rule albums: {}", item.id, e);
+        }
+
+        if let Some(engine) = ctx.search.lock().unwrap().as_mut() {
+            engine.add_item(store.to_searchable(&item)?)?;
+            store.clear_index_journal(std::slice::from_ref(&item.id))?;
+        }
+
+        if let Some(existing) = &existing {
+            store.link_items(&item.id, &existing.id, RelationType::DuplicateOf, None)?;
+        }
+        existing.map(|existing| existing.id)
+    };
+
+    // プラグインと Webhook は時間がかかることがあるので、ストアのロックを外してから呼ぶ
+    // 登録後のプラグインの変更は編集履歴に残るよう更新として書き込む
+    if let Some(plugins) = ctx.plugins {
+        let mut updated = item.clone();
+        if plugins.run(PluginHook::PostImport, &mut updated) {
+            let mut store = ctx.store.lock().unwrap();
+            let store = store.as_mut().context("Metadata store not initialized")?;
+            item = store.update_item(&updated)?;
+            if let Some(engine) = ctx.search.lock().unwrap().as_mut() {
+                engine.update_item(store.to_searchable(&item)?)?;
+                store.clear_index_journal(std::slice::from_ref(&item.id))?;
+            }
+        }
+    }
+    if let Some(webhooks) = ctx.webhooks {
+        webhooks.notify(WebhookEvent::ItemImported, serde_json::to_value(&item)?);
+    }
+
+    match duplicate_of {
+        Some(duplicate_of) => Ok(ImportOutcome::Linked { item, duplicate_of }),
+        None => Ok(ImportOutcome::Imported(item)),
+    }
+}
+
+// 元画像をコンテンツハッシュ名でライブラリの images ディレクトリへ保存
+fn store_original(paths: &LibraryPaths, source: &Path, hash: &str, data: &[u8]) -> Result<PathBuf> {
+    let images_dir = paths.images_dir();
+    fs::create_dir_all(&images_dir)?;
+
+    let ext = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_else(|| "jpg".to_string());
+    let dest = images_dir.join(format!("{}.{}", hash, ext));
+
+    if !dest.exists() {
+        // 同じファイルを並列で取り込むことがあるため、一時ファイルは書き込みごとに別の名前にする
+        let tmp = dest.with_extension(format!("{}.{}.tmp", ext, Uuid::new_v4().simple()));
+        fs::write(&tmp, data)?;
+        if let Err(e) = fs::rename(&tmp, &dest) {
+            let _ = fs::remove_file(&tmp);
+            // 先に別の取り込みが同じ内容を保存していれば成功とする
+            if !dest.exists() {
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(dest)
+}
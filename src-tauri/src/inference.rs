@@ -0,0 +1,141 @@
+use crate::models::{ModelInfo, ModelManager};
+use anyhow::{bail, Context, Result};
+use ndarray::{ArrayD, Axis, IxDyn};
+use ort::session::builder::GraphOptimizationLevel;
+use ort::session::Session;
+use ort::value::{DynValue, Tensor};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+// モデルへの入力（画像は f32、トークン列は i64）
+pub enum InferenceInput {
+    F32(ArrayD<f32>),
+    I64(ArrayD<i64>),
+}
+
+impl InferenceInput {
+    pub fn f32(shape: &[usize], data: Vec<f32>) -> Result<Self> {
+        Ok(InferenceInput::F32(ArrayD::from_shape_vec(IxDyn(shape), data)?))
+    }
+
+    pub fn i64(shape: &[usize], data: Vec<i64>) -> Result<Self> {
+        Ok(InferenceInput::I64(ArrayD::from_shape_vec(IxDyn(shape), data)?))
+    }
+
+    fn into_value(self) -> Result<DynValue> {
+        Ok(match self {
+            InferenceInput::F32(array) => Tensor::from_array(array)?.into_dyn(),
+            InferenceInput::I64(array) => Tensor::from_array(array)?.into_dyn(),
+        })
+    }
+}
+
+// ONNX Runtime による端末内の推論（読み込んだセッションはモデルごとに使い回す）
+pub struct InferenceService {
+    models: ModelManager,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+}
+
+impl InferenceService {
+    pub fn new(models_dir: PathBuf) -> Self {
+        InferenceService {
+            models: ModelManager::new(models_dir),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn models(&self) -> &ModelManager {
+        &self.models
+    }
+
+    pub fn list_models(&self) -> Vec<ModelInfo> {
+        self.models.list()
+    }
+
+    // 初めて使うときにファイルを検証してから読み込む
+    fn session(&self, model_id: &str) -> Result<Arc<Session>> {
+        if let Some(session) = self.sessions.lock().unwrap().get(model_id) {
+            return Ok(session.clone());
+        }
+        if !self.models.is_installed(model_id) {
+            bail!("Model {} is not installed", model_id);
+        }
+        if !self.models.verify(model_id)? {
+            bail!("Model {} is corrupted; download it again", model_id);
+        }
+        let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(threads)?
+            .commit_from_file(self.models.path(model_id))?;
+        let session = Arc::new(session);
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(model_id.to_string(), session.clone());
+        Ok(session)
+    }
+
+    // 名前付きの入力でモデルを実行し、f32 の出力を名前ごとに返す
+    // モデルが受け取らない入力（書き出し方によって attention_mask がないなど）は渡さない
+    pub fn run(&self, model_id: &str, inputs: Vec<(&str, InferenceInput)>) -> Result<HashMap<String, ArrayD<f32>>> {
+        let session = self.session(model_id)?;
+        let values = inputs
+            .into_iter()
+            .filter(|(name, _)| session.inputs.iter().any(|input| input.name == *name))
+            .map(|(name, input)| Ok((name, input.into_value()?)))
+            .collect::<Result<Vec<(&str, DynValue)>>>()?;
+        let outputs = session.run(values)?;
+        let mut results = HashMap::new();
+        for (name, value) in outputs.iter() {
+            if let Ok(tensor) = value.try_extract_tensor::<f32>() {
+                results.insert(name.to_string(), tensor.into_owned());
+            }
+        }
+        Ok(results)
+    }
+
+    // エンコーダー・デコーダー型のモデルで、最も確からしいトークンを1つずつ選んで文を生成する（貪欲法）
+    // context は毎回デコーダーへ渡す入力（エンコーダーの出力など）。開始のトークンを除いて返す
+    pub fn greedy_decode(
+        &self,
+        decoder_id: &str,
+        start: i64,
+        end: i64,
+        max_tokens: usize,
+        banned: &[i64],
+        context: impl Fn() -> Result<Vec<(&'static str, InferenceInput)>>,
+    ) -> Result<Vec<i64>> {
+        let mut ids = vec![start];
+        while ids.len() <= max_tokens {
+            let len = ids.len();
+            let mut inputs = context()?;
+            inputs.push(("input_ids", InferenceInput::i64(&[1, len], ids.clone())?));
+            let logits = self
+                .run(decoder_id, inputs)?
+                .remove("logits")
+                .context("Model has no logits output")?;
+            let next = logits
+                .index_axis(Axis(0), 0)
+                .index_axis(Axis(0), len - 1)
+                .iter()
+                .enumerate()
+                .filter(|(id, _)| !banned.contains(&(*id as i64)))
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(id, _)| id as i64)
+                .context("Model returned empty logits")?;
+            if next == end {
+                break;
+            }
+            ids.push(next);
+        }
+        ids.remove(0);
+        Ok(ids)
+    }
+
+    // モデルを削除・更新したときは読み込み済みのセッションを捨てる
+    pub fn unload(&self, model_id: &str) {
+        self.sessions.lock().unwrap().remove(model_id);
+    }
+}
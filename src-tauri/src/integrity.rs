@@ -0,0 +1,200 @@
+use crate::metadata_store::{ItemFilter, MetadataStore};
+use crate::search_engine::SearchEngine;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    // SQLite の整合性チェックで見つかった問題（自動では直せない）
+    DatabaseCorrupt,
+    // アイテムの元画像がディスクにない
+    MissingImage,
+    // メタデータストアにない（またはゴミ箱にある）アイテムが検索インデックスに残っている
+    OrphanedIndexDoc,
+    // 検索インデックスに登録されていないアイテム
+    MissingFromIndex,
+    // 同じアイテムが検索インデックスに複数登録されている
+    DuplicateIndexDoc,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityIssue {
+    pub kind: IssueKind,
+    pub item_id: Option<String>,
+    pub path: Option<String>,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+    pub checked_items: usize,
+    pub indexed_docs: usize,
+    pub issues: Vec<IntegrityIssue>,
+    // 種類ごとの件数
+    pub counts: BTreeMap<IssueKind, usize>,
+}
+
+impl IntegrityReport {
+    fn push(&mut self, kind: IssueKind, item_id: Option<&str>, path: Option<&str>, detail: String) {
+        *self.counts.entry(kind).or_default() += 1;
+        self.issues.push(IntegrityIssue {
+            kind,
+            item_id: item_id.map(str::to_string),
+            path: path.map(str::to_string),
+            detail,
+        });
+    }
+
+    fn item_ids(&self, kind: IssueKind) -> Vec<String> {
+        self.issues
+            .iter()
+            .filter(|i| i.kind == kind)
+            .filter_map(|i| i.item_id.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairAction {
+    // 検索インデックスにないアイテムを登録する
+    ReindexMissing,
+    // インデックスに残ったドキュメントを削除する
+    RemoveOrphanedIndexDocs,
+    // 重複したドキュメントを1件にまとめる
+    DeduplicateIndexDocs,
+    // 元画像がないアイテムをゴミ箱へ移す（保持期間内なら元に戻せる）
+    TrashMissingImages,
+}
+
+impl RepairAction {
+    fn issue_kind(self) -> IssueKind {
+        match self {
+            RepairAction::ReindexMissing => IssueKind::MissingFromIndex,
+            RepairAction::RemoveOrphanedIndexDocs => IssueKind::OrphanedIndexDoc,
+            RepairAction::DeduplicateIndexDocs => IssueKind::DuplicateIndexDoc,
+            RepairAction::TrashMissingImages => IssueKind::MissingImage,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairResult {
+    pub action: RepairAction,
+    pub repaired: usize,
+    // 直せなかったアイテムとその理由
+    pub failed: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairReport {
+    pub results: Vec<RepairResult>,
+    // 修復後にもう一度確認した結果
+    pub after: IntegrityReport,
+}
+
+// メタデータストア・検索インデックス・ディスク上のファイルを突き合わせる
+pub fn verify(store: &MetadataStore, engine: &SearchEngine) -> Result<IntegrityReport> {
+    let mut report = IntegrityReport::default();
+
+    for message in store.integrity_check()? {
+        report.push(IssueKind::DatabaseCorrupt, None, None, message);
+    }
+
+    let items = store.list_items(&ItemFilter::default())?;
+    report.checked_items = items.len();
+    for item in &items {
+        if let Some(image_path) = item.image_path.as_deref() {
+            if !Path::new(image_path).is_file() {
+                report.push(
+                    IssueKind::MissingImage,
+                    Some(&item.id),
+                    Some(image_path),
+                    "Image file not found".to_string(),
+                );
+            }
+        }
+    }
+
+    let indexed_ids = engine.indexed_ids()?;
+    report.indexed_docs = indexed_ids.len();
+    let mut doc_counts: HashMap<&str, usize> = HashMap::new();
+    for id in &indexed_ids {
+        *doc_counts.entry(id.as_str()).or_default() += 1;
+    }
+    let live_ids: HashSet<&str> = items.iter().map(|i| i.id.as_str()).collect();
+
+    for item in &items {
+        match doc_counts.get(item.id.as_str()) {
+            None => report.push(
+                IssueKind::MissingFromIndex,
+                Some(&item.id),
+                None,
+                "Not found in search index".to_string(),
+            ),
+            Some(&count) if count > 1 => report.push(
+                IssueKind::DuplicateIndexDoc,
+                Some(&item.id),
+                None,
+                format!("Indexed {} times", count),
+            ),
+            Some(_) => {}
+        }
+    }
+    let mut orphaned: Vec<&str> = doc_counts.keys().copied().filter(|id| !live_ids.contains(id)).collect();
+    orphaned.sort();
+    for id in orphaned {
+        report.push(
+            IssueKind::OrphanedIndexDoc,
+            Some(id),
+            None,
+            "Indexed but not in the library".to_string(),
+        );
+    }
+    Ok(report)
+}
+
+fn repair_item(store: &mut MetadataStore, engine: &mut SearchEngine, action: RepairAction, item_id: &str) -> Result<()> {
+    match action {
+        RepairAction::ReindexMissing | RepairAction::DeduplicateIndexDocs => {
+            if let Some(item) = store.get_item(item_id)? {
+                // update_item は同じ ID のドキュメントをすべて消してから登録し直す
+                engine.update_item(store.to_searchable(&item)?)?;
+            }
+        }
+        RepairAction::RemoveOrphanedIndexDocs => engine.delete_item(item_id)?,
+        RepairAction::TrashMissingImages => {
+            store.trash_item(item_id)?;
+            engine.delete_item(item_id)?;
+        }
+    }
+    Ok(())
+}
+
+// 指定された種類の問題を直し、もう一度確認した結果を返す
+pub fn repair(store: &mut MetadataStore, engine: &mut SearchEngine, actions: &[RepairAction]) -> Result<RepairReport> {
+    let before = verify(store, engine)?;
+    let mut results = Vec::new();
+    for &action in actions {
+        let mut result = RepairResult {
+            action,
+            repaired: 0,
+            failed: Vec::new(),
+        };
+        for item_id in before.item_ids(action.issue_kind()) {
+            match repair_item(store, engine, action, &item_id) {
+                Ok(()) => result.repaired += 1,
+                Err(e) => {
+                    log::warn!("Failed to repair {:?} for {}: {}", action, item_id, e);
+                    result.failed.push((item_id, e.to_string()));
+                }
+            }
+        }
+        results.push(result);
+    }
+    let after = verify(store, engine)?;
+    Ok(RepairReport { results, after })
+}
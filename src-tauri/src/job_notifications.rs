@@ -0,0 +1,66 @@
+use crate::i18n;
+use crate::jobs::{JobInfo, JobStatus};
+use crate::settings::JobNotifications;
+use serde_json::Value;
+
+// これより長くかかったジョブは、完了を OS の通知で知らせる
+const LONG_JOB_SECS: i64 = 10;
+
+fn count(result: &Value, key: &str) -> Option<u64> {
+    match result.get(key)? {
+        Value::Array(values) => Some(values.len() as u64),
+        value => value.as_u64(),
+    }
+}
+
+// 同期で記録した衝突の数（同期以外のジョブは 0）
+fn conflicts(job: &JobInfo) -> u64 {
+    job.result
+        .as_ref()
+        .and_then(|result| count(result, "conflicts_recorded"))
+        .unwrap_or(0)
+}
+
+fn completed_body(job: &JobInfo) -> String {
+    let result = job.result.as_ref().unwrap_or(&Value::Null);
+    match job.kind.as_str() {
+        "import" => match count(result, "imported").or_else(|| count(result, "items_imported")) {
+            Some(n) => i18n::format("notify.imported", &[("count", &n.to_string())]),
+            None => i18n::text("notify.import_completed"),
+        },
+        "backup" => match count(result, "item_count") {
+            Some(n) => i18n::format("notify.backed_up", &[("count", &n.to_string())]),
+            None => i18n::text("notify.backup_completed"),
+        },
+        "sync" | "lan-sync" => i18n::text("notify.sync_completed"),
+        _ => i18n::format("notify.job_completed", &[("name", &job.name)]),
+    }
+}
+
+// 終わったジョブの通知の (タイトル, 本文)。通知しないなら None
+// ウィンドウを見ているときは、成功の通知は画面の表示に任せる
+pub fn message(job: &JobInfo, level: JobNotifications, window_focused: bool) -> Option<(String, String)> {
+    if level == JobNotifications::Off {
+        return None;
+    }
+    match job.status {
+        JobStatus::Failed => Some((
+            i18n::format("notify.job_failed", &[("name", &job.name)]),
+            job.error.clone().unwrap_or_default(),
+        )),
+        JobStatus::Completed if conflicts(job) > 0 => Some((
+            job.name.clone(),
+            i18n::format("notify.sync_conflicts", &[("count", &conflicts(job).to_string())]),
+        )),
+        JobStatus::Completed if !window_focused => {
+            let long = (job.updated_at - job.created_at).num_seconds() >= LONG_JOB_SECS;
+            let notify = match level {
+                JobNotifications::All => true,
+                JobNotifications::LongJobs => long,
+                _ => false,
+            };
+            notify.then(|| (job.name.clone(), completed_body(job)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+// 同時に実行するジョブ数の上限
+const MAX_CONCURRENT_JOBS: usize = 2;
+// 終わったジョブを履歴に残す日数（保存するときにこれより古いものを捨てる）
+const FINISHED_JOB_RETENTION_DAYS: i64 = 7;
+// 待機中に一時停止されたジョブが再開を確かめる間隔
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+    // アプリ終了により中断された
+    Interrupted,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled | JobStatus::Interrupted
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: String,
+    pub name: String,
+    pub status: JobStatus,
+    pub progress: u64,
+    pub total: u64,
+    pub message: Option<String>,
+    pub error: Option<String>,
+    pub result: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct JobControl {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+    // 実行を始めたか（再開したときに待機中と実行中のどちらに戻すか）
+    started: AtomicBool,
+}
+
+type Notifier = Box<dyn Fn(&JobInfo) + Send + Sync>;
+
+struct JobManagerInner {
+    jobs: Mutex<HashMap<String, JobInfo>>,
+    controls: Mutex<HashMap<String, Arc<JobControl>>>,
+    slots: Semaphore,
+    store_path: PathBuf,
+    notifier: Notifier,
+}
+
+impl JobManagerInner {
+    fn update<F: FnOnce(&mut JobInfo)>(&self, id: &str, persist: bool, f: F) {
+        let snapshot = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = match jobs.get_mut(id) {
+                Some(job) => job,
+                None => return,
+            };
+            f(job);
+            job.updated_at = Utc::now();
+            job.clone()
+        };
+        if persist {
+            self.persist();
+        }
+        (self.notifier)(&snapshot);
+    }
+
+    fn persist(&self) {
+        let jobs: Vec<JobInfo> = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let cutoff = Utc::now() - ChronoDuration::days(FINISHED_JOB_RETENTION_DAYS);
+            jobs.retain(|_, job| !job.status.is_finished() || job.updated_at > cutoff);
+            jobs.values().cloned().collect()
+        };
+        let result = serde_json::to_string_pretty(&jobs)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(fs::write(&self.store_path, json)?));
+        if let Err(e) = result {
+            log::warn!("Failed to persist jobs: {}", e);
+        }
+    }
+
+    fn control(&self, id: &str) -> Option<Arc<JobControl>> {
+        self.controls.lock().unwrap().get(id).cloned()
+    }
+}
+
+// 長時間処理の進捗報告先（ジョブ以外からも同じ処理を呼べるようにする）
+pub trait ProgressReporter {
+    fn set_total(&self, total: u64);
+    fn progress(&self, done: u64, message: &str);
+    // キャンセルされていればエラーを返す
+    fn checkpoint(&self) -> Result<()>;
+}
+
+// 進捗を報告しない（バックグラウンドで自動実行する処理用）
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn set_total(&self, _total: u64) {}
+
+    fn progress(&self, _done: u64, _message: &str) {}
+
+    fn checkpoint(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// ジョブ処理側に渡すハンドル（進捗報告・一時停止/キャンセルの確認）
+pub struct JobContext {
+    id: String,
+    control: Arc<JobControl>,
+    inner: Arc<JobManagerInner>,
+}
+
+impl JobContext {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn set_total(&self, total: u64) {
+        self.inner.update(&self.id, false, |job| job.total = total);
+    }
+
+    pub fn progress(&self, done: u64, message: impl Into<String>) {
+        let message = message.into();
+        self.inner.update(&self.id, false, |job| {
+            job.progress = done;
+            job.message = Some(message);
+        });
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.control.cancelled.load(Ordering::SeqCst)
+    }
+
+    // 処理の区切りで呼ぶ: キャンセルされていればエラー、一時停止中は再開まで待つ
+    pub fn checkpoint(&self) -> Result<()> {
+        loop {
+            if self.is_cancelled() {
+                bail!("Job cancelled");
+            }
+            if !self.control.paused.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            std::thread::sleep(PAUSED_POLL_INTERVAL);
+        }
+    }
+}
+
+impl ProgressReporter for JobContext {
+    fn set_total(&self, total: u64) {
+        JobContext::set_total(self, total)
+    }
+
+    fn progress(&self, done: u64, message: &str) {
+        JobContext::progress(self, done, message)
+    }
+
+    fn checkpoint(&self) -> Result<()> {
+        JobContext::checkpoint(self)
+    }
+}
+
+#[derive(Clone)]
+pub struct JobManager {
+    inner: Arc<JobManagerInner>,
+}
+
+impl JobManager {
+    pub fn new<F>(store_path: PathBuf, notifier: F) -> Self
+    where
+        F: Fn(&JobInfo) + Send + Sync + 'static,
+    {
+        // 前回終了時に未完了だったジョブは中断扱いにする
+        let mut jobs: HashMap<String, JobInfo> = fs::read_to_string(&store_path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<Vec<JobInfo>>(&json).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|job| (job.id.clone(), job))
+            .collect();
+        for job in jobs.values_mut() {
+            if !job.status.is_finished() {
+                job.status = JobStatus::Interrupted;
+                job.updated_at = Utc::now();
+            }
+        }
+
+        let manager = JobManager {
+            inner: Arc::new(JobManagerInner {
+                jobs: Mutex::new(jobs),
+                controls: Mutex::new(HashMap::new()),
+                slots: Semaphore::new(MAX_CONCURRENT_JOBS),
+                store_path,
+                notifier: Box::new(notifier),
+            }),
+        };
+        manager.inner.persist();
+        manager
+    }
+
+    // ジョブを登録してキューに入れ、ジョブIDを返す
+    pub fn submit<F>(&self, kind: &str, name: &str, work: F) -> String
+    where
+        F: FnOnce(&JobContext) -> Result<serde_json::Value> + Send + 'static,
+    {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let job = JobInfo {
+            id: id.clone(),
+            kind: kind.to_string(),
+            name: name.to_string(),
+            status: JobStatus::Queued,
+            progress: 0,
+            total: 0,
+            message: None,
+            error: None,
+            result: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let control = Arc::new(JobControl::default());
+        self.inner.jobs.lock().unwrap().insert(id.clone(), job.clone());
+        self.inner.controls.lock().unwrap().insert(id.clone(), control.clone());
+        self.inner.persist();
+        (self.inner.notifier)(&job);
+
+        let inner = self.inner.clone();
+        let job_id = id.clone();
+        tauri::async_runtime::spawn(async move {
+            let _permit = loop {
+                let permit = inner.slots.acquire().await;
+                if control.cancelled.load(Ordering::SeqCst) {
+                    inner.update(&job_id, true, |job| job.status = JobStatus::Cancelled);
+                    inner.controls.lock().unwrap().remove(&job_id);
+                    return;
+                }
+                if !control.paused.load(Ordering::SeqCst) {
+                    break permit;
+                }
+                // 待機中に一時停止されたジョブは枠を空けて再開を待つ
+                drop(permit);
+                tokio::time::sleep(PAUSED_POLL_INTERVAL).await;
+            };
+            control.started.store(true, Ordering::SeqCst);
+            inner.update(&job_id, true, |job| {
+                // 直前にキャンセルされた場合はそのまま、一時停止された場合は最初の区切りで止まる
+                job.status = if job.status == JobStatus::Cancelled {
+                    JobStatus::Cancelled
+                } else if control.paused.load(Ordering::SeqCst) {
+                    JobStatus::Paused
+                } else {
+                    JobStatus::Running
+                };
+            });
+
+            let ctx = JobContext {
+                id: job_id.clone(),
+                control: control.clone(),
+                inner: inner.clone(),
+            };
+            let outcome = tauri::async_runtime::spawn_blocking(move || work(&ctx)).await;
+
+            let cancelled = control.cancelled.load(Ordering::SeqCst);
+            inner.update(&job_id, true, |job| match outcome {
+                Ok(Ok(result)) => {
+                    job.status = JobStatus::Completed;
+                    job.result = Some(result);
+                }
+                Ok(Err(_)) if cancelled => job.status = JobStatus::Cancelled,
+                Ok(Err(e)) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            });
+            inner.controls.lock().unwrap().remove(&job_id);
+        });
+
+        id
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.inner.jobs.lock().unwrap().values().cloned().collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobInfo> {
+        self.inner.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn pause(&self, id: &str) -> Result<()> {
+        let control = self.inner.control(id).ok_or_else(|| anyhow::anyhow!("Job not active: {}", id))?;
+        control.paused.store(true, Ordering::SeqCst);
+        self.inner.update(id, true, |job| {
+            if matches!(job.status, JobStatus::Queued | JobStatus::Running) {
+                job.status = JobStatus::Paused;
+            }
+        });
+        Ok(())
+    }
+
+    pub fn resume(&self, id: &str) -> Result<()> {
+        let control = self.inner.control(id).ok_or_else(|| anyhow::anyhow!("Job not active: {}", id))?;
+        control.paused.store(false, Ordering::SeqCst);
+        let started = control.started.load(Ordering::SeqCst);
+        self.inner.update(id, true, |job| {
+            if job.status == JobStatus::Paused {
+                job.status = if started { JobStatus::Running } else { JobStatus::Queued };
+            }
+        });
+        Ok(())
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<()> {
+        let control = self.inner.control(id).ok_or_else(|| anyhow::anyhow!("Job not active: {}", id))?;
+        control.cancelled.store(true, Ordering::SeqCst);
+        control.paused.store(false, Ordering::SeqCst);
+        // 始まっていないジョブは枠が空くのを待たずにキャンセル済みにする
+        if !control.started.load(Ordering::SeqCst) {
+            self.inner.update(id, true, |job| {
+                if !job.status.is_finished() {
+                    job.status = JobStatus::Cancelled;
+                }
+            });
+        }
+        Ok(())
+    }
+
+    pub fn clear_finished(&self) {
+        self.inner.jobs.lock().unwrap().retain(|_, job| !job.status.is_finished());
+        self.inner.persist();
+    }
+}
@@ -0,0 +1,805 @@
+use crate::folder_sync::FolderBackend;
+use crate::sync::{RemoteObject, SyncBackend};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use hmac::{Hmac, Mac};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, DistinguishedName, ServerConfig, ServerConnection,
+    SignatureScheme, StreamOwned,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SERVICE_TYPE: &str = "_snaporganizer._tcp.local.";
+const PAIRING_MINUTES: i64 = 2;
+const MAX_PAIRING_ATTEMPTS: u32 = 3;
+const MAX_HEADER_BYTES: usize = 1024 * 1024;
+const MAX_BODY_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+// 本文は届いた分だけ読み進める（長さの申告だけで大きなバッファを確保しない）
+const BODY_CHUNK_BYTES: usize = 1024 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(120);
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+// ペアリング済みの端末（証明書のフィンガープリントで識別する）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanPeer {
+    pub fingerprint: String,
+    pub name: String,
+    pub last_address: Option<String>,
+    pub paired_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanSyncConfig {
+    pub enabled: bool,
+    pub device_name: String,
+    #[serde(default)]
+    pub peers: Vec<LanPeer>,
+}
+
+impl Default for LanSyncConfig {
+    fn default() -> Self {
+        let device_name = std::env::var("COMPUTERNAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "Snap Organizer".to_string());
+        LanSyncConfig {
+            enabled: false,
+            device_name,
+            peers: Vec::new(),
+        }
+    }
+}
+
+impl LanSyncConfig {
+    pub fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join("config.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("config.json"), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn is_trusted(&self, fingerprint: &str) -> bool {
+        self.peers.iter().any(|p| p.fingerprint == fingerprint)
+    }
+
+    pub fn add_peer(&mut self, fingerprint: String, name: String, address: Option<String>) {
+        self.peers.retain(|p| p.fingerprint != fingerprint);
+        self.peers.push(LanPeer {
+            fingerprint,
+            name,
+            last_address: address,
+            paired_at: Utc::now(),
+        });
+    }
+}
+
+pub fn fingerprint(cert: &CertificateDer) -> String {
+    hex::encode(Sha256::digest(cert.as_ref()))
+}
+
+// この端末の証明書と秘密鍵（初回に自己署名で生成し、以降は同じものを使う）
+pub struct LanIdentity {
+    cert: CertificateDer<'static>,
+    key: Vec<u8>,
+}
+
+impl LanIdentity {
+    pub fn load_or_create(dir: &Path) -> Result<Self> {
+        let cert_file = dir.join("cert.der");
+        let key_file = dir.join("key.der");
+        if cert_file.is_file() && key_file.is_file() {
+            return Ok(LanIdentity {
+                cert: CertificateDer::from(fs::read(&cert_file)?),
+                key: fs::read(&key_file)?,
+            });
+        }
+
+        fs::create_dir_all(dir)?;
+        let generated = rcgen::generate_simple_self_signed(vec!["snap-organizer.local".to_string()])?;
+        let cert = generated.cert.der().clone();
+        let key = generated.key_pair.serialize_der();
+        fs::write(&cert_file, cert.as_ref())?;
+        fs::write(&key_file, &key)?;
+        Ok(LanIdentity { cert, key })
+    }
+
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.cert)
+    }
+
+    fn key(&self) -> PrivateKeyDer<'static> {
+        PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(self.key.clone()))
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+// CA を使わず、証明書のフィンガープリントを直接照合する
+#[derive(Debug)]
+struct PinnedServerVerifier {
+    expected: Option<String>,
+    seen: Mutex<Option<String>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let actual = fingerprint(end_entity);
+        *self.seen.lock().unwrap() = Some(actual.clone());
+        match &self.expected {
+            Some(expected) if *expected != actual => Err(rustls::Error::General(
+                "Peer certificate does not match the paired device".to_string(),
+            )),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+// クライアント証明書は必須。信頼済みかどうかは接続後に判定する（ペアリング要求だけは未登録でも受け付ける）
+#[derive(Debug)]
+struct AnyClientCertVerifier {
+    provider: Arc<CryptoProvider>,
+}
+
+impl ClientCertVerifier for AnyClientCertVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> std::result::Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LanRequest {
+    // ペアリング: SPAKE2 で PIN から共通の鍵を作り、互いに同じ鍵を持っていることを確かめる（PIN そのものは送らない）
+    Pair { name: String, message: String },
+    PairConfirm { proof: String },
+    // セッション開始時・終了時に、公開側は自分のライブラリとストアを同期する
+    Begin,
+    End,
+    List { prefix: String },
+    Get { key: String },
+    Put { key: String },
+    Delete { key: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LanResponse {
+    error: Option<String>,
+    objects: Option<Vec<RemoteObject>>,
+    etag: Option<String>,
+    name: Option<String>,
+    // ペアリングの SPAKE2 のメッセージと鍵の確認
+    message: Option<String>,
+    proof: Option<String>,
+}
+
+// フレーム: [ヘッダ長 u32][JSON ヘッダ][本文長 u64][本文]
+fn write_frame<W: Write, T: Serialize>(writer: &mut W, header: &T, body: &[u8]) -> Result<()> {
+    let json = serde_json::to_vec(header)?;
+    writer.write_all(&(json.len() as u32).to_be_bytes())?;
+    writer.write_all(&json)?;
+    writer.write_all(&(body.len() as u64).to_be_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// 受け取るフレームの大きさの上限
+#[derive(Debug, Clone, Copy)]
+struct FrameLimits {
+    header: usize,
+    body: u64,
+}
+
+const TRUSTED_FRAMES: FrameLimits = FrameLimits {
+    header: MAX_HEADER_BYTES,
+    body: MAX_BODY_BYTES,
+};
+
+// ペアリングしていない相手とは小さなフレームしかやり取りしない
+const PAIRING_FRAMES: FrameLimits = FrameLimits { header: 4 * 1024, body: 0 };
+
+fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R, limits: FrameLimits) -> Result<Option<(T, Vec<u8>)>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let header_len = u32::from_be_bytes(len) as usize;
+    if header_len > limits.header {
+        bail!("Frame header too large");
+    }
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+
+    let mut body_len = [0u8; 8];
+    reader.read_exact(&mut body_len)?;
+    let body_len = u64::from_be_bytes(body_len);
+    if body_len > limits.body {
+        bail!("Frame body too large");
+    }
+    let mut body = Vec::with_capacity((body_len as usize).min(BODY_CHUNK_BYTES));
+    reader.take(body_len).read_to_end(&mut body)?;
+    if body.len() as u64 != body_len {
+        bail!("Connection closed in the middle of a frame");
+    }
+    Ok(Some((serde_json::from_slice(&header)?, body)))
+}
+
+// PIN と両者の証明書から SPAKE2 を始める（中間者は別の証明書を見せるため鍵が一致しない）
+fn start_pake(pin: &str, client_fp: &str, server_fp: &str, server: bool) -> (Spake2<Ed25519Group>, Vec<u8>) {
+    let password = Password::new(pin.as_bytes());
+    let (client, server_id) = (Identity::new(client_fp.as_bytes()), Identity::new(server_fp.as_bytes()));
+    if server {
+        Spake2::<Ed25519Group>::start_b(&password, &client, &server_id)
+    } else {
+        Spake2::<Ed25519Group>::start_a(&password, &client, &server_id)
+    }
+}
+
+fn finish_pake(pake: Spake2<Ed25519Group>, message: &str) -> Result<Vec<u8>> {
+    let message = hex::decode(message).context("Invalid pairing message")?;
+    pake.finish(&message).map_err(|e| anyhow!("Invalid pairing message: {:?}", e))
+}
+
+// 鍵の確認: 自分の側の名前を鍵で署名する（相手の確認をそのまま返しても通らない）
+fn key_confirmation(key: &[u8], side: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(side.as_bytes());
+    mac
+}
+
+fn confirm_key(key: &[u8], side: &str) -> String {
+    hex::encode(key_confirmation(key, side).finalize().into_bytes())
+}
+
+fn is_confirmed(key: &[u8], side: &str, proof: Option<&str>) -> bool {
+    proof
+        .and_then(|proof| hex::decode(proof).ok())
+        .is_some_and(|proof| key_confirmation(key, side).verify_slice(&proof).is_ok())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingCode {
+    pub pin: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+struct Pairing {
+    pin: String,
+    expires_at: DateTime<Utc>,
+    attempts: u32,
+}
+
+// 公開側がセッションの前後に呼ぶ、ライブラリとストアの同期処理
+pub type LocalSync = Arc<dyn Fn() -> Result<()> + Send + Sync>;
+
+struct ServerShared {
+    dir: PathBuf,
+    fingerprint: String,
+    store: FolderBackend,
+    pairing: Mutex<Option<Pairing>>,
+    // 同時に同期できる相手は1台だけ
+    session: Mutex<()>,
+    local_sync: LocalSync,
+    shutdown: AtomicBool,
+}
+
+impl ServerShared {
+    fn handle_connection(&self, tcp: TcpStream, config: Arc<ServerConfig>) -> Result<()> {
+        tcp.set_read_timeout(Some(IO_TIMEOUT))?;
+        tcp.set_write_timeout(Some(IO_TIMEOUT))?;
+        let address = tcp.peer_addr().ok();
+        let mut stream = StreamOwned::new(ServerConnection::new(config)?, tcp);
+
+        // 先にハンドシェイクを済ませ、信頼済みの相手かどうかでフレームの上限を決めてから読む
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        let client_fp = stream
+            .conn
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(fingerprint)
+            .context("Client certificate missing")?;
+        let trusted = LanSyncConfig::load(&self.dir).is_trusted(&client_fp);
+        let limits = if trusted { TRUSTED_FRAMES } else { PAIRING_FRAMES };
+
+        let (request, body) = match read_frame::<_, LanRequest>(&mut stream, limits)? {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        if let LanRequest::Pair { name, message } = request {
+            return self.serve_pairing(&mut stream, &client_fp, &name, &message, address);
+        }
+
+        if !trusted {
+            let response = LanResponse {
+                error: Some("This device is not paired".to_string()),
+                ..Default::default()
+            };
+            return write_frame(&mut stream, &response, &[]);
+        }
+
+        let _session = self.session.lock().unwrap();
+        let mut next = Some((request, body));
+        while let Some((request, body)) = next {
+            let (response, body) = match self.handle_request(request, body) {
+                Ok(result) => result,
+                Err(e) => (
+                    LanResponse {
+                        error: Some(e.to_string()),
+                        ..Default::default()
+                    },
+                    Vec::new(),
+                ),
+            };
+            write_frame(&mut stream, &response, &body)?;
+            next = read_frame(&mut stream, TRUSTED_FRAMES)?;
+        }
+        Ok(())
+    }
+
+    // 相手の SPAKE2 のメッセージに応えて鍵の確認を送り、相手の鍵の確認が正しければ信頼済みとして登録する
+    fn serve_pairing<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        client_fp: &str,
+        name: &str,
+        message: &str,
+        address: Option<SocketAddr>,
+    ) -> Result<()> {
+        let key = match self.answer_pairing(client_fp, message) {
+            Ok((key, reply)) => {
+                let response = LanResponse {
+                    message: Some(hex::encode(reply)),
+                    proof: Some(confirm_key(&key, "server")),
+                    ..Default::default()
+                };
+                write_frame(stream, &response, &[])?;
+                key
+            }
+            Err(e) => {
+                let response = LanResponse {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                };
+                return write_frame(stream, &response, &[]);
+            }
+        };
+
+        // PIN が違えば相手はこちらの確認を受け入れずに切断する
+        let proof = match read_frame::<_, LanRequest>(stream, PAIRING_FRAMES)? {
+            Some((LanRequest::PairConfirm { proof }, _)) => proof,
+            _ => return Ok(()),
+        };
+        let response = match self.accept_pairing(client_fp, name, &key, &proof, address) {
+            Ok(()) => LanResponse {
+                name: Some(LanSyncConfig::load(&self.dir).device_name),
+                ..Default::default()
+            },
+            Err(e) => LanResponse {
+                error: Some(e.to_string()),
+                ..Default::default()
+            },
+        };
+        write_frame(stream, &response, &[])
+    }
+
+    // 1回の SPAKE2 のやり取りで試せる PIN は1つだけなので、試行の回数で総当たりを防ぐ
+    fn answer_pairing(&self, client_fp: &str, message: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+        let pin = {
+            let mut pairing = self.pairing.lock().unwrap();
+            let (pin, exhausted) = match pairing.as_mut() {
+                Some(current) if current.expires_at > Utc::now() => {
+                    current.attempts += 1;
+                    (current.pin.clone(), current.attempts >= MAX_PAIRING_ATTEMPTS)
+                }
+                _ => bail!("Pairing is not active on this device"),
+            };
+            if exhausted {
+                *pairing = None;
+            }
+            pin
+        };
+        let (pake, reply) = start_pake(&pin, client_fp, &self.fingerprint, true);
+        Ok((finish_pake(pake, message)?, reply))
+    }
+
+    fn accept_pairing(
+        &self,
+        client_fp: &str,
+        name: &str,
+        key: &[u8],
+        proof: &str,
+        address: Option<SocketAddr>,
+    ) -> Result<()> {
+        if !is_confirmed(key, "client", Some(proof)) {
+            bail!("Incorrect pairing code");
+        }
+        *self.pairing.lock().unwrap() = None;
+
+        let mut config = LanSyncConfig::load(&self.dir);
+        config.add_peer(client_fp.to_string(), name.to_string(), address.map(|a| a.ip().to_string()));
+        config.save(&self.dir)
+    }
+
+    fn handle_request(&self, request: LanRequest, body: Vec<u8>) -> Result<(LanResponse, Vec<u8>)> {
+        let mut response = LanResponse::default();
+        let mut out = Vec::new();
+        match request {
+            LanRequest::Pair { .. } | LanRequest::PairConfirm { .. } => bail!("Already paired"),
+            LanRequest::Begin | LanRequest::End => (self.local_sync)()?,
+            LanRequest::List { prefix } => response.objects = Some(self.store.list(&prefix)?),
+            LanRequest::Get { key } => out = self.store.get(&key)?,
+            LanRequest::Put { key } => response.etag = Some(self.store.put(&key, &body)?),
+            LanRequest::Delete { key } => self.store.delete(&key)?,
+        }
+        Ok((response, out))
+    }
+}
+
+// LAN 同期サーバー（mDNS で公開し、ペアリング済みの端末からの接続を受け付ける）
+pub struct LanSyncServer {
+    shared: Arc<ServerShared>,
+    mdns: ServiceDaemon,
+    service_name: String,
+    port: u16,
+}
+
+impl LanSyncServer {
+    pub fn start(dir: PathBuf, local_sync: LocalSync) -> Result<Self> {
+        let identity = LanIdentity::load_or_create(&dir)?;
+        let config = LanSyncConfig::load(&dir);
+        let provider = crypto_provider();
+        let server_config = Arc::new(
+            ServerConfig::builder_with_provider(provider.clone())
+                .with_protocol_versions(&[&rustls::version::TLS13])?
+                .with_client_cert_verifier(Arc::new(AnyClientCertVerifier { provider }))
+                .with_single_cert(vec![identity.cert.clone()], identity.key())?,
+        );
+
+        let listener = TcpListener::bind("0.0.0.0:0")?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+
+        let fingerprint = identity.fingerprint();
+        let shared = Arc::new(ServerShared {
+            store: FolderBackend::new(dir.join("store"))?,
+            dir,
+            fingerprint: fingerprint.clone(),
+            pairing: Mutex::new(None),
+            session: Mutex::new(()),
+            local_sync,
+            shutdown: AtomicBool::new(false),
+        });
+
+        let accept_shared = shared.clone();
+        std::thread::spawn(move || {
+            while !accept_shared.shutdown.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((tcp, _)) => {
+                        let _ = tcp.set_nonblocking(false);
+                        let shared = accept_shared.clone();
+                        let config = server_config.clone();
+                        std::thread::spawn(move || {
+                            if let Err(e) = shared.handle_connection(tcp, config) {
+                                log::warn!("LAN sync connection failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(200)),
+                    Err(e) => log::warn!("LAN sync accept failed: {}", e),
+                }
+            }
+        });
+
+        let mdns = ServiceDaemon::new()?;
+        let instance = format!("{}-{}", config.device_name, &fingerprint[..8]);
+        let host = format!("snap-{}.local.", &fingerprint[..8]);
+        let properties = HashMap::from([
+            ("fp".to_string(), fingerprint.clone()),
+            ("name".to_string(), config.device_name.clone()),
+        ]);
+        let service = ServiceInfo::new(SERVICE_TYPE, &instance, &host, "", port, properties)?.enable_addr_auto();
+        let service_name = service.get_fullname().to_string();
+        mdns.register(service)?;
+
+        Ok(LanSyncServer {
+            shared,
+            mdns,
+            service_name,
+            port,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    // 相手の端末で入力してもらう PIN を発行する
+    pub fn start_pairing(&self) -> PairingCode {
+        let pin = format!("{:06}", rand_u32() % 1_000_000);
+        let expires_at = Utc::now() + ChronoDuration::minutes(PAIRING_MINUTES);
+        *self.shared.pairing.lock().unwrap() = Some(Pairing {
+            pin: pin.clone(),
+            expires_at,
+            attempts: 0,
+        });
+        PairingCode { pin, expires_at }
+    }
+
+    pub fn stop(self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        let _ = self.mdns.unregister(&self.service_name);
+        let _ = self.mdns.shutdown();
+    }
+}
+
+fn rand_u32() -> u32 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredPeer {
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+    pub fingerprint: String,
+    pub paired: bool,
+}
+
+// mDNS で LAN 内の他の端末を探す
+pub fn discover_peers(dir: &Path, own_fingerprint: &str) -> Result<Vec<DiscoveredPeer>> {
+    let config = LanSyncConfig::load(dir);
+    let mdns = ServiceDaemon::new()?;
+    let receiver = mdns.browse(SERVICE_TYPE)?;
+
+    let mut peers: HashMap<String, DiscoveredPeer> = HashMap::new();
+    let deadline = std::time::Instant::now() + DISCOVERY_TIMEOUT;
+    while let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) {
+        let info = match receiver.recv_timeout(remaining) {
+            Ok(ServiceEvent::ServiceResolved(info)) => info,
+            Ok(_) => continue,
+            Err(_) => break,
+        };
+        let fingerprint = match info.get_property_val_str("fp") {
+            Some(fp) if fp != own_fingerprint => fp.to_string(),
+            _ => continue,
+        };
+        let address = match info.get_addresses().iter().find(|ip| ip.is_ipv4()).or(info.get_addresses().iter().next()) {
+            Some(ip) => ip.to_string(),
+            None => continue,
+        };
+        peers.insert(
+            fingerprint.clone(),
+            DiscoveredPeer {
+                name: info.get_property_val_str("name").unwrap_or_default().to_string(),
+                address,
+                port: info.get_port(),
+                paired: config.is_trusted(&fingerprint),
+                fingerprint,
+            },
+        );
+    }
+    let _ = mdns.stop_browse(SERVICE_TYPE);
+    let _ = mdns.shutdown();
+    Ok(peers.into_values().collect())
+}
+
+fn connect(
+    identity: &LanIdentity,
+    address: &str,
+    port: u16,
+    expected: Option<String>,
+) -> Result<(StreamOwned<ClientConnection, TcpStream>, Arc<PinnedServerVerifier>)> {
+    let provider = crypto_provider();
+    let verifier = Arc::new(PinnedServerVerifier {
+        expected,
+        seen: Mutex::new(None),
+        provider: provider.clone(),
+    });
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_client_auth_cert(vec![identity.cert.clone()], identity.key())?;
+
+    let tcp = TcpStream::connect_timeout(&format!("{}:{}", address, port).parse()?, IO_TIMEOUT)
+        .with_context(|| format!("Failed to connect to {}:{}", address, port))?;
+    tcp.set_read_timeout(Some(IO_TIMEOUT))?;
+    tcp.set_write_timeout(Some(IO_TIMEOUT))?;
+    let connection = ClientConnection::new(Arc::new(config), ServerName::try_from("snap-organizer.local")?.to_owned())?;
+    Ok((StreamOwned::new(connection, tcp), verifier))
+}
+
+// 相手の端末に表示された PIN でペアリングし、相手を信頼済みとして登録する
+pub fn pair_with(dir: &Path, address: &str, port: u16, pin: &str) -> Result<LanPeer> {
+    let identity = LanIdentity::load_or_create(dir)?;
+    let mut config = LanSyncConfig::load(dir);
+    let (mut stream, verifier) = connect(&identity, address, port, None)?;
+
+    // 証明書を確認するため先にハンドシェイクを済ませる
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+    let server_fp = verifier.seen.lock().unwrap().clone().context("Peer certificate missing")?;
+
+    let (pake, message) = start_pake(pin.trim(), &identity.fingerprint(), &server_fp, false);
+    let request = LanRequest::Pair {
+        name: config.device_name.clone(),
+        message: hex::encode(message),
+    };
+    write_frame(&mut stream, &request, &[])?;
+    let (response, _): (LanResponse, _) =
+        read_frame(&mut stream, PAIRING_FRAMES)?.context("Peer closed the connection")?;
+    if let Some(error) = response.error {
+        bail!(error);
+    }
+
+    // 相手も同じ PIN を知っていることを確かめてから、こちらの確認を送る
+    let reply = response.message.context("Peer did not answer the pairing request")?;
+    let key = finish_pake(pake, &reply)?;
+    if !is_confirmed(&key, "server", response.proof.as_deref()) {
+        bail!("Incorrect pairing code");
+    }
+    let confirm = LanRequest::PairConfirm {
+        proof: confirm_key(&key, "client"),
+    };
+    write_frame(&mut stream, &confirm, &[])?;
+    let (response, _): (LanResponse, _) =
+        read_frame(&mut stream, PAIRING_FRAMES)?.context("Peer closed the connection")?;
+    if let Some(error) = response.error {
+        bail!(error);
+    }
+
+    let name = response.name.unwrap_or_else(|| "Unknown device".to_string());
+    config.add_peer(server_fp, name, Some(address.to_string()));
+    config.save(dir)?;
+    Ok(config.peers.last().cloned().expect("peer was just added"))
+}
+
+// ペアリング済みの端末のストアを同期先として扱うクライアント
+pub struct LanBackend {
+    peer: LanPeer,
+    stream: Mutex<StreamOwned<ClientConnection, TcpStream>>,
+}
+
+impl LanBackend {
+    pub fn connect(dir: &Path, peer: &LanPeer, address: &str, port: u16) -> Result<Self> {
+        let identity = LanIdentity::load_or_create(dir)?;
+        let (stream, _) = connect(&identity, address, port, Some(peer.fingerprint.clone()))?;
+        Ok(LanBackend {
+            peer: peer.clone(),
+            stream: Mutex::new(stream),
+        })
+    }
+
+    fn call(&self, request: &LanRequest, body: &[u8]) -> Result<(LanResponse, Vec<u8>)> {
+        let mut stream = self.stream.lock().unwrap();
+        write_frame(&mut *stream, request, body)?;
+        let (response, body): (LanResponse, Vec<u8>) =
+            read_frame(&mut *stream, TRUSTED_FRAMES)?.ok_or_else(|| anyhow!("{} closed the connection", self.peer.name))?;
+        if let Some(error) = response.error {
+            bail!("{}: {}", self.peer.name, error);
+        }
+        Ok((response, body))
+    }
+
+    pub fn begin(&self) -> Result<()> {
+        self.call(&LanRequest::Begin, &[]).map(|_| ())
+    }
+
+    pub fn end(&self) -> Result<()> {
+        self.call(&LanRequest::End, &[]).map(|_| ())
+    }
+}
+
+impl SyncBackend for LanBackend {
+    fn remote_id(&self) -> String {
+        format!("lan:{}", self.peer.fingerprint)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        let (response, _) = self.call(&LanRequest::List { prefix: prefix.to_string() }, &[])?;
+        Ok(response.objects.unwrap_or_default())
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        Ok(self.call(&LanRequest::Get { key: key.to_string() }, &[])?.1)
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        let (response, _) = self.call(&LanRequest::Put { key: key.to_string() }, data)?;
+        response.etag.context("Peer did not return an etag")
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.call(&LanRequest::Delete { key: key.to_string() }, &[]).map(|_| ())
+    }
+}
@@ -0,0 +1,262 @@
+use crate::jobs::ProgressReporter;
+use crate::models;
+use crate::ocr;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Tesseract の言語データの取得元（速度を優先した tessdata_fast）
+const TESSDATA_URL: &str = "https://github.com/tesseract-ocr/tessdata_fast/raw/main";
+
+// ダウンロードできる OCR の言語データ
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LanguagePack {
+    // Tesseract の言語コード（traineddata のファイル名）
+    pub code: &'static str,
+    pub name: &'static str,
+    // 表示用のおおよそのサイズ（バイト）
+    pub size: u64,
+}
+
+pub const CATALOG: &[LanguagePack] = &[
+    LanguagePack {
+        code: "jpn",
+        name: "Japanese",
+        size: 2_500_000,
+    },
+    LanguagePack {
+        code: "jpn_vert",
+        name: "Japanese (vertical)",
+        size: 2_900_000,
+    },
+    LanguagePack {
+        code: "eng",
+        name: "English",
+        size: 4_100_000,
+    },
+    LanguagePack {
+        code: "kor",
+        name: "Korean",
+        size: 1_600_000,
+    },
+    LanguagePack {
+        code: "kor_vert",
+        name: "Korean (vertical)",
+        size: 1_000_000,
+    },
+    LanguagePack {
+        code: "chi_sim",
+        name: "Chinese (Simplified)",
+        size: 2_400_000,
+    },
+    LanguagePack {
+        code: "chi_sim_vert",
+        name: "Chinese (Simplified, vertical)",
+        size: 2_000_000,
+    },
+    LanguagePack {
+        code: "chi_tra",
+        name: "Chinese (Traditional)",
+        size: 2_300_000,
+    },
+    LanguagePack {
+        code: "chi_tra_vert",
+        name: "Chinese (Traditional, vertical)",
+        size: 2_000_000,
+    },
+    LanguagePack {
+        code: "deu",
+        name: "German",
+        size: 1_500_000,
+    },
+    LanguagePack {
+        code: "fra",
+        name: "French",
+        size: 1_100_000,
+    },
+    LanguagePack {
+        code: "spa",
+        name: "Spanish",
+        size: 2_200_000,
+    },
+    LanguagePack {
+        code: "ita",
+        name: "Italian",
+        size: 2_600_000,
+    },
+    LanguagePack {
+        code: "por",
+        name: "Portuguese",
+        size: 1_900_000,
+    },
+    LanguagePack {
+        code: "rus",
+        name: "Russian",
+        size: 3_700_000,
+    },
+    LanguagePack {
+        code: "vie",
+        name: "Vietnamese",
+        size: 500_000,
+    },
+    LanguagePack {
+        code: "tha",
+        name: "Thai",
+        size: 1_100_000,
+    },
+    // 画像の向きの判定（縦書き・横倒しの画像の読み直しに使う）
+    LanguagePack {
+        code: "osd",
+        name: "Orientation and script detection",
+        size: 10_600_000,
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguagePackInfo {
+    pub code: String,
+    // カタログにない（手で置いた）言語データはコードをそのまま名前にする
+    pub name: String,
+    pub size: u64,
+    pub installed: bool,
+    pub installed_size: Option<u64>,
+    // 既定で OCR に使う言語（削除できない）
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguagePacks {
+    pub packs: Vec<LanguagePackInfo>,
+    // tessdata ディレクトリ全体の使用量（バイト）
+    pub disk_usage: u64,
+}
+
+pub fn pack(code: &str) -> Result<&'static LanguagePack> {
+    CATALOG
+        .iter()
+        .find(|pack| pack.code == code)
+        .with_context(|| format!("Unknown OCR language: {}", code))
+}
+
+fn is_required(code: &str) -> bool {
+    ocr::DEFAULT_LANGUAGES.contains(&code)
+}
+
+// ライブラリの tessdata ディレクトリの言語データの管理
+pub struct LanguagePackManager {
+    dir: PathBuf,
+}
+
+impl LanguagePackManager {
+    pub fn new(dir: PathBuf) -> Self {
+        LanguagePackManager { dir }
+    }
+
+    fn path(&self, code: &str) -> PathBuf {
+        self.dir.join(format!("{}.traineddata", code))
+    }
+
+    // ダウンロード時に記録したハッシュ（同梱のものにはない）
+    fn hash_path(&self, code: &str) -> PathBuf {
+        self.dir.join(format!("{}.traineddata.sha256", code))
+    }
+
+    // カタログの言語と、カタログにないがインストールされている言語
+    pub fn list(&self) -> LanguagePacks {
+        let installed: Vec<(String, u64)> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter_map(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        let code = name.strip_suffix(".traineddata")?.to_string();
+                        Some((code, e.metadata().ok()?.len()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let installed_size = |code: &str| installed.iter().find(|(c, _)| c == code).map(|(_, size)| *size);
+
+        let mut packs: Vec<LanguagePackInfo> = CATALOG
+            .iter()
+            .map(|pack| {
+                let installed_size = installed_size(pack.code);
+                LanguagePackInfo {
+                    code: pack.code.to_string(),
+                    name: pack.name.to_string(),
+                    size: pack.size,
+                    installed: installed_size.is_some(),
+                    installed_size,
+                    required: is_required(pack.code),
+                }
+            })
+            .collect();
+        for (code, size) in &installed {
+            if CATALOG.iter().all(|pack| pack.code != code) {
+                packs.push(LanguagePackInfo {
+                    code: code.clone(),
+                    name: code.clone(),
+                    size: *size,
+                    installed: true,
+                    installed_size: Some(*size),
+                    required: is_required(code),
+                });
+            }
+        }
+        LanguagePacks {
+            packs,
+            disk_usage: dir_size(&self.dir),
+        }
+    }
+
+    pub fn download(&self, code: &str, reporter: &dyn ProgressReporter) -> Result<PathBuf> {
+        let pack = pack(code)?;
+        fs::create_dir_all(&self.dir)?;
+        let dest = self.path(code);
+        let url = format!("{}/{}.traineddata", TESSDATA_URL, code);
+        let hash = models::download_file(&url, &dest, None, pack.size, pack.name, reporter)
+            .with_context(|| format!("Failed to download OCR language {}", code))?;
+        fs::write(self.hash_path(code), &hash)?;
+        Ok(dest)
+    }
+
+    // ファイルが壊れていないか（ダウンロード時のハッシュと比べる。同梱・手で置いたものは空でなければ true）
+    pub fn verify(&self, code: &str) -> Result<bool> {
+        let path = self.path(code);
+        if !path.is_file() {
+            bail!("OCR language {} is not installed", code);
+        }
+        match fs::read_to_string(self.hash_path(code)) {
+            Ok(expected) => Ok(models::sha256_file(&path)? == expected.trim()),
+            Err(_) => Ok(fs::metadata(&path)?.len() > 0),
+        }
+    }
+
+    // 既定で使う言語は削除しない（同梱のものは次の起動時に戻るため）
+    pub fn delete(&self, code: &str) -> Result<bool> {
+        if is_required(code) {
+            bail!("OCR language {} is required and cannot be removed", code);
+        }
+        let path = self.path(code);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(&path)?;
+        let _ = fs::remove_file(self.hash_path(code));
+        Ok(true)
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
@@ -1,45 +1,460 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod api_server;
+mod app_lock;
+mod backup;
+mod bulk_edit;
+mod captions;
+mod cli;
+mod clipboard;
+mod deep_links;
+mod document_dates;
+mod document_types;
+mod drag_out;
+mod dropbox_sync;
+mod duplicates;
+mod email_export;
+mod embeddings;
+mod entities;
+mod error;
+mod exchange_rates;
+mod exif_data;
+mod expenses;
+mod folder_import;
+mod folder_sync;
+mod gdrive_sync;
+mod geocoding;
+mod hashing;
+mod i18n;
+mod ical_export;
+mod image_decode;
+mod import_pipeline;
+mod inference;
+mod integrity;
+mod job_notifications;
+mod jobs;
+mod lan_sync;
+mod language_packs;
+mod libraries;
+mod library_stats;
+mod logging;
+mod map_clusters;
+mod markdown_export;
+mod memo_templates;
+mod metadata_store;
+mod models;
+mod note_import;
+mod oauth;
+mod ocr;
+mod ocr_corrections;
+mod ocr_preprocess;
+mod organize;
+mod orphans;
+mod paths;
+mod plugins;
+mod private_items;
+mod qr_codes;
+mod protocol;
+mod quick_capture;
+mod receipts;
+mod recompress;
+mod reminders;
+mod resize_cache;
+mod retention;
+mod rules;
+mod s3_sync;
+mod screen_capture;
 mod search_engine;
+mod secrets;
+mod settings;
+mod storage_report;
+mod sync;
+mod table_export;
+mod tag_suggest;
+mod thumbnail_cache;
+mod timeline;
+mod titles;
+mod translation;
+mod trash;
+#[cfg(feature = "turbojpeg")]
+mod turbo_jpeg;
+mod upload_server;
+mod vector_index;
+mod viewer_bundle;
+mod watcher;
+mod webdav_sync;
+mod webhooks;
+#[cfg(windows)]
+mod windows_ocr;
 
-use search_engine::{SearchEngine, SearchableItem, SearchQuery, SearchResult};
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::Mutex;
-use tauri::State;
+use anyhow::Context;
+use api_server::{ApiBackend, ApiServer, ApiServerSettings, ApiServerStatus, ItemPatch, ShareLink};
+use app_lock::{AppLock, AppLockStatus};
+use captions::CaptionService;
+use chrono::{DateTime, Utc};
+use deep_links::DeepLink;
+use document_types::DocumentType;
+use drag_out::{DragFiles, DragFormat};
+use backup::{BackupOptions, BackupSource, RestoreMode};
+use bulk_edit::{BulkChanges, BulkTarget, BulkUpdateResult};
+use email_export::SmtpSettings;
+use entities::Entity;
+use error::AppError;
+use exchange_rates::ExchangeRates;
+use expenses::ExpenseSummary;
+use import_pipeline::{DuplicatePolicy, ImportContext, ImportOutcome, ImportProgress};
+use embeddings::EmbeddingService;
+use inference::InferenceService;
+use integrity::{IntegrityReport, RepairAction, RepairReport};
+use folder_sync::FolderBackend;
+use jobs::{JobInfo, JobManager, NoProgress};
+use lan_sync::{DiscoveredPeer, LanBackend, LanPeer, LanSyncConfig, LanSyncServer, PairingCode};
+use language_packs::{LanguagePackManager, LanguagePacks};
+use libraries::{ActiveLibrary, LibraryProfile, LibraryRegistry};
+use logging::{LogEntry, LogLevel};
+use memo_templates::{MemoTemplate, MemoTemplates};
+use metadata_store::{
+    ActivityEntry, ActivityFilter, AlbumRecord, ConflictRecord, ConflictSide, GroupRecord, ItemFilter, ItemRecord, ItemRelation, ItemVersion,
+    MetadataStore, OcrCorrection, RelatedItem, RelationType, Reminder, TagInfo, TrashedItem,
+};
+use models::ModelInfo;
+use oauth::{AuthorizationPrompt, OAuthFlows, OAuthProvider};
+use ocr::{OcrBackend, OcrMode, OcrOptions, OcrResult, OcrService, OcrSettings, OcrWord, TesseractEngine};
+use ocr_preprocess::PreprocessPreset;
+use ocr_corrections::{CorrectionDictionary, CorrectionEntry, OcrOverlay, OcrRegion};
+use paths::LibraryPaths;
+use plugins::{PluginHost, PluginInfo};
+use private_items::{PrivateVault, VaultStatus};
+use qr_codes::{QrCode, QrCodeDetails};
+use quick_capture::{CaptureMode, QuickCaptureSettings};
+use receipts::ReceiptFields;
+use resize_cache::{ResizeCache, ResizeFormat, ResizeKey};
+use retention::{RetentionCandidate, RetentionOutcome, RetentionSettings};
+use rules::{Rule, RulesService};
+use search_engine::{DateField, SearchEngine, SearchMode, SearchableItem, SearchQuery, SearchResult};
+use settings::{AppSettings, SettingsStore};
+use dropbox_sync::DropboxConfig;
+use gdrive_sync::GoogleDriveConfig;
+use geocoding::{GeocodedAddress, GeocodedLocation, GeocodingService, GeocodingSettings};
+use sync::{SyncConfig, SyncContext, SyncProviderConfig, SyncReport};
+use table_export::TableFormat;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use tauri_plugin_notification::NotificationExt;
+use thumbnail_cache::{ThumbnailCache, ThumbnailCacheStats, DEFAULT_CACHE_MAX_BYTES};
+use translation::{Translation, TranslationService, TranslationSettings};
+use trash::TrashSettings;
+use upload_server::{UploadServer, UploadServerInfo};
+use watcher::FolderWatcher;
+use webhooks::{Webhook, WebhookEvent, WebhookService};
+
+// 引数にサブコマンドがあれば GUI を起動せずに実行する（main から呼ぶ）
+pub use cli::run_from_args;
+pub use error::AppError;
+pub use image_decode::{decode as decode_image, DecodeError};
+
+// エクスポートなどで検索結果を使うときの件数の上限
+const QUERY_ITEMS_LIMIT: usize = 100_000;
+// 画像の意味での検索で、絞り込みの前に近傍索引から取り出す件数
+const SEMANTIC_CANDIDATES: usize = 500;
+
+const TRASH_PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+const REMINDER_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const RETENTION_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+// 意味での検索の索引は取り込みのたびには保存せず、この間隔でまとめて保存する
+const VECTOR_INDEX_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+// OCR し直すジョブの対象にする単語の確からしさの平均と、1件ごとに空ける間隔
+const OCR_BACKFILL_MAX_CONFIDENCE: f32 = 70.0;
+const OCR_BACKFILL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+// resize_image_cached が返す JPEG の画質（image クレートの既定値と同じ）
+const RESIZE_JPEG_QUALITY: u8 = 75;
+// 検索エンジンの準備を待たずに表示する最近のアイテムの件数
+const RECENT_ITEMS_LIMIT: usize = 200;
 
 // グローバルな検索エンジンインスタンス
 struct SearchEngineState(Mutex<Option<SearchEngine>>);
 
-#[tauri::command]
-async fn init_search_engine(
-    app_handle: tauri::AppHandle,
-    state: State<'_, SearchEngineState>,
-) -> Result<(), String> {
-    let app_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or("App data directory not found")?;
-    
-    let index_path = app_dir.join("search_index");
-    std::fs::create_dir_all(&index_path).map_err(|e| e.to_string())?;
-    
-    let search_engine = SearchEngine::new(&index_path).map_err(|e| e.to_string())?;
+// メタデータストア（SQLite、データの正本）
+struct MetadataStoreState(Mutex<Option<MetadataStore>>);
+
+// サムネイルキャッシュ（起動時に初期化）
+struct ThumbnailCacheState(ThumbnailCache);
+
+// resize_image_cached の結果のキャッシュ（起動時に初期化）
+struct ResizeCacheState(ResizeCache);
+
+// OCRサービス（Tesseract / Windows OCR を設定に従って切り替え）
+struct OcrState(OcrService);
+
+// OCR の直しから覚えた置き換え
+struct CorrectionsState(CorrectionDictionary);
+
+// バックグラウンドジョブ管理
+struct JobManagerState(JobManager);
+
+// フォルダ監視（監視対象がなければ None）
+struct FolderWatchState(Mutex<Option<FolderWatcher>>);
+
+// LAN 同期サーバー（無効なら None）
+struct LanSyncState(Mutex<Option<LanSyncServer>>);
+
+// スマホからの取り込み用アップロードサーバー（停止中は None）
+struct UploadServerState(Mutex<Option<UploadServer>>);
+
+// スクリプトなどから使う REST API サーバー（停止中は None）
+struct ApiServerState(Mutex<Option<ApiServer>>);
+
+// 範囲選択オーバーレイの結果待ち（選択範囲、キャンセル時は None を送る）
+struct ScreenCaptureState(Mutex<Option<tokio::sync::oneshot::Sender<Option<screen_capture::Region>>>>);
+
+const CAPTURE_OVERLAY_LABEL: &str = "capture-overlay";
+
+// 位置情報から地名を求める
+struct GeocodingState(GeocodingService);
+
+// 端末内の推論（ONNX Runtime）とモデルの管理（埋め込みの計算と共有する）
+struct InferenceState(Arc<InferenceService>);
+
+// 画像の埋め込みと意味での検索の索引
+struct EmbeddingState(EmbeddingService);
+
+// 画像の説明文の生成
+struct CaptionState(CaptionService);
+
+// OCR のテキストの翻訳（他の言語で探しても見つかるようにする）
+struct TranslationState(TranslationService);
+
+// 取得した為替レート（レシートの金額の換算）
+struct ExchangeRatesState(ExchangeRates);
+
+// 取り込み時の自動タグ付けルール
+struct RulesState(RulesService);
+struct MemoTemplatesState(MemoTemplates);
+
+// 非公開アイテムの鍵（アンロック状態はアプリを終了するまで保持）
+struct PrivateVaultState(PrivateVault);
+
+// アプリ全体の設定
+struct SettingsState(SettingsStore);
+
+// ライブラリのイベントを外部の URL へ送る
+struct WebhookState(WebhookService);
+
+// 取り込み時に呼ぶ外部プラグイン
+struct PluginState(PluginHost);
+
+// 保持している間だけログがファイルへ書き込まれる
+struct LogGuardState(#[allow(dead_code)] tracing_appender::non_blocking::WorkerGuard);
+
+// アプリのロック（ロック中は thumb / snap プロトコルで画像を返さない）
+struct AppLockState(AppLock);
+
+fn is_app_locked(app_handle: &AppHandle) -> bool {
+    app_handle.try_state::<AppLockState>().is_some_and(|state| state.0.is_locked())
+}
+
+fn locked_response() -> tauri::http::Response<Vec<u8>> {
+    protocol::error_response(tauri::http::StatusCode::FORBIDDEN, "App is locked".to_string())
+}
+
+// 進行中の OAuth サインイン（完了待ちはブロッキングスレッドで行うため Arc で共有）
+struct OAuthState(Arc<OAuthFlows>);
+
+// 取り込み処理に必要な状態をまとめて渡す
+fn with_import_context<R>(
+    app_handle: &AppHandle,
+    f: impl FnOnce(&ImportContext) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    let paths = LibraryPaths::from_app(app_handle)?;
+    let store = app_handle.state::<MetadataStoreState>();
+    let search = app_handle.state::<SearchEngineState>();
+    let ocr = app_handle.state::<OcrState>();
+    let corrections = app_handle.state::<CorrectionsState>();
+    let embeddings = app_handle.state::<EmbeddingState>();
+    let captions = app_handle.state::<CaptionState>();
+    let translator = app_handle.state::<TranslationState>();
+    let geocoder = app_handle.state::<GeocodingState>();
+    let rules = app_handle.state::<RulesState>();
+    let webhooks = app_handle.state::<WebhookState>();
+    let plugins = app_handle.state::<PluginState>();
+    let ctx = ImportContext {
+        paths: &paths,
+        store: &store.0,
+        search: &search.0,
+        ocr: Some(&ocr.0),
+        corrections: Some(&corrections.0),
+        embeddings: Some(&embeddings.0),
+        captions: Some(&captions.0),
+        translator: Some(&translator.0),
+        geocoder: Some(&geocoder.0),
+        rules: Some(&rules.0),
+        duplicates: app_handle.state::<SettingsState>().0.get().duplicate_policy,
+        whiteboard: false,
+        webhooks: Some(&webhooks.0),
+        plugins: Some(&plugins.0),
+    };
+    f(&ctx)
+}
+
+// 監視フォルダやスマホから届いた画像を取り込み、進捗をイベントで通知
+fn auto_import(app_handle: &AppHandle, path: &Path) -> anyhow::Result<ItemRecord> {
+    let _ = app_handle.emit("import-progress", ImportProgress::started(path));
+
+    let result = with_import_context(app_handle, |ctx| import_pipeline::import_file(ctx, path));
+
+    let progress = match &result {
+        Ok(outcome) => outcome.progress(path),
+        Err(e) => ImportProgress::failed(path, e),
+    };
+    let _ = app_handle.emit("import-progress", progress);
+    result.map(ImportOutcome::into_item)
+}
+
+// メタデータストアの内容を検索インデックスへ反映（検索エンジン未初期化なら何もしない）
+fn index_item(search: &SearchEngineState, store: &MetadataStore, item: &ItemRecord) -> Result<(), AppError> {
+    let searchable = store.to_searchable(item).map_err(AppError::from)?;
+    if let Some(engine) = search.0.lock().unwrap().as_mut() {
+        engine.update_item(searchable).map_err(AppError::from)?;
+        store.clear_index_journal(std::slice::from_ref(&item.id)).map_err(AppError::from)?;
+    }
+    Ok(())
+}
+
+// 複数のアイテムを検索インデックスへ反映し、コミットは1回にまとめる
+fn index_items(search: &SearchEngineState, store: &MetadataStore, items: &[ItemRecord]) -> anyhow::Result<()> {
+    let searchable = items
+        .iter()
+        .map(|item| store.to_searchable(item))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if let Some(engine) = search.0.lock().unwrap().as_mut() {
+        engine.update_items(searchable)?;
+        let ids: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+        store.clear_index_journal(&ids)?;
+    }
+    Ok(())
+}
+
+// 前回の終了までに検索インデックスへ反映できなかった変更を反映し直す
+fn replay_index_journal(store: &MetadataStore, search_engine: &mut SearchEngine) -> anyhow::Result<usize> {
+    let ids = store.index_journal()?;
+    for id in &ids {
+        match store.get_item(id)? {
+            Some(item) => search_engine.update_item(store.to_searchable(&item)?)?,
+            None => search_engine.delete_item(id)?,
+        }
+    }
+    store.clear_index_journal(&ids)?;
+    Ok(ids.len())
+}
+
+// 新しく付いたタグがあれば Webhook で知らせる（非公開アイテムは送らない）
+fn notify_tags_added(app_handle: &AppHandle, before: &[String], item: &ItemRecord) {
+    let added: Vec<&String> = item.tags.iter().filter(|t| !before.contains(t)).collect();
+    if added.is_empty() || item.private {
+        return;
+    }
+    app_handle.state::<WebhookState>().0.notify(
+        WebhookEvent::TagAdded,
+        serde_json::json!({ "tags": added, "item": item }),
+    );
+}
+
+fn start_folder_watcher(app_handle: &AppHandle, folders: &[PathBuf]) -> anyhow::Result<Option<FolderWatcher>> {
+    if folders.is_empty() {
+        return Ok(None);
+    }
+    let handle = app_handle.clone();
+    let watcher = FolderWatcher::start(folders, move |path| {
+        let _ = auto_import(&handle, &path);
+    })?;
+    Ok(Some(watcher))
+}
+
+// 検索インデックスを開き、前回反映できなかった変更を反映してから使えるようにする
+// 起動時にバックグラウンドで呼ぶ（すでに開いていれば何もしない）
+fn open_search_engine(app_handle: &AppHandle) -> anyhow::Result<()> {
+    // 起動時のスレッドと init_search_engine が同時に開かないようにする
+    static OPENING: Mutex<()> = Mutex::new(());
+    let _opening = OPENING.lock().unwrap();
+    let state = app_handle.state::<SearchEngineState>();
+    if state.0.lock().unwrap().is_some() {
+        return Ok(());
+    }
+
+    let started = std::time::Instant::now();
+    let paths = LibraryPaths::from_app(app_handle)?;
+    let index_path = paths.index_dir();
+    std::fs::create_dir_all(&index_path)?;
+    let mut search_engine = SearchEngine::new(&index_path)?;
+    let store_state = app_handle.state::<MetadataStoreState>();
+    if let Some(store) = store_state.0.lock().unwrap().as_ref() {
+        if search_engine.was_recreated() {
+            let count = rebuild_index(store, &mut search_engine)?;
+            log::info!("Rebuilt search index with {} items", count);
+        } else {
+            match replay_index_journal(store, &mut search_engine) {
+                Ok(0) => {}
+                Ok(count) => log::info!("Replayed {} pending index updates", count),
+                Err(e) => log::warn!("Failed to replay index journal: {}", e),
+            }
+        }
+    }
+    let documents = search_engine.warm_up()?;
     *state.0.lock().unwrap() = Some(search_engine);
-    
+    log::info!("Search index ready ({} documents, {:?})", documents, started.elapsed());
+    let _ = app_handle.emit("search-ready", documents);
     Ok(())
 }
 
+#[tauri::command]
+async fn init_search_engine(app_handle: tauri::AppHandle) -> Result<(), AppError> {
+    tauri::async_runtime::spawn_blocking(move || open_search_engine(&app_handle))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn is_search_ready(state: State<'_, SearchEngineState>) -> Result<bool, AppError> {
+    Ok(state.0.lock().unwrap().is_some())
+}
+
+// 検索エンジンの準備ができる前でも SQLite からすぐに返せる最近のアイテム
+#[tauri::command]
+async fn get_recent_items(
+    limit: Option<usize>,
+    state: State<'_, MetadataStoreState>,
+    vault: State<'_, PrivateVaultState>,
+) -> Result<Vec<ItemRecord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    // アーカイブしたアイテムは最近のアイテムに出さない
+    let filter = ItemFilter {
+        archived: Some(false),
+        limit: Some(limit.unwrap_or(RECENT_ITEMS_LIMIT)),
+        ..Default::default()
+    };
+    let items = store.list_items(&filter).map_err(AppError::from)?;
+    Ok(vault.0.visible(store, items))
+}
+
 #[tauri::command]
 async fn add_item_to_index(
     item: SearchableItem,
     state: State<'_, SearchEngineState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut engine = state.0.lock().unwrap();
     let search_engine = engine.as_mut().ok_or("Search engine not initialized")?;
     
-    search_engine.add_item(item).map_err(|e| e.to_string())?;
+    search_engine.add_item(item).map_err(AppError::from)?;
     Ok(())
 }
 
@@ -47,11 +462,11 @@ async fn add_item_to_index(
 async fn update_item_in_index(
     item: SearchableItem,
     state: State<'_, SearchEngineState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut engine = state.0.lock().unwrap();
     let search_engine = engine.as_mut().ok_or("Search engine not initialized")?;
     
-    search_engine.update_item(item).map_err(|e| e.to_string())?;
+    search_engine.update_item(item).map_err(AppError::from)?;
     Ok(())
 }
 
@@ -59,84 +474,4184 @@ async fn update_item_in_index(
 async fn delete_item_from_index(
     item_id: String,
     state: State<'_, SearchEngineState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut engine = state.0.lock().unwrap();
     let search_engine = engine.as_mut().ok_or("Search engine not initialized")?;
     
-    search_engine.delete_item(&item_id).map_err(|e| e.to_string())?;
+    search_engine.delete_item(&item_id).map_err(AppError::from)?;
     Ok(())
 }
 
 #[tauri::command]
 async fn search_items(
     query: SearchQuery,
+    store_state: State<'_, MetadataStoreState>,
     state: State<'_, SearchEngineState>,
-) -> Result<Vec<SearchResult>, String> {
+    vault: State<'_, PrivateVaultState>,
+    embeddings: State<'_, EmbeddingState>,
+) -> Result<Vec<SearchResult>, AppError> {
+    let store = store_state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    let text = query.query.clone();
+    let limit = query.limit.unwrap_or(20);
+    // メタデータストアで絞り込む条件がある場合は、非公開アイテムのテキストからは探さない
+    let store_filter = query.receipt.is_some() || query.near.is_some();
+    // album: などの条件だけの検索はキーワードの検索と同じ扱いにする
+    let mode = if search_engine::extract_album_filters(&text).0.trim().is_empty() {
+        SearchMode::Keyword
+    } else {
+        query.mode
+    };
+    let mut results = match mode {
+        SearchMode::Keyword => search_index(store, &state, query)?,
+        SearchMode::Semantic => {
+            if !embeddings.0.is_available() {
+                return Err("Semantic search models are not installed".into());
+            }
+            semantic_results(store, &embeddings.0, &query, limit).map_err(AppError::from)?
+        }
+        // モデルがなければキーワードだけで探す
+        SearchMode::Hybrid => {
+            let semantic = if embeddings.0.is_available() {
+                semantic_results(store, &embeddings.0, &query, limit * 2).map_err(AppError::from)?
+            } else {
+                Vec::new()
+            };
+            let keyword = search_index(
+                store,
+                &state,
+                SearchQuery {
+                    limit: Some(limit * 2),
+                    ..query
+                },
+            )?;
+            search_engine::reciprocal_rank_fusion(vec![keyword, semantic], limit)
+        }
+    };
+
+    // 非公開アイテムはロック中は結果に出さず、アンロック中は復号したテキストからも探す
+    let private_ids = store.private_item_ids().map_err(AppError::from)?;
+    if !vault.0.is_unlocked() {
+        results.retain(|result| !private_ids.contains(&result.id));
+    } else if !store_filter && search_engine::extract_album_filters(&text).1.is_empty() {
+        let found = vault.0.search(store, &text).map_err(AppError::from)?;
+        results.retain(|result| !found.iter().any(|f| f.id == result.id));
+        results.extend(found);
+        results.truncate(limit);
+    }
+    Ok(results)
+}
+
+// 画像の埋め込みでの検索（album: を除いた検索文字列との類似度の高い順）
+// キーワードの検索と同じ絞り込み条件（アルバム・レシート・日付・タグ・書類の種類）をメタデータストアで当てる
+fn semantic_results(
+    store: &MetadataStore,
+    embeddings: &EmbeddingService,
+    query: &SearchQuery,
+    limit: usize,
+) -> anyhow::Result<Vec<SearchResult>> {
+    let mut filters = SearchQuery {
+        query: query.query.clone(),
+        receipt: query.receipt.clone(),
+        date_from: query.date_from,
+        date_to: query.date_to,
+        date_field: query.date_field,
+        near: query.near,
+        ..Default::default()
+    };
+    let allowed = resolve_store_filters(store, &mut filters)?;
+    let vector = embeddings.embed_text(&filters.query)?;
+    let tags = tag_matchers(store, query.tags.clone())?;
+    let doc_types = query.doc_types.clone().filter(|t| !t.is_empty());
+
+    let mut results = Vec::new();
+    for (id, score) in embeddings.search(&vector, SEMANTIC_CANDIDATES) {
+        if allowed.as_ref().is_some_and(|allowed| !allowed.contains(&id)) {
+            continue;
+        }
+        let Some(item) = store.get_item(&id)? else {
+            continue;
+        };
+        let in_range = !filters.date_from.is_some_and(|from| item.created_at < from)
+            && !filters.date_to.is_some_and(|to| item.created_at > to);
+        let type_matches = !doc_types.as_ref().is_some_and(|types| !types.contains(&item.doc_type));
+        if item.private || !in_range || !type_matches || !matches_tags(&item, &tags) {
+            continue;
+        }
+        results.push(SearchResult {
+            id,
+            score,
+            highlights: Vec::new(),
+            matched_fields: vec!["image".to_string()],
+        });
+        if results.len() >= limit {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+// 検索インデックスとアルバム・レシートの絞り込みによる検索（非公開アイテムの扱いは呼び出し側で行う）
+fn search_index(
+    store: &MetadataStore,
+    state: &SearchEngineState,
+    mut query: SearchQuery,
+) -> Result<Vec<SearchResult>, AppError> {
+    let allowed = resolve_store_filters(store, &mut query).map_err(AppError::from)?;
+    let allowed = match allowed {
+        Some(allowed) => allowed,
+        None => {
+            let engine = state.0.lock().unwrap();
+            let search_engine = engine.as_ref().ok_or("Search engine not initialized")?;
+            return search_engine.search(query).map_err(AppError::from);
+        }
+    };
+
+    // album: やレシートの条件だけの検索は当てはまるアイテムを新しい順に返す
+    let limit = query.limit.unwrap_or(20);
+    if query.query.trim().is_empty() {
+        let filter = ItemFilter {
+            date_from: query.date_from,
+            date_to: query.date_to,
+            doc_types: query.doc_types.clone(),
+            ..Default::default()
+        };
+        let tags = tag_matchers(store, query.tags).map_err(AppError::from)?;
+        return Ok(store
+            .list_items(&filter)
+            .map_err(AppError::from)?
+            .into_iter()
+            .filter(|item| allowed.contains(&item.id) && matches_tags(item, &tags))
+            .take(limit)
+            .map(|item| SearchResult {
+                id: item.id,
+                score: 0.0,
+                highlights: Vec::new(),
+                matched_fields: Vec::new(),
+            })
+            .collect());
+    }
+
     let engine = state.0.lock().unwrap();
     let search_engine = engine.as_ref().ok_or("Search engine not initialized")?;
-    
-    search_engine.search(query).map_err(|e| e.to_string())
+    let mut results = search_engine
+        .search(SearchQuery {
+            limit: Some(QUERY_ITEMS_LIMIT),
+            ..query
+        })
+        .map_err(AppError::from)?;
+    results.retain(|result| allowed.contains(&result.id));
+    results.truncate(limit);
+    Ok(results)
 }
 
 #[tauri::command]
 async fn clear_search_index(
     state: State<'_, SearchEngineState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut engine = state.0.lock().unwrap();
     let search_engine = engine.as_mut().ok_or("Search engine not initialized")?;
     
-    search_engine.clear_index().map_err(|e| e.to_string())?;
+    search_engine.clear_index().map_err(AppError::from)?;
     Ok(())
 }
 
 #[tauri::command]
 async fn get_search_stats(
     state: State<'_, SearchEngineState>,
-) -> Result<HashMap<String, usize>, String> {
+) -> Result<HashMap<String, usize>, AppError> {
     let engine = state.0.lock().unwrap();
     let search_engine = engine.as_ref().ok_or("Search engine not initialized")?;
     
-    search_engine.get_stats().map_err(|e| e.to_string())
+    search_engine.get_stats().map_err(AppError::from)
 }
 
-// 既存のコマンド（画像リサイズなど）
 #[tauri::command]
-async fn resize_image(
-    image_data: Vec<u8>,
-    max_width: u32,
-    max_height: u32,
-) -> Result<Vec<u8>, String> {
-    use image::{DynamicImage, GenericImageView};
-    
-    let img = image::load_from_memory(&image_data)
-        .map_err(|e| format!("Failed to load image: {}", e))?;
-    
-    let (width, height) = img.dimensions();
-    
-    // アスペクト比を保持してリサイズ
-    let (new_width, new_height) = if width > height {
-        let ratio = max_width as f32 / width as f32;
-        (max_width, (height as f32 * ratio) as u32)
+async fn get_thumbnail(
+    path: String,
+    size: u32,
+    state: State<'_, ThumbnailCacheState>,
+) -> Result<String, AppError> {
+    let cached = state
+        .0
+        .get_or_create(&PathBuf::from(path), size)
+        .map_err(AppError::from)?;
+    Ok(cached.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn clear_thumbnail_cache(
+    state: State<'_, ThumbnailCacheState>,
+) -> Result<(), AppError> {
+    state.0.clear().map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_thumbnail_cache_stats(
+    state: State<'_, ThumbnailCacheState>,
+) -> Result<ThumbnailCacheStats, AppError> {
+    state.0.stats().map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_watch_folders(
+    state: State<'_, FolderWatchState>,
+) -> Result<Vec<String>, AppError> {
+    let watcher = state.0.lock().unwrap();
+    Ok(watcher
+        .as_ref()
+        .map(|w| w.folders().iter().map(|f| f.to_string_lossy().to_string()).collect())
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+async fn set_watch_folders(
+    folders: Vec<String>,
+    app_handle: AppHandle,
+    state: State<'_, FolderWatchState>,
+) -> Result<(), AppError> {
+    let folders: Vec<PathBuf> = folders.into_iter().map(PathBuf::from).collect();
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+
+    // 古い監視を止めてから新しい設定で開始
+    let mut watcher = state.0.lock().unwrap();
+    *watcher = None;
+    *watcher = start_folder_watcher(&app_handle, &folders).map_err(AppError::from)?;
+
+    watcher::save_watch_folders(&paths, &folders).map_err(AppError::from)?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn ocr_image(
+    path: Option<String>,
+    bytes: Option<Vec<u8>>,
+    languages: Option<Vec<String>>,
+    mode: Option<OcrMode>,
+    state: State<'_, OcrState>,
+) -> Result<OcrResult, AppError> {
+    let languages = languages.unwrap_or_else(ocr::default_languages);
+
+    let mode = mode.unwrap_or_default();
+
+    match (path, bytes) {
+        (Some(path), _) => state.0.recognize_file_as(Path::new(&path), &languages, mode),
+        (None, Some(bytes)) => state.0.recognize_bytes(&bytes, &languages, mode),
+        (None, None) => return Err(AppError::InvalidInput("Either path or bytes is required".to_string())),
+    }
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_ocr_languages(
+    state: State<'_, OcrState>,
+) -> Result<Vec<String>, AppError> {
+    let engine = state.0.tesseract().ok_or("OCR engine not available")?;
+    Ok(engine.installed_languages())
+}
+
+fn language_pack_manager(app_handle: &AppHandle) -> anyhow::Result<LanguagePackManager> {
+    Ok(LanguagePackManager::new(LibraryPaths::from_app(app_handle)?.tessdata_dir()))
+}
+
+// ダウンロードできる OCR の言語データとインストール状況、使用しているディスク容量
+#[tauri::command]
+async fn list_ocr_language_packs(app_handle: AppHandle) -> Result<LanguagePacks, AppError> {
+    Ok(language_pack_manager(&app_handle)?.list())
+}
+
+// 韓国語・中国語などの言語データをダウンロードする（ジョブとして実行し、ジョブIDを返す）
+// アプリを入れ直さなくても次の OCR から使える
+#[tauri::command]
+async fn download_ocr_language_pack(
+    code: String,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let pack = language_packs::pack(&code).map_err(AppError::from)?;
+    let name = i18n::format("job.download_ocr_language", &[("name", pack.name)]);
+    let job_id = state.0.submit("download_ocr_language", &name, move |job| {
+        language_pack_manager(&app_handle)?.download(&code, job)?;
+        Ok(serde_json::json!({ "code": code }))
+    });
+    Ok(job_id)
+}
+
+// ダウンロードした言語データを検証する（ハッシュが合わなければ false）
+#[tauri::command]
+async fn verify_ocr_language_pack(code: String, app_handle: AppHandle) -> Result<bool, AppError> {
+    tauri::async_runtime::spawn_blocking(move || language_pack_manager(&app_handle)?.verify(&code))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+// 言語データを削除する（既定で使う日本語と英語は削除できない）
+#[tauri::command]
+async fn delete_ocr_language_pack(code: String, app_handle: AppHandle) -> Result<bool, AppError> {
+    language_pack_manager(&app_handle)?.delete(&code).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_ocr_settings(
+    state: State<'_, OcrState>,
+) -> Result<OcrSettings, AppError> {
+    Ok(state.0.settings())
+}
+
+#[tauri::command]
+async fn set_ocr_settings(
+    settings: OcrSettings,
+    state: State<'_, OcrState>,
+) -> Result<(), AppError> {
+    state.0.set_settings(settings).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_ocr_backends(
+    state: State<'_, OcrState>,
+) -> Result<Vec<OcrBackend>, AppError> {
+    Ok(state.0.available_backends())
+}
+
+#[tauri::command]
+async fn get_jobs(
+    state: State<'_, JobManagerState>,
+) -> Result<Vec<JobInfo>, AppError> {
+    Ok(state.0.list())
+}
+
+#[tauri::command]
+async fn get_job(
+    job_id: String,
+    state: State<'_, JobManagerState>,
+) -> Result<Option<JobInfo>, AppError> {
+    Ok(state.0.get(&job_id))
+}
+
+#[tauri::command]
+async fn pause_job(
+    job_id: String,
+    state: State<'_, JobManagerState>,
+) -> Result<(), AppError> {
+    state.0.pause(&job_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn resume_job(
+    job_id: String,
+    state: State<'_, JobManagerState>,
+) -> Result<(), AppError> {
+    state.0.resume(&job_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn cancel_job(
+    job_id: String,
+    state: State<'_, JobManagerState>,
+) -> Result<(), AppError> {
+    state.0.cancel(&job_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn clear_finished_jobs(
+    state: State<'_, JobManagerState>,
+) -> Result<(), AppError> {
+    state.0.clear_finished();
+    Ok(())
+}
+
+// クリップボードの画像（スクリーンショットなど）を取り込み、新しいアイテムのIDを返す
+#[tauri::command]
+async fn import_from_clipboard(app_handle: AppHandle) -> Result<String, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let paths = LibraryPaths::from_app(&app_handle)?;
+        let staged = clipboard::save_clipboard_image(&paths.staging_dir())?;
+        let result = auto_import(&app_handle, &staged);
+        let _ = std::fs::remove_file(&staged);
+        Ok::<_, anyhow::Error>(result?.id)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// キャプチャした画像を取り込み、Inbox タグを付ける（キャンセルされたら None）
+fn run_quick_capture(app_handle: &AppHandle, mode: Option<CaptureMode>) -> anyhow::Result<Option<ItemRecord>> {
+    // ショートカットの連打で選択画面が重ならないようにする
+    static CAPTURING: AtomicBool = AtomicBool::new(false);
+    if CAPTURING.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    let result = (|| {
+        let paths = LibraryPaths::from_app(app_handle)?;
+        let settings = QuickCaptureSettings::load(&paths.quick_capture_settings_file());
+        let staged = match quick_capture::capture(mode.unwrap_or(settings.mode), &paths.staging_dir())? {
+            Some(staged) => staged,
+            None => return Ok(None),
+        };
+        let imported = auto_import(app_handle, &staged);
+        let _ = std::fs::remove_file(&staged);
+        let mut item = imported?;
+
+        let tag = settings.inbox_tag.trim();
+        if !tag.is_empty() && !item.tags.iter().any(|t| t == tag) {
+            let before = item.tags.clone();
+            item.tags.push(tag.to_string());
+            let store_state = app_handle.state::<MetadataStoreState>();
+            let mut store = store_state.0.lock().unwrap();
+            let store = store.as_mut().context("Metadata store not initialized")?;
+            item = store.update_item(&item)?;
+            index_item(&app_handle.state::<SearchEngineState>(), store, &item).map_err(anyhow::Error::msg)?;
+            notify_tags_added(app_handle, &before, &item);
+        }
+        let _ = app_handle.emit("quick-capture", &item);
+        Ok(Some(item))
+    })();
+    CAPTURING.store(false, Ordering::SeqCst);
+    result
+}
+
+// ショートカットやトレイから呼ばれたときはメインウィンドウを待たずに別スレッドで取り込む
+fn spawn_quick_capture(app_handle: &AppHandle, mode: Option<CaptureMode>) {
+    let handle = app_handle.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = run_quick_capture(&handle, mode) {
+            log::warn!("Quick capture failed: {}", e);
+            let _ = handle.emit("quick-capture-failed", e.to_string());
+        }
+    });
+}
+
+// 設定のショートカットを登録し直す
+fn register_quick_capture_shortcut(app_handle: &AppHandle, settings: &QuickCaptureSettings) -> anyhow::Result<()> {
+    let shortcuts = app_handle.global_shortcut();
+    shortcuts.unregister_all()?;
+    if let Some(shortcut) = settings.shortcut.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        shortcuts.register(shortcut)?;
+    }
+    Ok(())
+}
+
+fn show_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+// 取り込み済み（内容が同じ）ならそのアイテムを、なければ取り込んだアイテムを返す
+fn open_file(app_handle: &AppHandle, path: &Path) -> anyhow::Result<ItemRecord> {
+    let hash = hashing::content_hash(&std::fs::read(path)?);
+    {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        if let Some(existing) = store.find_by_hash(&hash)? {
+            return Ok(existing);
+        }
+    }
+    auto_import(app_handle, path)
+}
+
+// ダブルクリックや「このアプリケーションで開く」で渡された画像をウィンドウに表示する
+// 起動時の引数・2つ目の起動の引数（single-instance）・macOS の Opened イベントから呼ぶ
+fn open_files(app_handle: &AppHandle, paths: Vec<PathBuf>) {
+    let paths: Vec<PathBuf> = paths
+        .into_iter()
+        .filter(|path| paths::is_image_path(path) && path.is_file())
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    show_main_window(app_handle);
+    let handle = app_handle.clone();
+    // 取り込み（OCR を含む）は時間がかかるため UI のスレッドでは行わない
+    std::thread::spawn(move || {
+        for path in paths {
+            match open_file(&handle, &path) {
+                Ok(item) => {
+                    let _ = handle.emit("open-item", &item.id);
+                }
+                Err(e) => log::warn!("Failed to open {}: {}", path.display(), e),
+            }
+        }
+    });
+}
+
+// snaporganizer:// のリンクで開かれたとき、アイテムまたは検索結果を表示する
+fn open_deep_links(app_handle: &AppHandle, urls: &[tauri::Url]) {
+    for url in urls {
+        match DeepLink::parse(url) {
+            Some(DeepLink::Item(id)) => {
+                show_main_window(app_handle);
+                let _ = app_handle.emit("open-item", &id);
+            }
+            Some(DeepLink::Search(query)) => {
+                show_main_window(app_handle);
+                let _ = app_handle.emit("open-search", &query);
+            }
+            None => log::warn!("Unsupported deep link: {}", url),
+        }
+    }
+}
+
+// 他のアプリに貼り付けるためのリンク
+#[tauri::command]
+async fn get_item_link(item_id: String) -> Result<String, AppError> {
+    Ok(DeepLink::Item(item_id).to_url())
+}
+
+#[tauri::command]
+async fn get_search_link(query: String) -> Result<String, AppError> {
+    Ok(DeepLink::Search(query).to_url())
+}
+
+// ドラッグで渡すファイルを一時フォルダに書き出す（非公開アイテムはアンロック中のみ）
+fn prepare_drag_files(app_handle: &AppHandle, item_ids: &[String], format: DragFormat) -> anyhow::Result<DragFiles> {
+    if is_app_locked(app_handle) {
+        anyhow::bail!("App is locked");
+    }
+    let paths = LibraryPaths::from_app(app_handle)?;
+    drag_out::purge_old(&paths.drag_out_dir());
+
+    let vault = app_handle.state::<PrivateVaultState>();
+    let items = {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        let mut items = Vec::new();
+        for id in item_ids {
+            if let Some(item) = store.get_item(id)? {
+                items.push(vault.0.reveal(store, item));
+            }
+        }
+        items
+    };
+    let mut sources = Vec::new();
+    for item in items {
+        let Some(path) = item.image_path.as_deref().map(PathBuf::from) else {
+            continue;
+        };
+        let data = if private_items::is_sealed_path(&path) {
+            vault.0.read_image(&path)?
+        } else {
+            std::fs::read(&path)?
+        };
+        sources.push((item, data));
+    }
+    if sources.is_empty() {
+        anyhow::bail!("No images to drag");
+    }
+    let dir = paths.drag_out_dir().join(uuid::Uuid::new_v4().simple().to_string());
+    drag_out::materialize(&sources, &dir, format)
+}
+
+// ドラッグの開始時に UI から呼ぶ（ファイルを書き出してから OS のドラッグを始める）
+#[tauri::command]
+async fn start_drag_out(
+    item_ids: Vec<String>,
+    format: Option<DragFormat>,
+    window: tauri::WebviewWindow,
+    app_handle: AppHandle,
+) -> Result<DragFiles, AppError> {
+    let files = tauri::async_runtime::spawn_blocking(move || {
+        prepare_drag_files(&app_handle, &item_ids, format.unwrap_or_default())
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)?;
+
+    let item = drag::DragItem::Files(files.files.iter().map(PathBuf::from).collect());
+    let icon = drag::Image::File(files.icon.clone().map(PathBuf::from).unwrap_or_default());
+    let target = window.clone();
+    // OS のドラッグはメインスレッドから始める必要がある
+    window
+        .run_on_main_thread(move || {
+            #[cfg(target_os = "linux")]
+            let handle = target.gtk_window();
+            #[cfg(not(target_os = "linux"))]
+            let handle = tauri::Result::Ok(target);
+            let result = handle
+                .map_err(anyhow::Error::from)
+                .and_then(|handle| Ok(drag::start_drag(&handle, item, icon, |_, _| {}, Default::default())?));
+            if let Err(e) = result {
+                log::warn!("Failed to start drag: {}", e);
+            }
+        })
+        .map_err(AppError::from)?;
+    Ok(files)
+}
+
+// 長いジョブの完了・失敗・同期の衝突を OS の通知で知らせる（設定で量を変えられる）
+fn notify_job_finished(app_handle: &AppHandle, job: &JobInfo) {
+    let level = match app_handle.try_state::<SettingsState>() {
+        Some(settings) => settings.0.get().job_notifications,
+        None => return,
+    };
+    let focused = app_handle
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false);
+    let Some((title, body)) = job_notifications::message(job, level, focused) else {
+        return;
+    };
+    if let Err(e) = app_handle.notification().builder().title(title).body(body).show() {
+        log::warn!("Failed to show job notification: {}", e);
+    }
+}
+
+fn setup_tray(app: &tauri::App) -> tauri::Result<()> {
+    let capture = MenuItem::with_id(app, "quick-capture", i18n::text("tray.quick_capture"), true, None::<&str>)?;
+    let clipboard = MenuItem::with_id(app, "capture-clipboard", i18n::text("tray.capture_clipboard"), true, None::<&str>)?;
+    let show = MenuItem::with_id(app, "show", i18n::text("tray.show"), true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", i18n::text("tray.quit"), true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&capture, &clipboard, &show, &quit])?;
+
+    let mut tray = TrayIconBuilder::with_id("main").tooltip("Snap Organizer").menu(&menu);
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.on_menu_event(|app, event| match event.id.as_ref() {
+        "quick-capture" => spawn_quick_capture(app, Some(CaptureMode::Region)),
+        "capture-clipboard" => spawn_quick_capture(app, Some(CaptureMode::Clipboard)),
+        "show" => show_main_window(app),
+        "quit" => app.exit(0),
+        _ => {}
+    })
+    .build(app)?;
+    Ok(())
+}
+
+// クイックキャプチャを実行し、取り込んだアイテムのIDを返す（キャンセル時は None）
+#[tauri::command]
+async fn quick_capture(mode: Option<CaptureMode>, app_handle: AppHandle) -> Result<Option<String>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || run_quick_capture(&app_handle, mode))
+        .await
+        .map_err(AppError::from)?
+        .map(|item| item.map(|item| item.id))
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_quick_capture_settings(app_handle: AppHandle) -> Result<QuickCaptureSettings, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    Ok(QuickCaptureSettings::load(&paths.quick_capture_settings_file()))
+}
+
+#[tauri::command]
+async fn set_quick_capture_settings(settings: QuickCaptureSettings, app_handle: AppHandle) -> Result<(), AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    register_quick_capture_shortcut(&app_handle, &settings).map_err(AppError::from)?;
+    settings.save(&paths.quick_capture_settings_file()).map_err(AppError::from)
+}
+
+// カーソルのあるモニターを覆うオーバーレイを開き、撮影済みの画面から範囲を選ばせる
+fn open_capture_overlay(app_handle: &AppHandle, screenshot: &Path) -> anyhow::Result<()> {
+    if let Some(window) = app_handle.get_webview_window(CAPTURE_OVERLAY_LABEL) {
+        window.destroy()?;
+    }
+    let image = percent_encoding::utf8_percent_encode(&screenshot.to_string_lossy(), percent_encoding::NON_ALPHANUMERIC)
+        .to_string();
+    let url = WebviewUrl::App(format!("capture-overlay.html?image={}", image).into());
+    let mut builder = WebviewWindowBuilder::new(app_handle, CAPTURE_OVERLAY_LABEL, url)
+        .title("Snap Organizer")
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .resizable(false)
+        .focused(true);
+    let cursor = app_handle.cursor_position().ok();
+    if let Some(monitor) = cursor.and_then(|p| app_handle.monitor_from_point(p.x, p.y).ok().flatten()) {
+        let scale = monitor.scale_factor();
+        let (position, size) = (monitor.position(), monitor.size());
+        builder = builder
+            .position(position.x as f64 / scale, position.y as f64 / scale)
+            .inner_size(size.width as f64 / scale, size.height as f64 / scale);
     } else {
-        let ratio = max_height as f32 / height as f32;
-        ((width as f32 * ratio) as u32, max_height)
+        builder = builder.fullscreen(true);
+    }
+    let window = builder.build()?;
+
+    // Alt+F4 などで閉じられたときもキャンセルとして扱う
+    let handle = app_handle.clone();
+    window.on_window_event(move |event| {
+        if let tauri::WindowEvent::Destroyed = event {
+            if let Some(sender) = handle.state::<ScreenCaptureState>().0.lock().unwrap().take() {
+                let _ = sender.send(None);
+            }
+        }
+    });
+    Ok(())
+}
+
+// 画面の範囲を選んで撮影し、取り込んだアイテムのIDを返す（キャンセル時は None）
+#[tauri::command]
+async fn capture_screen_region(
+    app_handle: AppHandle,
+    state: State<'_, ScreenCaptureState>,
+) -> Result<Option<String>, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let point = app_handle.cursor_position().ok().map(|p| (p.x as i32, p.y as i32));
+    let staging_dir = paths.staging_dir();
+    let screenshot = tauri::async_runtime::spawn_blocking(move || screen_capture::capture_monitor(&staging_dir, point))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)?;
+
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    // 前の選択が残っていればキャンセルされる（Sender が破棄される）
+    *state.0.lock().unwrap() = Some(sender);
+    if let Err(e) = open_capture_overlay(&app_handle, &screenshot) {
+        state.0.lock().unwrap().take();
+        let _ = std::fs::remove_file(&screenshot);
+        return Err(e.into());
+    }
+    let region = receiver.await.ok().flatten();
+    if let Some(window) = app_handle.get_webview_window(CAPTURE_OVERLAY_LABEL) {
+        let _ = window.destroy();
+    }
+
+    let result = match region {
+        Some(region) => {
+            let handle = app_handle.clone();
+            let shot = screenshot.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let cropped = screen_capture::crop(&shot, &region)?;
+                let imported = auto_import(&handle, &cropped);
+                let _ = std::fs::remove_file(&cropped);
+                Ok::<_, anyhow::Error>(Some(imported?.id))
+            })
+            .await
+            .map_err(AppError::from)?
+            .map_err(AppError::from)
+        }
+        None => Ok(None),
     };
-    
-    let resized = img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3);
-    
-    let mut output = Vec::new();
-    resized
-        .write_to(&mut std::io::Cursor::new(&mut output), image::ImageFormat::Jpeg)
-        .map_err(|e| format!("Failed to encode image: {}", e))?;
-    
-    Ok(output)
+    let _ = std::fs::remove_file(&screenshot);
+    result
 }
 
-fn main() {
-    tauri::Builder::default()
-        .manage(SearchEngineState(Mutex::new(None)))
+// オーバーレイで範囲が選ばれた（またはキャンセルされた）ときに呼ばれる
+#[tauri::command]
+async fn finish_screen_region(
+    region: Option<screen_capture::Region>,
+    state: State<'_, ScreenCaptureState>,
+) -> Result<(), AppError> {
+    if let Some(sender) = state.0.lock().unwrap().take() {
+        let _ = sender.send(region);
+    }
+    Ok(())
+}
+
+// 複数ファイルの取り込みをジョブとして実行し、ジョブIDを返す
+// duplicates を省略すると設定の扱い（既定では重複は取り込まない）
+// whiteboard を指定するとホワイトボードの写真として整えた画像を取り込む
+#[tauri::command]
+async fn import_files(
+    paths: Vec<String>,
+    duplicates: Option<DuplicatePolicy>,
+    whiteboard: Option<bool>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let name = i18n::format("job.import_files", &[("count", &paths.len().to_string())]);
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("import", &name, move |job| {
+        with_import_context(&handle, |ctx| {
+            let ctx = ImportContext {
+                duplicates: duplicates.unwrap_or(ctx.duplicates),
+                whiteboard: whiteboard.unwrap_or(ctx.whiteboard),
+                ..*ctx
+            };
+            job.set_total(paths.len() as u64);
+            let files: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+            let results = import_pipeline::import_files(&ctx, &files, job, &|progress| {
+                let _ = handle.emit("import-progress", progress);
+            })?;
+            let mut imported = Vec::new();
+            let mut skipped = Vec::new();
+            let mut failed = Vec::new();
+            for (path, result) in paths.iter().zip(results) {
+                match result {
+                    Ok(ImportOutcome::Skipped(existing)) => {
+                        skipped.push(serde_json::json!({ "path": path, "existing_item_id": existing.id }))
+                    }
+                    Ok(outcome) => imported.push(outcome.into_item().id),
+                    Err(e) => failed.push(serde_json::json!({ "path": path, "error": e.to_string() })),
+                }
+            }
+            Ok(serde_json::json!({ "imported": imported, "skipped": skipped, "failed": failed }))
+        })
+    });
+    Ok(job_id)
+}
+
+// Google Keep（Takeout）や Evernote（.enex）のノートを取り込む（画像の付いたノートが対象、ジョブとして実行）
+#[tauri::command]
+async fn import_notes(
+    source: note_import::NoteSource,
+    path: String,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("import", &i18n::text("job.import_notes"), move |job| {
+        let report = with_import_context(&handle, |ctx| note_import::import_notes(ctx, source, Path::new(&path), job))?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// フォルダ内の画像をまとめて取り込む（ジョブとして実行し、1ファイルごとに import-progress を通知）
+#[tauri::command]
+async fn import_folder(
+    path: String,
+    options: Option<folder_import::FolderImportOptions>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let name = i18n::format("job.import_folder", &[("path", &path)]);
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("import", &name, move |job| {
+        let options = options.unwrap_or_default();
+        let report = with_import_context(&handle, |ctx| {
+            folder_import::import_folder(ctx, Path::new(&path), &options, job, &|progress| {
+                let _ = handle.emit("import-progress", progress);
+            })
+        })?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+#[tauri::command]
+async fn create_item(
+    item: ItemRecord,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<ItemRecord, AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    store.insert_item(&item).map_err(AppError::from)?;
+    index_item(&search_state, store, &item)?;
+    Ok(item)
+}
+
+#[tauri::command]
+async fn get_item(
+    item_id: String,
+    state: State<'_, MetadataStoreState>,
+    vault: State<'_, PrivateVaultState>,
+) -> Result<Option<ItemRecord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    let item = store.get_item(&item_id).map_err(AppError::from)?;
+    Ok(item.map(|item| vault.0.reveal(store, item)))
+}
+
+#[tauri::command]
+async fn update_item(
+    mut item: ItemRecord,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+    vault: State<'_, PrivateVaultState>,
+    app_handle: AppHandle,
+) -> Result<ItemRecord, AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    // 非公開アイテムのテキストは暗号化して保存する
+    let existing = store.get_item(&item.id).map_err(AppError::from)?;
+    if existing.as_ref().is_some_and(|e| e.private) {
+        vault.0.seal_update(store, &mut item).map_err(AppError::from)?;
+    }
+    let updated = store.update_item(&item).map_err(AppError::from)?;
+    index_item(&search_state, store, &updated)?;
+    // 名前のないグループに入れたら、アイテムの見出しからグループ名を付ける
+    let previous_group = existing.as_ref().and_then(|e| e.group_id.clone());
+    if let Some(group_id) = updated.group_id.as_deref().filter(|id| previous_group.as_deref() != Some(*id)) {
+        fill_group_title(&search_state, store, group_id)?;
+    }
+    notify_tags_added(&app_handle, &existing.map(|e| e.tags).unwrap_or_default(), &updated);
+    Ok(vault.0.reveal(store, updated))
+}
+
+// 多数のアイテムのタグ・位置情報・グループ・アーカイブをまとめて変える
+// メタデータストアは1つのトランザクションで更新し、検索インデックスも1回だけコミットする
+#[tauri::command]
+async fn bulk_update(
+    target: BulkTarget,
+    changes: BulkChanges,
+    app_handle: AppHandle,
+) -> Result<BulkUpdateResult, AppError> {
+    if changes.is_empty() {
+        return Err(AppError::InvalidInput("No changes to apply".to_string()));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let ids: Vec<String> = match target {
+            BulkTarget::Ids(ids) => ids,
+            BulkTarget::Query(query) => query_items(&app_handle, query)?.into_iter().map(|item| item.id).collect(),
+        };
+
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        if let Some(group_id) = &changes.group_id {
+            if store.get_group(group_id)?.is_none() {
+                anyhow::bail!("Group not found: {}", group_id);
+            }
+        }
+
+        let mut result = BulkUpdateResult {
+            matched: ids.len(),
+            ..Default::default()
+        };
+        let mut before = HashMap::new();
+        let mut items = Vec::new();
+        for id in &ids {
+            let Some(mut item) = store.get_item(id)? else {
+                result.missing.push(id.clone());
+                continue;
+            };
+            let tags = item.tags.clone();
+            if changes.apply(&mut item) {
+                before.insert(item.id.clone(), tags);
+                items.push(item);
+            }
+        }
+        let updated = store.update_items(&items)?;
+        result.updated = updated.iter().map(|item| item.id.clone()).collect();
+
+        let search_state = app_handle.state::<SearchEngineState>();
+        index_items(&search_state, store, &updated)?;
+        if let Some(group_id) = changes.group_id.as_deref().filter(|_| !updated.is_empty()) {
+            fill_group_title(&search_state, store, group_id).map_err(anyhow::Error::msg)?;
+        }
+        for item in &updated {
+            notify_tags_added(&app_handle, before.get(&item.id).map(Vec::as_slice).unwrap_or_default(), item);
+        }
+        Ok(result)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// メモ・タグ・位置情報・グループの変更履歴（新しい版から順）
+#[tauri::command]
+async fn get_item_history(item_id: String, state: State<'_, MetadataStoreState>) -> Result<Vec<ItemVersion>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.item_history(&item_id).map_err(AppError::from)
+}
+
+// OCR で読み取った単語と画像上の位置（タップした単語のコピーや検索語の強調表示に使う）
+#[tauri::command]
+async fn get_ocr_words(item_id: String, state: State<'_, MetadataStoreState>) -> Result<Vec<OcrWord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.ocr_words(&item_id).map_err(AppError::from)
+}
+
+// 単語ごとの OCR の確からしさ（確からしさの低い所を画像に重ねて示し、その範囲だけ直してもらうのに使う）
+#[tauri::command]
+async fn get_ocr_overlay(item_id: String, state: State<'_, MetadataStoreState>) -> Result<OcrOverlay, AppError> {
+    let (image_path, words, corrected_regions) = {
+        let store = state.0.lock().unwrap();
+        let store = store.as_ref().ok_or("Metadata store not initialized")?;
+        let item = store
+            .get_item(&item_id)
+            .map_err(AppError::from)?
+            .ok_or_else(|| AppError::NotFound(format!("Item not found: {}", item_id)))?;
+        let image_path = item.image_path.ok_or("Item has no image")?;
+        let words = store.ocr_words(&item_id).map_err(AppError::from)?;
+        let corrections = store.ocr_corrections(&item_id).map_err(AppError::from)?;
+        let regions: Vec<OcrRegion> = corrections.iter().filter_map(|c| c.region).collect();
+        (image_path, words, regions)
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        let data = std::fs::read(&image_path)?;
+        let (width, height) = image_decode::check(&data)?;
+        Ok::<_, anyhow::Error>(ocr_corrections::overlay(&words, &corrected_regions, width, height))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// OCR の結果を手で直す（region を指定すればその範囲の単語だけ、なければ全文を置き換える）
+// 元の OCR の結果は単語ごとに残し、直した内容は辞書に覚えて以降の取り込みでも同じ誤りを直す
+#[tauri::command]
+async fn update_ocr_text(
+    item_id: String,
+    corrected_text: String,
+    region: Option<OcrRegion>,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+    corrections: State<'_, CorrectionsState>,
+) -> Result<ItemRecord, AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    let mut item = store
+        .get_item(&item_id)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound(format!("Item not found: {}", item_id)))?;
+    if item.private {
+        return Err(AppError::InvalidInput("OCR text of private items cannot be corrected".to_string()));
+    }
+
+    let original = match &region {
+        Some(region) => {
+            let words: Vec<OcrWord> = store
+                .ocr_words(&item_id)
+                .map_err(AppError::from)?
+                .into_iter()
+                .filter(|w| region.contains(w))
+                .collect();
+            let original = ocr::join_words(&words);
+            if original.is_empty() || !item.ocr_text.contains(&original) {
+                return Err(AppError::InvalidInput("No OCR text found in the region".to_string()));
+            }
+            item.ocr_text = item.ocr_text.replacen(&original, &corrected_text, 1);
+            original
+        }
+        None => std::mem::replace(&mut item.ocr_text, corrected_text.clone()),
+    };
+    if original == corrected_text {
+        return Ok(item);
+    }
+
+    let updated = store.update_item(&item).map_err(AppError::from)?;
+    store
+        .add_ocr_correction(&item_id, region.as_ref(), &original, &corrected_text)
+        .map_err(AppError::from)?;
+    index_item(&search_state, store, &updated)?;
+    if let Err(e) = corrections.0.learn(&original, &corrected_text) {
+        log::warn!("Failed to save OCR correction dictionary: {}", e);
+    }
+    Ok(updated)
+}
+
+// アイテムの画像を指定した読み方（バックエンド・言語・前処理）で OCR し直して本文と単語の位置を置き換える
+// 覚えた直しは取り込み時と同じように当て、手書きとして読んだ「その他」の書類は手書きメモにする
+fn rerecognize_item(app_handle: &AppHandle, item_id: &str, options: &OcrOptions) -> anyhow::Result<ItemRecord> {
+    let image_path = ocr_image_path(app_handle, item_id)?;
+    // OCR は時間がかかるためストアのロックを持たずに行う
+    let result = app_handle
+        .state::<OcrState>()
+        .0
+        .recognize_with_options(Path::new(&image_path), options)?;
+    save_ocr_result(app_handle, item_id, &result)
+}
+
+// OCR し直すアイテムの画像（非公開のアイテムは読み直さない）
+fn ocr_image_path(app_handle: &AppHandle, item_id: &str) -> anyhow::Result<String> {
+    let store = app_handle.state::<MetadataStoreState>();
+    let store = store.0.lock().unwrap();
+    let store = store.as_ref().context("Metadata store not initialized")?;
+    let item = store.get_item(item_id)?.with_context(|| format!("Item not found: {}", item_id))?;
+    if item.private {
+        anyhow::bail!("OCR of private items cannot be run again");
+    }
+    item.image_path.with_context(|| format!("Item has no image: {}", item_id))
+}
+
+// 読み直した結果でアイテムの本文と単語の位置を置き換え、検索インデックスも更新する
+fn save_ocr_result(app_handle: &AppHandle, item_id: &str, result: &OcrResult) -> anyhow::Result<ItemRecord> {
+    let ocr_text = app_handle.state::<CorrectionsState>().0.apply(&result.text);
+
+    let store = app_handle.state::<MetadataStoreState>();
+    let mut store = store.0.lock().unwrap();
+    let store = store.as_mut().context("Metadata store not initialized")?;
+    let mut item = store.get_item(item_id)?.with_context(|| format!("Item not found: {}", item_id))?;
+    // ユーザーが入れた書類の日付は変えない（読み取った日付のままなら読み直す）
+    if item.document_date.is_none() || item.document_date == document_dates::parse(&item.ocr_text) {
+        item.document_date = document_dates::parse(&ocr_text);
+    }
+    // 見出しも同じく、推定したもののままなら付け直す
+    let generated = item.title.is_none() || item.title == titles::for_item(&item);
+    item.ocr_text = ocr_text;
+    if generated {
+        item.title = titles::for_item(&item);
+    }
+    if result.mode == OcrMode::Handwriting && item.doc_type == DocumentType::Other {
+        item.doc_type = DocumentType::HandwrittenNote;
+    }
+    let updated = store.update_item(&item)?;
+    store.set_ocr_words(item_id, &result.words)?;
+    index_item(&app_handle.state::<SearchEngineState>(), store, &updated).map_err(anyhow::Error::msg)?;
+    Ok(updated)
+}
+
+// 手書きのメモなど自動で見分けられなかったアイテムを、読み方を選んで OCR し直す
+#[tauri::command]
+async fn set_item_ocr_mode(
+    item_id: String,
+    mode: OcrMode,
+    languages: Option<Vec<String>>,
+    app_handle: AppHandle,
+) -> Result<ItemRecord, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let languages = languages.unwrap_or_else(ocr::default_languages);
+        rerecognize_item(&app_handle, &item_id, &OcrOptions::for_mode(&languages, mode))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// うまく読めなかったアイテムを、バックエンド・言語（縦書きの jpn_vert など）・前処理を変えて OCR し直す
+// 省略したものは設定に従う。読み直したテキストで検索インデックスも更新する
+#[tauri::command]
+async fn rerun_ocr(
+    item_id: String,
+    engine: Option<OcrBackend>,
+    languages: Option<Vec<String>>,
+    preprocessing_preset: Option<PreprocessPreset>,
+    app_handle: AppHandle,
+) -> Result<ItemRecord, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let preset = preprocessing_preset.unwrap_or_default();
+        let languages = languages.unwrap_or_else(ocr::default_languages);
+        // jpn_vert などを選んだときは縦書きとして読む
+        let mode = if preset == PreprocessPreset::Handwriting {
+            OcrMode::Handwriting
+        } else if languages.iter().any(|l| l.ends_with("_vert")) {
+            OcrMode::Vertical
+        } else {
+            OcrMode::Printed
+        };
+        let options = OcrOptions {
+            backend: engine,
+            languages,
+            mode,
+            preset,
+            rotation: 0,
+        };
+        rerecognize_item(&app_handle, &item_id, &options)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// アイテムの OCR の直しの記録（古い順）
+#[tauri::command]
+async fn get_ocr_corrections(item_id: String, state: State<'_, MetadataStoreState>) -> Result<Vec<OcrCorrection>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.ocr_corrections(&item_id).map_err(AppError::from)
+}
+
+// 覚えた OCR の直し（2回以上同じ直しをしたものが取り込み時に使われる）
+#[tauri::command]
+async fn list_correction_dictionary(state: State<'_, CorrectionsState>) -> Result<Vec<CorrectionEntry>, AppError> {
+    Ok(state.0.list())
+}
+
+#[tauri::command]
+async fn delete_correction_entry(from: String, state: State<'_, CorrectionsState>) -> Result<bool, AppError> {
+    state.0.delete(&from).map_err(AppError::from)
+}
+
+// 指定した版の内容に戻す（0 なら取り込み時の状態、UI の複数段の取り消しに使う）
+#[tauri::command]
+async fn revert_item(
+    item_id: String,
+    version: i64,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<ItemRecord, AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    let reverted = store.revert_item(&item_id, version).map_err(AppError::from)?;
+    index_item(&search_state, store, &reverted)?;
+    Ok(reverted)
+}
+
+// ライブラリ全体の完全一致・類似画像・撮り直しを探す（ジョブとして実行し、結果は重複のまとまりの一覧）
+// embedding_threshold は撮り直しとみなす画像の埋め込みのコサイン類似度（0〜1、大きいほど厳しい）
+#[tauri::command]
+async fn scan_duplicates(
+    threshold: Option<u32>,
+    embedding_threshold: Option<f32>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let threshold = threshold.unwrap_or(duplicates::DEFAULT_SIMILARITY_THRESHOLD);
+    let embedding_threshold = embedding_threshold.unwrap_or(duplicates::DEFAULT_EMBEDDING_THRESHOLD);
+    let job_id = state.0.submit("duplicates", &i18n::text("job.scan_duplicates"), move |job| {
+        let store = handle.state::<MetadataStoreState>();
+        let embeddings = handle.state::<EmbeddingState>();
+        let groups = duplicates::scan_duplicates(&store.0, threshold, Some(&embeddings.0), embedding_threshold, job)?;
+        Ok(serde_json::to_value(&groups)?)
+    });
+    Ok(job_id)
+}
+
+// 元画像・サムネイル・索引・ゴミ箱の使用量と、年・タグ別の内訳、整理の提案
+#[tauri::command]
+async fn get_storage_report(
+    large_file_mb: Option<u64>,
+    app_handle: AppHandle,
+) -> Result<storage_report::StorageReport, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let large_file_mb = large_file_mb.unwrap_or(storage_report::DEFAULT_LARGE_FILE_MB);
+    tauri::async_runtime::spawn_blocking(move || {
+        let store = app_handle.state::<MetadataStoreState>();
+        storage_report::build_report(&paths, &store.0, large_file_mb)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 大きな元画像を JPEG に再圧縮して容量を減らす（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn recompress_originals(
+    options: Option<recompress::RecompressOptions>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let options = options.unwrap_or_default();
+    let job_id = state.0.submit("recompress", &i18n::text("job.recompress"), move |job| {
+        let paths = LibraryPaths::from_app(&handle)?;
+        let store = handle.state::<MetadataStoreState>();
+        let report = recompress::recompress_originals(&paths, &store.0, &options, job)?;
+        reindex_items(&handle, &report.changed_item_ids)?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// タグの条件ごとに、そのタグか子孫のタグのどれかが付いていれば一致とする
+// （検索インデックスには祖先のタグも入っているため、インデックスでの検索はそのままでよい）
+fn tag_matchers(store: &MetadataStore, tags: Option<Vec<String>>) -> anyhow::Result<Vec<Vec<String>>> {
+    tags.unwrap_or_default().iter().map(|tag| store.tag_descendants(tag)).collect()
+}
+
+fn matches_tags(item: &ItemRecord, matchers: &[Vec<String>]) -> bool {
+    matchers.iter().all(|tags| tags.iter().any(|tag| item.tags.contains(tag)))
+}
+
+// 検索文字列の album: 条件・レシートの条件・地点からの距離・書類の日付の範囲を、当てはまるアイテムIDの集合にする
+// album: はアルバム（子アルバムを含む）に入っているもの、複数指定するとすべてに当てはまるもの、条件がなければ None
+// 書類の日付で絞り込む場合は、検索インデックスの撮影日で絞り込まないよう date_from / date_to を外す
+fn resolve_store_filters(store: &MetadataStore, query: &mut SearchQuery) -> anyhow::Result<Option<HashSet<String>>> {
+    let (rest, albums) = search_engine::extract_album_filters(&query.query);
+    query.query = rest;
+    let mut id_sets = Vec::new();
+    for album in &albums {
+        id_sets.push(store.album_filter_ids(album)?);
+    }
+    if let Some(receipt) = &query.receipt {
+        id_sets.push(store.receipt_filter_ids(receipt)?);
+    }
+    if let Some(near) = &query.near {
+        id_sets.push(store.location_filter_ids(near)?);
+    }
+    if query.date_field == DateField::Document && (query.date_from.is_some() || query.date_to.is_some()) {
+        id_sets.push(store.document_date_filter_ids(query.date_from.take(), query.date_to.take())?);
+    }
+    Ok(id_sets
+        .into_iter()
+        .reduce(|allowed, ids| allowed.intersection(&ids).cloned().collect()))
+}
+
+// 検索条件に一致するアイテムをメタデータストアから取得する
+// キーワードが空なら日付・タグ・アルバムの条件だけで絞り込む（検索インデックスを使わない）
+fn query_items(app_handle: &AppHandle, query: SearchQuery) -> anyhow::Result<Vec<ItemRecord>> {
+    let store_state = app_handle.state::<MetadataStoreState>();
+    let store = store_state.0.lock().unwrap();
+    let store = store.as_ref().context("Metadata store not initialized")?;
+    let items = query_store_items(app_handle, store, query)?;
+    // ロック中の非公開アイテムはエクスポートなどの対象にしない
+    Ok(app_handle.state::<PrivateVaultState>().0.visible(store, items))
+}
+
+fn query_store_items(
+    app_handle: &AppHandle,
+    store: &MetadataStore,
+    mut query: SearchQuery,
+) -> anyhow::Result<Vec<ItemRecord>> {
+    let limit = query.limit.unwrap_or(QUERY_ITEMS_LIMIT);
+    let allowed = resolve_store_filters(store, &mut query)?;
+    let is_allowed = |id: &str| allowed.as_ref().map_or(true, |ids| ids.contains(id));
+
+    if query.query.trim().is_empty() {
+        let filter = ItemFilter {
+            date_from: query.date_from,
+            date_to: query.date_to,
+            doc_types: query.doc_types.clone(),
+            ..Default::default()
+        };
+        let tags = tag_matchers(store, query.tags)?;
+        return Ok(store
+            .list_items(&filter)?
+            .into_iter()
+            .filter(|item| is_allowed(&item.id) && matches_tags(item, &tags))
+            .take(limit)
+            .collect());
+    }
+
+    let search_state = app_handle.state::<SearchEngineState>();
+    let engine = search_state.0.lock().unwrap();
+    let engine = engine.as_ref().context("Search engine not initialized")?;
+    let mut items = Vec::new();
+    // アルバムやレシートの条件で絞り込む場合は、絞り込んだ後の件数が足りるよう多めに検索する
+    let search_limit = if allowed.is_some() { QUERY_ITEMS_LIMIT } else { limit };
+    for result in engine.search(SearchQuery {
+        limit: Some(search_limit),
+        ..query
+    })? {
+        if !is_allowed(&result.id) {
+            continue;
+        }
+        items.extend(store.get_item(&result.id)?);
+        if items.len() >= limit {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+// OCR のテキストから見つけた電話番号・メールアドレス・URL・郵便番号・追跡番号・住所（タップで開く先を含む）
+#[tauri::command]
+async fn get_item_entities(item_id: String, state: State<'_, MetadataStoreState>) -> Result<Vec<Entity>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.item_entities(&item_id).map_err(AppError::from)
+}
+
+// 画像に写っていた QR コード（URL を開く・振込先をコピーするなどの操作を含む）
+#[tauri::command]
+async fn get_item_qr_codes(item_id: String, state: State<'_, MetadataStoreState>) -> Result<Vec<QrCodeDetails>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    let codes = store.qr_codes(&item_id).map_err(AppError::from)?;
+    Ok(codes.into_iter().map(QrCodeDetails::from).collect())
+}
+
+// 画像から QR コードを読み直して保存する（画像のデコードはストアのロックを持たずに行う）
+fn rescan_qr_codes(app_handle: &AppHandle, item: &ItemRecord) -> anyhow::Result<Vec<QrCode>> {
+    if item.private {
+        anyhow::bail!("QR codes of private items cannot be read");
+    }
+    let path = item.image_path.as_deref().context("Item has no image")?;
+    let codes = qr_codes::decode(&decode_image(&std::fs::read(path)?)?);
+    let store = app_handle.state::<MetadataStoreState>();
+    let mut store = store.0.lock().unwrap();
+    let store = store.as_mut().context("Metadata store not initialized")?;
+    store.set_qr_codes(&item.id, &codes)?;
+    Ok(codes)
+}
+
+#[tauri::command]
+async fn scan_qr_codes(item_id: String, app_handle: AppHandle) -> Result<Vec<QrCodeDetails>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let item = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store.get_item(&item_id)?.with_context(|| format!("Item not found: {}", item_id))?
+        };
+        let codes = rescan_qr_codes(&app_handle, &item)?;
+        Ok::<_, anyhow::Error>(codes.into_iter().map(QrCodeDetails::from).collect())
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// すべての画像から QR コードを読み直す
+// QR コードを読むようにする前に取り込んだ画像に使う（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn build_qr_codes(app_handle: AppHandle, state: State<'_, JobManagerState>) -> Result<String, AppError> {
+    let job_id = state.0.submit("qr_codes", &i18n::text("job.build_qr_codes"), move |job| {
+        let pending: Vec<ItemRecord> = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store
+                .list_items(&ItemFilter::default())?
+                .into_iter()
+                .filter(|i| !i.private && i.image_path.is_some())
+                .collect()
+        };
+        job.set_total(pending.len() as u64);
+
+        let mut found = 0;
+        for (i, item) in pending.iter().enumerate() {
+            job.checkpoint()?;
+            match rescan_qr_codes(&app_handle, item) {
+                Ok(codes) if !codes.is_empty() => found += 1,
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to read QR codes of {}: {}", item.id, e),
+            }
+            job.progress(i as u64 + 1, item.id.clone());
+        }
+        Ok(serde_json::json!({ "found": found }))
+    });
+    Ok(job_id)
+}
+
+// 本文が空か確からしさの低いアイテムを、いまの読み方（手書き・縦書き・スクリーンショットの判定を含む）で OCR し直す
+// 前より良く読めたときだけ置き換える。ユーザーが手で直したアイテムは対象にしない
+// OCR のプロセスが他の作業を妨げないよう interval_ms ずつ間を空け、読み直したアイテムは記録して中断しても続きから行う
+#[tauri::command]
+async fn backfill_ocr(
+    max_confidence: Option<f32>,
+    interval_ms: Option<u64>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let max_confidence = max_confidence.unwrap_or(OCR_BACKFILL_MAX_CONFIDENCE);
+    let interval = interval_ms.map_or(OCR_BACKFILL_INTERVAL, std::time::Duration::from_millis);
+    let job_id = state.0.submit("ocr_backfill", &i18n::text("job.backfill_ocr"), move |job| {
+        let pending: Vec<(ItemRecord, Vec<OcrWord>)> = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            let done = store.ocr_backfilled(ocr::PIPELINE_VERSION)?;
+            let mut pending = Vec::new();
+            for item in store.list_items(&ItemFilter::default())? {
+                if item.private || item.image_path.is_none() || done.contains(&item.id) {
+                    continue;
+                }
+                let words = store.ocr_words(&item.id)?;
+                // 単語の位置のない本文はノートなどから取り込んだもの
+                let weak = if words.is_empty() {
+                    item.ocr_text.trim().is_empty()
+                } else {
+                    ocr::mean_confidence(&words) < max_confidence
+                };
+                if weak && store.ocr_corrections(&item.id)?.is_empty() {
+                    pending.push((item, words));
+                }
+            }
+            pending
+        };
+        job.set_total(pending.len() as u64);
+
+        let ocr = app_handle.state::<OcrState>();
+        let (mut improved, mut failed) = (0, 0);
+        for (i, (item, words)) in pending.iter().enumerate() {
+            job.checkpoint()?;
+            if i > 0 {
+                std::thread::sleep(interval);
+            }
+            let recognized = ocr_image_path(&app_handle, &item.id)
+                .and_then(|path| ocr.0.recognize_auto(Path::new(&path), &ocr::default_languages()));
+            let outcome = recognized.and_then(|result| {
+                if ocr::text_score(&result.words) > ocr::text_score(words) {
+                    save_ocr_result(&app_handle, &item.id, &result)?;
+                    improved += 1;
+                }
+                let store = app_handle.state::<MetadataStoreState>();
+                let store = store.0.lock().unwrap();
+                let store = store.as_ref().context("Metadata store not initialized")?;
+                store.mark_ocr_backfilled(&item.id, ocr::PIPELINE_VERSION)
+            });
+            if let Err(e) = outcome {
+                log::warn!("Failed to run OCR again for {}: {}", item.id, e);
+                failed += 1;
+            }
+            job.progress(i as u64 + 1, item.id.clone());
+        }
+        Ok(serde_json::json!({ "processed": pending.len(), "improved": improved, "failed": failed }))
+    });
+    Ok(job_id)
+}
+
+// 書類の種類ごとのアイテム数（検索の絞り込みに使う）
+#[tauri::command]
+async fn get_document_type_counts(
+    state: State<'_, MetadataStoreState>,
+) -> Result<Vec<(DocumentType, usize)>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.document_type_counts().map_err(AppError::from)
+}
+
+// OCR のテキストから読み取ったレシートの項目（レシートでなければ None）
+#[tauri::command]
+async fn get_receipt_fields(
+    item_id: String,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Option<ReceiptFields>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.receipt_fields(&item_id).map_err(AppError::from)
+}
+
+// すべてのアイテムからレシートの項目を読み取り直し、レシートとみなした件数を返す
+#[tauri::command]
+async fn refresh_receipt_fields(app_handle: AppHandle) -> Result<usize, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store = app_handle.state::<MetadataStoreState>();
+        let mut store = store.0.lock().unwrap();
+        store.as_mut().context("Metadata store not initialized")?.refresh_receipt_fields()
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 検索条件に当てはまるレシートの合計を currency（JPY / USD など）に換算し、タグ別・月別に集計する
+// 他の通貨のレシートがあるときだけ為替レートを取得する（1日1回まで、取れなければ前回のレートを使う）
+#[tauri::command]
+async fn get_expense_summary(query: SearchQuery, currency: String, app_handle: AppHandle) -> Result<ExpenseSummary, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let currency = currency.trim().to_uppercase();
+        let items = query_items(&app_handle, query)?;
+        let ids: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+        let receipts = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            store.as_ref().context("Metadata store not initialized")?.receipt_fields_for(&ids)?
+        };
+        let rates = if receipts.values().any(|r| r.currency != currency) {
+            match app_handle.state::<ExchangeRatesState>().0.rates() {
+                Ok(rates) => Some(rates),
+                Err(e) => {
+                    log::warn!("Expenses in other currencies are not converted: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Ok::<_, anyhow::Error>(expenses::summarize(&items, &receipts, &currency, rates.as_ref()))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 検索結果を CSV / Excel に書き出す（ID・日付・タグ・メモ・場所・店名・購入日・金額・ファイルパス）
+#[tauri::command]
+async fn export_table(
+    query: SearchQuery,
+    format: TableFormat,
+    path: String,
+    app_handle: AppHandle,
+) -> Result<usize, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let items = query_items(&app_handle, query)?;
+        let ids: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+        let receipts = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            store.as_ref().context("Metadata store not initialized")?.receipt_fields_for(&ids)?
+        };
+        table_export::export_table(&items, &receipts, format, Path::new(&path))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 日付のあるアイテム（リマインダーと OCR の期限・予定）をカレンダーアプリで購読できる .ics に書き出す
+// 検索条件を省略するとライブラリ全体が対象
+#[tauri::command]
+async fn export_ical(query: Option<SearchQuery>, path: String, app_handle: AppHandle) -> Result<usize, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let items = query_items(&app_handle, query.unwrap_or_default())?;
+        let reminders = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            store.as_ref().context("Metadata store not initialized")?.list_reminders(None, false)?
+        };
+        let (calendar, count) = ical_export::build_calendar(&items, &reminders, "Snap Organizer");
+        std::fs::write(&path, calendar)?;
+        Ok::<_, anyhow::Error>(count)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 検索条件に合うアイテムを、アプリがなくてもブラウザで見られる静的なフォルダとして書き出す（ジョブとして実行）
+#[tauri::command]
+async fn export_viewer_bundle(
+    query: Option<SearchQuery>,
+    dest: String,
+    options: Option<viewer_bundle::ViewerBundleOptions>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let options = options.unwrap_or_default();
+    let job_id = state.0.submit("viewer-export", &i18n::text("job.export_viewer"), move |job| {
+        let items = query_items(&handle, query.unwrap_or_default())?;
+        let report = viewer_bundle::export_viewer_bundle(&items, Path::new(&dest), &options, job)?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// Obsidian などで使えるよう、1アイテム1ノートの Markdown として書き出す（ジョブとして実行）
+// 検索条件を省略するとライブラリ全体が対象
+#[tauri::command]
+async fn export_markdown(
+    query: Option<SearchQuery>,
+    options: markdown_export::MarkdownExportOptions,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("markdown-export", &i18n::text("job.export_markdown"), move |job| {
+        let items = query_items(&handle, query.unwrap_or_default())?;
+        let groups = {
+            let store = handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            store.as_ref().context("Metadata store not initialized")?.list_groups()?
+        };
+        let report = markdown_export::export_markdown(&items, &groups, &options, job)?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// 条件に合う元画像を「2024/05/receipt/」のようなフォルダ構成でコピーする（ジョブとして実行）
+// テンプレートを省略すると {year}/{month}/{tag}
+#[tauri::command]
+async fn organize_to_folders(
+    query: Option<SearchQuery>,
+    template: Option<String>,
+    dest: String,
+    mode: Option<organize::OrganizeMode>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("organize", &i18n::text("job.organize"), move |job| {
+        let items = query_items(&handle, query.unwrap_or_default())?;
+        let groups = {
+            let store = handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            store.as_ref().context("Metadata store not initialized")?.list_groups()?
+        };
+        let template = template.unwrap_or_else(|| organize::DEFAULT_TEMPLATE.to_string());
+        let report = organize::organize_to_folders(
+            &items,
+            &groups,
+            &template,
+            Path::new(&dest),
+            mode.unwrap_or_default(),
+            job,
+        )?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// 選んだアイテムの画像を縮小して添付し、メモの一覧を本文にしてメールで送る
+#[tauri::command]
+async fn email_items(
+    item_ids: Vec<String>,
+    to: Vec<String>,
+    subject: String,
+    app_handle: AppHandle,
+) -> Result<email_export::EmailReport, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let paths = LibraryPaths::from_app(&app_handle)?;
+        let items = {
+            let store_state = app_handle.state::<MetadataStoreState>();
+            let store = store_state.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            let mut items = Vec::new();
+            for id in &item_ids {
+                items.extend(store.get_item(id)?);
+            }
+            items
+        };
+        let settings = SmtpSettings::load(&paths.smtp_settings_file());
+        email_export::email_items(&settings, &items, &to, &subject)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_smtp_settings(app_handle: AppHandle) -> Result<SmtpSettings, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    Ok(SmtpSettings::load(&paths.smtp_settings_file()))
+}
+
+#[tauri::command]
+async fn set_smtp_settings(settings: SmtpSettings, app_handle: AppHandle) -> Result<(), AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    settings.save(&paths.smtp_settings_file()).map_err(AppError::from)
+}
+
+// 設定画面の「接続テスト」用
+#[tauri::command]
+async fn test_smtp_connection(settings: SmtpSettings) -> Result<(), AppError> {
+    tauri::async_runtime::spawn_blocking(move || settings.test_connection())
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+// 座標から地名を求める（見つからなければ None）
+#[tauri::command]
+async fn reverse_geocode(lat: f64, lng: f64, app_handle: AppHandle) -> Result<Option<GeocodedLocation>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<GeocodingState>().0.reverse_geocode(lat, lng))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+// 住所から座標と地名を求める（地図での絞り込みの中心を探すのにも使う）
+#[tauri::command]
+async fn geocode_address(address: String, app_handle: AppHandle) -> Result<Option<GeocodedAddress>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<GeocodingState>().0.geocode(&address))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+// OCR のテキストの最初の住所から座標と地名を入れる（住所がないか見つからなければ None）
+fn locate_by_address(app_handle: &AppHandle, item: &ItemRecord) -> anyhow::Result<Option<ItemRecord>> {
+    let Some(address) = entities::addresses(&item.ocr_text).into_iter().next() else {
+        return Ok(None);
+    };
+    let Some(location) = app_handle.state::<GeocodingState>().0.geocode(&address)? else {
+        return Ok(None);
+    };
+    let store = app_handle.state::<MetadataStoreState>();
+    let mut store = store.0.lock().unwrap();
+    let store = store.as_mut().context("Metadata store not initialized")?;
+    // 問い合わせている間に位置情報が入っていたら上書きしない
+    let Some(mut item) = store.get_item(&item.id)?.filter(|i| i.latitude.is_none()) else {
+        return Ok(None);
+    };
+    item.location_name = Some(location.name);
+    item.latitude = Some(location.latitude);
+    item.longitude = Some(location.longitude);
+    let updated = store.update_item(&item)?;
+    index_item(&app_handle.state::<SearchEngineState>(), store, &updated).map_err(anyhow::Error::msg)?;
+    Ok(Some(updated))
+}
+
+// 位置情報のないアイテムに、OCR のテキストの住所から座標と地名を入れる
+// 住所から位置を入れる設定にする前に取り込んだ画像に使う（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn locate_items_by_address(app_handle: AppHandle, state: State<'_, JobManagerState>) -> Result<String, AppError> {
+    let job_id = state.0.submit("addresses", &i18n::text("job.locate_addresses"), move |job| {
+        let pending: Vec<ItemRecord> = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store
+                .list_items(&ItemFilter::default())?
+                .into_iter()
+                .filter(|i| !i.private && i.latitude.is_none() && !entities::addresses(&i.ocr_text).is_empty())
+                .collect()
+        };
+        job.set_total(pending.len() as u64);
+
+        let mut located = 0;
+        for (i, item) in pending.iter().enumerate() {
+            job.checkpoint()?;
+            match locate_by_address(&app_handle, item) {
+                Ok(Some(_)) => located += 1,
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to locate {} by address: {}", item.id, e),
+            }
+            job.progress(i as u64 + 1, item.id.clone());
+        }
+        Ok(serde_json::json!({ "located": located }))
+    });
+    Ok(job_id)
+}
+
+#[tauri::command]
+async fn get_geocoding_settings(state: State<'_, GeocodingState>) -> Result<GeocodingSettings, AppError> {
+    Ok(state.0.settings())
+}
+
+#[tauri::command]
+async fn set_geocoding_settings(settings: GeocodingSettings, state: State<'_, GeocodingState>) -> Result<(), AppError> {
+    state.0.set_settings(settings).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_translation_settings(state: State<'_, TranslationState>) -> Result<TranslationSettings, AppError> {
+    Ok(state.0.settings())
+}
+
+#[tauri::command]
+async fn set_translation_settings(settings: TranslationSettings, state: State<'_, TranslationState>) -> Result<(), AppError> {
+    state.0.set_settings(settings).map_err(AppError::from)
+}
+
+// アイテムの OCR のテキストを訳したもの（まだ訳していない、または OCR のテキストが変わっていれば None）
+#[tauri::command]
+async fn get_translation(item_id: String, state: State<'_, MetadataStoreState>) -> Result<Option<Translation>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    match store.get_item(&item_id).map_err(AppError::from)? {
+        Some(item) => store.translation(&item).map_err(AppError::from),
+        None => Ok(None),
+    }
+}
+
+// 訳がない、または訳した後に OCR のテキストが変わったアイテムを訳して検索インデックスに入れ直す
+// 翻訳を有効にする前に取り込んだアイテムに使う（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn build_translations(app_handle: AppHandle, state: State<'_, JobManagerState>) -> Result<String, AppError> {
+    let job_id = state.0.submit("translations", &i18n::text("job.build_translations"), move |job| {
+        let translator = app_handle.state::<TranslationState>();
+        if !translator.0.is_enabled() {
+            anyhow::bail!("Translation is disabled");
+        }
+        let pending: Vec<ItemRecord> = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            let mut pending = Vec::new();
+            for item in store.list_items(&ItemFilter::default())? {
+                if !item.private && !item.ocr_text.trim().is_empty() && store.translation(&item)?.is_none() {
+                    pending.push(item);
+                }
+            }
+            pending
+        };
+        job.set_total(pending.len() as u64);
+
+        let mut translated = 0;
+        for (i, item) in pending.iter().enumerate() {
+            job.checkpoint()?;
+            match translator.0.translate(&item.ocr_text) {
+                Ok(Some(translation)) => {
+                    let store = app_handle.state::<MetadataStoreState>();
+                    let store = store.0.lock().unwrap();
+                    let store = store.as_ref().context("Metadata store not initialized")?;
+                    // 訳している間に OCR のテキストが変わっていれば、次に実行したときに訳し直す
+                    if let Some(current) = store.get_item(&item.id)?.filter(|c| c.ocr_text == item.ocr_text) {
+                        store.set_translation(&item.id, &item.ocr_text, &translation)?;
+                        index_item(&app_handle.state::<SearchEngineState>(), store, &current)
+                            .map_err(anyhow::Error::msg)?;
+                        translated += 1;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to translate OCR text of {}: {}", item.id, e),
+            }
+            job.progress(i as u64 + 1, item.id.clone());
+        }
+        Ok(serde_json::json!({ "translated": translated }))
+    });
+    Ok(job_id)
+}
+
+// 端末内の推論で使うモデルの一覧とダウンロード状況
+#[tauri::command]
+async fn list_models(state: State<'_, InferenceState>) -> Result<Vec<ModelInfo>, AppError> {
+    Ok(state.0.list_models())
+}
+
+// モデルをダウンロードして検証する（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn download_model(
+    model_id: String,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let spec = models::spec(&model_id).map_err(AppError::from)?;
+    let name = i18n::format("job.download_model", &[("name", spec.name)]);
+    let job_id = state.0.submit("download_model", &name, move |job| {
+        let inference = app_handle.state::<InferenceState>();
+        inference.0.models().download(&model_id, job)?;
+        inference.0.unload(&model_id);
+        if embeddings::is_model(&model_id) {
+            app_handle.state::<EmbeddingState>().0.unload();
+        }
+        if captions::is_model(&model_id) {
+            app_handle.state::<CaptionState>().0.unload();
+        }
+        if translation::is_model(&model_id) {
+            app_handle.state::<TranslationState>().0.unload();
+        }
+        Ok(serde_json::json!({ "model_id": model_id }))
+    });
+    Ok(job_id)
+}
+
+// ダウンロード済みのモデルを検証する（ハッシュが合わなければ false）
+#[tauri::command]
+async fn verify_model(model_id: String, app_handle: AppHandle) -> Result<bool, AppError> {
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<InferenceState>().0.models().verify(&model_id))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn delete_model(
+    model_id: String,
+    state: State<'_, InferenceState>,
+    embeddings: State<'_, EmbeddingState>,
+    captions: State<'_, CaptionState>,
+    translator: State<'_, TranslationState>,
+) -> Result<bool, AppError> {
+    state.0.unload(&model_id);
+    if embeddings::is_model(&model_id) {
+        embeddings.0.unload();
+    }
+    if captions::is_model(&model_id) {
+        captions.0.unload();
+    }
+    if translation::is_model(&model_id) {
+        translator.0.unload();
+    }
+    state.0.models().delete(&model_id).map_err(AppError::from)
+}
+
+// 文章（「ホワイトボードの図の写真」など）またはアイテムIDで、見た目が近い画像を探す
+// OCR で文字が読めなかった画像も見つかる。アイテムIDのときはそのアイテム自身は結果に含めない
+#[tauri::command]
+async fn semantic_search(
+    text_or_item_id: String,
+    limit: Option<usize>,
+    app_handle: AppHandle,
+) -> Result<Vec<SearchResult>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let embeddings = app_handle.state::<EmbeddingState>();
+        let query = text_or_item_id.trim();
+        let limit = limit.unwrap_or(20);
+        if query.is_empty() {
+            return Ok::<_, anyhow::Error>(Vec::new());
+        }
+        let (vector, exclude) = match embeddings.0.vector(query) {
+            Some(vector) => (vector, Some(query)),
+            None if embeddings.0.is_available() => (embeddings.0.embed_text(query)?, None),
+            None => anyhow::bail!("Semantic search models are not installed"),
+        };
+        // ゴミ箱・非公開のアイテムを除いても足りるよう多めに探す
+        let hits = embeddings.0.search(&vector, limit * 2 + 1);
+
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        let private_ids = store.private_item_ids()?;
+        let mut results = Vec::new();
+        for (id, score) in hits {
+            if exclude == Some(id.as_str()) || private_ids.contains(&id) || store.get_item(&id)?.is_none() {
+                continue;
+            }
+            results.push(SearchResult {
+                id,
+                score,
+                highlights: Vec::new(),
+                matched_fields: vec!["image".to_string()],
+            });
+            if results.len() == limit {
+                break;
+            }
+        }
+        Ok(results)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 埋め込みのない画像の埋め込みを計算して索引に入れ、削除・非公開にしたアイテムを索引から外す
+// モデルをダウンロードする前に取り込んだ画像に使う（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn build_semantic_index(app_handle: AppHandle, state: State<'_, JobManagerState>) -> Result<String, AppError> {
+    let job_id = state.0.submit("semantic_index", &i18n::text("job.build_semantic_index"), move |job| {
+        let embeddings = app_handle.state::<EmbeddingState>();
+        if !embeddings.0.is_available() {
+            anyhow::bail!("Semantic search models are not installed");
+        }
+        let items = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store.list_items(&ItemFilter::default())?
+        };
+        let live: HashSet<&str> = items.iter().filter(|i| !i.private).map(|i| i.id.as_str()).collect();
+        let removed = embeddings.0.with_index(|index| {
+            let stale: Vec<String> = index.ids().into_iter().filter(|id| !live.contains(id.as_str())).collect();
+            stale.iter().filter(|id| index.remove(id)).count()
+        });
+        let pending: Vec<&ItemRecord> = items
+            .iter()
+            .filter(|i| !i.private && i.image_path.is_some())
+            .filter(|i| !embeddings.0.with_index(|index| index.contains(&i.id)))
+            .collect();
+        job.set_total(pending.len() as u64);
+
+        let mut added = 0;
+        for (i, item) in pending.iter().enumerate() {
+            job.checkpoint()?;
+            let path = item.image_path.as_deref().unwrap_or_default();
+            let result = std::fs::read(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| Ok(decode_image(&data)?))
+                .and_then(|image| embeddings.0.embed_image(&image))
+                .and_then(|vector| embeddings.0.add(&item.id, vector));
+            match result {
+                Ok(()) => added += 1,
+                Err(e) => log::warn!("Failed to compute embedding for {}: {}", item.id, e),
+            }
+            // キャンセルされても計算済みの分は残す
+            if (i + 1) % 100 == 0 {
+                embeddings.0.flush()?;
+            }
+            job.progress(i as u64 + 1, item.id.clone());
+        }
+        embeddings.0.flush()?;
+        Ok(serde_json::json!({ "added": added, "removed": removed }))
+    });
+    Ok(job_id)
+}
+
+// アイテムの画像から説明文を生成し直して保存する（説明文を生成できなければ変更しない）
+fn regenerate_caption(app_handle: &AppHandle, item: &ItemRecord) -> anyhow::Result<Option<ItemRecord>> {
+    let path = item.image_path.as_deref().context("Item has no image")?;
+    let image = decode_image(&std::fs::read(path)?)?;
+    let Some(caption) = app_handle.state::<CaptionState>().0.caption(&image)? else {
+        return Ok(None);
+    };
+    let store = app_handle.state::<MetadataStoreState>();
+    let mut store = store.0.lock().unwrap();
+    let store = store.as_mut().context("Metadata store not initialized")?;
+    // 生成している間に変更されていてもよいよう読み直す
+    let Some(mut item) = store.get_item(&item.id)? else {
+        return Ok(None);
+    };
+    item.caption = Some(caption);
+    // 文字のない画像は説明文を見出しにする
+    if item.title.is_none() {
+        item.title = titles::for_item(&item);
+    }
+    let updated = store.update_item(&item)?;
+    index_item(&app_handle.state::<SearchEngineState>(), store, &updated).map_err(anyhow::Error::msg)?;
+    Ok(Some(updated))
+}
+
+// 1件の説明文を生成し直す
+#[tauri::command]
+async fn generate_caption(item_id: String, app_handle: AppHandle) -> Result<ItemRecord, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if !app_handle.state::<CaptionState>().0.is_available() {
+            anyhow::bail!("Captioning models are not installed");
+        }
+        let item = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store.get_item(&item_id)?.with_context(|| format!("Item not found: {}", item_id))?
+        };
+        if item.private {
+            anyhow::bail!("Captions of private items cannot be generated");
+        }
+        Ok(regenerate_caption(&app_handle, &item)?.unwrap_or(item))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 説明文のないアイテムの説明文を生成する
+// モデルをダウンロードする前に取り込んだ画像に使う（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn build_captions(app_handle: AppHandle, state: State<'_, JobManagerState>) -> Result<String, AppError> {
+    let job_id = state.0.submit("captions", &i18n::text("job.build_captions"), move |job| {
+        if !app_handle.state::<CaptionState>().0.is_available() {
+            anyhow::bail!("Captioning models are not installed");
+        }
+        let pending: Vec<ItemRecord> = {
+            let store = app_handle.state::<MetadataStoreState>();
+            let store = store.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            store
+                .list_items(&ItemFilter::default())?
+                .into_iter()
+                .filter(|i| !i.private && i.image_path.is_some() && i.caption.is_none())
+                .collect()
+        };
+        job.set_total(pending.len() as u64);
+
+        let mut generated = 0;
+        for (i, item) in pending.iter().enumerate() {
+            job.checkpoint()?;
+            match regenerate_caption(&app_handle, item) {
+                Ok(Some(_)) => generated += 1,
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to generate caption for {}: {}", item.id, e),
+            }
+            job.progress(i as u64 + 1, item.id.clone());
+        }
+        Ok(serde_json::json!({ "generated": generated }))
+    });
+    Ok(job_id)
+}
+
+// 自動タグ付けルールの一覧（上から順に評価する）
+#[tauri::command]
+async fn list_rules(state: State<'_, RulesState>) -> Result<Vec<Rule>, AppError> {
+    Ok(state.0.list())
+}
+
+// ルールを追加・更新する（ID が空なら新規）
+#[tauri::command]
+async fn save_rule(rule: Rule, state: State<'_, RulesState>) -> Result<Rule, AppError> {
+    state.0.save_rule(rule).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn delete_rule(id: String, state: State<'_, RulesState>) -> Result<bool, AppError> {
+    state.0.delete_rule(&id).map_err(AppError::from)
+}
+
+// ルールが既存のどのアイテムに一致するかを変更せずに調べる
+#[tauri::command]
+async fn dry_run_rule(rule: Rule, app_handle: AppHandle) -> Result<Vec<rules::RuleMatch>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        rules::dry_run(store, rule)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// メモのテンプレートの一覧（{date} {vendor} {amount} などをレシートの項目で埋める）
+#[tauri::command]
+async fn list_memo_templates(state: State<'_, MemoTemplatesState>) -> Result<Vec<MemoTemplate>, AppError> {
+    Ok(state.0.list())
+}
+
+// テンプレートを追加・更新する（ID が空なら新規）
+#[tauri::command]
+async fn save_memo_template(
+    template: MemoTemplate,
+    state: State<'_, MemoTemplatesState>,
+) -> Result<MemoTemplate, AppError> {
+    state.0.save_template(template).map_err(|e| AppError::InvalidInput(e.to_string()))
+}
+
+#[tauri::command]
+async fn delete_memo_template(id: String, state: State<'_, MemoTemplatesState>) -> Result<bool, AppError> {
+    state.0.delete_template(&id).map_err(AppError::from)
+}
+
+// テンプレートをレシートの項目などで埋めてアイテムのメモに入れ、変わったアイテムを返す
+// 非公開アイテムのメモは暗号化されているため対象にしない
+#[tauri::command]
+async fn apply_template(
+    item_ids: Vec<String>,
+    template_id: String,
+    app_handle: AppHandle,
+) -> Result<Vec<ItemRecord>, AppError> {
+    let template = app_handle
+        .state::<MemoTemplatesState>()
+        .0
+        .get(&template_id)
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        let receipts = store.receipt_fields_for(&item_ids)?;
+        let mut items = Vec::new();
+        for id in &item_ids {
+            let Some(mut item) = store.get_item(id)? else {
+                continue;
+            };
+            if item.private {
+                continue;
+            }
+            let memo = memo_templates::apply(&template, &item, receipts.get(id));
+            if memo != item.memo {
+                item.memo = memo;
+                items.push(item);
+            }
+        }
+        let updated = store.update_items(&items)?;
+        index_items(&app_handle.state::<SearchEngineState>(), store, &updated)?;
+        Ok(updated)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 地図の表示範囲にある位置情報付きアイテムをズームレベルに応じてまとめる
+#[tauri::command]
+async fn get_map_clusters(
+    bounds: map_clusters::MapBounds,
+    zoom: u32,
+    query: Option<SearchQuery>,
+    app_handle: AppHandle,
+) -> Result<Vec<map_clusters::MapCluster>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let items = query_items(&app_handle, query.unwrap_or_default())?;
+        Ok::<_, anyhow::Error>(map_clusters::cluster(&items, &bounds, zoom))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 日・月・年ごとの件数と代表サムネイル（タイムライン表示用）
+#[tauri::command]
+async fn get_timeline(
+    query: Option<SearchQuery>,
+    granularity: Option<timeline::TimelineGranularity>,
+    app_handle: AppHandle,
+) -> Result<Vec<timeline::TimelineBucket>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let query = query.unwrap_or_default();
+        let date_field = query.date_field;
+        let items = query_items(&app_handle, query)?;
+        Ok::<_, anyhow::Error>(timeline::build_timeline(&items, granularity.unwrap_or_default(), date_field))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// ダッシュボード用の集計（件数・タグ別・月別・OCR の割合・画像サイズ・よく使う場所）
+#[tauri::command]
+async fn get_library_stats(app_handle: AppHandle) -> Result<library_stats::LibraryStats, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store = app_handle.state::<MetadataStoreState>();
+        library_stats::build_stats(&store.0)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// アイテムをゴミ箱へ移す（検索対象から外れ、保持期間を過ぎると完全に削除される）
+#[tauri::command]
+async fn delete_item(
+    item_id: String,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<bool, AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    let deleted = store.trash_item(&item_id).map_err(AppError::from)?;
+    if let Some(engine) = search_state.0.lock().unwrap().as_mut() {
+        engine.delete_item(&item_id).map_err(AppError::from)?;
+        store.clear_index_journal(&[item_id]).map_err(AppError::from)?;
+    }
+    Ok(deleted)
+}
+
+#[tauri::command]
+async fn list_trash(state: State<'_, MetadataStoreState>) -> Result<Vec<TrashedItem>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.list_trash().map_err(AppError::from)
+}
+
+// ゴミ箱から戻し、検索インデックスにも戻す
+#[tauri::command]
+async fn restore_from_trash(
+    item_ids: Vec<String>,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<Vec<ItemRecord>, AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    let mut restored = Vec::new();
+    for id in &item_ids {
+        if let Some(item) = store.restore_item(id).map_err(AppError::from)? {
+            index_item(&search_state, store, &item)?;
+            restored.push(item);
+        }
+    }
+    Ok(restored)
+}
+
+// 指定したアイテム（省略時はゴミ箱のすべて）を完全に削除する
+#[tauri::command]
+async fn purge_trash(item_ids: Option<Vec<String>>, state: State<'_, MetadataStoreState>) -> Result<usize, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    match item_ids {
+        Some(ids) => trash::purge_items(store, &ids),
+        None => trash::purge_all(store),
+    }
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_trash_settings(app_handle: AppHandle) -> Result<TrashSettings, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    Ok(TrashSettings::load(&paths.trash_settings_file()))
+}
+
+#[tauri::command]
+async fn set_trash_settings(settings: TrashSettings, app_handle: AppHandle) -> Result<(), AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    settings.save(&paths.trash_settings_file()).map_err(AppError::from)
+}
+
+fn purge_expired_trash(app_handle: &AppHandle) -> anyhow::Result<usize> {
+    let paths = LibraryPaths::from_app(app_handle)?;
+    let settings = TrashSettings::load(&paths.trash_settings_file());
+    let retention = RetentionSettings::load(&paths.retention_settings_file());
+    let store_state = app_handle.state::<MetadataStoreState>();
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().context("Metadata store not initialized")?;
+    if let Err(e) = orphans::purge_expired(&paths, settings.retention_days) {
+        log::warn!("Failed to purge orphaned files: {}", e);
+    }
+    trash::purge_expired(store, &settings, &retention)
+}
+
+// タグごとの保持ルール（削除までの日数・保持する年数）
+#[tauri::command]
+async fn get_retention_settings(app_handle: AppHandle) -> Result<RetentionSettings, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    Ok(RetentionSettings::load(&paths.retention_settings_file()))
+}
+
+// 保存したルール（新しいルールには ID を付ける）を返す
+#[tauri::command]
+async fn set_retention_settings(
+    mut settings: RetentionSettings,
+    app_handle: AppHandle,
+) -> Result<RetentionSettings, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    settings
+        .save(&paths.retention_settings_file())
+        .map_err(|e| AppError::InvalidInput(e.to_string()))?;
+    Ok(settings)
+}
+
+// 保持ルールで削除の対象になり、ゴミ箱に移すのを待っているアイテム（ゴミ箱に移す日時の早い順）
+#[tauri::command]
+async fn list_retention_reviews(
+    app_handle: AppHandle,
+    store_state: State<'_, MetadataStoreState>,
+) -> Result<Vec<RetentionCandidate>, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let settings = RetentionSettings::load(&paths.retention_settings_file());
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    let (candidates, _) = retention::review(store, &settings).map_err(AppError::from)?;
+    Ok(candidates)
+}
+
+// 確認待ちのアイテムを削除しないことにする（exempt が false なら除外をやめる）。変更した件数を返す
+#[tauri::command]
+async fn set_retention_exempt(
+    item_ids: Vec<String>,
+    exempt: bool,
+    state: State<'_, MetadataStoreState>,
+) -> Result<usize, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    let mut changed = 0;
+    for id in &item_ids {
+        if store.set_retention_exempt(id, exempt).map_err(AppError::from)? {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+// 保持ルールを当て、確認期間を過ぎたアイテムをゴミ箱に移して検索インデックスからも外す
+fn enforce_retention(app_handle: &AppHandle) -> anyhow::Result<RetentionOutcome> {
+    let paths = LibraryPaths::from_app(app_handle)?;
+    let settings = RetentionSettings::load(&paths.retention_settings_file());
+    let store_state = app_handle.state::<MetadataStoreState>();
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().context("Metadata store not initialized")?;
+    let outcome = retention::enforce(store, &settings)?;
+    if !outcome.trashed.is_empty() {
+        if let Some(engine) = app_handle.state::<SearchEngineState>().0.lock().unwrap().as_mut() {
+            for id in &outcome.trashed {
+                engine.delete_item(id)?;
+            }
+            store.clear_index_journal(&outcome.trashed)?;
+        }
+    }
+    Ok(outcome)
+}
+
+// 定期的な確認を待たずに保持ルールを当てる
+#[tauri::command]
+async fn run_retention(app_handle: AppHandle) -> Result<RetentionOutcome, AppError> {
+    tauri::async_runtime::spawn_blocking(move || enforce_retention(&app_handle))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+// 新たに削除の対象になったアイテムがあれば、ゴミ箱に移す前に確認するよう OS の通知で知らせる
+fn notify_retention_review(app_handle: &AppHandle, outcome: &RetentionOutcome) {
+    if outcome.flagged == 0 {
+        return;
+    }
+    let body = i18n::format("notify.retention_review", &[("count", &outcome.flagged.to_string())]);
+    if let Err(e) = app_handle
+        .notification()
+        .builder()
+        .title(i18n::text("notify.retention_title"))
+        .body(body)
+        .show()
+    {
+        log::warn!("Failed to show retention notification: {}", e);
+    }
+    let _ = app_handle.emit("retention-review", outcome);
+}
+
+// 期限の近づいたリマインダーを OS の通知で知らせる
+fn notify_due_reminders(app_handle: &AppHandle) -> anyhow::Result<usize> {
+    let due = {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        reminders::take_due(store)?
+    };
+    for reminder in &due {
+        if let Err(e) = app_handle
+            .notification()
+            .builder()
+            .title(&reminder.title)
+            .body(&reminder.body)
+            .show()
+        {
+            log::warn!("Failed to show reminder notification: {}", e);
+        }
+        let _ = app_handle.emit("reminder-due", reminder);
+    }
+    Ok(due.len())
+}
+
+#[tauri::command]
+async fn list_items(
+    filter: Option<ItemFilter>,
+    state: State<'_, MetadataStoreState>,
+    vault: State<'_, PrivateVaultState>,
+) -> Result<Vec<ItemRecord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    let items = store.list_items(&filter.unwrap_or_default()).map_err(AppError::from)?;
+    Ok(vault.0.visible(store, items))
+}
+
+#[tauri::command]
+async fn list_tags(state: State<'_, MetadataStoreState>) -> Result<Vec<TagInfo>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.list_tags().map_err(AppError::from)
+}
+
+// タグ名をすべてのアイテムで変更する（新しい名前が既にあれば統合する）。変更したアイテム数を返す
+#[tauri::command]
+async fn rename_tag(old_name: String, new_name: String, app_handle: AppHandle) -> Result<usize, AppError> {
+    let changed: Vec<String> = {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().ok_or("Metadata store not initialized")?;
+        let items = store.rename_tag(&old_name, &new_name).map_err(AppError::from)?;
+        items.into_iter().map(|item| item.id).collect()
+    };
+    reindex_items(&app_handle, &changed).map_err(AppError::from)?;
+    Ok(changed.len())
+}
+
+// source のタグを target にまとめる。変更したアイテム数を返す
+#[tauri::command]
+async fn merge_tags(source: String, target: String, app_handle: AppHandle) -> Result<usize, AppError> {
+    let changed: Vec<String> = {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().ok_or("Metadata store not initialized")?;
+        let items = store.merge_tags(&source, &target).map_err(AppError::from)?;
+        items.into_iter().map(|item| item.id).collect()
+    };
+    reindex_items(&app_handle, &changed).map_err(AppError::from)?;
+    Ok(changed.len())
+}
+
+// 親タグを設定する（None なら最上位にする）
+#[tauri::command]
+async fn set_tag_parent(tag: String, parent: Option<String>, app_handle: AppHandle) -> Result<(), AppError> {
+    let affected = {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().ok_or("Metadata store not initialized")?;
+        store.set_tag_parent(&tag, parent.as_deref()).map_err(AppError::from)?
+    };
+    reindex_items(&app_handle, &affected).map_err(AppError::from)
+}
+
+// タグの色（#rrggbb）とアイコン（絵文字など）を設定する（None で解除）
+#[tauri::command]
+async fn set_tag_style(
+    tag: String,
+    color: Option<String>,
+    icon: Option<String>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<(), AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.set_tag_style(&tag, color.as_deref(), icon.as_deref()).map_err(AppError::from)
+}
+
+// OCR のキーワードとよく一緒に使われるタグから、アイテムに付けるタグの候補を出す
+#[tauri::command]
+async fn suggest_tags(
+    item_id: String,
+    limit: Option<usize>,
+    app_handle: AppHandle,
+) -> Result<Vec<tag_suggest::TagSuggestion>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        tag_suggest::suggest_tags(store, &item_id, limit.unwrap_or(tag_suggest::DEFAULT_SUGGESTION_LIMIT))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn list_albums(state: State<'_, MetadataStoreState>) -> Result<Vec<AlbumRecord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.list_albums().map_err(AppError::from)
+}
+
+// parent_id を指定すると、そのアルバムの中に作る
+#[tauri::command]
+async fn create_album(
+    title: String,
+    parent_id: Option<String>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<AlbumRecord, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.create_album(&title, parent_id.as_deref()).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn rename_album(
+    album_id: String,
+    title: String,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Option<AlbumRecord>, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.rename_album(&album_id, &title).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn move_album(
+    album_id: String,
+    parent_id: Option<String>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Option<AlbumRecord>, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.move_album(&album_id, parent_id.as_deref()).map_err(AppError::from)
+}
+
+// アルバムを削除する（中のアイテムは削除しない）
+#[tauri::command]
+async fn delete_album(album_id: String, state: State<'_, MetadataStoreState>) -> Result<bool, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.delete_album(&album_id).map_err(AppError::from)
+}
+
+// アルバム内のアイテム（並び順）
+#[tauri::command]
+async fn get_album_items(album_id: String, state: State<'_, MetadataStoreState>) -> Result<Vec<ItemRecord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.album_items(&album_id).map_err(AppError::from)
+}
+
+// position を省略すると末尾に追加する
+#[tauri::command]
+async fn add_to_album(
+    album_id: String,
+    item_ids: Vec<String>,
+    position: Option<usize>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<usize, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.add_album_items(&album_id, &item_ids, position).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn remove_from_album(
+    album_id: String,
+    item_ids: Vec<String>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<usize, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.remove_album_items(&album_id, &item_ids).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn reorder_album(
+    album_id: String,
+    item_ids: Vec<String>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<(), AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.reorder_album_items(&album_id, &item_ids).map_err(AppError::from)
+}
+
+// ログファイルから level 以上のものを新しい順に返す
+#[tauri::command]
+async fn get_recent_logs(level: Option<LogLevel>, limit: Option<usize>, app_handle: AppHandle) -> Result<Vec<LogEntry>, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        logging::recent_logs(&paths.logs_dir(), level, limit.unwrap_or(logging::DEFAULT_LOG_LIMIT))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// 不具合報告用にログと環境情報を zip にまとめる（含めたログファイルの数を返す）
+#[tauri::command]
+async fn export_diagnostics(path: String, app_handle: AppHandle) -> Result<usize, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let info = serde_json::json!({
+        "app_version": app_handle.package_info().version.to_string(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "generated_at": Utc::now(),
+        "ocr_backends": app_handle.state::<OcrState>().0.available_backends(),
+        "settings": app_handle.state::<SettingsState>().0.get(),
+    });
+    tauri::async_runtime::spawn_blocking(move || logging::export_diagnostics(&paths.logs_dir(), &info, Path::new(&path)))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+// ポータブルモードかどうかと、データの置き場所
+#[tauri::command]
+async fn get_portable_status(app_handle: AppHandle) -> Result<serde_json::Value, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    Ok(serde_json::json!({
+        "portable": paths::is_portable(),
+        "data_dir": paths.root(),
+    }))
+}
+
+// 登録されているライブラリと、現在開いているライブラリ
+#[tauri::command]
+async fn list_libraries(app_handle: AppHandle) -> Result<LibraryRegistry, AppError> {
+    let mut registry = LibraryRegistry::load(&app_handle).map_err(AppError::from)?;
+    registry.active_id = Some(registry.active().id.clone());
+    Ok(registry)
+}
+
+// 既存または新しいフォルダをライブラリとして登録する
+#[tauri::command]
+async fn add_library(name: Option<String>, path: String, app_handle: AppHandle) -> Result<LibraryProfile, AppError> {
+    let mut registry = LibraryRegistry::load(&app_handle).map_err(AppError::from)?;
+    let profile = registry.add(name.as_deref(), Path::new(&path)).map_err(AppError::from)?;
+    registry.save(&app_handle).map_err(AppError::from)?;
+    Ok(profile)
+}
+
+#[tauri::command]
+async fn rename_library(id: String, name: String, app_handle: AppHandle) -> Result<(), AppError> {
+    let mut registry = LibraryRegistry::load(&app_handle).map_err(AppError::from)?;
+    registry.rename(&id, &name).map_err(AppError::from)?;
+    registry.save(&app_handle).map_err(AppError::from)
+}
+
+// 一覧から外す（フォルダの中身は残る）
+#[tauri::command]
+async fn remove_library(id: String, app_handle: AppHandle) -> Result<bool, AppError> {
+    let mut registry = LibraryRegistry::load(&app_handle).map_err(AppError::from)?;
+    let removed = registry.remove(&id).map_err(AppError::from)?;
+    registry.save(&app_handle).map_err(AppError::from)?;
+    Ok(removed)
+}
+
+#[tauri::command]
+async fn set_library_prompt_on_startup(ask: bool, app_handle: AppHandle) -> Result<(), AppError> {
+    let mut registry = LibraryRegistry::load(&app_handle).map_err(AppError::from)?;
+    registry.ask_on_startup = ask;
+    registry.save(&app_handle).map_err(AppError::from)
+}
+
+// 指定したフォルダのライブラリに切り替える（未登録なら登録する）
+// ストア・インデックス・設定・同期などをすべて開き直すため、アプリを再起動する
+#[tauri::command]
+async fn switch_library(path: String, app_handle: AppHandle) -> Result<(), AppError> {
+    let mut registry = LibraryRegistry::load(&app_handle).map_err(AppError::from)?;
+    let profile = registry.add(None, Path::new(&path)).map_err(AppError::from)?;
+    let current = app_handle.state::<ActiveLibrary>().0.clone();
+    if Path::new(&profile.path) == current {
+        return Ok(());
+    }
+    registry.active_id = Some(profile.id.clone());
+    registry.save(&app_handle).map_err(AppError::from)?;
+    log::info!("Switching library to {} ({})", profile.name, profile.path);
+    app_handle.restart()
+}
+
+// アプリ全体の設定（変更は "settings-changed" イベントで通知する）
+#[tauri::command]
+async fn get_app_settings(state: State<'_, SettingsState>) -> Result<AppSettings, AppError> {
+    Ok(state.0.get())
+}
+
+#[tauri::command]
+async fn set_app_settings(settings: AppSettings, state: State<'_, SettingsState>) -> Result<AppSettings, AppError> {
+    state.0.set(settings).map_err(AppError::from)
+}
+
+// 一部の項目だけを変更する（null を指定した項目は初期値に戻る）
+#[tauri::command]
+async fn update_app_settings(patch: serde_json::Value, state: State<'_, SettingsState>) -> Result<AppSettings, AppError> {
+    state.0.update(patch).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_app_lock_status(state: State<'_, AppLockState>) -> Result<AppLockStatus, AppError> {
+    Ok(state.0.status())
+}
+
+// アプリのパスコードを設定・変更する（passcode を省略するとロックを無効にする）
+#[tauri::command]
+async fn set_app_passcode(current: Option<String>, passcode: Option<String>, app_handle: AppHandle) -> Result<(), AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app_handle
+            .state::<AppLockState>()
+            .0
+            .set_passcode(current.as_deref(), passcode.as_deref())
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// ロックを解除する（非公開アイテムのパスコードをキーチェーンに覚えさせていれば、それもアンロックする）
+#[tauri::command]
+async fn unlock_app(passcode: String, app_handle: AppHandle) -> Result<(), AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app_handle.state::<AppLockState>().0.unlock(&passcode)?;
+        if let Err(e) = app_handle.state::<PrivateVaultState>().0.unlock_from_keychain() {
+            log::warn!("Failed to unlock private items from keychain: {}", e);
+        }
+        Ok::<_, anyhow::Error>(())
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// ロックすると非公開アイテムもロックする
+#[tauri::command]
+async fn lock_app(app_handle: AppHandle) -> Result<(), AppError> {
+    app_handle.state::<AppLockState>().0.lock().map_err(AppError::from)?;
+    app_handle.state::<PrivateVaultState>().0.lock();
+    app_handle.state::<ResizeCacheState>().0.clear_memory();
+    let _ = app_handle.emit("app-locked", ());
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_private_vault_status(vault: State<'_, PrivateVaultState>) -> Result<VaultStatus, AppError> {
+    Ok(vault.0.status())
+}
+
+// 非公開アイテムのパスコードを設定・変更する（変更時は現在のパスコードが必要）
+#[tauri::command]
+async fn set_private_passcode(current: Option<String>, passcode: String, app_handle: AppHandle) -> Result<(), AppError> {
+    // scrypt による鍵の導出に時間がかかるためブロッキングスレッドで行う
+    tauri::async_runtime::spawn_blocking(move || {
+        app_handle
+            .state::<PrivateVaultState>()
+            .0
+            .set_passcode(current.as_deref(), &passcode)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// remember を指定するとパスコードを OS のキーチェーンに覚えさせ、アプリのロック解除と同時にアンロックする
+#[tauri::command]
+async fn unlock_private_items(passcode: String, remember: Option<bool>, app_handle: AppHandle) -> Result<(), AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let vault = app_handle.state::<PrivateVaultState>();
+        vault.0.unlock(&passcode)?;
+        match remember {
+            Some(true) => vault.0.remember_passcode(Some(&passcode)),
+            Some(false) => vault.0.remember_passcode(None),
+            None => Ok(()),
+        }
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn lock_private_items(
+    vault: State<'_, PrivateVaultState>,
+    resize_cache: State<'_, ResizeCacheState>,
+) -> Result<(), AppError> {
+    vault.0.lock();
+    resize_cache.0.clear_memory();
+    Ok(())
+}
+
+// 身分証などを非公開にする（画像とテキストを暗号化し、ロック中は一覧・検索に出さない）
+// 非公開の解除はアンロック中のみ
+#[tauri::command]
+async fn set_item_private(item_id: String, private: bool, app_handle: AppHandle) -> Result<ItemRecord, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let vault = app_handle.state::<PrivateVaultState>();
+        let thumbnails = app_handle.state::<ThumbnailCacheState>();
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        let item = store.get_item(&item_id)?.with_context(|| format!("Item not found: {}", item_id))?;
+        if private {
+            vault.0.seal_item(store, &thumbnails.0, &item)?;
+            app_handle.state::<EmbeddingState>().0.remove(&item.id);
+            if let Some(hash) = &item.content_hash {
+                app_handle.state::<ResizeCacheState>().0.remove(hash)?;
+            }
+        } else {
+            vault.0.unseal_item(store, &item)?;
+        }
+        let updated = store.get_item(&item_id)?.context("Item not found")?;
+        index_item(&app_handle.state::<SearchEngineState>(), store, &updated).map_err(anyhow::Error::msg)?;
+        Ok::<_, anyhow::Error>(vault.0.reveal(store, updated))
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// アイテムにリマインダーを付ける（remind_at を省略すると期限の日時に通知）
+#[tauri::command]
+async fn add_reminder(
+    item_id: String,
+    due_at: DateTime<Utc>,
+    remind_at: Option<DateTime<Utc>>,
+    note: Option<String>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Reminder, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store
+        .add_reminder(&item_id, due_at, remind_at, note.as_deref().unwrap_or(""))
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn update_reminder(
+    id: String,
+    due_at: DateTime<Utc>,
+    remind_at: Option<DateTime<Utc>>,
+    note: Option<String>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Option<Reminder>, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store
+        .update_reminder(&id, due_at, remind_at, note.as_deref().unwrap_or(""))
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn complete_reminder(
+    id: String,
+    completed: bool,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Option<Reminder>, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.set_reminder_completed(&id, completed).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn delete_reminder(id: String, state: State<'_, MetadataStoreState>) -> Result<bool, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.delete_reminder(&id).map_err(AppError::from)
+}
+
+// item_id を省略すると全アイテムのリマインダー（期限の近い順）
+#[tauri::command]
+async fn list_reminders(
+    item_id: Option<String>,
+    include_completed: Option<bool>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Vec<Reminder>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store
+        .list_reminders(item_id.as_deref(), include_completed.unwrap_or(false))
+        .map_err(AppError::from)
+}
+
+// 2つのアイテムを関係づける（a が b の page ページ目・a は b の重複・関連）
+#[tauri::command]
+async fn link_items(
+    a: String,
+    b: String,
+    relation_type: RelationType,
+    page: Option<u32>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<ItemRelation, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.link_items(&a, &b, relation_type, page).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn unlink_items(
+    a: String,
+    b: String,
+    relation_type: RelationType,
+    state: State<'_, MetadataStoreState>,
+) -> Result<bool, AppError> {
+    let mut store = state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    store.unlink_items(&a, &b, relation_type).map_err(AppError::from)
+}
+
+// 詳細画面用: アイテムに関係するアイテムの一覧
+#[tauri::command]
+async fn get_item_relations(item_id: String, state: State<'_, MetadataStoreState>) -> Result<Vec<RelatedItem>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.item_relations(&item_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn save_group(
+    group: GroupRecord,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<(), AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    store.save_group(&group).map_err(AppError::from)?;
+    if group.title.trim().is_empty() && fill_group_title(&search_state, store, &group.id)? {
+        return Ok(());
+    }
+
+    // グループ名は各アイテムのインデックスに含まれるため再登録する
+    let members = store
+        .list_items(&ItemFilter { group_id: Some(group.id.clone()), ..Default::default() })
+        .map_err(AppError::from)?;
+    for item in &members {
+        index_item(&search_state, store, item)?;
+    }
+    Ok(())
+}
+
+// 名前のないグループにアイテムの見出しから名前を付ける（付けたら true。名前のあるグループは変えない）
+fn fill_group_title(search: &SearchEngineState, store: &mut MetadataStore, group_id: &str) -> Result<bool, AppError> {
+    let Some(mut group) = store.get_group(group_id).map_err(AppError::from)? else {
+        return Ok(false);
+    };
+    if !group.title.trim().is_empty() {
+        return Ok(false);
+    }
+    let members = store
+        .list_items(&ItemFilter { group_id: Some(group_id.to_string()), ..Default::default() })
+        .map_err(AppError::from)?;
+    let Some(title) = titles::for_group(&members) else {
+        return Ok(false);
+    };
+    group.title = title;
+    group.updated_at = Utc::now();
+    store.save_group(&group).map_err(AppError::from)?;
+    for item in &members {
+        index_item(search, store, item)?;
+    }
+    Ok(true)
+}
+
+// アイテムの見出しを店名や OCR のテキスト・説明文から付け直す（ユーザーが付けた見出しも置き換える）
+#[tauri::command]
+async fn generate_item_title(
+    item_id: String,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<ItemRecord, AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+    let mut item = store
+        .get_item(&item_id)
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound(format!("Item not found: {}", item_id)))?;
+    if item.private {
+        return Err(AppError::InvalidInput("Titles of private items cannot be generated".to_string()));
+    }
+    item.title = titles::for_item(&item);
+    let updated = store.update_item(&item).map_err(AppError::from)?;
+    index_item(&search_state, store, &updated)?;
+    Ok(updated)
+}
+
+#[tauri::command]
+async fn list_groups(
+    state: State<'_, MetadataStoreState>,
+) -> Result<Vec<GroupRecord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.list_groups().map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn delete_group(
+    group_id: String,
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<(), AppError> {
+    let mut store = store_state.0.lock().unwrap();
+    let store = store.as_mut().ok_or("Metadata store not initialized")?;
+
+    let member_ids = store.delete_group(&group_id).map_err(AppError::from)?;
+    for id in member_ids {
+        if let Some(item) = store.get_item(&id).map_err(AppError::from)? {
+            index_item(&search_state, store, &item)?;
+        }
+    }
+    Ok(())
+}
+
+// メタデータストア・検索インデックス・ディスク上のファイルの食い違いを調べる
+#[tauri::command]
+async fn verify_library(app_handle: AppHandle) -> Result<IntegrityReport, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store_state = app_handle.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        let search_state = app_handle.state::<SearchEngineState>();
+        let engine = search_state.0.lock().unwrap();
+        let search_engine = engine.as_ref().context("Search engine not initialized")?;
+        integrity::verify(store, search_engine)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// verify_library で見つかった問題のうち、指定した種類のものを直す
+#[tauri::command]
+async fn repair_library(actions: Vec<RepairAction>, app_handle: AppHandle) -> Result<RepairReport, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = {
+            let store_state = app_handle.state::<MetadataStoreState>();
+            let mut store = store_state.0.lock().unwrap();
+            let store = store.as_mut().context("Metadata store not initialized")?;
+            let search_state = app_handle.state::<SearchEngineState>();
+            let mut engine = search_state.0.lock().unwrap();
+            let search_engine = engine.as_mut().context("Search engine not initialized")?;
+            integrity::repair(store, search_engine, &actions)?
+        };
+        let repaired: usize = report.results.iter().map(|r| r.repaired).sum();
+        log_activity(
+            &app_handle,
+            "library_repaired",
+            &format!("{} issues repaired", repaired),
+            serde_json::to_value(&report.results)?,
+        );
+        Ok::<_, anyhow::Error>(report)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// どのアイテムからも参照されていない元画像とサムネイルを片付ける（ジョブとして実行し、ジョブIDを返す）
+// 元画像は orphaned_files へ移し、ゴミ箱の保持期間を過ぎたら削除する
+#[tauri::command]
+async fn collect_orphans(app_handle: AppHandle, state: State<'_, JobManagerState>) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("collect-orphans", &i18n::text("job.collect_orphans"), move |job| {
+        let paths = LibraryPaths::from_app(&handle)?;
+        let store = handle.state::<MetadataStoreState>();
+        let report = orphans::collect_orphans(&store.0, &paths, job)?;
+        if report.reclaimed_bytes > 0 {
+            log_activity(
+                &handle,
+                "orphans_collected",
+                &format!("{} bytes reclaimed", report.reclaimed_bytes),
+                serde_json::to_value(&report)?,
+            );
+        }
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// メタデータストアを正として検索インデックスを作り直す
+#[tauri::command]
+async fn rebuild_search_index(
+    store_state: State<'_, MetadataStoreState>,
+    search_state: State<'_, SearchEngineState>,
+) -> Result<usize, AppError> {
+    let store = store_state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    let mut engine = search_state.0.lock().unwrap();
+    let search_engine = engine.as_mut().ok_or("Search engine not initialized")?;
+
+    rebuild_index(store, search_engine).map_err(AppError::from)
+}
+
+// SQLite の内容から検索インデックスを作り直す
+fn rebuild_index(store: &MetadataStore, search_engine: &mut SearchEngine) -> anyhow::Result<usize> {
+    let pending = store.index_journal()?;
+    search_engine.clear_index()?;
+    let items = store.list_items(&ItemFilter::default())?;
+    for item in &items {
+        search_engine.add_item(store.to_searchable(item)?)?;
+    }
+    store.clear_index_journal(&pending)?;
+    Ok(items.len())
+}
+
+// ライブラリ全体を1つのアーカイブへバックアップ（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn export_backup(
+    path: String,
+    options: Option<BackupOptions>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let options = options.unwrap_or_default();
+    let job_id = state.0.submit("backup", &i18n::text("job.export_backup"), move |job| {
+        let paths = LibraryPaths::from_app(&handle)?;
+        let store = handle.state::<MetadataStoreState>();
+        let source = BackupSource {
+            paths: &paths,
+            store: &store.0,
+        };
+        let manifest = backup::export_backup(&source, Path::new(&path), &options, job)?;
+        let summary = serde_json::json!({
+            "path": path,
+            "item_count": manifest.item_count,
+            "file_count": manifest.entries.len(),
+        });
+        log_activity(&handle, "backup_created", &path, summary.clone());
+        handle.state::<WebhookState>().0.notify(WebhookEvent::BackupCompleted, summary.clone());
+        Ok(summary)
+    });
+    Ok(job_id)
+}
+
+// 復元前にパスフレーズ入力が必要かどうかを判定
+#[tauri::command]
+async fn is_backup_encrypted(path: String) -> Result<bool, AppError> {
+    backup::is_encrypted(Path::new(&path)).map_err(AppError::from)
+}
+
+// バックアップから復元（replace: 置き換え / merge: 統合）。暗号化されている場合はパスフレーズが必要
+// ジョブとして実行し、ジョブIDを返す
+#[tauri::command]
+async fn import_backup(
+    path: String,
+    mode: RestoreMode,
+    passphrase: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("restore", &i18n::text("job.restore_backup"), move |job| {
+        let paths = LibraryPaths::from_app(&handle)?;
+        let store_state = handle.state::<MetadataStoreState>();
+        let target = BackupSource {
+            paths: &paths,
+            store: &store_state.0,
+        };
+        let report = backup::import_backup(&target, Path::new(&path), mode, passphrase.as_deref(), job)?;
+
+        // 復元した内容を検索インデックスへ反映
+        {
+            let store = store_state.0.lock().unwrap();
+            let store = store.as_ref().context("Metadata store not initialized")?;
+            let search_state = handle.state::<SearchEngineState>();
+            let mut engine = search_state.0.lock().unwrap();
+            if let Some(search_engine) = engine.as_mut() {
+                match mode {
+                    RestoreMode::Replace => {
+                        rebuild_index(store, search_engine)?;
+                    }
+                    RestoreMode::Merge => {
+                        for id in &report.changed_item_ids {
+                            if let Some(item) = store.get_item(id)? {
+                                search_engine.update_item(store.to_searchable(&item)?)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        log_activity(&handle, "backup_restored", &path, serde_json::json!({ "mode": mode }));
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// 同期で取り込んだ変更を検索インデックスへ反映
+fn apply_sync_report(app_handle: &AppHandle, report: &SyncReport) -> anyhow::Result<()> {
+    reindex_items(app_handle, &report.changed_item_ids)?;
+    let search_state = app_handle.state::<SearchEngineState>();
+    if let Some(search_engine) = search_state.0.lock().unwrap().as_mut() {
+        for id in &report.removed_item_ids {
+            search_engine.delete_item(id)?;
+        }
+    }
+    Ok(())
+}
+
+// バックグラウンド処理で変更されたアイテムを検索インデックスへ反映する
+fn reindex_items(app_handle: &AppHandle, item_ids: &[String]) -> anyhow::Result<()> {
+    let store_state = app_handle.state::<MetadataStoreState>();
+    let store = store_state.0.lock().unwrap();
+    let store = store.as_ref().context("Metadata store not initialized")?;
+    let search_state = app_handle.state::<SearchEngineState>();
+    let mut engine = search_state.0.lock().unwrap();
+    if let Some(search_engine) = engine.as_mut() {
+        for id in item_ids {
+            if let Some(item) = store.get_item(id)? {
+                search_engine.update_item(store.to_searchable(&item)?)?;
+            }
+        }
+        store.clear_index_journal(item_ids)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_sync_config(app_handle: AppHandle) -> Result<SyncConfig, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    Ok(SyncConfig::load(&paths.sync_config_file()))
+}
+
+#[tauri::command]
+async fn set_sync_config(config: SyncConfig, app_handle: AppHandle) -> Result<(), AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    config.save(&paths.sync_config_file()).map_err(AppError::from)
+}
+
+// 設定画面の「接続テスト」用
+#[tauri::command]
+async fn test_sync_connection(remote: SyncProviderConfig, app_handle: AppHandle) -> Result<(), AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let backend = remote.connect(&paths)?;
+        backend.list("groups/")?;
+        Ok::<_, anyhow::Error>(())
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+// バックアップ・同期などの操作を記録する（記録に失敗しても操作自体は失敗させない）
+fn log_activity(app_handle: &AppHandle, action: &str, summary: &str, details: serde_json::Value) {
+    let store_state = app_handle.state::<MetadataStoreState>();
+    let mut store = store_state.0.lock().unwrap();
+    if let Some(store) = store.as_mut() {
+        if let Err(e) = store.log_activity(action, None, summary, details) {
+            log::warn!("Failed to record {}: {}", action, e);
+        }
+    }
+}
+
+// 操作の記録（新しい順）。「このアイテムがいつ消えたか」や同期の調査に使う
+#[tauri::command]
+async fn get_activity_log(
+    filter: Option<ActivityFilter>,
+    state: State<'_, MetadataStoreState>,
+) -> Result<Vec<ActivityEntry>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.activity_log(&filter.unwrap_or_default()).map_err(AppError::from)
+}
+
+// リモートと差分同期（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn sync_now(app_handle: AppHandle, state: State<'_, JobManagerState>) -> Result<String, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let mut config = SyncConfig::load(&paths.sync_config_file());
+    let remote = config.remote.clone().ok_or("Sync is not configured")?;
+
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("sync", &i18n::text("job.sync"), move |job| {
+        let backend = remote.connect(&paths)?;
+        let store_state = handle.state::<MetadataStoreState>();
+        let ctx = SyncContext {
+            paths: &paths,
+            store: &store_state.0,
+        };
+        let report = match sync::sync_library(&ctx, backend.as_ref(), job) {
+            Ok(report) => report,
+            Err(e) => {
+                log_activity(&handle, "sync_failed", &e.to_string(), serde_json::Value::Null);
+                return Err(e);
+            }
+        };
+        apply_sync_report(&handle, &report)?;
+        log_activity(
+            &handle,
+            "sync_completed",
+            "",
+            serde_json::json!({
+                "uploaded": report.uploaded,
+                "downloaded": report.downloaded,
+                "deleted_remote": report.deleted_remote,
+                "deleted_local": report.deleted_local,
+                "merged": report.merged,
+                "conflicts_recorded": report.conflicts_recorded,
+            }),
+        );
+
+        config.last_synced_at = Some(chrono::Utc::now());
+        config.save(&paths.sync_config_file())?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// 同期で両側の編集が重なったフィールドの一覧（UI で両方の値を表示する）
+#[tauri::command]
+async fn get_sync_conflicts(state: State<'_, MetadataStoreState>) -> Result<Vec<ConflictRecord>, AppError> {
+    let store = state.0.lock().unwrap();
+    let store = store.as_ref().ok_or("Metadata store not initialized")?;
+    store.list_sync_conflicts().map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn resolve_sync_conflict(
+    conflict_id: i64,
+    keep: ConflictSide,
+    app_handle: AppHandle,
+    state: State<'_, MetadataStoreState>,
+) -> Result<(), AppError> {
+    let report = {
+        let mut store = state.0.lock().unwrap();
+        let store = store.as_mut().ok_or("Metadata store not initialized")?;
+        sync::resolve_conflict(store, conflict_id, keep).map_err(AppError::from)?
+    };
+    apply_sync_report(&app_handle, &report).map_err(AppError::from)
+}
+
+// Google Drive / Dropbox のサインインを開始（表示するURLとコードを返す）
+#[tauri::command]
+async fn start_sync_sign_in(provider: OAuthProvider, state: State<'_, OAuthState>) -> Result<AuthorizationPrompt, AppError> {
+    let flows = state.0.clone();
+    tauri::async_runtime::spawn_blocking(move || flows.start(provider))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+// サインインの完了を待ってトークンを保存し、同期先として設定する
+// Google はユーザーが承認するまで待機し、Dropbox は画面に表示されたコードを code に渡す
+#[tauri::command]
+async fn complete_sync_sign_in(
+    session_id: String,
+    code: Option<String>,
+    folder: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, OAuthState>,
+) -> Result<SyncConfig, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let flows = state.0.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let (provider, token) = flows.complete(&session_id, code.as_deref())?;
+        oauth::save_token(&paths.oauth_tokens_file(), provider, Some(&token))?;
+
+        let folder = folder.unwrap_or_else(|| "SnapOrganizer".to_string());
+        let mut config = SyncConfig::load(&paths.sync_config_file());
+        config.remote = Some(match provider {
+            OAuthProvider::GoogleDrive => SyncProviderConfig::GoogleDrive(GoogleDriveConfig { folder }),
+            OAuthProvider::Dropbox => SyncProviderConfig::Dropbox(DropboxConfig { folder }),
+        });
+        config.save(&paths.sync_config_file())?;
+        Ok::<_, anyhow::Error>(config)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn sign_out_sync(provider: OAuthProvider, app_handle: AppHandle) -> Result<(), AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    oauth::save_token(&paths.oauth_tokens_file(), provider, None).map_err(AppError::from)
+}
+
+// LAN 同期サーバーを起動する（相手との同期の前後に、自分のライブラリと公開用ストアを同期する）
+fn start_lan_server(app_handle: &AppHandle) -> anyhow::Result<LanSyncServer> {
+    let paths = LibraryPaths::from_app(app_handle)?;
+    let handle = app_handle.clone();
+    let local_sync = Arc::new(move || {
+        let paths = LibraryPaths::from_app(&handle)?;
+        let store_state = handle.state::<MetadataStoreState>();
+        let ctx = SyncContext {
+            paths: &paths,
+            store: &store_state.0,
+        };
+        let backend = FolderBackend::new(paths.lan_sync_dir().join("store"))?;
+        let report = sync::sync_library(&ctx, &backend, &NoProgress)?;
+        apply_sync_report(&handle, &report)
+    });
+    LanSyncServer::start(paths.lan_sync_dir(), local_sync)
+}
+
+#[tauri::command]
+async fn get_lan_sync_config(app_handle: AppHandle) -> Result<LanSyncConfig, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    Ok(LanSyncConfig::load(&paths.lan_sync_dir()))
+}
+
+#[tauri::command]
+async fn set_lan_sync_enabled(
+    enabled: bool,
+    device_name: Option<String>,
+    app_handle: AppHandle,
+    state: State<'_, LanSyncState>,
+) -> Result<LanSyncConfig, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let mut config = LanSyncConfig::load(&paths.lan_sync_dir());
+    config.enabled = enabled;
+    if let Some(name) = device_name.filter(|n| !n.trim().is_empty()) {
+        config.device_name = name.trim().to_string();
+    }
+    config.save(&paths.lan_sync_dir()).map_err(AppError::from)?;
+
+    let mut server = state.0.lock().unwrap();
+    if let Some(running) = server.take() {
+        running.stop();
+    }
+    if enabled {
+        *server = Some(start_lan_server(&app_handle).map_err(AppError::from)?);
+    }
+    Ok(config)
+}
+
+// 相手の端末で入力してもらう PIN を表示する
+#[tauri::command]
+async fn start_lan_pairing(state: State<'_, LanSyncState>) -> Result<PairingCode, AppError> {
+    let server = state.0.lock().unwrap();
+    let server = server.as_ref().ok_or("LAN sync is not enabled")?;
+    Ok(server.start_pairing())
+}
+
+#[tauri::command]
+async fn discover_lan_peers(app_handle: AppHandle) -> Result<Vec<DiscoveredPeer>, AppError> {
+    let dir = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?.lan_sync_dir();
+    tauri::async_runtime::spawn_blocking(move || {
+        let own = lan_sync::LanIdentity::load_or_create(&dir)?.fingerprint();
+        lan_sync::discover_peers(&dir, &own)
+    })
+    .await
+    .map_err(AppError::from)?
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn pair_lan_peer(address: String, port: u16, pin: String, app_handle: AppHandle) -> Result<LanPeer, AppError> {
+    let dir = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?.lan_sync_dir();
+    tauri::async_runtime::spawn_blocking(move || lan_sync::pair_with(&dir, &address, port, &pin))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn remove_lan_peer(fingerprint: String, app_handle: AppHandle) -> Result<(), AppError> {
+    let dir = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?.lan_sync_dir();
+    let mut config = LanSyncConfig::load(&dir);
+    config.peers.retain(|p| p.fingerprint != fingerprint);
+    config.save(&dir).map_err(AppError::from)
+}
+
+// ペアリング済みの端末と同期（ジョブとして実行し、ジョブIDを返す）
+#[tauri::command]
+async fn lan_sync_now(
+    fingerprint: String,
+    address: String,
+    port: u16,
+    app_handle: AppHandle,
+    state: State<'_, JobManagerState>,
+) -> Result<String, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let dir = paths.lan_sync_dir();
+    let mut config = LanSyncConfig::load(&dir);
+    let peer = config
+        .peers
+        .iter()
+        .find(|p| p.fingerprint == fingerprint)
+        .cloned()
+        .ok_or("This device is not paired")?;
+
+    let handle = app_handle.clone();
+    let job_id = state.0.submit("lan-sync", &i18n::format("job.lan_sync", &[("peer", &peer.name)]), move |job| {
+        let backend = LanBackend::connect(&dir, &peer, &address, port)?;
+        backend.begin()?;
+        let store_state = handle.state::<MetadataStoreState>();
+        let ctx = SyncContext {
+            paths: &paths,
+            store: &store_state.0,
+        };
+        let report = sync::sync_library(&ctx, &backend, job)?;
+        backend.end()?;
+        apply_sync_report(&handle, &report)?;
+
+        config.add_peer(peer.fingerprint.clone(), peer.name.clone(), Some(address.clone()));
+        config.save(&dir)?;
+        Ok(serde_json::to_value(&report)?)
+    });
+    Ok(job_id)
+}
+
+// スマホから写真を送るためのアップロードサーバーを起動し、接続用 URL と QR コードを返す
+#[tauri::command]
+async fn start_upload_server(
+    app_handle: AppHandle,
+    state: State<'_, UploadServerState>,
+) -> Result<UploadServerInfo, AppError> {
+    let mut server = state.0.lock().unwrap();
+    if let Some(running) = server.as_ref() {
+        return Ok(running.info().clone());
+    }
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let handle = app_handle.clone();
+    let started = UploadServer::start(
+        paths.staging_dir(),
+        Arc::new(move |path: &Path| Ok(auto_import(&handle, path)?.id)),
+    )
+    .map_err(AppError::from)?;
+    let info = started.info().clone();
+    *server = Some(started);
+    Ok(info)
+}
+
+#[tauri::command]
+async fn stop_upload_server(state: State<'_, UploadServerState>) -> Result<(), AppError> {
+    if let Some(running) = state.0.lock().unwrap().take() {
+        running.stop();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_upload_server_status(state: State<'_, UploadServerState>) -> Result<Option<UploadServerInfo>, AppError> {
+    Ok(state.0.lock().unwrap().as_ref().map(|server| server.info().clone()))
+}
+
+// REST API からの操作（非公開アイテムはロック状態にかかわらず扱わない）
+struct AppApiBackend(AppHandle);
+
+impl ApiBackend for AppApiBackend {
+    fn is_locked(&self) -> bool {
+        is_app_locked(&self.0)
+    }
+
+    fn search(&self, query: SearchQuery) -> anyhow::Result<Vec<ItemRecord>> {
+        let store_state = self.0.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        let items = query_store_items(&self.0, store, query)?;
+        Ok(items.into_iter().filter(|item| !item.private).collect())
+    }
+
+    fn get_item(&self, id: &str) -> anyhow::Result<Option<ItemRecord>> {
+        let store_state = self.0.state::<MetadataStoreState>();
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        Ok(store.get_item(id)?.filter(|item| !item.private))
+    }
+
+    fn import(&self, path: &Path) -> anyhow::Result<ItemRecord> {
+        auto_import(&self.0, path)
+    }
+
+    fn update_item(&self, id: &str, patch: ItemPatch) -> anyhow::Result<Option<ItemRecord>> {
+        let store_state = self.0.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        let Some(mut item) = store.get_item(id)?.filter(|item| !item.private) else {
+            return Ok(None);
+        };
+        let before = item.tags.clone();
+        patch.apply(&mut item);
+        let updated = store.update_item(&item)?;
+        index_item(&self.0.state::<SearchEngineState>(), store, &updated).map_err(anyhow::Error::msg)?;
+        notify_tags_added(&self.0, &before, &updated);
+        Ok(Some(updated))
+    }
+
+    fn delete_item(&self, id: &str) -> anyhow::Result<bool> {
+        let store_state = self.0.state::<MetadataStoreState>();
+        let mut store = store_state.0.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        if store.get_item(id)?.map_or(true, |item| item.private) {
+            return Ok(false);
+        }
+        let deleted = store.trash_item(id)?;
+        if let Some(engine) = self.0.state::<SearchEngineState>().0.lock().unwrap().as_mut() {
+            engine.delete_item(id)?;
+            store.clear_index_journal(&[id.to_string()])?;
+        }
+        Ok(deleted)
+    }
+
+    fn thumbnail(&self, source: &Path, size: u32) -> anyhow::Result<Vec<u8>> {
+        self.0.state::<ThumbnailCacheState>().0.read(source, size)
+    }
+}
+
+fn launch_api_server(app_handle: &AppHandle, settings: &ApiServerSettings) -> anyhow::Result<ApiServer> {
+    let paths = LibraryPaths::from_app(app_handle)?;
+    ApiServer::start(
+        settings.port,
+        settings.token.clone(),
+        settings.share_links,
+        paths.staging_dir(),
+        Arc::new(AppApiBackend(app_handle.clone())),
+    )
+}
+
+fn api_server_status(app_handle: &AppHandle, state: &ApiServerState) -> Result<ApiServerStatus, AppError> {
+    let paths = LibraryPaths::from_app(app_handle).map_err(AppError::from)?;
+    Ok(ApiServerStatus {
+        server: state.0.lock().unwrap().as_ref().map(|server| server.info().clone()),
+        settings: ApiServerSettings::load(&paths.api_settings_file()),
+    })
+}
+
+// REST API を開始し、次回以降も起動時に開始する（初回はトークンを作る）
+#[tauri::command]
+async fn start_api_server(
+    port: Option<u16>,
+    share_links: Option<bool>,
+    app_handle: AppHandle,
+    state: State<'_, ApiServerState>,
+) -> Result<ApiServerStatus, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let file = paths.api_settings_file();
+    let mut settings = ApiServerSettings::load(&file);
+    settings.ensure_token();
+    settings.enabled = true;
+    if let Some(port) = port {
+        settings.port = port;
+    }
+    if let Some(share_links) = share_links {
+        settings.share_links = share_links;
+    }
+    {
+        let mut server = state.0.lock().unwrap();
+        if let Some(running) = server.take() {
+            running.stop();
+        }
+        *server = Some(launch_api_server(&app_handle, &settings).map_err(AppError::from)?);
+    }
+    settings.save(&file).map_err(AppError::from)?;
+    api_server_status(&app_handle, &state)
+}
+
+#[tauri::command]
+async fn stop_api_server(app_handle: AppHandle, state: State<'_, ApiServerState>) -> Result<(), AppError> {
+    if let Some(running) = state.0.lock().unwrap().take() {
+        running.stop();
+    }
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let mut settings = ApiServerSettings::load(&paths.api_settings_file());
+    settings.enabled = false;
+    settings.save(&paths.api_settings_file()).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_api_server_status(
+    app_handle: AppHandle,
+    state: State<'_, ApiServerState>,
+) -> Result<ApiServerStatus, AppError> {
+    api_server_status(&app_handle, &state)
+}
+
+// トークンを作り直す（以前のトークンはすぐに使えなくなる）
+#[tauri::command]
+async fn regenerate_api_token(
+    app_handle: AppHandle,
+    state: State<'_, ApiServerState>,
+) -> Result<ApiServerStatus, AppError> {
+    let paths = LibraryPaths::from_app(&app_handle).map_err(AppError::from)?;
+    let mut settings = ApiServerSettings::load(&paths.api_settings_file());
+    settings.regenerate_token();
+    settings.save(&paths.api_settings_file()).map_err(AppError::from)?;
+    {
+        let mut server = state.0.lock().unwrap();
+        if let Some(running) = server.take() {
+            running.stop();
+            *server = Some(launch_api_server(&app_handle, &settings).map_err(AppError::from)?);
+        }
+    }
+    api_server_status(&app_handle, &state)
+}
+
+// 同じネットワークの人に1件だけ見せる期限付きリンクを作る（ttl は秒、REST API を共有リンクありで開始しているときだけ）
+#[tauri::command]
+async fn create_share_link(
+    item_id: String,
+    ttl: Option<u64>,
+    store_state: State<'_, MetadataStoreState>,
+    state: State<'_, ApiServerState>,
+) -> Result<ShareLink, AppError> {
+    {
+        let store = store_state.0.lock().unwrap();
+        let store = store.as_ref().ok_or("Metadata store not initialized")?;
+        let item = store
+            .get_item(&item_id)
+            .map_err(AppError::from)?
+            .ok_or_else(|| AppError::NotFound(format!("Item not found: {}", item_id)))?;
+        if item.private {
+            return Err(AppError::InvalidInput("Private items cannot be shared".to_string()));
+        }
+        if item.image_path.is_none() {
+            return Err(AppError::InvalidInput("Item has no image to share".to_string()));
+        }
+    }
+    let server = state.0.lock().unwrap();
+    let server = server
+        .as_ref()
+        .ok_or_else(|| AppError::InvalidInput("API server is not running".to_string()))?;
+    server
+        .share_link(&item_id, ttl.unwrap_or(api_server::DEFAULT_SHARE_TTL_SECS))
+        .map_err(|e| AppError::InvalidInput(e.to_string()))
+}
+
+#[tauri::command]
+async fn list_webhooks(state: State<'_, WebhookState>) -> Result<Vec<Webhook>, AppError> {
+    Ok(state.0.list())
+}
+
+#[tauri::command]
+async fn save_webhook(webhook: Webhook, state: State<'_, WebhookState>) -> Result<Webhook, AppError> {
+    state.0.save_webhook(webhook).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn delete_webhook(id: String, state: State<'_, WebhookState>) -> Result<bool, AppError> {
+    state.0.delete_webhook(&id).map_err(AppError::from)
+}
+
+// ping イベントをすぐに送り、受け取る側の HTTP ステータスを返す
+#[tauri::command]
+async fn test_webhook(id: String, app_handle: AppHandle) -> Result<u16, AppError> {
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<WebhookState>().0.test(&id))
+        .await
+        .map_err(AppError::from)?
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn list_plugins(state: State<'_, PluginState>) -> Result<Vec<PluginInfo>, AppError> {
+    Ok(state.0.list())
+}
+
+// plugins フォルダを読み直す（プラグインを追加・編集したとき）
+#[tauri::command]
+async fn reload_plugins(state: State<'_, PluginState>) -> Result<Vec<PluginInfo>, AppError> {
+    state.0.reload();
+    Ok(state.0.list())
+}
+
+#[tauri::command]
+async fn set_plugin_enabled(id: String, enabled: bool, state: State<'_, PluginState>) -> Result<(), AppError> {
+    state.0.set_enabled(&id, enabled).map_err(AppError::from)
+}
+
+// 画像を縮小して PNG にする
+fn resize_to_png(img_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>, AppError> {
+    let img = decode_image(img_data)?;
+
+    // リサイズ
+    let resized = img.resize(width, height, image::imageops::FilterType::Lanczos3);
+
+    // PNGでエンコード
+    let mut buf = Vec::new();
+    resized.write_to(&mut std::io::Cursor::new(&mut buf), image::ImageOutputFormat::Png)?;
+    Ok(buf)
+}
+
+fn size_header(request: &tauri::ipc::Request<'_>, name: &str) -> Result<u32, AppError> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| AppError::InvalidInput(format!("Missing or invalid {} header", name)))
+}
+
+// 画像のバイト列をそのまま受け取り、PNG のバイト列をそのまま返す（base64 の変換とその分のメモリが要らない）
+// 呼び出し方: invoke('resize_image_raw', bytes, { headers: { 'x-width': '800', 'x-height': '600' } })
+#[tauri::command]
+fn resize_image_raw(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, AppError> {
+    let tauri::ipc::InvokeBody::Raw(img_data) = request.body() else {
+        return Err(AppError::InvalidInput("Expected raw image bytes".to_string()));
+    };
+    let width = size_header(&request, "x-width")?;
+    let height = size_header(&request, "x-height")?;
+    Ok(tauri::ipc::Response::new(resize_to_png(img_data, width, height)?))
+}
+
+// 非推奨: base64 の文字列でやり取りする古い形式（resize_image_raw を使う）
+#[tauri::command]
+fn resize_image(base64_input: String, width: u32, height: u32) -> Result<String, AppError> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| log::warn!("resize_image is deprecated; use resize_image_raw"));
+
+    // base64デコード
+    let img_data = STANDARD.decode(&base64_input).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+    let buf = resize_to_png(&img_data, width, height)?;
+
+    // base64エンコードして返す
+    Ok(STANDARD.encode(&buf))
+}
+
+// 縦横比を保って縮小し、結果をキャッシュする
+#[tauri::command]
+async fn resize_image_cached(
+    image_data: Vec<u8>,
+    max_width: u32,
+    max_height: u32,
+    format: Option<ResizeFormat>,
+    cache: State<'_, ResizeCacheState>,
+    store_state: State<'_, MetadataStoreState>,
+) -> Result<tauri::ipc::Response, AppError> {
+    use image::{DynamicImage, GenericImageView};
+    
+    let key = ResizeKey {
+        hash: hashing::content_hash(&image_data),
+        width: max_width,
+        height: max_height,
+        format: format.unwrap_or_default(),
+    };
+    if let Some(cached) = cache.0.get(&key) {
+        return Ok(tauri::ipc::Response::new(cached));
+    }
+
+    let img = image_decode::decode(&image_data)?;
+    
+    let (width, height) = img.dimensions();
+    
+    // アスペクト比を保持してリサイズ
+    let (new_width, new_height) = if width > height {
+        let ratio = max_width as f32 / width as f32;
+        (max_width, (height as f32 * ratio) as u32)
+    } else {
+        let ratio = max_height as f32 / height as f32;
+        ((width as f32 * ratio) as u32, max_height)
+    };
+    
+    let resized = img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3);
+    
+    let output = match key.format {
+        ResizeFormat::Jpeg => image_decode::encode_jpeg(&resized, RESIZE_JPEG_QUALITY)?,
+        ResizeFormat::Png => {
+            let mut output = Vec::new();
+            resized.write_to(&mut std::io::Cursor::new(&mut output), image::ImageOutputFormat::Png)?;
+            output
+        }
+    };
+
+    // 非公開アイテムの画像はディスクのキャッシュに残さない
+    let persist = store_state
+        .0
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|store| store.find_by_hash(&key.hash).ok().flatten())
+        .map_or(true, |item| !item.private);
+    if let Err(e) = cache.0.insert(key, &output, persist) {
+        log::warn!("Failed to cache resized image: {}", e);
+    }
+    
+    // 数値の配列の JSON にせず、バイト列のまま返す
+    Ok(tauri::ipc::Response::new(output))
+}
+
+pub fn run() {
+    tauri::Builder::default()
+        // 2つ目の起動は引数を起動中のアプリへ渡して終了する（最初に登録する必要がある）
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            show_main_window(app);
+            let cwd = PathBuf::from(cwd);
+            open_files(app, args.iter().skip(1).map(|arg| cwd.join(arg)).collect());
+        }))
+        .plugin(tauri_plugin_deep_link::init())
+        .manage(SearchEngineState(Mutex::new(None)))
+        .manage(OAuthState(Arc::new(OAuthFlows::default())))
+        .manage(UploadServerState(Mutex::new(None)))
+        .manage(ScreenCaptureState(Mutex::new(None)))
+        .plugin(tauri_plugin_notification::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|app, _shortcut, event| {
+                    if event.state() == ShortcutState::Pressed {
+                        spawn_quick_capture(app, None);
+                    }
+                })
+                .build(),
+        )
+        .setup(|app| {
+            // 選ばれているライブラリのフォルダを以降のすべての処理で使う
+            let registry = LibraryRegistry::load(app.handle())?;
+            app.manage(ActiveLibrary(PathBuf::from(&registry.active().path)));
+
+            let paths = LibraryPaths::from_app(app.handle())?;
+            match logging::init(&paths.logs_dir()) {
+                Ok(guard) => {
+                    app.manage(LogGuardState(guard));
+                }
+                Err(e) => eprintln!("Failed to initialize logging: {}", e),
+            }
+            log::info!("Snap Organizer {} starting", app.package_info().version);
+
+            let thumbnails = ThumbnailCache::new(paths.thumbnails_dir(), DEFAULT_CACHE_MAX_BYTES)?;
+            app.manage(ThumbnailCacheState(thumbnails));
+            app.manage(ResizeCacheState(ResizeCache::new(paths.resize_cache_dir(), resize_cache::DISK_MAX_BYTES)?));
+
+            let mut store = MetadataStore::open(&paths.metadata_db_file())?;
+            // ポータブルモードでは image_path をライブラリからの相対パスで保存する
+            let relocated = store.set_relative_paths(paths::is_portable())?;
+            if relocated > 0 {
+                log::info!("Rewrote {} image paths for portable mode", relocated);
+            }
+            app.manage(MetadataStoreState(Mutex::new(Some(store))));
+
+            // 大きなライブラリでは検索インデックスを開くのに時間がかかるため、ウィンドウの表示を待たせない
+            let handle = app.handle().clone();
+            std::thread::spawn(move || {
+                if let Err(e) = open_search_engine(&handle) {
+                    log::error!("Failed to open search index: {}", e);
+                }
+            });
+
+            // ゴミ箱の保持期間を過ぎたアイテムを起動時と一定間隔で削除する
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                match purge_expired_trash(&handle) {
+                    Ok(0) => {}
+                    Ok(purged) => log::info!("Purged {} items from trash", purged),
+                    Err(e) => log::warn!("Failed to purge trash: {}", e),
+                }
+                std::thread::sleep(TRASH_PURGE_INTERVAL);
+            });
+
+            // タグごとの保持ルールを起動時と一定間隔で当てる
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                match enforce_retention(&handle) {
+                    Ok(outcome) => {
+                        if !outcome.trashed.is_empty() {
+                            log::info!("Moved {} items to trash by retention rules", outcome.trashed.len());
+                        }
+                        notify_retention_review(&handle, &outcome);
+                    }
+                    Err(e) => log::warn!("Failed to apply retention rules: {}", e),
+                }
+                std::thread::sleep(RETENTION_CHECK_INTERVAL);
+            });
+
+            // リマインダーの通知時刻を定期的に確認する
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                if let Err(e) = notify_due_reminders(&handle) {
+                    log::warn!("Failed to check reminders: {}", e);
+                }
+                std::thread::sleep(REMINDER_CHECK_INTERVAL);
+            });
+
+            // 同梱の jpn/eng traineddata をライブラリへ展開してから Tesseract を探す
+            if let Ok(resource_dir) = app.path().resource_dir() {
+                if let Err(e) = ocr::install_bundled_traineddata(&resource_dir.join("tessdata"), &paths.tessdata_dir()) {
+                    log::warn!("Failed to install bundled traineddata: {}", e);
+                }
+            }
+            let ocr_engine = TesseractEngine::locate(paths.tessdata_dir())
+                .map_err(|e| log::warn!("OCR disabled: {}", e))
+                .ok();
+            app.manage(OcrState(OcrService::new(ocr_engine, paths.ocr_settings_file())));
+            app.manage(CorrectionsState(CorrectionDictionary::new(paths.ocr_corrections_file())));
+
+            let mut geonames_dirs = vec![paths.geonames_dir()];
+            if let Ok(resource_dir) = app.path().resource_dir() {
+                geonames_dirs.push(resource_dir.join("geonames"));
+            }
+            app.manage(GeocodingState(GeocodingService::new(geonames_dirs, paths.geocoding_settings_file())));
+            let inference = Arc::new(InferenceService::new(paths.models_dir()));
+            app.manage(EmbeddingState(EmbeddingService::new(inference.clone(), paths.vector_index_file())));
+            app.manage(CaptionState(CaptionService::new(inference.clone())));
+            app.manage(ExchangeRatesState(ExchangeRates::new(paths.exchange_rates_file())));
+            app.manage(TranslationState(TranslationService::new(
+                inference.clone(),
+                paths.translation_settings_file(),
+            )));
+            app.manage(InferenceState(inference));
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(VECTOR_INDEX_FLUSH_INTERVAL);
+                if let Err(e) = handle.state::<EmbeddingState>().0.flush() {
+                    log::warn!("Failed to save vector index: {}", e);
+                }
+            });
+            app.manage(RulesState(RulesService::new(paths.rules_file())));
+            app.manage(MemoTemplatesState(MemoTemplates::new(paths.memo_templates_file())));
+            app.manage(PrivateVaultState(PrivateVault::new(paths.private_key_file())));
+            app.manage(AppLockState(AppLock::new(paths.app_lock_file())));
+
+            // 設定の変更はすべてのウィンドウに通知する
+            let settings = SettingsStore::new(paths.app_settings_file());
+            // バックエンドから出す文言（エラー・ジョブ名・通知）も表示言語に合わせる
+            i18n::set_locale(&settings.get().language);
+            let handle = app.handle().clone();
+            settings.subscribe(move |settings| {
+                i18n::set_locale(&settings.language);
+                let _ = handle.emit("settings-changed", settings);
+            });
+            app.manage(SettingsState(settings));
+            app.manage(WebhookState(WebhookService::new(paths.webhooks_file())?));
+            app.manage(PluginState(PluginHost::new(paths.plugins_dir(), paths.plugin_settings_file())));
+
+            let handle = app.handle().clone();
+            let job_manager = JobManager::new(paths.jobs_file(), move |job| {
+                let _ = handle.emit("job-progress", job);
+                notify_job_finished(&handle, job);
+            });
+            app.manage(JobManagerState(job_manager));
+
+            let folders = watcher::load_watch_folders(&paths);
+            let folder_watcher = start_folder_watcher(app.handle(), &folders).unwrap_or_else(|e| {
+                log::warn!("Failed to start folder watcher: {}", e);
+                None
+            });
+            app.manage(FolderWatchState(Mutex::new(folder_watcher)));
+
+            let lan_server = if LanSyncConfig::load(&paths.lan_sync_dir()).enabled {
+                start_lan_server(app.handle())
+                    .map_err(|e| log::warn!("Failed to start LAN sync: {}", e))
+                    .ok()
+            } else {
+                None
+            };
+            app.manage(LanSyncState(Mutex::new(lan_server)));
+
+            let api_settings = ApiServerSettings::load(&paths.api_settings_file());
+            let api_server = if api_settings.enabled {
+                launch_api_server(app.handle(), &api_settings)
+                    .map_err(|e| log::warn!("Failed to start API server: {}", e))
+                    .ok()
+            } else {
+                None
+            };
+            app.manage(ApiServerState(Mutex::new(api_server)));
+
+            setup_tray(app)?;
+            let quick_capture = QuickCaptureSettings::load(&paths.quick_capture_settings_file());
+            if let Err(e) = register_quick_capture_shortcut(app.handle(), &quick_capture) {
+                log::warn!("Failed to register quick capture shortcut: {}", e);
+            }
+
+            // 画像のダブルクリックで起動したとき
+            open_files(app.handle(), std::env::args_os().skip(1).map(PathBuf::from).collect());
+
+            // インストーラーを使わない開発版・Linux ではスキームを実行時に登録する
+            #[cfg(any(debug_assertions, target_os = "linux"))]
+            if let Err(e) = app.deep_link().register_all() {
+                log::warn!("Failed to register deep link scheme: {}", e);
+            }
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| open_deep_links(&handle, &event.urls()));
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                open_deep_links(app.handle(), &urls);
+            }
+            Ok(())
+        })
+        .register_asynchronous_uri_scheme_protocol("thumb", |ctx, request, responder| {
+            // キャッシュにないサムネイルは画像のデコードが要るので別スレッドで作る
+            let app_handle = ctx.app_handle().clone();
+            std::thread::spawn(move || {
+                if is_app_locked(&app_handle) {
+                    responder.respond(locked_response());
+                    return;
+                }
+                let response = match app_handle.try_state::<ThumbnailCacheState>() {
+                    Some(state) => protocol::thumbnail_response(&state.0, &request),
+                    None => protocol::error_response(
+                        tauri::http::StatusCode::SERVICE_UNAVAILABLE,
+                        "Thumbnail cache not initialized".to_string(),
+                    ),
+                };
+                responder.respond(response);
+            });
+        })
+        .register_asynchronous_uri_scheme_protocol("snap", |ctx, request, responder| {
+            // 大きな画像の読み込み・リサイズでUIスレッドを塞がないよう別スレッドで処理
+            let app_handle = ctx.app_handle().clone();
+            std::thread::spawn(move || {
+                if is_app_locked(&app_handle) {
+                    responder.respond(locked_response());
+                    return;
+                }
+                let states = (
+                    app_handle.try_state::<ThumbnailCacheState>(),
+                    app_handle.try_state::<PrivateVaultState>(),
+                );
+                let response = match states {
+                    (Some(state), Some(vault)) => protocol::snap_response(&state.0, &vault.0, &request),
+                    _ => protocol::error_response(
+                        tauri::http::StatusCode::SERVICE_UNAVAILABLE,
+                        "Thumbnail cache not initialized".to_string(),
+                    ),
+                };
+                responder.respond(response);
+            });
+        })
         .invoke_handler(tauri::generate_handler![
             init_search_engine,
+            is_search_ready,
+            get_recent_items,
             add_item_to_index,
             update_item_in_index,
             delete_item_from_index,
@@ -144,7 +4659,219 @@ fn main() {
             clear_search_index,
             get_search_stats,
             resize_image,
+            resize_image_raw,
+            resize_image_cached,
+            get_thumbnail,
+            clear_thumbnail_cache,
+            get_thumbnail_cache_stats,
+            get_watch_folders,
+            set_watch_folders,
+            ocr_image,
+            get_ocr_languages,
+            list_ocr_language_packs,
+            download_ocr_language_pack,
+            verify_ocr_language_pack,
+            delete_ocr_language_pack,
+            get_ocr_settings,
+            set_ocr_settings,
+            get_ocr_backends,
+            get_jobs,
+            get_job,
+            pause_job,
+            resume_job,
+            cancel_job,
+            clear_finished_jobs,
+            import_files,
+            create_item,
+            get_item,
+            update_item,
+            bulk_update,
+            generate_item_title,
+            delete_item,
+            get_item_history,
+            scan_duplicates,
+            get_storage_report,
+            recompress_originals,
+            get_expense_summary,
+            export_table,
+            export_markdown,
+            export_ical,
+            organize_to_folders,
+            email_items,
+            get_smtp_settings,
+            set_smtp_settings,
+            test_smtp_connection,
+            reverse_geocode,
+            geocode_address,
+            locate_items_by_address,
+            get_geocoding_settings,
+            set_geocoding_settings,
+            get_translation_settings,
+            set_translation_settings,
+            get_translation,
+            build_translations,
+            get_map_clusters,
+            get_timeline,
+            get_library_stats,
+            import_notes,
+            import_folder,
+            import_from_clipboard,
+            quick_capture,
+            get_quick_capture_settings,
+            set_quick_capture_settings,
+            capture_screen_region,
+            finish_screen_region,
+            revert_item,
+            list_items,
+            list_trash,
+            restore_from_trash,
+            purge_trash,
+            get_trash_settings,
+            set_trash_settings,
+            get_retention_settings,
+            set_retention_settings,
+            list_retention_reviews,
+            set_retention_exempt,
+            run_retention,
+            list_tags,
+            rename_tag,
+            merge_tags,
+            set_tag_parent,
+            set_tag_style,
+            suggest_tags,
+            list_rules,
+            save_rule,
+            delete_rule,
+            dry_run_rule,
+            list_memo_templates,
+            save_memo_template,
+            delete_memo_template,
+            apply_template,
+            list_albums,
+            create_album,
+            rename_album,
+            move_album,
+            delete_album,
+            get_album_items,
+            add_to_album,
+            remove_from_album,
+            reorder_album,
+            link_items,
+            unlink_items,
+            get_item_relations,
+            add_reminder,
+            update_reminder,
+            complete_reminder,
+            delete_reminder,
+            list_reminders,
+            get_private_vault_status,
+            set_private_passcode,
+            unlock_private_items,
+            lock_private_items,
+            set_item_private,
+            get_app_lock_status,
+            set_app_passcode,
+            unlock_app,
+            lock_app,
+            get_activity_log,
+            get_app_settings,
+            set_app_settings,
+            update_app_settings,
+            get_recent_logs,
+            export_diagnostics,
+            verify_library,
+            repair_library,
+            collect_orphans,
+            list_libraries,
+            add_library,
+            rename_library,
+            remove_library,
+            set_library_prompt_on_startup,
+            switch_library,
+            get_portable_status,
+            export_viewer_bundle,
+            save_group,
+            list_groups,
+            delete_group,
+            rebuild_search_index,
+            export_backup,
+            is_backup_encrypted,
+            import_backup,
+            get_sync_config,
+            set_sync_config,
+            test_sync_connection,
+            sync_now,
+            get_sync_conflicts,
+            resolve_sync_conflict,
+            start_sync_sign_in,
+            complete_sync_sign_in,
+            sign_out_sync,
+            get_lan_sync_config,
+            set_lan_sync_enabled,
+            start_lan_pairing,
+            discover_lan_peers,
+            pair_lan_peer,
+            remove_lan_peer,
+            lan_sync_now,
+            start_upload_server,
+            stop_upload_server,
+            get_upload_server_status,
+            start_api_server,
+            stop_api_server,
+            get_api_server_status,
+            regenerate_api_token,
+            create_share_link,
+            list_webhooks,
+            save_webhook,
+            delete_webhook,
+            test_webhook,
+            list_plugins,
+            reload_plugins,
+            set_plugin_enabled,
+            get_item_link,
+            get_search_link,
+            start_drag_out,
+            get_ocr_words,
+            get_ocr_overlay,
+            update_ocr_text,
+            get_ocr_corrections,
+            set_item_ocr_mode,
+            rerun_ocr,
+            backfill_ocr,
+            list_correction_dictionary,
+            delete_correction_entry,
+            get_receipt_fields,
+            get_item_entities,
+            get_item_qr_codes,
+            scan_qr_codes,
+            build_qr_codes,
+            get_document_type_counts,
+            refresh_receipt_fields,
+            list_models,
+            download_model,
+            verify_model,
+            delete_model,
+            semantic_search,
+            build_semantic_index,
+            generate_caption,
+            build_captions,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // 終了時にまだ保存していない意味での検索の索引を保存する
+            if let tauri::RunEvent::Exit = &event {
+                if let Some(embeddings) = app_handle.try_state::<EmbeddingState>() {
+                    if let Err(e) = embeddings.0.flush() {
+                        log::warn!("Failed to save vector index: {}", e);
+                    }
+                }
+            }
+            // macOS は開くファイルを引数ではなくイベントで渡す
+            #[cfg(target_os = "macos")]
+            if let tauri::RunEvent::Opened { urls } = &event {
+                let paths = urls.iter().filter_map(|url| url.to_file_path().ok()).collect();
+                open_files(app_handle, paths);
+            }
+        });
 }
@@ -0,0 +1,136 @@
+use crate::paths;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+// 既存のデータディレクトリをそのまま使うライブラリ
+pub const DEFAULT_LIBRARY_ID: &str = "default";
+const REGISTRY_FILE: &str = "libraries.json";
+
+// 個人用・会社用などの独立したライブラリ（インデックス・設定・同期設定はそれぞれのフォルダに持つ）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryProfile {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibraryRegistry {
+    #[serde(default)]
+    pub libraries: Vec<LibraryProfile>,
+    #[serde(default)]
+    pub active_id: Option<String>,
+    // 起動時にライブラリを選ぶ画面を出す
+    #[serde(default)]
+    pub ask_on_startup: bool,
+}
+
+// 起動時に選ばれたライブラリのフォルダ（LibraryPaths::from_app が参照する）
+pub struct ActiveLibrary(pub PathBuf);
+
+impl LibraryRegistry {
+    // ライブラリの一覧はどのライブラリにも属さないよう、アプリのデータディレクトリに置く
+    pub fn load(app_handle: &AppHandle) -> Result<Self> {
+        Ok(Self::load_from(&paths::app_data_root(app_handle)?))
+    }
+
+    // GUI を起動しない CLI からも同じ一覧を読む
+    pub fn load_from(base: &Path) -> Self {
+        let mut registry: LibraryRegistry = fs::read_to_string(base.join(REGISTRY_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        // 既定のライブラリはポータブルモードでドライブ名が変わっても今のフォルダを指す
+        let base_path = base.to_string_lossy().to_string();
+        if let Some(default) = registry.libraries.iter_mut().find(|l| l.id == DEFAULT_LIBRARY_ID) {
+            default.path = base_path;
+        } else {
+            registry.libraries.insert(
+                0,
+                LibraryProfile {
+                    id: DEFAULT_LIBRARY_ID.to_string(),
+                    name: "Default".to_string(),
+                    path: base_path,
+                    created_at: Utc::now(),
+                },
+            );
+        }
+        registry
+    }
+
+    pub fn save(&self, app_handle: &AppHandle) -> Result<()> {
+        let base = paths::app_data_root(app_handle)?;
+        fs::create_dir_all(&base)?;
+        fs::write(base.join(REGISTRY_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn active(&self) -> &LibraryProfile {
+        self.active_id
+            .as_deref()
+            .and_then(|id| self.libraries.iter().find(|l| l.id == id))
+            .unwrap_or(&self.libraries[0])
+    }
+
+    pub fn find_by_path(&self, path: &Path) -> Option<&LibraryProfile> {
+        self.libraries.iter().find(|l| Path::new(&l.path) == path)
+    }
+
+    // フォルダを登録する（登録済みならそのライブラリを返す）
+    pub fn add(&mut self, name: Option<&str>, path: &Path) -> Result<LibraryProfile> {
+        if let Some(existing) = self.find_by_path(path) {
+            return Ok(existing.clone());
+        }
+        if path.exists() && !path.is_dir() {
+            bail!("Not a folder: {}", path.display());
+        }
+        fs::create_dir_all(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let name = match name.map(str::trim) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string_lossy().to_string()),
+        };
+        let profile = LibraryProfile {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            path: path.to_string_lossy().to_string(),
+            created_at: Utc::now(),
+        };
+        self.libraries.push(profile.clone());
+        Ok(profile)
+    }
+
+    pub fn rename(&mut self, id: &str, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("Library name must not be empty");
+        }
+        let library = self
+            .libraries
+            .iter_mut()
+            .find(|l| l.id == id)
+            .with_context(|| format!("Library not found: {}", id))?;
+        library.name = name.to_string();
+        Ok(())
+    }
+
+    // 一覧から外すだけで、フォルダの中身は削除しない
+    pub fn remove(&mut self, id: &str) -> Result<bool> {
+        if id == DEFAULT_LIBRARY_ID {
+            bail!("The default library cannot be removed");
+        }
+        if self.active().id == id {
+            bail!("Switch to another library before removing this one");
+        }
+        let before = self.libraries.len();
+        self.libraries.retain(|l| l.id != id);
+        Ok(self.libraries.len() < before)
+    }
+}
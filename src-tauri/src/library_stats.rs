@@ -0,0 +1,97 @@
+use crate::metadata_store::{ItemFilter, MetadataStore};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::Mutex;
+
+const TOP_LOCATIONS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CountBucket {
+    pub label: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LibraryStats {
+    pub total_items: usize,
+    pub total_groups: usize,
+    pub untagged_items: usize,
+    pub items_with_location: usize,
+    // OCR のテキストがあるアイテムの割合（0〜100）
+    pub ocr_items: usize,
+    pub ocr_coverage_percent: f64,
+    pub total_image_bytes: u64,
+    pub average_image_bytes: u64,
+    pub oldest_item_at: Option<DateTime<Utc>>,
+    pub newest_item_at: Option<DateTime<Utc>>,
+    // 件数の多い順
+    pub by_tag: Vec<CountBucket>,
+    // 古い順（2024-05 のようなローカル時刻の年月）
+    pub by_month: Vec<CountBucket>,
+    pub top_locations: Vec<CountBucket>,
+}
+
+fn sorted_by_count(counts: HashMap<String, usize>) -> Vec<CountBucket> {
+    let mut buckets: Vec<CountBucket> = counts.into_iter().map(|(label, count)| CountBucket { label, count }).collect();
+    buckets.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.label.cmp(&b.label)));
+    buckets
+}
+
+pub fn build_stats(store: &Mutex<Option<MetadataStore>>) -> Result<LibraryStats> {
+    let (items, groups) = {
+        let store = store.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        (store.list_items(&ItemFilter::default())?, store.list_groups()?)
+    };
+
+    let mut stats = LibraryStats {
+        total_items: items.len(),
+        total_groups: groups.len(),
+        ..Default::default()
+    };
+    let mut by_tag = HashMap::new();
+    let mut by_month = BTreeMap::new();
+    let mut locations = HashMap::new();
+    let mut images = 0u64;
+    for item in &items {
+        if item.tags.is_empty() {
+            stats.untagged_items += 1;
+        }
+        for tag in &item.tags {
+            *by_tag.entry(tag.clone()).or_insert(0) += 1;
+        }
+        let created = item.created_at.with_timezone(&Local);
+        *by_month.entry(format!("{}-{:02}", created.year(), created.month())).or_insert(0) += 1;
+
+        if item.latitude.is_some() || item.location_name.is_some() {
+            stats.items_with_location += 1;
+        }
+        if let Some(location) = item.location_name.as_deref().map(str::trim).filter(|l| !l.is_empty()) {
+            *locations.entry(location.to_string()).or_insert(0) += 1;
+        }
+        if !item.ocr_text.trim().is_empty() {
+            stats.ocr_items += 1;
+        }
+        if let Some(size) = item.image_path.as_deref().and_then(|p| fs::metadata(p).ok()).map(|m| m.len()) {
+            stats.total_image_bytes += size;
+            images += 1;
+        }
+        stats.oldest_item_at = Some(stats.oldest_item_at.map_or(item.created_at, |d| d.min(item.created_at)));
+        stats.newest_item_at = Some(stats.newest_item_at.map_or(item.created_at, |d| d.max(item.created_at)));
+    }
+
+    if stats.total_items > 0 {
+        stats.ocr_coverage_percent = stats.ocr_items as f64 * 100.0 / stats.total_items as f64;
+    }
+    if images > 0 {
+        stats.average_image_bytes = stats.total_image_bytes / images;
+    }
+    stats.by_tag = sorted_by_count(by_tag);
+    stats.by_month = by_month.into_iter().map(|(label, count)| CountBucket { label, count }).collect();
+    stats.top_locations = sorted_by_count(locations);
+    stats.top_locations.truncate(TOP_LOCATIONS);
+    Ok(stats)
+}
@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const LOG_FILE_PREFIX: &str = "snap-organizer";
+const LOG_FILE_SUFFIX: &str = "log";
+// 1日1ファイルで、これより古いものは削除する
+const MAX_LOG_FILES: usize = 14;
+pub const DEFAULT_LOG_LIMIT: usize = 200;
+const DEFAULT_FILTER: &str = "info,tantivy=warn";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "TRACE" => Some(LogLevel::Trace),
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" => Some(LogLevel::Warn),
+            "ERROR" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: LogLevel,
+    pub target: String,
+    pub message: String,
+}
+
+// log クレートのマクロも含め、logs/ 以下の日ごとのファイルと標準エラーへ出力する
+// 戻り値の guard を保持している間だけファイルへ書き込まれる
+pub fn init(logs_dir: &Path) -> Result<WorkerGuard> {
+    fs::create_dir_all(logs_dir)?;
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(logs_dir)
+        .context("Failed to create log file")?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let filter = EnvFilter::try_from_env("SNAP_LOG").unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().json().with_writer(writer))
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .try_init()
+        .context("Logger already initialized")?;
+    Ok(guard)
+}
+
+// 新しい順に並べたログファイル
+fn log_files(logs_dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(logs_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with(LOG_FILE_PREFIX) && n.ends_with(LOG_FILE_SUFFIX))
+                .unwrap_or(false)
+        })
+        .collect();
+    files.sort();
+    files.reverse();
+    files
+}
+
+fn parse_line(line: &str) -> Option<LogEntry> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    let text = |v: Option<&serde_json::Value>| v.and_then(|v| v.as_str()).unwrap_or("").to_string();
+    Some(LogEntry {
+        timestamp: text(value.get("timestamp")),
+        level: LogLevel::parse(value.get("level")?.as_str()?)?,
+        target: text(value.get("target")),
+        message: text(value.pointer("/fields/message")),
+    })
+}
+
+// level 以上のログを新しい順に最大 limit 件返す
+pub fn recent_logs(logs_dir: &Path, level: Option<LogLevel>, limit: usize) -> Result<Vec<LogEntry>> {
+    let min_level = level.unwrap_or(LogLevel::Info);
+    let mut entries = Vec::new();
+    for file in log_files(logs_dir) {
+        let content = fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+        for entry in content.lines().rev().filter_map(parse_line) {
+            if entry.level < min_level {
+                continue;
+            }
+            entries.push(entry);
+            if entries.len() >= limit {
+                return Ok(entries);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+// 不具合報告に添付するためのログと環境情報をまとめた zip を作る
+pub fn export_diagnostics(logs_dir: &Path, info: &serde_json::Value, dest: &Path) -> Result<usize> {
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(File::create(dest)?);
+
+    zip.start_file("diagnostics.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(info)?)?;
+
+    let files = log_files(logs_dir);
+    for file in &files {
+        let name = file.file_name().and_then(|n| n.to_str()).unwrap_or(LOG_FILE_PREFIX);
+        zip.start_file(format!("logs/{}", name), options)?;
+        zip.write_all(&fs::read(file)?)?;
+    }
+    zip.finish()?;
+    Ok(files.len())
+}
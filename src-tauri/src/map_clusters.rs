@@ -0,0 +1,118 @@
+use crate::metadata_store::ItemRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+// 地図上でこの大きさ（ピクセル）のマス目ごとにまとめる
+const CELL_SIZE_PX: f64 = 64.0;
+const TILE_SIZE_PX: f64 = 256.0;
+const MAX_ZOOM: u32 = 22;
+// Web メルカトルで表示できる緯度の範囲
+const MAX_LATITUDE: f64 = 85.051_128_78;
+
+// 表示範囲（west > east なら日付変更線をまたぐ）
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct MapBounds {
+    pub north: f64,
+    pub south: f64,
+    pub east: f64,
+    pub west: f64,
+}
+
+impl MapBounds {
+    fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        if latitude < self.south || latitude > self.north {
+            return false;
+        }
+        if self.west <= self.east {
+            longitude >= self.west && longitude <= self.east
+        } else {
+            longitude >= self.west || longitude <= self.east
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MapCluster {
+    // まとめた地点の重心
+    pub latitude: f64,
+    pub longitude: f64,
+    pub count: usize,
+    // 代表のアイテム（最も新しいもの）のサムネイル用
+    pub representative_id: String,
+    pub representative_image: Option<String>,
+    // クリックしたときにこの範囲へズームする
+    pub bounds: [f64; 4],
+    // 1件だけのときはそのアイテム（それ以外は空）
+    pub item_id: Option<String>,
+}
+
+// 経度・緯度をズームレベルでの世界座標（ピクセル）に変換する
+fn project(latitude: f64, longitude: f64, zoom: u32) -> (f64, f64) {
+    let scale = TILE_SIZE_PX * 2f64.powi(zoom as i32);
+    let sin = latitude.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians().sin();
+    let x = (longitude + 180.0) / 360.0 * scale;
+    let y = (0.5 - ((1.0 + sin) / (1.0 - sin)).ln() / (4.0 * PI)) * scale;
+    (x, y)
+}
+
+struct Cell<'a> {
+    lat_sum: f64,
+    lon_sum: f64,
+    bounds: [f64; 4],
+    items: Vec<&'a ItemRecord>,
+}
+
+// 位置情報のあるアイテムを画面上のマス目でまとめる
+pub fn cluster(items: &[ItemRecord], bounds: &MapBounds, zoom: u32) -> Vec<MapCluster> {
+    let zoom = zoom.min(MAX_ZOOM);
+    let mut cells: HashMap<(i64, i64), Cell> = HashMap::new();
+    for item in items {
+        let (latitude, longitude) = match (item.latitude, item.longitude) {
+            (Some(lat), Some(lon)) if bounds.contains(lat, lon) => (lat, lon),
+            _ => continue,
+        };
+        let (x, y) = project(latitude, longitude, zoom);
+        let key = ((x / CELL_SIZE_PX).floor() as i64, (y / CELL_SIZE_PX).floor() as i64);
+        let cell = cells.entry(key).or_insert_with(|| Cell {
+            lat_sum: 0.0,
+            lon_sum: 0.0,
+            bounds: [latitude, longitude, latitude, longitude],
+            items: Vec::new(),
+        });
+        cell.lat_sum += latitude;
+        cell.lon_sum += longitude;
+        // [south, west, north, east]
+        cell.bounds[0] = cell.bounds[0].min(latitude);
+        cell.bounds[1] = cell.bounds[1].min(longitude);
+        cell.bounds[2] = cell.bounds[2].max(latitude);
+        cell.bounds[3] = cell.bounds[3].max(longitude);
+        cell.items.push(item);
+    }
+
+    let mut clusters: Vec<MapCluster> = cells
+        .into_values()
+        .map(|cell| {
+            let count = cell.items.len();
+            let representative = cell
+                .items
+                .iter()
+                .filter(|item| item.image_path.is_some())
+                .max_by_key(|item| item.created_at)
+                .or_else(|| cell.items.first())
+                .copied()
+                .expect("cell has at least one item");
+            MapCluster {
+                latitude: cell.lat_sum / count as f64,
+                longitude: cell.lon_sum / count as f64,
+                count,
+                representative_id: representative.id.clone(),
+                representative_image: representative.image_path.clone(),
+                bounds: cell.bounds,
+                item_id: (count == 1).then(|| representative.id.clone()),
+            }
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.count.cmp(&a.count));
+    clusters
+}
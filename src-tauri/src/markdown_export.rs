@@ -0,0 +1,195 @@
+use crate::jobs::ProgressReporter;
+use crate::metadata_store::{GroupRecord, ItemRecord};
+use anyhow::{Context, Result};
+use chrono::{Datelike, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ATTACHMENTS_DIR: &str = "attachments";
+const MAX_TITLE_CHARS: usize = 40;
+
+// ノートを置くフォルダの分け方
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderLayout {
+    #[default]
+    Flat,
+    Year,
+    YearMonth,
+    // 最初のタグ（タグなしは Untagged）
+    Tag,
+    // グループ名（グループなしは Ungrouped）
+    Group,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarkdownExportOptions {
+    pub dest: String,
+    #[serde(default)]
+    pub layout: FolderLayout,
+    // 画像を保存先の attachments フォルダにコピーする（false なら元画像の場所を参照する）
+    #[serde(default = "default_copy_images")]
+    pub copy_images: bool,
+}
+
+fn default_copy_images() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MarkdownExportReport {
+    pub notes_written: usize,
+    pub images_copied: usize,
+    pub dest: String,
+}
+
+// ファイル名・フォルダ名に使えない文字を置き換える
+pub fn sanitize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    cleaned.trim().trim_matches('.').to_string()
+}
+
+// 見出しがあれば見出し、なければメモの1行目
+fn note_title(item: &ItemRecord) -> String {
+    let first_line = item
+        .title
+        .as_deref()
+        .into_iter()
+        .chain(item.memo.lines())
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or_default();
+    sanitize(&first_line.chars().take(MAX_TITLE_CHARS).collect::<String>())
+}
+
+fn note_dir(item: &ItemRecord, layout: FolderLayout, groups: &HashMap<String, GroupRecord>) -> PathBuf {
+    let created = item.created_at.with_timezone(&Local);
+    match layout {
+        FolderLayout::Flat => PathBuf::new(),
+        FolderLayout::Year => PathBuf::from(created.year().to_string()),
+        FolderLayout::YearMonth => PathBuf::from(created.year().to_string()).join(format!("{:02}", created.month())),
+        FolderLayout::Tag => PathBuf::from(
+            item.tags
+                .first()
+                .map(|t| sanitize(t))
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| "Untagged".to_string()),
+        ),
+        FolderLayout::Group => PathBuf::from(
+            item.group_id
+                .as_ref()
+                .and_then(|id| groups.get(id))
+                .map(|g| sanitize(&g.title))
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| "Ungrouped".to_string()),
+        ),
+    }
+}
+
+// フロントマターの値は JSON の文字列表記で書く（YAML としても有効）
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn render_note(item: &ItemRecord, group: Option<&GroupRecord>, image_link: Option<&str>) -> String {
+    let mut note = String::from("---\n");
+    note.push_str(&format!("id: {}\n", yaml_string(&item.id)));
+    note.push_str(&format!("created: {}\n", item.created_at.with_timezone(&Local).to_rfc3339()));
+    note.push_str(&format!("updated: {}\n", item.updated_at.with_timezone(&Local).to_rfc3339()));
+    if !item.tags.is_empty() {
+        note.push_str("tags:\n");
+        for tag in &item.tags {
+            // Obsidian のタグに空白は使えない
+            note.push_str(&format!("  - {}\n", yaml_string(&tag.replace(' ', "_"))));
+        }
+    }
+    if let Some(group) = group {
+        note.push_str(&format!("group: {}\n", yaml_string(&group.title)));
+    }
+    if let Some(location) = &item.location_name {
+        note.push_str(&format!("location: {}\n", yaml_string(location)));
+    }
+    if let (Some(lat), Some(lon)) = (item.latitude, item.longitude) {
+        note.push_str(&format!("coordinates: [{}, {}]\n", lat, lon));
+    }
+    note.push_str("---\n\n");
+
+    if let Some(link) = image_link {
+        note.push_str(&format!("![](<{}>)\n\n", link));
+    }
+    if !item.memo.trim().is_empty() {
+        note.push_str("## Memo\n\n");
+        note.push_str(item.memo.trim());
+        note.push_str("\n\n");
+    }
+    if !item.ocr_text.trim().is_empty() {
+        note.push_str("## OCR\n\n");
+        note.push_str(item.ocr_text.trim());
+        note.push('\n');
+    }
+    note
+}
+
+// 1アイテム1ノートの Markdown として書き出す（同じ保存先への再エクスポートは上書きになる）
+pub fn export_markdown(
+    items: &[ItemRecord],
+    groups: &[GroupRecord],
+    options: &MarkdownExportOptions,
+    reporter: &dyn ProgressReporter,
+) -> Result<MarkdownExportReport> {
+    let dest = Path::new(&options.dest);
+    fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    let groups: HashMap<String, GroupRecord> = groups.iter().map(|g| (g.id.clone(), g.clone())).collect();
+    let mut report = MarkdownExportReport {
+        dest: options.dest.clone(),
+        ..Default::default()
+    };
+    reporter.set_total(items.len() as u64);
+
+    for (i, item) in items.iter().enumerate() {
+        reporter.checkpoint()?;
+        let dir = note_dir(item, options.layout, &groups);
+        fs::create_dir_all(dest.join(&dir))?;
+
+        let image = item.image_path.as_deref().map(Path::new).filter(|p| p.is_file());
+        let image_link = match image {
+            Some(image) if options.copy_images => {
+                let file_name = image.file_name().context("Invalid image path")?;
+                let target = dest.join(ATTACHMENTS_DIR).join(file_name);
+                if !target.exists() {
+                    fs::create_dir_all(dest.join(ATTACHMENTS_DIR))?;
+                    fs::copy(image, &target)?;
+                    report.images_copied += 1;
+                }
+                // ノートのフォルダからの相対パス
+                let up = "../".repeat(dir.components().count());
+                Some(format!("{}{}/{}", up, ATTACHMENTS_DIR, file_name.to_string_lossy()))
+            }
+            Some(image) => Some(image.to_string_lossy().replace('\\', "/")),
+            None => None,
+        };
+
+        let date = item.created_at.with_timezone(&Local).format("%Y-%m-%d");
+        let short_id: String = item.id.chars().take(8).collect();
+        let title = note_title(item);
+        let file_name = if title.is_empty() {
+            format!("{} {}.md", date, short_id)
+        } else {
+            format!("{} {} ({}).md", date, title, short_id)
+        };
+        let group = item.group_id.as_ref().and_then(|id| groups.get(id));
+        fs::write(dest.join(&dir).join(file_name), render_note(item, group, image_link.as_deref()))?;
+        report.notes_written += 1;
+        reporter.progress(i as u64 + 1, &item.id);
+    }
+    Ok(report)
+}
@@ -0,0 +1,162 @@
+use crate::metadata_store::ItemRecord;
+use crate::receipts::ReceiptFields;
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoTemplate {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    // 例: 経費 {date} {vendor} {amount}{currency}
+    // 差し込み項目は date / vendor / amount / currency / tax / subtotal / title / tags / location / today
+    // （これ以外の {...} はそのまま残す）
+    pub body: String,
+    // true ならメモを置き換え、false なら末尾に足す
+    #[serde(default)]
+    pub replace: bool,
+}
+
+fn placeholder_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{([a-z_]+)\}").unwrap())
+}
+
+// 3桁ごとにカンマを入れる
+fn group_digits(amount: u64) -> String {
+    let digits = amount.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+fn value(name: &str, item: &ItemRecord, receipt: Option<&ReceiptFields>) -> Option<String> {
+    let amount = |amount: Option<u64>| amount.map(group_digits).unwrap_or_default();
+    Some(match name {
+        // 購入日、書類の日付、取り込んだ日の順に使う
+        "date" => receipt
+            .and_then(|r| r.purchased_on)
+            .or(item.document_date)
+            .unwrap_or_else(|| item.created_at.with_timezone(&Local).date_naive())
+            .format("%Y-%m-%d")
+            .to_string(),
+        "vendor" => receipt.and_then(|r| r.vendor.clone()).unwrap_or_default(),
+        "amount" => amount(receipt.and_then(|r| r.total)),
+        "currency" => receipt.map(|r| r.currency.clone()).unwrap_or_default(),
+        "tax" => amount(receipt.and_then(|r| r.tax)),
+        "subtotal" => amount(receipt.and_then(|r| r.subtotal)),
+        "title" => item.title.clone().unwrap_or_default(),
+        "tags" => item.tags.join(", "),
+        "location" => item.location_name.clone().unwrap_or_default(),
+        "today" => Local::now().format("%Y-%m-%d").to_string(),
+        _ => return None,
+    })
+}
+
+// 差し込み項目をアイテムとレシートの項目で埋める（読み取れなかった項目は空にする）
+pub fn fill(body: &str, item: &ItemRecord, receipt: Option<&ReceiptFields>) -> String {
+    let filled = placeholder_pattern().replace_all(body, |caps: &regex::Captures| {
+        value(&caps[1], item, receipt).unwrap_or_else(|| caps[0].to_string())
+    });
+    // 空の項目で残った余分な空白を詰める
+    filled
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// テンプレートを当てたあとのメモ（足すときに同じ内容が既にあれば変えない）
+pub fn apply(template: &MemoTemplate, item: &ItemRecord, receipt: Option<&ReceiptFields>) -> String {
+    let filled = fill(&template.body, item, receipt);
+    if template.replace || item.memo.trim().is_empty() {
+        filled
+    } else if item.memo.contains(&filled) {
+        item.memo.clone()
+    } else {
+        format!("{}\n{}", item.memo.trim_end(), filled)
+    }
+}
+
+// メモのテンプレート（memo_templates.json に保存する）
+pub struct MemoTemplates {
+    path: PathBuf,
+    templates: Mutex<Vec<MemoTemplate>>,
+}
+
+impl MemoTemplates {
+    pub fn new(path: PathBuf) -> Self {
+        let templates = Self::load(&path);
+        MemoTemplates {
+            path,
+            templates: Mutex::new(templates),
+        }
+    }
+
+    fn load(path: &Path) -> Vec<MemoTemplate> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, templates: &[MemoTemplate]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(templates)?)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<MemoTemplate> {
+        self.templates.lock().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Result<MemoTemplate> {
+        self.templates
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| t.id == id)
+            .cloned()
+            .with_context(|| format!("Memo template not found: {}", id))
+    }
+
+    // ID が空なら新しいテンプレートとして追加し、あれば置き換える
+    pub fn save_template(&self, mut template: MemoTemplate) -> Result<MemoTemplate> {
+        if template.name.trim().is_empty() || template.body.trim().is_empty() {
+            bail!("Memo template needs a name and a body");
+        }
+        if template.id.is_empty() {
+            template.id = uuid::Uuid::new_v4().to_string();
+        }
+        let mut templates = self.templates.lock().unwrap();
+        match templates.iter_mut().find(|t| t.id == template.id) {
+            Some(existing) => *existing = template.clone(),
+            None => templates.push(template.clone()),
+        }
+        self.save(&templates)?;
+        Ok(template)
+    }
+
+    pub fn delete_template(&self, id: &str) -> Result<bool> {
+        let mut templates = self.templates.lock().unwrap();
+        let before = templates.len();
+        templates.retain(|t| t.id != id);
+        if templates.len() == before {
+            return Ok(false);
+        }
+        self.save(&templates)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,2998 @@
+use crate::document_dates;
+use crate::document_types::{self, DocumentType};
+use crate::entities::{self, Entity, EntityKind};
+use crate::geocoding::GeoFilter;
+use crate::hashing;
+use crate::ocr::OcrWord;
+use crate::ocr_corrections::OcrRegion;
+use crate::qr_codes::QrCode;
+use crate::receipts::{self, ReceiptFields, ReceiptFilter};
+use crate::search_engine::SearchableItem;
+use crate::titles;
+use crate::translation::Translation;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// スキーマのマイグレーション（PRAGMA user_version で適用済みの番号を管理）
+const MIGRATIONS: &[&str] = &[
+    // v1: アイテム・タグ・グループ・編集履歴
+    "
+    CREATE TABLE groups (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL DEFAULT '',
+        memo TEXT NOT NULL DEFAULT '',
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE items (
+        id TEXT PRIMARY KEY,
+        group_id TEXT REFERENCES groups(id) ON DELETE SET NULL,
+        image_path TEXT,
+        content_hash TEXT,
+        ocr_text TEXT NOT NULL DEFAULT '',
+        memo TEXT NOT NULL DEFAULT '',
+        location_name TEXT,
+        latitude REAL,
+        longitude REAL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_items_content_hash ON items(content_hash);
+    CREATE INDEX idx_items_created_at ON items(created_at);
+    CREATE INDEX idx_items_group_id ON items(group_id);
+    CREATE TABLE item_tags (
+        item_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (item_id, tag)
+    );
+    CREATE INDEX idx_item_tags_tag ON item_tags(tag);
+    CREATE TABLE edit_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        item_id TEXT NOT NULL,
+        field TEXT NOT NULL,
+        old_value TEXT,
+        new_value TEXT,
+        changed_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_edit_history_item_id ON edit_history(item_id);
+    ",
+    // v2: リモートごとの同期状態（最後に同期したときの etag とローカル側のバージョン）
+    "
+    CREATE TABLE sync_state (
+        remote TEXT NOT NULL,
+        key TEXT NOT NULL,
+        etag TEXT NOT NULL,
+        local_version TEXT NOT NULL,
+        synced_at TEXT NOT NULL,
+        PRIMARY KEY (remote, key)
+    );
+    ",
+    // v3: フィールドごとの最終更新日時と、同期時に見つかった編集の衝突
+    "
+    CREATE TABLE field_versions (
+        record_id TEXT NOT NULL,
+        field TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (record_id, field)
+    );
+    CREATE TABLE sync_conflicts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        remote TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        record_id TEXT NOT NULL,
+        field TEXT NOT NULL,
+        local_value TEXT NOT NULL,
+        remote_value TEXT NOT NULL,
+        applied TEXT NOT NULL,
+        detected_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_sync_conflicts_record_id ON sync_conflicts(record_id);
+    ",
+    // v4: ゴミ箱（削除日時が入っているアイテムは一覧・検索の対象外）
+    "
+    ALTER TABLE items ADD COLUMN deleted_at TEXT;
+    CREATE INDEX idx_items_deleted_at ON items(deleted_at);
+    ",
+    // v5: 編集履歴に版番号を付ける（1回の更新で変わったフィールドは同じ版になる）
+    "
+    ALTER TABLE edit_history ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
+    UPDATE edit_history SET version = (
+        SELECT COUNT(DISTINCT h.changed_at) FROM edit_history h
+        WHERE h.item_id = edit_history.item_id AND h.changed_at <= edit_history.changed_at
+    );
+    CREATE INDEX idx_edit_history_item_version ON edit_history(item_id, version);
+    ",
+    // v6: 重複検出用の画像の特徴（内容が変わったら content_hash で判別して計算し直す）
+    "
+    CREATE TABLE image_fingerprints (
+        item_id TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+        content_hash TEXT NOT NULL,
+        perceptual_hash TEXT NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL
+    );
+    ",
+    // v7: 再圧縮した元画像の記録（元のファイルのハッシュで重複判定できるように残す）
+    "
+    CREATE TABLE recompressed_originals (
+        item_id TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+        original_hash TEXT NOT NULL,
+        original_size INTEGER NOT NULL,
+        original_format TEXT NOT NULL,
+        recompressed_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_recompressed_originals_hash ON recompressed_originals(original_hash);
+    ",
+    // v8: アルバム（入れ子にでき、アイテムの並び順を持つ）
+    "
+    CREATE TABLE albums (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        parent_id TEXT REFERENCES albums(id) ON DELETE SET NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_albums_parent_id ON albums(parent_id);
+    CREATE TABLE album_items (
+        album_id TEXT NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+        item_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        added_at TEXT NOT NULL,
+        PRIMARY KEY (album_id, item_id)
+    );
+    CREATE INDEX idx_album_items_item_id ON album_items(item_id);
+    ",
+    // v9: タグの一覧と階層（親タグで検索すると子タグのアイテムも一致する）
+    "
+    CREATE TABLE tags (
+        name TEXT PRIMARY KEY,
+        parent TEXT REFERENCES tags(name) ON UPDATE CASCADE ON DELETE SET NULL
+    );
+    INSERT OR IGNORE INTO tags (name) SELECT DISTINCT tag FROM item_tags;
+    ",
+    // v10: タグの色とアイコン（絵文字など）
+    "
+    ALTER TABLE tags ADD COLUMN color TEXT;
+    ALTER TABLE tags ADD COLUMN icon TEXT;
+    ",
+    // v11: アイテム同士の関係（source が target の N ページ目・重複・関連）
+    "
+    CREATE TABLE item_relations (
+        source_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        target_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        relation_type TEXT NOT NULL,
+        page INTEGER,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (source_id, target_id, relation_type)
+    );
+    CREATE INDEX idx_item_relations_target_id ON item_relations(target_id);
+    ",
+    // v12: リマインダー（remind_at になったら通知し、notified_at を記録する）
+    "
+    CREATE TABLE reminders (
+        id TEXT PRIMARY KEY,
+        item_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        due_at TEXT NOT NULL,
+        remind_at TEXT NOT NULL,
+        note TEXT NOT NULL DEFAULT '',
+        notified_at TEXT,
+        completed_at TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_reminders_item_id ON reminders(item_id);
+    CREATE INDEX idx_reminders_remind_at ON reminders(remind_at);
+    ",
+    // v13: 非公開アイテム（OCR テキストとメモは暗号化して sealed_text に置く）
+    "
+    ALTER TABLE items ADD COLUMN private INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE items ADD COLUMN sealed_text BLOB;
+    ",
+    // v14: 操作の記録（追記のみ）
+    "
+    CREATE TABLE activity_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        occurred_at TEXT NOT NULL,
+        action TEXT NOT NULL,
+        record_id TEXT,
+        summary TEXT NOT NULL DEFAULT '',
+        details TEXT
+    );
+    CREATE INDEX idx_activity_log_occurred_at ON activity_log(occurred_at);
+    CREATE INDEX idx_activity_log_record_id ON activity_log(record_id);
+    ",
+    // v15: 検索インデックスへの反映待ちのアイテム
+    // データの変更と同じトランザクションでトリガーが記録し、インデックスへ反映した後に消す
+    // （反映の前に落ちても、次の起動時に残っている分を反映し直す）
+    "
+    CREATE TABLE index_journal (item_id TEXT PRIMARY KEY);
+    CREATE TRIGGER index_journal_items_insert AFTER INSERT ON items BEGIN
+        INSERT OR IGNORE INTO index_journal (item_id) VALUES (NEW.id);
+    END;
+    CREATE TRIGGER index_journal_items_update AFTER UPDATE ON items BEGIN
+        INSERT OR IGNORE INTO index_journal (item_id) VALUES (NEW.id);
+    END;
+    CREATE TRIGGER index_journal_items_delete AFTER DELETE ON items BEGIN
+        INSERT OR IGNORE INTO index_journal (item_id) VALUES (OLD.id);
+    END;
+    CREATE TRIGGER index_journal_tags_insert AFTER INSERT ON item_tags BEGIN
+        INSERT OR IGNORE INTO index_journal (item_id) VALUES (NEW.item_id);
+    END;
+    CREATE TRIGGER index_journal_tags_delete AFTER DELETE ON item_tags BEGIN
+        INSERT OR IGNORE INTO index_journal (item_id) VALUES (OLD.item_id);
+    END;
+    CREATE TRIGGER index_journal_groups_update AFTER UPDATE OF title ON groups BEGIN
+        INSERT OR IGNORE INTO index_journal (item_id) SELECT id FROM items WHERE group_id = NEW.id;
+    END;
+    ",
+    // v16: OCR で読み取った単語ごとの位置と確からしさ（JSON の配列、ocr_text はこれを並べたもの）
+    "
+    CREATE TABLE ocr_words (
+        item_id TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+        words TEXT NOT NULL
+    );
+    ",
+    // v17: ユーザーによる OCR の直し（元の OCR の結果は ocr_words に残す）
+    "
+    CREATE TABLE ocr_corrections (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        item_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        region TEXT,
+        original_text TEXT NOT NULL,
+        corrected_text TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_ocr_corrections_item ON ocr_corrections(item_id);
+    ",
+    // v18: OCR のテキストから読み取ったレシートの項目（合計が読み取れたアイテムだけ、金額は円単位）
+    "
+    CREATE TABLE receipt_fields (
+        item_id TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+        vendor TEXT,
+        purchased_on TEXT,
+        total INTEGER NOT NULL,
+        subtotal INTEGER,
+        tax INTEGER,
+        tax_included INTEGER NOT NULL DEFAULT 1
+    );
+    CREATE INDEX idx_receipt_fields_total ON receipt_fields(total);
+    CREATE INDEX idx_receipt_fields_purchased_on ON receipt_fields(purchased_on);
+    ",
+    // v19: OCR のテキストから見つけた電話番号・メールアドレス・URL・郵便番号・追跡番号（value は正規化した値）
+    "
+    CREATE TABLE item_entities (
+        item_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        kind TEXT NOT NULL,
+        value TEXT NOT NULL,
+        text TEXT NOT NULL,
+        link TEXT,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (item_id, kind, value)
+    );
+    CREATE INDEX idx_item_entities_value ON item_entities(value);
+    ",
+    // v20: 取り込み時に推定した書類の種類（receipt / invoice / business_card / whiteboard / handwritten_note / other）
+    "
+    ALTER TABLE items ADD COLUMN doc_type TEXT NOT NULL DEFAULT 'other';
+    CREATE INDEX idx_items_doc_type ON items(doc_type);
+    ",
+    // v21: 端末内のモデルで生成した画像の説明文（代替テキスト・検索用）
+    "
+    ALTER TABLE items ADD COLUMN caption TEXT;
+    ",
+    // v22: OCR のテキストを訳したもの（source_hash は訳した時点の ocr_text のハッシュで、変わっていれば訳し直す）
+    "
+    CREATE TABLE item_translations (
+        item_id TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+        language TEXT NOT NULL,
+        text TEXT NOT NULL,
+        source_hash TEXT NOT NULL
+    );
+    ",
+    // v23: レシートの通貨（ISO 4217、記号がなければ JPY）
+    "
+    ALTER TABLE receipt_fields ADD COLUMN currency TEXT NOT NULL DEFAULT 'JPY';
+    ",
+    // v24: OCR のテキストから読み取った書類の日付（YYYY-MM-DD、撮影日の代わりに並べ替え・絞り込みに使う）
+    "
+    ALTER TABLE items ADD COLUMN document_date TEXT;
+    CREATE INDEX idx_items_document_date ON items(document_date);
+    ",
+    // v25: 地点からの距離での絞り込み用（エンティティに住所を加えたため、既存のアイテムから読み取り直す）
+    "
+    CREATE INDEX idx_items_coordinates ON items(latitude, longitude);
+    ",
+    // v26: 画像に写っていた QR コード（payload は種類ごとに読み取った項目の JSON）
+    "
+    CREATE TABLE item_qr_codes (
+        item_id TEXT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        raw TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        PRIMARY KEY (item_id, position)
+    );
+    CREATE INDEX idx_item_qr_codes_kind ON item_qr_codes(kind);
+    ",
+    // v27: OCR し直すジョブで読み直したアイテムと、そのときの読み方の版（中断しても続きから行う）
+    "
+    CREATE TABLE ocr_backfill (
+        item_id TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+        version INTEGER NOT NULL,
+        processed_at TEXT NOT NULL
+    );
+    ",
+    // v28: 一覧に出す見出し（取り込み時に店名や OCR の1行目から付け、ユーザーが直すこともできる）
+    "
+    ALTER TABLE items ADD COLUMN title TEXT;
+    ",
+    // v29: 保持ルールで削除の対象になったアイテム（確認期間を過ぎたらゴミ箱に移す。exempt は削除しないことにしたもの）
+    "
+    CREATE TABLE retention_reviews (
+        item_id TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+        rule_id TEXT NOT NULL,
+        flagged_at TEXT NOT NULL,
+        exempt INTEGER NOT NULL DEFAULT 0
+    );
+    ",
+    // v30: アーカイブしたアイテム（通常の一覧からは外すが、削除はしない）
+    "
+    ALTER TABLE items ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX idx_items_archived ON items(archived);
+    ",
+];
+
+const ENTITIES_VERSION: usize = 25;
+const DOCUMENT_TYPES_VERSION: usize = 20;
+const RECEIPT_CURRENCY_VERSION: usize = 23;
+const DOCUMENT_DATES_VERSION: usize = 24;
+const TITLES_VERSION: usize = 28;
+
+// 同期時にフィールド単位でマージする項目（位置情報は名前と座標をまとめて1項目とする）
+pub const ITEM_FIELDS: &[&str] = &[
+    "memo", "tags", "ocr_text", "location", "group_id", "doc_type", "caption", "document_date", "title", "archived",
+];
+pub const GROUP_FIELDS: &[&str] = &["title", "memo"];
+
+// タグは GROUP_CONCAT で1列にまとめて取得する（区切り文字は制御文字 0x1F）
+const ITEM_COLUMNS: &str = "
+    items.id, items.group_id, resolve_image_path(items.image_path) AS image_path, items.content_hash, items.ocr_text, items.memo,
+    items.location_name, items.latitude, items.longitude, items.created_at, items.updated_at, items.private,
+    items.doc_type, items.caption, items.document_date, items.title, items.archived,
+    (SELECT GROUP_CONCAT(tag, char(31)) FROM item_tags WHERE item_tags.item_id = items.id) AS tags
+";
+
+const ALBUM_COLUMNS: &str = "
+    albums.id, albums.title, albums.parent_id, albums.created_at, albums.updated_at,
+    (SELECT COUNT(*) FROM album_items a JOIN items ON items.id = a.item_id
+     WHERE a.album_id = albums.id AND items.deleted_at IS NULL) AS item_count
+";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemRecord {
+    pub id: String,
+    pub group_id: Option<String>,
+    pub image_path: Option<String>,
+    pub content_hash: Option<String>,
+    pub ocr_text: String,
+    pub memo: String,
+    pub tags: Vec<String>,
+    pub location_name: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // 非公開（画像とテキストは暗号化されている）。変更は seal_item / unseal_item で行う
+    #[serde(default)]
+    pub private: bool,
+    // 取り込み時に推定した書類の種類（ユーザーが変えることもできる）
+    #[serde(default)]
+    pub doc_type: DocumentType,
+    // 画像の内容を1文で説明したもの（モデルで生成し、ユーザーが直すこともできる）
+    #[serde(default)]
+    pub caption: Option<String>,
+    // OCR のテキストから読み取った書類の日付（発行日など、ユーザーが直すこともできる）
+    #[serde(default)]
+    pub document_date: Option<NaiveDate>,
+    // 一覧に出す見出し（ファイル名の代わり。推定したものをユーザーが直すこともできる）
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl ItemRecord {
+    pub fn field_value(&self, field: &str) -> Value {
+        match field {
+            "memo" => json!(self.memo),
+            "ocr_text" => json!(self.ocr_text),
+            "tags" => {
+                // 並び順の違いは変更とみなさない
+                let mut tags = self.tags.clone();
+                tags.sort();
+                json!(tags)
+            }
+            "location" => json!({
+                "name": self.location_name,
+                "latitude": self.latitude,
+                "longitude": self.longitude,
+            }),
+            "group_id" => json!(self.group_id),
+            "doc_type" => json!(self.doc_type),
+            "caption" => json!(self.caption),
+            "document_date" => json!(self.document_date),
+            "title" => json!(self.title),
+            "archived" => json!(self.archived),
+            _ => Value::Null,
+        }
+    }
+
+    pub fn set_field_value(&mut self, field: &str, value: Value) -> Result<()> {
+        match field {
+            "memo" => self.memo = serde_json::from_value(value)?,
+            "ocr_text" => self.ocr_text = serde_json::from_value(value)?,
+            "tags" => self.tags = serde_json::from_value(value)?,
+            "location" => {
+                self.location_name = value["name"].as_str().map(|s| s.to_string());
+                self.latitude = value["latitude"].as_f64();
+                self.longitude = value["longitude"].as_f64();
+            }
+            "group_id" => self.group_id = serde_json::from_value(value)?,
+            "doc_type" => self.doc_type = serde_json::from_value(value)?,
+            "caption" => self.caption = serde_json::from_value(value)?,
+            "document_date" => self.document_date = serde_json::from_value(value)?,
+            "title" => self.title = serde_json::from_value(value)?,
+            "archived" => self.archived = serde_json::from_value(value)?,
+            _ => bail!("Unknown item field: {}", field),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupRecord {
+    pub id: String,
+    pub title: String,
+    pub memo: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl GroupRecord {
+    pub fn field_value(&self, field: &str) -> Value {
+        match field {
+            "title" => json!(self.title),
+            "memo" => json!(self.memo),
+            _ => Value::Null,
+        }
+    }
+
+    pub fn set_field_value(&mut self, field: &str, value: Value) -> Result<()> {
+        match field {
+            "title" => self.title = serde_json::from_value(value)?,
+            "memo" => self.memo = serde_json::from_value(value)?,
+            _ => bail!("Unknown group field: {}", field),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ItemFilter {
+    pub tag: Option<String>,
+    // いずれかの種類に当てはまるもの
+    pub doc_types: Option<Vec<DocumentType>>,
+    pub group_id: Option<String>,
+    pub date_from: Option<DateTime<Utc>>,
+    pub date_to: Option<DateTime<Utc>>,
+    // アーカイブしたものだけ（true）か、していないものだけ（false）。省略時はどちらも
+    pub archived: Option<bool>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old_value: Value,
+    pub new_value: Value,
+}
+
+// 1回の更新で記録された変更（version 0 は取り込み時の状態）
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemVersion {
+    pub version: i64,
+    pub changed_at: DateTime<Utc>,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecompressedOriginal {
+    pub original_hash: String,
+    pub original_size: u64,
+    pub original_format: String,
+    pub recompressed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageFingerprint {
+    pub content_hash: String,
+    pub perceptual_hash: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+// item_count はゴミ箱のアイテムを除いた直接の所属数（子アルバムの分は含まない）
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumRecord {
+    pub id: String,
+    pub title: String,
+    pub parent_id: Option<String>,
+    pub item_count: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagInfo {
+    pub name: String,
+    pub parent: Option<String>,
+    // #rrggbb
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    // このタグが付いたアイテム数
+    pub item_count: usize,
+    // 子孫のタグを含めたアイテム数（重複は1件と数える）
+    pub total_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationType {
+    // source が target（複数ページの書類の代表）の page ページ目
+    PageOf,
+    // source は target の撮り直し・重複
+    DuplicateOf,
+    // 向きのない関連
+    RelatedTo,
+}
+
+impl RelationType {
+    fn as_str(self) -> &'static str {
+        match self {
+            RelationType::PageOf => "page_of",
+            RelationType::DuplicateOf => "duplicate_of",
+            RelationType::RelatedTo => "related_to",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "page_of" => RelationType::PageOf,
+            "duplicate_of" => RelationType::DuplicateOf,
+            _ => RelationType::RelatedTo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemRelation {
+    pub source_id: String,
+    pub target_id: String,
+    pub relation_type: RelationType,
+    pub page: Option<u32>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 詳細画面に出す関係先のアイテム（outgoing なら item_id 側が source）
+#[derive(Debug, Clone, Serialize)]
+pub struct RelatedItem {
+    #[serde(flatten)]
+    pub relation: ItemRelation,
+    pub outgoing: bool,
+    pub item: ItemRecord,
+}
+
+// 期限（due_at）とその前に知らせる日時（remind_at）
+#[derive(Debug, Clone, Serialize)]
+pub struct Reminder {
+    pub id: String,
+    pub item_id: String,
+    pub due_at: DateTime<Utc>,
+    pub remind_at: DateTime<Utc>,
+    pub note: String,
+    pub notified_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// ユーザーによる OCR の直し（region がなければ全文を直したもの）
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrCorrection {
+    pub id: i64,
+    pub item_id: String,
+    pub region: Option<OcrRegion>,
+    pub original_text: String,
+    pub corrected_text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// 操作の記録（item_created / item_updated / item_trashed / item_restored / item_deleted / item_merged、
+// backup_created / backup_restored / sync_completed / sync_failed など）
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityEntry {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub action: String,
+    pub record_id: Option<String>,
+    pub summary: String,
+    pub details: Value,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ActivityFilter {
+    pub actions: Option<Vec<String>>,
+    pub record_id: Option<String>,
+    pub date_from: Option<DateTime<Utc>>,
+    pub date_to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+// 記録に残すアイテムの見出し（見出し、なければメモか OCR の1行目）
+fn item_label(item: &ItemRecord) -> String {
+    [item.title.as_deref().unwrap_or(""), item.memo.as_str(), item.ocr_text.as_str()]
+        .into_iter()
+        .flat_map(|t| t.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.chars().take(40).collect())
+        .unwrap_or_default()
+}
+
+// 保持ルールで削除の対象になったアイテムの記録
+#[derive(Debug, Clone)]
+pub struct RetentionReview {
+    pub item_id: String,
+    pub rule_id: String,
+    pub flagged_at: DateTime<Utc>,
+    pub exempt: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedItem {
+    #[serde(flatten)]
+    pub item: ItemRecord,
+    pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncEntry {
+    pub etag: String,
+    pub local_version: String,
+    pub synced_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictSide {
+    Local,
+    Remote,
+}
+
+impl ConflictSide {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConflictSide::Local => "local",
+            ConflictSide::Remote => "remote",
+        }
+    }
+}
+
+// 両方の端末で同じフィールドが編集されていた記録（新しい方を適用済み、UI でどちらを残すか選べる）
+// field が "deleted" の場合、値は各側で削除されていたかどうか
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictRecord {
+    pub id: i64,
+    pub remote: String,
+    pub kind: String,
+    pub record_id: String,
+    pub field: String,
+    pub local_value: Value,
+    pub remote_value: Value,
+    pub applied: ConflictSide,
+    pub detected_at: DateTime<Utc>,
+}
+
+pub struct MetadataStore {
+    conn: Connection,
+    // true なら image_path をライブラリのフォルダからの相対パスで保存する（ポータブルモード）
+    relative_paths: Arc<AtomicBool>,
+}
+
+// 相対パスはライブラリのフォルダ（データベースのあるフォルダ）を基準にする
+fn resolve_image_path(root: &Path, path: &str) -> String {
+    if Path::new(path).is_absolute() {
+        path.to_string()
+    } else {
+        root.join(path).to_string_lossy().to_string()
+    }
+}
+
+// ライブラリのフォルダの外にあるファイルは絶対パスのままにする（区切りは OS によらず /）
+fn relative_image_path(root: &Path, path: &str) -> String {
+    match Path::new(path).strip_prefix(root) {
+        Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+        Err(_) => path.to_string(),
+    }
+}
+
+impl MetadataStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open metadata store: {}", path.display()))?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let relative_paths = Arc::new(AtomicBool::new(false));
+        Self::register_path_functions(&conn, root, relative_paths.clone())?;
+
+        let mut store = MetadataStore { conn, relative_paths };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    // image_path の読み書きに使う SQL 関数（読むときは絶対パスに、書くときはポータブルモードなら相対パスにする）
+    fn register_path_functions(conn: &Connection, root: PathBuf, relative: Arc<AtomicBool>) -> Result<()> {
+        let resolve_root = root.clone();
+        conn.create_scalar_function(
+            "resolve_image_path",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            move |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|p| resolve_image_path(&resolve_root, &p))),
+        )?;
+        conn.create_scalar_function("store_image_path", 1, FunctionFlags::SQLITE_UTF8, move |ctx| {
+            let path = ctx.get::<Option<String>>(0)?;
+            Ok(path.map(|p| {
+                if relative.load(Ordering::Relaxed) {
+                    relative_image_path(&root, &p)
+                } else {
+                    p
+                }
+            }))
+        })?;
+        Ok(())
+    }
+
+    // ポータブルモードの切り替え（保存済みの image_path も書き換え、書き換えた件数を返す）
+    pub fn set_relative_paths(&mut self, enabled: bool) -> Result<usize> {
+        self.relative_paths.store(enabled, Ordering::Relaxed);
+        let changed = self.conn.execute(
+            "UPDATE items SET image_path = store_image_path(resolve_image_path(image_path))
+             WHERE image_path IS NOT NULL AND image_path != store_image_path(resolve_image_path(image_path))",
+            [],
+        )?;
+        Ok(changed)
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        let version: usize = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(migration)
+                .with_context(|| format!("Migration v{} failed", i + 1))?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        // OCR のテキストから読み取る項目は SQL では求められないため、追加したときに既存のアイテムから読み取る
+        // （レシートの項目は書類の種類で読み取るかを決めるので、種類を先に推定する）
+        if version > 0 && version < DOCUMENT_TYPES_VERSION {
+            self.refresh_document_types()?;
+        }
+        if version > 0 && version < RECEIPT_CURRENCY_VERSION {
+            self.refresh_receipt_fields()?;
+        }
+        if version > 0 && version < ENTITIES_VERSION {
+            self.refresh_entities()?;
+        }
+        if version > 0 && version < DOCUMENT_DATES_VERSION {
+            self.refresh_document_dates()?;
+        }
+        if version > 0 && version < TITLES_VERSION {
+            self.refresh_titles()?;
+        }
+        Ok(())
+    }
+
+    fn row_to_item(row: &Row) -> rusqlite::Result<ItemRecord> {
+        let tags: Option<String> = row.get("tags")?;
+        Ok(ItemRecord {
+            id: row.get("id")?,
+            group_id: row.get("group_id")?,
+            image_path: row.get("image_path")?,
+            content_hash: row.get("content_hash")?,
+            ocr_text: row.get("ocr_text")?,
+            memo: row.get("memo")?,
+            tags: tags
+                .map(|t| t.split('\u{1f}').map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+            location_name: row.get("location_name")?,
+            latitude: row.get("latitude")?,
+            longitude: row.get("longitude")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+            private: row.get("private")?,
+            doc_type: DocumentType::parse(&row.get::<_, String>("doc_type")?),
+            caption: row.get("caption")?,
+            document_date: row.get("document_date")?,
+            title: row.get("title")?,
+            archived: row.get("archived")?,
+        })
+    }
+
+    fn write_tags(conn: &Connection, item_id: &str, tags: &[String]) -> Result<()> {
+        conn.execute("DELETE FROM item_tags WHERE item_id = ?1", params![item_id])?;
+        for tag in tags {
+            conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+            conn.execute(
+                "INSERT OR IGNORE INTO item_tags (item_id, tag) VALUES (?1, ?2)",
+                params![item_id, tag],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn insert_item(&mut self, item: &ItemRecord) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO items (id, group_id, image_path, content_hash, ocr_text, memo,
+                location_name, latitude, longitude, created_at, updated_at, doc_type, caption, document_date, title,
+                archived)
+             VALUES (?1, ?2, store_image_path(?3), ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                item.id,
+                item.group_id,
+                item.image_path,
+                item.content_hash,
+                item.ocr_text,
+                item.memo,
+                item.location_name,
+                item.latitude,
+                item.longitude,
+                item.created_at,
+                item.updated_at,
+                item.doc_type.as_str(),
+                item.caption,
+                item.document_date,
+                item.title,
+                item.archived,
+            ],
+        )?;
+        Self::write_tags(&tx, &item.id, &item.tags)?;
+        Self::write_receipt_fields(&tx, &item.id, &item.ocr_text, item.doc_type)?;
+        Self::write_entities(&tx, &item.id, &item.ocr_text)?;
+        let details = json!({ "tags": item.tags });
+        Self::record_activity(&tx, "item_created", Some(&item.id), &item_label(item), details)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_item(&self, id: &str) -> Result<Option<ItemRecord>> {
+        let sql = format!("SELECT {} FROM items WHERE items.id = ?1 AND items.deleted_at IS NULL", ITEM_COLUMNS);
+        Ok(self
+            .conn
+            .query_row(&sql, params![id], Self::row_to_item)
+            .optional()?)
+    }
+
+    pub fn find_by_hash(&self, content_hash: &str) -> Result<Option<ItemRecord>> {
+        // 再圧縮したアイテムは元のファイルのハッシュでも一致させる
+        let sql = format!(
+            "SELECT {} FROM items WHERE items.deleted_at IS NULL AND (items.content_hash = ?1
+                OR items.id IN (SELECT item_id FROM recompressed_originals WHERE original_hash = ?1))
+             LIMIT 1",
+            ITEM_COLUMNS
+        );
+        Ok(self
+            .conn
+            .query_row(&sql, params![content_hash], Self::row_to_item)
+            .optional()?)
+    }
+
+    // タイムスタンプも含めてそのまま書き込む（バックアップ復元・同期用、履歴は残さない）
+    // ゴミ箱にあるアイテムは元に戻る。非公開アイテムは暗号化したテキストも一緒に渡す
+    pub fn put_item(&mut self, item: &ItemRecord, sealed_text: Option<&[u8]>) -> Result<()> {
+        if item.private && sealed_text.is_none() {
+            bail!("Private item {} has no sealed text", item.id);
+        }
+        let sealed_text = if item.private { sealed_text } else { None };
+        let tx = self.conn.transaction()?;
+        // INSERT OR REPLACE だと関連テーブルが CASCADE で消えるため UPSERT を使う
+        tx.execute(
+            "INSERT INTO items (id, group_id, image_path, content_hash, ocr_text, memo,
+                location_name, latitude, longitude, created_at, updated_at, doc_type, caption, document_date, title,
+                archived, private, sealed_text)
+             VALUES (?1, ?2, store_image_path(?3), ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+             ON CONFLICT(id) DO UPDATE SET group_id = excluded.group_id,
+                image_path = excluded.image_path, content_hash = excluded.content_hash,
+                ocr_text = excluded.ocr_text, memo = excluded.memo,
+                location_name = excluded.location_name, latitude = excluded.latitude,
+                longitude = excluded.longitude, created_at = excluded.created_at,
+                updated_at = excluded.updated_at, doc_type = excluded.doc_type,
+                caption = excluded.caption, document_date = excluded.document_date, title = excluded.title,
+                archived = excluded.archived, private = excluded.private, sealed_text = excluded.sealed_text,
+                deleted_at = NULL",
+            params![
+                item.id,
+                item.group_id,
+                item.image_path,
+                item.content_hash,
+                item.ocr_text,
+                item.memo,
+                item.location_name,
+                item.latitude,
+                item.longitude,
+                item.created_at,
+                item.updated_at,
+                item.doc_type.as_str(),
+                item.caption,
+                item.document_date,
+                item.title,
+                item.archived,
+                item.private,
+                sealed_text,
+            ],
+        )?;
+        Self::write_tags(&tx, &item.id, &item.tags)?;
+        Self::write_receipt_fields(&tx, &item.id, &item.ocr_text, item.doc_type)?;
+        Self::write_entities(&tx, &item.id, &item.ocr_text)?;
+        // 同期やバックアップの統合で書き込まれたもの
+        Self::record_activity(&tx, "item_merged", Some(&item.id), &item_label(item), Value::Null)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 画像ファイルの置き場所が変わったとき（別PCへの復元など）にパスを付け替える
+    pub fn relocate_images(&mut self, images_dir: &Path) -> Result<usize> {
+        // ゴミ箱のアイテムも対象にする
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, resolve_image_path(image_path) FROM items WHERE image_path IS NOT NULL")?;
+        let items = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+
+        let tx = self.conn.transaction()?;
+        let mut relocated = 0;
+        for (id, image_path) in items {
+            let file_name = match Path::new(&image_path).file_name() {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            let new_path = images_dir.join(file_name).to_string_lossy().to_string();
+            if image_path != new_path {
+                tx.execute(
+                    "UPDATE items SET image_path = store_image_path(?2) WHERE id = ?1",
+                    params![id, new_path],
+                )?;
+                relocated += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(relocated)
+    }
+
+    // 変更されたフィールドを編集履歴に記録してから更新する
+    pub fn update_item(&mut self, item: &ItemRecord) -> Result<ItemRecord> {
+        Ok(self.update_items(std::slice::from_ref(item))?.remove(0))
+    }
+
+    // 複数のアイテムを1つのトランザクションで更新する（途中で失敗したらすべて元に戻る）
+    pub fn update_items(&mut self, items: &[ItemRecord]) -> Result<Vec<ItemRecord>> {
+        let mut changes = Vec::new();
+        for item in items {
+            let existing = self
+                .get_item(&item.id)?
+                .with_context(|| format!("Item not found: {}", item.id))?;
+            let mut updated = item.clone();
+            updated.created_at = existing.created_at;
+            updated.updated_at = Utc::now();
+            changes.push((existing, updated));
+        }
+
+        let tx = self.conn.transaction()?;
+        for (existing, updated) in &changes {
+            Self::write_update(&tx, existing, updated)?;
+        }
+        tx.commit()?;
+        Ok(changes.into_iter().map(|(_, updated)| updated).collect())
+    }
+
+    // 編集履歴・フィールドの更新日時を記録してアイテムを書き換える
+    fn write_update(conn: &Connection, existing: &ItemRecord, updated: &ItemRecord) -> Result<()> {
+        let version: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(version), 0) + 1 FROM edit_history WHERE item_id = ?1",
+            params![updated.id],
+            |row| row.get(0),
+        )?;
+        let changes = diff_fields(existing, updated);
+        if !changes.is_empty() {
+            // 値は編集履歴にあるため、ここでは変更したフィールドだけ残す
+            let fields: Vec<&str> = changes.iter().map(|(field, _, _)| *field).collect();
+            let details = json!({ "fields": fields });
+            Self::record_activity(conn, "item_updated", Some(&updated.id), &item_label(updated), details)?;
+        }
+        for (field, old, new) in changes {
+            conn.execute(
+                "INSERT INTO edit_history (item_id, field, old_value, new_value, changed_at, version)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![updated.id, field, old, new, updated.updated_at, version],
+            )?;
+        }
+        conn.execute(
+            "UPDATE items SET group_id = ?2, image_path = store_image_path(?3), content_hash = ?4, ocr_text = ?5,
+                memo = ?6, location_name = ?7, latitude = ?8, longitude = ?9, updated_at = ?10, doc_type = ?11,
+                caption = ?12, document_date = ?13, title = ?14, archived = ?15
+             WHERE id = ?1",
+            params![
+                updated.id,
+                updated.group_id,
+                updated.image_path,
+                updated.content_hash,
+                updated.ocr_text,
+                updated.memo,
+                updated.location_name,
+                updated.latitude,
+                updated.longitude,
+                updated.updated_at,
+                updated.doc_type.as_str(),
+                updated.caption,
+                updated.document_date,
+                updated.title,
+                updated.archived,
+            ],
+        )?;
+        Self::write_tags(conn, &updated.id, &updated.tags)?;
+        if existing.ocr_text != updated.ocr_text || existing.doc_type != updated.doc_type {
+            Self::write_receipt_fields(conn, &updated.id, &updated.ocr_text, updated.doc_type)?;
+        }
+        if existing.ocr_text != updated.ocr_text {
+            Self::write_entities(conn, &updated.id, &updated.ocr_text)?;
+        }
+        for field in ITEM_FIELDS {
+            if existing.field_value(field) != updated.field_value(field) {
+                Self::write_field_version(conn, &updated.id, field, &updated.updated_at)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 新しい版から順に返す
+    pub fn item_history(&self, id: &str) -> Result<Vec<ItemVersion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT version, changed_at, field, old_value, new_value FROM edit_history
+             WHERE item_id = ?1 ORDER BY version DESC, id",
+        )?;
+        let rows = stmt
+            .query_map(params![id], |row| {
+                let field: String = row.get(2)?;
+                let change = FieldChange {
+                    old_value: history_value(&field, row.get(3)?),
+                    new_value: history_value(&field, row.get(4)?),
+                    field,
+                };
+                Ok((row.get::<_, i64>(0)?, row.get::<_, DateTime<Utc>>(1)?, change))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut versions: Vec<ItemVersion> = Vec::new();
+        for (version, changed_at, change) in rows {
+            match versions.last_mut() {
+                Some(last) if last.version == version => last.changes.push(change),
+                _ => versions.push(ItemVersion {
+                    version,
+                    changed_at,
+                    changes: vec![change],
+                }),
+            }
+        }
+        Ok(versions)
+    }
+
+    // 指定した版の時点の内容に戻す（それより後の変更を新しい順に打ち消す）
+    // 戻した操作も新しい版として記録されるため、さらに元に戻すこともできる
+    pub fn revert_item(&mut self, id: &str, version: i64) -> Result<ItemRecord> {
+        let mut item = self.get_item(id)?.with_context(|| format!("Item not found: {}", id))?;
+        let mut stmt = self.conn.prepare(
+            "SELECT field, old_value FROM edit_history
+             WHERE item_id = ?1 AND version > ?2 ORDER BY version DESC, id DESC",
+        )?;
+        let changes = stmt
+            .query_map(params![id, version], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+
+        for (field, old_value) in changes {
+            match field.as_str() {
+                "memo" => item.memo = old_value.unwrap_or_default(),
+                "ocr_text" => item.ocr_text = old_value.unwrap_or_default(),
+                // 以前の版では位置情報の名前だけを記録していた
+                "location_name" => item.location_name = old_value,
+                "group_id" => item.group_id = old_value,
+                field => item.set_field_value(field, history_value(field, old_value))?,
+            }
+        }
+        // 削除済みのグループには戻せない
+        if let Some(group_id) = item.group_id.clone() {
+            if self.get_group(&group_id)?.is_none() {
+                item.group_id = None;
+            }
+        }
+        self.update_item(&item)
+    }
+
+    // 完全に削除する（通常の削除はゴミ箱へ移す trash_item を使う）
+    pub fn delete_item(&mut self, id: &str) -> Result<bool> {
+        let tx = self.conn.transaction()?;
+        let deleted = tx.execute("DELETE FROM items WHERE id = ?1", params![id])?;
+        Self::forget_record(&tx, id)?;
+        if deleted > 0 {
+            Self::record_activity(&tx, "item_deleted", Some(id), "", Value::Null)?;
+        }
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    pub fn trash_item(&mut self, id: &str) -> Result<bool> {
+        let trashed = self.conn.execute(
+            "UPDATE items SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+            params![id, Utc::now()],
+        )?;
+        if trashed > 0 {
+            Self::record_activity(&self.conn, "item_trashed", Some(id), "", Value::Null)?;
+        }
+        Ok(trashed > 0)
+    }
+
+    // ゴミ箱から戻す（同期で相手側にも戻るよう updated_at を更新する）
+    pub fn restore_item(&mut self, id: &str) -> Result<Option<ItemRecord>> {
+        let restored = self.conn.execute(
+            "UPDATE items SET deleted_at = NULL, updated_at = ?2 WHERE id = ?1 AND deleted_at IS NOT NULL",
+            params![id, Utc::now()],
+        )?;
+        if restored == 0 {
+            return Ok(None);
+        }
+        Self::record_activity(&self.conn, "item_restored", Some(id), "", Value::Null)?;
+        self.get_item(id)
+    }
+
+    pub fn list_trash(&self) -> Result<Vec<TrashedItem>> {
+        let sql = format!(
+            "SELECT {}, items.deleted_at FROM items WHERE items.deleted_at IS NOT NULL ORDER BY items.deleted_at DESC",
+            ITEM_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let items = stmt
+            .query_map([], |row| {
+                Ok(TrashedItem {
+                    item: Self::row_to_item(row)?,
+                    deleted_at: row.get("deleted_at")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    }
+
+    pub fn trashed_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM items WHERE deleted_at IS NOT NULL AND deleted_at < ?1")?;
+        let ids = stmt
+            .query_map(params![cutoff], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(ids)
+    }
+
+    // ゴミ箱にあるアイテムだけを完全に削除し、削除したレコードを返す（画像の後始末用）
+    pub fn purge_item(&mut self, id: &str) -> Result<Option<ItemRecord>> {
+        let sql = format!(
+            "SELECT {} FROM items WHERE items.id = ?1 AND items.deleted_at IS NOT NULL",
+            ITEM_COLUMNS
+        );
+        let item = self.conn.query_row(&sql, params![id], Self::row_to_item).optional()?;
+        if item.is_some() {
+            self.delete_item(id)?;
+        }
+        Ok(item)
+    }
+
+    // 非公開にする: 平文のテキストと編集履歴を消し、暗号化したテキストと画像のパスに置き換える
+    pub fn seal_item(&mut self, id: &str, sealed_text: &[u8], image_path: Option<&str>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "UPDATE items SET private = 1, sealed_text = ?2, ocr_text = '', memo = '', caption = NULL, title = NULL,
+                document_date = NULL, image_path = store_image_path(?3), updated_at = ?4
+             WHERE id = ?1",
+            params![id, sealed_text, image_path, Utc::now()],
+        )?;
+        tx.execute("DELETE FROM edit_history WHERE item_id = ?1", params![id])?;
+        tx.execute("DELETE FROM ocr_words WHERE item_id = ?1", params![id])?;
+        tx.execute("DELETE FROM ocr_corrections WHERE item_id = ?1", params![id])?;
+        tx.execute("DELETE FROM receipt_fields WHERE item_id = ?1", params![id])?;
+        tx.execute("DELETE FROM item_entities WHERE item_id = ?1", params![id])?;
+        tx.execute("DELETE FROM item_translations WHERE item_id = ?1", params![id])?;
+        tx.execute("DELETE FROM item_qr_codes WHERE item_id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn unseal_item(&mut self, id: &str, ocr_text: &str, memo: &str, image_path: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE items SET private = 0, sealed_text = NULL, ocr_text = ?2, memo = ?3,
+                image_path = store_image_path(?4), updated_at = ?5, document_date = ?6
+             WHERE id = ?1",
+            params![id, ocr_text, memo, image_path, Utc::now(), document_dates::parse(ocr_text)],
+        )?;
+        let doc_type: String = self.conn.query_row("SELECT doc_type FROM items WHERE id = ?1", params![id], |row| row.get(0))?;
+        let doc_type = DocumentType::parse(&doc_type);
+        Self::write_receipt_fields(&self.conn, id, ocr_text, doc_type)?;
+        Self::write_entities(&self.conn, id, ocr_text)?;
+        // 見出しは非公開にしたときに消しているので付け直す
+        self.conn.execute(
+            "UPDATE items SET title = ?2 WHERE id = ?1 AND title IS NULL",
+            params![id, titles::generate(ocr_text, doc_type, None)],
+        )?;
+        Ok(())
+    }
+
+    pub fn sealed_text(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .conn
+            .query_row("SELECT sealed_text FROM items WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?
+            .flatten())
+    }
+
+    pub fn set_sealed_text(&mut self, id: &str, sealed_text: &[u8]) -> Result<()> {
+        self.conn.execute(
+            "UPDATE items SET sealed_text = ?2 WHERE id = ?1 AND private = 1",
+            params![id, sealed_text],
+        )?;
+        Ok(())
+    }
+
+    pub fn private_item_ids(&self) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM items WHERE private = 1 AND deleted_at IS NULL")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(ids)
+    }
+
+    // 同じ画像を参照しているアイテムが残っているか（ゴミ箱のアイテムも含む）
+    pub fn is_image_referenced(&self, image_path: &str) -> Result<bool> {
+        Ok(self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM items WHERE resolve_image_path(image_path) = ?1)",
+            params![image_path],
+            |row| row.get(0),
+        )?)
+    }
+
+    // ゴミ箱を含むすべてのアイテムが参照している元画像のパスと内容のハッシュ
+    pub fn image_references(&self) -> Result<(HashSet<String>, HashSet<String>)> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT resolve_image_path(image_path), content_hash FROM items")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut paths = HashSet::new();
+        let mut hashes = HashSet::new();
+        for (path, hash) in rows {
+            paths.extend(path);
+            hashes.extend(hash);
+        }
+        Ok((paths, hashes))
+    }
+
+    // 削除したレコードのフィールド更新日時と未解決の衝突を片付ける
+    fn forget_record(conn: &Connection, record_id: &str) -> Result<()> {
+        conn.execute("DELETE FROM field_versions WHERE record_id = ?1", params![record_id])?;
+        conn.execute("DELETE FROM sync_conflicts WHERE record_id = ?1", params![record_id])?;
+        Ok(())
+    }
+
+    pub fn list_items(&self, filter: &ItemFilter) -> Result<Vec<ItemRecord>> {
+        let mut conditions: Vec<&str> = vec!["items.deleted_at IS NULL"];
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+        if let Some(tag) = &filter.tag {
+            conditions.push("EXISTS (SELECT 1 FROM item_tags t WHERE t.item_id = items.id AND t.tag = ?)");
+            values.push(Box::new(tag.clone()));
+        }
+        if let Some(group_id) = &filter.group_id {
+            conditions.push("items.group_id = ?");
+            values.push(Box::new(group_id.clone()));
+        }
+        let doc_type_condition;
+        if let Some(doc_types) = filter.doc_types.as_ref().filter(|t| !t.is_empty()) {
+            doc_type_condition = format!("items.doc_type IN ({})", vec!["?"; doc_types.len()].join(", "));
+            conditions.push(&doc_type_condition);
+            for doc_type in doc_types {
+                values.push(Box::new(doc_type.as_str()));
+            }
+        }
+        if let Some(date_from) = filter.date_from {
+            conditions.push("items.created_at >= ?");
+            values.push(Box::new(date_from));
+        }
+        if let Some(date_to) = filter.date_to {
+            conditions.push("items.created_at <= ?");
+            values.push(Box::new(date_to));
+        }
+        if let Some(archived) = filter.archived {
+            conditions.push("items.archived = ?");
+            values.push(Box::new(archived));
+        }
+
+        let mut sql = format!("SELECT {} FROM items", ITEM_COLUMNS);
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+        sql.push_str(" ORDER BY items.created_at DESC");
+        sql.push_str(&format!(
+            " LIMIT {} OFFSET {}",
+            filter.limit.map(|l| l as i64).unwrap_or(-1),
+            filter.offset.unwrap_or(0)
+        ));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let items = stmt
+            .query_map(params_from_iter(values.iter()), Self::row_to_item)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    }
+
+    pub fn count_items(&self) -> Result<usize> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM items WHERE deleted_at IS NULL", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn save_group(&mut self, group: &GroupRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO groups (id, title, memo, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(id) DO UPDATE SET title = excluded.title, memo = excluded.memo,
+                updated_at = excluded.updated_at",
+            params![group.id, group.title, group.memo, group.created_at, group.updated_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_group(&self, id: &str) -> Result<Option<GroupRecord>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, title, memo, created_at, updated_at FROM groups WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(GroupRecord {
+                        id: row.get(0)?,
+                        title: row.get(1)?,
+                        memo: row.get(2)?,
+                        created_at: row.get(3)?,
+                        updated_at: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    pub fn list_groups(&self) -> Result<Vec<GroupRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, title, memo, created_at, updated_at FROM groups ORDER BY created_at DESC")?;
+        let groups = stmt
+            .query_map([], |row| {
+                Ok(GroupRecord {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    memo: row.get(2)?,
+                    created_at: row.get(3)?,
+                    updated_at: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(groups)
+    }
+
+    // グループを削除（所属アイテムは group_id が NULL になる）
+    pub fn delete_group(&mut self, id: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM items WHERE group_id = ?1")?;
+        let member_ids = stmt
+            .query_map(params![id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        drop(stmt);
+        self.conn.execute("DELETE FROM groups WHERE id = ?1", params![id])?;
+        Self::forget_record(&self.conn, id)?;
+        Ok(member_ids)
+    }
+
+    fn row_to_album(row: &Row) -> rusqlite::Result<AlbumRecord> {
+        Ok(AlbumRecord {
+            id: row.get("id")?,
+            title: row.get("title")?,
+            parent_id: row.get("parent_id")?,
+            item_count: row.get::<_, i64>("item_count")? as usize,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+
+    pub fn get_album(&self, id: &str) -> Result<Option<AlbumRecord>> {
+        let sql = format!("SELECT {} FROM albums WHERE albums.id = ?1", ALBUM_COLUMNS);
+        Ok(self.conn.query_row(&sql, params![id], Self::row_to_album).optional()?)
+    }
+
+    pub fn list_albums(&self) -> Result<Vec<AlbumRecord>> {
+        let sql = format!("SELECT {} FROM albums ORDER BY albums.title COLLATE NOCASE", ALBUM_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let albums = stmt
+            .query_map([], Self::row_to_album)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(albums)
+    }
+
+    // parent_id を親にすると循環するか（自分自身や子孫の下には移せない）
+    fn would_cycle(&self, id: &str, parent_id: &str) -> Result<bool> {
+        let mut current = Some(parent_id.to_string());
+        while let Some(album_id) = current {
+            if album_id == id {
+                return Ok(true);
+            }
+            current = self
+                .conn
+                .query_row("SELECT parent_id FROM albums WHERE id = ?1", params![album_id], |row| row.get(0))
+                .optional()?
+                .flatten();
+        }
+        Ok(false)
+    }
+
+    pub fn create_album(&mut self, title: &str, parent_id: Option<&str>) -> Result<AlbumRecord> {
+        if let Some(parent_id) = parent_id {
+            if self.get_album(parent_id)?.is_none() {
+                bail!("Album not found: {}", parent_id);
+            }
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+        self.conn.execute(
+            "INSERT INTO albums (id, title, parent_id, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![id, title.trim(), parent_id, now],
+        )?;
+        self.get_album(&id)?.context("Failed to create album")
+    }
+
+    pub fn rename_album(&mut self, id: &str, title: &str) -> Result<Option<AlbumRecord>> {
+        self.conn.execute(
+            "UPDATE albums SET title = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, title.trim(), Utc::now()],
+        )?;
+        self.get_album(id)
+    }
+
+    // 別のアルバムの下へ移す（None なら最上位にする）
+    pub fn move_album(&mut self, id: &str, parent_id: Option<&str>) -> Result<Option<AlbumRecord>> {
+        if let Some(parent_id) = parent_id {
+            if self.get_album(parent_id)?.is_none() {
+                bail!("Album not found: {}", parent_id);
+            }
+            if self.would_cycle(id, parent_id)? {
+                bail!("Cannot move an album into itself or its descendants");
+            }
+        }
+        self.conn.execute(
+            "UPDATE albums SET parent_id = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, parent_id, Utc::now()],
+        )?;
+        self.get_album(id)
+    }
+
+    // アルバムを削除する（アイテムは残り、子アルバムは削除したアルバムの親へ移る）
+    pub fn delete_album(&mut self, id: &str) -> Result<bool> {
+        let tx = self.conn.transaction()?;
+        let parent_id: Option<Option<String>> = tx
+            .query_row("SELECT parent_id FROM albums WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        let parent_id = match parent_id {
+            Some(parent_id) => parent_id,
+            None => return Ok(false),
+        };
+        tx.execute("UPDATE albums SET parent_id = ?2 WHERE parent_id = ?1", params![id, parent_id])?;
+        tx.execute("DELETE FROM albums WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    // アイテムを追加する（position を省略すると末尾、既に入っているものはそのまま）
+    pub fn add_album_items(&mut self, album_id: &str, item_ids: &[String], position: Option<usize>) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let exists: bool =
+            tx.query_row("SELECT EXISTS(SELECT 1 FROM albums WHERE id = ?1)", params![album_id], |row| row.get(0))?;
+        if !exists {
+            bail!("Album not found: {}", album_id);
+        }
+        let start = match position {
+            Some(position) => {
+                tx.execute(
+                    "UPDATE album_items SET position = position + ?2 WHERE album_id = ?1 AND position >= ?3",
+                    params![album_id, item_ids.len() as i64, position as i64],
+                )?;
+                position as i64
+            }
+            None => tx.query_row(
+                "SELECT COALESCE(MAX(position) + 1, 0) FROM album_items WHERE album_id = ?1",
+                params![album_id],
+                |row| row.get(0),
+            )?,
+        };
+        let now = Utc::now();
+        let mut added = 0;
+        for (i, item_id) in item_ids.iter().enumerate() {
+            added += tx.execute(
+                "INSERT OR IGNORE INTO album_items (album_id, item_id, position, added_at)
+                 SELECT ?1, id, ?3, ?4 FROM items WHERE id = ?2",
+                params![album_id, item_id, start + i as i64, now],
+            )?;
+        }
+        tx.execute("UPDATE albums SET updated_at = ?2 WHERE id = ?1", params![album_id, now])?;
+        tx.commit()?;
+        Ok(added)
+    }
+
+    pub fn remove_album_items(&mut self, album_id: &str, item_ids: &[String]) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut removed = 0;
+        for item_id in item_ids {
+            removed += tx.execute(
+                "DELETE FROM album_items WHERE album_id = ?1 AND item_id = ?2",
+                params![album_id, item_id],
+            )?;
+        }
+        tx.execute("UPDATE albums SET updated_at = ?2 WHERE id = ?1", params![album_id, Utc::now()])?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    // 指定した順に並べ替える（指定されなかったアイテムは元の順でその後ろに続く）
+    pub fn reorder_album_items(&mut self, album_id: &str, item_ids: &[String]) -> Result<()> {
+        let current = self.album_item_ids(album_id)?;
+        let mut ordered: Vec<&String> = item_ids.iter().filter(|id| current.contains(id)).collect();
+        ordered.extend(current.iter().filter(|id| !item_ids.contains(id)));
+
+        let tx = self.conn.transaction()?;
+        for (position, item_id) in ordered.iter().enumerate() {
+            tx.execute(
+                "UPDATE album_items SET position = ?3 WHERE album_id = ?1 AND item_id = ?2",
+                params![album_id, item_id, position as i64],
+            )?;
+        }
+        tx.execute("UPDATE albums SET updated_at = ?2 WHERE id = ?1", params![album_id, Utc::now()])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // アルバム内のアイテムID（並び順、ゴミ箱のアイテムは除く）
+    pub fn album_item_ids(&self, album_id: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.item_id FROM album_items a JOIN items ON items.id = a.item_id
+             WHERE a.album_id = ?1 AND items.deleted_at IS NULL ORDER BY a.position",
+        )?;
+        let ids = stmt
+            .query_map(params![album_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(ids)
+    }
+
+    pub fn album_items(&self, album_id: &str) -> Result<Vec<ItemRecord>> {
+        let sql = format!(
+            "SELECT {} FROM album_items a JOIN items ON items.id = a.item_id
+             WHERE a.album_id = ?1 AND items.deleted_at IS NULL ORDER BY a.position",
+            ITEM_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let items = stmt
+            .query_map(params![album_id], Self::row_to_item)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    }
+
+    // 名前（大文字小文字を区別しない）または ID に一致するアルバムと、その子孫のアイテムID
+    pub fn album_filter_ids(&self, name_or_id: &str) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "WITH RECURSIVE tree(id) AS (
+                SELECT id FROM albums WHERE id = ?1 OR title = ?1 COLLATE NOCASE
+                UNION SELECT albums.id FROM albums JOIN tree ON albums.parent_id = tree.id
+             )
+             SELECT DISTINCT item_id FROM album_items WHERE album_id IN (SELECT id FROM tree)",
+        )?;
+        let ids = stmt
+            .query_map(params![name_or_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(ids)
+    }
+
+    fn row_to_relation(row: &Row) -> rusqlite::Result<ItemRelation> {
+        let relation_type: String = row.get("relation_type")?;
+        Ok(ItemRelation {
+            source_id: row.get("source_id")?,
+            target_id: row.get("target_id")?,
+            relation_type: RelationType::parse(&relation_type),
+            page: row.get::<_, Option<i64>>("page")?.map(|p| p as u32),
+            created_at: row.get("created_at")?,
+        })
+    }
+
+    // source と target を関係づける（同じ関係が既にあればページ番号だけ更新する）
+    pub fn link_items(
+        &mut self,
+        source_id: &str,
+        target_id: &str,
+        relation_type: RelationType,
+        page: Option<u32>,
+    ) -> Result<ItemRelation> {
+        if source_id == target_id {
+            bail!("Cannot link an item to itself");
+        }
+        for id in [source_id, target_id] {
+            if self.get_item(id)?.is_none() {
+                bail!("Item not found: {}", id);
+            }
+        }
+        // 関連は向きを持たないので、逆向きで登録済みならそれを返す
+        let (source_id, target_id) = match relation_type {
+            RelationType::RelatedTo if source_id > target_id => (target_id, source_id),
+            _ => (source_id, target_id),
+        };
+        let page = match relation_type {
+            RelationType::PageOf => Some(page.unwrap_or(1).max(1)),
+            _ => None,
+        };
+        let tx = self.conn.transaction()?;
+        // 1つのページが属する書類は1つだけ
+        if relation_type == RelationType::PageOf {
+            tx.execute(
+                "DELETE FROM item_relations WHERE source_id = ?1 AND relation_type = ?2 AND target_id <> ?3",
+                params![source_id, relation_type.as_str(), target_id],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO item_relations (source_id, target_id, relation_type, page, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(source_id, target_id, relation_type) DO UPDATE SET page = excluded.page",
+            params![source_id, target_id, relation_type.as_str(), page, Utc::now()],
+        )?;
+        let relation = tx.query_row(
+            "SELECT * FROM item_relations WHERE source_id = ?1 AND target_id = ?2 AND relation_type = ?3",
+            params![source_id, target_id, relation_type.as_str()],
+            Self::row_to_relation,
+        )?;
+        tx.commit()?;
+        Ok(relation)
+    }
+
+    pub fn unlink_items(&mut self, source_id: &str, target_id: &str, relation_type: RelationType) -> Result<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM item_relations
+             WHERE relation_type = ?3
+               AND ((source_id = ?1 AND target_id = ?2) OR (?3 = 'related_to' AND source_id = ?2 AND target_id = ?1))",
+            params![source_id, target_id, relation_type.as_str()],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // アイテムに関係するアイテム（ページ順、ゴミ箱のアイテムは除く）
+    pub fn item_relations(&self, item_id: &str) -> Result<Vec<RelatedItem>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM item_relations WHERE source_id = ?1 OR target_id = ?1
+             ORDER BY relation_type, page, created_at",
+        )?;
+        let relations = stmt
+            .query_map(params![item_id], Self::row_to_relation)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut related = Vec::new();
+        for relation in relations {
+            let outgoing = relation.source_id == item_id;
+            let other_id = if outgoing { &relation.target_id } else { &relation.source_id };
+            if let Some(item) = self.get_item(other_id)? {
+                related.push(RelatedItem { relation, outgoing, item });
+            }
+        }
+        Ok(related)
+    }
+
+    fn row_to_reminder(row: &Row) -> rusqlite::Result<Reminder> {
+        Ok(Reminder {
+            id: row.get("id")?,
+            item_id: row.get("item_id")?,
+            due_at: row.get("due_at")?,
+            remind_at: row.get("remind_at")?,
+            note: row.get("note")?,
+            notified_at: row.get("notified_at")?,
+            completed_at: row.get("completed_at")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+
+    pub fn get_reminder(&self, id: &str) -> Result<Option<Reminder>> {
+        Ok(self
+            .conn
+            .query_row("SELECT * FROM reminders WHERE id = ?1", params![id], Self::row_to_reminder)
+            .optional()?)
+    }
+
+    // remind_at を省略すると期限の日時に知らせる
+    pub fn add_reminder(
+        &mut self,
+        item_id: &str,
+        due_at: DateTime<Utc>,
+        remind_at: Option<DateTime<Utc>>,
+        note: &str,
+    ) -> Result<Reminder> {
+        if self.get_item(item_id)?.is_none() {
+            bail!("Item not found: {}", item_id);
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        self.conn.execute(
+            "INSERT INTO reminders (id, item_id, due_at, remind_at, note, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, item_id, due_at, remind_at.unwrap_or(due_at), note.trim(), Utc::now()],
+        )?;
+        self.get_reminder(&id)?.context("Failed to create reminder")
+    }
+
+    // 日時を変えたら通知し直す
+    pub fn update_reminder(
+        &mut self,
+        id: &str,
+        due_at: DateTime<Utc>,
+        remind_at: Option<DateTime<Utc>>,
+        note: &str,
+    ) -> Result<Option<Reminder>> {
+        self.conn.execute(
+            "UPDATE reminders
+             SET notified_at = CASE WHEN remind_at = ?3 THEN notified_at END,
+                 due_at = ?2, remind_at = ?3, note = ?4
+             WHERE id = ?1",
+            params![id, due_at, remind_at.unwrap_or(due_at), note.trim()],
+        )?;
+        self.get_reminder(id)
+    }
+
+    pub fn set_reminder_completed(&mut self, id: &str, completed: bool) -> Result<Option<Reminder>> {
+        let completed_at = completed.then(Utc::now);
+        self.conn.execute(
+            "UPDATE reminders SET completed_at = ?2 WHERE id = ?1",
+            params![id, completed_at],
+        )?;
+        self.get_reminder(id)
+    }
+
+    pub fn delete_reminder(&mut self, id: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM reminders WHERE id = ?1", params![id])? > 0)
+    }
+
+    // item_id を省略すると全アイテムのリマインダー（期限の近い順、ゴミ箱のアイテムは除く）
+    pub fn list_reminders(&self, item_id: Option<&str>, include_completed: bool) -> Result<Vec<Reminder>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT reminders.* FROM reminders JOIN items ON items.id = reminders.item_id
+             WHERE items.deleted_at IS NULL
+               AND (?1 IS NULL OR reminders.item_id = ?1)
+               AND (?2 OR reminders.completed_at IS NULL)
+             ORDER BY reminders.due_at",
+        )?;
+        let reminders = stmt
+            .query_map(params![item_id, include_completed], Self::row_to_reminder)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(reminders)
+    }
+
+    // 通知時刻を過ぎてまだ知らせていないもの
+    pub fn due_reminders(&self, now: DateTime<Utc>) -> Result<Vec<Reminder>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT reminders.* FROM reminders JOIN items ON items.id = reminders.item_id
+             WHERE items.deleted_at IS NULL
+               AND reminders.notified_at IS NULL AND reminders.completed_at IS NULL
+               AND reminders.remind_at <= ?1
+             ORDER BY reminders.remind_at",
+        )?;
+        let reminders = stmt
+            .query_map(params![now], Self::row_to_reminder)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(reminders)
+    }
+
+    pub fn mark_reminder_notified(&mut self, id: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE reminders SET notified_at = ?2 WHERE id = ?1",
+            params![id, Utc::now()],
+        )?;
+        Ok(())
+    }
+
+    fn record_activity(
+        conn: &Connection,
+        action: &str,
+        record_id: Option<&str>,
+        summary: &str,
+        details: Value,
+    ) -> Result<()> {
+        let details = (!details.is_null()).then(|| details.to_string());
+        conn.execute(
+            "INSERT INTO activity_log (occurred_at, action, record_id, summary, details) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![Utc::now(), action, record_id, summary, details],
+        )?;
+        Ok(())
+    }
+
+    // バックアップや同期など、アイテム以外の操作を記録する
+    pub fn log_activity(&mut self, action: &str, record_id: Option<&str>, summary: &str, details: Value) -> Result<()> {
+        Self::record_activity(&self.conn, action, record_id, summary, details)
+    }
+
+    // 画像の上で単語を選んでコピーしたり、検索語の位置を囲んだりするための単語の位置（空なら消す）
+    pub fn set_ocr_words(&self, item_id: &str, words: &[OcrWord]) -> Result<()> {
+        if words.is_empty() {
+            self.conn.execute("DELETE FROM ocr_words WHERE item_id = ?1", params![item_id])?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO ocr_words (item_id, words) VALUES (?1, ?2)",
+                params![item_id, serde_json::to_string(words)?],
+            )?;
+        }
+        Ok(())
+    }
+
+    // OCR のテキストを訳したものを保存する（訳した元のテキストのハッシュと一緒に残す）
+    pub fn set_translation(&self, item_id: &str, ocr_text: &str, translation: &Translation) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO item_translations (item_id, language, text, source_hash) VALUES (?1, ?2, ?3, ?4)",
+            params![item_id, translation.language, translation.text, hashing::content_hash(ocr_text.as_bytes())],
+        )?;
+        Ok(())
+    }
+
+    // 今の OCR のテキストを訳したもの（訳した後に OCR のテキストが変わっていれば None）
+    pub fn translation(&self, item: &ItemRecord) -> Result<Option<Translation>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT language, text FROM item_translations WHERE item_id = ?1 AND source_hash = ?2",
+                params![item.id, hashing::content_hash(item.ocr_text.as_bytes())],
+                |row| {
+                    Ok(Translation {
+                        language: row.get(0)?,
+                        text: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    // 画像から読み取った QR コードを置き換える
+    pub fn set_qr_codes(&mut self, item_id: &str, codes: &[QrCode]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM item_qr_codes WHERE item_id = ?1", params![item_id])?;
+        for (position, code) in codes.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO item_qr_codes (item_id, position, kind, raw, payload) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    item_id,
+                    position as i64,
+                    code.payload.kind(),
+                    code.raw,
+                    serde_json::to_string(&code.payload)?
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // OCR し直すジョブで version 以降の読み方で読み直したアイテム
+    pub fn ocr_backfilled(&self, version: u32) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare("SELECT item_id FROM ocr_backfill WHERE version >= ?1")?;
+        let ids = stmt
+            .query_map(params![version], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(ids)
+    }
+
+    pub fn mark_ocr_backfilled(&self, item_id: &str, version: u32) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO ocr_backfill (item_id, version, processed_at) VALUES (?1, ?2, ?3)",
+            params![item_id, version, Utc::now()],
+        )?;
+        Ok(())
+    }
+
+    pub fn retention_reviews(&self) -> Result<Vec<RetentionReview>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT item_id, rule_id, flagged_at, exempt FROM retention_reviews")?;
+        let reviews = stmt
+            .query_map([], |row| {
+                Ok(RetentionReview {
+                    item_id: row.get(0)?,
+                    rule_id: row.get(1)?,
+                    flagged_at: row.get(2)?,
+                    exempt: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(reviews)
+    }
+
+    pub fn flag_retention_review(&self, item_id: &str, rule_id: &str, flagged_at: DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO retention_reviews (item_id, rule_id, flagged_at) VALUES (?1, ?2, ?3)",
+            params![item_id, rule_id, flagged_at],
+        )?;
+        Ok(())
+    }
+
+    // 削除しないことにする（false なら記録を消し、次の確認で改めて確認期間を設ける）
+    // 確認待ちでないアイテムなら false を返す
+    pub fn set_retention_exempt(&self, item_id: &str, exempt: bool) -> Result<bool> {
+        let updated = if exempt {
+            self.conn
+                .execute("UPDATE retention_reviews SET exempt = 1 WHERE item_id = ?1", params![item_id])?
+        } else {
+            self.conn
+                .execute("DELETE FROM retention_reviews WHERE item_id = ?1", params![item_id])?
+        };
+        Ok(updated > 0)
+    }
+
+    pub fn clear_retention_reviews(&mut self, item_ids: &[String]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for id in item_ids {
+            tx.execute("DELETE FROM retention_reviews WHERE item_id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // アイテムの QR コード（保存した項目が読めない古い形式なら、読み取ったままの文字列から読み直す）
+    pub fn qr_codes(&self, item_id: &str) -> Result<Vec<QrCode>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT raw, payload FROM item_qr_codes WHERE item_id = ?1 ORDER BY position")?;
+        let rows = stmt
+            .query_map(params![item_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows
+            .into_iter()
+            .map(|(raw, payload)| match serde_json::from_str(&payload) {
+                Ok(payload) => QrCode { raw, payload },
+                Err(_) => QrCode::parse(&raw),
+            })
+            .collect())
+    }
+
+    pub fn ocr_words(&self, item_id: &str) -> Result<Vec<OcrWord>> {
+        let words: Option<String> = self
+            .conn
+            .query_row("SELECT words FROM ocr_words WHERE item_id = ?1", params![item_id], |row| row.get(0))
+            .optional()?;
+        match words {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn add_ocr_correction(
+        &self,
+        item_id: &str,
+        region: Option<&OcrRegion>,
+        original_text: &str,
+        corrected_text: &str,
+    ) -> Result<OcrCorrection> {
+        let created_at = Utc::now();
+        let region_json = region.map(serde_json::to_string).transpose()?;
+        self.conn.execute(
+            "INSERT INTO ocr_corrections (item_id, region, original_text, corrected_text, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![item_id, region_json, original_text, corrected_text, created_at],
+        )?;
+        Ok(OcrCorrection {
+            id: self.conn.last_insert_rowid(),
+            item_id: item_id.to_string(),
+            region: region.copied(),
+            original_text: original_text.to_string(),
+            corrected_text: corrected_text.to_string(),
+            created_at,
+        })
+    }
+
+    // 古い順
+    pub fn ocr_corrections(&self, item_id: &str) -> Result<Vec<OcrCorrection>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, item_id, region, original_text, corrected_text, created_at
+             FROM ocr_corrections WHERE item_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map(params![item_id], |row| {
+                let region: Option<String> = row.get(2)?;
+                Ok(OcrCorrection {
+                    id: row.get(0)?,
+                    item_id: row.get(1)?,
+                    region: region.and_then(|json| serde_json::from_str(&json).ok()),
+                    original_text: row.get(3)?,
+                    corrected_text: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    // OCR のテキストからレシートの項目を読み取り直す（レシート・請求書でない、または合計がなければ消す）
+    fn write_receipt_fields(conn: &Connection, item_id: &str, ocr_text: &str, doc_type: DocumentType) -> Result<()> {
+        let fields = receipts::parse(ocr_text);
+        if !doc_type.has_receipt_fields() || !fields.is_receipt() {
+            conn.execute("DELETE FROM receipt_fields WHERE item_id = ?1", params![item_id])?;
+            return Ok(());
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO receipt_fields (item_id, vendor, purchased_on, total, subtotal, tax, tax_included, currency)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                item_id,
+                fields.vendor,
+                fields.purchased_on,
+                fields.total.map(|v| v as i64),
+                fields.subtotal.map(|v| v as i64),
+                fields.tax.map(|v| v as i64),
+                fields.tax_included,
+                fields.currency,
+            ],
+        )?;
+        Ok(())
+    }
+
+    // 取り込み済みのアイテムすべてについて読み取り直し、レシートとみなした件数を返す
+    pub fn refresh_receipt_fields(&mut self) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let items: Vec<(String, String, String)> = {
+            let mut stmt =
+                tx.prepare("SELECT id, ocr_text, doc_type FROM items WHERE private = 0 AND deleted_at IS NULL")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        for (id, ocr_text, doc_type) in &items {
+            Self::write_receipt_fields(&tx, id, ocr_text, DocumentType::parse(doc_type))?;
+        }
+        let count = tx.query_row("SELECT COUNT(*) FROM receipt_fields", [], |row| row.get::<_, i64>(0))?;
+        tx.commit()?;
+        Ok(count as usize)
+    }
+
+    // 書類の日付が入っていないアイテムについて OCR のテキストから読み取る（ユーザーが入れた日付は変えない）
+    pub fn refresh_document_dates(&mut self) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let items: Vec<(String, String)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, ocr_text FROM items WHERE private = 0 AND deleted_at IS NULL AND document_date IS NULL",
+            )?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        for (id, ocr_text) in &items {
+            if let Some(date) = document_dates::parse(ocr_text) {
+                tx.execute("UPDATE items SET document_date = ?2 WHERE id = ?1", params![id, date])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 見出しのないアイテムに店名や OCR のテキスト・説明文から見出しを付ける（ユーザーが付けた見出しは変えない）
+    pub fn refresh_titles(&mut self) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let items: Vec<(String, String, String, Option<String>)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, ocr_text, doc_type, caption FROM items
+                 WHERE private = 0 AND deleted_at IS NULL AND title IS NULL",
+            )?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        let mut titled = 0;
+        for (id, ocr_text, doc_type, caption) in &items {
+            if let Some(title) = titles::generate(ocr_text, DocumentType::parse(doc_type), caption.as_deref()) {
+                tx.execute("UPDATE items SET title = ?2 WHERE id = ?1", params![id, title])?;
+                titled += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(titled)
+    }
+
+    // 取り込み済みのアイテムの書類の種類を OCR の結果から推定し直す（画像の大きさは使わない）
+    pub fn refresh_document_types(&mut self) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let items: Vec<(String, String, Option<String>)> = {
+            let mut stmt = tx.prepare(
+                "SELECT items.id, items.ocr_text, ocr_words.words FROM items
+                 LEFT JOIN ocr_words ON ocr_words.item_id = items.id
+                 WHERE items.private = 0",
+            )?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        for (id, ocr_text, words) in &items {
+            let words: Vec<OcrWord> = words
+                .as_deref()
+                .and_then(|json| serde_json::from_str(json).ok())
+                .unwrap_or_default();
+            let doc_type = document_types::classify(ocr_text, &words, 0, 0);
+            tx.execute("UPDATE items SET doc_type = ?2 WHERE id = ?1", params![id, doc_type.as_str()])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 書類の種類ごとのアイテム数（検索の絞り込みの候補に件数を出す）
+    pub fn document_type_counts(&self) -> Result<Vec<(DocumentType, usize)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT doc_type, COUNT(*) FROM items WHERE deleted_at IS NULL GROUP BY doc_type ORDER BY COUNT(*) DESC",
+        )?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows
+            .into_iter()
+            .map(|(doc_type, count)| (DocumentType::parse(&doc_type), count as usize))
+            .collect())
+    }
+
+    fn row_to_receipt_fields(row: &Row) -> rusqlite::Result<ReceiptFields> {
+        Ok(ReceiptFields {
+            vendor: row.get("vendor")?,
+            currency: row.get("currency")?,
+            purchased_on: row.get("purchased_on")?,
+            total: row.get::<_, Option<i64>>("total")?.map(|v| v as u64),
+            subtotal: row.get::<_, Option<i64>>("subtotal")?.map(|v| v as u64),
+            tax: row.get::<_, Option<i64>>("tax")?.map(|v| v as u64),
+            tax_included: row.get("tax_included")?,
+        })
+    }
+
+    pub fn receipt_fields(&self, item_id: &str) -> Result<Option<ReceiptFields>> {
+        Ok(self
+            .conn
+            .query_row("SELECT * FROM receipt_fields WHERE item_id = ?1", params![item_id], Self::row_to_receipt_fields)
+            .optional()?)
+    }
+
+    // 書き出し用にまとめて取得する（レシートでないアイテムは含まない）
+    pub fn receipt_fields_for(&self, item_ids: &[String]) -> Result<HashMap<String, ReceiptFields>> {
+        let mut stmt = self.conn.prepare("SELECT * FROM receipt_fields WHERE item_id = ?1")?;
+        let mut fields = HashMap::new();
+        for id in item_ids {
+            if let Some(receipt) = stmt.query_row(params![id], Self::row_to_receipt_fields).optional()? {
+                fields.insert(id.clone(), receipt);
+            }
+        }
+        Ok(fields)
+    }
+
+    // 店名（部分一致）・金額・購入日で絞り込んだアイテムの ID
+    pub fn receipt_filter_ids(&self, filter: &ReceiptFilter) -> Result<HashSet<String>> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(vendor) = &filter.vendor {
+            conditions.push("vendor LIKE ?");
+            values.push(Box::new(format!("%{}%", vendor)));
+        }
+        if let Some(min) = filter.amount_min {
+            conditions.push("total >= ?");
+            values.push(Box::new(min as i64));
+        }
+        if let Some(max) = filter.amount_max {
+            conditions.push("total <= ?");
+            values.push(Box::new(max as i64));
+        }
+        if let Some(from) = filter.purchased_from {
+            conditions.push("purchased_on >= ?");
+            values.push(Box::new(from));
+        }
+        if let Some(to) = filter.purchased_to {
+            conditions.push("purchased_on <= ?");
+            values.push(Box::new(to));
+        }
+        let mut sql = "SELECT item_id FROM receipt_fields".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        let mut stmt = self.conn.prepare(&sql)?;
+        let ids = stmt
+            .query_map(params_from_iter(values.iter()), |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(ids)
+    }
+
+    // 座標が地点から半径内にあるアイテムのID（緯度・経度の範囲で絞り込んでから距離を求める）
+    pub fn location_filter_ids(&self, filter: &GeoFilter) -> Result<HashSet<String>> {
+        let (min_lat, max_lat, min_lon, max_lon) = filter.bounds();
+        let mut stmt = self.conn.prepare(
+            "SELECT id, latitude, longitude FROM items WHERE deleted_at IS NULL
+                AND latitude BETWEEN ?1 AND ?2 AND longitude BETWEEN ?3 AND ?4",
+        )?;
+        let rows = stmt
+            .query_map(params![min_lat, max_lat, min_lon, max_lon], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows
+            .into_iter()
+            .filter(|(_, latitude, longitude)| filter.contains(*latitude, *longitude))
+            .map(|(id, _, _)| id)
+            .collect())
+    }
+
+    // 書類の日付（なければ撮影日）が範囲に入るアイテムのID
+    pub fn document_date_filter_ids(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<HashSet<String>> {
+        // 書類の日付は日単位なので、範囲の端はローカル時刻の日付で比べる
+        let from_date = from.map(|from| from.with_timezone(&Local).date_naive());
+        let to_date = to.map(|to| to.with_timezone(&Local).date_naive());
+        let mut stmt = self.conn.prepare(
+            "SELECT id FROM items WHERE deleted_at IS NULL AND CASE WHEN document_date IS NOT NULL
+                THEN (?1 IS NULL OR document_date >= ?1) AND (?2 IS NULL OR document_date <= ?2)
+                ELSE (?3 IS NULL OR created_at >= ?3) AND (?4 IS NULL OR created_at <= ?4) END",
+        )?;
+        let ids = stmt
+            .query_map(params![from_date, to_date, from, to], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(ids)
+    }
+
+    // OCR のテキストから電話番号などを見つけ直す
+    fn write_entities(conn: &Connection, item_id: &str, ocr_text: &str) -> Result<()> {
+        conn.execute("DELETE FROM item_entities WHERE item_id = ?1", params![item_id])?;
+        let mut stmt = conn.prepare(
+            "INSERT INTO item_entities (item_id, kind, value, text, link, position) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for (position, entity) in entities::extract(ocr_text).iter().enumerate() {
+            stmt.execute(params![
+                item_id,
+                entity.kind.as_str(),
+                entity.value,
+                entity.text,
+                entity.link,
+                position as i64
+            ])?;
+        }
+        Ok(())
+    }
+
+    pub fn refresh_entities(&mut self) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let items: Vec<(String, String)> = {
+            let mut stmt = tx.prepare("SELECT id, ocr_text FROM items WHERE private = 0 AND deleted_at IS NULL")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+        for (id, ocr_text) in &items {
+            Self::write_entities(&tx, id, ocr_text)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // 見つけた順
+    pub fn item_entities(&self, item_id: &str) -> Result<Vec<Entity>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT kind, value, text, link FROM item_entities WHERE item_id = ?1 ORDER BY position",
+        )?;
+        let rows = stmt
+            .query_map(params![item_id], |row| {
+                let kind: String = row.get(0)?;
+                Ok((kind, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(String, String, String, Option<String>)>>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(kind, value, text, link)| {
+                Some(Entity {
+                    kind: EntityKind::parse(&kind)?,
+                    value,
+                    text,
+                    link,
+                })
+            })
+            .collect())
+    }
+
+    // 検索インデックスへの反映が済んでいない可能性のあるアイテム
+    pub fn index_journal(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT item_id FROM index_journal")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(ids)
+    }
+
+    // インデックスへ反映したアイテムを反映待ちから外す
+    pub fn clear_index_journal(&self, item_ids: &[String]) -> Result<()> {
+        let mut stmt = self.conn.prepare("DELETE FROM index_journal WHERE item_id = ?1")?;
+        for id in item_ids {
+            stmt.execute(params![id])?;
+        }
+        Ok(())
+    }
+
+    // 新しい順
+    pub fn activity_log(&self, filter: &ActivityFilter) -> Result<Vec<ActivityEntry>> {
+        let mut conditions: Vec<String> = vec!["1 = 1".to_string()];
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(actions) = filter.actions.as_ref().filter(|a| !a.is_empty()) {
+            conditions.push(format!("action IN ({})", vec!["?"; actions.len()].join(", ")));
+            for action in actions {
+                values.push(Box::new(action.clone()));
+            }
+        }
+        if let Some(record_id) = &filter.record_id {
+            conditions.push("record_id = ?".to_string());
+            values.push(Box::new(record_id.clone()));
+        }
+        if let Some(date_from) = filter.date_from {
+            conditions.push("occurred_at >= ?".to_string());
+            values.push(Box::new(date_from));
+        }
+        if let Some(date_to) = filter.date_to {
+            conditions.push("occurred_at <= ?".to_string());
+            values.push(Box::new(date_to));
+        }
+        let sql = format!(
+            "SELECT * FROM activity_log WHERE {} ORDER BY id DESC LIMIT {} OFFSET {}",
+            conditions.join(" AND "),
+            filter.limit.map(|l| l as i64).unwrap_or(-1),
+            filter.offset.unwrap_or(0)
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let entries = stmt
+            .query_map(params_from_iter(values.iter()), |row| {
+                let details: Option<String> = row.get("details")?;
+                Ok(ActivityEntry {
+                    id: row.get("id")?,
+                    occurred_at: row.get("occurred_at")?,
+                    action: row.get("action")?,
+                    record_id: row.get("record_id")?,
+                    summary: row.get("summary")?,
+                    details: details.and_then(|d| serde_json::from_str(&d).ok()).unwrap_or(Value::Null),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    // タグの一覧と件数（ゴミ箱のアイテムは数えない）
+    pub fn list_tags(&self) -> Result<Vec<TagInfo>> {
+        let mut stmt = self.conn.prepare("SELECT name, parent, color, icon FROM tags ORDER BY name")?;
+        let tags = stmt
+            .query_map([], |row| {
+                Ok(TagInfo {
+                    name: row.get(0)?,
+                    parent: row.get(1)?,
+                    color: row.get(2)?,
+                    icon: row.get(3)?,
+                    item_count: 0,
+                    total_count: 0,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+
+        let mut stmt = self.conn.prepare(
+            "SELECT t.tag, t.item_id FROM item_tags t JOIN items ON items.id = t.item_id
+             WHERE items.deleted_at IS NULL",
+        )?;
+        let mut items_by_tag: HashMap<String, HashSet<String>> = HashMap::new();
+        for row in stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))? {
+            let (tag, item_id) = row?;
+            items_by_tag.entry(tag).or_default().insert(item_id);
+        }
+
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for tag in &tags {
+            if let Some(parent) = &tag.parent {
+                children.entry(parent.as_str()).or_default().push(tag.name.as_str());
+            }
+        }
+        let mut infos = Vec::new();
+        for tag in &tags {
+            let mut total = HashSet::new();
+            let mut stack = vec![tag.name.as_str()];
+            let mut visited = HashSet::new();
+            while let Some(tag) = stack.pop() {
+                if !visited.insert(tag) {
+                    continue;
+                }
+                total.extend(items_by_tag.get(tag).into_iter().flatten());
+                stack.extend(children.get(tag).into_iter().flatten());
+            }
+            infos.push(TagInfo {
+                item_count: items_by_tag.get(&tag.name).map_or(0, |items| items.len()),
+                total_count: total.len(),
+                ..tag.clone()
+            });
+        }
+        Ok(infos)
+    }
+
+    // タグの色・アイコンを設定する（None で解除）
+    pub fn set_tag_style(&mut self, tag: &str, color: Option<&str>, icon: Option<&str>) -> Result<()> {
+        if let Some(color) = color {
+            let valid = color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+            if !valid {
+                bail!("Invalid color: {}", color);
+            }
+        }
+        self.conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+        self.conn.execute(
+            "UPDATE tags SET color = ?2, icon = ?3 WHERE name = ?1",
+            params![tag, color, icon.map(str::trim).filter(|i| !i.is_empty())],
+        )?;
+        Ok(())
+    }
+
+    // タグ自身と子孫のタグ
+    pub fn tag_descendants(&self, tag: &str) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "WITH RECURSIVE tree(name) AS (
+                SELECT ?1 UNION SELECT tags.name FROM tags JOIN tree ON tags.parent = tree.name
+             )
+             SELECT name FROM tree",
+        )?;
+        let tags = stmt
+            .query_map(params![tag], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(tags)
+    }
+
+    // タグと祖先のタグ（検索インデックスには祖先のタグも入れる）
+    pub fn tags_with_ancestors(&self, tags: &[String]) -> Result<Vec<String>> {
+        let mut expanded: Vec<String> = Vec::new();
+        for tag in tags {
+            let mut current = Some(tag.clone());
+            while let Some(name) = current {
+                if expanded.contains(&name) {
+                    break;
+                }
+                current = self
+                    .conn
+                    .query_row("SELECT parent FROM tags WHERE name = ?1", params![name], |row| row.get(0))
+                    .optional()?
+                    .flatten();
+                expanded.push(name);
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn items_with_tags(&self, tags: &[String]) -> Result<Vec<ItemRecord>> {
+        let mut items = Vec::new();
+        let mut seen = HashSet::new();
+        for tag in tags {
+            for item in self.list_items(&ItemFilter {
+                tag: Some(tag.clone()),
+                ..Default::default()
+            })? {
+                if seen.insert(item.id.clone()) {
+                    items.push(item);
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    // 親タグを設定する（None なら最上位）。検索インデックスを更新すべきアイテムを返す
+    pub fn set_tag_parent(&mut self, tag: &str, parent: Option<&str>) -> Result<Vec<String>> {
+        if let Some(parent) = parent {
+            if self.tags_with_ancestors(&[parent.to_string()])?.iter().any(|t| t == tag) {
+                bail!("Cannot make a tag a child of itself or its descendants");
+            }
+        }
+        let tx = self.conn.transaction()?;
+        tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+        if let Some(parent) = parent {
+            tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![parent])?;
+        }
+        tx.execute("UPDATE tags SET parent = ?2 WHERE name = ?1", params![tag, parent])?;
+        tx.commit()?;
+
+        let descendants = self.tag_descendants(tag)?;
+        Ok(self.items_with_tags(&descendants)?.into_iter().map(|item| item.id).collect())
+    }
+
+    // タグ名をすべてのアイテムで変更する（新しい名前が既にあれば統合する）
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> Result<Vec<ItemRecord>> {
+        let new = new.trim();
+        if new.is_empty() {
+            bail!("Tag name is empty");
+        }
+        if old == new {
+            return Ok(Vec::new());
+        }
+        let exists: bool =
+            self.conn.query_row("SELECT EXISTS(SELECT 1 FROM tags WHERE name = ?1)", params![new], |row| row.get(0))?;
+        if exists {
+            return self.merge_tags(old, new);
+        }
+
+        let items = self.replace_tag_in_items(old, new)?;
+        let tx = self.conn.transaction()?;
+        // 子タグの parent は ON UPDATE CASCADE で追従する
+        tx.execute("UPDATE tags SET name = ?2 WHERE name = ?1", params![old, new])?;
+        for (existing, updated) in &items {
+            Self::write_update(&tx, existing, updated)?;
+        }
+        tx.commit()?;
+        Ok(items.into_iter().map(|(_, updated)| updated).collect())
+    }
+
+    // source のタグを target にまとめる（source の子タグは target の子になり、source は一覧から消える）
+    pub fn merge_tags(&mut self, source: &str, target: &str) -> Result<Vec<ItemRecord>> {
+        if source == target {
+            return Ok(Vec::new());
+        }
+        let items = self.replace_tag_in_items(source, target)?;
+        let tx = self.conn.transaction()?;
+        tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![target])?;
+        // target が source の子だった場合は source の親へ付け替える
+        tx.execute(
+            "UPDATE tags SET parent = (SELECT parent FROM tags WHERE name = ?1) WHERE name = ?2 AND parent = ?1",
+            params![source, target],
+        )?;
+        tx.execute("UPDATE tags SET parent = ?2 WHERE parent = ?1", params![source, target])?;
+        for (existing, updated) in &items {
+            Self::write_update(&tx, existing, updated)?;
+        }
+        tx.execute("DELETE FROM tags WHERE name = ?1", params![source])?;
+        tx.commit()?;
+        Ok(items.into_iter().map(|(_, updated)| updated).collect())
+    }
+
+    // old のタグが付いたアイテムについて、old を new に置き換えた内容を作る（ゴミ箱のアイテムも対象）
+    fn replace_tag_in_items(&self, old: &str, new: &str) -> Result<Vec<(ItemRecord, ItemRecord)>> {
+        let sql = format!(
+            "SELECT {} FROM items WHERE EXISTS (SELECT 1 FROM item_tags t WHERE t.item_id = items.id AND t.tag = ?1)",
+            ITEM_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let items = stmt
+            .query_map(params![old], Self::row_to_item)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let now = Utc::now();
+        Ok(items
+            .into_iter()
+            .map(|existing| {
+                let mut updated = existing.clone();
+                updated.tags = Vec::new();
+                for tag in &existing.tags {
+                    let tag = if tag == old { new.to_string() } else { tag.clone() };
+                    if !updated.tags.contains(&tag) {
+                        updated.tags.push(tag);
+                    }
+                }
+                updated.updated_at = now;
+                (existing, updated)
+            })
+            .collect())
+    }
+
+    pub fn sync_entries(&self, remote: &str) -> Result<HashMap<String, SyncEntry>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, etag, local_version, synced_at FROM sync_state WHERE remote = ?1")?;
+        let entries = stmt
+            .query_map(params![remote], |row| {
+                Ok((
+                    row.get(0)?,
+                    SyncEntry {
+                        etag: row.get(1)?,
+                        local_version: row.get(2)?,
+                        synced_at: row.get(3)?,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(entries)
+    }
+
+    pub fn set_sync_entry(&mut self, remote: &str, key: &str, etag: &str, local_version: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sync_state (remote, key, etag, local_version, synced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(remote, key) DO UPDATE SET etag = excluded.etag,
+                local_version = excluded.local_version, synced_at = excluded.synced_at",
+            params![remote, key, etag, local_version, Utc::now()],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_sync_entry(&mut self, remote: &str, key: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM sync_state WHERE remote = ?1 AND key = ?2", params![remote, key])?;
+        Ok(())
+    }
+
+    pub fn clear_sync_entries(&mut self, remote: &str) -> Result<()> {
+        self.conn.execute("DELETE FROM sync_state WHERE remote = ?1", params![remote])?;
+        Ok(())
+    }
+
+    fn write_field_version(conn: &Connection, record_id: &str, field: &str, updated_at: &DateTime<Utc>) -> Result<()> {
+        conn.execute(
+            "INSERT INTO field_versions (record_id, field, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(record_id, field) DO UPDATE SET updated_at = excluded.updated_at",
+            params![record_id, field, updated_at],
+        )?;
+        Ok(())
+    }
+
+    // フィールドごとの最終更新日時（記録がないフィールドはレコードの updated_at を使う）
+    pub fn field_versions(&self, record_id: &str) -> Result<HashMap<String, DateTime<Utc>>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT field, updated_at FROM field_versions WHERE record_id = ?1")?;
+        let versions = stmt
+            .query_map(params![record_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(versions)
+    }
+
+    pub fn set_field_versions(&mut self, record_id: &str, versions: &HashMap<String, DateTime<Utc>>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for (field, updated_at) in versions {
+            Self::write_field_version(&tx, record_id, field, updated_at)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn row_to_conflict(row: &Row) -> rusqlite::Result<ConflictRecord> {
+        let parse = |text: String| serde_json::from_str(&text).unwrap_or(Value::Null);
+        let applied: String = row.get("applied")?;
+        Ok(ConflictRecord {
+            id: row.get("id")?,
+            remote: row.get("remote")?,
+            kind: row.get("kind")?,
+            record_id: row.get("record_id")?,
+            field: row.get("field")?,
+            local_value: parse(row.get("local_value")?),
+            remote_value: parse(row.get("remote_value")?),
+            applied: if applied == "remote" { ConflictSide::Remote } else { ConflictSide::Local },
+            detected_at: row.get("detected_at")?,
+        })
+    }
+
+    // 同じフィールドの古い衝突は新しいもので置き換える
+    pub fn add_sync_conflict(&mut self, conflict: &ConflictRecord) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM sync_conflicts WHERE record_id = ?1 AND field = ?2",
+            params![conflict.record_id, conflict.field],
+        )?;
+        tx.execute(
+            "INSERT INTO sync_conflicts (remote, kind, record_id, field, local_value, remote_value, applied, detected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                conflict.remote,
+                conflict.kind,
+                conflict.record_id,
+                conflict.field,
+                conflict.local_value.to_string(),
+                conflict.remote_value.to_string(),
+                conflict.applied.as_str(),
+                conflict.detected_at,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn list_sync_conflicts(&self) -> Result<Vec<ConflictRecord>> {
+        let mut stmt = self.conn.prepare("SELECT * FROM sync_conflicts ORDER BY detected_at DESC")?;
+        let conflicts = stmt
+            .query_map([], Self::row_to_conflict)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(conflicts)
+    }
+
+    pub fn get_sync_conflict(&self, id: i64) -> Result<Option<ConflictRecord>> {
+        Ok(self
+            .conn
+            .query_row("SELECT * FROM sync_conflicts WHERE id = ?1", params![id], Self::row_to_conflict)
+            .optional()?)
+    }
+
+    pub fn remove_sync_conflict(&mut self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM sync_conflicts WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    // 元画像を再圧縮したファイルに差し替える（元のハッシュは最初の1回分だけ残す）
+    pub fn replace_original(
+        &mut self,
+        item_id: &str,
+        image_path: &str,
+        content_hash: &str,
+        original: &RecompressedOriginal,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "UPDATE items SET image_path = store_image_path(?2), content_hash = ?3, updated_at = ?4 WHERE id = ?1",
+            params![item_id, image_path, content_hash, Utc::now()],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO recompressed_originals
+                (item_id, original_hash, original_size, original_format, recompressed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                item_id,
+                original.original_hash,
+                original.original_size as i64,
+                original.original_format,
+                original.recompressed_at,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn fingerprints(&self) -> Result<HashMap<String, ImageFingerprint>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT item_id, content_hash, perceptual_hash, width, height FROM image_fingerprints")?;
+        let fingerprints = stmt
+            .query_map([], |row| {
+                let perceptual_hash: String = row.get(2)?;
+                Ok((
+                    row.get(0)?,
+                    ImageFingerprint {
+                        content_hash: row.get(1)?,
+                        perceptual_hash: u64::from_str_radix(&perceptual_hash, 16).unwrap_or(0),
+                        width: row.get(3)?,
+                        height: row.get(4)?,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(fingerprints)
+    }
+
+    pub fn set_fingerprint(&mut self, item_id: &str, fingerprint: &ImageFingerprint) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO image_fingerprints (item_id, content_hash, perceptual_hash, width, height)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(item_id) DO UPDATE SET content_hash = excluded.content_hash,
+                perceptual_hash = excluded.perceptual_hash, width = excluded.width, height = excluded.height",
+            params![
+                item_id,
+                fingerprint.content_hash,
+                format!("{:016x}", fingerprint.perceptual_hash),
+                fingerprint.width,
+                fingerprint.height,
+            ],
+        )?;
+        Ok(())
+    }
+
+    // SQLite の整合性チェック（問題がなければ空）
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let messages = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(messages.into_iter().filter(|m| m != "ok").collect())
+    }
+
+    // 一貫性のあるスナップショットをファイルに書き出す（バックアップ用）
+    pub fn snapshot_to(&self, dest: &Path) -> Result<()> {
+        if dest.exists() {
+            std::fs::remove_file(dest)?;
+        }
+        self.conn
+            .execute("VACUUM INTO ?1", params![dest.to_string_lossy().to_string()])?;
+        Ok(())
+    }
+
+    // 検索インデックス用の形に変換（グループ名を解決する）
+    pub fn to_searchable(&self, item: &ItemRecord) -> Result<SearchableItem> {
+        let group_title = match &item.group_id {
+            Some(group_id) => self.get_group(group_id)?.map(|g| g.title),
+            None => None,
+        };
+        Ok(SearchableItem {
+            id: item.id.clone(),
+            ocr_text: item.ocr_text.clone(),
+            memo: item.memo.clone(),
+            tags: self.tags_with_ancestors(&item.tags)?,
+            location_name: item.location_name.clone(),
+            created_at: item.created_at,
+            updated_at: item.updated_at,
+            group_title,
+            image_path: item.image_path.clone(),
+            ocr_words: self.ocr_words(&item.id)?,
+            entities: self.item_entities(&item.id)?.into_iter().map(|e| e.value).collect(),
+            doc_type: item.doc_type,
+            caption: item.caption.clone(),
+            title: item.title.clone(),
+            translated_text: self.translation(item)?.map(|t| t.text).unwrap_or_default(),
+        })
+    }
+}
+
+// 編集履歴の対象フィールドの差分 (フィールド名, 旧値, 新値)
+fn diff_fields(old: &ItemRecord, new: &ItemRecord) -> Vec<(&'static str, Option<String>, Option<String>)> {
+    let mut changes = Vec::new();
+    if old.memo != new.memo {
+        changes.push(("memo", Some(old.memo.clone()), Some(new.memo.clone())));
+    }
+    if old.ocr_text != new.ocr_text {
+        changes.push(("ocr_text", Some(old.ocr_text.clone()), Some(new.ocr_text.clone())));
+    }
+    if old.tags != new.tags {
+        changes.push((
+            "tags",
+            serde_json::to_string(&old.tags).ok(),
+            serde_json::to_string(&new.tags).ok(),
+        ));
+    }
+    if old.field_value("location") != new.field_value("location") {
+        changes.push((
+            "location",
+            Some(old.field_value("location").to_string()),
+            Some(new.field_value("location").to_string()),
+        ));
+    }
+    if old.group_id != new.group_id {
+        changes.push(("group_id", old.group_id.clone(), new.group_id.clone()));
+    }
+    if old.doc_type != new.doc_type {
+        changes.push((
+            "doc_type",
+            Some(old.doc_type.as_str().to_string()),
+            Some(new.doc_type.as_str().to_string()),
+        ));
+    }
+    if old.caption != new.caption {
+        changes.push(("caption", old.caption.clone(), new.caption.clone()));
+    }
+    if old.document_date != new.document_date {
+        changes.push((
+            "document_date",
+            old.document_date.map(|d| d.to_string()),
+            new.document_date.map(|d| d.to_string()),
+        ));
+    }
+    if old.title != new.title {
+        changes.push(("title", old.title.clone(), new.title.clone()));
+    }
+    if old.archived != new.archived {
+        changes.push(("archived", Some(old.archived.to_string()), Some(new.archived.to_string())));
+    }
+    changes
+}
+
+// 編集履歴の値を JSON に変換（タグと位置情報は JSON 文字列で、それ以外はそのまま保存している）
+fn history_value(field: &str, value: Option<String>) -> Value {
+    match (field, value) {
+        (_, None) => Value::Null,
+        ("tags" | "location", Some(json)) => serde_json::from_str(&json).unwrap_or(Value::Null),
+        ("archived", Some(text)) => Value::Bool(text == "true"),
+        (_, Some(text)) => Value::String(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (MetadataStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("snap-organizer-test-{}", uuid::Uuid::new_v4()));
+        let store = MetadataStore::open(&dir.join("library.db")).unwrap();
+        (store, dir)
+    }
+
+    fn item(id: &str) -> ItemRecord {
+        let now = Utc::now();
+        ItemRecord {
+            id: id.to_string(),
+            group_id: None,
+            image_path: None,
+            content_hash: Some(format!("hash-{}", id)),
+            ocr_text: "領収書 1,200円".to_string(),
+            memo: "経費".to_string(),
+            tags: vec!["receipt".to_string()],
+            location_name: None,
+            latitude: None,
+            longitude: None,
+            created_at: now,
+            updated_at: now,
+            private: false,
+            doc_type: DocumentType::default(),
+            caption: None,
+            document_date: None,
+            title: None,
+            archived: false,
+        }
+    }
+
+    #[test]
+    fn put_item_keeps_sealed_items_private() {
+        let (mut source, source_dir) = temp_store();
+        let (mut target, target_dir) = temp_store();
+
+        source.insert_item(&item("a")).unwrap();
+        source.seal_item("a", b"sealed", Some("/library/images/private-a.jpg.age")).unwrap();
+        let sealed = source.get_item("a").unwrap().unwrap();
+        let sealed_text = source.sealed_text("a").unwrap();
+        assert!(sealed.private);
+
+        // 新規に書き込む場合も、公開アイテムを上書きする場合も非公開のまま残る
+        target.put_item(&sealed, sealed_text.as_deref()).unwrap();
+        target.insert_item(&item("b")).unwrap();
+        target.put_item(&ItemRecord { id: "b".to_string(), ..sealed.clone() }, sealed_text.as_deref()).unwrap();
+        for id in ["a", "b"] {
+            let restored = target.get_item(id).unwrap().unwrap();
+            assert!(restored.private);
+            assert_eq!(restored.ocr_text, "");
+            assert_eq!(restored.memo, "");
+            assert_eq!(target.sealed_text(id).unwrap().as_deref(), Some(&b"sealed"[..]));
+        }
+        assert_eq!(target.private_item_ids().unwrap().len(), 2);
+
+        // 暗号化したテキストがなければ書き込まない
+        assert!(target.put_item(&ItemRecord { id: "c".to_string(), ..sealed }, None).is_err());
+        assert!(target.get_item("c").unwrap().is_none());
+
+        drop(source);
+        drop(target);
+        let _ = std::fs::remove_dir_all(source_dir);
+        let _ = std::fs::remove_dir_all(target_dir);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::jobs::ProgressReporter;
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::Client;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// 大きなモデルでも途中で切れないように長めにする
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const CHUNK_SIZE: usize = 256 * 1024;
+
+// 端末内の推論で使う ONNX モデルと付随するファイル（トークナイザーなど）
+// sha256 を固定していないモデルは、初回のダウンロード時のハッシュを記録して以後の検証に使う
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ModelSpec {
+    pub id: &'static str,
+    pub name: &'static str,
+    // このモデルを使う機能（同じ機能のモデルはすべてダウンロードが必要）
+    pub feature: &'static str,
+    pub url: &'static str,
+    // models ディレクトリでのファイル名
+    pub file: &'static str,
+    pub sha256: Option<&'static str>,
+    // 表示用のおおよそのサイズ（バイト）
+    pub size: u64,
+}
+
+// 使える機能が追加されるたびにここへモデルを足す
+pub const CATALOG: &[ModelSpec] = &[
+    // 画像と文章を同じ空間に埋め込む CLIP（ViT-B/32、量子化版）
+    ModelSpec {
+        id: "clip-vit-b32-vision",
+        name: "CLIP ViT-B/32 (image)",
+        feature: "semantic_search",
+        url: "https://huggingface.co/Xenova/clip-vit-base-patch32/resolve/main/onnx/vision_model_quantized.onnx",
+        file: "clip-vit-b32-vision.onnx",
+        sha256: None,
+        size: 89_000_000,
+    },
+    ModelSpec {
+        id: "clip-vit-b32-text",
+        name: "CLIP ViT-B/32 (text)",
+        feature: "semantic_search",
+        url: "https://huggingface.co/Xenova/clip-vit-base-patch32/resolve/main/onnx/text_model_quantized.onnx",
+        file: "clip-vit-b32-text.onnx",
+        sha256: None,
+        size: 64_000_000,
+    },
+    ModelSpec {
+        id: "clip-vit-b32-tokenizer",
+        name: "CLIP tokenizer",
+        feature: "semantic_search",
+        url: "https://huggingface.co/Xenova/clip-vit-base-patch32/resolve/main/tokenizer.json",
+        file: "clip-vit-b32-tokenizer.json",
+        sha256: None,
+        size: 2_200_000,
+    },
+    // 画像の説明文を生成する ViT + GPT-2（量子化版、英語）
+    ModelSpec {
+        id: "vit-gpt2-caption-encoder",
+        name: "ViT-GPT2 captioning (encoder)",
+        feature: "captions",
+        url: "https://huggingface.co/Xenova/vit-gpt2-image-captioning/resolve/main/onnx/encoder_model_quantized.onnx",
+        file: "vit-gpt2-caption-encoder.onnx",
+        sha256: None,
+        size: 88_000_000,
+    },
+    ModelSpec {
+        id: "vit-gpt2-caption-decoder",
+        name: "ViT-GPT2 captioning (decoder)",
+        feature: "captions",
+        url: "https://huggingface.co/Xenova/vit-gpt2-image-captioning/resolve/main/onnx/decoder_model_quantized.onnx",
+        file: "vit-gpt2-caption-decoder.onnx",
+        sha256: None,
+        size: 155_000_000,
+    },
+    ModelSpec {
+        id: "vit-gpt2-caption-tokenizer",
+        name: "ViT-GPT2 captioning tokenizer",
+        feature: "captions",
+        url: "https://huggingface.co/Xenova/vit-gpt2-image-captioning/resolve/main/tokenizer.json",
+        file: "vit-gpt2-caption-tokenizer.json",
+        sha256: None,
+        size: 2_100_000,
+    },
+    // OCR のテキストを日本語から英語へ訳す Marian（opus-mt-ja-en、量子化版）
+    ModelSpec {
+        id: "opus-mt-ja-en-encoder",
+        name: "Opus-MT ja-en (encoder)",
+        feature: "translation",
+        url: "https://huggingface.co/Xenova/opus-mt-ja-en/resolve/main/onnx/encoder_model_quantized.onnx",
+        file: "opus-mt-ja-en-encoder.onnx",
+        sha256: None,
+        size: 52_000_000,
+    },
+    ModelSpec {
+        id: "opus-mt-ja-en-decoder",
+        name: "Opus-MT ja-en (decoder)",
+        feature: "translation",
+        url: "https://huggingface.co/Xenova/opus-mt-ja-en/resolve/main/onnx/decoder_model_quantized.onnx",
+        file: "opus-mt-ja-en-decoder.onnx",
+        sha256: None,
+        size: 60_000_000,
+    },
+    ModelSpec {
+        id: "opus-mt-ja-en-tokenizer",
+        name: "Opus-MT ja-en tokenizer",
+        feature: "translation",
+        url: "https://huggingface.co/Xenova/opus-mt-ja-en/resolve/main/tokenizer.json",
+        file: "opus-mt-ja-en-tokenizer.json",
+        sha256: None,
+        size: 3_000_000,
+    },
+    ModelSpec {
+        id: "opus-mt-ja-en-config",
+        name: "Opus-MT ja-en config",
+        feature: "translation",
+        url: "https://huggingface.co/Xenova/opus-mt-ja-en/resolve/main/config.json",
+        file: "opus-mt-ja-en-config.json",
+        sha256: None,
+        size: 2_000,
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelInfo {
+    #[serde(flatten)]
+    pub spec: ModelSpec,
+    pub installed: bool,
+    // ダウンロード済みのファイルのサイズ
+    pub installed_size: Option<u64>,
+}
+
+pub fn spec(id: &str) -> Result<&'static ModelSpec> {
+    CATALOG
+        .iter()
+        .find(|spec| spec.id == id)
+        .with_context(|| format!("Unknown model: {}", id))
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// url を dest の隣の一時ファイルへダウンロードし、ハッシュが合ったものだけを dest に置き換えてハッシュを返す
+// （OCR の言語データのダウンロードにも使う）
+pub fn download_file(
+    url: &str,
+    dest: &Path,
+    expected_sha256: Option<&str>,
+    size: u64,
+    label: &str,
+    reporter: &dyn ProgressReporter,
+) -> Result<String> {
+    let mut partial = dest.to_path_buf().into_os_string();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+
+    let client = Client::builder().timeout(DOWNLOAD_TIMEOUT).build()?;
+    let mut response = client
+        .get(url)
+        .header("User-Agent", concat!("SnapOrganizer/", env!("CARGO_PKG_VERSION")))
+        .send()?
+        .error_for_status()?;
+    let total = response.content_length().unwrap_or(size);
+    reporter.set_total(total);
+
+    let result = (|| -> Result<String> {
+        let mut file = File::create(&partial)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut done = 0u64;
+        loop {
+            reporter.checkpoint()?;
+            let read = response.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read])?;
+            hasher.update(&buffer[..read]);
+            done += read as u64;
+            reporter.progress(done, label);
+        }
+        file.sync_all()?;
+        Ok(hex::encode(hasher.finalize()))
+    })();
+    let hash = match result {
+        Ok(hash) => hash,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+
+    if let Some(expected) = expected_sha256.filter(|expected| !expected.eq_ignore_ascii_case(&hash)) {
+        let _ = fs::remove_file(&partial);
+        bail!("Checksum mismatch (expected {}, got {})", expected, hash);
+    }
+    fs::rename(&partial, dest)?;
+    Ok(hash)
+}
+
+// モデルの保存場所（ライブラリの models ディレクトリ）の管理
+pub struct ModelManager {
+    dir: PathBuf,
+}
+
+impl ModelManager {
+    pub fn new(dir: PathBuf) -> Self {
+        ModelManager { dir }
+    }
+
+    pub fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(spec(id).map_or_else(|_| format!("{}.onnx", id), |spec| spec.file.to_string()))
+    }
+
+    // ダウンロード時に記録したハッシュ
+    fn hash_path(&self, id: &str) -> PathBuf {
+        let mut path = self.path(id).into_os_string();
+        path.push(".sha256");
+        PathBuf::from(path)
+    }
+
+    // 機能に必要なモデルがすべてダウンロード済みか
+    pub fn feature_installed(&self, feature: &str) -> bool {
+        CATALOG
+            .iter()
+            .filter(|spec| spec.feature == feature)
+            .all(|spec| self.is_installed(spec.id))
+    }
+
+    pub fn is_installed(&self, id: &str) -> bool {
+        self.path(id).is_file()
+    }
+
+    pub fn list(&self) -> Vec<ModelInfo> {
+        CATALOG
+            .iter()
+            .map(|spec| {
+                let installed_size = fs::metadata(self.path(spec.id)).ok().map(|m| m.len());
+                ModelInfo {
+                    spec: *spec,
+                    installed: installed_size.is_some(),
+                    installed_size,
+                }
+            })
+            .collect()
+    }
+
+    fn expected_hash(&self, spec: &ModelSpec) -> Option<String> {
+        spec.sha256
+            .map(|hash| hash.to_lowercase())
+            .or_else(|| fs::read_to_string(self.hash_path(spec.id)).ok().map(|h| h.trim().to_string()))
+    }
+
+    // ファイルが壊れていないか（ハッシュが分からなければ検証できないため true）
+    pub fn verify(&self, id: &str) -> Result<bool> {
+        let spec = spec(id)?;
+        let path = self.path(id);
+        if !path.is_file() {
+            bail!("Model {} is not installed", id);
+        }
+        Ok(match self.expected_hash(spec) {
+            Some(expected) => sha256_file(&path)? == expected,
+            None => true,
+        })
+    }
+
+    // 一時ファイルへダウンロードし、ハッシュが合ったものだけを置き換える
+    pub fn download(&self, id: &str, reporter: &dyn ProgressReporter) -> Result<PathBuf> {
+        let spec = spec(id)?;
+        fs::create_dir_all(&self.dir)?;
+        let dest = self.path(id);
+        let hash = download_file(spec.url, &dest, spec.sha256, spec.size, spec.name, reporter)
+            .with_context(|| format!("Failed to download model {}", id))?;
+        fs::write(self.hash_path(id), &hash)?;
+        Ok(dest)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        spec(id)?;
+        let path = self.path(id);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(&path)?;
+        let _ = fs::remove_file(self.hash_path(id));
+        Ok(true)
+    }
+}
@@ -0,0 +1,379 @@
+use crate::import_pipeline::{self, ImportContext, ImportOutcome};
+use crate::jobs::ProgressReporter;
+use crate::paths::is_image_path;
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// 他のアプリから取り出したノート（画像はファイル名と内容）
+#[derive(Debug, Default)]
+pub struct ImportedNote {
+    pub title: String,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub attachments: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSource {
+    // Google Takeout の Keep フォルダ（または個別の .json）
+    GoogleKeep,
+    // Evernote の .enex
+    Evernote,
+}
+
+// ノートを1件ずつ読み出す（大きなエクスポートでも全件をメモリに載せない）
+pub trait NoteReader {
+    fn read_notes(&self, path: &Path, on_note: &mut dyn FnMut(ImportedNote) -> Result<()>) -> Result<()>;
+}
+
+impl NoteSource {
+    fn reader(self) -> Box<dyn NoteReader> {
+        match self {
+            NoteSource::GoogleKeep => Box::new(KeepReader),
+            NoteSource::Evernote => Box::new(EnexReader),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct NoteImportReport {
+    pub notes_read: usize,
+    pub items_imported: usize,
+    // 画像のないノートやゴミ箱のノート
+    pub notes_skipped: usize,
+    // 内容が同じアイテムが既にあって取り込まなかった画像
+    pub duplicates_skipped: usize,
+    pub imported_item_ids: Vec<String>,
+    pub failed: Vec<serde_json::Value>,
+}
+
+// --- Google Keep ---
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepNote {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text_content: String,
+    #[serde(default)]
+    list_content: Vec<KeepListItem>,
+    #[serde(default)]
+    labels: Vec<KeepLabel>,
+    #[serde(default)]
+    attachments: Vec<KeepAttachment>,
+    #[serde(default)]
+    is_trashed: bool,
+    created_timestamp_usec: Option<i64>,
+    user_edited_timestamp_usec: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepListItem {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    is_checked: bool,
+}
+
+#[derive(Deserialize)]
+struct KeepLabel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepAttachment {
+    file_path: String,
+}
+
+pub struct KeepReader;
+
+impl KeepReader {
+    // Takeout では JSON に書かれた拡張子と実際のファイルの拡張子が違うことがある（.jpeg と .jpg など）
+    fn find_attachment(dir: &Path, file_path: &str) -> Option<PathBuf> {
+        let path = dir.join(file_path);
+        if path.is_file() {
+            return Some(path);
+        }
+        let stem = path.file_stem()?.to_string_lossy().to_string();
+        ["jpg", "jpeg", "png", "gif", "webp"]
+            .iter()
+            .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+            .find(|p| p.is_file())
+    }
+
+    fn read_note(json_path: &Path) -> Result<Option<ImportedNote>> {
+        let note: KeepNote = serde_json::from_slice(&fs::read(json_path)?)
+            .with_context(|| format!("Invalid Keep note: {}", json_path.display()))?;
+        if note.is_trashed {
+            return Ok(None);
+        }
+        let mut text = note.text_content;
+        for item in &note.list_content {
+            text.push_str(&format!("\n- [{}] {}", if item.is_checked { "x" } else { " " }, item.text));
+        }
+
+        let dir = json_path.parent().unwrap_or(Path::new("."));
+        let mut attachments = Vec::new();
+        for attachment in &note.attachments {
+            match Self::find_attachment(dir, &attachment.file_path) {
+                Some(path) if is_image_path(&path) => {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                    attachments.push((name, fs::read(&path)?));
+                }
+                Some(_) => {}
+                None => log::warn!("Keep attachment not found: {}", attachment.file_path),
+            }
+        }
+
+        Ok(Some(ImportedNote {
+            title: note.title,
+            text: text.trim().to_string(),
+            tags: note.labels.into_iter().map(|l| l.name).collect(),
+            created_at: note.created_timestamp_usec.and_then(DateTime::from_timestamp_micros),
+            updated_at: note.user_edited_timestamp_usec.and_then(DateTime::from_timestamp_micros),
+            attachments,
+        }))
+    }
+}
+
+impl NoteReader for KeepReader {
+    fn read_notes(&self, path: &Path, on_note: &mut dyn FnMut(ImportedNote) -> Result<()>) -> Result<()> {
+        let files: Vec<PathBuf> = if path.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(path)?
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().map(|e| e.eq_ignore_ascii_case("json")).unwrap_or(false))
+                .collect();
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+        for file in files {
+            if let Some(note) = Self::read_note(&file)? {
+                on_note(note)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// --- Evernote ---
+
+pub struct EnexReader;
+
+fn parse_enex_date(text: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(text.trim(), "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|d| d.and_utc())
+}
+
+// ENML（XHTML）の本文からテキストだけを取り出す
+fn enml_to_text(enml: &str) -> String {
+    static BREAKS: OnceLock<Regex> = OnceLock::new();
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+    let breaks = BREAKS.get_or_init(|| Regex::new(r"(?i)<br\s*/?>|</(div|p|li|h[1-6]|tr)>").unwrap());
+    let tags = TAGS.get_or_init(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+    let text = breaks.replace_all(enml, "\n");
+    let text = tags.replace_all(&text, "");
+    let text = quick_xml::escape::unescape(&text)
+        .map(|t| t.to_string())
+        .unwrap_or_else(|_| text.to_string());
+    text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim().to_string()
+}
+
+fn extension_for_mime(mime: &str) -> &'static str {
+    match mime {
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/bmp" => "bmp",
+        "image/tiff" => "tiff",
+        _ => "jpg",
+    }
+}
+
+#[derive(Default)]
+struct EnexResource {
+    data: String,
+    mime: String,
+    file_name: String,
+}
+
+impl NoteReader for EnexReader {
+    fn read_notes(&self, path: &Path, on_note: &mut dyn FnMut(ImportedNote) -> Result<()>) -> Result<()> {
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut reader = Reader::from_reader(BufReader::new(file));
+        reader.config_mut().trim_text(true);
+
+        let mut buf = Vec::new();
+        let mut note: Option<ImportedNote> = None;
+        let mut content = String::new();
+        let mut resource: Option<EnexResource> = None;
+        let mut field: Option<Vec<u8>> = None;
+        loop {
+            let event = reader.read_event_into(&mut buf)?;
+            let text = match &event {
+                Event::Start(e) => {
+                    let name = e.local_name().as_ref().to_vec();
+                    match name.as_slice() {
+                        b"note" => {
+                            note = Some(ImportedNote::default());
+                            content.clear();
+                        }
+                        b"resource" => resource = Some(EnexResource::default()),
+                        _ => {}
+                    }
+                    field = Some(name);
+                    None
+                }
+                Event::Text(t) => Some(t.unescape()?.to_string()),
+                Event::CData(c) => Some(String::from_utf8_lossy(c).to_string()),
+                Event::End(e) => {
+                    match e.local_name().as_ref() {
+                        b"resource" => {
+                            if let (Some(note), Some(res)) = (note.as_mut(), resource.take()) {
+                                if res.mime.starts_with("image/") {
+                                    let compact: String = res.data.split_whitespace().collect();
+                                    let data = base64::engine::general_purpose::STANDARD.decode(compact)?;
+                                    let name = if res.file_name.is_empty() {
+                                        format!("attachment.{}", extension_for_mime(&res.mime))
+                                    } else {
+                                        res.file_name
+                                    };
+                                    note.attachments.push((name, data));
+                                }
+                            }
+                        }
+                        b"note" => {
+                            if let Some(mut finished) = note.take() {
+                                finished.text = enml_to_text(&content);
+                                on_note(finished)?;
+                            }
+                        }
+                        _ => {}
+                    }
+                    field = None;
+                    None
+                }
+                Event::Eof => break,
+                _ => None,
+            };
+
+            if let (Some(text), Some(name), Some(note)) = (text, field.as_deref(), note.as_mut()) {
+                match (name, resource.as_mut()) {
+                    (b"data", Some(res)) => res.data.push_str(&text),
+                    (b"mime", Some(res)) => res.mime = text,
+                    (b"file-name", Some(res)) => res.file_name = text,
+                    (b"title", None) => note.title = text,
+                    (b"content", None) => content.push_str(&text),
+                    (b"created", None) => note.created_at = parse_enex_date(&text),
+                    (b"updated", None) => note.updated_at = parse_enex_date(&text),
+                    (b"tag", None) => note.tags.push(text),
+                    _ => {}
+                }
+            }
+            buf.clear();
+        }
+        Ok(())
+    }
+}
+
+// --- 取り込み ---
+
+fn compose_memo(note: &ImportedNote) -> String {
+    match (note.title.trim(), note.text.trim()) {
+        ("", text) => text.to_string(),
+        (title, "") => title.to_string(),
+        (title, text) => format!("{}\n\n{}", title, text),
+    }
+}
+
+// ノートの画像を1枚ずつ取り込み、ノートの本文・タグ・日時を引き継ぐ
+fn import_note(ctx: &ImportContext, note: &ImportedNote, report: &mut NoteImportReport) -> Result<()> {
+    let staging = ctx.paths.staging_dir();
+    fs::create_dir_all(&staging)?;
+    let memo = compose_memo(note);
+
+    for (name, data) in &note.attachments {
+        let safe_name: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+            .collect();
+        let staged = staging.join(format!("{}-{}", uuid::Uuid::new_v4().simple(), safe_name));
+        fs::write(&staged, data)?;
+        let result = import_pipeline::import_file(ctx, &staged);
+        let _ = fs::remove_file(&staged);
+
+        let mut item = match result {
+            Ok(ImportOutcome::Skipped(_)) => {
+                report.duplicates_skipped += 1;
+                continue;
+            }
+            Ok(outcome) => outcome.into_item(),
+            Err(e) => {
+                report.failed.push(serde_json::json!({ "note": note.title, "file": name, "error": e.to_string() }));
+                continue;
+            }
+        };
+        item.memo = memo.clone();
+        for tag in &note.tags {
+            if !item.tags.contains(tag) {
+                item.tags.push(tag.clone());
+            }
+        }
+        if let Some(created_at) = note.created_at {
+            item.created_at = created_at;
+        }
+        item.updated_at = note.updated_at.unwrap_or(item.created_at).max(item.created_at);
+
+        let mut store = ctx.store.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        store.put_item(&item, None)?;
+        if let Some(engine) = ctx.search.lock().unwrap().as_mut() {
+            engine.update_item(store.to_searchable(&item)?)?;
+        }
+        report.items_imported += 1;
+        report.imported_item_ids.push(item.id);
+    }
+    Ok(())
+}
+
+pub fn import_notes(
+    ctx: &ImportContext,
+    source: NoteSource,
+    path: &Path,
+    reporter: &dyn ProgressReporter,
+) -> Result<NoteImportReport> {
+    if !path.exists() {
+        bail!("Not found: {}", path.display());
+    }
+    let mut report = NoteImportReport::default();
+    source.reader().read_notes(path, &mut |note| {
+        reporter.checkpoint()?;
+        report.notes_read += 1;
+        if note.attachments.is_empty() {
+            report.notes_skipped += 1;
+        } else {
+            import_note(ctx, &note, &mut report)?;
+        }
+        reporter.progress(report.notes_read as u64, &note.title);
+        Ok(())
+    })?;
+    Ok(report)
+}
@@ -0,0 +1,385 @@
+use crate::secrets;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine as _;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
+
+// クライアントIDはビルド時に環境変数で埋め込む（公開クライアントのためシークレットは秘匿情報ではない）
+const GOOGLE_CLIENT_ID: Option<&str> = option_env!("SNAP_GOOGLE_CLIENT_ID");
+const GOOGLE_CLIENT_SECRET: Option<&str> = option_env!("SNAP_GOOGLE_CLIENT_SECRET");
+const DROPBOX_APP_KEY: Option<&str> = option_env!("SNAP_DROPBOX_APP_KEY");
+
+const GOOGLE_DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+// アプリが作成したファイルにだけアクセスできるスコープ
+const GOOGLE_DRIVE_SCOPE: &str = "https://www.googleapis.com/auth/drive.file";
+const DROPBOX_AUTHORIZE_URL: &str = "https://www.dropbox.com/oauth2/authorize";
+const DROPBOX_TOKEN_URL: &str = "https://api.dropboxapi.com/oauth2/token";
+
+// 期限切れ直前のトークンは使わずに更新する
+const REFRESH_MARGIN_SECS: i64 = 60;
+const PKCE_SESSION_MINUTES: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuthProvider {
+    GoogleDrive,
+    Dropbox,
+}
+
+impl OAuthProvider {
+    fn client_id(self) -> Result<&'static str> {
+        let id = match self {
+            OAuthProvider::GoogleDrive => GOOGLE_CLIENT_ID,
+            OAuthProvider::Dropbox => DROPBOX_APP_KEY,
+        };
+        id.ok_or_else(|| anyhow!("{:?} sign-in is not available in this build", self))
+    }
+
+    fn client_secret(self) -> Option<&'static str> {
+        match self {
+            OAuthProvider::GoogleDrive => GOOGLE_CLIENT_SECRET,
+            OAuthProvider::Dropbox => None,
+        }
+    }
+
+    fn token_url(self) -> &'static str {
+        match self {
+            OAuthProvider::GoogleDrive => GOOGLE_TOKEN_URL,
+            OAuthProvider::Dropbox => DROPBOX_TOKEN_URL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthToken {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+}
+
+impl TokenResponse {
+    fn into_token(self, previous_refresh: Option<String>) -> OAuthToken {
+        OAuthToken {
+            access_token: self.access_token,
+            // 更新時はリフレッシュトークンが返らないことがあるので以前のものを引き継ぐ
+            refresh_token: self.refresh_token.or(previous_refresh),
+            expires_at: self.expires_in.map(|secs| Utc::now() + ChronoDuration::seconds(secs)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    user_code: String,
+    verification_url: String,
+    expires_in: i64,
+    interval: Option<u64>,
+}
+
+// 画面に表示するサインイン手順
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorizationPrompt {
+    pub session_id: String,
+    pub provider: OAuthProvider,
+    pub verification_url: String,
+    // デバイスフローの場合にユーザーが入力するコード（Dropbox は表示されたコードを貼り付けてもらう）
+    pub user_code: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+enum PendingAuth {
+    // Google: デバイスフロー（トークンエンドポイントをポーリング）
+    Device {
+        device_code: String,
+        interval: u64,
+        expires_at: DateTime<Utc>,
+    },
+    // Dropbox: デバイスフロー非対応のため、リダイレクトなしの PKCE でコードを貼り付けてもらう
+    Pkce {
+        code_verifier: String,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+fn http_client() -> Result<Client> {
+    Ok(Client::builder().timeout(Duration::from_secs(60)).build()?)
+}
+
+fn token_request(provider: OAuthProvider, form: &[(&str, &str)]) -> Result<std::result::Result<TokenResponse, TokenError>> {
+    let response = http_client()?
+        .post(provider.token_url())
+        .form(form)
+        .send()
+        .context("OAuth token request failed")?;
+    if response.status().is_success() {
+        Ok(Ok(response.json()?))
+    } else {
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        Ok(Err(serde_json::from_str(&text).unwrap_or(TokenError {
+            error: status.to_string(),
+            error_description: Some(text),
+        })))
+    }
+}
+
+// 進行中のサインイン
+#[derive(Default)]
+pub struct OAuthFlows {
+    pending: Mutex<HashMap<String, (OAuthProvider, PendingAuth)>>,
+}
+
+impl OAuthFlows {
+    pub fn start(&self, provider: OAuthProvider) -> Result<AuthorizationPrompt> {
+        let client_id = provider.client_id()?;
+        let session_id = Uuid::new_v4().to_string();
+
+        let (prompt, pending) = match provider {
+            OAuthProvider::GoogleDrive => {
+                let response = http_client()?
+                    .post(GOOGLE_DEVICE_CODE_URL)
+                    .form(&[("client_id", client_id), ("scope", GOOGLE_DRIVE_SCOPE)])
+                    .send()
+                    .context("Failed to start Google sign-in")?;
+                if !response.status().is_success() {
+                    bail!("Google sign-in failed: {}", response.text().unwrap_or_default());
+                }
+                let device: DeviceCodeResponse = response.json()?;
+                let expires_at = Utc::now() + ChronoDuration::seconds(device.expires_in);
+                (
+                    AuthorizationPrompt {
+                        session_id: session_id.clone(),
+                        provider,
+                        verification_url: device.verification_url,
+                        user_code: Some(device.user_code),
+                        expires_at,
+                    },
+                    PendingAuth::Device {
+                        device_code: device.device_code,
+                        interval: device.interval.unwrap_or(5),
+                        expires_at,
+                    },
+                )
+            }
+            OAuthProvider::Dropbox => {
+                let code_verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+                let challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                    .encode(Sha256::digest(code_verifier.as_bytes()));
+                let url = reqwest::Url::parse_with_params(
+                    DROPBOX_AUTHORIZE_URL,
+                    &[
+                        ("client_id", client_id),
+                        ("response_type", "code"),
+                        ("code_challenge", challenge.as_str()),
+                        ("code_challenge_method", "S256"),
+                        ("token_access_type", "offline"),
+                    ],
+                )?;
+                let expires_at = Utc::now() + ChronoDuration::minutes(PKCE_SESSION_MINUTES);
+                (
+                    AuthorizationPrompt {
+                        session_id: session_id.clone(),
+                        provider,
+                        verification_url: url.to_string(),
+                        user_code: None,
+                        expires_at,
+                    },
+                    PendingAuth::Pkce { code_verifier, expires_at },
+                )
+            }
+        };
+
+        let mut pending_map = self.pending.lock().unwrap();
+        // 期限切れのセッションを掃除
+        pending_map.retain(|_, (_, auth)| match auth {
+            PendingAuth::Device { expires_at, .. } | PendingAuth::Pkce { expires_at, .. } => *expires_at > Utc::now(),
+        });
+        pending_map.insert(session_id, (provider, pending));
+        Ok(prompt)
+    }
+
+    // サインインの完了を待ってトークンを返す（デバイスフローはユーザーが承認するまでブロックする）
+    pub fn complete(&self, session_id: &str, code: Option<&str>) -> Result<(OAuthProvider, OAuthToken)> {
+        let (provider, pending) = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(session_id)
+            .context("Sign-in session expired; please start again")?;
+        let client_id = provider.client_id()?;
+
+        match pending {
+            PendingAuth::Device {
+                device_code,
+                mut interval,
+                expires_at,
+            } => {
+                let mut form = vec![
+                    ("client_id", client_id),
+                    ("device_code", device_code.as_str()),
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ];
+                if let Some(secret) = provider.client_secret() {
+                    form.push(("client_secret", secret));
+                }
+                while Utc::now() < expires_at {
+                    std::thread::sleep(Duration::from_secs(interval));
+                    match token_request(provider, &form)? {
+                        Ok(token) => return Ok((provider, token.into_token(None))),
+                        Err(e) if e.error == "authorization_pending" => {}
+                        Err(e) if e.error == "slow_down" => interval += 5,
+                        Err(e) => bail!("Sign-in failed: {}", e.error_description.unwrap_or(e.error)),
+                    }
+                }
+                bail!("Sign-in timed out; please start again")
+            }
+            PendingAuth::Pkce { code_verifier, .. } => {
+                let code = code.map(str::trim).filter(|c| !c.is_empty()).context("Authorization code is required")?;
+                let form = [
+                    ("client_id", client_id),
+                    ("code", code),
+                    ("code_verifier", code_verifier.as_str()),
+                    ("grant_type", "authorization_code"),
+                ];
+                match token_request(provider, &form)? {
+                    Ok(token) => Ok((provider, token.into_token(None))),
+                    Err(e) => bail!("Sign-in failed: {}", e.error_description.unwrap_or(e.error)),
+                }
+            }
+        }
+    }
+}
+
+fn secret_names(provider: OAuthProvider) -> (String, String) {
+    let provider = serde_json::to_value(provider)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+    (format!("{}-access-token", provider), format!("{}-refresh-token", provider))
+}
+
+// 認証情報はライブラリの設定とは別ファイルに保存する（バックアップには含めない）
+// トークンそのものは OS のキーチェーンに置く
+pub fn load_tokens(path: &Path) -> HashMap<OAuthProvider, OAuthToken> {
+    let mut tokens: HashMap<OAuthProvider, OAuthToken> = fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    for (provider, token) in tokens.iter_mut() {
+        let (access, refresh) = secret_names(*provider);
+        secrets::fill(path, &access, &mut token.access_token);
+        let mut refresh_token = token.refresh_token.take().unwrap_or_default();
+        secrets::fill(path, &refresh, &mut refresh_token);
+        token.refresh_token = Some(refresh_token).filter(|t| !t.is_empty());
+    }
+    tokens
+}
+
+pub fn save_token(path: &Path, provider: OAuthProvider, token: Option<&OAuthToken>) -> Result<()> {
+    let mut tokens = load_tokens(path);
+    match token {
+        Some(token) => {
+            tokens.insert(provider, token.clone());
+        }
+        None => {
+            tokens.remove(&provider);
+        }
+    }
+    let (access, refresh) = secret_names(provider);
+    if token.is_none() {
+        // 空文字を渡すとキーチェーンから削除される
+        secrets::stash(path, &access, &mut String::new());
+        secrets::stash(path, &refresh, &mut String::new());
+    }
+    for (provider, token) in tokens.iter_mut() {
+        let (access, refresh) = secret_names(*provider);
+        secrets::stash(path, &access, &mut token.access_token);
+        let mut refresh_token = token.refresh_token.take().unwrap_or_default();
+        secrets::stash(path, &refresh, &mut refresh_token);
+        token.refresh_token = Some(refresh_token).filter(|t| !t.is_empty());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&tokens)?)?;
+    Ok(())
+}
+
+// 期限切れのアクセストークンを自動で更新し、更新結果を保存する
+pub struct OAuthClient {
+    provider: OAuthProvider,
+    tokens_file: PathBuf,
+    token: Mutex<OAuthToken>,
+}
+
+impl OAuthClient {
+    pub fn load(provider: OAuthProvider, tokens_file: PathBuf) -> Result<Self> {
+        let token = load_tokens(&tokens_file)
+            .remove(&provider)
+            .with_context(|| format!("Not signed in to {:?}", provider))?;
+        Ok(OAuthClient {
+            provider,
+            tokens_file,
+            token: Mutex::new(token),
+        })
+    }
+
+    pub fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().unwrap();
+        let expired = token
+            .expires_at
+            .map(|at| at - ChronoDuration::seconds(REFRESH_MARGIN_SECS) <= Utc::now())
+            .unwrap_or(false);
+        if !expired {
+            return Ok(token.access_token.clone());
+        }
+
+        let refresh_token = token
+            .refresh_token
+            .clone()
+            .with_context(|| format!("{:?} session expired; please sign in again", self.provider))?;
+        let client_id = self.provider.client_id()?;
+        let mut form = vec![
+            ("client_id", client_id),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+        ];
+        if let Some(secret) = self.provider.client_secret() {
+            form.push(("client_secret", secret));
+        }
+        let refreshed = match token_request(self.provider, &form)? {
+            Ok(response) => response.into_token(Some(refresh_token.clone())),
+            Err(e) => bail!(
+                "{:?} session expired; please sign in again ({})",
+                self.provider,
+                e.error_description.unwrap_or(e.error)
+            ),
+        };
+        save_token(&self.tokens_file, self.provider, Some(&refreshed))?;
+        *token = refreshed;
+        Ok(token.access_token.clone())
+    }
+}
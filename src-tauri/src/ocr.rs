@@ -0,0 +1,801 @@
+use crate::ocr_preprocess::{self, PreprocessPreset, Preprocessed};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+pub const DEFAULT_LANGUAGES: &[&str] = &["jpn", "eng"];
+// 読み方の版（改善したら上げると、OCR し直すジョブで前の版で読み直したアイテムも対象になる）
+pub const PIPELINE_VERSION: u32 = 1;
+// 印刷の文字として読んだ結果がこれより少ない・確からしさが低いときは手書きとして読み直す
+const HANDWRITING_MIN_CHARS: usize = 20;
+const HANDWRITING_MAX_CONFIDENCE: f32 = 55.0;
+// 縦書き用の traineddata がある言語
+const VERTICAL_LANGUAGES: &[&str] = &["jpn", "chi_sim", "chi_tra", "kor"];
+// 向きの判定（osd）がこれより確かでなければ回さない
+const MIN_ORIENTATION_CONFIDENCE: f32 = 1.5;
+// 配置を保つときの行の間隔（単語の高さに対する倍率）と、続けて入れる空行の上限
+const LAYOUT_LINE_PITCH: f32 = 1.6;
+const LAYOUT_MAX_BLANK_LINES: usize = 2;
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrWord {
+    pub text: String,
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+    pub confidence: f32,
+    pub block: u32,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrResult {
+    pub text: String,
+    pub words: Vec<OcrWord>,
+    pub languages: Vec<String>,
+    pub engine: String,
+    pub mean_confidence: f32,
+    #[serde(default)]
+    pub mode: OcrMode,
+}
+
+// 印刷の文字として読むか、手書き向けの前処理とエンジンの設定で読むか、縦書きとして読むか
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrMode {
+    #[default]
+    Printed,
+    Handwriting,
+    // 本・FAX・役所の書類などの縦書き（Tesseract の jpn_vert などで読む）
+    Vertical,
+    // 画面のスクリーンショット（表やターミナルをコピーしても使えるよう、改行・列・字下げを単語の位置から再現する）
+    Layout,
+}
+
+impl OcrResult {
+    fn text_score(&self) -> f32 {
+        text_score(&self.words)
+    }
+
+    // ほとんど読めなかった（手書きのメモなど）
+    fn looks_unreadable(&self) -> bool {
+        let chars = self.text.chars().filter(|c| !c.is_whitespace()).count();
+        chars < HANDWRITING_MIN_CHARS || self.mean_confidence < HANDWRITING_MAX_CONFIDENCE
+    }
+}
+
+// 確からしさで重み付けした文字数（読み方を変えた結果どうしを比べるのに使う）
+pub fn text_score(words: &[OcrWord]) -> f32 {
+    words
+        .iter()
+        .map(|w| w.text.chars().filter(|c| !c.is_whitespace()).count() as f32 * w.confidence / 100.0)
+        .sum()
+}
+
+pub fn mean_confidence(words: &[OcrWord]) -> f32 {
+    if words.is_empty() {
+        0.0
+    } else {
+        words.iter().map(|w| w.confidence).sum::<f32>() / words.len() as f32
+    }
+}
+
+pub fn default_languages() -> Vec<String> {
+    DEFAULT_LANGUAGES.iter().map(|l| l.to_string()).collect()
+}
+
+// 縦書き用の traineddata に置き換える（縦書きのない言語・指定済みのものはそのまま）
+pub fn vertical_languages(languages: &[String]) -> Vec<String> {
+    languages
+        .iter()
+        .map(|l| {
+            if VERTICAL_LANGUAGES.contains(&l.as_str()) {
+                format!("{}_vert", l)
+            } else {
+                l.clone()
+            }
+        })
+        .collect()
+}
+
+// Tesseract の実行ファイル（同梱のサイドカー、なければ PATH 上のもの）を使うOCRエンジン
+pub struct TesseractEngine {
+    binary: PathBuf,
+    tessdata_dir: PathBuf,
+}
+
+impl TesseractEngine {
+    pub fn new(binary: PathBuf, tessdata_dir: PathBuf) -> Self {
+        TesseractEngine { binary, tessdata_dir }
+    }
+
+    // 実行ファイルの隣に同梱されたサイドカーを優先し、なければ PATH から探す
+    pub fn locate(tessdata_dir: PathBuf) -> Result<Self> {
+        let name = if cfg!(windows) { "tesseract.exe" } else { "tesseract" };
+        let bundled = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(name)))
+            .filter(|path| path.is_file());
+        let binary = bundled.unwrap_or_else(|| PathBuf::from(name));
+
+        let engine = Self::new(binary, tessdata_dir);
+        engine.version().context("Tesseract executable not found")?;
+        Ok(engine)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.binary);
+        #[cfg(windows)]
+        {
+            // コンソールウィンドウを表示しない
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x0800_0000);
+        }
+        command
+    }
+
+    pub fn version(&self) -> Result<String> {
+        let output = self.command().arg("--version").output()?;
+        let text = String::from_utf8_lossy(&output.stdout).to_string()
+            + &String::from_utf8_lossy(&output.stderr);
+        Ok(text.lines().next().unwrap_or_default().trim().to_string())
+    }
+
+    pub fn tessdata_dir(&self) -> &Path {
+        &self.tessdata_dir
+    }
+
+    pub fn installed_languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = fs::read_dir(&self.tessdata_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter_map(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        name.strip_suffix(".traineddata").map(|l| l.to_string())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        languages.sort();
+        languages
+    }
+
+    fn has_language(&self, language: &str) -> bool {
+        self.tessdata_dir.join(format!("{}.traineddata", language)).is_file()
+    }
+
+    // 縦書きとして読めるか（縦書き用の traineddata が入っている）
+    pub fn supports_vertical(&self, languages: &[String]) -> bool {
+        vertical_languages(languages)
+            .iter()
+            .any(|l| l.ends_with("_vert") && self.has_language(l))
+    }
+
+    // 画像の向き（正しく読むために時計回りに回す角度）を osd で調べる
+    // osd.traineddata がない・判定が確かでないときは None
+    pub fn orientation(&self, image_path: &Path) -> Result<Option<u32>> {
+        if !self.has_language("osd") {
+            return Ok(None);
+        }
+        let output = self
+            .command()
+            .arg(image_path)
+            .arg("stdout")
+            .arg("--tessdata-dir")
+            .arg(&self.tessdata_dir)
+            .args(["-l", "osd", "--psm", "0"])
+            .output()
+            .context("Failed to run tesseract")?;
+        // 文字が少なすぎると判定できずに失敗する
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        let value = |name: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|v| v.trim_start_matches(':').trim().parse::<f32>().ok())
+        };
+        match (value("Rotate"), value("Orientation confidence")) {
+            (Some(rotate), Some(confidence)) if confidence >= MIN_ORIENTATION_CONFIDENCE => {
+                Ok(Some(rotate as u32 % 360))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn ensure_languages(&self, languages: &[String]) -> Result<()> {
+        let missing: Vec<&String> = languages
+            .iter()
+            .filter(|l| !self.has_language(l))
+            .collect();
+        if !missing.is_empty() {
+            bail!(
+                "Missing traineddata for: {}",
+                missing.iter().map(|l| l.as_str()).collect::<Vec<_>>().join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn recognize_file(&self, image_path: &Path, languages: &[String], mode: OcrMode) -> Result<OcrResult> {
+        let languages = if languages.is_empty() { default_languages() } else { languages.to_vec() };
+        let languages = if mode == OcrMode::Vertical { vertical_languages(&languages) } else { languages };
+        self.ensure_languages(&languages)?;
+
+        let mut command = self.command();
+        command
+            .arg(image_path)
+            .arg("stdout")
+            .arg("--tessdata-dir")
+            .arg(&self.tessdata_dir)
+            .arg("-l")
+            .arg(languages.join("+"));
+        // 手書きは行がそろっていないため、段組みを仮定せずに散らばった文字を探す（LSTM のみ）
+        // 縦書きは縦に並んだ文字の1つのまとまりとして、回して行に分けて読む
+        match mode {
+            OcrMode::Handwriting => {
+                command.args(["--oem", "1", "--psm", "11"]);
+            }
+            OcrMode::Vertical => {
+                command.args(["--psm", "5"]);
+            }
+            // 画面は1つのまとまりとして行ごとに読み、単語の間の空白も残す
+            OcrMode::Layout => {
+                command.args(["--psm", "6", "-c", "preserve_interword_spaces=1"]);
+            }
+            OcrMode::Printed => {}
+        }
+        let output = command
+            .arg("tsv")
+            .output()
+            .context("Failed to run tesseract")?;
+
+        if !output.status.success() {
+            bail!("Tesseract failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        let words = parse_tsv(&String::from_utf8_lossy(&output.stdout));
+        Ok(build_result(words, languages, "tesseract"))
+    }
+}
+
+// 同梱の traineddata をライブラリの tessdata ディレクトリへコピー（既存のものは上書きしない）
+pub fn install_bundled_traineddata(bundled_dir: &Path, tessdata_dir: &Path) -> Result<usize> {
+    fs::create_dir_all(tessdata_dir)?;
+    if !bundled_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut installed = 0;
+    for entry in fs::read_dir(bundled_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if !name.to_string_lossy().ends_with(".traineddata") {
+            continue;
+        }
+        let dest = tessdata_dir.join(&name);
+        if !dest.exists() {
+            fs::copy(entry.path(), &dest)?;
+            installed += 1;
+        }
+    }
+    Ok(installed)
+}
+
+// tesseract の TSV 出力から単語（level 5）を取り出す
+fn parse_tsv(tsv: &str) -> Vec<OcrWord> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 12 || cols[0] != "5" {
+                return None;
+            }
+            let text = cols[11].trim();
+            let confidence: f32 = cols[10].parse().ok()?;
+            if text.is_empty() || confidence < 0.0 {
+                return None;
+            }
+            let block: u32 = cols[2].parse().ok()?;
+            let paragraph: u32 = cols[3].parse().ok()?;
+            let line: u32 = cols[4].parse().ok()?;
+            Some(OcrWord {
+                text: text.to_string(),
+                left: cols[6].parse().ok()?,
+                top: cols[7].parse().ok()?,
+                width: cols[8].parse().ok()?,
+                height: cols[9].parse().ok()?,
+                confidence,
+                block,
+                // 段落をまたいでも一意になるよう行番号を合成
+                line: paragraph * 1000 + line,
+            })
+        })
+        .collect()
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x30FF | // 句読点・ひらがな・カタカナ
+        0x3400..=0x4DBF |
+        0x4E00..=0x9FFF |
+        0xF900..=0xFAFF |
+        0xFF00..=0xFFEF)
+}
+
+// 単語を行ごとに連結（日本語の文字間には空白を入れない）
+pub fn join_words(words: &[OcrWord]) -> String {
+    let mut text = String::new();
+    let mut current: Option<(u32, u32)> = None;
+
+    for word in words {
+        let key = (word.block, word.line);
+        match current {
+            Some(prev) if prev == key => {
+                let prev_cjk = text.chars().last().map(is_cjk).unwrap_or(false);
+                let next_cjk = word.text.chars().next().map(is_cjk).unwrap_or(false);
+                if !(prev_cjk && next_cjk) {
+                    text.push(' ');
+                }
+            }
+            Some(_) => text.push('\n'),
+            None => {}
+        }
+        text.push_str(&word.text);
+        current = Some(key);
+    }
+    text
+}
+
+// 画面上の文字の幅（全角の文字は2文字分）
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if is_cjk(c) { 2 } else { 1 }).sum()
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    values.sort_by(f32::total_cmp);
+    values.get(values.len() / 2).copied().unwrap_or(0.0)
+}
+
+// 単語の位置から改行・列・字下げを再現したテキスト（1文字の幅と行の高さは単語の大きさの中央値から求める）
+pub fn layout_text(words: &[OcrWord]) -> String {
+    let cell = median(
+        words
+            .iter()
+            .map(|w| w.width as f32 / display_width(&w.text).max(1) as f32)
+            .collect(),
+    );
+    let row = median(words.iter().map(|w| w.height as f32).collect());
+    if cell <= 0.0 || row <= 0.0 {
+        return join_words(words);
+    }
+    let origin = words.iter().map(|w| w.left).min().unwrap_or(0);
+
+    // 単語の中心が行の先頭の単語の上端と下端の間にあれば同じ行とする
+    let mut sorted: Vec<&OcrWord> = words.iter().collect();
+    sorted.sort_by_key(|w| (w.top + w.height / 2, w.left));
+    let mut lines: Vec<(u32, u32, Vec<&OcrWord>)> = Vec::new();
+    for word in sorted {
+        let center = word.top + word.height / 2;
+        match lines.last_mut() {
+            Some((top, bottom, line)) if (*top..=*bottom).contains(&center) => line.push(word),
+            _ => lines.push((word.top, word.top + word.height, vec![word])),
+        }
+    }
+
+    let mut text = String::new();
+    let mut previous_center = None;
+    for (top, bottom, mut line) in lines {
+        let center = (top + bottom) as f32 / 2.0;
+        if let Some(previous) = previous_center {
+            let breaks = ((center - previous) / (row * LAYOUT_LINE_PITCH)).round() as usize;
+            text.push_str(&"\n".repeat(breaks.clamp(1, LAYOUT_MAX_BLANK_LINES + 1)));
+        }
+        previous_center = Some(center);
+
+        line.sort_by_key(|w| w.left);
+        let mut width = 0;
+        let mut previous: Option<&OcrWord> = None;
+        for word in line {
+            // 前の単語と重ならないよう、少なくとも1文字空ける（日本語の文字が続くときは詰める）
+            let min_column = match previous {
+                None => 0,
+                Some(prev) => {
+                    let close = (word.left.saturating_sub(prev.left + prev.width) as f32) < cell / 2.0;
+                    let prev_cjk = prev.text.chars().last().is_some_and(is_cjk);
+                    let next_cjk = word.text.chars().next().is_some_and(is_cjk);
+                    if close && prev_cjk && next_cjk {
+                        width
+                    } else {
+                        width + 1
+                    }
+                }
+            };
+            let column = (((word.left - origin) as f32 / cell).round() as usize).max(min_column);
+            text.push_str(&" ".repeat(column - width));
+            text.push_str(&word.text);
+            width = column + display_width(&word.text);
+            previous = Some(word);
+        }
+    }
+    text
+}
+
+pub fn build_result(words: Vec<OcrWord>, languages: Vec<String>, engine: &str) -> OcrResult {
+    OcrResult {
+        text: join_words(&words),
+        mean_confidence: mean_confidence(&words),
+        words,
+        languages,
+        engine: engine.to_string(),
+        mode: OcrMode::Printed,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrBackend {
+    Tesseract,
+    Windows,
+}
+
+// 言語ごとに使うOCRバックエンドの設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrSettings {
+    pub default_backend: OcrBackend,
+    #[serde(default)]
+    pub backend_by_language: HashMap<String, OcrBackend>,
+    // 手書きの認識に使うバックエンド（省略時は言語ごとの設定と同じ）
+    #[serde(default)]
+    pub handwriting_backend: Option<OcrBackend>,
+    // 印刷の文字としてほとんど読めなかった画像を手書きとして読み直す
+    #[serde(default = "default_detect_handwriting")]
+    pub detect_handwriting: bool,
+    // 印刷の文字としてほとんど読めなかった画像の向きと縦書きかを調べて読み直す
+    #[serde(default = "default_detect_vertical")]
+    pub detect_vertical: bool,
+    // スクリーンショット（PNG）は改行・列・字下げを保って読む
+    #[serde(default = "default_preserve_screenshot_layout")]
+    pub preserve_screenshot_layout: bool,
+}
+
+fn default_detect_handwriting() -> bool {
+    true
+}
+
+fn default_detect_vertical() -> bool {
+    true
+}
+
+fn default_preserve_screenshot_layout() -> bool {
+    true
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        OcrSettings {
+            // Windows では追加モデル不要で高速な Windows OCR を既定にする
+            default_backend: if cfg!(windows) { OcrBackend::Windows } else { OcrBackend::Tesseract },
+            backend_by_language: HashMap::new(),
+            handwriting_backend: None,
+            detect_handwriting: true,
+            detect_vertical: true,
+            preserve_screenshot_layout: true,
+        }
+    }
+}
+
+impl OcrSettings {
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // 先頭の言語（主言語）の設定を優先
+    pub fn backend_for(&self, languages: &[String]) -> OcrBackend {
+        languages
+            .first()
+            .and_then(|l| self.backend_by_language.get(l))
+            .copied()
+            .unwrap_or(self.default_backend)
+    }
+
+    pub fn backend_for_mode(&self, languages: &[String], mode: OcrMode) -> OcrBackend {
+        match (mode, self.handwriting_backend) {
+            (OcrMode::Handwriting, Some(backend)) => backend,
+            // 縦書き用の traineddata は Tesseract にしかない
+            (OcrMode::Vertical, _) => OcrBackend::Tesseract,
+            _ => self.backend_for(languages),
+        }
+    }
+}
+
+// 読み方の指定（バックエンドを省略すると設定に従い、使えなければもう一方で読む）
+#[derive(Debug, Clone, Default)]
+pub struct OcrOptions {
+    pub backend: Option<OcrBackend>,
+    pub languages: Vec<String>,
+    pub mode: OcrMode,
+    pub preset: PreprocessPreset,
+    // 読む前に時計回りに回す角度（90度単位、横倒しや逆さまに撮った画像）
+    pub rotation: u32,
+}
+
+impl OcrOptions {
+    // 手書きは手書き向けの前処理をした画像を読む
+    pub fn for_mode(languages: &[String], mode: OcrMode) -> Self {
+        OcrOptions {
+            backend: None,
+            languages: languages.to_vec(),
+            mode,
+            preset: match mode {
+                OcrMode::Printed | OcrMode::Vertical | OcrMode::Layout => PreprocessPreset::None,
+                OcrMode::Handwriting => PreprocessPreset::Handwriting,
+            },
+            rotation: 0,
+        }
+    }
+}
+
+// 設定に従ってバックエンドを選び、使えなければもう一方にフォールバックする
+pub struct OcrService {
+    tesseract: Option<TesseractEngine>,
+    settings: Mutex<OcrSettings>,
+    settings_path: PathBuf,
+}
+
+impl OcrService {
+    pub fn new(tesseract: Option<TesseractEngine>, settings_path: PathBuf) -> Self {
+        OcrService {
+            tesseract,
+            settings: Mutex::new(OcrSettings::load(&settings_path)),
+            settings_path,
+        }
+    }
+
+    pub fn tesseract(&self) -> Option<&TesseractEngine> {
+        self.tesseract.as_ref()
+    }
+
+    pub fn settings(&self) -> OcrSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set_settings(&self, settings: OcrSettings) -> Result<()> {
+        settings.save(&self.settings_path)?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    pub fn available_backends(&self) -> Vec<OcrBackend> {
+        let mut backends = Vec::new();
+        if self.tesseract.is_some() {
+            backends.push(OcrBackend::Tesseract);
+        }
+        if cfg!(windows) {
+            backends.push(OcrBackend::Windows);
+        }
+        backends
+    }
+
+    pub fn recognize_file(&self, image_path: &Path, languages: &[String]) -> Result<OcrResult> {
+        self.recognize_file_as(image_path, languages, OcrMode::Printed)
+    }
+
+    // 読み方を指定して認識する
+    pub fn recognize_file_as(&self, image_path: &Path, languages: &[String], mode: OcrMode) -> Result<OcrResult> {
+        self.recognize_with_options(image_path, &OcrOptions::for_mode(languages, mode))
+    }
+
+    // バックエンド・言語・前処理を指定して認識する（回した・前処理した画像の単語の位置は元の画像の座標に戻す）
+    pub fn recognize_with_options(&self, image_path: &Path, options: &OcrOptions) -> Result<OcrResult> {
+        let languages = if options.languages.is_empty() { default_languages() } else { options.languages.clone() };
+        let mode = options.mode;
+        let recognize = |path: &Path| match options.backend {
+            // 指定されたバックエンドが使えなければ、ほかのもので読まずに失敗とする
+            Some(backend) => self.recognize_with(backend, path, &languages, mode),
+            None => {
+                let preferred = self.settings.lock().unwrap().backend_for_mode(&languages, mode);
+                self.recognize_with_fallback(preferred, path, &languages, mode)
+            }
+        };
+        let rotation = options.rotation % 360;
+        let prepared = match (options.preset, rotation) {
+            (PreprocessPreset::None, 0) => None,
+            (preset, rotation) => {
+                let data = fs::read(image_path).with_context(|| format!("Failed to read {}", image_path.display()))?;
+                let image = crate::image_decode::decode(&data)?;
+                let size = (image.width(), image.height());
+                let rotated = ocr_preprocess::rotate(&image, rotation);
+                let prepared = ocr_preprocess::apply(&rotated, preset).unwrap_or_else(|| Preprocessed {
+                    image: rotated.to_luma8(),
+                    scale: 1.0,
+                });
+                Some((prepared, size))
+            }
+        };
+        let mut result = match prepared {
+            None => recognize(image_path)?,
+            Some((prepared, (width, height))) => {
+                let tmp = std::env::temp_dir().join(format!("snap-ocr-{}.png", Uuid::new_v4()));
+                prepared.image.save(&tmp)?;
+                let result = recognize(&tmp);
+                let _ = fs::remove_file(&tmp);
+                let mut result = result?;
+                if prepared.scale > 1.0 {
+                    for word in &mut result.words {
+                        word.left = (word.left as f32 / prepared.scale) as u32;
+                        word.top = (word.top as f32 / prepared.scale) as u32;
+                        word.width = (word.width as f32 / prepared.scale).ceil() as u32;
+                        word.height = (word.height as f32 / prepared.scale).ceil() as u32;
+                    }
+                }
+                for word in &mut result.words {
+                    unrotate(word, rotation, width, height);
+                }
+                result
+            }
+        };
+        if mode == OcrMode::Layout {
+            result.text = layout_text(&result.words);
+        }
+        result.mode = mode;
+        Ok(result)
+    }
+
+    // 印刷の文字として（スクリーンショットは配置を保って）読み、ほとんど読めなければ
+    // 手書き・回した画像・縦書きとしても読んで結果の良いものを使う
+    pub fn recognize_auto(&self, image_path: &Path, languages: &[String]) -> Result<OcrResult> {
+        let settings = self.settings();
+        let mode = if settings.preserve_screenshot_layout && looks_like_screenshot(image_path) {
+            OcrMode::Layout
+        } else {
+            OcrMode::Printed
+        };
+        let printed = self.recognize_file_as(image_path, languages, mode)?;
+        if !printed.looks_unreadable() {
+            return Ok(printed);
+        }
+        let mut candidates = Vec::new();
+        if settings.detect_handwriting {
+            candidates.push(OcrOptions::for_mode(languages, OcrMode::Handwriting));
+        }
+        if settings.detect_vertical {
+            candidates.extend(self.layout_candidates(image_path, languages));
+        }
+        let mut best = printed;
+        for options in candidates {
+            match self.recognize_with_options(image_path, &options) {
+                Ok(result) if result.text_score() > best.text_score() => best = result,
+                Ok(_) => {}
+                Err(e) => log::warn!("{:?} OCR failed for {}: {}", options.mode, image_path.display(), e),
+            }
+        }
+        Ok(best)
+    }
+
+    // 向き（osd）と文字の並び方から、読み直す候補を選ぶ（Tesseract がなければなし）
+    fn layout_candidates(&self, image_path: &Path, languages: &[String]) -> Vec<OcrOptions> {
+        let Some(tesseract) = self.tesseract.as_ref() else {
+            return Vec::new();
+        };
+        let mut candidates = Vec::new();
+        let rotation = tesseract.orientation(image_path).unwrap_or_else(|e| {
+            log::warn!("Orientation detection failed for {}: {}", image_path.display(), e);
+            None
+        });
+        if let Some(rotation) = rotation.filter(|r| *r != 0) {
+            candidates.push(OcrOptions {
+                rotation,
+                ..OcrOptions::for_mode(languages, OcrMode::Printed)
+            });
+        }
+        let languages = if languages.is_empty() { default_languages() } else { languages.to_vec() };
+        if tesseract.supports_vertical(&languages) {
+            let vertical = fs::read(image_path)
+                .ok()
+                .and_then(|data| crate::image_decode::decode(&data).ok())
+                .is_some_and(|image| ocr_preprocess::looks_vertical(&image));
+            if vertical {
+                candidates.push(OcrOptions::for_mode(&languages, OcrMode::Vertical));
+            }
+        }
+        candidates
+    }
+
+    fn recognize_with_fallback(
+        &self,
+        preferred: OcrBackend,
+        image_path: &Path,
+        languages: &[String],
+        mode: OcrMode,
+    ) -> Result<OcrResult> {
+        match self.recognize_with(preferred, image_path, languages, mode) {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                let fallback = match preferred {
+                    OcrBackend::Tesseract => OcrBackend::Windows,
+                    OcrBackend::Windows => OcrBackend::Tesseract,
+                };
+                log::warn!("OCR backend {:?} failed ({}), falling back to {:?}", preferred, e, fallback);
+                self.recognize_with(fallback, image_path, languages, mode)
+                    .map_err(|fallback_err| anyhow!("{}; {}", e, fallback_err))
+            }
+        }
+    }
+
+    // バイト列はテンポラリファイルに書き出してから認識する
+    pub fn recognize_bytes(&self, data: &[u8], languages: &[String], mode: OcrMode) -> Result<OcrResult> {
+        let tmp = std::env::temp_dir().join(format!("snap-ocr-{}.img", Uuid::new_v4()));
+        fs::write(&tmp, data)?;
+        let result = self.recognize_file_as(&tmp, languages, mode);
+        let _ = fs::remove_file(&tmp);
+        result
+    }
+
+    fn recognize_with(&self, backend: OcrBackend, image_path: &Path, languages: &[String], mode: OcrMode) -> Result<OcrResult> {
+        match backend {
+            OcrBackend::Tesseract => self
+                .tesseract
+                .as_ref()
+                .ok_or_else(|| anyhow!("Tesseract is not available"))?
+                .recognize_file(image_path, languages, mode),
+            #[cfg(windows)]
+            OcrBackend::Windows => crate::windows_ocr::recognize_file(image_path, languages),
+            #[cfg(not(windows))]
+            OcrBackend::Windows => bail!("Windows OCR is only available on Windows"),
+        }
+    }
+}
+
+// スクリーンショットらしいか（画面の保存は PNG、カメラの写真は JPEG・HEIC）
+fn looks_like_screenshot(image_path: &Path) -> bool {
+    let mut header = [0u8; 8];
+    fs::File::open(image_path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| header == PNG_SIGNATURE)
+}
+
+// 回した画像での単語の位置を元の画像の座標に戻す（width・height は元の画像の大きさ）
+fn unrotate(word: &mut OcrWord, rotation: u32, width: u32, height: u32) {
+    let (left, top, w, h) = (word.left, word.top, word.width, word.height);
+    match rotation {
+        90 => {
+            word.left = top;
+            word.top = height.saturating_sub(left + w);
+            word.width = h;
+            word.height = w;
+        }
+        180 => {
+            word.left = width.saturating_sub(left + w);
+            word.top = height.saturating_sub(top + h);
+        }
+        270 => {
+            word.left = width.saturating_sub(top + h);
+            word.top = left;
+            word.width = h;
+            word.height = w;
+        }
+        _ => {}
+    }
+}
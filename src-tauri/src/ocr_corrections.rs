@@ -0,0 +1,263 @@
+use crate::ocr::{self, OcrWord};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// 同じ直しがこの回数に達したら、取り込み時の OCR の結果にも当てる
+const MIN_OCCURRENCES: u32 = 2;
+// 覚える語句の長さ（長い文章の書き直しは OCR の誤りの傾向とはみなさない）
+const MAX_ENTRY_CHARS: usize = 30;
+// 単語の確からしさ（0〜100）の段階の境目
+const HIGH_CONFIDENCE: f32 = 85.0;
+const LOW_CONFIDENCE: f32 = 60.0;
+
+// 画像上の範囲（OCR の単語と同じ画素単位）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OcrRegion {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OcrRegion {
+    // 単語の中心が範囲に入っていれば範囲内とみなす
+    pub fn contains(&self, word: &OcrWord) -> bool {
+        let x = word.left + word.width / 2;
+        let y = word.top + word.height / 2;
+        x >= self.left && x < self.left + self.width && y >= self.top && y < self.top + self.height
+    }
+}
+
+impl OcrRegion {
+    fn of(word: &OcrWord) -> Self {
+        OcrRegion {
+            left: word.left,
+            top: word.top,
+            width: word.width,
+            height: word.height,
+        }
+    }
+
+    // 両方を含む範囲
+    fn union(&self, other: &OcrRegion) -> Self {
+        let left = self.left.min(other.left);
+        let top = self.top.min(other.top);
+        OcrRegion {
+            left,
+            top,
+            width: (self.left + self.width).max(other.left + other.width) - left,
+            height: (self.top + self.height).max(other.top + other.height) - top,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfidenceLevel {
+    High,
+    Medium,
+    Low,
+}
+
+impl ConfidenceLevel {
+    fn of(confidence: f32) -> Self {
+        if confidence >= HIGH_CONFIDENCE {
+            ConfidenceLevel::High
+        } else if confidence >= LOW_CONFIDENCE {
+            ConfidenceLevel::Medium
+        } else {
+            ConfidenceLevel::Low
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlayWord {
+    #[serde(flatten)]
+    pub region: OcrRegion,
+    pub text: String,
+    pub confidence: f32,
+    pub level: ConfidenceLevel,
+    // ユーザーが直した範囲にある（確からしさに関わらず直す候補にしない）
+    pub corrected: bool,
+}
+
+// 画像に重ねて確からしさの低い所を色分けするためのデータ（座標は元の画像の画素単位）
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrOverlay {
+    pub width: u32,
+    pub height: u32,
+    pub mean_confidence: f32,
+    pub words: Vec<OverlayWord>,
+    // 確からしさの低い単語を同じ行で続くものごとにまとめた範囲（直してもらう候補、update_ocr_text の region に渡せる）
+    pub low_confidence_regions: Vec<OcrRegion>,
+}
+
+pub fn overlay(words: &[OcrWord], corrected_regions: &[OcrRegion], width: u32, height: u32) -> OcrOverlay {
+    let mean_confidence = ocr::mean_confidence(words);
+    let mut low_confidence_regions: Vec<OcrRegion> = Vec::new();
+    let mut previous_low: Option<(u32, u32)> = None;
+    let words = words
+        .iter()
+        .map(|word| {
+            let region = OcrRegion::of(word);
+            let corrected = corrected_regions.iter().any(|r| r.contains(word));
+            let level = ConfidenceLevel::of(word.confidence);
+            let line = (word.block, word.line);
+            if level == ConfidenceLevel::Low && !corrected {
+                match low_confidence_regions.last_mut() {
+                    Some(last) if previous_low == Some(line) => *last = last.union(&region),
+                    _ => low_confidence_regions.push(region),
+                }
+                previous_low = Some(line);
+            } else {
+                previous_low = None;
+            }
+            OverlayWord {
+                region,
+                text: word.text.clone(),
+                confidence: word.confidence,
+                level,
+                corrected,
+            }
+        })
+        .collect();
+    OcrOverlay {
+        width,
+        height,
+        mean_confidence,
+        words,
+        low_confidence_regions,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrectionEntry {
+    pub from: String,
+    pub to: String,
+    // ユーザーが同じ直しをした回数
+    pub count: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl CorrectionEntry {
+    pub fn is_active(&self) -> bool {
+        self.count >= MIN_OCCURRENCES
+    }
+}
+
+fn is_learnable(from: &str, to: &str) -> bool {
+    let from = from.trim();
+    let to = to.trim();
+    !from.is_empty() && from != to && from.chars().count() <= MAX_ENTRY_CHARS && to.chars().count() <= MAX_ENTRY_CHARS
+}
+
+// 直す前と後の文字から、語句の置き換えを取り出す
+// 行数が同じなら行ごとに比べ、空白で区切った語の数も同じなら語ごとに比べる
+pub fn replacements(original: &str, corrected: &str) -> Vec<(String, String)> {
+    let original_lines: Vec<&str> = original.lines().collect();
+    let corrected_lines: Vec<&str> = corrected.lines().collect();
+    if original_lines.len() != corrected_lines.len() {
+        return if is_learnable(original, corrected) {
+            vec![(original.trim().to_string(), corrected.trim().to_string())]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let mut pairs = Vec::new();
+    for (from, to) in original_lines.iter().zip(&corrected_lines).filter(|(a, b)| a != b) {
+        let from_words: Vec<&str> = from.split_whitespace().collect();
+        let to_words: Vec<&str> = to.split_whitespace().collect();
+        if from_words.len() > 1 && from_words.len() == to_words.len() {
+            for (a, b) in from_words.iter().zip(&to_words) {
+                if is_learnable(a, b) {
+                    pairs.push((a.to_string(), b.to_string()));
+                }
+            }
+        } else if is_learnable(from, to) {
+            pairs.push((from.trim().to_string(), to.trim().to_string()));
+        }
+    }
+    pairs
+}
+
+// OCR の直しから覚えた置き換え（ocr_corrections.json に保存）
+pub struct CorrectionDictionary {
+    path: PathBuf,
+    entries: Mutex<Vec<CorrectionEntry>>,
+}
+
+impl CorrectionDictionary {
+    pub fn new(path: PathBuf) -> Self {
+        CorrectionDictionary {
+            entries: Mutex::new(Self::load(&path)),
+            path,
+        }
+    }
+
+    fn load(path: &Path) -> Vec<CorrectionEntry> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, entries: &[CorrectionEntry]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(entries)?)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<CorrectionEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    // ユーザーの直しを記録する
+    pub fn learn(&self, original: &str, corrected: &str) -> Result<()> {
+        let pairs = replacements(original, corrected);
+        if pairs.is_empty() {
+            return Ok(());
+        }
+        let mut entries = self.entries.lock().unwrap();
+        for (from, to) in pairs {
+            match entries.iter_mut().find(|e| e.from == from && e.to == to) {
+                Some(entry) => {
+                    entry.count += 1;
+                    entry.updated_at = Utc::now();
+                }
+                None => entries.push(CorrectionEntry {
+                    from,
+                    to,
+                    count: 1,
+                    updated_at: Utc::now(),
+                }),
+            }
+        }
+        self.save(&entries)
+    }
+
+    pub fn delete(&self, from: &str) -> Result<bool> {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|e| e.from != from);
+        if entries.len() == before {
+            return Ok(false);
+        }
+        self.save(&entries)?;
+        Ok(true)
+    }
+
+    // 繰り返し直された語句を置き換える（長い語句から順に当てる）
+    pub fn apply(&self, text: &str) -> String {
+        let mut active: Vec<CorrectionEntry> = self.list().into_iter().filter(CorrectionEntry::is_active).collect();
+        active.sort_by_key(|e| std::cmp::Reverse(e.from.chars().count()));
+        active.iter().fold(text.to_string(), |text, e| text.replace(&e.from, &e.to))
+    }
+}
@@ -0,0 +1,349 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+// 手書きの線が細すぎないよう、長い辺がこれより小さい画像は拡大する
+const HANDWRITING_MIN_LONG_SIDE: u32 = 2000;
+const MAX_UPSCALE: f32 = 3.0;
+// 照明のむらを求めるときの縮小率とぼかしの強さ
+const BACKGROUND_DOWNSCALE: u32 = 16;
+const BACKGROUND_BLUR_SIGMA: f32 = 2.0;
+// コントラストを広げるときに黒とみなす暗い側の割合
+const BLACK_POINT_PERCENTILE: f32 = 0.02;
+// 縦書きかを調べるときに縮小する大きさと、文字のない列・行とみなすインクの割合
+const LAYOUT_SIDE: u32 = 1000;
+const BLANK_INK_RATIO: f32 = 0.01;
+// 文字のない列の割合が、文字のない行の割合のこの倍より多ければ縦書き（行間より列の間が多い）
+const VERTICAL_GAP_RATIO: f32 = 1.5;
+const MIN_GAP_FRACTION: f32 = 0.1;
+// ホワイトボードの前処理: この明るさより明るい（割った後の）画素は白にする
+const WHITEBOARD_WHITE_POINT: f32 = 215.0;
+// マーカーの色を強める倍率
+const MARKER_SATURATION: f32 = 1.4;
+// ボードの四隅を探すときに縮小する大きさと、台形補正するボードの面積の割合（画像全体に対して）
+const BOARD_SEARCH_SIDE: u32 = 400;
+const MIN_BOARD_AREA: f32 = 0.2;
+const MAX_BOARD_AREA: f32 = 0.95;
+
+// OCR の前に画像に当てる処理（読み取りに失敗したアイテムを別の処理で読み直すときに選ぶ）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreprocessPreset {
+    // 元の画像のまま
+    #[default]
+    None,
+    // 影や照明のむらを消してコントラストを広げる（斜めから撮った書類など）
+    Document,
+    // 白黒の2値にする（薄い印刷・感熱紙のレシートなど）
+    Binarize,
+    Handwriting,
+    // 会議のホワイトボード（照り返しと背景のむらを消して白くし、マーカーの色は残す）
+    Whiteboard,
+}
+
+// 前処理した画像と、元の画像に対する拡大率（認識した単語の位置を元の座標に戻すのに使う）
+pub struct Preprocessed {
+    pub image: GrayImage,
+    pub scale: f32,
+}
+
+// 紙の背景をぼかして求め、画素をそれで割って影や照明のむらを消す
+fn flatten_background(gray: &GrayImage) -> GrayImage {
+    let (width, height) = gray.dimensions();
+    let small = imageops::resize(
+        gray,
+        (width / BACKGROUND_DOWNSCALE).max(1),
+        (height / BACKGROUND_DOWNSCALE).max(1),
+        FilterType::Triangle,
+    );
+    let background = imageops::resize(&imageops::blur(&small, BACKGROUND_BLUR_SIGMA), width, height, FilterType::Triangle);
+    GrayImage::from_fn(width, height, |x, y| {
+        let value = gray.get_pixel(x, y)[0] as f32;
+        let paper = background.get_pixel(x, y)[0].max(1) as f32;
+        Luma([(value / paper * 255.0).min(255.0) as u8])
+    })
+}
+
+// 暗い側の数%を黒に合わせてコントラストを広げる（薄い鉛筆の線を濃くする）
+fn stretch_contrast(gray: &mut GrayImage) {
+    let mut histogram = [0u64; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let threshold = (gray.len() as f32 * BLACK_POINT_PERCENTILE) as u64;
+    let mut count = 0;
+    let black = histogram
+        .iter()
+        .position(|&n| {
+            count += n;
+            count > threshold
+        })
+        .unwrap_or(0) as f32;
+    if black >= 254.0 {
+        return;
+    }
+    for pixel in gray.pixels_mut() {
+        pixel[0] = ((pixel[0] as f32 - black).max(0.0) / (255.0 - black) * 255.0) as u8;
+    }
+}
+
+// 明るさのヒストグラムから白と黒を分ける閾値を求める（大津の方法）
+fn otsu_threshold(gray: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let total = gray.len() as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(i, &n)| i as f64 * n as f64).sum();
+    let (mut background, mut background_sum) = (0.0, 0.0);
+    let (mut best, mut best_variance) = (0u8, 0.0);
+    for (i, &n) in histogram.iter().enumerate() {
+        background += n as f64;
+        background_sum += i as f64 * n as f64;
+        let foreground = total - background;
+        if background == 0.0 || foreground == 0.0 {
+            continue;
+        }
+        let mean_background = background_sum / background;
+        let mean_foreground = (sum - background_sum) / foreground;
+        let variance = background * foreground * (mean_background - mean_foreground).powi(2);
+        if variance > best_variance {
+            best = i as u8;
+            best_variance = variance;
+        }
+    }
+    best
+}
+
+// 書類向けの前処理（グレースケール・照明のむらの除去・コントラストの強調）
+pub fn document(image: &DynamicImage) -> Preprocessed {
+    let mut flattened = flatten_background(&image.to_luma8());
+    stretch_contrast(&mut flattened);
+    Preprocessed { image: flattened, scale: 1.0 }
+}
+
+// 照明のむらを消してから白黒の2値にする
+pub fn binarize(image: &DynamicImage) -> Preprocessed {
+    let mut flattened = flatten_background(&image.to_luma8());
+    let threshold = otsu_threshold(&flattened);
+    for pixel in flattened.pixels_mut() {
+        pixel[0] = if pixel[0] > threshold { 255 } else { 0 };
+    }
+    Preprocessed { image: flattened, scale: 1.0 }
+}
+
+// 選んだ前処理を当てる（None なら元の画像をそのまま使う）
+pub fn apply(image: &DynamicImage, preset: PreprocessPreset) -> Option<Preprocessed> {
+    match preset {
+        PreprocessPreset::None => None,
+        PreprocessPreset::Document => Some(document(image)),
+        PreprocessPreset::Binarize => Some(binarize(image)),
+        PreprocessPreset::Handwriting => Some(handwriting(image)),
+        // OCR では単語の位置が元の画像とずれないよう台形補正はしない
+        PreprocessPreset::Whiteboard => Some(Preprocessed {
+            image: DynamicImage::ImageRgb8(whiten_board(&image.to_rgb8())).to_luma8(),
+            scale: 1.0,
+        }),
+    }
+}
+
+// 文字の範囲の中で、インクのほとんどない列（縦の隙間）と行（横の隙間）の割合
+fn gap_fractions(binary: &GrayImage) -> Option<(f32, f32)> {
+    let (width, height) = binary.dimensions();
+    let mut columns = vec![0u32; width as usize];
+    let mut rows = vec![0u32; height as usize];
+    for (x, y, pixel) in binary.enumerate_pixels() {
+        if pixel[0] == 0 {
+            columns[x as usize] += 1;
+            rows[y as usize] += 1;
+        }
+    }
+    // 余白を除いた文字のある範囲だけを数える
+    let fraction = |ink: &[u32], length: u32| -> Option<f32> {
+        let limit = (length as f32 * BLANK_INK_RATIO).ceil() as u32;
+        let first = ink.iter().position(|&n| n > limit)?;
+        let last = ink.iter().rposition(|&n| n > limit)?;
+        let span = &ink[first..=last];
+        Some(span.iter().filter(|&&n| n <= limit).count() as f32 / span.len() as f32)
+    };
+    Some((fraction(&columns, height)?, fraction(&rows, width)?))
+}
+
+// 縦書きの文章らしいか（文字の列の間の隙間が行の間の隙間より目立つ）
+// 横書きの文章を90度回して撮った画像も縦書きに見えるため、向きは別に調べる
+pub fn looks_vertical(image: &DynamicImage) -> bool {
+    let binary = binarize(&image.thumbnail(LAYOUT_SIDE, LAYOUT_SIDE)).image;
+    match gap_fractions(&binary) {
+        Some((column_gaps, row_gaps)) => column_gaps >= MIN_GAP_FRACTION && column_gaps > row_gaps * VERTICAL_GAP_RATIO,
+        None => false,
+    }
+}
+
+// 時計回りに回す（90度単位、それ以外はそのまま）
+pub fn rotate(image: &DynamicImage, degrees: u32) -> DynamicImage {
+    match degrees % 360 {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => image.clone(),
+    }
+}
+
+// 手書きのメモ向けの前処理（グレースケール・拡大・照明のむらの除去・コントラストの強調）
+pub fn handwriting(image: &DynamicImage) -> Preprocessed {
+    let long_side = image.width().max(image.height()).max(1);
+    let scale = (HANDWRITING_MIN_LONG_SIDE as f32 / long_side as f32).clamp(1.0, MAX_UPSCALE);
+    let gray = image.to_luma8();
+    let gray = if scale > 1.0 {
+        let width = (image.width() as f32 * scale).round() as u32;
+        let height = (image.height() as f32 * scale).round() as u32;
+        imageops::resize(&gray, width, height, FilterType::CatmullRom)
+    } else {
+        gray
+    };
+    let mut flattened = flatten_background(&gray);
+    stretch_contrast(&mut flattened);
+    Preprocessed { image: flattened, scale }
+}
+
+// 色ごとにボードの背景を求めて割り、照り返しやむら・色かぶりを消して背景を白くする
+// 白に近い画素は白にそろえ、マーカーの色は濃くする
+fn whiten_board(rgb: &RgbImage) -> RgbImage {
+    let channels: Vec<GrayImage> = (0..3)
+        .map(|c| {
+            let channel = GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| Luma([rgb.get_pixel(x, y)[c]]));
+            flatten_background(&channel)
+        })
+        .collect();
+    RgbImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+        let [r, g, b] = [0, 1, 2].map(|c| channels[c].get_pixel(x, y)[0] as f32);
+        if r.min(g).min(b) > WHITEBOARD_WHITE_POINT {
+            return Rgb([255, 255, 255]);
+        }
+        let gray = (r + g + b) / 3.0;
+        // 白い点を WHITE_POINT に合わせて濃さを広げ、灰色からの差を広げて色を強める
+        Rgb([r, g, b].map(|v| {
+            let v = gray + (v - gray) * MARKER_SATURATION;
+            (v / WHITEBOARD_WHITE_POINT * 255.0).clamp(0.0, 255.0) as u8
+        }))
+    })
+}
+
+// 明るい領域（ボード）の四隅を探す（左上・右上・右下・左下、元の画像の座標）
+// ボードが小さすぎる・画像のほぼ全体なら None
+fn board_corners(image: &DynamicImage) -> Option<[(f32, f32); 4]> {
+    let small = image.thumbnail(BOARD_SEARCH_SIDE, BOARD_SEARCH_SIDE).to_luma8();
+    let threshold = otsu_threshold(&small);
+    let mut corners = [(0u32, 0u32); 4];
+    let mut extremes = [i64::MAX, i64::MIN, i64::MIN, i64::MAX];
+    let mut found = false;
+    for (x, y, pixel) in small.enumerate_pixels() {
+        if pixel[0] <= threshold {
+            continue;
+        }
+        found = true;
+        let (sum, diff) = (x as i64 + y as i64, x as i64 - y as i64);
+        // 左上は x + y が最小、右上は x - y が最大、右下は x + y が最大、左下は x - y が最小
+        let candidates = [(0, sum, true), (1, diff, false), (2, sum, false), (3, diff, true)];
+        for (i, value, smaller) in candidates {
+            if (smaller && value < extremes[i]) || (!smaller && value > extremes[i]) {
+                extremes[i] = value;
+                corners[i] = (x, y);
+            }
+        }
+    }
+    if !found {
+        return None;
+    }
+    // 四角形の面積（靴ひもの公式）
+    let area = (0..4)
+        .map(|i| {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            a.0 as f32 * b.1 as f32 - b.0 as f32 * a.1 as f32
+        })
+        .sum::<f32>()
+        .abs()
+        / 2.0;
+    let ratio = area / small.len() as f32;
+    if !(MIN_BOARD_AREA..=MAX_BOARD_AREA).contains(&ratio) {
+        return None;
+    }
+    let scale_x = image.width() as f32 / small.width() as f32;
+    let scale_y = image.height() as f32 / small.height() as f32;
+    Some(corners.map(|(x, y)| ((x as f32 + 0.5) * scale_x, (y as f32 + 0.5) * scale_y)))
+}
+
+// 出力の長方形の4点を corners に移す射影変換の係数を求める（8元連立方程式をガウスの消去法で解く）
+fn homography(width: f32, height: f32, corners: &[(f32, f32); 4]) -> Option<[f64; 8]> {
+    let targets = [(0.0, 0.0), (width as f64, 0.0), (width as f64, height as f64), (0.0, height as f64)];
+    let mut rows = [[0.0f64; 9]; 8];
+    for (i, (&(u, v), &(x, y))) in targets.iter().zip(corners.iter()).enumerate() {
+        let (x, y) = (x as f64, y as f64);
+        rows[i * 2] = [u, v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x, x];
+        rows[i * 2 + 1] = [0.0, 0.0, 0.0, u, v, 1.0, -u * y, -v * y, y];
+    }
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&a, &b| rows[a][col].abs().total_cmp(&rows[b][col].abs()))?;
+        if rows[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        rows.swap(col, pivot);
+        let pivot_row = rows[col];
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != col {
+                let factor = row[col] / pivot_row[col];
+                for (value, pivot) in row.iter_mut().zip(pivot_row.iter()).skip(col) {
+                    *value -= factor * pivot;
+                }
+            }
+        }
+    }
+    let mut h = [0.0; 8];
+    for (i, value) in h.iter_mut().enumerate() {
+        *value = rows[i][8] / rows[i][i];
+    }
+    Some(h)
+}
+
+// 周りの4画素から補間して色を取る
+fn sample(rgb: &RgbImage, x: f32, y: f32) -> Rgb<u8> {
+    let max_x = rgb.width() as f32 - 1.0;
+    let max_y = rgb.height() as f32 - 1.0;
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    let (x0, y0) = (x.floor(), y.floor());
+    let (x1, y1) = ((x0 + 1.0).min(max_x), (y0 + 1.0).min(max_y));
+    let (fx, fy) = (x - x0, y - y0);
+    let pixel = |px: f32, py: f32| rgb.get_pixel(px as u32, py as u32);
+    let (a, b, c, d) = (pixel(x0, y0), pixel(x1, y0), pixel(x0, y1), pixel(x1, y1));
+    Rgb([0, 1, 2].map(|i| {
+        let top = a[i] as f32 * (1.0 - fx) + b[i] as f32 * fx;
+        let bottom = c[i] as f32 * (1.0 - fx) + d[i] as f32 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    }))
+}
+
+// 斜めから撮ったボードを正面から見た長方形に直す（ボードが見つからなければ None）
+fn correct_keystone(image: &DynamicImage) -> Option<RgbImage> {
+    let corners = board_corners(image)?;
+    let distance = |a: (f32, f32), b: (f32, f32)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+    let width = distance(corners[0], corners[1]).max(distance(corners[3], corners[2])).round();
+    let height = distance(corners[0], corners[3]).max(distance(corners[1], corners[2])).round();
+    if width < 1.0 || height < 1.0 {
+        return None;
+    }
+    let h = homography(width, height, &corners)?;
+    let rgb = image.to_rgb8();
+    Some(RgbImage::from_fn(width as u32, height as u32, |u, v| {
+        let (u, v) = (u as f64 + 0.5, v as f64 + 0.5);
+        let w = h[6] * u + h[7] * v + 1.0;
+        let x = (h[0] * u + h[1] * v + h[2]) / w;
+        let y = (h[3] * u + h[4] * v + h[5]) / w;
+        sample(&rgb, x as f32 - 0.5, y as f32 - 0.5)
+    }))
+}
+
+// ホワイトボードを撮った写真を共有できる画像に整える（台形補正・照り返しの除去・背景の白色化）
+pub fn whiteboard(image: &DynamicImage) -> DynamicImage {
+    let board = correct_keystone(image).unwrap_or_else(|| image.to_rgb8());
+    DynamicImage::ImageRgb8(whiten_board(&board))
+}
@@ -0,0 +1,133 @@
+use crate::jobs::ProgressReporter;
+use crate::markdown_export::sanitize;
+use crate::metadata_store::{GroupRecord, ItemRecord};
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_TEMPLATE: &str = "{year}/{month}/{tag}";
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrganizeMode {
+    #[default]
+    Copy,
+    // 容量を使わないハードリンク（別ドライブなどで作れない場合はコピーする）
+    HardLink,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OrganizeReport {
+    pub copied: usize,
+    pub linked: usize,
+    // 同じファイルが既にある
+    pub skipped: usize,
+    pub failed: Vec<serde_json::Value>,
+    pub dest: String,
+}
+
+// フォルダ構成のテンプレートを展開する
+// 使える項目: {year} {month} {day} {tag}（最初のタグ） {tags}（すべてのタグ） {group} {location}
+fn render_template(template: &str, item: &ItemRecord, groups: &HashMap<String, GroupRecord>) -> PathBuf {
+    let created = item.created_at.with_timezone(&Local);
+    let or = |value: Option<String>, fallback: &str| {
+        value
+            .map(|v| sanitize(&v))
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| fallback.to_string())
+    };
+    let values = [
+        ("{year}", created.year().to_string()),
+        ("{month}", format!("{:02}", created.month())),
+        ("{day}", format!("{:02}", created.day())),
+        ("{tag}", or(item.tags.first().cloned(), "untagged")),
+        ("{tags}", or(Some(item.tags.join("-")), "untagged")),
+        (
+            "{group}",
+            or(item.group_id.as_ref().and_then(|id| groups.get(id)).map(|g| g.title.clone()), "ungrouped"),
+        ),
+        ("{location}", or(item.location_name.clone(), "unknown")),
+    ];
+
+    template
+        .split(['/', '\\'])
+        .filter(|segment| !segment.trim().is_empty())
+        .map(|segment| {
+            let mut rendered = segment.to_string();
+            for (key, value) in &values {
+                rendered = rendered.replace(key, value);
+            }
+            // テンプレート以外の部分もフォルダ名として使えるようにする（.. で保存先の外に出さない）
+            let rendered = sanitize(&rendered);
+            if rendered.is_empty() { "_".to_string() } else { rendered }
+        })
+        .collect()
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.len() == b.len(),
+        _ => false,
+    }
+}
+
+// 条件に合うアイテムの元画像を、メタデータから決めたフォルダ構成でコピー（またはハードリンク）する
+pub fn organize_to_folders(
+    items: &[ItemRecord],
+    groups: &[GroupRecord],
+    template: &str,
+    dest: &Path,
+    mode: OrganizeMode,
+    reporter: &dyn ProgressReporter,
+) -> Result<OrganizeReport> {
+    if template.trim().is_empty() {
+        bail!("Folder template is empty");
+    }
+    fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    let groups: HashMap<String, GroupRecord> = groups.iter().map(|g| (g.id.clone(), g.clone())).collect();
+    let mut report = OrganizeReport {
+        dest: dest.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    reporter.set_total(items.len() as u64);
+
+    for (i, item) in items.iter().enumerate() {
+        reporter.checkpoint()?;
+        let source = match item.image_path.as_deref().map(Path::new).filter(|p| p.is_file()) {
+            Some(source) => source,
+            None => {
+                reporter.progress(i as u64 + 1, &item.id);
+                continue;
+            }
+        };
+        let result = (|| -> Result<()> {
+            let dir = dest.join(render_template(template, item, &groups));
+            fs::create_dir_all(&dir)?;
+            let ext = source.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            let date = item.created_at.with_timezone(&Local).format("%Y-%m-%d");
+            let short_id: String = item.id.chars().take(8).collect();
+            let target = dir.join(format!("{}_{}.{}", date, short_id, ext));
+
+            if target.exists() && same_file(source, &target) {
+                report.skipped += 1;
+                return Ok(());
+            }
+            let _ = fs::remove_file(&target);
+            if matches!(mode, OrganizeMode::HardLink) && fs::hard_link(source, &target).is_ok() {
+                report.linked += 1;
+            } else {
+                fs::copy(source, &target).with_context(|| format!("Failed to copy to {}", target.display()))?;
+                report.copied += 1;
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            report.failed.push(serde_json::json!({ "item_id": item.id, "error": e.to_string() }));
+        }
+        reporter.progress(i as u64 + 1, &item.id);
+    }
+    Ok(report)
+}
@@ -0,0 +1,140 @@
+use crate::jobs::ProgressReporter;
+use crate::metadata_store::MetadataStore;
+use crate::paths::LibraryPaths;
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// 取り込み中のファイルを消さないよう、これより新しいファイルは対象にしない
+const GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OrphanReport {
+    // orphaned_files へ移した元画像
+    pub image_files: Vec<String>,
+    pub image_bytes: u64,
+    // 削除したサムネイル（再生成できるため移さない）
+    pub thumbnail_files: usize,
+    pub thumbnail_bytes: u64,
+    pub reclaimed_bytes: u64,
+    // 元画像の移動先（ゴミ箱の保持期間を過ぎると削除される）
+    pub trash_dir: Option<String>,
+}
+
+fn is_old_enough(metadata: &fs::Metadata) -> bool {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|m| SystemTime::now().duration_since(m).ok())
+        .is_some_and(|age| age >= GRACE_PERIOD)
+}
+
+// 猶予期間を過ぎたファイルの一覧 (パス, サイズ)
+fn old_files(dir: &Path) -> Vec<(PathBuf, u64)> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            (metadata.is_file() && is_old_enough(&metadata)).then(|| (entry.path(), metadata.len()))
+        })
+        .collect()
+}
+
+// サムネイルのファイル名（{元画像のハッシュ}_{サイズ}.jpg）からハッシュを取り出す
+fn thumbnail_source_hash(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    name.split_once('_').map(|(hash, _)| hash)
+}
+
+// どのアイテムからも参照されていない元画像を orphaned_files へ移し、サムネイルは削除する
+// 元画像の参照の確認から移動まではストアをロックしたままにして、取り込みと競合しないようにする
+pub fn collect_orphans(
+    store: &Mutex<Option<MetadataStore>>,
+    paths: &LibraryPaths,
+    reporter: &dyn ProgressReporter,
+) -> Result<OrphanReport> {
+    let store = store.lock().unwrap();
+    let (image_paths, content_hashes) = store
+        .as_ref()
+        .context("Metadata store not initialized")?
+        .image_references()?;
+    let image_paths: HashSet<PathBuf> = image_paths.into_iter().map(PathBuf::from).collect();
+
+    let images = old_files(&paths.images_dir());
+    let thumbnails = old_files(&paths.thumbnails_dir());
+    reporter.set_total((images.len() + thumbnails.len()) as u64);
+
+    let mut report = OrphanReport::default();
+    let trash_dir = paths.orphaned_files_dir().join(Utc::now().format("%Y%m%d-%H%M%S").to_string());
+    let mut done = 0;
+
+    for (path, bytes) in images {
+        reporter.checkpoint()?;
+        done += 1;
+        if image_paths.contains(&path) {
+            continue;
+        }
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        fs::create_dir_all(&trash_dir)?;
+        let dest = trash_dir.join(name);
+        if let Err(e) = fs::rename(&path, &dest) {
+            log::warn!("Failed to move {}: {}", path.display(), e);
+            continue;
+        }
+        reporter.progress(done, &name.to_string_lossy());
+        report.image_files.push(path.to_string_lossy().to_string());
+        report.image_bytes += bytes;
+    }
+
+    drop(store);
+
+    for (path, bytes) in thumbnails {
+        reporter.checkpoint()?;
+        done += 1;
+        let referenced = thumbnail_source_hash(&path).is_some_and(|hash| content_hashes.contains(hash));
+        if referenced {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                report.thumbnail_files += 1;
+                report.thumbnail_bytes += bytes;
+            }
+            Err(e) => log::warn!("Failed to remove {}: {}", path.display(), e),
+        }
+        reporter.progress(done, "");
+    }
+
+    report.reclaimed_bytes = report.image_bytes + report.thumbnail_bytes;
+    if !report.image_files.is_empty() {
+        report.trash_dir = Some(trash_dir.to_string_lossy().to_string());
+    }
+    Ok(report)
+}
+
+// 保持期間を過ぎた orphaned_files 以下のフォルダを削除する
+pub fn purge_expired(paths: &LibraryPaths, retention_days: u32) -> Result<usize> {
+    if retention_days == 0 {
+        return Ok(0);
+    }
+    let cutoff = Utc::now() - Duration::days(retention_days as i64);
+    let cutoff = cutoff.format("%Y%m%d-%H%M%S").to_string();
+    let mut purged = 0;
+    for entry in fs::read_dir(paths.orphaned_files_dir()).into_iter().flatten().flatten() {
+        let expired = entry.file_name().to_str().is_some_and(|name| name < cutoff.as_str());
+        if expired && entry.path().is_dir() {
+            fs::remove_dir_all(entry.path())?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
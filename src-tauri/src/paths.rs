@@ -0,0 +1,246 @@
+use crate::libraries::ActiveLibrary;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+// 取り込み・配信の対象とする画像の拡張子
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff", "heic"];
+
+pub fn is_image_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// 実行ファイルの隣にこの名前のファイルがあればポータブルモード（データもすべて実行ファイルの隣に置く）
+const PORTABLE_MARKER: &str = "portable";
+const PORTABLE_DATA_DIR: &str = "SnapOrganizerData";
+
+// ポータブルモードのデータフォルダ（環境変数 SNAP_ORGANIZER_PORTABLE でも有効になる）
+pub fn portable_data_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let exe_dir = exe.parent()?;
+    let enabled = exe_dir.join(PORTABLE_MARKER).exists() || std::env::var_os("SNAP_ORGANIZER_PORTABLE").is_some();
+    enabled.then(|| exe_dir.join(PORTABLE_DATA_DIR))
+}
+
+pub fn is_portable() -> bool {
+    portable_data_dir().is_some()
+}
+
+// ライブラリ一覧と既定のライブラリを置くフォルダ
+pub fn app_data_root(app_handle: &AppHandle) -> Result<PathBuf> {
+    if let Some(dir) = portable_data_dir() {
+        return Ok(dir);
+    }
+    app_handle.path().app_data_dir().context("App data directory not found")
+}
+
+// ライブラリのデータ配置（インデックス・画像・キャッシュなど）
+#[derive(Debug, Clone)]
+pub struct LibraryPaths {
+    root: PathBuf,
+}
+
+impl LibraryPaths {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LibraryPaths { root: root.into() }
+    }
+
+    // 起動時に選ばれたライブラリ（未選択なら既定のデータディレクトリ）
+    pub fn from_app(app_handle: &AppHandle) -> Result<Self> {
+        if let Some(active) = app_handle.try_state::<ActiveLibrary>() {
+            return Ok(Self::new(active.0.clone()));
+        }
+        Ok(Self::new(app_data_root(app_handle)?))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn index_dir(&self) -> PathBuf {
+        self.root.join("search_index")
+    }
+
+    pub fn metadata_db_file(&self) -> PathBuf {
+        self.root.join("library.db")
+    }
+
+    pub fn thumbnails_dir(&self) -> PathBuf {
+        self.root.join("thumbnails")
+    }
+
+    // resize_image の結果（バックアップには含めない）
+    pub fn resize_cache_dir(&self) -> PathBuf {
+        self.root.join("cache").join("resized")
+    }
+
+    pub fn images_dir(&self) -> PathBuf {
+        self.root.join("images")
+    }
+
+    // 非公開アイテムの鍵（パスコードで暗号化してある）
+    pub fn private_key_file(&self) -> PathBuf {
+        self.root.join("private_key.json")
+    }
+
+    // 日ごとのログファイル
+    pub fn logs_dir(&self) -> PathBuf {
+        self.root.join("logs")
+    }
+
+    // どのアイテムからも参照されていなかったファイル（ゴミ箱と同じ保持期間で削除する）
+    pub fn orphaned_files_dir(&self) -> PathBuf {
+        self.root.join("orphaned_files")
+    }
+
+    pub fn tessdata_dir(&self) -> PathBuf {
+        self.root.join("tessdata")
+    }
+
+    // GeoNames の地名データ（cities*.txt と admin1CodesASCII.txt）
+    pub fn geonames_dir(&self) -> PathBuf {
+        self.root.join("geonames")
+    }
+
+    // 端末内の推論で使う ONNX モデル
+    pub fn models_dir(&self) -> PathBuf {
+        self.root.join("models")
+    }
+
+    // 画像の埋め込みの近傍探索の索引（意味での検索用）
+    pub fn vector_index_file(&self) -> PathBuf {
+        self.root.join("vectors.hnsw")
+    }
+
+    pub fn geocoding_settings_file(&self) -> PathBuf {
+        self.root.join("geocoding.json")
+    }
+
+    pub fn translation_settings_file(&self) -> PathBuf {
+        self.root.join("translation.json")
+    }
+
+    // 取得した為替レート（レシートの金額の換算に使う）
+    pub fn exchange_rates_file(&self) -> PathBuf {
+        self.root.join("exchange_rates.json")
+    }
+
+    pub fn jobs_file(&self) -> PathBuf {
+        self.root.join("jobs.json")
+    }
+
+    pub fn ocr_settings_file(&self) -> PathBuf {
+        self.root.join("ocr_settings.json")
+    }
+
+    pub fn watch_config_file(&self) -> PathBuf {
+        self.root.join("watch_folders.json")
+    }
+
+    pub fn trash_settings_file(&self) -> PathBuf {
+        self.root.join("trash_settings.json")
+    }
+
+    // タグごとの保持ルール
+    pub fn retention_settings_file(&self) -> PathBuf {
+        self.root.join("retention.json")
+    }
+
+    pub fn quick_capture_settings_file(&self) -> PathBuf {
+        self.root.join("quick_capture.json")
+    }
+
+    // アプリ全体の設定（表示・並び順など）
+    pub fn app_settings_file(&self) -> PathBuf {
+        self.root.join("settings.json")
+    }
+
+    pub fn rules_file(&self) -> PathBuf {
+        self.root.join("rules.json")
+    }
+
+    // メモに差し込むテンプレート
+    pub fn memo_templates_file(&self) -> PathBuf {
+        self.root.join("memo_templates.json")
+    }
+
+    // OCR の直しから覚えた置き換え
+    pub fn ocr_corrections_file(&self) -> PathBuf {
+        self.root.join("ocr_corrections.json")
+    }
+
+    // 同期設定と OAuth トークンは認証情報を含むためバックアップには含めない
+    pub fn sync_config_file(&self) -> PathBuf {
+        self.root.join("sync.json")
+    }
+
+    pub fn oauth_tokens_file(&self) -> PathBuf {
+        self.root.join("oauth_tokens.json")
+    }
+
+    // アプリのロック（パスコードの確認用の値、バックアップには含めない）
+    pub fn app_lock_file(&self) -> PathBuf {
+        self.root.join("app_lock.json")
+    }
+
+    // SMTP のパスワードを含むためバックアップには含めない
+    pub fn smtp_settings_file(&self) -> PathBuf {
+        self.root.join("smtp.json")
+    }
+
+    // REST API の設定（トークンを含むためバックアップには含めない）
+    pub fn api_settings_file(&self) -> PathBuf {
+        self.root.join("api_server.json")
+    }
+
+    // 取り込み時に呼ぶ外部プラグイン（plugins/<フォルダ>/plugin.json）
+    pub fn plugins_dir(&self) -> PathBuf {
+        self.root.join("plugins")
+    }
+
+    // 有効にしたプラグイン（共有されたバックアップから勝手に有効にならないようバックアップには含めない）
+    pub fn plugin_settings_file(&self) -> PathBuf {
+        self.root.join("plugins.json")
+    }
+
+    // Webhook の送信先（URL に認証情報を含むことがあるためバックアップには含めない）
+    pub fn webhooks_file(&self) -> PathBuf {
+        self.root.join("webhooks.json")
+    }
+
+    // LAN 同期の証明書・ペアリング情報・公開用ストア（バックアップには含めない）
+    pub fn lan_sync_dir(&self) -> PathBuf {
+        self.root.join("lan_sync")
+    }
+
+    // スマホから受信したファイルや他のアプリから取り出した画像を、取り込むまで一時的に置く場所
+    pub fn staging_dir(&self) -> PathBuf {
+        self.root.join("staging")
+    }
+
+    // ドラッグで他のアプリへ渡すために書き出した一時ファイル
+    pub fn drag_out_dir(&self) -> PathBuf {
+        self.staging_dir().join("drag-out")
+    }
+
+    // バックアップ対象の設定ファイル
+    pub fn settings_files(&self) -> Vec<PathBuf> {
+        vec![
+            self.ocr_settings_file(),
+            self.watch_config_file(),
+            self.trash_settings_file(),
+            self.retention_settings_file(),
+            self.quick_capture_settings_file(),
+            self.geocoding_settings_file(),
+            self.translation_settings_file(),
+            self.app_settings_file(),
+            self.rules_file(),
+            self.memo_templates_file(),
+            self.ocr_corrections_file(),
+            self.private_key_file(),
+        ]
+    }
+}
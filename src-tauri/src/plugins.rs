@@ -0,0 +1,280 @@
+use crate::metadata_store::ItemRecord;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const MANIFEST_FILE: &str = "plugin.json";
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+// プラグインを呼ぶタイミング
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginHook {
+    // OCR の直後（自動タグ付けルールより前なので、文字の補正がルールに反映される）
+    PostOcr,
+    // メタデータストアと検索インデックスに登録する直前
+    PreIndex,
+    // 登録した後（返した変更は更新として記録される）
+    PostImport,
+}
+
+// plugins/<フォルダ>/plugin.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // 実行ファイル（相対パスはプラグインのフォルダから）
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub hooks: Vec<PluginHook>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub id: String,
+    #[serde(flatten)]
+    pub manifest: PluginManifest,
+    pub dir: String,
+    pub enabled: bool,
+}
+
+// プラグインが標準出力に返す変更（省略した項目はそのまま）
+#[derive(Debug, Default, Deserialize)]
+struct PluginOutput {
+    #[serde(default)]
+    add_tags: Vec<String>,
+    ocr_text: Option<String>,
+    memo: Option<String>,
+    location_name: Option<String>,
+}
+
+impl PluginOutput {
+    fn apply(self, item: &mut ItemRecord) {
+        for tag in self.add_tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !item.tags.iter().any(|t| t == tag) {
+                item.tags.push(tag.to_string());
+            }
+        }
+        if let Some(text) = self.ocr_text {
+            item.ocr_text = text;
+        }
+        if let Some(memo) = self.memo {
+            item.memo = memo;
+        }
+        if let Some(name) = self.location_name {
+            item.location_name = Some(name).filter(|n| !n.trim().is_empty());
+        }
+    }
+}
+
+// 有効にしたプラグイン（ライブラリを共有しても勝手に実行されないよう、置いただけでは有効にしない）
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PluginSettings {
+    #[serde(default)]
+    enabled: HashSet<String>,
+}
+
+#[derive(Clone)]
+struct Plugin {
+    id: String,
+    dir: PathBuf,
+    manifest: PluginManifest,
+}
+
+// 外部の実行ファイルをアイテムの JSON を標準入力に渡して呼び出す
+// 入力: { "hook": "post_ocr", "item": {...} } / 出力: { "add_tags": [...], "ocr_text": "...", ... }（何も変えないなら空でよい）
+pub struct PluginHost {
+    dir: PathBuf,
+    settings_file: PathBuf,
+    plugins: Mutex<Vec<Plugin>>,
+    enabled: Mutex<HashSet<String>>,
+}
+
+impl PluginHost {
+    pub fn new(dir: PathBuf, settings_file: PathBuf) -> Self {
+        let settings: PluginSettings = fs::read_to_string(&settings_file)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let host = PluginHost {
+            plugins: Mutex::new(Vec::new()),
+            enabled: Mutex::new(settings.enabled),
+            dir,
+            settings_file,
+        };
+        host.reload();
+        host
+    }
+
+    // plugins フォルダを読み直す（読めないマニフェストは飛ばす）
+    pub fn reload(&self) {
+        let mut plugins = Vec::new();
+        for entry in fs::read_dir(&self.dir).into_iter().flatten().flatten() {
+            let dir = entry.path();
+            let manifest_path = dir.join(MANIFEST_FILE);
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let manifest = fs::read_to_string(&manifest_path)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| Ok(serde_json::from_str::<PluginManifest>(&json)?));
+            match manifest {
+                Ok(manifest) => plugins.push(Plugin {
+                    id: entry.file_name().to_string_lossy().to_string(),
+                    dir,
+                    manifest,
+                }),
+                Err(e) => log::warn!("Skipping plugin {}: {}", manifest_path.display(), e),
+            }
+        }
+        plugins.sort_by(|a, b| a.id.cmp(&b.id));
+        *self.plugins.lock().unwrap() = plugins;
+    }
+
+    pub fn list(&self) -> Vec<PluginInfo> {
+        let enabled = self.enabled.lock().unwrap();
+        self.plugins
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|p| PluginInfo {
+                id: p.id.clone(),
+                manifest: p.manifest.clone(),
+                dir: p.dir.to_string_lossy().to_string(),
+                enabled: enabled.contains(&p.id),
+            })
+            .collect()
+    }
+
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Result<()> {
+        if !self.plugins.lock().unwrap().iter().any(|p| p.id == id) {
+            bail!("Plugin not found: {}", id);
+        }
+        let mut ids = self.enabled.lock().unwrap();
+        if enabled {
+            ids.insert(id.to_string());
+        } else {
+            ids.remove(id);
+        }
+        if let Some(parent) = self.settings_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let settings = PluginSettings { enabled: ids.clone() };
+        fs::write(&self.settings_file, serde_json::to_string_pretty(&settings)?)?;
+        Ok(())
+    }
+
+    // 有効なプラグインを ID 順に呼び、変更をアイテムに反映する（変わったら true）
+    // プラグインの失敗では処理を止めない
+    pub fn run(&self, hook: PluginHook, item: &mut ItemRecord) -> bool {
+        // 実行中に一覧の表示や有効化を待たせないよう、呼ぶプラグインを取り出してからロックを外す
+        let plugins: Vec<Plugin> = {
+            let enabled = self.enabled.lock().unwrap();
+            self.plugins
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|p| enabled.contains(&p.id) && p.manifest.hooks.contains(&hook))
+                .cloned()
+                .collect()
+        };
+        let mut changed = false;
+        for plugin in &plugins {
+            match plugin.invoke(hook, item) {
+                Ok(output) => {
+                    let before = serde_json::to_value(&*item).ok();
+                    output.apply(item);
+                    changed |= serde_json::to_value(&*item).ok() != before;
+                }
+                Err(e) => log::warn!("Plugin {} failed on {:?} for {}: {:#}", plugin.id, hook, item.id, e),
+            }
+        }
+        changed
+    }
+}
+
+impl Plugin {
+    fn command(&self) -> PathBuf {
+        let command = Path::new(&self.manifest.command);
+        if command.is_absolute() {
+            command.to_path_buf()
+        } else {
+            self.dir.join(command)
+        }
+    }
+
+    fn invoke(&self, hook: PluginHook, item: &ItemRecord) -> Result<PluginOutput> {
+        let mut command = Command::new(self.command());
+        command
+            .args(&self.manifest.args)
+            .current_dir(&self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(windows)]
+        {
+            // コンソールウィンドウを表示しない
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x0800_0000);
+        }
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to start {}", self.command().display()))?;
+
+        let input = serde_json::to_vec(&serde_json::json!({ "hook": hook, "item": item }))?;
+        let mut stdin = child.stdin.take().context("stdin not captured")?;
+        let mut stdout = child.stdout.take().context("stdout not captured")?;
+        let mut stderr = child.stderr.take().context("stderr not captured")?;
+        // パイプが詰まらないよう、書き込みと読み出しは別スレッドで行う
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        let reader = std::thread::spawn(move || {
+            let mut out = Vec::new();
+            stdout.read_to_end(&mut out).map(|_| out)
+        });
+        let error_reader = std::thread::spawn(move || {
+            let mut err = String::new();
+            let _ = stderr.read_to_string(&mut err);
+            err
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(self.manifest.timeout_secs);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("Timed out after {}s", self.manifest.timeout_secs);
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+
+        // 入力を読まずに終了するプラグインもあるため、書き込みの失敗は無視する
+        let _ = writer.join();
+        let out = reader.join().map_err(|_| anyhow::anyhow!("Failed to read plugin output"))??;
+        let err = error_reader.join().unwrap_or_default();
+        if !status.success() {
+            bail!("Exited with {}: {}", status, err.trim());
+        }
+        if out.iter().all(u8::is_ascii_whitespace) {
+            return Ok(PluginOutput::default());
+        }
+        serde_json::from_slice(&out).context("Plugin output is not valid JSON")
+    }
+}
@@ -0,0 +1,384 @@
+use crate::hashing;
+use crate::metadata_store::{ItemRecord, MetadataStore};
+use crate::secrets;
+use crate::search_engine::SearchResult;
+use crate::thumbnail_cache::ThumbnailCache;
+use age::secrecy::{ExposeSecret, SecretString};
+use age::x25519;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+// OS のキーチェーンに覚えさせるパスコードの項目名
+const KEYCHAIN_PASSCODE: &str = "private-passcode";
+// 暗号化した画像の拡張子（元の拡張子の後ろに付ける）
+const SEALED_EXTENSION: &str = "age";
+
+// 暗号化して保存するテキスト
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SealedText {
+    pub ocr_text: String,
+    pub memo: String,
+}
+
+// 鍵ファイル: 公開鍵は平文（ロック中でも非公開にできる）、秘密鍵はパスコードで暗号化する
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    recipient: String,
+    identity: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    // パスコードが設定済みか
+    pub configured: bool,
+    pub unlocked: bool,
+}
+
+#[derive(Default)]
+struct Session {
+    identity: Option<x25519::Identity>,
+    // 復号したテキスト（ロックすると消す）
+    texts: HashMap<String, SealedText>,
+}
+
+fn secret(passcode: &str) -> Result<SecretString> {
+    if passcode.is_empty() {
+        bail!("Passcode must not be empty");
+    }
+    Ok(SecretString::from(passcode.to_string()))
+}
+
+// 非公開アイテムの鍵の管理と暗号化・復号（アンロックはアプリを終了するまで有効）
+pub struct PrivateVault {
+    key_file: PathBuf,
+    session: Mutex<Session>,
+}
+
+impl PrivateVault {
+    pub fn new(key_file: PathBuf) -> Self {
+        PrivateVault {
+            key_file,
+            session: Mutex::new(Session::default()),
+        }
+    }
+
+    fn load_key_file(&self) -> Result<Option<KeyFile>> {
+        if !self.key_file.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(&self.key_file)?;
+        Ok(Some(serde_json::from_str(&json).context("Invalid private key file")?))
+    }
+
+    fn recipient(&self) -> Result<x25519::Recipient> {
+        let key_file = self.load_key_file()?.context("Set a passcode for private items first")?;
+        x25519::Recipient::from_str(&key_file.recipient).map_err(|e| anyhow!("Invalid private key file: {}", e))
+    }
+
+    fn identity(&self) -> Result<x25519::Identity> {
+        self.session
+            .lock()
+            .unwrap()
+            .identity
+            .clone()
+            .context("Private items are locked")
+    }
+
+    pub fn status(&self) -> VaultStatus {
+        VaultStatus {
+            configured: self.key_file.exists(),
+            unlocked: self.session.lock().unwrap().identity.is_some(),
+        }
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.session.lock().unwrap().identity.is_some()
+    }
+
+    // 秘密鍵をパスコードで復号する
+    fn open_identity(&self, key_file: &KeyFile, passcode: &str) -> Result<x25519::Identity> {
+        let encrypted = STANDARD.decode(&key_file.identity).context("Invalid private key file")?;
+        let passphrase = age::scrypt::Identity::new(secret(passcode)?);
+        let plain = age::decrypt(&passphrase, &encrypted).map_err(|_| anyhow!("Incorrect passcode"))?;
+        let plain = String::from_utf8(plain).context("Invalid private key file")?;
+        x25519::Identity::from_str(plain.trim()).map_err(|e| anyhow!("Invalid private key file: {}", e))
+    }
+
+    fn write_key_file(&self, identity: &x25519::Identity, passcode: &str) -> Result<()> {
+        let passphrase = age::scrypt::Recipient::new(secret(passcode)?);
+        let encrypted = age::encrypt(&passphrase, identity.to_string().expose_secret().as_bytes())?;
+        let key_file = KeyFile {
+            recipient: identity.to_public().to_string(),
+            identity: STANDARD.encode(encrypted),
+        };
+        if let Some(parent) = self.key_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.key_file.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&key_file)?)?;
+        fs::rename(&tmp, &self.key_file)?;
+        Ok(())
+    }
+
+    // 初回は鍵を作り、2回目以降は現在のパスコードで確認してから変更する（変更後はアンロック状態）
+    pub fn set_passcode(&self, current: Option<&str>, new: &str) -> Result<()> {
+        let identity = match self.load_key_file()? {
+            Some(key_file) => {
+                let current = current.context("The current passcode is required")?;
+                self.open_identity(&key_file, current)?
+            }
+            None => x25519::Identity::generate(),
+        };
+        self.write_key_file(&identity, new)?;
+        self.session.lock().unwrap().identity = Some(identity);
+        Ok(())
+    }
+
+    pub fn unlock(&self, passcode: &str) -> Result<()> {
+        let key_file = self.load_key_file()?.context("Set a passcode for private items first")?;
+        let identity = self.open_identity(&key_file, passcode)?;
+        self.session.lock().unwrap().identity = Some(identity);
+        Ok(())
+    }
+
+    pub fn lock(&self) {
+        *self.session.lock().unwrap() = Session::default();
+    }
+
+    // パスコードを OS のキーチェーンに覚えさせる（None で忘れる）
+    pub fn remember_passcode(&self, passcode: Option<&str>) -> Result<()> {
+        secrets::set(&self.key_file, KEYCHAIN_PASSCODE, passcode.unwrap_or(""))
+    }
+
+    // キーチェーンにパスコードがあればアンロックする（アプリのロック解除時）
+    pub fn unlock_from_keychain(&self) -> Result<bool> {
+        match secrets::get(&self.key_file, KEYCHAIN_PASSCODE)? {
+            Some(passcode) => {
+                self.unlock(&passcode)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(age::encrypt(&self.recipient()?, data)?)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        age::decrypt(&self.identity()?, data).context("Failed to decrypt private data")
+    }
+
+    fn seal_text(&self, text: &SealedText) -> Result<Vec<u8>> {
+        self.encrypt(&serde_json::to_vec(text)?)
+    }
+
+    // 非公開アイテムのテキストを復号する（アンロック中のみ、結果はセッション中キャッシュする）
+    fn text(&self, store: &MetadataStore, item_id: &str) -> Result<SealedText> {
+        if let Some(text) = self.session.lock().unwrap().texts.get(item_id) {
+            return Ok(text.clone());
+        }
+        let text = match store.sealed_text(item_id)? {
+            Some(sealed) => serde_json::from_slice(&self.decrypt(&sealed)?)?,
+            None => SealedText::default(),
+        };
+        self.session
+            .lock()
+            .unwrap()
+            .texts
+            .insert(item_id.to_string(), text.clone());
+        Ok(text)
+    }
+
+    // アンロック中なら非公開アイテムのテキストを埋める（ロック中はそのまま）
+    pub fn reveal(&self, store: &MetadataStore, mut item: ItemRecord) -> ItemRecord {
+        if item.private && self.is_unlocked() {
+            match self.text(store, &item.id) {
+                Ok(text) => {
+                    item.ocr_text = text.ocr_text;
+                    item.memo = text.memo;
+                }
+                Err(e) => log::warn!("Failed to decrypt private item {}: {}", item.id, e),
+            }
+        }
+        item
+    }
+
+    // 一覧・検索結果から、ロック中は非公開アイテムを除き、アンロック中はテキストを埋める
+    pub fn visible(&self, store: &MetadataStore, items: Vec<ItemRecord>) -> Vec<ItemRecord> {
+        let unlocked = self.is_unlocked();
+        items
+            .into_iter()
+            .filter(|item| unlocked || !item.private)
+            .map(|item| self.reveal(store, item))
+            .collect()
+    }
+
+    // 非公開アイテムは検索インデックスにテキストを持たないため、アンロック中は復号したテキストから探す
+    pub fn search(&self, store: &MetadataStore, query: &str) -> Result<Vec<SearchResult>> {
+        let terms: Vec<String> = query.split_whitespace().map(|t| t.to_lowercase()).collect();
+        if terms.is_empty() || !self.is_unlocked() {
+            return Ok(Vec::new());
+        }
+        let mut results = Vec::new();
+        for id in store.private_item_ids()? {
+            let text = self.text(store, &id)?;
+            let fields = [("ocr_text", text.ocr_text.to_lowercase()), ("memo", text.memo.to_lowercase())];
+            if !terms.iter().all(|t| fields.iter().any(|(_, value)| value.contains(t))) {
+                continue;
+            }
+            let matched_fields = fields
+                .iter()
+                .filter(|(_, value)| terms.iter().any(|t| value.contains(t)))
+                .map(|(name, _)| name.to_string())
+                .collect();
+            results.push(SearchResult {
+                id,
+                score: 0.0,
+                highlights: Vec::new(),
+                matched_fields,
+            });
+        }
+        Ok(results)
+    }
+
+    // アンロック中に編集された非公開アイテムのテキストを暗号化し直し、平文は保存しない
+    pub fn seal_update(&self, store: &mut MetadataStore, item: &mut ItemRecord) -> Result<()> {
+        let text = SealedText {
+            ocr_text: std::mem::take(&mut item.ocr_text),
+            memo: std::mem::take(&mut item.memo),
+        };
+        if !self.is_unlocked() {
+            // ロック中は中身を見られないので、テキストの変更は受け付けない
+            if !text.ocr_text.is_empty() || !text.memo.is_empty() {
+                bail!("Private items are locked");
+            }
+            return Ok(());
+        }
+        store.set_sealed_text(&item.id, &self.seal_text(&text)?)?;
+        self.session.lock().unwrap().texts.insert(item.id.clone(), text);
+        Ok(())
+    }
+
+    // 画像とテキストを暗号化して非公開にする（元の画像と、そのサムネイルは削除する）
+    pub fn seal_item(&self, store: &mut MetadataStore, thumbnails: &ThumbnailCache, item: &ItemRecord) -> Result<()> {
+        if item.private {
+            return Ok(());
+        }
+        let text = SealedText {
+            ocr_text: item.ocr_text.clone(),
+            memo: item.memo.clone(),
+        };
+        let sealed_text = self.seal_text(&text)?;
+
+        let original = item.image_path.as_deref().map(PathBuf::from);
+        let sealed_path = match &original {
+            Some(original) => {
+                let data = fs::read(original)
+                    .with_context(|| format!("Failed to read image: {}", original.display()))?;
+                let sealed_path = sealed_image_path(original, &item.id);
+                let tmp = sealed_path.with_extension("tmp");
+                fs::write(&tmp, self.encrypt(&data)?)?;
+                fs::rename(&tmp, &sealed_path)?;
+                Some(sealed_path)
+            }
+            None => None,
+        };
+
+        let sealed_path_str = sealed_path.as_ref().map(|p| p.to_string_lossy().to_string());
+        store.seal_item(&item.id, &sealed_text, sealed_path_str.as_deref())?;
+
+        // 同じ画像を他のアイテムが使っていなければ平文の画像を消す
+        if let Some(original) = original {
+            if !store.is_image_referenced(&original.to_string_lossy())? {
+                if let Ok(hash) = hashing::file_hash(&original) {
+                    thumbnails.remove(&hash)?;
+                }
+                fs::remove_file(&original)?;
+            }
+        }
+        if self.is_unlocked() {
+            self.session.lock().unwrap().texts.insert(item.id.clone(), text);
+        }
+        Ok(())
+    }
+
+    // 非公開を解除する（アンロック中のみ）
+    pub fn unseal_item(&self, store: &mut MetadataStore, item: &ItemRecord) -> Result<()> {
+        if !item.private {
+            return Ok(());
+        }
+        let text = self.text(store, &item.id)?;
+        let sealed = item.image_path.as_deref().map(PathBuf::from);
+        let restored = match &sealed {
+            Some(sealed) => {
+                let data = self.decrypt(&fs::read(sealed)?)?;
+                let restored = original_image_path(sealed, &hashing::content_hash(&data));
+                if !restored.exists() {
+                    let tmp = restored.with_extension("tmp");
+                    fs::write(&tmp, &data)?;
+                    fs::rename(&tmp, &restored)?;
+                }
+                Some(restored.to_string_lossy().to_string())
+            }
+            None => None,
+        };
+        store.unseal_item(&item.id, &text.ocr_text, &text.memo, restored.as_deref())?;
+        if let Some(sealed) = sealed {
+            fs::remove_file(sealed)?;
+        }
+        self.session.lock().unwrap().texts.remove(&item.id);
+        Ok(())
+    }
+
+    // 暗号化した画像を復号して返す（アンロック中のみ）
+    pub fn read_image(&self, path: &Path) -> Result<Vec<u8>> {
+        if !is_sealed_path(path) {
+            bail!("Not a private image: {}", path.display());
+        }
+        self.decrypt(&fs::read(path)?)
+    }
+}
+
+// images/<hash>.jpg → images/private-<item_id>.jpg.age（バックアップの対象に含まれるよう同じディレクトリに置く）
+fn sealed_image_path(original: &Path, item_id: &str) -> PathBuf {
+    let ext = original.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_lowercase();
+    original.with_file_name(format!("private-{}.{}.{}", item_id, ext, SEALED_EXTENSION))
+}
+
+fn original_image_path(sealed: &Path, hash: &str) -> PathBuf {
+    let ext = sealed
+        .file_stem()
+        .map(Path::new)
+        .and_then(|stem| stem.extension())
+        .and_then(|e| e.to_str())
+        .unwrap_or("jpg")
+        .to_string();
+    sealed.with_file_name(format!("{}.{}", hash, ext))
+}
+
+// 2つの鍵ファイルが同じ鍵か（バックアップの非公開アイテムをこのライブラリで復号できるか）
+pub fn same_key(key_file: &Path, other: &Path) -> Result<bool> {
+    let recipient = |path: &Path| -> Result<Option<String>> {
+        Ok(PrivateVault::new(path.to_path_buf()).load_key_file()?.map(|key_file| key_file.recipient))
+    };
+    Ok(match (recipient(key_file)?, recipient(other)?) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    })
+}
+
+pub fn is_sealed_path(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some(SEALED_EXTENSION)
+}
+
+// private-<id>.jpg.age の元の拡張子（Content-Type の判定用）
+pub fn inner_path(path: &Path) -> PathBuf {
+    path.file_stem().map(PathBuf::from).unwrap_or_default()
+}
@@ -0,0 +1,214 @@
+use crate::paths::is_image_path;
+use crate::private_items::{self, PrivateVault};
+use crate::thumbnail_cache::{ThumbnailCache, DEFAULT_THUMBNAIL_SIZE};
+use anyhow::Result;
+use percent_encoding::percent_decode_str;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tauri::http::{header, Request, Response, StatusCode};
+
+// URIのパス部分（パーセントエンコードされたファイルパス）を取り出す
+pub fn decode_path(request: &Request<Vec<u8>>) -> PathBuf {
+    let raw = request.uri().path().trim_start_matches('/');
+    PathBuf::from(percent_decode_str(raw).decode_utf8_lossy().to_string())
+}
+
+pub fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "heic" => "image/heic",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn query_param<'a>(request: &'a Request<Vec<u8>>, name: &str) -> Option<&'a str> {
+    request.uri().query().and_then(|query| {
+        query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            (key == name).then_some(value)
+        })
+    })
+}
+
+pub fn error_response(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(message.into_bytes())
+        .unwrap()
+}
+
+// thumb://localhost/<エンコード済みパス>?size=256
+pub fn thumbnail_response(cache: &ThumbnailCache, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let path = decode_path(request);
+    let size = query_param(request, "size")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+
+    if !is_image_path(&path) {
+        return error_response(StatusCode::FORBIDDEN, format!("Not an image file: {}", path.display()));
+    }
+    if !path.is_file() {
+        return error_response(StatusCode::NOT_FOUND, format!("Image not found: {}", path.display()));
+    }
+
+    match cache.read(&path, size) {
+        Ok(bytes) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "image/jpeg")
+            .header(header::CACHE_CONTROL, "max-age=31536000")
+            .body(bytes)
+            .unwrap(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+// "bytes=start-end" 形式の単一レンジを解析（終端を含む）
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') || len == 0 {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (start, "") => (start.parse().ok()?, len - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(len - 1)),
+    };
+    (start <= end && start < len).then_some((start, end))
+}
+
+fn range_header(request: &Request<Vec<u8>>) -> Option<&str> {
+    request.headers().get(header::RANGE).and_then(|v| v.to_str().ok())
+}
+
+fn bytes_response(request: &Request<Vec<u8>>, bytes: Vec<u8>, mime: &str) -> Response<Vec<u8>> {
+    let len = bytes.len() as u64;
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    match range_header(request) {
+        Some(value) => match parse_range(value, len) {
+            Some((start, end)) => builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+                .body(bytes[start as usize..=end as usize].to_vec())
+                .unwrap(),
+            None => builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Vec::new())
+                .unwrap(),
+        },
+        None => builder.status(StatusCode::OK).body(bytes).unwrap(),
+    }
+}
+
+// 元ファイルは要求された範囲だけを読み込む
+fn original_response(request: &Request<Vec<u8>>, path: &Path) -> Result<Response<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mime = content_type(path);
+
+    let range = match range_header(request) {
+        Some(value) => match parse_range(value, len) {
+            Some(range) => Some(range),
+            None => {
+                return Ok(Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                    .body(Vec::new())?);
+            }
+        },
+        None => None,
+    };
+
+    let builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let response = match range {
+        Some((start, end)) => {
+            let mut buf = vec![0u8; (end - start + 1) as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut buf)?;
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+                .body(buf)?
+        }
+        None => {
+            let mut buf = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut buf)?;
+            builder.status(StatusCode::OK).body(buf)?
+        }
+    };
+    Ok(response)
+}
+
+// 非公開アイテムの画像はアンロック中だけ復号して返す（縮小版もディスクにはキャッシュしない）
+fn private_response(vault: &PrivateVault, request: &Request<Vec<u8>>, kind: &str, path: &Path) -> Response<Vec<u8>> {
+    if !vault.is_unlocked() {
+        return error_response(StatusCode::FORBIDDEN, "Private items are locked".to_string());
+    }
+    let result = vault.read_image(path).and_then(|data| match kind {
+        "original" => Ok(bytes_response(request, data, content_type(&private_items::inner_path(path)))),
+        "resized" => {
+            let size = query_param(request, "size")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+            Ok(bytes_response(request, ThumbnailCache::render(&data, size)?, "image/jpeg"))
+        }
+        _ => Ok(error_response(StatusCode::BAD_REQUEST, format!("Unknown snap resource: {}", kind))),
+    });
+    result.unwrap_or_else(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+// snap://localhost/original/<エンコード済みパス>
+// snap://localhost/resized/<エンコード済みパス>?size=1024
+pub fn snap_response(cache: &ThumbnailCache, vault: &PrivateVault, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let raw = request.uri().path().trim_start_matches('/');
+    let (kind, encoded) = raw.split_once('/').unwrap_or((raw, ""));
+    let path = PathBuf::from(percent_decode_str(encoded).decode_utf8_lossy().to_string());
+
+    if private_items::is_sealed_path(&path) && path.is_file() {
+        return private_response(vault, request, kind, &path);
+    }
+
+    if !is_image_path(&path) {
+        return error_response(StatusCode::FORBIDDEN, format!("Not an image file: {}", path.display()));
+    }
+    if !path.is_file() {
+        return error_response(StatusCode::NOT_FOUND, format!("Image not found: {}", path.display()));
+    }
+
+    let result = match kind {
+        "original" => original_response(request, &path),
+        "resized" => {
+            let size = query_param(request, "size")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+            cache
+                .read(&path, size)
+                .map(|bytes| bytes_response(request, bytes, "image/jpeg"))
+        }
+        _ => return error_response(StatusCode::BAD_REQUEST, format!("Unknown snap resource: {}", kind)),
+    };
+
+    result.unwrap_or_else(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
@@ -0,0 +1,446 @@
+use crate::i18n;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+// 大きな写真はこの長さまで縮小してから探す（小さく写った QR コードが潰れない程度）
+const MAX_SCAN_SIDE: u32 = 3000;
+
+// QR コードの中身を種類ごとに読み取ったもの
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QrPayload {
+    Url {
+        url: String,
+    },
+    // WIFI:T:WPA;S:ネットワーク名;P:パスワード;;
+    Wifi {
+        ssid: String,
+        password: Option<String>,
+        // WPA / WEP / nopass
+        security: Option<String>,
+        hidden: bool,
+    },
+    // vCard / MECARD
+    Contact {
+        name: Option<String>,
+        organization: Option<String>,
+        phones: Vec<String>,
+        emails: Vec<String>,
+        address: Option<String>,
+        url: Option<String>,
+    },
+    // EMVCo の店舗提示型コード（JPQR など）
+    Payment {
+        merchant_name: Option<String>,
+        merchant_city: Option<String>,
+        amount: Option<String>,
+        currency: Option<String>,
+        country: Option<String>,
+    },
+    // 振込の QR コード（EPC の BCD 形式）
+    BankTransfer {
+        beneficiary: String,
+        account: String,
+        bic: Option<String>,
+        amount: Option<String>,
+        currency: Option<String>,
+        reference: Option<String>,
+    },
+    Text {
+        text: String,
+    },
+}
+
+impl QrPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            QrPayload::Url { .. } => "url",
+            QrPayload::Wifi { .. } => "wifi",
+            QrPayload::Contact { .. } => "contact",
+            QrPayload::Payment { .. } => "payment",
+            QrPayload::BankTransfer { .. } => "bank_transfer",
+            QrPayload::Text { .. } => "text",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QrCode {
+    // 読み取ったままの文字列
+    pub raw: String,
+    pub payload: QrPayload,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QrActionKind {
+    OpenUrl,
+    Copy,
+}
+
+// 画面に出すボタン（field はどの値か。url / password / phone / email / account / payment_details / transfer_details / text）
+#[derive(Debug, Clone, Serialize)]
+pub struct QrAction {
+    pub kind: QrActionKind,
+    pub field: &'static str,
+    pub value: String,
+}
+
+fn open(field: &'static str, value: &str) -> QrAction {
+    QrAction {
+        kind: QrActionKind::OpenUrl,
+        field,
+        value: value.to_string(),
+    }
+}
+
+fn copy(field: &'static str, value: &str) -> QrAction {
+    QrAction {
+        kind: QrActionKind::Copy,
+        field,
+        value: value.to_string(),
+    }
+}
+
+fn money(amount: &Option<String>, currency: &Option<String>) -> Option<String> {
+    amount.as_ref().map(|amount| match currency {
+        Some(currency) => format!("{} {}", currency, amount),
+        None => amount.clone(),
+    })
+}
+
+// 支払先・振込先をそのまま貼り付けられる形にする（項目名は表示中の言語）
+fn details_text(fields: &[(&str, Option<&str>)]) -> String {
+    fields
+        .iter()
+        .filter_map(|(key, value)| value.map(|value| format!("{}: {}", i18n::text(key), value)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl QrCode {
+    pub fn parse(raw: &str) -> Self {
+        QrCode {
+            raw: raw.to_string(),
+            payload: parse_payload(raw),
+        }
+    }
+
+    pub fn actions(&self) -> Vec<QrAction> {
+        match &self.payload {
+            QrPayload::Url { url } => vec![open("url", url), copy("url", url)],
+            QrPayload::Wifi { password, .. } => password.iter().map(|p| copy("password", p)).collect(),
+            QrPayload::Contact { phones, emails, url, .. } => phones
+                .iter()
+                .map(|phone| open("phone", &format!("tel:{}", phone)))
+                .chain(emails.iter().map(|email| open("email", &format!("mailto:{}", email))))
+                .chain(url.iter().map(|url| open("url", url)))
+                .collect(),
+            QrPayload::Payment {
+                merchant_name,
+                amount,
+                currency,
+                ..
+            } => {
+                let amount = money(amount, currency);
+                let details = details_text(&[("qr.merchant", merchant_name.as_deref()), ("qr.amount", amount.as_deref())]);
+                vec![copy("payment_details", &details)]
+            }
+            QrPayload::BankTransfer {
+                beneficiary,
+                account,
+                bic,
+                amount,
+                currency,
+                reference,
+            } => {
+                let amount = money(amount, currency);
+                let details = details_text(&[
+                    ("qr.beneficiary", Some(beneficiary.as_str())),
+                    ("qr.account", Some(account.as_str())),
+                    ("qr.bic", bic.as_deref()),
+                    ("qr.amount", amount.as_deref()),
+                    ("qr.reference", reference.as_deref()),
+                ]);
+                vec![copy("transfer_details", &details), copy("account", account)]
+            }
+            QrPayload::Text { text } => vec![copy("text", text)],
+        }
+    }
+}
+
+// 画面に出す QR コードと、それに対してできること
+#[derive(Debug, Clone, Serialize)]
+pub struct QrCodeDetails {
+    #[serde(flatten)]
+    pub code: QrCode,
+    pub actions: Vec<QrAction>,
+}
+
+impl From<QrCode> for QrCodeDetails {
+    fn from(code: QrCode) -> Self {
+        let actions = code.actions();
+        QrCodeDetails { code, actions }
+    }
+}
+
+// 画像の中の QR コードをすべて読む（読めなかったものは飛ばす）
+pub fn decode(image: &DynamicImage) -> Vec<QrCode> {
+    let image = if image.width().max(image.height()) > MAX_SCAN_SIDE {
+        image.resize(MAX_SCAN_SIDE, MAX_SCAN_SIDE, FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+    let gray = image.to_luma8();
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    let mut prepared =
+        rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| gray.get_pixel(x as u32, y as u32)[0]);
+    let mut codes: Vec<QrCode> = Vec::new();
+    for grid in prepared.detect_grids() {
+        match grid.decode() {
+            Ok((_, content)) if !content.trim().is_empty() => {
+                if !codes.iter().any(|c| c.raw == content) {
+                    codes.push(QrCode::parse(&content));
+                }
+            }
+            Ok(_) => {}
+            Err(e) => log::debug!("Failed to decode QR code: {:?}", e),
+        }
+    }
+    codes
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    text.get(..prefix.len())
+        .filter(|head| head.eq_ignore_ascii_case(prefix))
+        .map(|_| &text[prefix.len()..])
+}
+
+fn parse_payload(raw: &str) -> QrPayload {
+    let trimmed = raw.trim();
+    let has_prefix = |prefix: &str| strip_prefix_ignore_case(trimmed, prefix).is_some();
+    let parsed = if has_prefix("http://") || has_prefix("https://") {
+        Some(QrPayload::Url { url: trimmed.to_string() })
+    } else if let Some(body) = strip_prefix_ignore_case(trimmed, "WIFI:") {
+        parse_wifi(body)
+    } else if has_prefix("BEGIN:VCARD") {
+        parse_vcard(trimmed)
+    } else if let Some(body) = strip_prefix_ignore_case(trimmed, "MECARD:") {
+        parse_mecard(body)
+    } else if trimmed.starts_with("000201") {
+        parse_emv(trimmed)
+    } else if trimmed.starts_with("BCD") {
+        parse_epc(trimmed)
+    } else {
+        None
+    };
+    parsed.unwrap_or_else(|| QrPayload::Text { text: raw.to_string() })
+}
+
+// ; で区切る（\; \: \, \\ は区切りではなく文字）
+fn split_escaped(text: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            current.extend(chars.next());
+        } else if c == separator {
+            parts.push(std::mem::take(&mut current));
+        } else {
+            current.push(c);
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+// ; で区切った項目のうち空でないものを空白でつなぐ
+fn join_components<'a>(parts: impl Iterator<Item = &'a String>) -> Option<String> {
+    non_empty(&parts.map(|p| p.trim()).filter(|p| !p.is_empty()).collect::<Vec<_>>().join(" "))
+}
+
+fn parse_wifi(body: &str) -> Option<QrPayload> {
+    let mut ssid = None;
+    let mut password = None;
+    let mut security = None;
+    let mut hidden = false;
+    for field in split_escaped(body, ';') {
+        let Some((key, value)) = field.split_once(':') else {
+            continue;
+        };
+        match key.to_uppercase().as_str() {
+            "S" => ssid = non_empty(value),
+            "P" => password = non_empty(value),
+            "T" => security = non_empty(value),
+            "H" => hidden = value.eq_ignore_ascii_case("true"),
+            _ => {}
+        }
+    }
+    Some(QrPayload::Wifi {
+        ssid: ssid?,
+        password,
+        security,
+        hidden,
+    })
+}
+
+fn parse_vcard(text: &str) -> Option<QrPayload> {
+    // 折り返した行（空白で始まる行）は前の行に続ける
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+    let (mut name, mut structured_name, mut organization, mut address, mut url) = (None, None, None, None, None);
+    let (mut phones, mut emails) = (Vec::new(), Vec::new());
+    for line in &lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // TEL;TYPE=CELL のような引数は使わない
+        let key = key.split(';').next().unwrap_or("").to_uppercase();
+        match key.as_str() {
+            "FN" => name = non_empty(value),
+            // 姓;名;ミドルネーム;敬称（前）;敬称（後）
+            "N" => structured_name = join_components(split_escaped(value, ';').iter().take(2)),
+            "ORG" => organization = join_components(split_escaped(value, ';').iter()),
+            "TEL" => phones.extend(non_empty(value)),
+            "EMAIL" => emails.extend(non_empty(value)),
+            "ADR" => address = address.or(join_components(split_escaped(value, ';').iter())),
+            "URL" => url = url.or(non_empty(value)),
+            _ => {}
+        }
+    }
+    Some(QrPayload::Contact {
+        name: name.or(structured_name),
+        organization,
+        phones,
+        emails,
+        address,
+        url,
+    })
+}
+
+fn parse_mecard(body: &str) -> Option<QrPayload> {
+    let (mut name, mut organization, mut address, mut url) = (None, None, None, None);
+    let (mut phones, mut emails) = (Vec::new(), Vec::new());
+    for field in split_escaped(body, ';') {
+        let Some((key, value)) = field.split_once(':') else {
+            continue;
+        };
+        match key.to_uppercase().as_str() {
+            // MECARD の N は「姓,名」
+            "N" => name = non_empty(&value.replace(',', " ")),
+            "ORG" => organization = non_empty(value),
+            "TEL" => phones.extend(non_empty(value)),
+            "EMAIL" => emails.extend(non_empty(value)),
+            "ADR" => address = non_empty(&value.replace(',', " ")),
+            "URL" => url = non_empty(value),
+            _ => {}
+        }
+    }
+    Some(QrPayload::Contact {
+        name,
+        organization,
+        phones,
+        emails,
+        address,
+        url,
+    })
+}
+
+// EMVCo の TLV（ID 2桁・長さ2桁・値）を読む
+fn emv_fields(text: &str) -> Option<Vec<(String, String)>> {
+    let mut fields = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let id = rest.get(..2)?;
+        let len: usize = rest.get(2..4)?.parse().ok()?;
+        // 長さは文字数（日本語の店名は複数バイト）
+        let value: String = rest.get(4..)?.chars().take(len).collect();
+        if value.chars().count() != len {
+            return None;
+        }
+        rest = &rest[4 + value.len()..];
+        fields.push((id.to_string(), value));
+    }
+    Some(fields)
+}
+
+// CRC-16/CCITT-FALSE（ID 63 の値まで含めずに、ID と長さ "6304" までを計算する）
+fn emv_crc(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// ISO 4217 の数字のコードのうちよく使うもの（それ以外は数字のまま）
+fn currency_code(numeric: &str) -> String {
+    match numeric {
+        "392" => "JPY",
+        "840" => "USD",
+        "978" => "EUR",
+        "156" => "CNY",
+        "410" => "KRW",
+        "344" => "HKD",
+        "901" => "TWD",
+        "702" => "SGD",
+        "764" => "THB",
+        _ => numeric,
+    }
+    .to_string()
+}
+
+fn parse_emv(text: &str) -> Option<QrPayload> {
+    let crc_at = text.rfind("6304")?;
+    let expected = u16::from_str_radix(text.get(crc_at + 4..crc_at + 8)?, 16).ok()?;
+    if emv_crc(text[..crc_at + 4].as_bytes()) != expected {
+        return None;
+    }
+    let fields = emv_fields(text)?;
+    let get = |id: &str| fields.iter().find(|(key, _)| key == id).and_then(|(_, value)| non_empty(value));
+    Some(QrPayload::Payment {
+        merchant_name: get("59"),
+        merchant_city: get("60"),
+        amount: get("54"),
+        currency: get("53").map(|c| currency_code(&c)),
+        country: get("58"),
+    })
+}
+
+// EPC069-12（BCD / 版 / 文字コード / SCT / BIC / 受取人 / IBAN / 通貨と金額 / 目的 / 参照 / 備考）
+fn parse_epc(text: &str) -> Option<QrPayload> {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    if lines.len() < 7 || lines.get(3) != Some(&"SCT") {
+        return None;
+    }
+    let field = |i: usize| lines.get(i).and_then(|v| non_empty(v));
+    let (currency, amount) = match field(7) {
+        Some(value) if value.len() > 3 && value[..3].chars().all(|c| c.is_ascii_uppercase()) => {
+            (Some(value[..3].to_string()), Some(value[3..].to_string()))
+        }
+        _ => (None, None),
+    };
+    Some(QrPayload::BankTransfer {
+        beneficiary: field(5)?,
+        account: field(6)?,
+        bic: field(4),
+        amount,
+        currency,
+        reference: field(9).or(field(10)),
+    })
+}
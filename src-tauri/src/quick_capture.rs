@@ -0,0 +1,127 @@
+use crate::clipboard;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(not(target_os = "macos"))]
+use std::process::Command;
+
+pub const DEFAULT_SHORTCUT: &str = "CmdOrCtrl+Shift+S";
+pub const DEFAULT_INBOX_TAG: &str = "Inbox";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    // OS の範囲指定スクリーンショット
+    #[default]
+    Region,
+    // クリップボードの画像
+    Clipboard,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuickCaptureSettings {
+    // グローバルショートカット（None なら登録しない）
+    #[serde(default = "default_shortcut")]
+    pub shortcut: Option<String>,
+    #[serde(default)]
+    pub mode: CaptureMode,
+    // 取り込んだアイテムに付けるタグ
+    #[serde(default = "default_inbox_tag")]
+    pub inbox_tag: String,
+}
+
+fn default_shortcut() -> Option<String> {
+    Some(DEFAULT_SHORTCUT.to_string())
+}
+
+fn default_inbox_tag() -> String {
+    DEFAULT_INBOX_TAG.to_string()
+}
+
+impl Default for QuickCaptureSettings {
+    fn default() -> Self {
+        QuickCaptureSettings {
+            shortcut: default_shortcut(),
+            mode: CaptureMode::default(),
+            inbox_tag: default_inbox_tag(),
+        }
+    }
+}
+
+impl QuickCaptureSettings {
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// 画像を staging ディレクトリに用意する（キャンセルされたら None）
+pub fn capture(mode: CaptureMode, staging_dir: &Path) -> Result<Option<PathBuf>> {
+    fs::create_dir_all(staging_dir)?;
+    match mode {
+        CaptureMode::Clipboard => clipboard::save_clipboard_image(staging_dir).map(Some),
+        CaptureMode::Region => capture_region(staging_dir),
+    }
+}
+
+#[cfg(not(windows))]
+fn staged_path(staging_dir: &Path) -> PathBuf {
+    staging_dir.join(format!("capture-{}.png", uuid::Uuid::new_v4().simple()))
+}
+
+#[cfg(target_os = "macos")]
+fn capture_region(staging_dir: &Path) -> Result<Option<PathBuf>> {
+    let path = staged_path(staging_dir);
+    // -i: 範囲選択（Esc でキャンセルするとファイルは作られない） -x: シャッター音なし
+    std::process::Command::new("screencapture").arg("-i").arg("-x").arg(&path).status()?;
+    Ok(path.is_file().then_some(path))
+}
+
+// Windows は切り取り領域（ms-screenclip）の結果がクリップボードに入るのを待つ
+#[cfg(windows)]
+fn capture_region(staging_dir: &Path) -> Result<Option<PathBuf>> {
+    use std::time::{Duration, Instant};
+    const TIMEOUT: Duration = Duration::from_secs(60);
+    const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+    let signature = |image: &image::RgbaImage| crate::hashing::content_hash(image.as_raw());
+    let before = clipboard::clipboard_image().ok().flatten().map(|image| signature(&image));
+    Command::new("explorer").arg("ms-screenclip:").spawn()?;
+
+    let started = Instant::now();
+    while started.elapsed() < TIMEOUT {
+        std::thread::sleep(POLL_INTERVAL);
+        if let Ok(Some(image)) = clipboard::clipboard_image() {
+            if before.as_deref() != Some(signature(&image).as_str()) {
+                return clipboard::save_png(image, staging_dir).map(Some);
+            }
+        }
+    }
+    Ok(None)
+}
+
+// Linux はデスクトップ環境のスクリーンショットツールを順に試す
+#[cfg(all(unix, not(target_os = "macos")))]
+fn capture_region(staging_dir: &Path) -> Result<Option<PathBuf>> {
+    let path = staged_path(staging_dir);
+    let tools: [(&str, &[&str]); 2] = [("gnome-screenshot", &["-a", "-f"]), ("spectacle", &["-r", "-b", "-n", "-o"])];
+    for (program, args) in tools {
+        match Command::new(program).args(args).arg(&path).status() {
+            Ok(_) => return Ok(path.is_file().then_some(path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    anyhow::bail!("No screenshot tool found (gnome-screenshot or spectacle)")
+}
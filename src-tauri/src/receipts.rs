@@ -0,0 +1,249 @@
+use crate::table_export::extract_amounts;
+use chrono::NaiveDate;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+// 店名を探す範囲（レシートの先頭の行）
+const VENDOR_LINES: usize = 8;
+const MAX_VENDOR_CHARS: usize = 40;
+
+const TOTAL_LABELS: &[&str] = &["合計", "お買上", "お買い上げ", "お会計", "ご請求", "請求金額", "お支払", "total"];
+const SUBTOTAL_LABELS: &[&str] = &["小計", "subtotal", "sub total"];
+const TAX_LABELS: &[&str] = &["消費税", "内税", "外税", "税額", "tax"];
+// 合計の行に見えても金額ではないもの（点数・預り金・釣り銭）
+const NOT_AMOUNT_LABELS: &[&str] = &["点数", "お預", "預り", "釣", "change"];
+const VENDOR_SKIP_WORDS: &[&str] = &[
+    "tel", "電話", "〒", "http", "www", "領収", "レシート", "receipt", "いらっしゃいませ", "ありがとう", "登録番号",
+];
+const VENDOR_HINTS: &[&str] = &[
+    "店", "株式会社", "(株)", "（株）", "商店", "薬局", "ストア", "マート", "食堂", "store", "shop", "mart", "cafe",
+];
+// 通貨の記号と ISO 4217 のコード（HK$ などを先に数えてから $ を数える）
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[
+    ("円", "JPY"),
+    ("¥", "JPY"),
+    ("￥", "JPY"),
+    ("hk$", "HKD"),
+    ("nt$", "TWD"),
+    ("us$", "USD"),
+    ("s$", "SGD"),
+    ("a$", "AUD"),
+    ("c$", "CAD"),
+    ("$", "USD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("₩", "KRW"),
+];
+// 記号がなければ円とみなす
+pub const DEFAULT_CURRENCY: &str = "JPY";
+
+// レシートから読み取った項目（金額は currency の単位）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReceiptFields {
+    pub vendor: Option<String>,
+    // ISO 4217 の通貨コード（JPY / USD など）
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    pub purchased_on: Option<NaiveDate>,
+    pub total: Option<u64>,
+    pub subtotal: Option<u64>,
+    pub tax: Option<u64>,
+    // 合計に消費税が含まれている（内税）
+    pub tax_included: bool,
+}
+
+impl ReceiptFields {
+    // 合計が読み取れたものだけをレシートとみなす
+    pub fn is_receipt(&self) -> bool {
+        self.total.is_some()
+    }
+}
+
+// 検索・一覧の絞り込み条件（すべて省略可、指定したものをすべて満たすもの）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReceiptFilter {
+    pub vendor: Option<String>,
+    pub amount_min: Option<u64>,
+    pub amount_max: Option<u64>,
+    pub purchased_from: Option<NaiveDate>,
+    pub purchased_to: Option<NaiveDate>,
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+fn currency_code_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\b(JPY|USD|EUR|GBP|KRW|CNY|RMB|HKD|TWD|SGD|AUD|CAD)\b").unwrap())
+}
+
+// 通貨の記号とコードをそれぞれ数え、最も多く出てきた通貨を返す（同じ数なら先に数えたもの、つまり円を優先）
+pub fn detect_currency(text: &str) -> String {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut add = |code: &str| match counts.iter_mut().find(|(c, _)| c == code) {
+        Some((_, count)) => *count += 1,
+        None => counts.push((code.to_string(), 1)),
+    };
+    let mut rest = text.to_lowercase();
+    for (symbol, code) in CURRENCY_SYMBOLS {
+        for _ in 0..rest.matches(symbol).count() {
+            add(code);
+        }
+        rest = rest.replace(symbol, " ");
+    }
+    for code in currency_code_pattern().find_iter(&text.to_uppercase()) {
+        add(if code.as_str() == "RMB" { "CNY" } else { code.as_str() });
+    }
+    let mut best: Option<(String, usize)> = None;
+    for (code, count) in counts {
+        if best.as_ref().map_or(true, |(_, max)| count > *max) {
+            best = Some((code, count));
+        }
+    }
+    best.map_or_else(default_currency, |(code, _)| code)
+}
+
+fn amount_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"([0-9]{1,3}(?:[,，][0-9]{3})+|[0-9]+)\s*円?").unwrap())
+}
+
+fn date_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"((?:19|20)[0-9]{2})\s*[年/.\-]\s*([0-9]{1,2})\s*[月/.\-]\s*([0-9]{1,2})").unwrap()
+    })
+}
+
+fn contains_any(line: &str, labels: &[&str]) -> bool {
+    labels.iter().any(|label| line.contains(label))
+}
+
+// 行の最後の金額（「合計 3点 ¥1,234」なら 1234）
+fn line_amount(line: &str) -> Option<u64> {
+    let line = date_pattern().replace_all(line, " ");
+    // 税率（10% など）は金額として拾わない
+    let line = line.replace(['%', '％'], "% ");
+    amount_pattern()
+        .captures_iter(&line)
+        .filter(|caps| !line[caps.get(0).unwrap().end()..].starts_with('%'))
+        .filter_map(|caps| caps[1].replace([',', '，'], "").parse().ok())
+        .last()
+}
+
+// 項目名の行に金額がなければ次の行を見る（OCR で列が別の行に分かれることがある）
+fn labelled_amount(lines: &[String], index: usize) -> Option<u64> {
+    line_amount(&lines[index]).or_else(|| lines.get(index + 1).filter(|l| !l.is_empty()).and_then(|l| line_amount(l)))
+}
+
+fn is_tax_line(line: &str) -> bool {
+    contains_any(line, TAX_LABELS) && !line.contains("対象") && !line.contains("税込")
+}
+
+fn is_subtotal_line(line: &str) -> bool {
+    contains_any(line, SUBTOTAL_LABELS)
+}
+
+fn is_total_line(line: &str) -> bool {
+    contains_any(line, TOTAL_LABELS)
+        && !is_tax_line(line)
+        && !is_subtotal_line(line)
+        && !line.contains("対象")
+        && !contains_any(line, NOT_AMOUNT_LABELS)
+}
+
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    date_pattern().captures_iter(text).find_map(|caps| {
+        NaiveDate::from_ymd_opt(caps[1].parse().ok()?, caps[2].parse().ok()?, caps[3].parse().ok()?)
+    })
+}
+
+fn vendor_candidate(line: &str) -> Option<String> {
+    let line = line.trim();
+    let chars = line.chars().count();
+    if !(2..=MAX_VENDOR_CHARS).contains(&chars) || date_pattern().is_match(line) {
+        return None;
+    }
+    let lower = line.to_lowercase();
+    if contains_any(&lower, VENDOR_SKIP_WORDS) {
+        return None;
+    }
+    // 数字や記号ばかりの行（電話番号・レジ番号など）は店名ではない
+    let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+    (letters * 2 > chars).then(|| line.to_string())
+}
+
+// 先頭の行から店名らしいものを選ぶ（店・株式会社などを含む行を優先する）
+fn parse_vendor(lines: &[String]) -> Option<String> {
+    let candidates: Vec<String> = lines.iter().take(VENDOR_LINES).filter_map(|l| vendor_candidate(l)).collect();
+    candidates
+        .iter()
+        .find(|c| contains_any(&c.to_lowercase(), VENDOR_HINTS))
+        .or_else(|| candidates.first())
+        .cloned()
+}
+
+// OCR のテキストから合計・消費税・購入日・店名を読み取る
+pub fn parse(text: &str) -> ReceiptFields {
+    let lines: Vec<String> = text.lines().map(|l| l.trim().to_string()).collect();
+    let lowered: Vec<String> = lines.iter().map(|l| l.to_lowercase()).collect();
+
+    let mut total = None;
+    let mut subtotal = None;
+    let mut taxes = Vec::new();
+    let mut tax_overall = None;
+    let mut tax_included = None;
+    for (i, line) in lowered.iter().enumerate() {
+        if is_tax_line(line) {
+            if let Some(amount) = labelled_amount(&lines, i) {
+                // 「消費税等」「税額合計」は税率ごとの内訳の合計
+                if line.contains('等') || line.contains("合計") {
+                    tax_overall.get_or_insert(amount);
+                } else {
+                    taxes.push(amount);
+                }
+            }
+            if line.contains('内') {
+                tax_included = Some(true);
+            } else if line.contains('外') {
+                tax_included = Some(false);
+            }
+        } else if is_subtotal_line(line) {
+            subtotal = subtotal.or_else(|| labelled_amount(&lines, i));
+        } else if is_total_line(line) {
+            // 合計の行が複数あれば最初のものを使う
+            total = total.or_else(|| labelled_amount(&lines, i));
+            if line.contains("税込") {
+                tax_included.get_or_insert(true);
+            }
+        }
+    }
+
+    let tax = tax_overall.or_else(|| (!taxes.is_empty()).then(|| taxes.iter().sum()));
+    let tax_included = tax_included.unwrap_or(match (subtotal, tax, total) {
+        (Some(subtotal), Some(tax), Some(total)) => subtotal + tax != total,
+        _ => true,
+    });
+    let purchased_on = parse_date(text);
+    // 合計の行がなければ、小計と外税から求めるか、円の付いた金額の最大のものを合計とみなす
+    // （日付も消費税もない画像の金額はレシートの合計とはみなさない）
+    let total = total
+        .or_else(|| subtotal.map(|s| if tax_included { s } else { s + tax.unwrap_or(0) }))
+        .or_else(|| {
+            (tax.is_some() || purchased_on.is_some())
+                .then(|| extract_amounts(text).into_iter().max())
+                .flatten()
+        });
+
+    ReceiptFields {
+        vendor: parse_vendor(&lines),
+        currency: detect_currency(text),
+        purchased_on,
+        total,
+        subtotal,
+        tax,
+        tax_included,
+    }
+}
@@ -0,0 +1,231 @@
+use crate::hashing;
+use crate::jobs::ProgressReporter;
+use crate::metadata_store::{ItemFilter, ItemRecord, MetadataStore, RecompressedOriginal};
+use crate::paths::LibraryPaths;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ColorType, DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+// 元のサイズの 9 割より小さくならなければ差し替えない
+const MIN_SAVING_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecompressOptions {
+    // 対象のアイテム（省略時はしきい値を超えるすべての元画像）
+    #[serde(default)]
+    pub item_ids: Option<Vec<String>>,
+    #[serde(default = "default_min_size_mb")]
+    pub min_size_mb: u64,
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    // 長辺がこれを超える場合は縮小する（省略時は解像度を保つ）
+    #[serde(default)]
+    pub max_dimension: Option<u32>,
+}
+
+fn default_min_size_mb() -> u64 {
+    5
+}
+
+fn default_quality() -> u8 {
+    85
+}
+
+impl Default for RecompressOptions {
+    fn default() -> Self {
+        RecompressOptions {
+            item_ids: None,
+            min_size_mb: default_min_size_mb(),
+            quality: default_quality(),
+            max_dimension: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecompressReport {
+    pub recompressed: usize,
+    pub skipped: usize,
+    pub failed: Vec<serde_json::Value>,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub changed_item_ids: Vec<String>,
+}
+
+// 透明な画素がある画像は JPEG にすると背景が変わるため対象外にする
+fn has_transparency(img: &DynamicImage) -> bool {
+    img.color().has_alpha() && img.to_rgba8().pixels().any(|p| p[3] < 255)
+}
+
+// 元の JPEG の Exif（撮影日時・位置情報・向き）を再圧縮後のファイルに引き継ぐ
+fn copy_exif(original: &[u8], encoded: Vec<u8>) -> Vec<u8> {
+    let mut exif = None;
+    let mut pos = 2;
+    while pos + 4 <= original.len() && original[pos] == 0xFF {
+        let marker = original[pos + 1];
+        // SOS 以降は画像データ
+        if marker == 0xDA {
+            break;
+        }
+        let len = u16::from_be_bytes([original[pos + 2], original[pos + 3]]) as usize;
+        let end = pos + 2 + len;
+        if end > original.len() {
+            break;
+        }
+        if marker == 0xE1 && original[pos + 4..end].starts_with(b"Exif\0\0") {
+            exif = Some(&original[pos..end]);
+            break;
+        }
+        pos = end;
+    }
+    let exif = match exif {
+        Some(exif) => exif,
+        None => return encoded,
+    };
+
+    // SOI と JFIF(APP0) の後ろに挿入する
+    let mut insert_at = 2;
+    if encoded.len() > 6 && encoded[2] == 0xFF && encoded[3] == 0xE0 {
+        insert_at += 2 + u16::from_be_bytes([encoded[4], encoded[5]]) as usize;
+    }
+    let mut output = Vec::with_capacity(encoded.len() + exif.len());
+    output.extend_from_slice(&encoded[..insert_at]);
+    output.extend_from_slice(exif);
+    output.extend_from_slice(&encoded[insert_at..]);
+    output
+}
+
+fn encode(data: &[u8], options: &RecompressOptions) -> Result<Option<Vec<u8>>> {
+    let img = image::load_from_memory(data).context("Failed to decode image")?;
+    if has_transparency(&img) {
+        return Ok(None);
+    }
+    let img = match options.max_dimension {
+        Some(max) if img.width().max(img.height()) > max => {
+            img.resize(max, max, image::imageops::FilterType::Lanczos3)
+        }
+        _ => img,
+    };
+    let rgb = img.to_rgb8();
+    let (width, height) = img.dimensions();
+    let mut encoded = Vec::new();
+    JpegEncoder::new_with_quality(&mut encoded, options.quality.clamp(1, 100))
+        .encode(rgb.as_raw(), width, height, ColorType::Rgb8)
+        .context("Failed to encode image")?;
+
+    if image::guess_format(data).ok() == Some(image::ImageFormat::Jpeg) {
+        encoded = copy_exif(data, encoded);
+    }
+    if encoded.len() as f64 > data.len() as f64 * MIN_SAVING_RATIO {
+        return Ok(None);
+    }
+    Ok(Some(encoded))
+}
+
+fn select_items(store: &MetadataStore, options: &RecompressOptions) -> Result<Vec<ItemRecord>> {
+    match &options.item_ids {
+        Some(ids) => {
+            let mut items = Vec::new();
+            for id in ids {
+                items.extend(store.get_item(id)?);
+            }
+            Ok(items)
+        }
+        None => {
+            let min_bytes = options.min_size_mb * 1024 * 1024;
+            Ok(store
+                .list_items(&ItemFilter::default())?
+                .into_iter()
+                .filter(|item| {
+                    item.image_path
+                        .as_deref()
+                        .and_then(|p| fs::metadata(p).ok())
+                        .map(|m| m.len() > min_bytes)
+                        .unwrap_or(false)
+                })
+                .collect())
+        }
+    }
+}
+
+// 元画像を JPEG に再圧縮して差し替える（元のファイルのハッシュ・サイズ・形式は記録しておく）
+pub fn recompress_originals(
+    paths: &LibraryPaths,
+    store: &Mutex<Option<MetadataStore>>,
+    options: &RecompressOptions,
+    reporter: &dyn ProgressReporter,
+) -> Result<RecompressReport> {
+    let items = {
+        let store = store.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        select_items(store, options)?
+    };
+    reporter.set_total(items.len() as u64);
+
+    let images_dir = paths.images_dir();
+    let mut report = RecompressReport::default();
+    for (i, item) in items.iter().enumerate() {
+        reporter.checkpoint()?;
+        let image_path = match item.image_path.as_deref() {
+            Some(path) => path,
+            None => continue,
+        };
+        let result = (|| -> Result<bool> {
+            let data = fs::read(image_path).with_context(|| format!("Failed to read {}", image_path))?;
+            let encoded = match encode(&data, options)? {
+                Some(encoded) => encoded,
+                None => return Ok(false),
+            };
+
+            let hash = hashing::content_hash(&encoded);
+            let new_path = images_dir.join(format!("{}.jpg", hash));
+            let tmp = new_path.with_extension("jpg.tmp");
+            fs::write(&tmp, &encoded)?;
+            fs::rename(&tmp, &new_path)?;
+            let new_path = new_path.to_string_lossy().to_string();
+
+            let original = RecompressedOriginal {
+                original_hash: item.content_hash.clone().unwrap_or_else(|| hashing::content_hash(&data)),
+                original_size: data.len() as u64,
+                original_format: image::guess_format(&data)
+                    .map(|f| format!("{:?}", f).to_lowercase())
+                    .unwrap_or_default(),
+                recompressed_at: Utc::now(),
+            };
+
+            let mut store = store.lock().unwrap();
+            let store = store.as_mut().context("Metadata store not initialized")?;
+            // 処理中に画像が差し替えられていたら何もしない
+            let current = store.get_item(&item.id)?;
+            if current.as_ref().and_then(|c| c.image_path.as_deref()) != Some(image_path) {
+                return Ok(false);
+            }
+            store.replace_original(&item.id, &new_path, &hash, &original)?;
+            if !store.is_image_referenced(image_path)? {
+                fs::remove_file(image_path)?;
+            }
+            report.bytes_before += data.len() as u64;
+            report.bytes_after += encoded.len() as u64;
+            Ok(true)
+        })();
+
+        match result {
+            Ok(true) => {
+                report.recompressed += 1;
+                report.changed_item_ids.push(item.id.clone());
+            }
+            Ok(false) => report.skipped += 1,
+            Err(e) => {
+                log::warn!("Failed to recompress {}: {}", Path::new(image_path).display(), e);
+                report.failed.push(serde_json::json!({ "item_id": item.id, "error": e.to_string() }));
+            }
+        }
+        reporter.progress(i as u64 + 1, &item.id);
+    }
+    Ok(report)
+}
@@ -0,0 +1,54 @@
+use crate::metadata_store::{ItemRecord, MetadataStore, Reminder};
+use anyhow::Result;
+use chrono::{Local, Utc};
+use serde::Serialize;
+
+// 通知の本文に使うテキストの長さ
+const SUMMARY_CHARS: usize = 60;
+
+// 通知と同時にフロントエンドへ送る内容（クリックで該当アイテムを開けるように）
+#[derive(Debug, Clone, Serialize)]
+pub struct DueReminder {
+    pub reminder: Reminder,
+    pub title: String,
+    pub body: String,
+}
+
+// アイテムの内容がわかる短い説明（メモ → OCR の1行目）
+fn item_summary(item: &ItemRecord) -> String {
+    let text = [item.memo.as_str(), item.ocr_text.as_str()]
+        .into_iter()
+        .flat_map(|t| t.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("");
+    let mut summary: String = text.chars().take(SUMMARY_CHARS).collect();
+    if text.chars().count() > SUMMARY_CHARS {
+        summary.push('…');
+    }
+    summary
+}
+
+fn notification_text(reminder: &Reminder, item: &ItemRecord) -> (String, String) {
+    let due = reminder.due_at.with_timezone(&Local).format("%Y/%m/%d %H:%M");
+    let title = if reminder.note.is_empty() {
+        format!("期限: {}", due)
+    } else {
+        format!("{}（期限: {}）", reminder.note, due)
+    };
+    (title, item_summary(item))
+}
+
+// 通知時刻を過ぎたリマインダーを通知済みにし、通知する内容を返す
+pub fn take_due(store: &mut MetadataStore) -> Result<Vec<DueReminder>> {
+    let mut due = Vec::new();
+    for reminder in store.due_reminders(Utc::now())? {
+        store.mark_reminder_notified(&reminder.id)?;
+        let Some(item) = store.get_item(&reminder.item_id)? else {
+            continue;
+        };
+        let (title, body) = notification_text(&reminder, &item);
+        due.push(DueReminder { reminder, title, body });
+    }
+    Ok(due)
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// メモリに置く分（グリッドのスクロールで行き来する範囲が収まる程度）
+pub const MEMORY_MAX_BYTES: usize = 64 * 1024 * 1024;
+pub const DISK_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFormat {
+    #[default]
+    Jpeg,
+    Png,
+}
+
+impl ResizeFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ResizeFormat::Jpeg => "jpg",
+            ResizeFormat::Png => "png",
+        }
+    }
+}
+
+// 元画像の内容のハッシュ・出力の大きさ・形式が同じなら同じ結果になる
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResizeKey {
+    pub hash: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: ResizeFormat,
+}
+
+impl ResizeKey {
+    fn file_name(&self) -> String {
+        format!("{}_{}x{}.{}", self.hash, self.width, self.height, self.format.extension())
+    }
+}
+
+#[derive(Default)]
+struct MemoryCache {
+    // 値は (データ, 最後に使った順番)
+    entries: HashMap<ResizeKey, (Vec<u8>, u64)>,
+    total_bytes: usize,
+    clock: u64,
+}
+
+impl MemoryCache {
+    fn get(&mut self, key: &ResizeKey) -> Option<Vec<u8>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(key).map(|(data, used)| {
+            *used = clock;
+            data.clone()
+        })
+    }
+
+    fn insert(&mut self, key: ResizeKey, data: Vec<u8>) {
+        if data.len() > MEMORY_MAX_BYTES {
+            return;
+        }
+        self.clock += 1;
+        self.total_bytes += data.len();
+        if let Some((old, _)) = self.entries.insert(key, (data, self.clock)) {
+            self.total_bytes -= old.len();
+        }
+        // 上限を超えたら最後に使ったのが古いものから捨てる
+        while self.total_bytes > MEMORY_MAX_BYTES {
+            let Some(oldest) = self.entries.iter().min_by_key(|(_, (_, used))| *used).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            if let Some((data, _)) = self.entries.remove(&oldest) {
+                self.total_bytes -= data.len();
+            }
+        }
+    }
+
+    fn remove_hash(&mut self, hash: &str) {
+        let total_bytes = &mut self.total_bytes;
+        self.entries.retain(|key, (data, _)| {
+            let keep = key.hash != hash;
+            if !keep {
+                *total_bytes -= data.len();
+            }
+            keep
+        });
+    }
+}
+
+// resize_image の結果のキャッシュ（メモリとディスクの2段の LRU）
+// 同じアイテムを開き直したりグリッドを戻ったりしたときにデコードとエンコードをやり直さない
+pub struct ResizeCache {
+    dir: PathBuf,
+    disk_max_bytes: u64,
+    memory: Mutex<MemoryCache>,
+}
+
+impl ResizeCache {
+    pub fn new(dir: PathBuf, disk_max_bytes: u64) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create resize cache directory: {}", dir.display()))?;
+        Ok(ResizeCache {
+            dir,
+            disk_max_bytes,
+            memory: Mutex::new(MemoryCache::default()),
+        })
+    }
+
+    pub fn get(&self, key: &ResizeKey) -> Option<Vec<u8>> {
+        if let Some(data) = self.memory.lock().unwrap().get(key) {
+            return Some(data);
+        }
+        let path = self.dir.join(key.file_name());
+        let data = fs::read(&path).ok()?;
+        // LRU判定のため最終利用時刻として更新日時を更新
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        self.memory.lock().unwrap().insert(key.clone(), data.clone());
+        Some(data)
+    }
+
+    // persist が false ならメモリにだけ置く（非公開アイテムの画像はディスクに残さない）
+    pub fn insert(&self, key: ResizeKey, data: &[u8], persist: bool) -> Result<()> {
+        if persist {
+            let path = self.dir.join(key.file_name());
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, data)?;
+            fs::rename(&tmp, &path)?;
+            self.evict()?;
+        }
+        self.memory.lock().unwrap().insert(key, data.to_vec());
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((entry.path(), metadata.len(), modified));
+            }
+        }
+        Ok(entries)
+    }
+
+    // 上限を超えたら最終利用が古いものから削除
+    fn evict(&self) -> Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.disk_max_bytes {
+            return Ok(());
+        }
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if total <= self.disk_max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(len);
+            }
+        }
+        Ok(())
+    }
+
+    // 元画像のハッシュに対応する結果をすべて消す（非公開にした画像など）
+    pub fn remove(&self, source_hash: &str) -> Result<()> {
+        self.memory.lock().unwrap().remove_hash(source_hash);
+        let prefix = format!("{}_", source_hash);
+        for (path, _, _) in self.entries()? {
+            if file_name_starts_with(&path, &prefix) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // ロックしたときに、メモリにだけ置いた非公開アイテムの画像も含めて捨てる
+    pub fn clear_memory(&self) {
+        *self.memory.lock().unwrap() = MemoryCache::default();
+    }
+}
+
+fn file_name_starts_with(path: &Path, prefix: &str) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with(prefix))
+}
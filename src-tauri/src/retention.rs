@@ -0,0 +1,247 @@
+use crate::metadata_store::{ItemFilter, ItemRecord, MetadataStore};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Months, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// 削除の対象になってからゴミ箱に移すまでの日数（この間に確認して除外できる）
+pub const DEFAULT_REVIEW_DAYS: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RetentionAction {
+    // 書類の日付（なければ取り込んだ日）から after_days 日を過ぎたらゴミ箱に移す
+    Delete {
+        after_days: u32,
+    },
+    // years 年（None なら無期限）は削除のルールでもゴミ箱の保持期間でも自動では削除しない
+    Keep {
+        #[serde(default)]
+        years: Option<u32>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionRule {
+    #[serde(default)]
+    pub id: String,
+    // 子のタグの付いたアイテムにも当てる（tax なら tax/2024 も）
+    pub tag: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub action: RetentionAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionSettings {
+    #[serde(default)]
+    pub rules: Vec<RetentionRule>,
+    #[serde(default = "default_review_days")]
+    pub review_days: u32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_review_days() -> u32 {
+    DEFAULT_REVIEW_DAYS
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        RetentionSettings {
+            rules: Vec::new(),
+            review_days: DEFAULT_REVIEW_DAYS,
+        }
+    }
+}
+
+impl RetentionSettings {
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    // ID のないルールには ID を付けて保存する
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        for rule in &mut self.rules {
+            rule.tag = rule.tag.trim().to_string();
+            if rule.tag.is_empty() {
+                bail!("Retention rule has no tag");
+            }
+            if rule.id.is_empty() {
+                rule.id = uuid::Uuid::new_v4().to_string();
+            }
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = &RetentionRule> {
+        self.rules.iter().filter(|rule| rule.enabled)
+    }
+
+    fn has_delete_rules(&self) -> bool {
+        self.enabled()
+            .any(|rule| matches!(rule.action, RetentionAction::Delete { .. }))
+    }
+
+    fn has_keep_rules(&self) -> bool {
+        self.enabled()
+            .any(|rule| matches!(rule.action, RetentionAction::Keep { .. }))
+    }
+}
+
+// 削除の確認待ちのアイテム
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionCandidate {
+    pub item: ItemRecord,
+    pub rule_id: String,
+    pub tag: String,
+    // 削除の対象になった日時と、除外しなければゴミ箱に移す日時
+    pub flagged_at: DateTime<Utc>,
+    pub delete_at: DateTime<Utc>,
+    // ユーザーが削除しないことにしたもの（ルールを変えても残す）
+    pub exempt: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionOutcome {
+    // 新たに削除の対象になったアイテムの数
+    pub flagged: usize,
+    // 確認期間を過ぎてゴミ箱に移したアイテム
+    pub trashed: Vec<String>,
+}
+
+// 保持期間を数える起点（書類の日付、なければ取り込んだ日）
+fn base_date(item: &ItemRecord) -> DateTime<Utc> {
+    item.document_date
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+        .unwrap_or(item.created_at)
+}
+
+// 保持するルールの期間内にあるか
+fn is_kept(settings: &RetentionSettings, item: &ItemRecord, tags: &[String], now: DateTime<Utc>) -> bool {
+    settings.enabled().any(|rule| match rule.action {
+        RetentionAction::Keep { years } if tags.contains(&rule.tag) => match years {
+            Some(years) => base_date(item)
+                .checked_add_months(Months::new(years.saturating_mul(12)))
+                .map_or(true, |until| now < until),
+            None => true,
+        },
+        _ => false,
+    })
+}
+
+// 削除のルールで期限を過ぎたもの（保持のルールに当たるものは除く）
+fn expired_rule<'a>(
+    settings: &'a RetentionSettings,
+    item: &ItemRecord,
+    tags: &[String],
+    now: DateTime<Utc>,
+) -> Option<&'a RetentionRule> {
+    if is_kept(settings, item, tags, now) {
+        return None;
+    }
+    settings.enabled().find(|rule| match rule.action {
+        RetentionAction::Delete { after_days } => {
+            tags.contains(&rule.tag) && base_date(item) + Duration::days(after_days as i64) <= now
+        }
+        RetentionAction::Keep { .. } => false,
+    })
+}
+
+// 期限を過ぎたアイテムを確認待ちにし、期限を過ぎなくなったもの（タグを外したなど）は外して、確認待ちの一覧を返す
+pub fn review(store: &mut MetadataStore, settings: &RetentionSettings) -> Result<(Vec<RetentionCandidate>, usize)> {
+    let now = Utc::now();
+    let mut expired = Vec::new();
+    if settings.has_delete_rules() {
+        for item in store.list_items(&ItemFilter::default())? {
+            let tags = store.tags_with_ancestors(&item.tags)?;
+            if let Some(rule) = expired_rule(settings, &item, &tags, now) {
+                expired.push((item, rule));
+            }
+        }
+    }
+
+    let reviews: HashMap<String, _> = store
+        .retention_reviews()?
+        .into_iter()
+        .map(|review| (review.item_id.clone(), review))
+        .collect();
+    let stale: Vec<String> = reviews
+        .values()
+        .filter(|review| !review.exempt && !expired.iter().any(|(item, _)| item.id == review.item_id))
+        .map(|review| review.item_id.clone())
+        .collect();
+    store.clear_retention_reviews(&stale)?;
+
+    let mut flagged = 0;
+    let mut candidates = Vec::new();
+    for (item, rule) in expired {
+        let (flagged_at, exempt) = match reviews.get(&item.id) {
+            Some(review) => (review.flagged_at, review.exempt),
+            None => {
+                store.flag_retention_review(&item.id, &rule.id, now)?;
+                flagged += 1;
+                (now, false)
+            }
+        };
+        candidates.push(RetentionCandidate {
+            item,
+            rule_id: rule.id.clone(),
+            tag: rule.tag.clone(),
+            flagged_at,
+            delete_at: flagged_at + Duration::days(settings.review_days as i64),
+            exempt,
+        });
+    }
+    candidates.sort_by_key(|candidate| candidate.delete_at);
+    Ok((candidates, flagged))
+}
+
+// 確認期間を過ぎて除外されていないアイテムをゴミ箱に移す（定期的に実行する）
+pub fn enforce(store: &mut MetadataStore, settings: &RetentionSettings) -> Result<RetentionOutcome> {
+    let (candidates, flagged) = review(store, settings)?;
+    let now = Utc::now();
+    let mut trashed = Vec::new();
+    for candidate in candidates.iter().filter(|c| !c.exempt && c.delete_at <= now) {
+        if store.trash_item(&candidate.item.id)? {
+            trashed.push(candidate.item.id.clone());
+        }
+    }
+    store.clear_retention_reviews(&trashed)?;
+    Ok(RetentionOutcome { flagged, trashed })
+}
+
+// ゴミ箱のアイテムのうち、保持するルールの期間内にないもの（ゴミ箱の保持期間で削除してよいもの）
+pub fn unprotected(store: &MetadataStore, settings: &RetentionSettings, ids: Vec<String>) -> Result<Vec<String>> {
+    if !settings.has_keep_rules() {
+        return Ok(ids);
+    }
+    let now = Utc::now();
+    let trashed: HashMap<String, ItemRecord> = store
+        .list_trash()?
+        .into_iter()
+        .map(|trashed| (trashed.item.id.clone(), trashed.item))
+        .collect();
+    let mut purgeable = Vec::new();
+    for id in ids {
+        if let Some(item) = trashed.get(&id) {
+            if is_kept(settings, item, &store.tags_with_ancestors(&item.tags)?, now) {
+                continue;
+            }
+        }
+        purgeable.push(id);
+    }
+    Ok(purgeable)
+}
@@ -0,0 +1,248 @@
+use crate::metadata_store::{ItemFilter, ItemRecord, MetadataStore};
+use anyhow::{bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    #[default]
+    OcrText,
+    Memo,
+    Location,
+    // OCR テキスト・メモ・地名のどれか
+    Any,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCondition {
+    #[serde(default)]
+    pub field: RuleField,
+    // 正規表現（例: スターバックス|Starbucks）
+    pub pattern: String,
+    #[serde(default = "default_true")]
+    pub case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub conditions: Vec<RuleCondition>,
+    // true ならすべての条件、false ならどれか1つに一致したとき
+    #[serde(default = "default_true")]
+    pub match_all: bool,
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    // アルバム名または ID（名前で見つからなければ作る）
+    #[serde(default)]
+    pub add_albums: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// ルールを当てた結果、アイテムに付くもの
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RuleOutcome {
+    pub rule_ids: Vec<String>,
+    pub add_tags: Vec<String>,
+    pub add_albums: Vec<String>,
+}
+
+impl RuleOutcome {
+    pub fn is_empty(&self) -> bool {
+        self.rule_ids.is_empty()
+    }
+}
+
+// ドライランで一致した既存アイテム
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleMatch {
+    pub item_id: String,
+    pub add_tags: Vec<String>,
+    pub add_albums: Vec<String>,
+}
+
+struct CompiledRule {
+    rule: Rule,
+    patterns: Vec<(RuleField, Regex)>,
+}
+
+impl CompiledRule {
+    fn compile(rule: Rule) -> Result<Self> {
+        if rule.conditions.is_empty() {
+            bail!("Rule has no conditions: {}", rule.name);
+        }
+        let patterns = rule
+            .conditions
+            .iter()
+            .map(|c| {
+                let regex = RegexBuilder::new(&c.pattern)
+                    .case_insensitive(c.case_insensitive)
+                    .build()
+                    .with_context(|| format!("Invalid pattern: {}", c.pattern))?;
+                Ok((c.field, regex))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(CompiledRule { rule, patterns })
+    }
+
+    fn matches(&self, item: &ItemRecord) -> bool {
+        let location = item.location_name.as_deref().unwrap_or("");
+        let mut results = self.patterns.iter().map(|(field, regex)| match field {
+            RuleField::OcrText => regex.is_match(&item.ocr_text),
+            RuleField::Memo => regex.is_match(&item.memo),
+            RuleField::Location => regex.is_match(location),
+            RuleField::Any => [item.ocr_text.as_str(), item.memo.as_str(), location].iter().any(|t| regex.is_match(t)),
+        });
+        if self.rule.match_all {
+            results.all(|m| m)
+        } else {
+            results.any(|m| m)
+        }
+    }
+}
+
+fn evaluate(rules: &[CompiledRule], item: &ItemRecord) -> RuleOutcome {
+    let mut outcome = RuleOutcome::default();
+    for compiled in rules.iter().filter(|c| c.rule.enabled && c.matches(item)) {
+        outcome.rule_ids.push(compiled.rule.id.clone());
+        for tag in &compiled.rule.add_tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !item.tags.iter().any(|t| t == tag) && !outcome.add_tags.iter().any(|t| t == tag) {
+                outcome.add_tags.push(tag.to_string());
+            }
+        }
+        for album in &compiled.rule.add_albums {
+            let album = album.trim();
+            if !album.is_empty() && !outcome.add_albums.iter().any(|a| a == album) {
+                outcome.add_albums.push(album.to_string());
+            }
+        }
+    }
+    outcome
+}
+
+// アルバムを ID か名前で探し、なければ最上位に作る
+fn resolve_album(store: &mut MetadataStore, name_or_id: &str) -> Result<String> {
+    if let Some(album) = store.get_album(name_or_id)? {
+        return Ok(album.id);
+    }
+    let existing = store
+        .list_albums()?
+        .into_iter()
+        .find(|a| a.title.to_lowercase() == name_or_id.to_lowercase());
+    match existing {
+        Some(album) => Ok(album.id),
+        None => Ok(store.create_album(name_or_id, None)?.id),
+    }
+}
+
+// ルールで決まったアルバムへアイテムを入れる（アイテムは登録済みであること）
+pub fn add_to_albums(store: &mut MetadataStore, item_id: &str, albums: &[String]) -> Result<()> {
+    for album in albums {
+        let album_id = resolve_album(store, album)?;
+        store.add_album_items(&album_id, &[item_id.to_string()], None)?;
+    }
+    Ok(())
+}
+
+// 取り込み時の自動タグ付けルール（rules.json に上から順に保存する）
+pub struct RulesService {
+    path: PathBuf,
+    rules: Mutex<Vec<CompiledRule>>,
+}
+
+impl RulesService {
+    pub fn new(path: PathBuf) -> Self {
+        let rules = Self::load(&path)
+            .into_iter()
+            .filter_map(|rule| {
+                CompiledRule::compile(rule)
+                    .map_err(|e| log::warn!("Skipping invalid rule: {}", e))
+                    .ok()
+            })
+            .collect();
+        RulesService {
+            path,
+            rules: Mutex::new(rules),
+        }
+    }
+
+    fn load(path: &Path) -> Vec<Rule> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, rules: &[CompiledRule]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let rules: Vec<&Rule> = rules.iter().map(|c| &c.rule).collect();
+        fs::write(&self.path, serde_json::to_string_pretty(&rules)?)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<Rule> {
+        self.rules.lock().unwrap().iter().map(|c| c.rule.clone()).collect()
+    }
+
+    // ID が空なら新しいルールとして末尾に追加し、あれば置き換える
+    pub fn save_rule(&self, mut rule: Rule) -> Result<Rule> {
+        if rule.id.is_empty() {
+            rule.id = uuid::Uuid::new_v4().to_string();
+        }
+        let compiled = CompiledRule::compile(rule.clone())?;
+        let mut rules = self.rules.lock().unwrap();
+        match rules.iter_mut().find(|c| c.rule.id == rule.id) {
+            Some(existing) => *existing = compiled,
+            None => rules.push(compiled),
+        }
+        self.save(&rules)?;
+        Ok(rule)
+    }
+
+    pub fn delete_rule(&self, id: &str) -> Result<bool> {
+        let mut rules = self.rules.lock().unwrap();
+        let before = rules.len();
+        rules.retain(|c| c.rule.id != id);
+        if rules.len() == before {
+            return Ok(false);
+        }
+        self.save(&rules)?;
+        Ok(true)
+    }
+
+    // 有効なルールをすべて当てる
+    pub fn evaluate(&self, item: &ItemRecord) -> RuleOutcome {
+        evaluate(&self.rules.lock().unwrap(), item)
+    }
+}
+
+// 保存前のルールでも、既存のアイテムのどれに一致するかを調べる（変更はしない）
+pub fn dry_run(store: &MetadataStore, rule: Rule) -> Result<Vec<RuleMatch>> {
+    let compiled = [CompiledRule::compile(Rule { enabled: true, ..rule })?];
+    let matches = store
+        .list_items(&ItemFilter::default())?
+        .iter()
+        .filter_map(|item| {
+            let outcome = evaluate(&compiled, item);
+            (!outcome.is_empty()).then(|| RuleMatch {
+                item_id: item.id.clone(),
+                add_tags: outcome.add_tags,
+                add_albums: outcome.add_albums,
+            })
+        })
+        .collect();
+    Ok(matches)
+}
@@ -0,0 +1,243 @@
+use crate::sync::{RemoteObject, SyncBackend};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::blocking::{Client, Response};
+use reqwest::{header, Method, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+// SigV4 の URI エンコード（RFC 3986 の非予約文字以外をすべてエンコード）
+const URI_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+// S3 互換ストレージ（AWS・MinIO・Cloudflare R2・Wasabi など）の接続設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    // バケット内の保存先（複数のライブラリを1つのバケットに置く場合など）
+    #[serde(default)]
+    pub prefix: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    is_truncated: bool,
+    #[serde(default)]
+    contents: Vec<ListedObject>,
+    next_continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedObject {
+    key: String,
+    #[serde(rename = "ETag")]
+    etag: String,
+    size: u64,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, URI_ENCODE).to_string()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn trim_etag(etag: &str) -> String {
+    etag.trim_matches('"').to_string()
+}
+
+// パス形式（https://endpoint/bucket/key）でアクセスする S3 クライアント
+pub struct S3Backend {
+    config: S3Config,
+    endpoint: Url,
+    prefix: String,
+    client: Client,
+}
+
+impl S3Backend {
+    pub fn new(config: S3Config) -> Result<Self> {
+        let endpoint = Url::parse(&config.endpoint).context("Invalid S3 endpoint URL")?;
+        if endpoint.host_str().is_none() {
+            bail!("Invalid S3 endpoint URL: {}", config.endpoint);
+        }
+        if config.bucket.is_empty() {
+            bail!("S3 bucket is not set");
+        }
+
+        let prefix = config.prefix.trim_matches('/');
+        let prefix = if prefix.is_empty() { String::new() } else { format!("{}/", prefix) };
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(S3Backend {
+            config,
+            endpoint,
+            prefix,
+            client,
+        })
+    }
+
+    fn host(&self) -> String {
+        let host = self.endpoint.host_str().unwrap_or_default();
+        match self.endpoint.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        }
+    }
+
+    // キーのパス区切りは残してセグメントごとにエンコードする
+    fn object_path(&self, key: &str) -> String {
+        let mut path = format!("/{}", encode(&self.config.bucket));
+        for segment in format!("{}{}", self.prefix, key).split('/') {
+            path.push('/');
+            path.push_str(&encode(segment));
+        }
+        path
+    }
+
+    fn send(&self, method: Method, path: &str, query: &[(&str, &str)], body: Vec<u8>) -> Result<Response> {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let mut query: Vec<(String, String)> = query.iter().map(|(k, v)| (encode(k), encode(v))).collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method,
+            path,
+            canonical_query,
+            self.host(),
+            payload_hash,
+            amz_date,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut signing_key = hmac_sha256(
+            format!("AWS4{}", self.config.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.config.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key_id, scope, signed_headers, signature
+        );
+
+        let mut url = format!("{}{}", self.endpoint.origin().ascii_serialization(), path);
+        if !canonical_query.is_empty() {
+            url.push('?');
+            url.push_str(&canonical_query);
+        }
+
+        let response = self
+            .client
+            .request(method.clone(), &url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(header::AUTHORIZATION, authorization)
+            .body(body)
+            .send()
+            .with_context(|| format!("S3 request failed: {} {}", method, path))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().unwrap_or_default();
+            bail!("S3 {} {} returned {}: {}", method, path, status, text.chars().take(500).collect::<String>());
+        }
+        Ok(response)
+    }
+}
+
+impl SyncBackend for S3Backend {
+    fn remote_id(&self) -> String {
+        format!("s3:{}/{}/{}", self.host(), self.config.bucket, self.prefix)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        let bucket_path = format!("/{}", encode(&self.config.bucket));
+        let full_prefix = format!("{}{}", self.prefix, prefix);
+        let mut objects = Vec::new();
+        let mut continuation: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", full_prefix.as_str())];
+            if let Some(token) = continuation.as_deref() {
+                query.push(("continuation-token", token));
+            }
+            let xml = self.send(Method::GET, &bucket_path, &query, Vec::new())?.text()?;
+            let page: ListBucketResult = quick_xml::de::from_str(&xml).context("Invalid S3 list response")?;
+
+            for object in page.contents {
+                let key = match object.key.strip_prefix(&self.prefix) {
+                    Some(key) if !key.is_empty() && !key.ends_with('/') => key.to_string(),
+                    _ => continue,
+                };
+                objects.push(RemoteObject {
+                    key,
+                    etag: trim_etag(&object.etag),
+                    size: object.size,
+                    modified: object.last_modified,
+                });
+            }
+
+            match page.next_continuation_token {
+                Some(token) if page.is_truncated => continuation = Some(token),
+                _ => break,
+            }
+        }
+        Ok(objects)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let response = self.send(Method::GET, &self.object_path(key), &[], Vec::new())?;
+        Ok(response.bytes()?.to_vec())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        let response = self.send(Method::PUT, &self.object_path(key), &[], data.to_vec())?;
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(trim_etag)
+            .with_context(|| format!("S3 did not return an ETag for {}", key))?;
+        Ok(etag)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.send(Method::DELETE, &self.object_path(key), &[], Vec::new())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::{bail, Context, Result};
+use image::RgbaImage;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 選択された範囲（スクリーンショットの画素単位）
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// 指定した位置（カーソル位置）を含むモニター全体を撮影して staging ディレクトリに保存する
+// 選択用のオーバーレイを出す前に撮っておき、オーバーレイ自体が写り込まないようにする
+pub fn capture_monitor(staging_dir: &Path, point: Option<(i32, i32)>) -> Result<PathBuf> {
+    let monitor = match point.and_then(|(x, y)| xcap::Monitor::from_point(x, y).ok()) {
+        Some(monitor) => monitor,
+        None => xcap::Monitor::all()?
+            .into_iter()
+            .find(|m| m.is_primary())
+            .context("No monitor found")?,
+    };
+    let shot = monitor.capture_image().context("Failed to capture screen")?;
+    // xcap が使う image クレートのバージョンに依存しないよう画素列で受け渡す
+    let (width, height) = (shot.width(), shot.height());
+    let image = RgbaImage::from_raw(width, height, shot.into_raw()).context("Invalid screenshot")?;
+
+    fs::create_dir_all(staging_dir)?;
+    let path = staging_dir.join(format!("screen-{}.png", uuid::Uuid::new_v4().simple()));
+    image.save(&path)?;
+    Ok(path)
+}
+
+// スクリーンショットから選択範囲を切り出して保存する
+pub fn crop(screenshot: &Path, region: &Region) -> Result<PathBuf> {
+    let mut image = image::open(screenshot)
+        .with_context(|| format!("Failed to open {}", screenshot.display()))?
+        .to_rgba8();
+    let x = region.x.min(image.width());
+    let y = region.y.min(image.height());
+    let width = region.width.min(image.width() - x);
+    let height = region.height.min(image.height() - y);
+    if width == 0 || height == 0 {
+        bail!("Selected region is empty");
+    }
+
+    let cropped = image::imageops::crop(&mut image, x, y, width, height).to_image();
+    let path = screenshot.with_file_name(format!("capture-{}.png", uuid::Uuid::new_v4().simple()));
+    cropped.save(&path)?;
+    Ok(path)
+}
@@ -1,3 +1,8 @@
+use crate::document_types::DocumentType;
+use crate::entities;
+use crate::geocoding::GeoFilter;
+use crate::ocr::{self, OcrWord};
+use crate::receipts::ReceiptFilter;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -7,9 +12,9 @@ use tantivy::{
     collector::TopDocs,
     directory::MmapDirectory,
     doc,
-    query::{BooleanQuery, Occur, QueryParser, TermQuery},
-    schema::{Field, IndexRecordOption, Schema, SchemaBuilder, TextFieldIndexing, TextOptions, STORED, TEXT},
-    Index, IndexReader, IndexWriter, Term,
+    query::{BooleanQuery, Occur, Query, QueryParser, TermQuery},
+    schema::{Field, IndexRecordOption, Schema, SchemaBuilder, TextFieldIndexing, TextOptions, STORED, STRING, TEXT},
+    DocAddress, Index, IndexReader, IndexWriter, Term,
 };
 use uuid::Uuid;
 
@@ -24,6 +29,23 @@ pub struct SearchableItem {
     pub updated_at: DateTime<Utc>,
     pub group_title: Option<String>,
     pub image_path: Option<String>,
+    // 単語ごとの位置（ocr_text が空なら単語を並べたものをインデックスに入れる）
+    #[serde(default)]
+    pub ocr_words: Vec<OcrWord>,
+    // 電話番号・メールアドレス・追跡番号などの正規化した値（完全一致で探す）
+    #[serde(default)]
+    pub entities: Vec<String>,
+    #[serde(default)]
+    pub doc_type: DocumentType,
+    // 画像の説明文（文字のない写真もキーワードで見つかるようにする）
+    #[serde(default)]
+    pub caption: Option<String>,
+    // OCR のテキストを訳したもの（日本語で探しても英語の書類が見つかるように、またその逆）
+    #[serde(default)]
+    pub translated_text: String,
+    // 一覧に出す見出し（店名や書類の1行目）
+    #[serde(default)]
+    pub title: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,7 +56,7 @@ pub struct SearchResult {
     pub matched_fields: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SearchQuery {
     pub query: String,
     pub fields: Option<Vec<String>>,
@@ -42,6 +64,87 @@ pub struct SearchQuery {
     pub date_to: Option<DateTime<Utc>>,
     pub tags: Option<Vec<String>>,
     pub limit: Option<usize>,
+    // レシートの店名・金額・購入日での絞り込み（メタデータストアで絞り込む）
+    pub receipt: Option<ReceiptFilter>,
+    // 書類の種類（いずれかに当てはまるもの）
+    pub doc_types: Option<Vec<DocumentType>>,
+    // キーワード・画像の意味・その両方のどれで探すか（省略時はキーワード）
+    #[serde(default)]
+    pub mode: SearchMode,
+    // date_from / date_to を撮影日と書類の日付のどちらに当てるか（省略時は撮影日）
+    #[serde(default)]
+    pub date_field: DateField,
+    // 地点から半径内の座標を持つもの（メタデータストアで絞り込む）
+    pub near: Option<GeoFilter>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateField {
+    #[default]
+    Captured,
+    // OCR のテキストから読み取った発行日など（読み取れなかったアイテムは撮影日を使う）
+    Document,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    #[default]
+    Keyword,
+    // 画像の埋め込みとの類似度
+    Semantic,
+    // キーワードの順位と類似度の順位を融合する（OCR が崩れた画像も見つかる）
+    Hybrid,
+}
+
+// 順位の融合（Reciprocal Rank Fusion）の定数（大きいほど上位と下位の差が小さくなる）
+const RRF_K: f32 = 60.0;
+
+// 複数の検索結果を順位だけで混ぜる（BM25 と類似度のように尺度の違う点数を直接比べない）
+// 同じアイテムは点数を足し、ハイライトと一致したフィールドをまとめる
+pub fn reciprocal_rank_fusion(lists: Vec<Vec<SearchResult>>, limit: usize) -> Vec<SearchResult> {
+    let mut fused: Vec<SearchResult> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for list in lists {
+        for (rank, result) in list.into_iter().enumerate() {
+            let score = 1.0 / (RRF_K + rank as f32 + 1.0);
+            match positions.get(&result.id) {
+                Some(&i) => {
+                    let merged = &mut fused[i];
+                    merged.score += score;
+                    merged.highlights.extend(result.highlights);
+                    for field in result.matched_fields {
+                        if !merged.matched_fields.contains(&field) {
+                            merged.matched_fields.push(field);
+                        }
+                    }
+                }
+                None => {
+                    positions.insert(result.id.clone(), fused.len());
+                    fused.push(SearchResult { score, ..result });
+                }
+            }
+        }
+    }
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+    fused.truncate(limit);
+    fused
+}
+
+// 検索文字列から album:名前 / album:"名前 (空白あり)" を取り出し、残りのキーワードと分ける
+// （アルバムは検索インデックスに持たないため、メタデータストアで絞り込む）
+pub fn extract_album_filters(query: &str) -> (String, Vec<String>) {
+    static ALBUM_FILTER: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+    let pattern = ALBUM_FILTER.get_or_init(|| regex::Regex::new(r#"(?i)(?:^|\s)album:(?:"([^"]*)"|(\S+))"#).unwrap());
+    let albums = pattern
+        .captures_iter(query)
+        .filter_map(|c| c.get(1).or_else(|| c.get(2)))
+        .map(|m| m.as_str().trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    let rest = pattern.replace_all(query, " ").split_whitespace().collect::<Vec<_>>().join(" ");
+    (rest, albums)
 }
 
 pub struct SearchEngine {
@@ -50,6 +153,8 @@ pub struct SearchEngine {
     writer: IndexWriter,
     schema: Schema,
     fields: HashMap<String, Field>,
+    // 既存のインデックスのフィールドが足りず作り直した（呼び出し側でアイテムを入れ直す）
+    recreated: bool,
 }
 
 impl SearchEngine {
@@ -57,10 +162,18 @@ impl SearchEngine {
         let schema = Self::create_schema();
         let fields = Self::get_fields(&schema);
         
-        let index = if index_path.exists() {
-            Index::open_in_dir(index_path)?
-        } else {
-            Index::create_in_dir(index_path, schema.clone())?
+        let mut recreated = false;
+        let index = match Index::open_in_dir(index_path) {
+            Ok(index) if Self::has_all_fields(&index.schema(), &schema) => index,
+            Ok(_) => {
+                // 新しいフィールドを追加したバージョンでは空のインデックスから作り直す
+                log::info!("Search index schema changed, recreating: {}", index_path.display());
+                std::fs::remove_dir_all(index_path)?;
+                std::fs::create_dir_all(index_path)?;
+                recreated = true;
+                Index::create_in_dir(index_path, schema.clone())?
+            }
+            Err(_) => Index::create_in_dir(index_path, schema.clone())?,
         };
 
         let reader = index.reader()?;
@@ -72,9 +185,19 @@ impl SearchEngine {
             writer,
             schema,
             fields,
+            recreated,
         })
     }
 
+    fn has_all_fields(existing: &Schema, schema: &Schema) -> bool {
+        schema.fields().all(|(_, entry)| existing.get_field(entry.name()).is_ok())
+    }
+
+    // 開いたときにインデックスを作り直したので、メタデータストアから入れ直す必要がある
+    pub fn was_recreated(&self) -> bool {
+        self.recreated
+    }
+
     fn create_schema() -> Schema {
         let mut schema_builder = SchemaBuilder::new();
         
@@ -133,6 +256,46 @@ impl SearchEngine {
                 .set_stored(),
         );
         let image_path_field = schema_builder.add_text_field("image_path", STORED);
+        // 正規化した値をそのまま1語として入れる（分かち書きしない）
+        let entities_field = schema_builder.add_text_field(
+            "entities",
+            TextOptions::default().set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer("raw")
+                    .set_index_option(IndexRecordOption::Basic),
+            ),
+        );
+        let doc_type_field = schema_builder.add_text_field("doc_type", STRING);
+        let caption_field = schema_builder.add_text_field(
+            "caption",
+            TextOptions::default()
+                .set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer("standard")
+                        .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+                )
+                .set_stored(),
+        );
+        let translated_text_field = schema_builder.add_text_field(
+            "translated_text",
+            TextOptions::default()
+                .set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer("standard")
+                        .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+                )
+                .set_stored(),
+        );
+        let title_field = schema_builder.add_text_field(
+            "title",
+            TextOptions::default()
+                .set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer("standard")
+                        .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+                )
+                .set_stored(),
+        );
 
         schema_builder.build()
     }
@@ -148,13 +311,23 @@ impl SearchEngine {
         fields.insert("updated_at".to_string(), schema.get_field("updated_at").unwrap());
         fields.insert("group_title".to_string(), schema.get_field("group_title").unwrap());
         fields.insert("image_path".to_string(), schema.get_field("image_path").unwrap());
+        fields.insert("entities".to_string(), schema.get_field("entities").unwrap());
+        fields.insert("doc_type".to_string(), schema.get_field("doc_type").unwrap());
+        fields.insert("caption".to_string(), schema.get_field("caption").unwrap());
+        fields.insert("translated_text".to_string(), schema.get_field("translated_text").unwrap());
+        fields.insert("title".to_string(), schema.get_field("title").unwrap());
         fields
     }
 
-    pub fn add_item(&mut self, item: SearchableItem) -> Result<()> {
-        let doc = doc!(
+    fn document(&self, item: SearchableItem) -> tantivy::TantivyDocument {
+        let ocr_text = if item.ocr_text.is_empty() {
+            ocr::join_words(&item.ocr_words)
+        } else {
+            item.ocr_text
+        };
+        let mut doc = doc!(
             self.fields["id"] => item.id,
-            self.fields["ocr_text"] => item.ocr_text,
+            self.fields["ocr_text"] => ocr_text,
             self.fields["memo"] => item.memo,
             self.fields["tags"] => item.tags.join(" "),
             self.fields["location_name"] => item.location_name.unwrap_or_default(),
@@ -162,8 +335,19 @@ impl SearchEngine {
             self.fields["updated_at"] => tantivy::DateTime::from_utc(item.updated_at),
             self.fields["group_title"] => item.group_title.unwrap_or_default(),
             self.fields["image_path"] => item.image_path.unwrap_or_default(),
+            self.fields["doc_type"] => item.doc_type.as_str(),
+            self.fields["caption"] => item.caption.unwrap_or_default(),
+            self.fields["translated_text"] => item.translated_text,
+            self.fields["title"] => item.title.unwrap_or_default(),
         );
+        for entity in &item.entities {
+            doc.add_text(self.fields["entities"], entity);
+        }
+        doc
+    }
 
+    pub fn add_item(&mut self, item: SearchableItem) -> Result<()> {
+        let doc = self.document(item);
         self.writer.add_document(doc)?;
         self.writer.commit()?;
         Ok(())
@@ -179,6 +363,18 @@ impl SearchEngine {
         Ok(())
     }
 
+    // 複数のアイテムを置き換えてからまとめて1回だけコミットする（一括編集用）
+    pub fn update_items(&mut self, items: Vec<SearchableItem>) -> Result<()> {
+        for item in items {
+            let term = Term::from_field_text(self.fields["id"], &item.id);
+            self.writer.delete_term(term);
+            let doc = self.document(item);
+            self.writer.add_document(doc)?;
+        }
+        self.writer.commit()?;
+        Ok(())
+    }
+
     pub fn delete_item(&mut self, item_id: &str) -> Result<()> {
         let term = Term::from_field_text(self.fields["id"], item_id);
         self.writer.delete_term(term);
@@ -194,6 +390,9 @@ impl SearchEngine {
             self.fields["tags"],
             self.fields["location_name"],
             self.fields["group_title"],
+            self.fields["caption"],
+            self.fields["translated_text"],
+            self.fields["title"],
         ]);
 
         // メインクエリの構築
@@ -211,8 +410,20 @@ impl SearchEngine {
             query_parser.parse_query(&query.query)?
         };
 
+        // 電話番号・メールアドレスなどの形の検索語は、エンティティのフィールドの完全一致でも探す
+        let main_query: Box<dyn Query> = match entities::search_key(&query.query) {
+            Some(key) => {
+                let entity_term = Term::from_field_text(self.fields["entities"], &key);
+                Box::new(BooleanQuery::new(vec![
+                    (Occur::Should, Box::new(main_query) as Box<dyn Query>),
+                    (Occur::Should, Box::new(TermQuery::new(entity_term, IndexRecordOption::Basic)) as Box<dyn Query>),
+                ]))
+            }
+            None => Box::new(main_query),
+        };
+
         // フィルター条件の構築
-        let mut filters = Vec::new();
+        let mut filters: Vec<(Occur, Box<dyn Query>)> = Vec::new();
 
         // 日付フィルター
         if let Some(date_from) = query.date_from {
@@ -239,6 +450,18 @@ impl SearchEngine {
             }
         }
 
+        // 書類の種類フィルター
+        if let Some(doc_types) = query.doc_types.filter(|t| !t.is_empty()) {
+            let any_type: Vec<(Occur, Box<dyn Query>)> = doc_types
+                .iter()
+                .map(|doc_type| {
+                    let term = Term::from_field_text(self.fields["doc_type"], doc_type.as_str());
+                    (Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>)
+                })
+                .collect();
+            filters.push((Occur::Must, Box::new(BooleanQuery::new(any_type))));
+        }
+
         // 最終的なクエリの構築
         let final_query = if filters.is_empty() {
             main_query
@@ -280,7 +503,7 @@ impl SearchEngine {
         let mut highlights = Vec::new();
         
         // 各フィールドからハイライトを生成
-        let fields_to_highlight = ["ocr_text", "memo", "location_name", "group_title"];
+        let fields_to_highlight = ["ocr_text", "memo", "location_name", "group_title", "caption", "translated_text", "title"];
         
         for field_name in fields_to_highlight {
             if let Some(&field) = self.fields.get(field_name) {
@@ -299,7 +522,7 @@ impl SearchEngine {
         let mut matched_fields = Vec::new();
         let query_lower = query.to_lowercase();
         
-        let fields_to_check = ["ocr_text", "memo", "tags", "location_name", "group_title"];
+        let fields_to_check = ["ocr_text", "memo", "tags", "location_name", "group_title", "caption", "translated_text", "title"];
         
         for field_name in fields_to_check {
             if let Some(&field) = self.fields.get(field_name) {
@@ -320,6 +543,35 @@ impl SearchEngine {
         Ok(())
     }
 
+    // 最初の検索が遅くならないよう、各セグメントの転置インデックスを読み込んでおく（ドキュメント数を返す）
+    pub fn warm_up(&self) -> Result<u64> {
+        let searcher = self.reader.searcher();
+        for segment_reader in searcher.segment_readers() {
+            for field in self.fields.values() {
+                if self.schema.get_field_entry(*field).is_indexed() {
+                    segment_reader.inverted_index(*field)?;
+                }
+            }
+        }
+        Ok(searcher.num_docs())
+    }
+
+    // インデックスにあるドキュメントの ID（同じ ID が重複していればその数だけ含む）
+    pub fn indexed_ids(&self) -> Result<Vec<String>> {
+        self.reader.reload()?;
+        let searcher = self.reader.searcher();
+        let mut ids = Vec::new();
+        for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+            for doc_id in segment_reader.doc_ids_alive() {
+                let doc: tantivy::TantivyDocument = searcher.doc(DocAddress::new(segment_ord as u32, doc_id))?;
+                if let Some(id) = doc.get_first(self.fields["id"]).and_then(|v| v.as_text()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        Ok(ids)
+    }
+
     pub fn get_stats(&self) -> Result<HashMap<String, usize>> {
         let searcher = self.reader.searcher();
         let stats = searcher.segment_readers().iter().map(|reader| {
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use std::path::Path;
+
+// OS のキーチェーン（macOS キーチェーン・Windows 資格情報マネージャー・Secret Service）のサービス名
+const SERVICE: &str = "snap-organizer";
+
+// 設定ファイルごとに別の項目にする（ライブラリが複数あっても混ざらないように）
+fn account(path: &Path, name: &str) -> String {
+    format!("{}:{}", path.display(), name)
+}
+
+fn entry(path: &Path, name: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, &account(path, name))
+}
+
+pub fn get(path: &Path, name: &str) -> Result<Option<String>> {
+    match entry(path, name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// 空文字は削除として扱う
+pub fn set(path: &Path, name: &str, value: &str) -> Result<()> {
+    let entry = entry(path, name)?;
+    if value.is_empty() {
+        return match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        };
+    }
+    entry.set_password(value)?;
+    Ok(())
+}
+
+// 保存前: 値をキーチェーンへ移してファイルには空文字を書く
+// キーチェーンが使えない環境ではファイルに残す
+pub fn stash(path: &Path, name: &str, value: &mut String) {
+    match set(path, name, value) {
+        Ok(()) => value.clear(),
+        Err(e) => log::warn!("OS keychain unavailable, keeping {} in {}: {}", name, path.display(), e),
+    }
+}
+
+// 読み込み後: ファイルが空ならキーチェーンの値で埋める
+pub fn fill(path: &Path, name: &str, value: &mut String) {
+    if !value.is_empty() {
+        return;
+    }
+    match get(path, name) {
+        Ok(Some(secret)) => *value = secret,
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to read {} from OS keychain: {}", name, e),
+    }
+}
@@ -0,0 +1,226 @@
+use crate::import_pipeline::DuplicatePolicy;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// 保存形式の版（MIGRATIONS の数と一致させる）
+pub const SETTINGS_VERSION: u32 = 2;
+
+// 保存されている版から順に適用する（index = 移行元の版）
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_v1, migrate_v2];
+
+// v0 → v1: 版番号のない最初の形式（変換は不要）
+fn migrate_v1(_settings: &mut Map<String, Value>) {}
+
+// v1 → v2: thumbnail_size を grid_thumbnail_size に改名
+fn migrate_v2(settings: &mut Map<String, Value>) {
+    if let Some(size) = settings.remove("thumbnail_size") {
+        settings.entry("grid_thumbnail_size").or_insert(size);
+    }
+}
+
+const MIN_THUMBNAIL_SIZE: u32 = 64;
+const MAX_THUMBNAIL_SIZE: u32 = 1024;
+const MAX_DEVICE_NAME_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Newest,
+    Oldest,
+    Updated,
+}
+
+// バックグラウンドのジョブが終わったときの OS の通知
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobNotifications {
+    Off,
+    // 失敗と同期の衝突だけ
+    Problems,
+    // 問題に加えて、時間のかかったジョブの完了
+    #[default]
+    LongJobs,
+    // すべてのジョブの完了
+    All,
+}
+
+// アプリ全体の設定（フロントエンドの localStorage から移したもの。バックグラウンドの処理からも参照する）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub version: u32,
+    pub theme: Theme,
+    // 表示言語（例: "ja", "en"）
+    pub language: String,
+    // 同期やスマホからの取り込みで表示する端末名
+    pub device_name: String,
+    pub sort_order: SortOrder,
+    pub grid_thumbnail_size: u32,
+    pub confirm_before_delete: bool,
+    // エクスポートの保存先の初期値
+    pub default_export_dir: Option<String>,
+    // 内容が同じアイテムが既にある画像を取り込むときの扱い
+    pub duplicate_policy: DuplicatePolicy,
+    pub job_notifications: JobNotifications,
+    // 型を決めていないフロントエンドの設定
+    pub extra: Map<String, Value>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            version: SETTINGS_VERSION,
+            theme: Theme::default(),
+            language: "ja".to_string(),
+            device_name: String::new(),
+            sort_order: SortOrder::default(),
+            grid_thumbnail_size: 256,
+            confirm_before_delete: true,
+            default_export_dir: None,
+            duplicate_policy: DuplicatePolicy::default(),
+            job_notifications: JobNotifications::default(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.language.trim().is_empty() {
+            bail!("language must not be empty");
+        }
+        if self.device_name.chars().count() > MAX_DEVICE_NAME_CHARS {
+            bail!("device_name must be at most {} characters", MAX_DEVICE_NAME_CHARS);
+        }
+        if !(MIN_THUMBNAIL_SIZE..=MAX_THUMBNAIL_SIZE).contains(&self.grid_thumbnail_size) {
+            bail!(
+                "grid_thumbnail_size must be between {} and {}",
+                MIN_THUMBNAIL_SIZE,
+                MAX_THUMBNAIL_SIZE
+            );
+        }
+        Ok(())
+    }
+
+    // 古い版の JSON を現在の版へ移行してから読み込む
+    fn from_value(value: Value) -> Result<Self> {
+        let Value::Object(mut settings) = value else {
+            bail!("Settings must be a JSON object");
+        };
+        let version = settings.get("version").and_then(Value::as_u64).unwrap_or(0) as usize;
+        if version > MIGRATIONS.len() {
+            bail!("Settings were saved by a newer version of the app (v{})", version);
+        }
+        for migrate in &MIGRATIONS[version..] {
+            migrate(&mut settings);
+        }
+        settings.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+        Ok(serde_json::from_value(Value::Object(settings))?)
+    }
+}
+
+type Listener = Box<dyn Fn(&AppSettings) + Send + Sync>;
+
+// 設定の読み書きと変更の通知
+pub struct SettingsStore {
+    path: PathBuf,
+    current: Mutex<AppSettings>,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+impl SettingsStore {
+    pub fn new(path: PathBuf) -> Self {
+        let current = fs::read_to_string(&path)
+            .ok()
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(anyhow::Error::from)
+                    .and_then(AppSettings::from_value)
+                    .and_then(|settings| settings.validate().map(|_| settings))
+                    .unwrap_or_else(|e| {
+                        log::warn!("Invalid settings in {}, using defaults: {}", path.display(), e);
+                        AppSettings::default()
+                    })
+            })
+            .unwrap_or_default();
+        SettingsStore {
+            path,
+            current: Mutex::new(current),
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn get(&self) -> AppSettings {
+        self.current.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: AppSettings) -> Result<AppSettings> {
+        let settings = AppSettings {
+            version: SETTINGS_VERSION,
+            ..settings
+        };
+        settings.validate()?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&settings)?)?;
+        fs::rename(&tmp, &self.path)?;
+
+        let changed = {
+            let mut current = self.current.lock().unwrap();
+            let changed = *current != settings;
+            *current = settings.clone();
+            changed
+        };
+        if changed {
+            for listener in self.listeners.lock().unwrap().iter() {
+                listener(&settings);
+            }
+        }
+        Ok(settings)
+    }
+
+    // 一部の項目だけを変更する（JSON のマージ。null を指定すると初期値に戻す）
+    pub fn update(&self, patch: Value) -> Result<AppSettings> {
+        let Value::Object(patch) = patch else {
+            bail!("Settings patch must be a JSON object");
+        };
+        let mut merged = match serde_json::to_value(self.get())? {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        let defaults = match serde_json::to_value(AppSettings::default())? {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        for (key, value) in patch {
+            let value = match value {
+                Value::Null => defaults.get(&key).cloned().unwrap_or(Value::Null),
+                value => value,
+            };
+            merged.insert(key, value);
+        }
+        let settings: AppSettings = serde_json::from_value(Value::Object(merged)).context("Invalid settings")?;
+        self.set(settings)
+    }
+
+    // 設定が変わるたびに呼ばれる（バックグラウンドの処理やフロントエンドへの通知）
+    pub fn subscribe(&self, listener: impl Fn(&AppSettings) + Send + Sync + 'static) {
+        self.listeners.lock().unwrap().push(Box::new(listener));
+    }
+}
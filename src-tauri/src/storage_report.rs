@@ -0,0 +1,174 @@
+use crate::metadata_store::{ItemFilter, MetadataStore};
+use crate::paths::LibraryPaths;
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+// これより大きい元画像を再圧縮の候補にする
+pub const DEFAULT_LARGE_FILE_MB: u64 = 5;
+// 再圧縮で減るサイズの見積もり（大きな PNG のスクリーンショットやカメラの JPEG は半分以下になることが多い）
+const RECOMPRESS_SAVING_RATIO: f64 = 0.5;
+const MAX_LARGE_ORIGINALS: usize = 50;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageBucket {
+    pub label: String,
+    pub item_count: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LargeOriginal {
+    pub item_id: String,
+    pub image_path: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupAction {
+    RecompressOriginals,
+    PurgeTrash,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupSuggestion {
+    pub action: CleanupAction,
+    pub item_ids: Vec<String>,
+    pub estimated_savings: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StorageReport {
+    pub originals_bytes: u64,
+    pub thumbnails_bytes: u64,
+    pub index_bytes: u64,
+    pub database_bytes: u64,
+    // ゴミ箱のアイテムだけが参照している元画像
+    pub trash_bytes: u64,
+    pub total_bytes: u64,
+    pub by_year: Vec<UsageBucket>,
+    // 複数のタグが付いたアイテムはそれぞれのタグに数える（タグなしは label が空）
+    pub by_tag: Vec<UsageBucket>,
+    pub large_originals: Vec<LargeOriginal>,
+    pub suggestions: Vec<CleanupSuggestion>,
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn add_to(buckets: &mut BTreeMap<String, UsageBucket>, label: String, bytes: u64) {
+    let bucket = buckets.entry(label.clone()).or_insert_with(|| UsageBucket {
+        label,
+        ..Default::default()
+    });
+    bucket.item_count += 1;
+    bucket.bytes += bytes;
+}
+
+pub fn build_report(
+    paths: &LibraryPaths,
+    store: &Mutex<Option<MetadataStore>>,
+    large_file_mb: u64,
+) -> Result<StorageReport> {
+    let (items, trash) = {
+        let store = store.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        (store.list_items(&ItemFilter::default())?, store.list_trash()?)
+    };
+
+    let db_file = paths.metadata_db_file();
+    let mut report = StorageReport {
+        originals_bytes: dir_size(&paths.images_dir()),
+        thumbnails_bytes: dir_size(&paths.thumbnails_dir()),
+        index_bytes: dir_size(&paths.index_dir()),
+        database_bytes: ["", "-wal", "-shm"]
+            .iter()
+            .map(|suffix| file_size(Path::new(&format!("{}{}", db_file.display(), suffix))))
+            .sum(),
+        ..Default::default()
+    };
+
+    let large_threshold = large_file_mb * 1024 * 1024;
+    let mut by_year = BTreeMap::new();
+    let mut by_tag = BTreeMap::new();
+    let mut live_images = HashSet::new();
+    for item in &items {
+        let image_path = match item.image_path.as_deref() {
+            Some(path) => path,
+            None => continue,
+        };
+        let bytes = file_size(Path::new(image_path));
+        live_images.insert(image_path);
+
+        add_to(&mut by_year, item.created_at.year().to_string(), bytes);
+        if item.tags.is_empty() {
+            add_to(&mut by_tag, String::new(), bytes);
+        }
+        for tag in &item.tags {
+            add_to(&mut by_tag, tag.clone(), bytes);
+        }
+        if bytes > large_threshold {
+            report.large_originals.push(LargeOriginal {
+                item_id: item.id.clone(),
+                image_path: image_path.to_string(),
+                bytes,
+            });
+        }
+    }
+
+    let mut trash_ids = Vec::new();
+    let mut counted = HashSet::new();
+    for trashed in &trash {
+        trash_ids.push(trashed.item.id.clone());
+        if let Some(path) = trashed.item.image_path.as_deref() {
+            if !live_images.contains(path) && counted.insert(path) {
+                report.trash_bytes += file_size(Path::new(path));
+            }
+        }
+    }
+
+    report.total_bytes = report.originals_bytes + report.thumbnails_bytes + report.index_bytes + report.database_bytes;
+    report.by_year = by_year.into_values().rev().collect();
+    report.by_tag = by_tag.into_values().collect();
+    report.by_tag.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+    report.large_originals.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+    if !report.large_originals.is_empty() {
+        let large_bytes: u64 = report.large_originals.iter().map(|o| o.bytes).sum();
+        report.suggestions.push(CleanupSuggestion {
+            action: CleanupAction::RecompressOriginals,
+            item_ids: report.large_originals.iter().map(|o| o.item_id.clone()).collect(),
+            estimated_savings: (large_bytes as f64 * RECOMPRESS_SAVING_RATIO) as u64,
+        });
+    }
+    if !trash_ids.is_empty() {
+        report.suggestions.push(CleanupSuggestion {
+            action: CleanupAction::PurgeTrash,
+            item_ids: trash_ids,
+            estimated_savings: report.trash_bytes,
+        });
+    }
+    report.large_originals.truncate(MAX_LARGE_ORIGINALS);
+    Ok(report)
+}
@@ -0,0 +1,768 @@
+use crate::jobs::ProgressReporter;
+use crate::dropbox_sync::{DropboxBackend, DropboxConfig};
+use crate::gdrive_sync::{GoogleDriveBackend, GoogleDriveConfig};
+use crate::metadata_store::{
+    ConflictRecord, ConflictSide, GroupRecord, ItemFilter, ItemRecord, MetadataStore, SyncEntry, GROUP_FIELDS,
+    ITEM_FIELDS,
+};
+use crate::oauth::{OAuthClient, OAuthProvider};
+use crate::paths::LibraryPaths;
+use crate::s3_sync::{S3Backend, S3Config};
+use crate::secrets;
+use crate::webdav_sync::{WebDavBackend, WebDavConfig};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+// リモート上のレイアウト
+// originals/<hash>.<ext>  元画像（内容で名前が決まるため上書きされない）
+// items/<id>.json         アイテムのメタデータ
+// groups/<id>.json        グループのメタデータ
+const ORIGINALS_PREFIX: &str = "originals/";
+const ITEMS_PREFIX: &str = "items/";
+const GROUPS_PREFIX: &str = "groups/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteObject {
+    pub key: String,
+    pub etag: String,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+// 同期先ストレージの共通インターフェース（キーはリモートのルートからの相対パス）
+pub trait SyncBackend: Send + Sync {
+    // 同期状態を区別するためのリモートの識別子
+    fn remote_id(&self) -> String;
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>>;
+    fn get(&self, key: &str) -> Result<Vec<u8>>;
+    // 書き込み後の etag を返す
+    fn put(&self, key: &str, data: &[u8]) -> Result<String>;
+    fn delete(&self, key: &str) -> Result<()>;
+
+    fn upload(&self, key: &str, source: &Path) -> Result<String> {
+        let data = fs::read(source).with_context(|| format!("Failed to read {}", source.display()))?;
+        self.put(key, &data)
+    }
+
+    fn download(&self, key: &str, dest: &Path) -> Result<()> {
+        let data = self.get(key)?;
+        // 途中で失敗しても壊れたファイルを残さない
+        let tmp = dest.with_extension("partial");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, dest)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum SyncProviderConfig {
+    S3(S3Config),
+    WebDav(WebDavConfig),
+    #[serde(rename = "google_drive")]
+    GoogleDrive(GoogleDriveConfig),
+    Dropbox(DropboxConfig),
+}
+
+impl SyncProviderConfig {
+    // OAuth のプロバイダはサインイン済みのトークンを使う
+    pub fn connect(&self, paths: &LibraryPaths) -> Result<Box<dyn SyncBackend>> {
+        match self {
+            SyncProviderConfig::S3(config) => Ok(Box::new(S3Backend::new(config.clone())?)),
+            SyncProviderConfig::WebDav(config) => Ok(Box::new(WebDavBackend::new(config.clone())?)),
+            SyncProviderConfig::GoogleDrive(config) => {
+                let auth = OAuthClient::load(OAuthProvider::GoogleDrive, paths.oauth_tokens_file())?;
+                Ok(Box::new(GoogleDriveBackend::new(config.clone(), auth)?))
+            }
+            SyncProviderConfig::Dropbox(config) => {
+                let auth = OAuthClient::load(OAuthProvider::Dropbox, paths.oauth_tokens_file())?;
+                Ok(Box::new(DropboxBackend::new(config.clone(), auth)?))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub remote: Option<SyncProviderConfig>,
+    #[serde(default)]
+    pub last_synced_at: Option<DateTime<Utc>>,
+}
+
+impl SyncConfig {
+    // S3 のシークレットキーと WebDAV のパスワードは OS のキーチェーンに置く
+    pub fn load(path: &Path) -> Self {
+        let mut config: Self = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        match &mut config.remote {
+            Some(SyncProviderConfig::S3(s3)) => {
+                secrets::fill(path, "s3-secret-access-key", &mut s3.secret_access_key)
+            }
+            Some(SyncProviderConfig::WebDav(webdav)) => secrets::fill(path, "webdav-password", &mut webdav.password),
+            _ => {}
+        }
+        config
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut config = self.clone();
+        match &mut config.remote {
+            Some(SyncProviderConfig::S3(s3)) => {
+                secrets::stash(path, "s3-secret-access-key", &mut s3.secret_access_key)
+            }
+            Some(SyncProviderConfig::WebDav(webdav)) => secrets::stash(path, "webdav-password", &mut webdav.password),
+            _ => {}
+        }
+        fs::write(path, serde_json::to_string_pretty(&config)?)?;
+        Ok(())
+    }
+}
+
+// 同期中にローカルで編集されたため反映を見送ったもの（次回の同期でマージされる）
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncConflict {
+    pub key: String,
+    pub kind: String,
+    pub id: String,
+    pub local_version: Option<String>,
+    pub remote_etag: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub uploaded: usize,
+    pub downloaded: usize,
+    pub deleted_remote: usize,
+    pub deleted_local: usize,
+    // 両側の変更をフィールド単位でマージしたレコード数と、記録した衝突の数
+    pub merged: usize,
+    pub conflicts_recorded: usize,
+    pub conflicts: Vec<SyncConflict>,
+    // 検索インデックスへの反映が必要なアイテム
+    pub changed_item_ids: Vec<String>,
+    pub removed_item_ids: Vec<String>,
+}
+
+pub struct SyncContext<'a> {
+    pub paths: &'a LibraryPaths,
+    pub store: &'a Mutex<Option<MetadataStore>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncAction {
+    Push,
+    Pull,
+    DeleteRemote,
+    DeleteLocal,
+    // 前回の同期情報がなく両側に存在する（内容が同じなら同期済みとして扱う）
+    Compare,
+    Conflict,
+    // 両側で削除済み
+    Forget,
+}
+
+// 前回同期時の状態と比べて、どちら側が変更されたかで処理を決める
+fn plan(local: Option<&str>, remote: Option<&str>, base: Option<&SyncEntry>) -> Option<SyncAction> {
+    let base = match base {
+        Some(base) => base,
+        None => {
+            return match (local, remote) {
+                (None, None) => None,
+                (Some(_), None) => Some(SyncAction::Push),
+                (None, Some(_)) => Some(SyncAction::Pull),
+                (Some(_), Some(_)) => Some(SyncAction::Compare),
+            }
+        }
+    };
+
+    let local_changed = local != Some(base.local_version.as_str());
+    let remote_changed = remote != Some(base.etag.as_str());
+    match (local_changed, remote_changed) {
+        (false, false) => None,
+        (true, false) if local.is_some() => Some(SyncAction::Push),
+        (true, false) => Some(SyncAction::DeleteRemote),
+        (false, true) if remote.is_some() => Some(SyncAction::Pull),
+        (false, true) => Some(SyncAction::DeleteLocal),
+        (true, true) if local.is_none() && remote.is_none() => Some(SyncAction::Forget),
+        (true, true) => Some(SyncAction::Conflict),
+    }
+}
+
+fn version_of(updated_at: &DateTime<Utc>) -> String {
+    updated_at.to_rfc3339()
+}
+
+fn record_id<'a>(key: &'a str, prefix: &str) -> &'a str {
+    key.strip_prefix(prefix)
+        .and_then(|rest| rest.strip_suffix(".json"))
+        .unwrap_or(key)
+}
+
+fn parse_version(version: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(version).ok().map(|d| d.with_timezone(&Utc))
+}
+
+// リモートに置くレコード（フィールドごとの更新日時を添えて、相手側でもフィールド単位でマージできるようにする）
+#[derive(Serialize, Deserialize)]
+struct RemoteRecord<T> {
+    #[serde(flatten)]
+    record: T,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    field_updated_at: HashMap<String, DateTime<Utc>>,
+}
+
+fn original_key(image_path: &str) -> Option<String> {
+    Path::new(image_path)
+        .file_name()
+        .map(|name| format!("{}{}", ORIGINALS_PREFIX, name.to_string_lossy()))
+}
+
+// アイテムとグループを同じ手順で同期するための共通処理
+trait SyncRecord: Serialize + DeserializeOwned + Clone {
+    const PREFIX: &'static str;
+    const KIND: &'static str;
+    const FIELDS: &'static [&'static str];
+    fn updated_at(&self) -> &DateTime<Utc>;
+    fn set_updated_at(&mut self, updated_at: DateTime<Utc>);
+    fn field_value(&self, field: &str) -> Value;
+    fn set_field_value(&mut self, field: &str, value: Value) -> Result<()>;
+    fn load(store: &MetadataStore, id: &str) -> Result<Option<Self>>;
+    fn store(store: &mut MetadataStore, record: &Self) -> Result<()>;
+    fn remove(store: &mut MetadataStore, id: &str) -> Result<()>;
+
+    // 送信前・取り込み前の追加処理（アイテムの元画像など）
+    fn before_push(&self, _syncer: &mut Syncer) -> Result<()> {
+        Ok(())
+    }
+
+    fn before_pull(&mut self, _syncer: &mut Syncer) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SyncRecord for GroupRecord {
+    const PREFIX: &'static str = GROUPS_PREFIX;
+    const KIND: &'static str = "group";
+    const FIELDS: &'static [&'static str] = GROUP_FIELDS;
+
+    fn updated_at(&self) -> &DateTime<Utc> {
+        &self.updated_at
+    }
+
+    fn set_updated_at(&mut self, updated_at: DateTime<Utc>) {
+        self.updated_at = updated_at;
+    }
+
+    fn field_value(&self, field: &str) -> Value {
+        GroupRecord::field_value(self, field)
+    }
+
+    fn set_field_value(&mut self, field: &str, value: Value) -> Result<()> {
+        GroupRecord::set_field_value(self, field, value)
+    }
+
+    fn load(store: &MetadataStore, id: &str) -> Result<Option<Self>> {
+        store.get_group(id)
+    }
+
+    fn store(store: &mut MetadataStore, record: &Self) -> Result<()> {
+        store.save_group(record)
+    }
+
+    fn remove(store: &mut MetadataStore, id: &str) -> Result<()> {
+        store.delete_group(id).map(|_| ())
+    }
+}
+
+impl SyncRecord for ItemRecord {
+    const PREFIX: &'static str = ITEMS_PREFIX;
+    const KIND: &'static str = "item";
+    const FIELDS: &'static [&'static str] = ITEM_FIELDS;
+
+    fn updated_at(&self) -> &DateTime<Utc> {
+        &self.updated_at
+    }
+
+    fn set_updated_at(&mut self, updated_at: DateTime<Utc>) {
+        self.updated_at = updated_at;
+    }
+
+    fn field_value(&self, field: &str) -> Value {
+        ItemRecord::field_value(self, field)
+    }
+
+    fn set_field_value(&mut self, field: &str, value: Value) -> Result<()> {
+        ItemRecord::set_field_value(self, field, value)
+    }
+
+    fn load(store: &MetadataStore, id: &str) -> Result<Option<Self>> {
+        store.get_item(id)
+    }
+
+    // 非公開アイテムは端末ごとの鍵で暗号化しているので同期しない（ローカルの非公開アイテムも上書きしない）
+    fn store(store: &mut MetadataStore, record: &Self) -> Result<()> {
+        if record.private || store.sealed_text(&record.id)?.is_some() {
+            return Ok(());
+        }
+        store.put_item(record, None)
+    }
+
+    // 相手側で削除されたアイテムもゴミ箱に入れる（元に戻せるように）
+    fn remove(store: &mut MetadataStore, id: &str) -> Result<()> {
+        store.trash_item(id).map(|_| ())
+    }
+
+    fn before_push(&self, syncer: &mut Syncer) -> Result<()> {
+        syncer.push_original(self)
+    }
+
+    fn before_pull(&mut self, syncer: &mut Syncer) -> Result<()> {
+        syncer.pull_original(self)
+    }
+}
+
+struct Syncer<'a> {
+    ctx: &'a SyncContext<'a>,
+    backend: &'a dyn SyncBackend,
+    remote_id: String,
+    remote_originals: HashSet<String>,
+    report: SyncReport,
+}
+
+impl<'a> Syncer<'a> {
+    fn with_store<T>(&self, f: impl FnOnce(&mut MetadataStore) -> Result<T>) -> Result<T> {
+        let mut store = self.ctx.store.lock().unwrap();
+        let store = store.as_mut().context("Metadata store not initialized")?;
+        f(store)
+    }
+
+    // 同期中にローカルで編集された場合は上書きせず衝突として扱う
+    fn local_unchanged<T: SyncRecord>(store: &MetadataStore, id: &str, expected: Option<&str>) -> Result<bool> {
+        let current = T::load(store, id)?.map(|r| version_of(r.updated_at()));
+        Ok(current.as_deref() == expected)
+    }
+
+    fn conflict(&mut self, kind: &str, key: &str, id: &str, local: Option<&str>, remote: Option<&str>) {
+        self.report.conflicts.push(SyncConflict {
+            key: key.to_string(),
+            kind: kind.to_string(),
+            id: id.to_string(),
+            local_version: local.map(|v| v.to_string()),
+            remote_etag: remote.map(|e| e.to_string()),
+        });
+    }
+
+    fn push_original(&mut self, item: &ItemRecord) -> Result<()> {
+        let image_path = match item.image_path.as_deref() {
+            Some(path) if Path::new(path).is_file() => path,
+            _ => return Ok(()),
+        };
+        if let Some(key) = original_key(image_path) {
+            if !self.remote_originals.contains(&key) {
+                self.backend.upload(&key, Path::new(image_path))?;
+                self.remote_originals.insert(key);
+                self.report.uploaded += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // 取り込んだアイテムの画像パスをこのPCのライブラリに合わせ、元画像がなければ取得する
+    fn pull_original(&mut self, item: &mut ItemRecord) -> Result<()> {
+        let file_name = match item.image_path.as_deref().and_then(|p| Path::new(p).file_name()) {
+            Some(name) => name.to_owned(),
+            None => return Ok(()),
+        };
+        let images_dir = self.ctx.paths.images_dir();
+        let local_path = images_dir.join(&file_name);
+        item.image_path = Some(local_path.to_string_lossy().to_string());
+
+        let key = format!("{}{}", ORIGINALS_PREFIX, file_name.to_string_lossy());
+        if !local_path.exists() && self.remote_originals.contains(&key) {
+            fs::create_dir_all(&images_dir)?;
+            self.backend.download(&key, &local_path)?;
+            self.report.downloaded += 1;
+        }
+        Ok(())
+    }
+
+    fn apply<T: SyncRecord>(
+        &mut self,
+        key: &str,
+        action: SyncAction,
+        local: Option<&T>,
+        remote_etag: Option<&str>,
+        base: Option<&SyncEntry>,
+    ) -> Result<()> {
+        let id = record_id(key, T::PREFIX).to_string();
+        let local_version = local.map(|r| version_of(r.updated_at()));
+        let remote_id = self.remote_id.clone();
+
+        match action {
+            SyncAction::Push => {
+                let record = local.context("Nothing to push")?;
+                self.push(key, &id, record)?;
+            }
+            SyncAction::DeleteRemote => {
+                self.backend.delete(key)?;
+                self.with_store(|store| store.remove_sync_entry(&remote_id, key))?;
+                self.report.deleted_remote += 1;
+            }
+            SyncAction::Pull | SyncAction::Compare => {
+                let etag = remote_etag.context("Nothing to pull")?.to_string();
+                let remote: RemoteRecord<T> = serde_json::from_slice(&self.backend.get(key)?)
+                    .with_context(|| format!("Invalid remote record: {}", key))?;
+                let remote_version = version_of(remote.record.updated_at());
+
+                if action == SyncAction::Compare {
+                    match local {
+                        Some(_) if Some(&remote_version) == local_version.as_ref() => {
+                            self.with_store(|store| store.set_sync_entry(&remote_id, key, &etag, &remote_version))?;
+                        }
+                        Some(local) => self.merge(key, &id, local, remote, None)?,
+                        None => {}
+                    }
+                    return Ok(());
+                }
+
+                let RemoteRecord { record: mut remote, field_updated_at } = remote;
+                remote.before_pull(self)?;
+                let applied = self.with_store(|store| {
+                    if !Self::local_unchanged::<T>(store, &id, local_version.as_deref())? {
+                        return Ok(false);
+                    }
+                    T::store(store, &remote)?;
+                    store.set_field_versions(&id, &field_updated_at)?;
+                    store.set_sync_entry(&remote_id, key, &etag, &remote_version)?;
+                    Ok(true)
+                })?;
+                if applied {
+                    self.report.downloaded += 1;
+                    if T::KIND == "item" {
+                        self.report.changed_item_ids.push(id);
+                    }
+                } else {
+                    self.conflict(T::KIND, key, &id, local_version.as_deref(), Some(&etag));
+                }
+            }
+            SyncAction::DeleteLocal => {
+                let applied = self.with_store(|store| {
+                    if !Self::local_unchanged::<T>(store, &id, local_version.as_deref())? {
+                        return Ok(false);
+                    }
+                    T::remove(store, &id)?;
+                    store.remove_sync_entry(&remote_id, key)?;
+                    Ok(true)
+                })?;
+                if applied {
+                    self.report.deleted_local += 1;
+                    if T::KIND == "item" {
+                        self.report.removed_item_ids.push(id);
+                    }
+                } else {
+                    self.conflict(T::KIND, key, &id, local_version.as_deref(), None);
+                }
+            }
+            SyncAction::Conflict => {
+                let since = base.and_then(|b| parse_version(&b.local_version));
+                match (local, remote_etag) {
+                    (Some(local), Some(_)) => {
+                        let remote: RemoteRecord<T> = serde_json::from_slice(&self.backend.get(key)?)
+                            .with_context(|| format!("Invalid remote record: {}", key))?;
+                        self.merge(key, &id, local, remote, since)?;
+                    }
+                    // 片側で削除・もう片側で編集された場合は編集を残し、削除されたことを衝突として記録する
+                    (Some(local), None) => {
+                        self.push(key, &id, local)?;
+                        self.record_deletion::<T>(&id, ConflictSide::Remote)?;
+                    }
+                    (None, Some(_)) => {
+                        self.apply::<T>(key, SyncAction::Pull, None, remote_etag, base)?;
+                        self.record_deletion::<T>(&id, ConflictSide::Local)?;
+                    }
+                    (None, None) => {}
+                }
+            }
+            SyncAction::Forget => {
+                self.with_store(|store| store.remove_sync_entry(&remote_id, key))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn push<T: SyncRecord>(&mut self, key: &str, id: &str, record: &T) -> Result<()> {
+        record.before_push(self)?;
+        let field_updated_at = self.with_store(|store| store.field_versions(id))?;
+        let payload = RemoteRecord {
+            record: record.clone(),
+            field_updated_at,
+        };
+        let etag = self.backend.put(key, &serde_json::to_vec_pretty(&payload)?)?;
+        let version = version_of(record.updated_at());
+        let remote_id = self.remote_id.clone();
+        self.with_store(|store| store.set_sync_entry(&remote_id, key, &etag, &version))?;
+        self.report.uploaded += 1;
+        Ok(())
+    }
+
+    fn new_conflict(
+        &self,
+        kind: &str,
+        id: &str,
+        field: &str,
+        local: Value,
+        remote: Value,
+        applied: ConflictSide,
+    ) -> ConflictRecord {
+        ConflictRecord {
+            id: 0,
+            remote: self.remote_id.clone(),
+            kind: kind.to_string(),
+            record_id: id.to_string(),
+            field: field.to_string(),
+            local_value: local,
+            remote_value: remote,
+            applied,
+            detected_at: Utc::now(),
+        }
+    }
+
+    // deleted_side 側で削除されていた
+    fn record_deletion<T: SyncRecord>(&mut self, id: &str, deleted_side: ConflictSide) -> Result<()> {
+        let deleted_locally = deleted_side == ConflictSide::Local;
+        let applied = if deleted_locally { ConflictSide::Remote } else { ConflictSide::Local };
+        let conflict = self.new_conflict(
+            T::KIND,
+            id,
+            "deleted",
+            json!(deleted_locally),
+            json!(!deleted_locally),
+            applied,
+        );
+        self.with_store(|store| store.add_sync_conflict(&conflict))?;
+        self.report.conflicts_recorded += 1;
+        Ok(())
+    }
+
+    // 両側で変更されたレコードをフィールド単位でマージする
+    // 異なる値のフィールドは更新日時が新しい方を採用し、前回の同期以降に両側で編集されていれば衝突として記録する
+    fn merge<T: SyncRecord>(
+        &mut self,
+        key: &str,
+        id: &str,
+        local: &T,
+        remote: RemoteRecord<T>,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let local_times = self.with_store(|store| store.field_versions(id))?;
+        let mut merged = local.clone();
+        let mut merged_times = local_times.clone();
+        let mut conflicts = Vec::new();
+
+        for field in T::FIELDS {
+            let local_value = local.field_value(field);
+            let remote_value = remote.record.field_value(field);
+            if local_value == remote_value {
+                continue;
+            }
+            let local_time = local_times.get(*field).copied().unwrap_or(*local.updated_at());
+            let remote_time = remote
+                .field_updated_at
+                .get(*field)
+                .copied()
+                .unwrap_or(*remote.record.updated_at());
+            let applied = if remote_time > local_time {
+                merged.set_field_value(field, remote_value.clone())?;
+                merged_times.insert(field.to_string(), remote_time);
+                ConflictSide::Remote
+            } else {
+                ConflictSide::Local
+            };
+            let edited_on_both = since.map(|s| local_time > s && remote_time > s).unwrap_or(true);
+            if edited_on_both {
+                conflicts.push(self.new_conflict(T::KIND, id, field, local_value, remote_value, applied));
+            }
+        }
+
+        // マージ結果は新しい版として両側に書き込む
+        merged.set_updated_at(Utc::now());
+        let local_version = version_of(local.updated_at());
+        let applied = self.with_store(|store| {
+            if !Self::local_unchanged::<T>(store, id, Some(&local_version))? {
+                return Ok(false);
+            }
+            T::store(store, &merged)?;
+            store.set_field_versions(id, &merged_times)?;
+            for conflict in &conflicts {
+                store.add_sync_conflict(conflict)?;
+            }
+            Ok(true)
+        })?;
+        if !applied {
+            self.conflict(T::KIND, key, id, Some(&local_version), None);
+            return Ok(());
+        }
+
+        self.push(key, id, &merged)?;
+        self.report.merged += 1;
+        self.report.conflicts_recorded += conflicts.len();
+        if T::KIND == "item" {
+            self.report.changed_item_ids.push(id.to_string());
+        }
+        Ok(())
+    }
+
+    fn plan_records<T: SyncRecord>(
+        local: &HashMap<String, T>,
+        remote: &HashMap<String, RemoteObject>,
+        base: &HashMap<String, SyncEntry>,
+    ) -> Vec<(String, SyncAction)> {
+        let keys: BTreeSet<&String> = local
+            .keys()
+            .chain(remote.keys().filter(|k| k.starts_with(T::PREFIX)))
+            .chain(base.keys().filter(|k| k.starts_with(T::PREFIX)))
+            .collect();
+        keys.into_iter()
+            .filter_map(|key| {
+                let local_version = local.get(key).map(|r| version_of(r.updated_at()));
+                let remote_etag = remote.get(key).map(|o| o.etag.as_str());
+                plan(local_version.as_deref(), remote_etag, base.get(key)).map(|action| (key.clone(), action))
+            })
+            .collect()
+    }
+}
+
+// ローカルライブラリとリモートを差分同期する
+// 片側だけの変更は反映し、両側で変更されたものはフィールド単位でマージする
+pub fn sync_library(
+    ctx: &SyncContext,
+    backend: &dyn SyncBackend,
+    reporter: &dyn ProgressReporter,
+) -> Result<SyncReport> {
+    let remote_id = backend.remote_id();
+
+    // ネットワーク処理中はロックを持たないよう、ローカルの状態を先に取得する
+    let (items, groups, mut base) = {
+        let store = ctx.store.lock().unwrap();
+        let store = store.as_ref().context("Metadata store not initialized")?;
+        let items: HashMap<String, ItemRecord> = store
+            .list_items(&ItemFilter::default())?
+            .into_iter()
+            .filter(|item| !item.private)
+            .map(|item| (format!("{}{}.json", ITEMS_PREFIX, item.id), item))
+            .collect();
+        let groups: HashMap<String, GroupRecord> = store
+            .list_groups()?
+            .into_iter()
+            .map(|group| (format!("{}{}.json", GROUPS_PREFIX, group.id), group))
+            .collect();
+        (items, groups, store.sync_entries(&remote_id)?)
+    };
+
+    let remote: HashMap<String, RemoteObject> = backend
+        .list("")
+        .context("Failed to list remote objects")?
+        .into_iter()
+        .map(|object| (object.key.clone(), object))
+        .collect();
+
+    // リモートが空になっている（初期化された）場合、ローカルを削除せず全件を送り直す
+    let remote_has_records = remote.keys().any(|k| k.starts_with(ITEMS_PREFIX) || k.starts_with(GROUPS_PREFIX));
+    if !remote_has_records && !base.is_empty() {
+        log::warn!("Remote {} appears to have been reset; uploading the whole library", remote_id);
+        let mut store = ctx.store.lock().unwrap();
+        store
+            .as_mut()
+            .context("Metadata store not initialized")?
+            .clear_sync_entries(&remote_id)?;
+        base.clear();
+    }
+
+    let mut syncer = Syncer {
+        ctx,
+        backend,
+        remote_id,
+        remote_originals: remote.keys().filter(|k| k.starts_with(ORIGINALS_PREFIX)).cloned().collect(),
+        report: SyncReport::default(),
+    };
+
+    // アイテムがグループを参照するため、グループを先に同期する
+    let group_plan = Syncer::plan_records(&groups, &remote, &base);
+    let item_plan = Syncer::plan_records(&items, &remote, &base);
+    reporter.set_total((group_plan.len() + item_plan.len()) as u64);
+
+    let mut done = 0;
+    for (key, action) in group_plan {
+        reporter.checkpoint()?;
+        let remote_etag = remote.get(&key).map(|o| o.etag.as_str());
+        syncer.apply(&key, action, groups.get(&key), remote_etag, base.get(&key))?;
+        done += 1;
+        reporter.progress(done, &key);
+    }
+    for (key, action) in item_plan {
+        reporter.checkpoint()?;
+        let remote_etag = remote.get(&key).map(|o| o.etag.as_str());
+        syncer.apply(&key, action, items.get(&key), remote_etag, base.get(&key))?;
+        done += 1;
+        reporter.progress(done, &key);
+    }
+
+    Ok(syncer.report)
+}
+
+// 記録された衝突について残す側を選ぶ
+// 適用済みと違う側を選んだ場合だけ書き換える（新しい版として次回の同期で相手にも反映される）
+pub fn resolve_conflict(store: &mut MetadataStore, conflict_id: i64, keep: ConflictSide) -> Result<SyncReport> {
+    let conflict = store
+        .get_sync_conflict(conflict_id)?
+        .with_context(|| format!("Conflict not found: {}", conflict_id))?;
+    let value = match keep {
+        ConflictSide::Local => conflict.local_value.clone(),
+        ConflictSide::Remote => conflict.remote_value.clone(),
+    };
+    let id = conflict.record_id.as_str();
+    let mut report = SyncReport::default();
+
+    if conflict.field == "deleted" {
+        if value.as_bool() == Some(true) {
+            if conflict.kind == ItemRecord::KIND {
+                store.trash_item(id)?;
+                report.removed_item_ids.push(id.to_string());
+            } else {
+                report.changed_item_ids = store.delete_group(id)?;
+            }
+        }
+    } else if keep != conflict.applied {
+        if conflict.kind == ItemRecord::KIND {
+            let mut item = store.get_item(id)?.with_context(|| format!("Item not found: {}", id))?;
+            item.set_field_value(&conflict.field, value)?;
+            store.update_item(&item)?;
+            report.changed_item_ids.push(id.to_string());
+        } else {
+            let mut group = store.get_group(id)?.with_context(|| format!("Group not found: {}", id))?;
+            group.set_field_value(&conflict.field, value)?;
+            group.updated_at = Utc::now();
+            store.save_group(&group)?;
+            // グループ名は所属アイテムの検索対象に含まれる
+            let filter = ItemFilter {
+                group_id: Some(id.to_string()),
+                ..Default::default()
+            };
+            report.changed_item_ids = store.list_items(&filter)?.into_iter().map(|item| item.id).collect();
+        }
+    }
+    store.remove_sync_conflict(conflict_id)?;
+    Ok(report)
+}
@@ -0,0 +1,153 @@
+use crate::metadata_store::ItemRecord;
+use crate::receipts::ReceiptFields;
+use anyhow::Result;
+use chrono::Local;
+use regex::Regex;
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableFormat {
+    Csv,
+    Xlsx,
+}
+
+const HEADERS: &[&str] = &[
+    "id", "date", "tags", "memo", "location", "latitude", "longitude", "vendor", "purchased_on", "amounts",
+    "subtotal", "tax", "total", "file_path",
+];
+
+// OCR テキストから金額を拾う（¥1,234 / 1,234円 の形式）
+pub fn extract_amounts(text: &str) -> Vec<u64> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r"[¥￥]\s*([0-9][0-9,，]*)|([0-9][0-9,，]*)\s*円").unwrap()
+    });
+    pattern
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .filter_map(|m| m.as_str().replace([',', '，'], "").parse().ok())
+        .collect()
+}
+
+struct Row {
+    cells: Vec<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    subtotal: Option<u64>,
+    tax: Option<u64>,
+    total: Option<u64>,
+}
+
+fn amount_cell(amount: Option<u64>) -> String {
+    amount.map(|a| a.to_string()).unwrap_or_default()
+}
+
+fn to_row(item: &ItemRecord, receipt: Option<&ReceiptFields>) -> Row {
+    let amounts = extract_amounts(&item.ocr_text);
+    // レシートとして読み取れなかったものは、最も大きい金額を合計とみなす
+    let total = receipt.and_then(|r| r.total).or_else(|| amounts.iter().max().copied());
+    let subtotal = receipt.and_then(|r| r.subtotal);
+    let tax = receipt.and_then(|r| r.tax);
+    Row {
+        cells: vec![
+            item.id.clone(),
+            item.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+            item.tags.join(", "),
+            item.memo.clone(),
+            item.location_name.clone().unwrap_or_default(),
+            item.latitude.map(|v| v.to_string()).unwrap_or_default(),
+            item.longitude.map(|v| v.to_string()).unwrap_or_default(),
+            receipt.and_then(|r| r.vendor.clone()).unwrap_or_default(),
+            receipt
+                .and_then(|r| r.purchased_on)
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            amounts.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", "),
+            amount_cell(subtotal),
+            amount_cell(tax),
+            amount_cell(total),
+            item.image_path.clone().unwrap_or_default(),
+        ],
+        latitude: item.latitude,
+        longitude: item.longitude,
+        subtotal,
+        tax,
+        total,
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_csv(rows: &[Row], dest: &Path) -> Result<()> {
+    // Excel で開いたときに文字化けしないよう BOM を付ける
+    let mut output = String::from("\u{feff}");
+    output.push_str(&HEADERS.join(","));
+    output.push_str("\r\n");
+    for row in rows {
+        let cells: Vec<String> = row.cells.iter().map(|c| csv_field(c)).collect();
+        output.push_str(&cells.join(","));
+        output.push_str("\r\n");
+    }
+    fs::write(dest, output)?;
+    Ok(())
+}
+
+fn write_xlsx(rows: &[Row], dest: &Path) -> Result<()> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    let bold = Format::new().set_bold();
+    for (col, header) in HEADERS.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *header, &bold)?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let r = i as u32 + 1;
+        for (col, cell) in row.cells.iter().enumerate() {
+            // 数値の列は集計しやすいよう数値として書き込む
+            let number = match HEADERS[col] {
+                "latitude" => row.latitude,
+                "longitude" => row.longitude,
+                "subtotal" => row.subtotal.map(|t| t as f64),
+                "tax" => row.tax.map(|t| t as f64),
+                "total" => row.total.map(|t| t as f64),
+                _ => None,
+            };
+            match number {
+                Some(value) => sheet.write_number(r, col as u16, value)?,
+                None => sheet.write_string(r, col as u16, cell)?,
+            };
+        }
+    }
+    sheet.autofit();
+    workbook.save(dest)?;
+    Ok(())
+}
+
+// アイテムの一覧を表として書き出し、行数を返す（receipts はレシートとして読み取った項目）
+pub fn export_table(
+    items: &[ItemRecord],
+    receipts: &HashMap<String, ReceiptFields>,
+    format: TableFormat,
+    dest: &Path,
+) -> Result<usize> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let rows: Vec<Row> = items.iter().map(|item| to_row(item, receipts.get(&item.id))).collect();
+    match format {
+        TableFormat::Csv => write_csv(&rows, dest)?,
+        TableFormat::Xlsx => write_xlsx(&rows, dest)?,
+    }
+    Ok(rows.len())
+}
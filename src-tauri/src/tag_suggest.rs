@@ -0,0 +1,166 @@
+use crate::metadata_store::{ItemFilter, ItemRecord, MetadataStore};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+pub const DEFAULT_SUGGESTION_LIMIT: usize = 5;
+// 学習に使うタグ付きアイテムの数（新しいものから）
+const MAX_TRAINING_ITEMS: usize = 3000;
+// タグの付いたアイテムがこれより少ないと、キーワードからは推測しない
+const MIN_TAG_ITEMS: usize = 2;
+
+const NAME_WEIGHT: f64 = 1.0;
+const KEYWORD_WEIGHT: f64 = 0.6;
+const CO_OCCURRENCE_WEIGHT: f64 = 0.4;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionReason {
+    // タグ名が OCR のテキストやメモに含まれている
+    NameInText,
+    // そのタグが付いたアイテムによく出てくる語を含んでいる
+    Keywords,
+    // 既に付いているタグと一緒に使われることが多い
+    CoOccurrence,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagSuggestion {
+    pub tag: String,
+    pub score: f64,
+    pub reasons: Vec<SuggestionReason>,
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+// 英数字は3文字以上の単語、日本語は分かち書きしないため2文字ずつ区切る
+fn tokens(text: &str) -> HashSet<String> {
+    let mut tokens = HashSet::new();
+    let mut word = String::new();
+    let mut cjk: Vec<char> = Vec::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if is_cjk(c) {
+            cjk.push(c);
+        } else {
+            for pair in cjk.windows(2) {
+                tokens.insert(pair.iter().collect());
+            }
+            cjk.clear();
+        }
+        if c.is_ascii_alphanumeric() {
+            word.push(c.to_ascii_lowercase());
+        } else {
+            if word.chars().count() >= 3 && !word.chars().all(|c| c.is_ascii_digit()) {
+                tokens.insert(word.clone());
+            }
+            word.clear();
+        }
+    }
+    tokens
+}
+
+fn item_text(item: &ItemRecord) -> String {
+    format!("{}\n{}", item.ocr_text, item.memo)
+}
+
+// OCR のキーワードと、よく一緒に付けられるタグからタグの候補を出す
+pub fn suggest_tags(store: &MetadataStore, item_id: &str, limit: usize) -> Result<Vec<TagSuggestion>> {
+    let item = store.get_item(item_id)?.with_context(|| format!("Item not found: {}", item_id))?;
+    let tagged: Vec<ItemRecord> = store
+        .list_items(&ItemFilter::default())?
+        .into_iter()
+        .filter(|other| other.id != item.id && !other.tags.is_empty())
+        .take(MAX_TRAINING_ITEMS)
+        .collect();
+    let known_tags: Vec<String> = store.list_tags()?.into_iter().map(|t| t.name).collect();
+
+    let mut scores: HashMap<String, (f64, Vec<SuggestionReason>)> = HashMap::new();
+    let mut add = |tag: &str, score: f64, reason: SuggestionReason| {
+        if score <= 0.0 || item.tags.iter().any(|t| t == tag) {
+            return;
+        }
+        let entry = scores.entry(tag.to_string()).or_insert((0.0, Vec::new()));
+        entry.0 += score;
+        entry.1.push(reason);
+    };
+
+    // タグ名そのものが書かれている
+    let text = item_text(&item).to_lowercase();
+    for tag in &known_tags {
+        if tag.chars().count() >= 2 && text.contains(&tag.to_lowercase()) {
+            add(tag, NAME_WEIGHT, SuggestionReason::NameInText);
+        }
+    }
+
+    // タグごとによく出る語（そのタグのアイテムでの出現率 × 全体での珍しさ）
+    let item_tokens = tokens(&text);
+    if !item_tokens.is_empty() {
+        let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+        let mut tag_frequency: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+        let mut tag_items: HashMap<&str, usize> = HashMap::new();
+        let other_tokens: Vec<HashSet<String>> = tagged.iter().map(|other| tokens(&item_text(other))).collect();
+        for (other, other_tokens) in tagged.iter().zip(&other_tokens) {
+            let shared: Vec<&str> = other_tokens.iter().filter(|t| item_tokens.contains(*t)).map(|t| t.as_str()).collect();
+            for token in &shared {
+                *document_frequency.entry(token).or_insert(0) += 1;
+            }
+            for tag in &other.tags {
+                *tag_items.entry(tag.as_str()).or_insert(0) += 1;
+                let frequency = tag_frequency.entry(tag.as_str()).or_default();
+                for token in &shared {
+                    *frequency.entry(token).or_insert(0) += 1;
+                }
+            }
+        }
+        let total = tagged.len().max(1) as f64;
+        let keyword_scores: Vec<(&str, f64)> = tag_frequency
+            .iter()
+            .filter(|(tag, _)| tag_items[*tag] >= MIN_TAG_ITEMS)
+            .map(|(tag, frequency)| {
+                let score: f64 = frequency
+                    .iter()
+                    .map(|(token, count)| {
+                        let idf = (total / document_frequency[token] as f64).ln();
+                        *count as f64 / tag_items[tag] as f64 * idf
+                    })
+                    .sum();
+                (*tag, score)
+            })
+            .collect();
+        // 最も高いものを 1 として比べる
+        let max = keyword_scores.iter().map(|(_, s)| *s).fold(0.0, f64::max);
+        if max > 0.0 {
+            for (tag, score) in &keyword_scores {
+                add(tag, *score / max * KEYWORD_WEIGHT, SuggestionReason::Keywords);
+            }
+        }
+    }
+
+    // 既に付いているタグと一緒に付けられる割合
+    for existing in &item.tags {
+        let with_existing: Vec<&ItemRecord> = tagged.iter().filter(|other| other.tags.contains(existing)).collect();
+        if with_existing.is_empty() {
+            continue;
+        }
+        let mut co_occurrence: HashMap<&str, usize> = HashMap::new();
+        for other in &with_existing {
+            for tag in other.tags.iter().filter(|t| *t != existing) {
+                *co_occurrence.entry(tag.as_str()).or_insert(0) += 1;
+            }
+        }
+        for (tag, count) in co_occurrence {
+            let ratio = count as f64 / with_existing.len() as f64;
+            add(tag, ratio * CO_OCCURRENCE_WEIGHT, SuggestionReason::CoOccurrence);
+        }
+    }
+
+    let mut suggestions: Vec<TagSuggestion> = scores
+        .into_iter()
+        .map(|(tag, (score, reasons))| TagSuggestion { tag, score, reasons })
+        .collect();
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.tag.cmp(&b.tag)));
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
@@ -0,0 +1,203 @@
+use crate::hashing;
+use crate::image_decode;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 512 * 1024 * 1024; // 512MB
+
+// 作るサムネイルの大きさ（指定された大きさ以上で最も小さいもの、最大でも最後のもの）
+const THUMBNAIL_SIZES: [u32; 4] = [128, 256, 512, 1024];
+// 上限を超えたらこの割合まで減らす（上限ぎりぎりだと書き込みのたびに掃除が走るため）
+const EVICT_TARGET_PERCENT: u64 = 90;
+
+fn thumbnail_size(size: u32) -> u32 {
+    if size == 0 {
+        return DEFAULT_THUMBNAIL_SIZE;
+    }
+    THUMBNAIL_SIZES
+        .into_iter()
+        .find(|&allowed| allowed >= size)
+        .unwrap_or(THUMBNAIL_SIZES[THUMBNAIL_SIZES.len() - 1])
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThumbnailCacheStats {
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub max_bytes: u64,
+}
+
+// 元画像のパス・更新日時・サイズが同じならハッシュを再計算しない
+#[derive(PartialEq, Eq, Hash)]
+struct SourceKey {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    // キャッシュの合計サイズ（起動時に1回だけ数え、以降は追加・削除のたびに増減する）
+    total_bytes: Mutex<u64>,
+    hashes: Mutex<HashMap<SourceKey, String>>,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create thumbnail directory: {}", dir.display()))?;
+        let cache = ThumbnailCache {
+            dir,
+            max_bytes,
+            total_bytes: Mutex::new(0),
+            hashes: Mutex::new(HashMap::new()),
+        };
+        *cache.total_bytes.lock().unwrap() = cache.entries()?.iter().map(|(_, len, _)| len).sum();
+        Ok(cache)
+    }
+
+    // サムネイルを取得（なければ生成してキャッシュ）し、キャッシュファイルのパスを返す
+    pub fn get_or_create(&self, source: &Path, size: u32) -> Result<PathBuf> {
+        let size = thumbnail_size(size);
+        let hash = self.source_hash(source)?;
+        let cached = self.dir.join(format!("{}_{}.jpg", hash, size));
+
+        if cached.exists() {
+            // LRU判定のため最終利用時刻として更新日時を更新
+            if let Ok(file) = fs::File::options().write(true).open(&cached) {
+                let _ = file.set_modified(SystemTime::now());
+            }
+            return Ok(cached);
+        }
+
+        let data = fs::read(source)
+            .with_context(|| format!("Failed to read image: {}", source.display()))?;
+        let bytes = Self::render(&data, size)?;
+
+        // 書き込み途中のファイルを読まれないよう一時ファイル経由で配置
+        let tmp = cached.with_extension("jpg.tmp");
+        fs::write(&tmp, &bytes)?;
+        fs::rename(&tmp, &cached)?;
+
+        let total = {
+            let mut total = self.total_bytes.lock().unwrap();
+            *total += bytes.len() as u64;
+            *total
+        };
+        if total > self.max_bytes {
+            self.evict()?;
+        }
+        Ok(cached)
+    }
+
+    pub fn read(&self, source: &Path, size: u32) -> Result<Vec<u8>> {
+        let path = self.get_or_create(source, size)?;
+        Ok(fs::read(path)?)
+    }
+
+    fn source_hash(&self, source: &Path) -> Result<String> {
+        let metadata = fs::metadata(source)
+            .with_context(|| format!("Image not found: {}", source.display()))?;
+        let key = SourceKey {
+            path: source.to_path_buf(),
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        };
+
+        if let Some(hash) = self.hashes.lock().unwrap().get(&key) {
+            return Ok(hash.clone());
+        }
+
+        let hash = hashing::file_hash(source)?;
+        self.hashes.lock().unwrap().insert(key, hash.clone());
+        Ok(hash)
+    }
+
+    pub fn render(data: &[u8], size: u32) -> Result<Vec<u8>> {
+        let img = image_decode::decode(data)?;
+        let thumbnail = image::DynamicImage::ImageRgb8(img.thumbnail(size, size).to_rgb8());
+        image_decode::encode_jpeg(&thumbnail, 80).context("Failed to encode thumbnail")
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((entry.path(), metadata.len(), modified));
+            }
+        }
+        Ok(entries)
+    }
+
+    // 上限を超えたら最終利用が古いものから上限の9割まで削除（合計もディレクトリの実際の値で数え直す）
+    fn evict(&self) -> Result<()> {
+        let mut total_bytes = self.total_bytes.lock().unwrap();
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            *total_bytes = total;
+            return Ok(());
+        }
+
+        let target = self.max_bytes / 100 * EVICT_TARGET_PERCENT;
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if total <= target {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(len);
+            }
+        }
+        *total_bytes = total;
+        Ok(())
+    }
+
+    fn removed(&self, len: u64) {
+        let mut total = self.total_bytes.lock().unwrap();
+        *total = total.saturating_sub(len);
+    }
+
+    // 元画像のハッシュに対応するサムネイルをすべて消す（非公開にした画像など）
+    pub fn remove(&self, source_hash: &str) -> Result<()> {
+        let prefix = format!("{}_", source_hash);
+        for (path, len, _) in self.entries()? {
+            let matches = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(&prefix));
+            if matches {
+                fs::remove_file(path)?;
+                self.removed(len);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        for (path, len, _) in self.entries()? {
+            fs::remove_file(path)?;
+            self.removed(len);
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<ThumbnailCacheStats> {
+        let entries = self.entries()?;
+        Ok(ThumbnailCacheStats {
+            file_count: entries.len(),
+            total_bytes: entries.iter().map(|(_, len, _)| len).sum(),
+            max_bytes: self.max_bytes,
+        })
+    }
+}
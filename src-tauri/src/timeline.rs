@@ -0,0 +1,82 @@
+use crate::metadata_store::ItemRecord;
+use crate::search_engine::DateField;
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// 1つのまとまりに付ける代表サムネイルの数
+const REPRESENTATIVES_PER_BUCKET: usize = 4;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineGranularity {
+    #[default]
+    Day,
+    Month,
+    Year,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineThumbnail {
+    pub item_id: String,
+    pub image_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineBucket {
+    // 2024-05-12 / 2024-05 / 2024（ローカル時刻）
+    pub key: String,
+    pub count: usize,
+    pub representatives: Vec<TimelineThumbnail>,
+}
+
+// まとめるのに使う日付（書類の日付が読み取れなかったアイテムは撮影日）
+fn item_date(item: &ItemRecord, date_field: DateField) -> NaiveDate {
+    match (date_field, item.document_date) {
+        (DateField::Document, Some(date)) => date,
+        _ => item.created_at.with_timezone(&Local).date_naive(),
+    }
+}
+
+fn bucket_key(date: NaiveDate, granularity: TimelineGranularity) -> String {
+    match granularity {
+        TimelineGranularity::Day => date.format("%Y-%m-%d").to_string(),
+        TimelineGranularity::Month => format!("{}-{:02}", date.year(), date.month()),
+        TimelineGranularity::Year => date.year().to_string(),
+    }
+}
+
+// アイテムを日・月・年ごとにまとめる（新しい順）
+pub fn build_timeline(
+    items: &[ItemRecord],
+    granularity: TimelineGranularity,
+    date_field: DateField,
+) -> Vec<TimelineBucket> {
+    let mut buckets: BTreeMap<String, Vec<&ItemRecord>> = BTreeMap::new();
+    for item in items {
+        let key = bucket_key(item_date(item, date_field), granularity);
+        buckets.entry(key).or_default().push(item);
+    }
+    buckets
+        .into_iter()
+        .rev()
+        .map(|(key, mut items)| {
+            items.sort_by(|a, b| {
+                (item_date(b, date_field), b.created_at).cmp(&(item_date(a, date_field), a.created_at))
+            });
+            TimelineBucket {
+                key,
+                count: items.len(),
+                representatives: items
+                    .iter()
+                    .filter(|item| item.image_path.is_some())
+                    .take(REPRESENTATIVES_PER_BUCKET)
+                    .map(|item| TimelineThumbnail {
+                        item_id: item.id.clone(),
+                        image_path: item.image_path.clone(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,72 @@
+use crate::document_dates;
+use crate::document_types::DocumentType;
+use crate::metadata_store::ItemRecord;
+use crate::receipts;
+use std::collections::HashMap;
+
+// 見出しにする OCR の行を探す範囲（書類の先頭の行）
+const TITLE_LINES: usize = 10;
+const MIN_TITLE_CHARS: usize = 3;
+const MAX_TITLE_CHARS: usize = 40;
+// 見出しに向かない定型の行
+const SKIP_WORDS: &[&str] = &[
+    "tel", "fax", "電話", "〒", "http", "www", "@", "いらっしゃいませ", "ありがとう", "page",
+];
+
+// 数字や記号ばかりの行・日付だけの行・定型の行は見出しにしない
+fn title_candidate(line: &str) -> Option<String> {
+    let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    let chars = line.chars().count();
+    if chars < MIN_TITLE_CHARS {
+        return None;
+    }
+    let lower = line.to_lowercase();
+    if SKIP_WORDS.iter().any(|word| lower.contains(word)) || document_dates::parse(&line).is_some() {
+        return None;
+    }
+    let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+    (letters * 2 > chars).then(|| truncate(&line))
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_TITLE_CHARS {
+        return text.to_string();
+    }
+    let mut title: String = text.chars().take(MAX_TITLE_CHARS - 1).collect();
+    title.push('…');
+    title
+}
+
+// アイテムの見出しを推定する（レシート・請求書は店名、それ以外は OCR の最初の意味のある行、文字がなければ説明文）
+pub fn generate(ocr_text: &str, doc_type: DocumentType, caption: Option<&str>) -> Option<String> {
+    if doc_type.has_receipt_fields() {
+        if let Some(vendor) = receipts::parse(ocr_text).vendor {
+            return Some(truncate(&vendor));
+        }
+    }
+    ocr_text
+        .lines()
+        .take(TITLE_LINES)
+        .find_map(title_candidate)
+        .or_else(|| caption.map(str::trim).filter(|c| !c.is_empty()).map(truncate))
+}
+
+pub fn for_item(item: &ItemRecord) -> Option<String> {
+    generate(&item.ocr_text, item.doc_type, item.caption.as_deref())
+}
+
+// グループの見出しはアイテムの見出しで最も多いもの（同数なら先に取り込んだアイテムのもの）
+pub fn for_group(items: &[ItemRecord]) -> Option<String> {
+    let mut items: Vec<&ItemRecord> = items.iter().filter(|item| !item.private).collect();
+    items.sort_by_key(|item| item.created_at);
+    let titles: Vec<String> = items
+        .iter()
+        .filter_map(|item| item.title.clone().or_else(|| for_item(item)))
+        .collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for title in &titles {
+        *counts.entry(title.as_str()).or_default() += 1;
+    }
+    let max = counts.values().copied().max()?;
+    titles.iter().find(|title| counts[title.as_str()] == max).cloned()
+}
@@ -0,0 +1,310 @@
+use crate::inference::{InferenceInput, InferenceService};
+use crate::models;
+use crate::secrets;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokenizers::Tokenizer;
+
+const KEYCHAIN_API_KEY: &str = "translation-api-key";
+const API_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const FEATURE: &str = "translation";
+const ENCODER_MODEL: &str = "opus-mt-ja-en-encoder";
+const DECODER_MODEL: &str = "opus-mt-ja-en-decoder";
+const TOKENIZER: &str = "opus-mt-ja-en-tokenizer";
+const CONFIG: &str = "opus-mt-ja-en-config";
+// 端末内のモデルに一度に渡す長さ（行の途中では切らない）
+const MAX_CHUNK_CHARS: usize = 300;
+const MAX_INPUT_TOKENS: usize = 512;
+const MAX_OUTPUT_TOKENS: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationProvider {
+    #[default]
+    Disabled,
+    // 端末内の Marian モデル（日本語から英語のみ）
+    Local,
+    // LibreTranslate 互換の API（OCR のテキストが外部に送られる）
+    Api,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationSettings {
+    #[serde(default)]
+    pub provider: TranslationProvider,
+    // 取り込み時に OCR のテキストを翻訳する（しない場合は翻訳のジョブで後からまとめて行う）
+    #[serde(default = "default_translate_on_import")]
+    pub translate_on_import: bool,
+    // 例: https://libretranslate.example.com/translate
+    #[serde(default)]
+    pub api_url: String,
+    // 保存時は OS のキーチェーンへ移す
+    #[serde(default)]
+    pub api_key: String,
+}
+
+fn default_translate_on_import() -> bool {
+    true
+}
+
+impl Default for TranslationSettings {
+    fn default() -> Self {
+        TranslationSettings {
+            provider: TranslationProvider::default(),
+            translate_on_import: default_translate_on_import(),
+            api_url: String::new(),
+            api_key: String::new(),
+        }
+    }
+}
+
+impl TranslationSettings {
+    pub fn load(path: &Path) -> Self {
+        let mut settings: Self = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        secrets::fill(path, KEYCHAIN_API_KEY, &mut settings.api_key);
+        settings
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut settings = self.clone();
+        secrets::stash(path, KEYCHAIN_API_KEY, &mut settings.api_key);
+        fs::write(path, serde_json::to_string_pretty(&settings)?)?;
+        Ok(())
+    }
+}
+
+// OCR のテキストを別の言語に訳したもの
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Translation {
+    // 訳した先の言語（ja / en）
+    pub language: String,
+    pub text: String,
+}
+
+fn is_japanese(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{ff66}'..='\u{ff9f}')
+}
+
+// 日本語と英語のどちらで書かれているか（かな・漢字が文字の3割以上なら日本語）
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let japanese = text.chars().filter(|&c| is_japanese(c)).count();
+    let latin = text.chars().filter(|c| c.is_ascii_alphabetic()).count();
+    if japanese == 0 && latin == 0 {
+        None
+    } else if japanese * 10 >= (japanese + latin) * 3 {
+        Some("ja")
+    } else {
+        Some("en")
+    }
+}
+
+fn target_language(source: &str) -> &'static str {
+    if source == "ja" {
+        "en"
+    } else {
+        "ja"
+    }
+}
+
+// 行の途中で切らずに、モデルに渡せる長さごとにまとめる
+fn chunks(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if !current.is_empty() && current.chars().count() + line.chars().count() > MAX_CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// Marian の config.json のうちデコードに使う値
+#[derive(Deserialize)]
+struct MarianConfig {
+    decoder_start_token_id: i64,
+    eos_token_id: i64,
+    pad_token_id: i64,
+}
+
+struct LocalModel {
+    tokenizer: Tokenizer,
+    config: MarianConfig,
+}
+
+#[derive(Deserialize)]
+struct ApiResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+// OCR のテキストを日本語と英語の間で訳す（どちらの言語で探しても見つかるよう検索インデックスに入れる）
+pub struct TranslationService {
+    inference: Arc<InferenceService>,
+    settings: Mutex<TranslationSettings>,
+    settings_path: PathBuf,
+    local: Mutex<Option<Arc<LocalModel>>>,
+}
+
+impl TranslationService {
+    pub fn new(inference: Arc<InferenceService>, settings_path: PathBuf) -> Self {
+        TranslationService {
+            inference,
+            settings: Mutex::new(TranslationSettings::load(&settings_path)),
+            settings_path,
+            local: Mutex::new(None),
+        }
+    }
+
+    pub fn settings(&self) -> TranslationSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set_settings(&self, settings: TranslationSettings) -> Result<()> {
+        settings.save(&self.settings_path)?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.settings().provider != TranslationProvider::Disabled
+    }
+
+    // 訳す（翻訳しない設定・訳せない言語・空のテキストなら None）
+    pub fn translate(&self, text: &str) -> Result<Option<Translation>> {
+        let Some(source) = detect_language(text) else {
+            return Ok(None);
+        };
+        let target = target_language(source);
+        let settings = self.settings();
+        let translated = match settings.provider {
+            TranslationProvider::Disabled => return Ok(None),
+            // 端末内のモデルは日本語から英語へのみ訳せる
+            TranslationProvider::Local if source != "ja" => return Ok(None),
+            TranslationProvider::Local => chunks(text)
+                .iter()
+                .map(|chunk| self.translate_local(chunk))
+                .collect::<Result<Vec<_>>>()?
+                .join("\n"),
+            TranslationProvider::Api => self.translate_api(&settings, text, source, target)?,
+        };
+        let translated = translated.trim();
+        if translated.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Translation {
+            language: target.to_string(),
+            text: translated.to_string(),
+        }))
+    }
+
+    fn local(&self) -> Result<Arc<LocalModel>> {
+        let mut local = self.local.lock().unwrap();
+        if let Some(model) = local.as_ref() {
+            return Ok(model.clone());
+        }
+        if !self.inference.models().feature_installed(FEATURE) {
+            bail!("Translation models are not installed");
+        }
+        let models = self.inference.models();
+        let tokenizer =
+            Tokenizer::from_file(models.path(TOKENIZER)).map_err(|e| anyhow!("Failed to load tokenizer: {}", e))?;
+        let config: MarianConfig = serde_json::from_str(&fs::read_to_string(models.path(CONFIG))?)
+            .context("Invalid translation model config")?;
+        let model = Arc::new(LocalModel { tokenizer, config });
+        *local = Some(model.clone());
+        Ok(model)
+    }
+
+    fn translate_local(&self, text: &str) -> Result<String> {
+        let model = self.local()?;
+        let encoding = model
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| anyhow!("Failed to tokenize text: {}", e))?;
+        let mut ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
+        ids.truncate(MAX_INPUT_TOKENS);
+        let len = ids.len();
+        let mask = vec![1i64; len];
+        let hidden = self
+            .inference
+            .run(
+                ENCODER_MODEL,
+                vec![
+                    ("input_ids", InferenceInput::i64(&[1, len], ids)?),
+                    ("attention_mask", InferenceInput::i64(&[1, len], mask.clone())?),
+                ],
+            )?
+            .remove("last_hidden_state")
+            .context("Model has no last_hidden_state output")?;
+
+        let config = &model.config;
+        let output = self.inference.greedy_decode(
+            DECODER_MODEL,
+            config.decoder_start_token_id,
+            config.eos_token_id,
+            MAX_OUTPUT_TOKENS.min(len * 3 + 10),
+            &[config.pad_token_id],
+            || {
+                Ok(vec![
+                    ("encoder_hidden_states", InferenceInput::F32(hidden.clone())),
+                    ("encoder_attention_mask", InferenceInput::i64(&[1, len], mask.clone())?),
+                ])
+            },
+        )?;
+        let tokens: Vec<u32> = output.iter().map(|&id| id as u32).collect();
+        model
+            .tokenizer
+            .decode(&tokens, true)
+            .map_err(|e| anyhow!("Failed to decode translation: {}", e))
+    }
+
+    fn translate_api(&self, settings: &TranslationSettings, text: &str, source: &str, target: &str) -> Result<String> {
+        if settings.api_url.is_empty() {
+            bail!("Translation API URL is not set");
+        }
+        let mut body = serde_json::json!({ "q": text, "source": source, "target": target, "format": "text" });
+        if !settings.api_key.is_empty() {
+            body["api_key"] = serde_json::json!(settings.api_key);
+        }
+        let response: ApiResponse = reqwest::blocking::Client::builder()
+            .timeout(API_TIMEOUT)
+            .build()?
+            .post(&settings.api_url)
+            .header("User-Agent", concat!("SnapOrganizer/", env!("CARGO_PKG_VERSION")))
+            .json(&body)
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(response.translated_text)
+    }
+
+    // モデルを消したときは読み込み済みのトークナイザーと設定も捨てる
+    pub fn unload(&self) {
+        *self.local.lock().unwrap() = None;
+    }
+}
+
+// 翻訳に使うモデルか（ダウンロード・削除したときに読み込み済みのものを捨てる）
+pub fn is_model(model_id: &str) -> bool {
+    models::CATALOG
+        .iter()
+        .any(|spec| spec.id == model_id && spec.feature == FEATURE)
+}
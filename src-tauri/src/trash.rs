@@ -0,0 +1,88 @@
+use crate::metadata_store::MetadataStore;
+use crate::retention::{self, RetentionSettings};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashSettings {
+    // ゴミ箱に入れてから完全に削除するまでの日数（0 なら自動では削除しない）
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_retention_days() -> u32 {
+    DEFAULT_RETENTION_DAYS
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        TrashSettings {
+            retention_days: DEFAULT_RETENTION_DAYS,
+        }
+    }
+}
+
+impl TrashSettings {
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// ゴミ箱のアイテムを完全に削除し、ほかのアイテムが参照していない元画像も削除する
+pub fn purge_items(store: &mut MetadataStore, ids: &[String]) -> Result<usize> {
+    let mut purged = 0;
+    for id in ids {
+        let item = match store.purge_item(id)? {
+            Some(item) => item,
+            None => continue,
+        };
+        if let Some(image_path) = item.image_path.as_deref() {
+            if !store.is_image_referenced(image_path)? {
+                match fs::remove_file(image_path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        log::warn!("Failed to remove {}: {}", image_path, e);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+pub fn purge_all(store: &mut MetadataStore) -> Result<usize> {
+    let ids: Vec<String> = store.list_trash()?.into_iter().map(|t| t.item.id).collect();
+    purge_items(store, &ids)
+}
+
+// 保持期間を過ぎたアイテムを削除する（保持ルールの期間内のタグが付いたものは残す）
+pub fn purge_expired(
+    store: &mut MetadataStore,
+    settings: &TrashSettings,
+    retention: &RetentionSettings,
+) -> Result<usize> {
+    if settings.retention_days == 0 {
+        return Ok(0);
+    }
+    let cutoff = Utc::now() - Duration::days(settings.retention_days as i64);
+    let ids = store.trashed_before(cutoff)?;
+    let ids = retention::unprotected(store, retention, ids)?;
+    purge_items(store, &ids)
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::Serialize;
+use std::fs;
+use std::io::Read;
+use std::net::{IpAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// スマホから1回に送れるファイルの上限
+const MAX_UPLOAD_BYTES: u64 = 50 * 1024 * 1024;
+
+const UPLOAD_PAGE: &str = include_str!("upload_page.html");
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadServerInfo {
+    pub url: String,
+    // 画面に表示する QR コード（SVG）
+    pub qr_svg: String,
+}
+
+// 受け取ったファイルを取り込み、アイテムIDを返す処理
+pub type UploadHandler = Arc<dyn Fn(&Path) -> Result<String> + Send + Sync>;
+
+// LAN 内のスマホから写真を受け取るための HTTP サーバー（明示的に開始したときだけ動く）
+pub struct UploadServer {
+    server: Arc<Server>,
+    info: UploadServerInfo,
+}
+
+// 外部への経路で使われるローカルアドレス（UDP の connect はパケットを送らない）
+pub fn lan_address() -> Result<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect("8.8.8.8:80")?;
+    Ok(socket.local_addr()?.ip())
+}
+
+pub fn query_param(url: &str, name: &str) -> Option<String> {
+    let query = url.split_once('?')?.1;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| percent_encoding::percent_decode_str(value).decode_utf8_lossy().to_string())
+    })
+}
+
+// 端末から送られたファイル名はパス区切りなどを取り除いて使う
+pub fn sanitize_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    let name = name.trim_matches('.');
+    if name.is_empty() {
+        "upload.jpg".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+pub fn json_response(status: u16, body: serde_json::Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(body.to_string().into_bytes())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+impl UploadServer {
+    pub fn start(staging_dir: PathBuf, handler: UploadHandler) -> Result<Self> {
+        fs::create_dir_all(&staging_dir)?;
+        let server = Arc::new(
+            Server::http("0.0.0.0:0").map_err(|e| anyhow::anyhow!("Failed to start upload server: {}", e))?,
+        );
+        let port = server
+            .server_addr()
+            .to_ip()
+            .context("Upload server is not bound to a TCP address")?
+            .port();
+
+        // URL に含めたトークンを知っている端末（QR コードを読んだ端末）だけが送信できる
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let url = format!("http://{}:{}/?t={}", lan_address()?, port, token);
+        let qr_svg = QrCode::new(url.as_bytes())?
+            .render::<svg::Color>()
+            .min_dimensions(240, 240)
+            .build();
+
+        let worker = server.clone();
+        std::thread::spawn(move || {
+            for request in worker.incoming_requests() {
+                let staging_dir = staging_dir.clone();
+                let handler = handler.clone();
+                let token = token.clone();
+                // 取り込み（OCR を含む）は時間がかかるため、リクエストごとにスレッドを分ける
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_request(request, &token, &staging_dir, &handler) {
+                        log::warn!("Upload request failed: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(UploadServer {
+            server,
+            info: UploadServerInfo { url, qr_svg },
+        })
+    }
+
+    pub fn info(&self) -> &UploadServerInfo {
+        &self.info
+    }
+
+    pub fn stop(&self) {
+        self.server.unblock();
+    }
+}
+
+fn handle_request(mut request: Request, token: &str, staging_dir: &Path, handler: &UploadHandler) -> Result<()> {
+    let url = request.url().to_string();
+    if query_param(&url, "t").as_deref() != Some(token) {
+        return Ok(request.respond(Response::from_string("Forbidden").with_status_code(403))?);
+    }
+    let path = url.split('?').next().unwrap_or("/");
+
+    match (request.method(), path) {
+        (Method::Get, "/") => {
+            let page = UPLOAD_PAGE.replace("{{TOKEN}}", token);
+            let response = Response::from_string(page)
+                .with_header(Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap());
+            Ok(request.respond(response)?)
+        }
+        (Method::Post, "/upload") => {
+            if request.body_length().map(|len| len as u64 > MAX_UPLOAD_BYTES).unwrap_or(false) {
+                return Ok(request.respond(json_response(413, serde_json::json!({ "error": "File too large" })))?);
+            }
+            let name = sanitize_file_name(&query_param(&url, "name").unwrap_or_default());
+            let staged = staging_dir.join(format!("{}-{}", uuid::Uuid::new_v4().simple(), name));
+
+            let mut data = Vec::new();
+            request
+                .as_reader()
+                .take(MAX_UPLOAD_BYTES + 1)
+                .read_to_end(&mut data)?;
+            if data.len() as u64 > MAX_UPLOAD_BYTES {
+                return Ok(request.respond(json_response(413, serde_json::json!({ "error": "File too large" })))?);
+            }
+            fs::write(&staged, &data)?;
+
+            let result = handler(&staged);
+            let _ = fs::remove_file(&staged);
+            let response = match result {
+                Ok(item_id) => json_response(200, serde_json::json!({ "item_id": item_id })),
+                Err(e) => json_response(422, serde_json::json!({ "error": e.to_string() })),
+            };
+            Ok(request.respond(response)?)
+        }
+        _ => Ok(request.respond(Response::from_string("Not Found").with_status_code(404))?),
+    }
+}
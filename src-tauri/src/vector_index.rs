@@ -0,0 +1,409 @@
+use anyhow::{bail, Context, Result};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"SOHNSW01";
+// 各層でつなぐ近傍の数（最下層は 2 倍）
+const M: usize = 16;
+const EF_CONSTRUCTION: usize = 100;
+const EF_SEARCH: usize = 64;
+// 削除済みのノードがこの割合を超えたら保存時に作り直す
+const MAX_DELETED_RATIO: f32 = 0.3;
+
+struct Node {
+    id: String,
+    vector: Vec<f32>,
+    // 層ごとの近傍（ノードの番号）
+    links: Vec<Vec<u32>>,
+    deleted: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: u32,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// 正規化したベクトルどうしの距離（1 - コサイン類似度）
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>()
+}
+
+pub fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+// ID から決まる層（作り直しても同じグラフになるよう乱数の代わりにハッシュを使う）
+fn random_level(id: &str) -> usize {
+    let hash = blake3::hash(id.as_bytes());
+    let bits = u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap());
+    let uniform = ((bits >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    (-uniform.ln() / (M as f64).ln()).floor() as usize
+}
+
+fn max_links(level: usize) -> usize {
+    if level == 0 {
+        M * 2
+    } else {
+        M
+    }
+}
+
+// 画像の埋め込みの近傍探索（HNSW）。ベクトルもグラフと一緒にファイルへ保存する
+pub struct VectorIndex {
+    // 埋め込みを計算したモデル（違うモデルのベクトルは混ぜない）
+    model: String,
+    dim: usize,
+    nodes: Vec<Node>,
+    ids: HashMap<String, u32>,
+    entry: Option<u32>,
+    max_level: usize,
+    deleted: usize,
+    dirty: bool,
+}
+
+impl VectorIndex {
+    pub fn new(model: &str, dim: usize) -> Self {
+        VectorIndex {
+            model: model.to_string(),
+            dim,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry: None,
+            max_level: 0,
+            deleted: 0,
+            dirty: false,
+        }
+    }
+
+    // ファイルがない・壊れている・モデルが違うときは空の索引から始める
+    pub fn open(path: &Path, model: &str, dim: usize) -> Self {
+        if !path.exists() {
+            return Self::new(model, dim);
+        }
+        match Self::load(path) {
+            Ok(index) if index.model == model && index.dim == dim => index,
+            Ok(_) => {
+                log::info!("Embedding model changed; starting a new vector index");
+                Self::new(model, dim)
+            }
+            Err(e) => {
+                log::warn!("Failed to load vector index {}: {}", path.display(), e);
+                Self::new(model, dim)
+            }
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains_key(id)
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.ids.keys().cloned().collect()
+    }
+
+    pub fn vector(&self, id: &str) -> Option<&[f32]> {
+        self.ids.get(id).map(|&node| self.nodes[node as usize].vector.as_slice())
+    }
+
+    fn node_distance(&self, query: &[f32], node: u32) -> f32 {
+        distance(query, &self.nodes[node as usize].vector)
+    }
+
+    // 上の層では最も近いノードへ貪欲にたどる
+    fn greedy(&self, query: &[f32], mut current: u32, level: usize) -> u32 {
+        let mut best = self.node_distance(query, current);
+        loop {
+            let mut changed = false;
+            for &neighbor in self.nodes[current as usize].links.get(level).into_iter().flatten() {
+                let d = self.node_distance(query, neighbor);
+                if d < best {
+                    best = d;
+                    current = neighbor;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return current;
+            }
+        }
+    }
+
+    // 層の中で近い順に ef 個の候補を探す（削除済みのノードも経由には使う）
+    fn search_layer(&self, query: &[f32], entry: u32, ef: usize, level: usize) -> Vec<Candidate> {
+        let first = Candidate {
+            distance: self.node_distance(query, entry),
+            node: entry,
+        };
+        let mut visited = HashSet::from([entry]);
+        let mut candidates = BinaryHeap::from([std::cmp::Reverse(first)]);
+        let mut found = BinaryHeap::from([first]);
+
+        while let Some(std::cmp::Reverse(current)) = candidates.pop() {
+            if found.len() >= ef && current.distance > found.peek().unwrap().distance {
+                break;
+            }
+            for &neighbor in self.nodes[current.node as usize].links.get(level).into_iter().flatten() {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let candidate = Candidate {
+                    distance: self.node_distance(query, neighbor),
+                    node: neighbor,
+                };
+                if found.len() < ef || candidate.distance < found.peek().unwrap().distance {
+                    candidates.push(std::cmp::Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    // 近傍が多すぎるノードは近いものだけ残す
+    fn prune(&mut self, node: u32, level: usize) {
+        let limit = max_links(level);
+        if self.nodes[node as usize].links[level].len() <= limit {
+            return;
+        }
+        let vector = self.nodes[node as usize].vector.clone();
+        let mut links: Vec<Candidate> = self.nodes[node as usize].links[level]
+            .iter()
+            .map(|&n| Candidate {
+                distance: self.node_distance(&vector, n),
+                node: n,
+            })
+            .collect();
+        links.sort();
+        self.nodes[node as usize].links[level] = links.into_iter().take(limit).map(|c| c.node).collect();
+    }
+
+    // ベクトルを追加する（同じ ID があれば置き換える）
+    pub fn insert(&mut self, id: &str, mut vector: Vec<f32>) -> Result<()> {
+        if vector.len() != self.dim {
+            bail!("Embedding has {} dimensions (expected {})", vector.len(), self.dim);
+        }
+        normalize(&mut vector);
+        self.remove(id);
+
+        let node = self.nodes.len() as u32;
+        let level = random_level(id);
+        self.nodes.push(Node {
+            id: id.to_string(),
+            vector,
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.ids.insert(id.to_string(), node);
+        self.dirty = true;
+
+        let Some(mut entry) = self.entry else {
+            self.entry = Some(node);
+            self.max_level = level;
+            return Ok(());
+        };
+        let query = self.nodes[node as usize].vector.clone();
+        for l in (level + 1..=self.max_level).rev() {
+            entry = self.greedy(&query, entry, l);
+        }
+        for l in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(&query, entry, EF_CONSTRUCTION, l);
+            let neighbors: Vec<u32> = candidates.iter().take(max_links(l)).map(|c| c.node).collect();
+            for &neighbor in &neighbors {
+                self.nodes[neighbor as usize].links[l].push(node);
+                self.prune(neighbor, l);
+            }
+            self.nodes[node as usize].links[l] = neighbors;
+            entry = candidates[0].node;
+        }
+        if level > self.max_level {
+            self.max_level = level;
+            self.entry = Some(node);
+        }
+        Ok(())
+    }
+
+    // グラフからは外さず削除済みにする（多くなったら保存時に作り直す）
+    pub fn remove(&mut self, id: &str) -> bool {
+        match self.ids.remove(id) {
+            Some(node) => {
+                self.nodes[node as usize].deleted = true;
+                self.deleted += 1;
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 近い順に (ID, コサイン類似度) を返す
+    pub fn search(&self, query: &[f32], limit: usize) -> Vec<(String, f32)> {
+        let Some(mut entry) = self.entry else {
+            return Vec::new();
+        };
+        if query.len() != self.dim || limit == 0 {
+            return Vec::new();
+        }
+        let mut query = query.to_vec();
+        normalize(&mut query);
+        for l in (1..=self.max_level).rev() {
+            entry = self.greedy(&query, entry, l);
+        }
+        // 削除済みのノードを除いても件数が足りるよう多めに探す
+        let ef = (limit + self.deleted.min(limit * 4)).max(EF_SEARCH);
+        self.search_layer(&query, entry, ef, 0)
+            .into_iter()
+            .filter(|c| !self.nodes[c.node as usize].deleted)
+            .take(limit)
+            .map(|c| (self.nodes[c.node as usize].id.clone(), 1.0 - c.distance))
+            .collect()
+    }
+
+    // 削除済みのノードを除いてグラフを作り直す
+    fn compact(&mut self) -> Result<()> {
+        let nodes = std::mem::take(&mut self.nodes);
+        self.ids.clear();
+        self.entry = None;
+        self.max_level = 0;
+        self.deleted = 0;
+        for node in nodes.into_iter().filter(|n| !n.deleted) {
+            self.insert(&node.id, node.vector)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if !self.nodes.is_empty() && self.deleted as f32 / self.nodes.len() as f32 > MAX_DELETED_RATIO {
+            self.compact()?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        {
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            w.write_all(MAGIC)?;
+            write_bytes(&mut w, self.model.as_bytes())?;
+            write_u32(&mut w, self.dim as u32)?;
+            write_u32(&mut w, self.nodes.len() as u32)?;
+            write_u32(&mut w, self.entry.unwrap_or(u32::MAX))?;
+            write_u32(&mut w, self.max_level as u32)?;
+            for node in &self.nodes {
+                write_bytes(&mut w, node.id.as_bytes())?;
+                w.write_all(&[node.deleted as u8])?;
+                for x in &node.vector {
+                    w.write_all(&x.to_le_bytes())?;
+                }
+                write_u32(&mut w, node.links.len() as u32)?;
+                for links in &node.links {
+                    write_u32(&mut w, links.len() as u32)?;
+                    for link in links {
+                        write_u32(&mut w, *link)?;
+                    }
+                }
+            }
+            w.flush()?;
+        }
+        fs::rename(&tmp, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("Not a vector index file");
+        }
+        let model = String::from_utf8(read_bytes(&mut r)?)?;
+        let dim = read_u32(&mut r)? as usize;
+        let count = read_u32(&mut r)? as usize;
+        let entry = Some(read_u32(&mut r)?).filter(|e| *e != u32::MAX);
+        let max_level = read_u32(&mut r)? as usize;
+
+        let mut index = Self::new(&model, dim);
+        index.max_level = max_level;
+        for node in 0..count {
+            let id = String::from_utf8(read_bytes(&mut r)?)?;
+            let mut flag = [0; 1];
+            r.read_exact(&mut flag)?;
+            let mut vector = vec![0f32; dim];
+            for x in vector.iter_mut() {
+                let mut bytes = [0; 4];
+                r.read_exact(&mut bytes)?;
+                *x = f32::from_le_bytes(bytes);
+            }
+            let levels = read_u32(&mut r)? as usize;
+            let mut links = Vec::with_capacity(levels);
+            for _ in 0..levels {
+                let n = read_u32(&mut r)? as usize;
+                let level: Vec<u32> = (0..n).map(|_| read_u32(&mut r)).collect::<Result<_>>()?;
+                if level.iter().any(|&l| l as usize >= count) {
+                    bail!("Corrupted vector index");
+                }
+                links.push(level);
+            }
+            let deleted = flag[0] != 0;
+            if deleted {
+                index.deleted += 1;
+            } else {
+                index.ids.insert(id.clone(), node as u32);
+            }
+            index.nodes.push(Node { id, vector, links, deleted });
+        }
+        index.entry = entry.filter(|e| (*e as usize) < count);
+        Ok(index)
+    }
+}
+
+fn write_u32(w: &mut impl Write, value: u32) -> Result<()> {
+    w.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> Result<()> {
+    write_u32(w, bytes.len() as u32)?;
+    w.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes).context("Unexpected end of vector index")?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_bytes(r: &mut impl Read) -> Result<Vec<u8>> {
+    let len = read_u32(r)? as usize;
+    let mut bytes = vec![0; len];
+    r.read_exact(&mut bytes).context("Unexpected end of vector index")?;
+    Ok(bytes)
+}
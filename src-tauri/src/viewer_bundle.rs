@@ -0,0 +1,148 @@
+use crate::jobs::ProgressReporter;
+use crate::metadata_store::ItemRecord;
+use crate::thumbnail_cache::ThumbnailCache;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+const VIEWER_PAGE: &str = include_str!("viewer_page.html");
+const THUMBNAIL_SIZE: u32 = 320;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewerBundleOptions {
+    #[serde(default = "default_title")]
+    pub title: String,
+    // 画像の長辺の上限（元画像は入れず、縮小した JPEG にする）
+    #[serde(default = "default_image_size")]
+    pub image_size: u32,
+    #[serde(default = "default_true")]
+    pub include_ocr_text: bool,
+    // 地名と緯度経度（共有相手に見せたくないことが多いため既定では入れない）
+    #[serde(default)]
+    pub include_location: bool,
+}
+
+fn default_title() -> String {
+    "Snap Organizer".to_string()
+}
+
+fn default_image_size() -> u32 {
+    1600
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ViewerBundleOptions {
+    fn default() -> Self {
+        ViewerBundleOptions {
+            title: default_title(),
+            image_size: default_image_size(),
+            include_ocr_text: true,
+            include_location: false,
+        }
+    }
+}
+
+// data.js に書き出す1アイテム分
+#[derive(Debug, Serialize)]
+struct ViewerItem {
+    id: String,
+    image: String,
+    thumbnail: String,
+    memo: String,
+    ocr_text: String,
+    tags: Vec<String>,
+    location_name: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ViewerBundleReport {
+    pub items_exported: usize,
+    // 非公開・画像なし・読み込めなかったアイテム
+    pub items_skipped: usize,
+    pub dest: String,
+}
+
+// 共有用の静的なフォルダを作る（index.html をブラウザで開くだけで見られる）
+// fetch は file:// で使えないため、メタデータは data.js としてスクリプトで読み込ませる
+pub fn export_viewer_bundle(
+    items: &[ItemRecord],
+    dest: &Path,
+    options: &ViewerBundleOptions,
+    reporter: &dyn ProgressReporter,
+) -> Result<ViewerBundleReport> {
+    if dest.is_file() {
+        bail!("Not a folder: {}", dest.display());
+    }
+    let images_dir = dest.join("images");
+    let thumbs_dir = dest.join("thumbs");
+    fs::create_dir_all(&images_dir)?;
+    fs::create_dir_all(&thumbs_dir)?;
+    reporter.set_total(items.len() as u64);
+
+    let mut report = ViewerBundleReport {
+        dest: dest.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    let mut exported = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        reporter.checkpoint()?;
+        reporter.progress(i as u64 + 1, &item.id);
+        // 非公開アイテムの画像は暗号化されているため共有しない
+        let image_path = match item.image_path.as_deref() {
+            Some(path) if !item.private => path,
+            _ => {
+                report.items_skipped += 1;
+                continue;
+            }
+        };
+        let rendered = fs::read(image_path).map_err(anyhow::Error::from).and_then(|data| {
+            Ok((
+                ThumbnailCache::render(&data, options.image_size)?,
+                ThumbnailCache::render(&data, THUMBNAIL_SIZE)?,
+            ))
+        });
+        let (image, thumbnail) = match rendered {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                log::warn!("Skipping {} in viewer bundle: {}", item.id, e);
+                report.items_skipped += 1;
+                continue;
+            }
+        };
+        let file_name = format!("{}.jpg", item.id);
+        fs::write(images_dir.join(&file_name), image)?;
+        fs::write(thumbs_dir.join(&file_name), thumbnail)?;
+
+        exported.push(ViewerItem {
+            id: item.id.clone(),
+            image: format!("images/{}", file_name),
+            thumbnail: format!("thumbs/{}", file_name),
+            memo: item.memo.clone(),
+            ocr_text: if options.include_ocr_text { item.ocr_text.clone() } else { String::new() },
+            tags: item.tags.clone(),
+            location_name: item.location_name.clone().filter(|_| options.include_location),
+            latitude: item.latitude.filter(|_| options.include_location),
+            longitude: item.longitude.filter(|_| options.include_location),
+            created_at: item.created_at,
+        });
+    }
+
+    let data = serde_json::json!({
+        "title": options.title,
+        "generated_at": Utc::now(),
+        "items": exported,
+    });
+    fs::write(dest.join("data.js"), format!("window.SNAP_DATA = {};\n", serde_json::to_string(&data)?))?;
+    fs::write(dest.join("index.html"), VIEWER_PAGE)?;
+
+    report.items_exported = exported.len();
+    Ok(report)
+}
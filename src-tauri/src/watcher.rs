@@ -0,0 +1,116 @@
+use crate::paths::{is_image_path, LibraryPaths};
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ファイルサイズがこの時間変化しなければ書き込み完了とみなす
+const SETTLE_DURATION: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub fn load_watch_folders(paths: &LibraryPaths) -> Vec<PathBuf> {
+    fs::read_to_string(paths.watch_config_file())
+        .ok()
+        .and_then(|json| serde_json::from_str::<Vec<PathBuf>>(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_watch_folders(paths: &LibraryPaths, folders: &[PathBuf]) -> Result<()> {
+    fs::create_dir_all(paths.root())?;
+    let json = serde_json::to_string_pretty(folders)?;
+    fs::write(paths.watch_config_file(), json)?;
+    Ok(())
+}
+
+// 指定フォルダを監視し、新しい画像ファイルが書き込み完了したらコールバックを呼ぶ
+pub struct FolderWatcher {
+    _watcher: RecommendedWatcher,
+    folders: Vec<PathBuf>,
+}
+
+impl FolderWatcher {
+    pub fn start<F>(folders: &[PathBuf], on_file: F) -> Result<Self>
+    where
+        F: Fn(PathBuf) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<PathBuf>();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        let _ = tx.send(path);
+                    }
+                }
+            }
+        })?;
+
+        for folder in folders {
+            watcher
+                .watch(folder, RecursiveMode::Recursive)
+                .with_context(|| format!("Failed to watch folder: {}", folder.display()))?;
+        }
+
+        // watcher が破棄されると送信側も破棄され、ワーカースレッドは終了する
+        thread::spawn(move || run_worker(rx, on_file));
+
+        Ok(FolderWatcher {
+            _watcher: watcher,
+            folders: folders.to_vec(),
+        })
+    }
+
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+}
+
+fn run_worker<F: Fn(PathBuf)>(rx: Receiver<PathBuf>, on_file: F) {
+    // 書き込み中のファイル: (直近のサイズ, サイズが変化した時刻)
+    let mut pending: HashMap<PathBuf, (u64, Instant)> = HashMap::new();
+    let mut handled: HashSet<PathBuf> = HashSet::new();
+
+    loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(path) => {
+                if is_image_path(&path) && !handled.contains(&path) {
+                    pending.entry(path).or_insert((0, Instant::now()));
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        let mut ready = Vec::new();
+        pending.retain(|path, (last_size, last_change)| {
+            let size = match file_size(path) {
+                Some(size) => size,
+                None => return false,
+            };
+            if size != *last_size {
+                *last_size = size;
+                *last_change = now;
+                return true;
+            }
+            if now.duration_since(*last_change) >= SETTLE_DURATION {
+                ready.push(path.clone());
+                return false;
+            }
+            true
+        });
+
+        for path in ready {
+            handled.insert(path.clone());
+            on_file(path);
+        }
+    }
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| m.len())
+}
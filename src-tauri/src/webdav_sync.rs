@@ -0,0 +1,430 @@
+use crate::sync::{RemoteObject, SyncBackend};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::blocking::{Body, Client, RequestBuilder, Response};
+use reqwest::{header, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// パスセグメント内でエンコードが必要な文字
+const SEGMENT_ENCODE: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'/').add(b'<').add(b'>')
+    .add(b'?').add(b'[').add(b']').add(b'^').add(b'`').add(b'{').add(b'|').add(b'}');
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+// これより大きいファイルは分割してアップロードする
+const CHUNK_SIZE: u64 = 10 * 1024 * 1024;
+const ORIGINALS_DIR: &str = "originals/";
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop><d:resourcetype/><d:getetag/><d:getcontentlength/><d:getlastmodified/></d:prop>
+</d:propfind>"#;
+
+// Nextcloud / ownCloud / Synology などの WebDAV サーバーの接続設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    // 例: https://cloud.example.com/remote.php/dav/files/alice/
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    // ライブラリを置くフォルダ（url からの相対パス）
+    #[serde(default = "default_folder")]
+    pub folder: String,
+    // Nextcloud の分割アップロード用 URL（例: https://cloud.example.com/remote.php/dav/uploads/alice）
+    // 未設定の場合は大きなファイルもストリーミングで1回の PUT で送る
+    #[serde(default)]
+    pub uploads_url: Option<String>,
+}
+
+fn default_folder() -> String {
+    "SnapOrganizer".to_string()
+}
+
+#[derive(Debug, Default)]
+struct DavEntry {
+    href: String,
+    etag: String,
+    size: u64,
+    modified: Option<DateTime<Utc>>,
+    is_collection: bool,
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT_ENCODE).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn trim_etag(etag: &str) -> String {
+    etag.trim_start_matches("W/").trim_matches('"').to_string()
+}
+
+// フォルダあたりのファイル数を抑えるため、元画像はハッシュ先頭2文字のサブフォルダに分ける
+// originals/<hash>.<ext> → originals/<hash[0..2]>/<hash>.<ext>
+fn to_remote_path(key: &str) -> String {
+    match key.strip_prefix(ORIGINALS_DIR) {
+        Some(name) if name.len() > 2 && name.is_char_boundary(2) && !name.contains('/') => {
+            format!("{}{}/{}", ORIGINALS_DIR, &name[..2], name)
+        }
+        _ => key.to_string(),
+    }
+}
+
+fn to_key(remote_path: &str) -> String {
+    match remote_path.strip_prefix(ORIGINALS_DIR).and_then(|rest| rest.split_once('/')) {
+        Some((_, name)) => format!("{}{}", ORIGINALS_DIR, name),
+        None => remote_path.to_string(),
+    }
+}
+
+// PROPFIND の multistatus 応答を読む（名前空間の接頭辞はサーバーごとに異なるためローカル名で判定）
+fn parse_multistatus(xml: &str) -> Result<Vec<DavEntry>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut entries = Vec::new();
+    let mut current: Option<DavEntry> = None;
+    let mut field: Option<Vec<u8>> = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = e.local_name().as_ref().to_vec();
+                match name.as_slice() {
+                    b"response" => current = Some(DavEntry::default()),
+                    b"collection" => {
+                        if let Some(entry) = current.as_mut() {
+                            entry.is_collection = true;
+                        }
+                    }
+                    _ => field = Some(name),
+                }
+            }
+            Event::Empty(e) => {
+                if e.local_name().as_ref() == b"collection" {
+                    if let Some(entry) = current.as_mut() {
+                        entry.is_collection = true;
+                    }
+                }
+            }
+            Event::Text(text) => {
+                if let (Some(entry), Some(name)) = (current.as_mut(), field.as_deref()) {
+                    let text = text.unescape()?.to_string();
+                    match name {
+                        b"href" => entry.href = text,
+                        b"getetag" => entry.etag = trim_etag(&text),
+                        b"getcontentlength" => entry.size = text.parse().unwrap_or(0),
+                        b"getlastmodified" => {
+                            entry.modified = DateTime::parse_from_rfc2822(&text)
+                                .ok()
+                                .map(|d| d.with_timezone(&Utc))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"response" {
+                    if let Some(entry) = current.take() {
+                        entries.push(entry);
+                    }
+                }
+                field = None;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+pub struct WebDavBackend {
+    config: WebDavConfig,
+    // ライブラリのルートフォルダの URL（末尾スラッシュ付き）
+    root: Url,
+    client: Client,
+    // 作成済み（または存在を確認済み）のフォルダ
+    known_dirs: Mutex<HashSet<String>>,
+}
+
+impl WebDavBackend {
+    pub fn new(config: WebDavConfig) -> Result<Self> {
+        let base = format!("{}/", config.url.trim_end_matches('/'));
+        let folder = config.folder.trim_matches('/');
+        let root = if folder.is_empty() { base } else { format!("{}{}/", base, encode_path(folder)) };
+        let root = Url::parse(&root).context("Invalid WebDAV URL")?;
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(WebDavBackend {
+            config,
+            root,
+            client,
+            known_dirs: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn url(&self, remote_path: &str) -> Result<Url> {
+        Ok(self.root.join(&encode_path(remote_path))?)
+    }
+
+    fn request(&self, method: &str, url: Url) -> RequestBuilder {
+        self.client
+            .request(Method::from_bytes(method.as_bytes()).expect("valid HTTP method"), url)
+            .basic_auth(&self.config.username, Some(&self.config.password))
+    }
+
+    fn check(response: Response, what: &str) -> Result<Response> {
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().unwrap_or_default();
+            bail!("WebDAV {} returned {}: {}", what, status, text.chars().take(500).collect::<String>());
+        }
+        Ok(response)
+    }
+
+    fn propfind(&self, url: Url, depth: &str) -> Result<Option<Vec<DavEntry>>> {
+        let response = self
+            .request("PROPFIND", url.clone())
+            .header("Depth", depth)
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .with_context(|| format!("WebDAV request failed: PROPFIND {}", url))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let xml = Self::check(response, "PROPFIND")?.text()?;
+        Ok(Some(parse_multistatus(&xml)?))
+    }
+
+    // href（エンコード済みの絶対パス）をルートからの相対パスに変換
+    fn relative_path(&self, href: &str) -> Option<String> {
+        let href_path = match Url::parse(href) {
+            Ok(url) => url.path().to_string(),
+            Err(_) => href.to_string(),
+        };
+        let decoded = percent_decode_str(&href_path).decode_utf8_lossy().to_string();
+        let root = percent_decode_str(self.root.path()).decode_utf8_lossy().to_string();
+        decoded.strip_prefix(&root).map(|rest| rest.to_string())
+    }
+
+    // 親フォルダを順に作成する（MKCOL は既存なら 405 を返す）
+    fn ensure_dirs(&self, remote_path: &str) -> Result<()> {
+        let mut dirs = vec![String::new()];
+        let mut current = String::new();
+        if let Some((parent, _)) = remote_path.rsplit_once('/') {
+            for segment in parent.split('/') {
+                current.push_str(segment);
+                current.push('/');
+                dirs.push(current.clone());
+            }
+        }
+
+        for dir in dirs {
+            if self.known_dirs.lock().unwrap().contains(&dir) {
+                continue;
+            }
+            let response = self
+                .request("MKCOL", self.url(&dir)?)
+                .send()
+                .with_context(|| format!("WebDAV request failed: MKCOL {}", dir))?;
+            let status = response.status();
+            if !(status.is_success() || status == StatusCode::METHOD_NOT_ALLOWED) {
+                Self::check(response, "MKCOL")?;
+            }
+            self.known_dirs.lock().unwrap().insert(dir);
+        }
+        Ok(())
+    }
+
+    // PUT の応答に ETag がないサーバーでは改めて取得する
+    fn etag_after_put(&self, response: &Response, url: Url) -> Result<String> {
+        let from_header = ["OC-ETag", "ETag"].iter().find_map(|name| {
+            response
+                .headers()
+                .get(*name)
+                .and_then(|v| v.to_str().ok())
+                .map(trim_etag)
+        });
+        if let Some(etag) = from_header {
+            return Ok(etag);
+        }
+        self.propfind(url.clone(), "0")?
+            .and_then(|entries| entries.into_iter().next())
+            .map(|entry| entry.etag)
+            .filter(|etag| !etag.is_empty())
+            .with_context(|| format!("WebDAV server did not return an ETag for {}", url))
+    }
+
+    // Nextcloud の分割アップロード（チャンクを一時フォルダに送ってから MOVE で結合）
+    fn upload_chunked(&self, uploads_url: &str, dest: Url, source: &Path, size: u64) -> Result<String> {
+        let session = Url::parse(&format!("{}/snap-{}/", uploads_url.trim_end_matches('/'), uuid::Uuid::new_v4()))
+            .context("Invalid WebDAV uploads URL")?;
+        let destination = dest.to_string();
+
+        let response = self
+            .request("MKCOL", session.clone())
+            .header("Destination", &destination)
+            .send()?;
+        Self::check(response, "MKCOL (upload session)")?;
+
+        let result = (|| -> Result<String> {
+            let mut file = File::open(source)?;
+            let mut index = 1;
+            let mut buf = vec![0u8; CHUNK_SIZE as usize];
+            loop {
+                let mut filled = 0;
+                while filled < buf.len() {
+                    let n = file.read(&mut buf[filled..])?;
+                    if n == 0 {
+                        break;
+                    }
+                    filled += n;
+                }
+                if filled == 0 {
+                    break;
+                }
+                let response = self
+                    .request("PUT", session.join(&format!("{:05}", index))?)
+                    .header("Destination", &destination)
+                    .header("OC-Total-Length", size)
+                    .body(buf[..filled].to_vec())
+                    .send()?;
+                Self::check(response, "PUT (chunk)")?;
+                index += 1;
+            }
+
+            let response = self
+                .request("MOVE", session.join(".file")?)
+                .header("Destination", &destination)
+                .header("OC-Total-Length", size)
+                .header("Overwrite", "T")
+                .send()?;
+            let response = Self::check(response, "MOVE (assemble chunks)")?;
+            self.etag_after_put(&response, dest.clone())
+        })();
+
+        if result.is_err() {
+            let _ = self.request("DELETE", session).send();
+        }
+        result
+    }
+}
+
+impl SyncBackend for WebDavBackend {
+    fn remote_id(&self) -> String {
+        format!("webdav:{}", self.root)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        let mut objects = Vec::new();
+        // Depth: infinity は無効にされていることが多いため1階層ずつたどる
+        let mut pending = vec![to_remote_path(prefix)];
+        while let Some(dir) = pending.pop() {
+            let entries = match self.propfind(self.url(&dir)?, "1")? {
+                Some(entries) => entries,
+                None => continue,
+            };
+            self.known_dirs.lock().unwrap().insert(dir.clone());
+
+            for entry in entries {
+                let path = match self.relative_path(&entry.href) {
+                    Some(path) => path,
+                    None => continue,
+                };
+                if path.trim_end_matches('/') == dir.trim_end_matches('/') {
+                    continue;
+                }
+                if entry.is_collection {
+                    let child = format!("{}/", path.trim_end_matches('/'));
+                    pending.push(child);
+                } else if !path.ends_with(".partial") {
+                    objects.push(RemoteObject {
+                        key: to_key(&path),
+                        etag: entry.etag,
+                        size: entry.size,
+                        modified: entry.modified,
+                    });
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let response = self
+            .request("GET", self.url(&to_remote_path(key))?)
+            .send()
+            .with_context(|| format!("WebDAV request failed: GET {}", key))?;
+        Ok(Self::check(response, "GET")?.bytes()?.to_vec())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<String> {
+        let remote_path = to_remote_path(key);
+        self.ensure_dirs(&remote_path)?;
+        let url = self.url(&remote_path)?;
+        let response = self
+            .request("PUT", url.clone())
+            .body(data.to_vec())
+            .send()
+            .with_context(|| format!("WebDAV request failed: PUT {}", key))?;
+        let response = Self::check(response, "PUT")?;
+        self.etag_after_put(&response, url)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let response = self
+            .request("DELETE", self.url(&to_remote_path(key))?)
+            .send()
+            .with_context(|| format!("WebDAV request failed: DELETE {}", key))?;
+        if response.status() != StatusCode::NOT_FOUND {
+            Self::check(response, "DELETE")?;
+        }
+        Ok(())
+    }
+
+    // 大きなファイルはメモリに読み込まずに送る
+    fn upload(&self, key: &str, source: &Path) -> Result<String> {
+        let remote_path = to_remote_path(key);
+        self.ensure_dirs(&remote_path)?;
+        let url = self.url(&remote_path)?;
+        let size = fs::metadata(source)?.len();
+
+        if let Some(uploads_url) = self.config.uploads_url.as_deref().filter(|_| size > CHUNK_SIZE) {
+            return self.upload_chunked(uploads_url, url, source, size);
+        }
+
+        let file = File::open(source).with_context(|| format!("Failed to read {}", source.display()))?;
+        let response = self
+            .request("PUT", url.clone())
+            .body(Body::sized(file, size))
+            .send()
+            .with_context(|| format!("WebDAV request failed: PUT {}", key))?;
+        let response = Self::check(response, "PUT")?;
+        self.etag_after_put(&response, url)
+    }
+
+    fn download(&self, key: &str, dest: &Path) -> Result<()> {
+        let response = self
+            .request("GET", self.url(&to_remote_path(key))?)
+            .send()
+            .with_context(|| format!("WebDAV request failed: GET {}", key))?;
+        let mut response = Self::check(response, "GET")?;
+
+        let tmp = dest.with_extension("partial");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        response.copy_to(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp, dest)?;
+        Ok(())
+    }
+}
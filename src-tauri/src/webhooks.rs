@@ -0,0 +1,279 @@
+use crate::secrets;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+// 失敗したときに待つ時間（この回数だけ送り直す）
+const RETRY_DELAYS: [Duration; 3] = [Duration::from_secs(5), Duration::from_secs(30), Duration::from_secs(120)];
+// 送信待ちと送り直し待ちの上限（受け取る側が止まっている間に溜まり続けないように）
+const QUEUE_CAPACITY: usize = 256;
+const MAX_PENDING_RETRIES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    ItemImported,
+    TagAdded,
+    BackupCompleted,
+}
+
+impl WebhookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::ItemImported => "item_imported",
+            WebhookEvent::TagAdded => "tag_added",
+            WebhookEvent::BackupCompleted => "backup_completed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    // 送るイベント（空ならすべて）
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    // 指定すると本文の HMAC-SHA256 を X-Snap-Signature ヘッダーに付ける
+    // （OS のキーチェーンに保存し、webhooks.json には書かない）
+    #[serde(default)]
+    pub secret: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Webhook {
+    // キーチェーンの項目名
+    fn keychain_secret(&self) -> String {
+        format!("webhook-secret-{}", self.id)
+    }
+
+    fn accepts(&self, event: WebhookEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+// 送る内容（受け取る側で event を見て振り分ける）
+#[derive(Debug, Clone, Serialize)]
+struct Payload {
+    event: &'static str,
+    occurred_at: chrono::DateTime<Utc>,
+    data: serde_json::Value,
+}
+
+struct Delivery {
+    webhook: Webhook,
+    body: String,
+    // 送り直した回数
+    attempt: usize,
+}
+
+// ライブラリのイベントを登録された URL へ JSON で POST する（webhooks.json に保存）
+// 送信はバックグラウンドのスレッドで順に行い、取り込みなどの処理を待たせない
+pub struct WebhookService {
+    path: PathBuf,
+    webhooks: Mutex<Vec<Webhook>>,
+    queue: SyncSender<Delivery>,
+}
+
+impl WebhookService {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        let (queue, deliveries) = mpsc::sync_channel::<Delivery>(QUEUE_CAPACITY);
+        std::thread::spawn(move || run_deliveries(&client, deliveries));
+        Ok(WebhookService {
+            webhooks: Mutex::new(Self::load(&path)),
+            path,
+            queue,
+        })
+    }
+
+    fn load(path: &Path) -> Vec<Webhook> {
+        let mut webhooks: Vec<Webhook> = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        for webhook in &mut webhooks {
+            let name = webhook.keychain_secret();
+            secrets::fill(path, &name, &mut webhook.secret);
+        }
+        webhooks
+    }
+
+    fn save(&self, webhooks: &[Webhook]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut webhooks = webhooks.to_vec();
+        for webhook in &mut webhooks {
+            let name = webhook.keychain_secret();
+            secrets::stash(&self.path, &name, &mut webhook.secret);
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&webhooks)?)?;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        self.webhooks.lock().unwrap().clone()
+    }
+
+    // ID が空なら新しい Webhook として追加し、あれば置き換える
+    pub fn save_webhook(&self, mut webhook: Webhook) -> Result<Webhook> {
+        let url = reqwest::Url::parse(webhook.url.trim()).context("Invalid webhook URL")?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Webhook URL must be http or https");
+        }
+        webhook.url = url.to_string();
+        if webhook.id.is_empty() {
+            webhook.id = uuid::Uuid::new_v4().to_string();
+        }
+        let mut webhooks = self.webhooks.lock().unwrap();
+        match webhooks.iter_mut().find(|w| w.id == webhook.id) {
+            Some(existing) => *existing = webhook.clone(),
+            None => webhooks.push(webhook.clone()),
+        }
+        self.save(&webhooks)?;
+        Ok(webhook)
+    }
+
+    pub fn delete_webhook(&self, id: &str) -> Result<bool> {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let Some(index) = webhooks.iter().position(|w| w.id == id) else {
+            return Ok(false);
+        };
+        let removed = webhooks.remove(index);
+        self.save(&webhooks)?;
+        if let Err(e) = secrets::set(&self.path, &removed.keychain_secret(), "") {
+            log::warn!("Failed to remove webhook secret from OS keychain: {}", e);
+        }
+        Ok(true)
+    }
+
+    // イベントを受け取る Webhook すべてへの送信を予約する
+    pub fn notify(&self, event: WebhookEvent, data: serde_json::Value) {
+        let targets: Vec<Webhook> = self
+            .webhooks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|w| w.accepts(event))
+            .cloned()
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        let payload = Payload {
+            event: event.name(),
+            occurred_at: Utc::now(),
+            data,
+        };
+        let body = match serde_json::to_string(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                log::warn!("Failed to serialize webhook payload: {}", e);
+                return;
+            }
+        };
+        for webhook in targets {
+            let delivery = Delivery {
+                webhook,
+                body: body.clone(),
+                attempt: 0,
+            };
+            if let Err(TrySendError::Full(delivery)) = self.queue.try_send(delivery) {
+                log::warn!("Webhook queue is full, dropping {} for {}", payload.event, delivery.webhook.name);
+            }
+        }
+    }
+
+    // 設定画面の「テスト送信」（すぐに送り、HTTP ステータスを返す）
+    pub fn test(&self, id: &str) -> Result<u16> {
+        let webhook = self
+            .list()
+            .into_iter()
+            .find(|w| w.id == id)
+            .with_context(|| format!("Webhook not found: {}", id))?;
+        let payload = serde_json::json!({
+            "event": "ping",
+            "occurred_at": Utc::now(),
+            "data": {},
+        });
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        send(&client, &webhook, &payload.to_string())
+    }
+}
+
+fn signature(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn send(client: &Client, webhook: &Webhook, body: &str) -> Result<u16> {
+    let mut request = client
+        .post(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header("User-Agent", concat!("SnapOrganizer/", env!("CARGO_PKG_VERSION")));
+    if !webhook.secret.is_empty() {
+        request = request.header("X-Snap-Signature", signature(&webhook.secret, body));
+    }
+    let status = request.body(body.to_string()).send()?.status();
+    if !status.is_success() {
+        bail!("Webhook {} returned {}", webhook.name, status);
+    }
+    Ok(status.as_u16())
+}
+
+// 新しい送信を受け付けながら、失敗したものは時刻が来たら送り直す（待っている間も他の送信を止めない）
+fn run_deliveries(client: &Client, deliveries: Receiver<Delivery>) {
+    // 送り直し待ち（送る時刻の順）
+    let mut retries: Vec<(Instant, Delivery)> = Vec::new();
+    loop {
+        let received = match retries.first() {
+            Some((due, _)) => deliveries.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => deliveries.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(delivery) => deliver(client, delivery, &mut retries),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        let now = Instant::now();
+        while retries.first().is_some_and(|(due, _)| *due <= now) {
+            let (_, delivery) = retries.remove(0);
+            deliver(client, delivery, &mut retries);
+        }
+    }
+}
+
+// 一時的な失敗（受け取る側の停止・ネットワーク切断）に備えて間隔を空けて送り直す
+fn deliver(client: &Client, mut delivery: Delivery, retries: &mut Vec<(Instant, Delivery)>) {
+    let Err(e) = send(client, &delivery.webhook, &delivery.body) else {
+        return;
+    };
+    match RETRY_DELAYS.get(delivery.attempt) {
+        Some(delay) if retries.len() < MAX_PENDING_RETRIES => {
+            log::info!("Retrying webhook {} in {:?}: {}", delivery.webhook.name, delay, e);
+            delivery.attempt += 1;
+            let due = Instant::now() + *delay;
+            let at = retries.partition_point(|(d, _)| *d <= due);
+            retries.insert(at, (due, delivery));
+        }
+        _ => log::warn!("Giving up webhook {}: {}", delivery.webhook.name, e),
+    }
+}
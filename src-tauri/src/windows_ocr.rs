@@ -0,0 +1,87 @@
+use crate::ocr::{build_result, OcrResult, OcrWord};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use windows::core::HSTRING;
+use windows::Globalization::Language;
+use windows::Graphics::Imaging::BitmapDecoder;
+use windows::Media::Ocr::OcrEngine;
+use windows::Storage::{FileAccessMode, StorageFile};
+
+// Windows OCR は単語ごとの信頼度を返さないため固定値を使う
+const WINDOWS_OCR_CONFIDENCE: f32 = 90.0;
+
+// Tesseract の言語コードを BCP-47 の言語タグに変換
+fn language_tag(code: &str) -> &str {
+    match code {
+        "jpn" | "jpn_vert" => "ja",
+        "eng" => "en-US",
+        "chi_sim" => "zh-Hans",
+        "chi_tra" => "zh-Hant",
+        "kor" => "ko",
+        "deu" => "de-DE",
+        "fra" => "fr-FR",
+        other => other,
+    }
+}
+
+pub fn available_languages() -> Vec<String> {
+    OcrEngine::AvailableRecognizerLanguages()
+        .map(|languages| {
+            languages
+                .into_iter()
+                .filter_map(|l| l.LanguageTag().ok())
+                .map(|tag| tag.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn create_engine(languages: &[String]) -> Result<(OcrEngine, String)> {
+    // Windows OCR は1言語ずつしか認識できないため、使える最初の言語を選ぶ
+    for code in languages {
+        let language = Language::CreateLanguage(&HSTRING::from(language_tag(code)))?;
+        if OcrEngine::IsLanguageSupported(&language)? {
+            return Ok((OcrEngine::TryCreateFromLanguage(&language)?, code.clone()));
+        }
+    }
+    bail!("Windows OCR language pack not installed for: {}", languages.join(", "))
+}
+
+pub fn recognize_file(image_path: &Path, languages: &[String]) -> Result<OcrResult> {
+    let (engine, language) = create_engine(languages)?;
+
+    // StorageFile は絶対パスしか受け付けない
+    let absolute = if image_path.is_absolute() {
+        image_path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(image_path)
+    };
+
+    let file = StorageFile::GetFileFromPathAsync(&HSTRING::from(absolute.as_os_str()))?
+        .get()
+        .with_context(|| format!("Failed to open image: {}", image_path.display()))?;
+    let stream = file.OpenAsync(FileAccessMode::Read)?.get()?;
+    let decoder = BitmapDecoder::CreateAsync(&stream)?.get()?;
+    let bitmap = decoder.GetSoftwareBitmapAsync()?.get()?;
+
+    let result = engine.RecognizeAsync(&bitmap)?.get()?;
+
+    let mut words = Vec::new();
+    for (line_index, line) in result.Lines()?.into_iter().enumerate() {
+        for word in line.Words()? {
+            let rect = word.BoundingRect()?;
+            words.push(OcrWord {
+                text: word.Text()?.to_string(),
+                left: rect.X.max(0.0) as u32,
+                top: rect.Y.max(0.0) as u32,
+                width: rect.Width.max(0.0) as u32,
+                height: rect.Height.max(0.0) as u32,
+                confidence: WINDOWS_OCR_CONFIDENCE,
+                block: 0,
+                line: line_index as u32,
+            });
+        }
+    }
+
+    Ok(build_result(words, vec![language], "windows"))
+}
@@ -4,6 +4,7 @@ import { TagChip } from '../TagChip';
 import { ArrowLeft, Edit3, Check, X, Trash2, Calendar, FileText, StickyNote, Mic, MicOff, Share2, RotateCw, RotateCcw, MapPin, Navigation } from 'lucide-react';
 import { loadImageBlob, saveImageBlob } from '../../utils/imageDB';
 import { shareItem } from '../../utils/share';
+import { adjustImage, DEFAULT_ADJUSTMENTS, hasAdjustments, ImageAdjustments } from '../../utils/imageResize';
 import { LocationMap } from '../LocationMap';
 import LocationEditorModal from '../LocationEditorModal';
 import NavigationModal from '../NavigationModal';
@@ -21,6 +22,14 @@ const getTagColor = (tagName: string, availableTags: Tag[]) => {
   return found ? found.color : '#ccc';
 };
 
+// 補正スライダーの定義（値の範囲は WASM の adjust_image に合わせる）
+const ADJUSTMENT_SLIDERS: { key: keyof ImageAdjustments; label: string; min: number; max: number; step: number }[] = [
+  { key: 'brightness', label: '明るさ', min: -1, max: 1, step: 0.05 },
+  { key: 'contrast', label: 'コントラスト', min: -1, max: 1, step: 0.05 },
+  { key: 'gamma', label: 'ガンマ', min: 0.2, max: 3, step: 0.05 },
+  { key: 'saturation', label: '彩度', min: -1, max: 1, step: 0.05 },
+];
+
 // Utility function
 const blobToBase64 = (blob: Blob): Promise<string> => {
   return new Promise((resolve, reject) => {
@@ -49,6 +58,8 @@ export const DetailScreen: React.FC<DetailScreenProps> = ({
   const [isNavigationModalOpen, setIsNavigationModalOpen] = useState(false);
   const [imageUrl, setImageUrl] = useState<string>('');
   const [rotation, setRotation] = useState<number>(0);
+  const [adjustments, setAdjustments] = useState<ImageAdjustments>(DEFAULT_ADJUSTMENTS);
+  const [adjustedUrl, setAdjustedUrl] = useState<string>('');
   const [speechLang, setSpeechLang] = useState('ja-JP');
 
   useEffect(() => {
@@ -59,6 +70,29 @@ export const DetailScreen: React.FC<DetailScreenProps> = ({
     }
   }, [item.image]);
 
+  // スライダーを動かしたら、保存時と同じ処理で補正したプレビューを作る
+  useEffect(() => {
+    if (!isEditing || !item.image || !hasAdjustments(adjustments)) {
+      setAdjustedUrl('');
+      return;
+    }
+    let cancelled = false;
+    const timer = setTimeout(async () => {
+      try {
+        const blob = await loadImageBlob(item.image);
+        if (!blob || cancelled) return;
+        const adjusted = await adjustImage(await blobToBase64(blob), adjustments);
+        if (!cancelled) setAdjustedUrl(adjusted);
+      } catch (error) {
+        console.error('画像の補正に失敗しました:', error);
+      }
+    }, 200);
+    return () => {
+      cancelled = true;
+      clearTimeout(timer);
+    };
+  }, [isEditing, item.image, adjustments]);
+
   const formatDate = (date: Date) => {
     return new Intl.DateTimeFormat('ja-JP', {
       year: 'numeric',
@@ -119,20 +153,29 @@ export const DetailScreen: React.FC<DetailScreenProps> = ({
     });
   };
 
-  // 保存時に回転を反映
+  // 保存時に補正と回転を反映
   const handleSave = async () => {
     let base64 = '';
     if (item.image) {
       const blob = await loadImageBlob(item.image);
       base64 = await blobToBase64(blob!);
-      if (rotation && rotation % 360 !== 0) {
-        base64 = await applyRotationToBase64(base64, rotation);
+      const rotated = rotation && rotation % 360 !== 0;
+      if (hasAdjustments(adjustments) || rotated) {
+        if (hasAdjustments(adjustments)) {
+          base64 = await adjustImage(base64, adjustments);
+        }
+        if (rotated) {
+          base64 = await applyRotationToBase64(base64, rotation);
+        }
         // 保存用blob生成
         const response = await fetch(base64);
-        const rotatedBlob = await response.blob();
-        await loadImageBlob(item.image).then(() => saveImageBlob(item.id, rotatedBlob));
+        const editedBlob = await response.blob();
+        await saveImageBlob(item.id, editedBlob);
+        setImageUrl(URL.createObjectURL(editedBlob));
       }
     }
+    setAdjustments(DEFAULT_ADJUSTMENTS);
+    setRotation(0);
     
     // メタデータを更新
     const updatedMetadata = {
@@ -154,6 +197,8 @@ export const DetailScreen: React.FC<DetailScreenProps> = ({
     setEditedMemo(item.memo);
     setEditedTags(item.tags);
     setEditedLocation(item.metadata?.location || null);
+    setAdjustments(DEFAULT_ADJUSTMENTS);
+    setRotation(0);
     setIsEditing(false);
   };
 
@@ -298,7 +343,7 @@ export const DetailScreen: React.FC<DetailScreenProps> = ({
           {imageUrl && (
             <div className="mb-4 relative">
               <img
-                src={imageUrl}
+                src={adjustedUrl || imageUrl}
                 alt="Postal Item"
                 className="rounded-lg w-full h-auto object-contain transition-transform duration-300"
                 style={{ transform: `rotate(${rotation}deg)` }}
@@ -319,6 +364,36 @@ export const DetailScreen: React.FC<DetailScreenProps> = ({
                   </button>
                 </div>
               )}
+              {isEditing && (
+                <div className="bg-white p-4 rounded-lg shadow-sm mt-2 space-y-2">
+                  {ADJUSTMENT_SLIDERS.map(slider => (
+                    <label key={slider.key} className="flex items-center gap-3 text-sm text-gray-700">
+                      <span className="w-24 shrink-0">{slider.label}</span>
+                      <input
+                        type="range"
+                        min={slider.min}
+                        max={slider.max}
+                        step={slider.step}
+                        value={adjustments[slider.key]}
+                        onChange={e => {
+                          const value = parseFloat(e.target.value);
+                          setAdjustments(prev => ({ ...prev, [slider.key]: value }));
+                        }}
+                        className="flex-1"
+                      />
+                      <span className="w-10 text-right tabular-nums">{adjustments[slider.key].toFixed(2)}</span>
+                    </label>
+                  ))}
+                  {hasAdjustments(adjustments) && (
+                    <button
+                      onClick={() => setAdjustments(DEFAULT_ADJUSTMENTS)}
+                      className="text-sm text-blue-600 hover:underline"
+                    >
+                      補正をリセット
+                    </button>
+                  )}
+                </div>
+              )}
             </div>
           )}
 
@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback } from 'react';
 import { PhotoItem, PostalItemGroup } from '../types';
+import { errorMessage } from '../utils/appError';
 
 // Tauri APIの条件付きインポート
 let invoke: any = null;
@@ -153,7 +154,7 @@ export const useSearch = () => {
       setIsInitialized(true);
       setError(null);
     } catch (err) {
-      setError(`検索エンジンの初期化に失敗しました: ${err}`);
+      setError(`検索エンジンの初期化に失敗しました: ${errorMessage(err)}`);
       console.error('Search engine initialization failed:', err);
     }
   }, [useTauri]);
@@ -287,7 +288,7 @@ export const useSearch = () => {
       });
 
     } catch (err) {
-      setError(`検索に失敗しました: ${err}`);
+      setError(`検索に失敗しました: ${errorMessage(err)}`);
       console.error('Search failed:', err);
     } finally {
       setIsSearching(false);
@@ -0,0 +1,17 @@
+// Rust 側のコマンドが返すエラー（src-tauri/src/error.rs の AppError）
+export interface AppError {
+  code: string;
+  message: string;
+  detail: string;
+  retryable: boolean;
+  reason?: Record<string, unknown>;
+}
+
+export function isAppError(err: unknown): err is AppError {
+  return typeof err === 'object' && err !== null && 'code' in err && 'message' in err;
+}
+
+// 画面に出す文言（AppError 以外はそのまま文字列にする）
+export function errorMessage(err: unknown): string {
+  return isAppError(err) ? err.message : String(err);
+}
@@ -2,6 +2,7 @@
 let wasmApi: {
   batch_resize_images: (images: any, qualities: any, max_width: number, max_height: number) => any;
   preprocess_image_for_ocr: (base64Image: string) => string;
+  adjust_image: (base64Image: string, brightness: number, contrast: number, gamma: number, saturation: number, quality: number) => string;
   // 他のWASM関数もここに追加できます
 } | null = null;
 let wasmInitPromise: Promise<void> | null = null;
@@ -29,6 +30,7 @@ async function initializeWasm() {
       wasmApi = {
         batch_resize_images: wasm.batch_resize_images,
         preprocess_image_for_ocr: wasm.preprocess_image_for_ocr,
+        adjust_image: wasm.adjust_image,
       };
 
       console.log('WASM module initialized successfully.');
@@ -214,6 +216,35 @@ export async function preprocessImageForOcr(base64Image: string): Promise<string
   );
 }
 
+// 編集画面のスライダーの値（brightness, contrast, saturation: -1〜1、gamma: 1で変化なし）
+export interface ImageAdjustments {
+  brightness: number;
+  contrast: number;
+  gamma: number;
+  saturation: number;
+}
+
+export const DEFAULT_ADJUSTMENTS: ImageAdjustments = { brightness: 0, contrast: 0, gamma: 1, saturation: 0 };
+
+export const hasAdjustments = (adjustments: ImageAdjustments): boolean =>
+  adjustments.brightness !== 0 ||
+  adjustments.contrast !== 0 ||
+  adjustments.gamma !== 1 ||
+  adjustments.saturation !== 0;
+
+// プレビューと保存で同じ WASM の処理を通す（見た目と保存結果を一致させるため Canvas にはフォールバックしない）
+export async function adjustImage(
+  base64Image: string,
+  adjustments: ImageAdjustments,
+  quality: number = 0.92
+): Promise<string> {
+  const validatedImage = validateImage(base64Image);
+  await initializeWasm();
+  if (!wasmApi?.adjust_image) throw new Error("WASM function not available");
+  const { brightness, contrast, gamma, saturation } = adjustments;
+  return wasmApi.adjust_image(validatedImage, brightness, contrast, gamma, saturation, quality);
+}
+
 export const adjustImageOrientation = (file: File): Promise<string> => {
   return new Promise((resolve, reject) => {
     const reader = new FileReader();
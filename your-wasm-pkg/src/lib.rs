@@ -3,10 +3,12 @@ use image::{ImageBuffer, GenericImageView, DynamicImage};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use std::io::Cursor;
 use web_sys::console;
-use js_sys::{Array, Uint8Array};
+use js_sys::{Array, Object, Reflect, Uint8Array};
 use exif::{Reader, Tag, In};
 
 const MAX_IMAGE_SIZE: u32 = 4096;
+// タイル分割時のデフォルト重なり幅（境界で文字が切れないように）
+const DEFAULT_TILE_OVERLAP: u32 = 128;
 
 #[wasm_bindgen]
 pub fn batch_resize_images(
@@ -83,6 +85,69 @@ fn scale_dimensions(width: u32, height: u32, max_size: u32) -> (u32, u32) {
     }
 }
 
+// OCR向けの前処理（グレースケール化・コントラスト調整・シャープネス強調）
+fn apply_ocr_preprocessing(img: DynamicImage) -> DynamicImage {
+    log("Converting to grayscale");
+    let mut processed = DynamicImage::ImageLuma8(img.to_luma8());
+
+    // コントラスト自動調整
+    log("Adjusting contrast");
+    processed = DynamicImage::ImageLuma8(
+        ImageBuffer::from_fn(processed.width(), processed.height(), |x, y| {
+            let pixel = processed.get_pixel(x, y).0[0] as f32;
+            let normalized = (pixel - 128.0) * 1.5 + 128.0;
+            let clamped = normalized.clamp(0.0, 255.0) as u8;
+            image::Luma([clamped])
+        })
+    );
+
+    // シャープネス強調
+    log("Applying sharpening");
+    let kernel = [
+        -1.0, -1.0, -1.0,
+        -1.0,  9.0, -1.0,
+        -1.0, -1.0, -1.0,
+    ];
+    processed.filter3x3(&kernel)
+}
+
+// data URL形式のBase64文字列を画像にデコード
+fn decode_data_url(base64_image: &str) -> Result<DynamicImage, JsValue> {
+    let base64_data = match base64_image.split(",").nth(1) {
+        Some(data) => data,
+        None => {
+            log("Invalid base64 format: missing comma separator");
+            return Err(JsValue::from_str("Invalid base64 format: missing comma separator"));
+        }
+    };
+
+    let image_data = BASE64.decode(base64_data).map_err(|e| {
+        log(&format!("Base64 decode error: {}", e));
+        JsValue::from_str(&format!("Base64 decode error: {}", e))
+    })?;
+
+    image::load_from_memory(&image_data).map_err(|e| {
+        log(&format!("Image decode error: {}", e));
+        JsValue::from_str(&format!("Image decode error: {}", e))
+    })
+}
+
+// 画像をJPEGのdata URLにエンコード
+fn encode_jpeg_data_url(img: &DynamicImage, quality: u8) -> Result<String, JsValue> {
+    // JPEGはアルファを持てないのでRGB(またはグレースケール)に揃える
+    let img = match img {
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageRgb8(_) => img.clone(),
+        _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+    };
+    let mut jpeg_data = Vec::new();
+    let mut cursor = Cursor::new(&mut jpeg_data);
+    if let Err(e) = img.write_to(&mut cursor, image::ImageOutputFormat::Jpeg(quality.clamp(1, 100))) {
+        log(&format!("JPEG encode error: {}", e));
+        return Err(JsValue::from_str(&format!("JPEG encode error: {}", e)));
+    }
+    Ok(format!("data:image/jpeg;base64,{}", BASE64.encode(&jpeg_data)))
+}
+
 #[wasm_bindgen]
 pub fn resize_image(
     base64_image: &str,
@@ -142,7 +207,7 @@ pub fn resize_image(
     // JPEG形式でエンコード
     let mut jpeg_data = Vec::new();
     let mut cursor = Cursor::new(&mut jpeg_data);
-    if let Err(e) = resized.write_to(&mut cursor, image::ImageOutputFormat::Jpeg((quality as u8).max(1).min(100))) {
+    if let Err(e) = resized.write_to(&mut cursor, image::ImageOutputFormat::Jpeg((quality as u8).clamp(1, 100))) {
         log(&format!("JPEG encode error: {}", e));
         return Err(JsValue::from_str(&format!("JPEG encode error: {}", e)));
     }
@@ -203,28 +268,7 @@ pub fn preprocess_image_for_ocr(base64_image: &str) -> Result<String, JsValue> {
     };
 
     // OCR前処理
-    log("Converting to grayscale");
-    let mut processed = DynamicImage::ImageLuma8(img.to_luma8());
-
-    // コントラスト自動調整
-    log("Adjusting contrast");
-    processed = DynamicImage::ImageLuma8(
-        ImageBuffer::from_fn(processed.width(), processed.height(), |x, y| {
-            let pixel = processed.get_pixel(x, y).0[0] as f32;
-            let normalized = (pixel - 128.0) * 1.5 + 128.0;
-            let clamped = normalized.max(0.0).min(255.0) as u8;
-            image::Luma([clamped])
-        })
-    );
-
-    // シャープネス強調
-    log("Applying sharpening");
-    let kernel = [
-        -1.0, -1.0, -1.0,
-        -1.0,  9.0, -1.0,
-        -1.0, -1.0, -1.0,
-    ];
-    let processed = processed.filter3x3(&kernel);
+    let processed = apply_ocr_preprocessing(img);
 
     // JPEG形式でエンコード（高品質）
     log("Encoding to JPEG");
@@ -245,3 +289,350 @@ pub fn preprocess_image_for_ocr(base64_image: &str) -> Result<String, JsValue> {
     Ok(base64_output)
 }
 
+
+// 画像がタイル処理を必要とするサイズかどうか
+#[wasm_bindgen]
+pub fn image_requires_tiling(width: u32, height: u32) -> bool {
+    width > MAX_IMAGE_SIZE || height > MAX_IMAGE_SIZE
+}
+
+// 重なりを持つタイル矩形を計算（x, y, width, height）
+fn compute_tiles(width: u32, height: u32, tile_size: u32, overlap: u32) -> Vec<(u32, u32, u32, u32)> {
+    let tile_size = tile_size.max(1);
+    let stride = tile_size.saturating_sub(overlap).max(1);
+
+    let axis = |length: u32| -> Vec<u32> {
+        let mut starts = vec![0];
+        while starts[starts.len() - 1] + tile_size < length {
+            starts.push(starts[starts.len() - 1] + stride);
+        }
+        starts
+    };
+
+    let mut tiles = Vec::new();
+    for y in axis(height) {
+        for x in axis(width) {
+            tiles.push((x, y, tile_size.min(width - x), tile_size.min(height - y)));
+        }
+    }
+    tiles
+}
+
+fn set_field(obj: &Object, key: &str, value: &JsValue) -> Result<(), JsValue> {
+    Reflect::set(obj, &JsValue::from_str(key), value).map(|_| ())
+}
+
+fn get_number(obj: &JsValue, key: &str) -> f64 {
+    Reflect::get(obj, &JsValue::from_str(key))
+        .ok()
+        .and_then(|v| v.as_f64())
+        .unwrap_or(0.0)
+}
+
+// 巨大なパノラマ画像を縮小せずに重なり付きタイルへ分割し、各タイルにOCR前処理を施す
+// 戻り値: { index, x, y, width, height, data } の配列（data は JPEG の data URL）
+#[wasm_bindgen]
+pub fn preprocess_image_tiles(base64_image: &str, tile_size: u32, overlap: u32) -> Result<Array, JsValue> {
+    console_error_panic_hook::set_once();
+    log(&format!("Starting preprocess_image_tiles with tile_size={}, overlap={}", tile_size, overlap));
+
+    let img = decode_data_url(base64_image)?;
+    let (width, height) = img.dimensions();
+    log(&format!("Original image dimensions: {}x{}", width, height));
+
+    let tile_size = if tile_size == 0 { MAX_IMAGE_SIZE } else { tile_size.min(MAX_IMAGE_SIZE) };
+    let overlap = if overlap >= tile_size { DEFAULT_TILE_OVERLAP.min(tile_size / 2) } else { overlap };
+
+    let tiles = compute_tiles(width, height, tile_size, overlap);
+    log(&format!("Splitting into {} tiles", tiles.len()));
+
+    let result = Array::new();
+    for (index, (x, y, tile_width, tile_height)) in tiles.into_iter().enumerate() {
+        let tile = img.crop_imm(x, y, tile_width, tile_height);
+        let processed = apply_ocr_preprocessing(tile);
+        let data = encode_jpeg_data_url(&processed, 95)?;
+
+        let obj = Object::new();
+        set_field(&obj, "index", &JsValue::from(index as u32))?;
+        set_field(&obj, "x", &JsValue::from(x))?;
+        set_field(&obj, "y", &JsValue::from(y))?;
+        set_field(&obj, "width", &JsValue::from(tile_width))?;
+        set_field(&obj, "height", &JsValue::from(tile_height))?;
+        set_field(&obj, "data", &JsValue::from_str(&data))?;
+        result.push(&obj);
+    }
+
+    log("Tile preprocessing completed successfully");
+    Ok(result)
+}
+
+struct TileWord {
+    text: String,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl TileWord {
+    fn overlap_ratio(&self, other: &TileWord) -> f64 {
+        let ix = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
+        let iy = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
+        if ix <= 0.0 || iy <= 0.0 {
+            return 0.0;
+        }
+        let smaller = (self.width * self.height).min(other.width * other.height);
+        if smaller <= 0.0 { 0.0 } else { ix * iy / smaller }
+    }
+}
+
+// タイルごとのOCR結果を元画像の座標系に戻して結合する
+// 入力: { x, y, words: [{ text, x, y, width, height }] } の配列（words の座標はタイル内座標）
+// 重なり領域で重複した単語を除去し、行ごとに左から右へ並べたテキストを返す
+#[wasm_bindgen]
+pub fn merge_tile_ocr_results(tiles: Array) -> String {
+    let mut words: Vec<TileWord> = Vec::new();
+
+    for tile in tiles.iter() {
+        let offset_x = get_number(&tile, "x");
+        let offset_y = get_number(&tile, "y");
+        let tile_words = match Reflect::get(&tile, &JsValue::from_str("words")) {
+            Ok(value) if Array::is_array(&value) => Array::from(&value),
+            _ => continue,
+        };
+
+        for word in tile_words.iter() {
+            let text = Reflect::get(&word, &JsValue::from_str("text"))
+                .ok()
+                .and_then(|v| v.as_string())
+                .unwrap_or_default();
+            if text.trim().is_empty() {
+                continue;
+            }
+            let candidate = TileWord {
+                text,
+                x: get_number(&word, "x") + offset_x,
+                y: get_number(&word, "y") + offset_y,
+                width: get_number(&word, "width"),
+                height: get_number(&word, "height"),
+            };
+            // 重なり領域で同じ単語が二重に認識されたものを除外
+            let duplicated = words
+                .iter()
+                .any(|w| w.text == candidate.text && w.overlap_ratio(&candidate) > 0.5);
+            if !duplicated {
+                words.push(candidate);
+            }
+        }
+    }
+
+    // 上から順に並べ、縦方向の中心が近い単語を同じ行にまとめる
+    words.sort_by(|a, b| (a.y + a.height / 2.0).partial_cmp(&(b.y + b.height / 2.0)).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut lines: Vec<Vec<TileWord>> = Vec::new();
+    for word in words {
+        let center = word.y + word.height / 2.0;
+        let same_line = lines
+            .last()
+            .and_then(|line| line.last())
+            .map(|last| (center - (last.y + last.height / 2.0)).abs() < last.height.max(word.height) / 2.0)
+            .unwrap_or(false);
+        if same_line {
+            lines.last_mut().unwrap().push(word);
+        } else {
+            lines.push(vec![word]);
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|mut line| {
+            line.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap_or(std::cmp::Ordering::Equal));
+            line.into_iter().map(|w| w.text).collect::<Vec<_>>().join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 矩形 { x, y, width, height } を画像範囲内に収めて取り出す
+fn read_rect(value: &JsValue, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let x = get_number(value, "x").max(0.0) as u32;
+    let y = get_number(value, "y").max(0.0) as u32;
+    if x >= width || y >= height {
+        return None;
+    }
+    let w = (get_number(value, "width").max(0.0) as u32).min(width - x);
+    let h = (get_number(value, "height").max(0.0) as u32).min(height - y);
+    if w == 0 || h == 0 {
+        return None;
+    }
+    Some((x, y, w, h))
+}
+
+fn blur_rects(img: &mut DynamicImage, rects: &[(u32, u32, u32, u32)], sigma: f32) {
+    for &(x, y, w, h) in rects {
+        // sigma未指定時は領域サイズに応じて十分に判別不能な強さにする
+        let sigma = if sigma > 0.0 { sigma } else { (w.max(h) as f32 / 8.0).max(4.0) };
+        let blurred = img.crop_imm(x, y, w, h).blur(sigma);
+        image::imageops::overlay(img, &blurred, x as i64, y as i64);
+    }
+}
+
+// 指定した矩形領域をぼかす（共有・同期前の匿名化用）
+// rects: { x, y, width, height } の配列、sigma <= 0 で自動
+#[wasm_bindgen]
+pub fn blur_regions(base64_image: &str, rects: Array, sigma: f32) -> Result<String, JsValue> {
+    console_error_panic_hook::set_once();
+    log(&format!("Starting blur_regions with {} rects, sigma={}", rects.length(), sigma));
+
+    let mut img = decode_data_url(base64_image)?;
+    let (width, height) = img.dimensions();
+    let rects: Vec<_> = rects.iter().filter_map(|r| read_rect(&r, width, height)).collect();
+
+    blur_rects(&mut img, &rects, sigma);
+
+    log("Region blur completed successfully");
+    encode_jpeg_data_url(&img, 92)
+}
+
+#[cfg(feature = "face-detect")]
+fn detect_face_rects(img: &DynamicImage, model: &[u8]) -> Result<Vec<(u32, u32, u32, u32, f64)>, JsValue> {
+    let model = rustface::read_model(Cursor::new(model)).map_err(|e| {
+        log(&format!("Face model load error: {}", e));
+        JsValue::from_str(&format!("Face model load error: {}", e))
+    })?;
+    let mut detector = rustface::create_detector_with_model(model);
+    detector.set_min_face_size(20);
+    detector.set_score_thresh(2.0);
+    detector.set_pyramid_scale_factor(0.8);
+    detector.set_slide_window_step(4, 4);
+
+    let gray = img.to_luma8();
+    let (width, height) = gray.dimensions();
+    let mut image_data = rustface::ImageData::new(&gray, width, height);
+    let faces = detector.detect(&mut image_data);
+
+    Ok(faces
+        .iter()
+        .filter_map(|face| {
+            let bbox = face.bbox();
+            let x = bbox.x().max(0) as u32;
+            let y = bbox.y().max(0) as u32;
+            if x >= width || y >= height {
+                return None;
+            }
+            let w = bbox.width().min(width - x);
+            let h = bbox.height().min(height - y);
+            Some((x, y, w, h, face.score()))
+        })
+        .collect())
+}
+
+// 顔検出（SeetaFaceモデルのバイト列をJSから渡す）
+// 戻り値: { x, y, width, height, score } の配列
+#[cfg(feature = "face-detect")]
+#[wasm_bindgen]
+pub fn detect_faces(base64_image: &str, model: &[u8]) -> Result<Array, JsValue> {
+    console_error_panic_hook::set_once();
+    log("Starting detect_faces");
+
+    let img = decode_data_url(base64_image)?;
+    let result = Array::new();
+    for (x, y, w, h, score) in detect_face_rects(&img, model)? {
+        let obj = Object::new();
+        set_field(&obj, "x", &JsValue::from(x))?;
+        set_field(&obj, "y", &JsValue::from(y))?;
+        set_field(&obj, "width", &JsValue::from(w))?;
+        set_field(&obj, "height", &JsValue::from(h))?;
+        set_field(&obj, "score", &JsValue::from_f64(score))?;
+        result.push(&obj);
+    }
+
+    log(&format!("Detected {} faces", result.length()));
+    Ok(result)
+}
+
+// ワンクリック匿名化: 顔を検出してすべてぼかす
+#[cfg(feature = "face-detect")]
+#[wasm_bindgen]
+pub fn anonymize_faces(base64_image: &str, model: &[u8], sigma: f32) -> Result<String, JsValue> {
+    console_error_panic_hook::set_once();
+    log("Starting anonymize_faces");
+
+    let mut img = decode_data_url(base64_image)?;
+    // 髪や輪郭まで隠れるよう検出枠を少し広げる
+    let (width, height) = img.dimensions();
+    let rects: Vec<_> = detect_face_rects(&img, model)?
+        .into_iter()
+        .map(|(x, y, w, h, _)| {
+            let pad_x = w / 5;
+            let pad_y = h / 4;
+            let nx = x.saturating_sub(pad_x);
+            let ny = y.saturating_sub(pad_y);
+            (nx, ny, (w + pad_x * 2).min(width - nx), (h + pad_y * 2).min(height - ny))
+        })
+        .collect();
+
+    log(&format!("Blurring {} faces", rects.len()));
+    blur_rects(&mut img, &rects, sigma);
+    encode_jpeg_data_url(&img, 92)
+}
+
+// 明るさ・コントラスト・ガンマ用のルックアップテーブルを作成
+// brightness, contrast: -1.0〜1.0（0で変化なし）、gamma: 1.0で変化なし
+fn build_tone_lut(brightness: f32, contrast: f32, gamma: f32) -> [u8; 256] {
+    let brightness = brightness.clamp(-1.0, 1.0) * 255.0;
+    let contrast = contrast.clamp(-1.0, 1.0);
+    // -1で灰一色、+1で約5倍の傾きになるように変換
+    let contrast_factor = if contrast >= 0.0 { 1.0 + contrast * 4.0 } else { 1.0 + contrast };
+    let gamma = if gamma > 0.0 { gamma } else { 1.0 };
+
+    let mut lut = [0u8; 256];
+    for (i, value) in lut.iter_mut().enumerate() {
+        let gamma_corrected = 255.0 * (i as f32 / 255.0).powf(1.0 / gamma);
+        let adjusted = (gamma_corrected - 128.0) * contrast_factor + 128.0 + brightness;
+        *value = adjusted.clamp(0.0, 255.0).round() as u8;
+    }
+    lut
+}
+
+fn apply_adjustments(img: &DynamicImage, brightness: f32, contrast: f32, gamma: f32, saturation: f32) -> DynamicImage {
+    let lut = build_tone_lut(brightness, contrast, gamma);
+    // -1でグレースケール、+1で彩度2倍
+    let saturation = 1.0 + saturation.clamp(-1.0, 1.0);
+
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let (r, g, b) = (lut[r as usize] as f32, lut[g as usize] as f32, lut[b as usize] as f32);
+        let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+        let mix = |c: f32| (luma + (c - luma) * saturation).clamp(0.0, 255.0).round() as u8;
+        pixel.0 = [mix(r), mix(g), mix(b), a];
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+// 編集画面のスライダー用の手動補正
+// プレビューと保存で同じ処理を通すことで、見た目と保存結果を一致させる
+#[wasm_bindgen]
+pub fn adjust_image(
+    base64_image: &str,
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+    saturation: f32,
+    quality: f32,
+) -> Result<String, JsValue> {
+    console_error_panic_hook::set_once();
+    log(&format!(
+        "Starting adjust_image with brightness={}, contrast={}, gamma={}, saturation={}, quality={}",
+        brightness, contrast, gamma, saturation, quality
+    ));
+
+    let img = decode_data_url(base64_image)?;
+    let adjusted = apply_adjustments(&img, brightness, contrast, gamma, saturation);
+
+    log("Image adjustment completed successfully");
+    // quality は batch_resize_images と同じく 0.0〜1.0
+    encode_jpeg_data_url(&adjusted, (quality * 100.0).round().clamp(1.0, 100.0) as u8)
+}